@@ -0,0 +1,460 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+
+use chrono::NaiveDate;
+use ci_monitor_core::data::{Job, Pipeline, Project};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+use schemars::JsonSchema;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::AnalysisLookup;
+
+/// Errors which may occur when parsing a GitHub usage report.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum ActionsUsageError {
+    /// The report has no header.
+    #[error("the usage report is empty")]
+    Empty {},
+    /// A required column is missing.
+    #[error("the usage report has no '{}' column", column)]
+    MissingColumn {
+        /// The column.
+        column: &'static str,
+    },
+    /// A row is invalid.
+    #[error("invalid usage report row on line {}: {}", line, reason)]
+    InvalidRow {
+        /// The line number (starting at 1).
+        line: usize,
+        /// Why the row is invalid.
+        reason: &'static str,
+    },
+}
+
+impl ActionsUsageError {
+    fn missing_column(column: &'static str) -> Self {
+        Self::MissingColumn {
+            column,
+        }
+    }
+
+    fn invalid_row(line: usize, reason: &'static str) -> Self {
+        Self::InvalidRow {
+            line,
+            reason,
+        }
+    }
+}
+
+/// Actions minutes billed for a repository on a day.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct ActionsUsage {
+    /// The repository (`owner/name`).
+    pub repository: String,
+    /// The day of the usage.
+    pub date: NaiveDate,
+    /// The number of minutes billed (before any OS multiplier).
+    pub minutes: f64,
+}
+
+/// Split a CSV line into its fields.
+///
+/// Quoted fields may contain commas and doubled quotes, but not newlines.
+fn csv_fields(line: &str) -> Option<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            },
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+
+    if quoted {
+        None
+    } else {
+        fields.push(field);
+        Some(fields)
+    }
+}
+
+/// Normalize a column name so that old and new report formats may be matched.
+fn column_key(name: &str) -> String {
+    name.trim().to_ascii_lowercase().replace([' ', '-'], "_")
+}
+
+/// Parse the Actions minutes from a GitHub billing usage report (CSV).
+///
+/// Both the legacy (`Date,Product,...,Repository Slug,...`) and enhanced billing
+/// (`date,product,...,repository,...`) exports are supported. Rows for other products (e.g.,
+/// Packages) or units (e.g., storage) are ignored.
+pub fn parse_actions_usage(report: &str) -> Result<Vec<ActionsUsage>, ActionsUsageError> {
+    let mut lines = report
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+    let (_, header) = lines.next().ok_or(ActionsUsageError::Empty {})?;
+    let columns = csv_fields(header.trim_start_matches('\u{feff}'))
+        .ok_or_else(|| ActionsUsageError::invalid_row(1, "unterminated quote"))?
+        .iter()
+        .map(|name| column_key(name))
+        .collect::<Vec<_>>();
+    let column = |names: &[&'static str]| {
+        names
+            .iter()
+            .find_map(|name| columns.iter().position(|column| column == name))
+            .ok_or_else(|| ActionsUsageError::missing_column(names[0]))
+    };
+    let date_col = column(&["date"])?;
+    let product_col = column(&["product"])?;
+    let quantity_col = column(&["quantity"])?;
+    let unit_col = column(&["unit_type"])?;
+    let repository_col = column(&["repository", "repository_slug"])?;
+
+    let mut usage = Vec::new();
+    for (idx, line) in lines {
+        let line_no = idx + 1;
+        let fields = csv_fields(line)
+            .ok_or_else(|| ActionsUsageError::invalid_row(line_no, "unterminated quote"))?;
+        let field = |col: usize| {
+            fields
+                .get(col)
+                .map(|field| field.trim())
+                .ok_or_else(|| ActionsUsageError::invalid_row(line_no, "missing fields"))
+        };
+
+        if !field(product_col)?.eq_ignore_ascii_case("actions") {
+            continue;
+        }
+        let unit = field(unit_col)?.to_ascii_lowercase();
+        if !unit.starts_with("minute") {
+            continue;
+        }
+        let date = field(date_col)?;
+        let date = date
+            .get(..10)
+            .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+            .ok_or_else(|| ActionsUsageError::invalid_row(line_no, "invalid date"))?;
+        let minutes = field(quantity_col)?
+            .parse()
+            .map_err(|_| ActionsUsageError::invalid_row(line_no, "invalid quantity"))?;
+        let repository = field(repository_col)?;
+        if repository.is_empty() {
+            continue;
+        }
+
+        usage.push(ActionsUsage {
+            repository: repository.into(),
+            date,
+            minutes,
+        });
+    }
+
+    Ok(usage)
+}
+
+/// Billed Actions minutes for a repository compared against job durations in the store.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct UsageReconciliation {
+    /// The repository (`owner/name`).
+    pub repository: String,
+    /// The first day of usage in the report.
+    pub first_day: NaiveDate,
+    /// The last day of usage in the report.
+    pub last_day: NaiveDate,
+    /// The number of minutes billed.
+    pub billed_minutes: f64,
+    /// The number of minutes computed from stored jobs.
+    ///
+    /// Each job is rounded up to the next minute as GitHub does when billing.
+    pub computed_minutes: f64,
+    /// The number of stored jobs which ran during the report's period.
+    pub jobs: usize,
+    /// Whether the repository is in the store.
+    pub known: bool,
+    /// Whether the billed minutes differ from those computed by more than the tolerance.
+    pub discrepancy: bool,
+}
+
+impl UsageReconciliation {
+    /// How many more minutes were billed than computed.
+    pub fn difference(&self) -> f64 {
+        self.billed_minutes - self.computed_minutes
+    }
+}
+
+/// Reconcile billed Actions minutes against the durations of stored jobs.
+///
+/// Repositories are matched against project paths (ignoring case). Jobs are counted if they
+/// started on a day the report covers for the repository. Results are sorted by the magnitude
+/// of the difference, largest first. Repositories are discrepancies if the difference exceeds
+/// `tolerance` as a fraction of the computed minutes.
+pub fn reconcile_actions_usage<L>(
+    storage: &L,
+    usage: &[ActionsUsage],
+    tolerance: f64,
+) -> Vec<UsageReconciliation>
+where
+    L: AnalysisLookup<L>,
+{
+    let mut reconciliations: BTreeMap<String, UsageReconciliation> = BTreeMap::new();
+    for entry in usage {
+        let reconciliation = reconciliations
+            .entry(entry.repository.to_ascii_lowercase())
+            .or_insert_with(|| {
+                UsageReconciliation {
+                    repository: entry.repository.clone(),
+                    first_day: entry.date,
+                    last_day: entry.date,
+                    billed_minutes: 0.,
+                    computed_minutes: 0.,
+                    jobs: 0,
+                    known: false,
+                    discrepancy: false,
+                }
+            });
+        reconciliation.first_day = reconciliation.first_day.min(entry.date);
+        reconciliation.last_day = reconciliation.last_day.max(entry.date);
+        reconciliation.billed_minutes += entry.minutes;
+    }
+
+    let projects = <L as DiscoverableLookup<Project<L>>>::all_indices(storage);
+    for idx in &projects {
+        let project = if let Some(project) = <L as Lookup<Project<L>>>::lookup(storage, idx) {
+            project
+        } else {
+            continue;
+        };
+        if let Some(reconciliation) =
+            reconciliations.get_mut(&project.instance_path.to_ascii_lowercase())
+        {
+            reconciliation.known = true;
+        }
+    }
+
+    let jobs = <L as DiscoverableLookup<Job<L>>>::all_indices(storage);
+    for idx in &jobs {
+        let job = if let Some(job) = <L as Lookup<Job<L>>>::lookup(storage, idx) {
+            job
+        } else {
+            continue;
+        };
+        let (started_at, finished_at) =
+            if let (Some(started_at), Some(finished_at)) = (job.started_at, job.finished_at) {
+                (started_at, finished_at)
+            } else {
+                continue;
+            };
+        let reconciliation = <L as Lookup<Pipeline<L>>>::lookup(storage, &job.pipeline)
+            .and_then(|pipeline| <L as Lookup<Project<L>>>::lookup(storage, &pipeline.project))
+            .and_then(|project| {
+                reconciliations.get_mut(&project.instance_path.to_ascii_lowercase())
+            });
+        let reconciliation = if let Some(reconciliation) = reconciliation {
+            reconciliation
+        } else {
+            continue;
+        };
+        let day = started_at.date_naive();
+        if day < reconciliation.first_day || reconciliation.last_day < day {
+            continue;
+        }
+
+        let seconds = (finished_at - started_at).num_seconds().max(0);
+        reconciliation.jobs += 1;
+        reconciliation.computed_minutes += ((seconds + 59) / 60) as f64;
+    }
+
+    let mut reconciliations = reconciliations
+        .into_values()
+        .map(|mut reconciliation| {
+            let expected = reconciliation.computed_minutes.max(1.);
+            reconciliation.discrepancy = reconciliation.difference().abs() / expected > tolerance;
+            reconciliation
+        })
+        .collect::<Vec<_>>();
+    reconciliations.sort_by(|lhs, rhs| rhs.difference().abs().total_cmp(&lhs.difference().abs()));
+    reconciliations
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+    use ci_monitor_core::data::{Instance, Job, JobState, PipelineStatus};
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test;
+    use crate::{ActionsUsage, ActionsUsageError};
+
+    const LEGACY: &str = "\
+Date,Product,SKU,Quantity,Unit Type,Price Per Unit ($),Multiplier,\
+Owner,Repository Slug,Actions Workflow,Notes
+2024-01-01,Actions,Compute - UBUNTU,12,minute,0.008,1.0,org,org/repo,.github/workflows/ci.yml,
+2024-01-02,Actions,Compute - WINDOWS,3,minute,0.016,2.0,org,org/repo,\".github/workflows/a,b.yml\",
+2024-01-02,Shared Storage,Shared Storage,0.5,gigabyte-hours,0.008,1.0,org,org/repo,,
+2024-01-02,Packages,Data Transfer Out,1,gigabyte,0.5,1.0,org,org/other,,
+";
+
+    const ENHANCED: &str = "\
+date,product,sku,quantity,unit_type,applied_cost_per_quantity,gross_amount,discount_amount,\
+net_amount,username,organization,repository,workflow_path,cost_center_name
+2024-01-01T00:00:00Z,actions,actions_linux,4,minutes,0.008,0.032,0,0.032,user,org,org/repo,\
+.github/workflows/ci.yml,
+";
+
+    fn day(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 1, day).unwrap()
+    }
+
+    fn at(day: u32, minute: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, day, 0, 0, 0).unwrap() + Duration::minutes(minute)
+    }
+
+    #[test]
+    fn csv_fields() {
+        assert_eq!(
+            super::csv_fields("a,\"b,c\",\"d \"\"e\"\"\",").unwrap(),
+            ["a", "b,c", "d \"e\"", ""],
+        );
+        assert_eq!(super::csv_fields("\"a"), None);
+    }
+
+    #[test]
+    fn parse_actions_usage_legacy() {
+        let usage = super::parse_actions_usage(LEGACY).unwrap();
+
+        assert_eq!(usage.len(), 2);
+        assert_eq!(usage[0].repository, "org/repo");
+        assert_eq!(usage[0].date, day(1));
+        assert_eq!(usage[0].minutes, 12.);
+        assert_eq!(usage[1].date, day(2));
+        assert_eq!(usage[1].minutes, 3.);
+    }
+
+    #[test]
+    fn parse_actions_usage_enhanced() {
+        let usage = super::parse_actions_usage(ENHANCED).unwrap();
+
+        assert_eq!(usage.len(), 1);
+        assert_eq!(usage[0].repository, "org/repo");
+        assert_eq!(usage[0].date, day(1));
+        assert_eq!(usage[0].minutes, 4.);
+    }
+
+    #[test]
+    fn parse_actions_usage_errors() {
+        let err = super::parse_actions_usage("").unwrap_err();
+        assert!(matches!(err, ActionsUsageError::Empty {}));
+
+        let err = super::parse_actions_usage("date,product,quantity,unit_type\n").unwrap_err();
+        if let ActionsUsageError::MissingColumn {
+            column,
+        } = err
+        {
+            assert_eq!(column, "repository");
+        } else {
+            panic!("unexpected error: {:?}", err);
+        }
+
+        let report = "date,product,quantity,unit_type,repository\nyesterday,actions,1,minute,r\n";
+        let err = super::parse_actions_usage(report).unwrap_err();
+        if let ActionsUsageError::InvalidRow {
+            line,
+            reason,
+        } = err
+        {
+            assert_eq!(line, 2);
+            assert_eq!(reason, "invalid date");
+        } else {
+            panic!("unexpected error: {:?}", err);
+        }
+    }
+
+    #[test]
+    fn reconcile_actions_usage() {
+        let mut lookup = VecLookup::default();
+        let instance = Instance::builder()
+            .unique_id(0)
+            .forge("github")
+            .url("github.com")
+            .build()
+            .unwrap();
+        let instance = lookup.store(instance);
+        let user = test::user(&mut lookup, instance, 0);
+        let project = test::project(&mut lookup, instance, 1, "Org/Repo");
+        let pipeline = test::pipeline(project, 1, PipelineStatus::Success, at(1, 0));
+        let pipeline = lookup.store(pipeline);
+
+        // (day, start, seconds)
+        let jobs = [(1, 0, 600), (1, 60, 61), (2, 0, 120), (3, 0, 6000)];
+        for (id, (day, start, seconds)) in jobs.into_iter().enumerate() {
+            let job = Job::builder()
+                .user(user)
+                .name("job")
+                .state(JobState::Success)
+                .created_at(at(day, start))
+                .started_at(Some(at(day, start)))
+                .finished_at(Some(at(day, start) + Duration::seconds(seconds)))
+                .forge_id(id as u64)
+                .pipeline(pipeline)
+                .build()
+                .unwrap();
+            lookup.store(job);
+        }
+
+        let usage = [
+            ActionsUsage {
+                repository: "org/repo".into(),
+                date: day(1),
+                minutes: 12.,
+            },
+            ActionsUsage {
+                repository: "org/repo".into(),
+                date: day(2),
+                minutes: 30.,
+            },
+            ActionsUsage {
+                repository: "org/missing".into(),
+                date: day(1),
+                minutes: 5.,
+            },
+        ];
+
+        let reconciliations = super::reconcile_actions_usage(&lookup, &usage, 0.1);
+
+        assert_eq!(reconciliations.len(), 2);
+        assert_eq!(reconciliations[0].repository, "org/repo");
+        assert!(reconciliations[0].known);
+        assert_eq!(reconciliations[0].jobs, 3);
+        assert_eq!(reconciliations[0].billed_minutes, 42.);
+        assert_eq!(reconciliations[0].computed_minutes, 14.);
+        assert_eq!(reconciliations[0].difference(), 28.);
+        assert!(reconciliations[0].discrepancy);
+        assert_eq!(reconciliations[1].repository, "org/missing");
+        assert!(!reconciliations[1].known);
+        assert_eq!(reconciliations[1].jobs, 0);
+    }
+}
@@ -0,0 +1,676 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::{DateTime, Duration, Utc};
+use ci_monitor_core::data::{Elapsed, Job, JobState, Pipeline, PipelineStatus, Project};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+use glob::Pattern;
+use schemars::JsonSchema;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{AnalysisLookup, FailureRate, NotificationRouter, Route, RouteKind};
+
+/// Errors which may occur when declaring alert rules.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum AlertError {
+    /// A pattern is invalid.
+    #[error("invalid pattern '{}': {}", pattern, source)]
+    InvalidPattern {
+        /// The pattern.
+        pattern: String,
+        /// The source of the error.
+        #[source]
+        source: glob::PatternError,
+    },
+    /// A condition cannot be evaluated for the selected entities.
+    #[error(
+        "alert '{}': the `{}` condition does not apply to {}",
+        rule,
+        condition.name(),
+        entity.name()
+    )]
+    UnsupportedCondition {
+        /// The name of the rule.
+        rule: String,
+        /// The selected entities.
+        entity: AlertEntity,
+        /// The condition.
+        condition: AlertCondition,
+    },
+    /// Jobs may only be selected by name for job alerts.
+    #[error("alert '{}': jobs may only be selected for job alerts", rule)]
+    JobsSelector {
+        /// The name of the rule.
+        rule: String,
+    },
+}
+
+impl AlertError {
+    fn invalid_pattern(pattern: String, source: glob::PatternError) -> Self {
+        Self::InvalidPattern {
+            pattern,
+            source,
+        }
+    }
+
+    fn unsupported_condition(rule: String, entity: AlertEntity, condition: AlertCondition) -> Self {
+        Self::UnsupportedCondition {
+            rule,
+            entity,
+            condition,
+        }
+    }
+
+    fn jobs_selector(rule: String) -> Self {
+        Self::JobsSelector {
+            rule,
+        }
+    }
+}
+
+fn pattern(pattern: &str) -> Result<Pattern, AlertError> {
+    Pattern::new(pattern).map_err(|err| AlertError::invalid_pattern(pattern.into(), err))
+}
+
+/// The entities an alert rule is evaluated over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AlertEntity {
+    /// Top-level pipelines.
+    Pipelines,
+    /// Jobs.
+    Jobs,
+}
+
+impl AlertEntity {
+    const ALL: &'static [Self] = &[Self::Pipelines, Self::Jobs];
+
+    /// The name of the entities.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Pipelines => "pipelines",
+            Self::Jobs => "jobs",
+        }
+    }
+
+    /// Look up entities by their name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|entity| entity.name() == name)
+    }
+}
+
+/// A condition measured over the selected entities.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AlertCondition {
+    /// The fraction of finished entities which failed.
+    FailureRate,
+    /// The number of failed entities.
+    Failures,
+    /// The mean time (in seconds) jobs spent queued.
+    QueueTime,
+    /// The mean time (in seconds) entities took to run.
+    Duration,
+}
+
+impl AlertCondition {
+    const ALL: &'static [Self] = &[
+        Self::FailureRate,
+        Self::Failures,
+        Self::QueueTime,
+        Self::Duration,
+    ];
+
+    /// The name of the condition.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::FailureRate => "failure_rate",
+            Self::Failures => "failures",
+            Self::QueueTime => "queue_time",
+            Self::Duration => "duration",
+        }
+    }
+
+    /// Look up a condition by its name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|condition| condition.name() == name)
+    }
+
+    fn applies_to(self, entity: AlertEntity) -> bool {
+        !matches!((self, entity), (Self::QueueTime, AlertEntity::Pipelines))
+    }
+}
+
+/// A rule raising an alert when a condition exceeds a threshold.
+#[derive(Debug, Clone)]
+pub struct AlertRule {
+    name: String,
+    entity: AlertEntity,
+    condition: AlertCondition,
+    threshold: f64,
+    window: Duration,
+    projects: Option<Pattern>,
+    jobs: Option<Pattern>,
+    refs: Option<Pattern>,
+    channel: Option<String>,
+}
+
+impl AlertRule {
+    /// Alert when a condition over entities of each project exceeds a threshold.
+    ///
+    /// By default, entities which finished within the last day are considered and alerts are
+    /// routed to the owners of the project.
+    pub fn new<N>(
+        name: N,
+        entity: AlertEntity,
+        condition: AlertCondition,
+        threshold: f64,
+    ) -> Result<Self, AlertError>
+    where
+        N: Into<String>,
+    {
+        let name = name.into();
+        if !condition.applies_to(entity) {
+            return Err(AlertError::unsupported_condition(name, entity, condition));
+        }
+
+        Ok(Self {
+            name,
+            entity,
+            condition,
+            threshold,
+            window: Duration::days(1),
+            projects: None,
+            jobs: None,
+            refs: None,
+            channel: None,
+        })
+    }
+
+    /// The period of time before the evaluation the condition is measured over.
+    pub fn window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Only consider projects whose path matches a glob pattern.
+    pub fn projects(mut self, projects: &str) -> Result<Self, AlertError> {
+        self.projects = Some(pattern(projects)?);
+        Ok(self)
+    }
+
+    /// Only consider jobs whose name matches a glob pattern.
+    pub fn jobs(mut self, jobs: &str) -> Result<Self, AlertError> {
+        if self.entity != AlertEntity::Jobs {
+            return Err(AlertError::jobs_selector(self.name));
+        }
+        self.jobs = Some(pattern(jobs)?);
+        Ok(self)
+    }
+
+    /// Only consider pipelines (or jobs of pipelines) for refs matching a glob pattern.
+    pub fn refs(mut self, refs: &str) -> Result<Self, AlertError> {
+        self.refs = Some(pattern(refs)?);
+        Ok(self)
+    }
+
+    /// Deliver alerts to a channel rather than to the owners of the project.
+    pub fn channel<C>(mut self, channel: C) -> Self
+    where
+        C: Into<String>,
+    {
+        self.channel = Some(channel.into());
+        self
+    }
+
+    /// The name of the rule.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn selects(&self, project: &str, refname: Option<&str>) -> bool {
+        self.projects
+            .as_ref()
+            .is_none_or(|projects| projects.matches(project))
+            && self
+                .refs
+                .as_ref()
+                .is_none_or(|refs| refname.is_some_and(|refname| refs.matches(refname)))
+    }
+}
+
+/// Measurements of the entities selected by a rule within a project.
+#[derive(Debug, Default)]
+struct Samples<'a> {
+    failures: FailureRate,
+    durations: Vec<f64>,
+    queue_times: Vec<f64>,
+    jobs: BTreeSet<&'a str>,
+}
+
+impl Samples<'_> {
+    fn value(&self, condition: AlertCondition) -> Option<f64> {
+        let mean = |values: &[f64]| {
+            if values.is_empty() {
+                None
+            } else {
+                Some(values.iter().sum::<f64>() / values.len() as f64)
+            }
+        };
+
+        match condition {
+            AlertCondition::FailureRate => {
+                (self.failures.finished > 0).then(|| self.failures.rate())
+            },
+            AlertCondition::Failures => Some(self.failures.failed as f64),
+            AlertCondition::QueueTime => mean(&self.queue_times),
+            AlertCondition::Duration => mean(&self.durations),
+        }
+    }
+}
+
+/// An alert raised by a rule.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct Alert {
+    /// The name of the rule.
+    pub rule: String,
+    /// The path of the project on its instance.
+    pub project: String,
+    /// The entities the condition was measured over.
+    ///
+    /// One of `pipelines` or `jobs`.
+    #[serde(serialize_with = "crate::serialize::name")]
+    #[schemars(with = "String")]
+    pub entity: AlertEntity,
+    /// The condition.
+    ///
+    /// One of `failure_rate`, `failures`, `queue_time`, or `duration`.
+    #[serde(serialize_with = "crate::serialize::name")]
+    #[schemars(with = "String")]
+    pub condition: AlertCondition,
+    /// The measured value.
+    pub value: f64,
+    /// The threshold the value exceeded.
+    pub threshold: f64,
+    /// The number of finished entities measured.
+    pub samples: usize,
+    /// The start of the measured window.
+    pub since: DateTime<Utc>,
+    /// Where to deliver the alert.
+    pub routes: Vec<Route>,
+}
+
+fn duration_secs(started_at: Option<DateTime<Utc>>, finished_at: DateTime<Utc>) -> Option<f64> {
+    started_at
+        .and_then(|started_at| Elapsed::new(finished_at - started_at))
+        .map(Elapsed::as_seconds)
+}
+
+/// Evaluate alert rules over the store.
+///
+/// Each rule is measured separately for each project over entities which finished within its
+/// window before `now`. Child pipelines and deleted entities are ignored. Jobs which are allowed
+/// to fail do not count as failures and failed quarantined jobs are ignored. Alerts are routed
+/// to the rule's channel if it has one or to the owners of the project (and its measured jobs)
+/// otherwise. Results are sorted by rule (in the given order) and then by project.
+pub fn evaluate_alerts<L>(
+    storage: &L,
+    rules: &[AlertRule],
+    router: &NotificationRouter,
+    now: DateTime<Utc>,
+) -> Vec<Alert>
+where
+    L: AnalysisLookup<L>,
+{
+    // Samples borrow from the entities, so the indices used to look them up must outlive them.
+    let pipelines = if rules
+        .iter()
+        .any(|rule| rule.entity == AlertEntity::Pipelines)
+    {
+        <L as DiscoverableLookup<Pipeline<L>>>::all_indices(storage)
+    } else {
+        Vec::new()
+    };
+    let jobs = if rules.iter().any(|rule| rule.entity == AlertEntity::Jobs) {
+        <L as DiscoverableLookup<Job<L>>>::all_indices(storage)
+    } else {
+        Vec::new()
+    };
+    let mut samples: Vec<BTreeMap<&str, Samples>> = rules.iter().map(|_| BTreeMap::new()).collect();
+
+    for idx in &pipelines {
+        let pipeline = if let Some(pipeline) = <L as Lookup<Pipeline<L>>>::lookup(storage, idx) {
+            pipeline
+        } else {
+            continue;
+        };
+        if !pipeline.status.is_finished()
+            || pipeline.parent_pipeline.is_some()
+            || pipeline.cim_deleted_at.is_some()
+        {
+            continue;
+        }
+        let project =
+            if let Some(project) = <L as Lookup<Project<L>>>::lookup(storage, &pipeline.project) {
+                project
+            } else {
+                continue;
+            };
+        let finished_at = pipeline.finished_at.unwrap_or(pipeline.updated_at);
+
+        for (rule, samples) in rules.iter().zip(samples.iter_mut()) {
+            if rule.entity != AlertEntity::Pipelines
+                || finished_at <= now - rule.window
+                || now < finished_at
+                || !rule.selects(&project.instance_path, pipeline.refname.as_deref())
+            {
+                continue;
+            }
+
+            let samples = samples.entry(&project.instance_path).or_default();
+            samples
+                .failures
+                .add(pipeline.status == PipelineStatus::Failed);
+            samples
+                .durations
+                .extend(duration_secs(pipeline.started_at, finished_at));
+        }
+    }
+
+    for idx in &jobs {
+        let job = if let Some(job) = <L as Lookup<Job<L>>>::lookup(storage, idx) {
+            job
+        } else {
+            continue;
+        };
+        let is_finished = matches!(
+            job.state,
+            JobState::Success | JobState::Failed | JobState::Canceled,
+        );
+        if !is_finished || job.cim_deleted_at.is_some() {
+            continue;
+        }
+        let finished_at = if let Some(finished_at) = job.finished_at {
+            finished_at
+        } else {
+            continue;
+        };
+        let pipeline =
+            if let Some(pipeline) = <L as Lookup<Pipeline<L>>>::lookup(storage, &job.pipeline) {
+                pipeline
+            } else {
+                continue;
+            };
+        let project =
+            if let Some(project) = <L as Lookup<Project<L>>>::lookup(storage, &pipeline.project) {
+                project
+            } else {
+                continue;
+            };
+        if router.quarantine.suppresses(project, job) {
+            continue;
+        }
+
+        for (rule, samples) in rules.iter().zip(samples.iter_mut()) {
+            if rule.entity != AlertEntity::Jobs
+                || finished_at <= now - rule.window
+                || now < finished_at
+                || !rule.selects(&project.instance_path, pipeline.refname.as_deref())
+                || !rule
+                    .jobs
+                    .as_ref()
+                    .is_none_or(|jobs| jobs.matches(&job.name))
+            {
+                continue;
+            }
+
+            let samples = samples.entry(&project.instance_path).or_default();
+            samples
+                .failures
+                .add(job.state == JobState::Failed && !job.allow_failure);
+            samples
+                .durations
+                .extend(duration_secs(job.started_at, finished_at));
+            samples
+                .queue_times
+                .extend(job.queued_duration.map(Elapsed::as_seconds));
+            samples.jobs.insert(&job.name);
+        }
+    }
+
+    let mut alerts = Vec::new();
+    for (rule, samples) in rules.iter().zip(samples) {
+        for (project, samples) in samples {
+            let value = if let Some(value) = samples.value(rule.condition) {
+                value
+            } else {
+                continue;
+            };
+            if value <= rule.threshold {
+                continue;
+            }
+
+            let routes = if let Some(channel) = rule.channel.as_ref() {
+                vec![Route {
+                    owner: None,
+                    channel: channel.clone(),
+                    deliver_at: now,
+                    kind: RouteKind::Immediate,
+                }]
+            } else {
+                let jobs = samples.jobs.iter().copied().collect::<Vec<_>>();
+                router.route(project, &jobs, now)
+            };
+
+            alerts.push(Alert {
+                rule: rule.name.clone(),
+                project: project.into(),
+                entity: rule.entity,
+                condition: rule.condition,
+                value,
+                threshold: rule.threshold,
+                samples: samples.failures.finished,
+                since: now - rule.window,
+                routes,
+            });
+        }
+    }
+
+    alerts
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use ci_monitor_core::data::{
+        Elapsed, Job, JobState, Pipeline, PipelineSource, PipelineStatus, Project,
+    };
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test::{self, at_hour};
+    use crate::{
+        AlertCondition, AlertEntity, AlertRule, NotificationRouter, OwnershipRule, Quarantine,
+        QuarantinedJob, RouteKind,
+    };
+
+    #[test]
+    fn names() {
+        assert_eq!(AlertEntity::from_name("jobs"), Some(AlertEntity::Jobs));
+        assert_eq!(AlertEntity::from_name("runners"), None);
+        assert_eq!(
+            AlertCondition::from_name("failure_rate"),
+            Some(AlertCondition::FailureRate),
+        );
+        assert_eq!(AlertCondition::from_name("failure-rate"), None);
+
+        let err = AlertRule::new(
+            "queue",
+            AlertEntity::Pipelines,
+            AlertCondition::QueueTime,
+            60.,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "alert 'queue': the `queue_time` condition does not apply to pipelines",
+        );
+
+        let err = AlertRule::new(
+            "failures",
+            AlertEntity::Pipelines,
+            AlertCondition::Failures,
+            0.,
+        )
+        .unwrap()
+        .jobs("test")
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "alert 'failures': jobs may only be selected for job alerts",
+        );
+    }
+
+    #[test]
+    fn evaluate_alerts() {
+        let mut lookup = VecLookup::default();
+        let instance = test::instance(&mut lookup);
+        let project = |id, path: &str| {
+            Project::builder()
+                .forge_id(id)
+                .instance(instance)
+                .instance_path(path)
+                .url(format!("project/{}", id))
+                .build()
+                .unwrap()
+        };
+        let app = lookup.store(project(1, "group/app"));
+        let lib = lookup.store(project(2, "group/lib"));
+        let user = test::user(&mut lookup, instance, 0);
+
+        let pipeline = |id, project, refname: &str, status, hour| {
+            Pipeline::builder()
+                .project(project)
+                .sha("0000000000000000000000000000000000000000")
+                .refname(Some(refname.into()))
+                .source(PipelineSource::Push)
+                .status(status)
+                .forge_id(id)
+                .url(format!("pipeline/{}", id))
+                .created_at(at_hour(hour - 1))
+                .updated_at(at_hour(hour))
+                .started_at(Some(at_hour(hour - 1)))
+                .finished_at(Some(at_hour(hour)))
+                .build()
+                .unwrap()
+        };
+        let app_main = lookup.store(pipeline(10, app, "main", PipelineStatus::Failed, 20));
+        lookup.store(pipeline(11, app, "main", PipelineStatus::Success, 21));
+        lookup.store(pipeline(12, app, "topic", PipelineStatus::Failed, 22));
+        // Outside of the window.
+        lookup.store(pipeline(13, app, "main", PipelineStatus::Failed, 1));
+        let lib_main = lookup.store(pipeline(20, lib, "main", PipelineStatus::Success, 20));
+
+        let job = |id, pipeline, name: &str, state, queued| {
+            Job::builder()
+                .user(user)
+                .name(name)
+                .state(state)
+                .created_at(at_hour(19))
+                .started_at(Some(at_hour(19)))
+                .finished_at(Some(at_hour(20)))
+                .queued_duration(Elapsed::from_seconds(queued))
+                .forge_id(id)
+                .pipeline(pipeline)
+                .build()
+                .unwrap()
+        };
+        lookup.store(job(100, app_main, "build", JobState::Success, 30.));
+        lookup.store(job(101, app_main, "test", JobState::Failed, 90.));
+        lookup.store(job(102, app_main, "docs", JobState::Failed, 600.));
+        lookup.store(job(200, lib_main, "build", JobState::Success, 900.));
+
+        let router = NotificationRouter::new("#ci")
+            .rules([OwnershipRule::new("group/*", ["alice"]).unwrap()])
+            .quarantine(
+                Quarantine::default()
+                    .job(QuarantinedJob::new("group/*", "docs", at_hour(0), at_hour(48)).unwrap()),
+            );
+        let rules = [
+            AlertRule::new(
+                "main failures",
+                AlertEntity::Pipelines,
+                AlertCondition::FailureRate,
+                0.25,
+            )
+            .unwrap()
+            .refs("main")
+            .unwrap()
+            .window(Duration::hours(12))
+            .channel("#releases"),
+            AlertRule::new(
+                "slow queue",
+                AlertEntity::Jobs,
+                AlertCondition::QueueTime,
+                45.,
+            )
+            .unwrap(),
+            AlertRule::new(
+                "test failures",
+                AlertEntity::Jobs,
+                AlertCondition::Failures,
+                0.,
+            )
+            .unwrap()
+            .jobs("test*")
+            .unwrap(),
+        ];
+        let alerts = super::evaluate_alerts(&lookup, &rules, &router, at_hour(24));
+
+        assert_eq!(alerts.len(), 4);
+
+        // Only `main` pipelines within the window are measured.
+        let alert = &alerts[0];
+        assert_eq!(alert.rule, "main failures");
+        assert_eq!(alert.project, "group/app");
+        assert_eq!(alert.value, 0.5);
+        assert_eq!(alert.samples, 2);
+        assert_eq!(alert.since, at_hour(12));
+        assert_eq!(alert.routes.len(), 1);
+        assert_eq!(alert.routes[0].channel, "#releases");
+        assert_eq!(alert.routes[0].kind, RouteKind::Immediate);
+
+        // Failed quarantined jobs are ignored.
+        let alert = &alerts[1];
+        assert_eq!(alert.rule, "slow queue");
+        assert_eq!(alert.project, "group/app");
+        assert_eq!(alert.value, 60.);
+        assert_eq!(alert.samples, 2);
+        assert_eq!(alert.routes[0].owner.as_deref(), Some("alice"));
+        let alert = &alerts[2];
+        assert_eq!(alert.rule, "slow queue");
+        assert_eq!(alert.project, "group/lib");
+        assert_eq!(alert.value, 900.);
+
+        let alert = &alerts[3];
+        assert_eq!(alert.rule, "test failures");
+        assert_eq!(alert.project, "group/app");
+        assert_eq!(alert.value, 1.);
+        assert_eq!(alert.condition, AlertCondition::Failures);
+    }
+}
@@ -0,0 +1,172 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use ci_monitor_core::data::{CrawlSession, Instance};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::AnalysisLookup;
+
+/// The period to group API usage by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum UsagePeriod {
+    /// Group by hour.
+    Hour,
+    /// Group by day (in UTC).
+    Day,
+}
+
+impl UsagePeriod {
+    fn start_of(self, hour: DateTime<Utc>) -> DateTime<Utc> {
+        let period = match self {
+            Self::Hour => TimeDelta::hours(1),
+            Self::Day => TimeDelta::days(1),
+        };
+        hour.duration_trunc(period).unwrap_or(hour)
+    }
+}
+
+/// API requests made to an instance during a period.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct ApiUsageBucket {
+    /// The URL of the instance.
+    pub instance: String,
+    /// The start of the period.
+    pub start: DateTime<Utc>,
+    /// The number of requests made.
+    pub requests: u64,
+}
+
+/// Collect the API usage recorded by crawl sessions.
+///
+/// Usage is summed across all crawls within each period. Only periods starting at or after
+/// `since` are reported. Results are sorted by instance URL and then by period.
+pub fn api_usage_history<L>(
+    storage: &L,
+    period: UsagePeriod,
+    since: Option<DateTime<Utc>>,
+) -> Vec<ApiUsageBucket>
+where
+    L: AnalysisLookup<L>,
+{
+    let mut buckets: BTreeMap<(String, DateTime<Utc>), u64> = BTreeMap::new();
+    let sessions = <L as DiscoverableLookup<CrawlSession<L>>>::all_indices(storage);
+    for idx in &sessions {
+        let session = if let Some(session) = <L as Lookup<CrawlSession<L>>>::lookup(storage, idx) {
+            session
+        } else {
+            continue;
+        };
+
+        for usage in &session.api_usage {
+            let start = period.start_of(usage.hour);
+            if since.is_some_and(|since| start < since) {
+                continue;
+            }
+            let instance =
+                if let Some(instance) = <L as Lookup<Instance>>::lookup(storage, &usage.instance) {
+                    instance
+                } else {
+                    continue;
+                };
+
+            *buckets.entry((instance.url.clone(), start)).or_default() += usage.requests;
+        }
+    }
+
+    buckets
+        .into_iter()
+        .map(|((instance, start), requests)| {
+            ApiUsageBucket {
+                instance,
+                start,
+                requests,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use ci_monitor_core::data::{ApiUsage, CrawlSession, Instance};
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test::at_hour;
+    use crate::UsagePeriod;
+
+    fn lookup() -> VecLookup {
+        let mut lookup = VecLookup::default();
+        let instance = |lookup: &mut VecLookup, id, url: &str| {
+            let instance = Instance::builder()
+                .unique_id(id)
+                .forge("gitlab")
+                .url(url)
+                .build()
+                .unwrap();
+            lookup.store(instance)
+        };
+        let first = instance(&mut lookup, 0, "first.example.com");
+        let second = instance(&mut lookup, 1, "second.example.com");
+
+        let sessions = [
+            (1, vec![(first, 0, 10), (first, 1, 20)]),
+            (2, vec![(first, 1, 5), (second, 1, 7)]),
+            (3, vec![(first, 25, 100)]),
+        ];
+        for (revision, usage) in sessions {
+            let api_usage = usage
+                .into_iter()
+                .map(|(instance, hour, requests)| ApiUsage::new(instance, at_hour(hour), requests))
+                .collect();
+            let session = CrawlSession::builder()
+                .started_at(at_hour(0))
+                .api_usage(api_usage)
+                .revision(revision)
+                .build()
+                .unwrap();
+            lookup.store(session);
+        }
+
+        lookup
+    }
+
+    #[test]
+    fn api_usage_history_hourly() {
+        let lookup = lookup();
+
+        let history = super::api_usage_history(&lookup, UsagePeriod::Hour, None);
+
+        assert_eq!(history.len(), 4);
+        assert_eq!(history[0].instance, "first.example.com");
+        assert_eq!(history[0].start, at_hour(0));
+        assert_eq!(history[0].requests, 10);
+        assert_eq!(history[1].start, at_hour(1));
+        assert_eq!(history[1].requests, 25);
+        assert_eq!(history[2].start, at_hour(25));
+        assert_eq!(history[3].instance, "second.example.com");
+        assert_eq!(history[3].requests, 7);
+    }
+
+    #[test]
+    fn api_usage_history_daily() {
+        let lookup = lookup();
+
+        let history = super::api_usage_history(&lookup, UsagePeriod::Day, Some(at_hour(24)));
+
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].instance, "first.example.com");
+        assert_eq!(history[0].start, at_hour(24));
+        assert_eq!(history[0].requests, 100);
+    }
+}
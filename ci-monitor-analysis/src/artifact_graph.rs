@@ -0,0 +1,260 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+
+use ci_monitor_core::data::{Job, Pipeline};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::{AnalysisLookup, EntityGraph, GraphNodeKind};
+
+/// A job within an artifact dependency graph.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct ArtifactGraphJob {
+    /// The ID of the job.
+    pub id: u64,
+    /// The name of the job.
+    pub name: String,
+    /// The stage of the job.
+    pub stage: String,
+}
+
+/// A job consuming the artifacts of another job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct ArtifactEdge {
+    /// The ID of the job producing the artifacts.
+    pub producer: u64,
+    /// The ID of the job consuming the artifacts.
+    pub consumer: u64,
+}
+
+/// The artifact dependencies between jobs of a pipeline.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct ArtifactGraph {
+    /// The ID of the pipeline.
+    pub pipeline: u64,
+    /// The jobs of the pipeline ordered by ID.
+    pub jobs: Vec<ArtifactGraphJob>,
+    /// Artifact consumption between jobs.
+    pub edges: Vec<ArtifactEdge>,
+}
+
+impl ArtifactGraph {
+    /// Build the artifact dependency graph of a pipeline.
+    ///
+    /// Only the latest attempt of each job is included. Returns `None` if the pipeline is not in
+    /// the storage or its artifact dependencies are not known.
+    pub fn pipeline<L>(storage: &L, pipeline: u64) -> Option<Self>
+    where
+        L: AnalysisLookup<L>,
+        <L as Lookup<Pipeline<L>>>::Index: PartialEq,
+    {
+        let idx = <L as DiscoverableLookup<Pipeline<L>>>::find(storage, pipeline)?;
+        let deps = <L as Lookup<Pipeline<L>>>::lookup(storage, &idx)?
+            .artifact_dependencies
+            .as_ref()?;
+
+        let mut latest: BTreeMap<&str, &Job<L>> = BTreeMap::new();
+        let job_indices = <L as DiscoverableLookup<Job<L>>>::all_indices(storage);
+        for job_idx in &job_indices {
+            let job = if let Some(job) = <L as Lookup<Job<L>>>::lookup(storage, job_idx) {
+                job
+            } else {
+                continue;
+            };
+            if job.pipeline != idx || job.cim_deleted_at.is_some() {
+                continue;
+            }
+
+            let entry = latest.entry(job.name.as_str()).or_insert(job);
+            if entry.forge_id < job.forge_id {
+                *entry = job;
+            }
+        }
+
+        let mut edges = Vec::new();
+        for consumer in latest.values() {
+            if let Some(sources) = deps.jobs.get(&consumer.name) {
+                edges.extend(sources.iter().filter_map(|source| {
+                    latest.get(source.as_str()).map(|producer| {
+                        ArtifactEdge {
+                            producer: producer.forge_id,
+                            consumer: consumer.forge_id,
+                        }
+                    })
+                }));
+            } else if let Some(stage) = deps.stage_index(&consumer.stage) {
+                // Jobs consume artifacts from all jobs in earlier stages by default.
+                edges.extend(
+                    latest
+                        .values()
+                        .filter(|producer| {
+                            deps.stage_index(&producer.stage)
+                                .is_some_and(|producer_stage| producer_stage < stage)
+                        })
+                        .map(|producer| {
+                            ArtifactEdge {
+                                producer: producer.forge_id,
+                                consumer: consumer.forge_id,
+                            }
+                        }),
+                );
+            }
+        }
+        edges.sort_by_key(|edge| (edge.producer, edge.consumer));
+
+        let mut jobs = latest
+            .values()
+            .map(|job| {
+                ArtifactGraphJob {
+                    id: job.forge_id,
+                    name: job.name.clone(),
+                    stage: job.stage.clone(),
+                }
+            })
+            .collect::<Vec<_>>();
+        jobs.sort_by_key(|job| job.id);
+
+        Some(Self {
+            pipeline,
+            jobs,
+            edges,
+        })
+    }
+
+    /// The IDs of jobs which consume the artifacts of a job.
+    pub fn consumers(&self, job: u64) -> impl Iterator<Item = u64> + '_ {
+        self.edges
+            .iter()
+            .filter(move |edge| edge.producer == job)
+            .map(|edge| edge.consumer)
+    }
+
+    /// The IDs of jobs whose artifacts a job consumes.
+    pub fn producers(&self, job: u64) -> impl Iterator<Item = u64> + '_ {
+        self.edges
+            .iter()
+            .filter(move |edge| edge.consumer == job)
+            .map(|edge| edge.producer)
+    }
+}
+
+impl From<&ArtifactGraph> for EntityGraph {
+    fn from(graph: &ArtifactGraph) -> Self {
+        let mut entity_graph = Self::default();
+        let ids = graph
+            .jobs
+            .iter()
+            .map(|job| {
+                let label = format!("{}: {}", job.stage, job.name);
+                let (id, _) = entity_graph.add_node(GraphNodeKind::Job, job.id, label);
+                (job.id, id)
+            })
+            .collect::<BTreeMap<_, _>>();
+        for edge in &graph.edges {
+            if let (Some(from), Some(to)) = (ids.get(&edge.producer), ids.get(&edge.consumer)) {
+                entity_graph.add_edge(from, to, "artifacts");
+            }
+        }
+        entity_graph
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use ci_monitor_core::data::{
+        ArtifactDependencies, Job, JobState, Pipeline, PipelineSource, PipelineStatus,
+    };
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test;
+    use crate::{ArtifactGraph, EntityGraph, GraphFormat};
+
+    #[test]
+    fn artifact_graph() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut lookup = VecLookup::default();
+        let instance = test::instance(&mut lookup);
+        let user = test::user(&mut lookup, instance, 0);
+        let project = test::project(&mut lookup, instance, 0, "project");
+
+        let mut deps = ArtifactDependencies::default();
+        deps.stages = vec!["build".into(), "test".into(), "deploy".into()];
+        deps.jobs
+            .insert("test:fast".into(), vec!["build:linux".into()]);
+        deps.jobs.insert("lint".into(), Vec::new());
+        let pipeline = |id, deps| {
+            Pipeline::builder()
+                .project(project)
+                .sha("0000000000000000000000000000000000000000")
+                .source(PipelineSource::Push)
+                .status(PipelineStatus::Success)
+                .forge_id(id)
+                .artifact_dependencies(deps)
+                .url("url")
+                .created_at(now)
+                .updated_at(now)
+                .build()
+                .unwrap()
+        };
+        let pipeline_idx = lookup.store(pipeline(1, Some(deps)));
+        let unknown_idx = lookup.store(pipeline(2, None));
+
+        let job = |id, name: &str, stage: &str, pipeline| {
+            Job::builder()
+                .user(user)
+                .name(name)
+                .stage(stage)
+                .state(JobState::Success)
+                .created_at(now)
+                .forge_id(id)
+                .pipeline(pipeline)
+                .build()
+                .unwrap()
+        };
+        lookup.store(job(10, "build:linux", "build", pipeline_idx));
+        lookup.store(job(11, "build:windows", "build", pipeline_idx));
+        // A retry replaces the original job.
+        lookup.store(job(12, "build:windows", "build", pipeline_idx));
+        lookup.store(job(13, "test:fast", "test", pipeline_idx));
+        lookup.store(job(14, "test:full", "test", pipeline_idx));
+        lookup.store(job(15, "lint", "test", pipeline_idx));
+        lookup.store(job(16, "upload", "deploy", pipeline_idx));
+        lookup.store(job(20, "build:linux", "build", unknown_idx));
+
+        assert_eq!(ArtifactGraph::pipeline(&lookup, 2), None);
+        assert_eq!(ArtifactGraph::pipeline(&lookup, 3), None);
+
+        let graph = ArtifactGraph::pipeline(&lookup, 1).unwrap();
+
+        assert_eq!(graph.pipeline, 1);
+        assert_eq!(
+            graph.jobs.iter().map(|job| job.id).collect::<Vec<_>>(),
+            [10, 12, 13, 14, 15, 16],
+        );
+        assert_eq!(graph.consumers(10).collect::<Vec<_>>(), [13, 14, 16]);
+        assert_eq!(graph.consumers(12).collect::<Vec<_>>(), [14, 16]);
+        assert_eq!(graph.consumers(15).collect::<Vec<_>>(), [16]);
+        assert_eq!(graph.consumers(16).count(), 0);
+        assert_eq!(graph.producers(13).collect::<Vec<_>>(), [10]);
+        assert_eq!(graph.producers(15).count(), 0);
+        assert_eq!(
+            graph.producers(16).collect::<Vec<_>>(),
+            [10, 12, 13, 14, 15],
+        );
+
+        let rendered = EntityGraph::from(&graph).render(GraphFormat::Mermaid);
+        assert!(rendered.contains("    job_10 -->|artifacts| job_13\n"));
+    }
+}
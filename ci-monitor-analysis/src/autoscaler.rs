@@ -0,0 +1,312 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, Utc};
+use ci_monitor_core::data::{AutoscaledInstance, Job, Runner, RunnerHost};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::sections::median;
+use crate::AnalysisLookup;
+
+/// Autoscaler behavior on a runner host along with the queue times of its jobs.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct AutoscalerActivity {
+    /// The name of the host.
+    pub host: String,
+    /// The number of instances created.
+    pub spin_ups: usize,
+    /// The number of instances removed.
+    pub removals: usize,
+    /// The number of instances which still exist.
+    pub active: usize,
+    /// The mean time (in seconds) created instances took to become ready.
+    pub mean_time_to_ready: Option<f64>,
+    /// The mean lifetime (in seconds) of created instances which have been removed.
+    pub mean_lifetime: Option<f64>,
+    /// The number of hours instances existed.
+    pub instance_hours: f64,
+    /// The estimated cost of the instances.
+    ///
+    /// Requires the cost per hour of the host.
+    pub estimated_cost: Option<f64>,
+    /// The number of jobs started on runners of the host.
+    pub jobs: usize,
+    /// The median queue time (in seconds) of the jobs.
+    pub median_queue_time: f64,
+    /// The number of jobs queued while an instance was spinning up.
+    pub jobs_during_spin_up: usize,
+    /// The median queue time (in seconds) of jobs queued while an instance was spinning up.
+    pub median_queue_time_during_spin_up: f64,
+}
+
+fn seconds(duration: Duration) -> f64 {
+    duration.num_milliseconds() as f64 / 1000.
+}
+
+fn mean(values: &[f64]) -> Option<f64> {
+    if values.is_empty() {
+        None
+    } else {
+        Some(values.iter().sum::<f64>() / values.len() as f64)
+    }
+}
+
+fn median_or_zero(values: &mut [f64]) -> f64 {
+    if values.is_empty() {
+        0.
+    } else {
+        median(values)
+    }
+}
+
+fn spinning_up(instances: &[AutoscaledInstance], at: DateTime<Utc>) -> bool {
+    instances.iter().any(|instance| {
+        instance
+            .ready_at
+            .is_some_and(|ready_at| instance.created_at <= at && at < ready_at)
+    })
+}
+
+#[derive(Default)]
+struct Samples {
+    queue_times: Vec<f64>,
+    spin_up_queue_times: Vec<f64>,
+}
+
+/// Summarize autoscaler behavior on runner hosts between `since` and `now`.
+///
+/// Only hosts with recorded autoscaled instances are reported. Instances count as spin-ups or
+/// removals if the event happened within the window while instance hours only count the part of
+/// each lifetime within the window. Jobs are attributed to the host of their runner if they
+/// started within the window with a known queue time. A job was queued during a spin-up if an
+/// instance on the host was being created when it was queued. Results are sorted by instance
+/// hours (most first).
+pub fn autoscaler_activity<L>(
+    storage: &L,
+    since: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Vec<AutoscalerActivity>
+where
+    L: AnalysisLookup<L>,
+{
+    let mut hosts = BTreeMap::new();
+    let host_indices = <L as DiscoverableLookup<RunnerHost>>::all_indices(storage);
+    for idx in &host_indices {
+        let host = if let Some(host) = <L as Lookup<RunnerHost>>::lookup(storage, idx) {
+            host
+        } else {
+            continue;
+        };
+        if !host.autoscaled_instances.is_empty() {
+            hosts.insert(host.name.clone(), host);
+        }
+    }
+
+    let mut samples: BTreeMap<&str, Samples> = BTreeMap::new();
+    for idx in <L as DiscoverableLookup<Job<L>>>::all_indices(storage) {
+        let job = if let Some(job) = <L as Lookup<Job<L>>>::lookup(storage, &idx) {
+            job
+        } else {
+            continue;
+        };
+        let (started_at, queued) =
+            if let (Some(started_at), Some(queued)) = (job.started_at, job.queued_duration) {
+                (started_at, queued)
+            } else {
+                continue;
+            };
+        if job.cim_deleted_at.is_some() || started_at < since || now < started_at {
+            continue;
+        }
+        let host = job
+            .runner
+            .as_ref()
+            .and_then(|runner| <L as Lookup<Runner<L>>>::lookup(storage, runner))
+            .and_then(|runner| runner.runner_host.as_ref())
+            .and_then(|host| <L as Lookup<RunnerHost>>::lookup(storage, host))
+            .and_then(|host| hosts.get(host.name.as_str()));
+        let host = if let Some(host) = host {
+            host
+        } else {
+            continue;
+        };
+
+        let entry = samples.entry(&host.name).or_default();
+        entry.queue_times.push(queued.as_seconds());
+        let queued_at = started_at - queued.duration();
+        if spinning_up(&host.autoscaled_instances, queued_at) {
+            entry.spin_up_queue_times.push(queued.as_seconds());
+        }
+    }
+
+    let in_window = |at: DateTime<Utc>| since <= at && at <= now;
+    let mut activities = hosts
+        .values()
+        .map(|host| {
+            let instances = &host.autoscaled_instances;
+            let created = instances
+                .iter()
+                .filter(|instance| in_window(instance.created_at))
+                .collect::<Vec<_>>();
+            let times_to_ready = created
+                .iter()
+                .filter_map(|instance| {
+                    instance
+                        .ready_at
+                        .map(|ready_at| seconds(ready_at - instance.created_at))
+                })
+                .collect::<Vec<_>>();
+            let lifetimes = created
+                .iter()
+                .filter(|instance| instance.removed_at.is_some_and(|at| at <= now))
+                .map(|instance| seconds(instance.lifetime(now)))
+                .collect::<Vec<_>>();
+            let instance_hours = instances
+                .iter()
+                .map(|instance| {
+                    let start = instance.created_at.max(since);
+                    let end = instance.removed_at.unwrap_or(now).min(now);
+                    if start < end {
+                        seconds(end - start) / 3600.
+                    } else {
+                        0.
+                    }
+                })
+                .sum::<f64>();
+            let mut samples = samples.remove(host.name.as_str()).unwrap_or_default();
+
+            AutoscalerActivity {
+                host: host.name.clone(),
+                spin_ups: created.len(),
+                removals: instances
+                    .iter()
+                    .filter(|instance| instance.removed_at.is_some_and(in_window))
+                    .count(),
+                active: instances
+                    .iter()
+                    .filter(|instance| {
+                        instance.created_at <= now && instance.removed_at.is_none_or(|at| now < at)
+                    })
+                    .count(),
+                mean_time_to_ready: mean(&times_to_ready),
+                mean_lifetime: mean(&lifetimes),
+                instance_hours,
+                estimated_cost: host
+                    .estimated_cost_per_hour
+                    .map(|cost_per_hour| cost_per_hour * instance_hours),
+                jobs: samples.queue_times.len(),
+                median_queue_time: median_or_zero(&mut samples.queue_times),
+                jobs_during_spin_up: samples.spin_up_queue_times.len(),
+                median_queue_time_during_spin_up: median_or_zero(&mut samples.spin_up_queue_times),
+            }
+        })
+        .collect::<Vec<_>>();
+    activities.sort_by(|lhs, rhs| rhs.instance_hours.total_cmp(&lhs.instance_hours));
+    activities
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use ci_monitor_core::data::{
+        AutoscalerEventKind, Elapsed, Job, JobState, PipelineStatus, Runner, RunnerHost,
+        RunnerProtectionLevel, RunnerType,
+    };
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test::{self, at};
+
+    #[test]
+    fn autoscaler_activity() {
+        let mut lookup = VecLookup::default();
+        let instance = test::instance(&mut lookup);
+        let user = test::user(&mut lookup, instance, 0);
+        let project = test::project(&mut lookup, instance, 1, "group/project");
+
+        let mut autoscaled = RunnerHost::builder()
+            .name("autoscaled")
+            .unique_id(0)
+            .estimated_cost_per_hour(Some(2.))
+            .build()
+            .unwrap();
+        // (name, event, minute)
+        let events = [
+            ("old", AutoscalerEventKind::Created, -60),
+            ("old", AutoscalerEventKind::Ready, -55),
+            ("old", AutoscalerEventKind::Removed, 30),
+            ("a", AutoscalerEventKind::Created, 0),
+            ("a", AutoscalerEventKind::Ready, 4),
+            ("a", AutoscalerEventKind::Removed, 60),
+            ("b", AutoscalerEventKind::Created, 60),
+            ("b", AutoscalerEventKind::Ready, 66),
+        ];
+        for (name, kind, minute) in events {
+            autoscaled.record_autoscaler_event(name, "docker+machine", kind, at(minute));
+        }
+        let autoscaled = lookup.store(autoscaled);
+        let fixed = RunnerHost::builder()
+            .name("fixed")
+            .unique_id(1)
+            .build()
+            .unwrap();
+        lookup.store(fixed);
+
+        let runner = Runner::builder()
+            .runner_type(RunnerType::Instance)
+            .protection_level(RunnerProtectionLevel::Any)
+            .forge_id(1)
+            .instance(instance)
+            .runner_host(Some(autoscaled))
+            .build()
+            .unwrap();
+        let runner = lookup.store(runner);
+        let pipeline = test::pipeline(project, 1, PipelineStatus::Success, at(0));
+        let pipeline = lookup.store(pipeline);
+
+        // (queued minutes, start)
+        let jobs = [(5, 6), (1, 20), (7, 68), (1, -30)];
+        for (id, (queued, start)) in jobs.into_iter().enumerate() {
+            let job = Job::builder()
+                .user(user)
+                .name("job")
+                .state(JobState::Success)
+                .created_at(at(start - queued))
+                .started_at(Some(at(start)))
+                .finished_at(Some(at(start + 5)))
+                .queued_duration(Elapsed::new(Duration::minutes(queued)))
+                .runner(Some(runner))
+                .forge_id(id as u64)
+                .pipeline(pipeline)
+                .build()
+                .unwrap();
+            lookup.store(job);
+        }
+
+        let activities = super::autoscaler_activity(&lookup, at(0), at(90));
+        assert_eq!(activities.len(), 1);
+        let activity = &activities[0];
+        assert_eq!(activity.host, "autoscaled");
+        assert_eq!(activity.spin_ups, 2);
+        assert_eq!(activity.removals, 2);
+        assert_eq!(activity.active, 1);
+        assert_eq!(activity.mean_time_to_ready, Some(300.));
+        assert_eq!(activity.mean_lifetime, Some(3600.));
+        // 30 minutes of `old`, 60 of `a`, and 30 of `b`.
+        assert_eq!(activity.instance_hours, 2.);
+        assert_eq!(activity.estimated_cost, Some(4.));
+        assert_eq!(activity.jobs, 3);
+        assert_eq!(activity.median_queue_time, 300.);
+        assert_eq!(activity.jobs_during_spin_up, 2);
+        assert_eq!(activity.median_queue_time_during_spin_up, 360.);
+    }
+}
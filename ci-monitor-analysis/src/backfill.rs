@@ -0,0 +1,189 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::cmp::Reverse;
+use std::collections::BTreeSet;
+
+use ci_monitor_core::data::{ArtifactKind, Job, JobArtifact, Pipeline, Project};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::AnalysisLookup;
+
+/// A job whose log has not been stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MissingLog {
+    /// The ID of the project on the forge.
+    pub project: u64,
+    /// The ID of the job on the forge.
+    pub job: u64,
+}
+
+/// How many job logs have been stored.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct LogBackfill {
+    /// The number of jobs which should have a log.
+    pub jobs: usize,
+    /// The number of jobs with a stored log.
+    pub stored: usize,
+    /// Jobs without a stored log, newest first.
+    ///
+    /// Only the number of jobs is serialized.
+    #[serde(serialize_with = "crate::serialize::count")]
+    #[schemars(with = "usize")]
+    pub missing: Vec<MissingLog>,
+}
+
+impl LogBackfill {
+    /// The percentage of jobs with a stored log.
+    pub fn percent_complete(&self) -> f64 {
+        if self.jobs == 0 {
+            100.
+        } else {
+            100. * self.stored as f64 / self.jobs as f64
+        }
+    }
+}
+
+/// Find jobs which are missing a stored log.
+///
+/// Only jobs which have started and finished are expected to have a log. Erased and deleted jobs
+/// are ignored. Since progress is computed from the store, a backfill may be resumed by computing
+/// it again.
+pub fn log_backfill<L>(storage: &L) -> LogBackfill
+where
+    L: AnalysisLookup<L>,
+    <L as Lookup<Job<L>>>::Index: Ord,
+{
+    let artifacts = <L as DiscoverableLookup<JobArtifact<L>>>::all_indices(storage);
+    let stored_logs = artifacts
+        .iter()
+        .filter_map(|idx| <L as Lookup<JobArtifact<L>>>::lookup(storage, idx))
+        .filter(|artifact| artifact.kind == ArtifactKind::JobLog && artifact.state.is_stored())
+        .map(|artifact| &artifact.job)
+        .collect::<BTreeSet<_>>();
+
+    let mut jobs = 0;
+    let mut stored = 0;
+    let mut missing = Vec::new();
+
+    let indices = <L as DiscoverableLookup<Job<L>>>::all_indices(storage);
+    for idx in &indices {
+        let job = if let Some(job) = <L as Lookup<Job<L>>>::lookup(storage, idx) {
+            job
+        } else {
+            continue;
+        };
+        if job.started_at.is_none()
+            || job.finished_at.is_none()
+            || job.erased_at.is_some()
+            || job.cim_deleted_at.is_some()
+        {
+            continue;
+        }
+
+        jobs += 1;
+        if stored_logs.contains(idx) {
+            stored += 1;
+            continue;
+        }
+
+        let project = <L as Lookup<Pipeline<L>>>::lookup(storage, &job.pipeline)
+            .and_then(|pipeline| <L as Lookup<Project<L>>>::lookup(storage, &pipeline.project));
+        if let Some(project) = project {
+            missing.push(MissingLog {
+                project: project.forge_id,
+                job: job.forge_id,
+            });
+        }
+    }
+
+    missing.sort_by_key(|missing| Reverse(missing.job));
+
+    LogBackfill {
+        jobs,
+        stored,
+        missing,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ci_monitor_core::data::{
+        ArtifactKind, ArtifactState, Job, JobArtifact, JobState, PipelineStatus,
+    };
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test::{self, at};
+
+    #[test]
+    fn log_backfill_empty() {
+        let lookup = VecLookup::default();
+        let backfill = super::log_backfill(&lookup);
+
+        assert_eq!(backfill.jobs, 0);
+        assert!(backfill.missing.is_empty());
+        assert_eq!(backfill.percent_complete(), 100.);
+    }
+
+    #[test]
+    fn log_backfill() {
+        let mut lookup = VecLookup::default();
+        let instance = test::instance(&mut lookup);
+        let project = test::project(&mut lookup, instance, 7, "project");
+        let user = test::user(&mut lookup, instance, 0);
+        let pipeline = test::pipeline(project, 0, PipelineStatus::Success, at(0));
+        let pipeline = lookup.store(pipeline);
+
+        let job = |id, finished: bool, erased: bool| {
+            Job::builder()
+                .user(user)
+                .state(JobState::Success)
+                .created_at(at(0))
+                .started_at(Some(at(0)))
+                .finished_at(finished.then(|| at(1)))
+                .erased_at(erased.then(|| at(2)))
+                .forge_id(id)
+                .pipeline(pipeline)
+                .build()
+                .unwrap()
+        };
+        let stored = lookup.store(job(1, true, false));
+        let present = lookup.store(job(2, true, false));
+        lookup.store(job(3, true, false));
+        // Running and erased jobs are not expected to have logs.
+        lookup.store(job(4, false, false));
+        lookup.store(job(5, true, true));
+
+        let log = |unique_id, job, state| {
+            JobArtifact::builder()
+                .state(state)
+                .kind(ArtifactKind::JobLog)
+                .name("job.log")
+                .size(0)
+                .unique_id(unique_id)
+                .job(job)
+                .build()
+                .unwrap()
+        };
+        lookup.store(log(0, stored, ArtifactState::Stored));
+        lookup.store(log(1, present, ArtifactState::Present));
+
+        let backfill = super::log_backfill(&lookup);
+
+        assert_eq!(backfill.jobs, 3);
+        assert_eq!(backfill.stored, 1);
+        assert_eq!(backfill.missing.len(), 2);
+        assert_eq!(backfill.missing[0].project, 7);
+        assert_eq!(backfill.missing[0].job, 3);
+        assert_eq!(backfill.missing[1].job, 2);
+    }
+}
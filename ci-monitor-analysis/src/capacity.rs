@@ -0,0 +1,431 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::iter;
+
+use chrono::{DateTime, Duration, Utc};
+use ci_monitor_core::data::{Elapsed, Instance, Job, Runner};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+
+use crate::concurrency::job_instance;
+use crate::AnalysisLookup;
+
+/// A job to be scheduled in a simulation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SimulatedJob {
+    /// When the job became ready to run.
+    pub ready_at: DateTime<Utc>,
+    /// How long the job takes to run.
+    pub duration: Duration,
+    /// The tags requested by the job.
+    pub tags: BTreeSet<String>,
+}
+
+impl SimulatedJob {
+    /// Create a new job for simulation.
+    pub fn new<I, T>(ready_at: DateTime<Utc>, duration: Duration, tags: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        Self {
+            ready_at,
+            duration,
+            tags: tags.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+/// A runner which may execute jobs in a simulation.
+///
+/// Each runner executes a single job at a time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SimulatedRunner {
+    /// The tags of the runner.
+    pub tags: BTreeSet<String>,
+    /// Whether the runner accepts jobs without tags.
+    pub run_untagged: bool,
+}
+
+impl SimulatedRunner {
+    /// Create a new runner for simulation.
+    pub fn new<I, T>(tags: I, run_untagged: bool) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        Self {
+            tags: tags.into_iter().map(Into::into).collect(),
+            run_untagged,
+        }
+    }
+
+    /// Whether the runner may execute a job.
+    pub fn can_run(&self, job: &SimulatedJob) -> bool {
+        self.accepts_tags(&job.tags)
+    }
+
+    /// Whether the runner may execute a job requesting the given tags.
+    pub(crate) fn accepts_tags(&self, tags: &BTreeSet<String>) -> bool {
+        if tags.is_empty() {
+            self.run_untagged
+        } else {
+            tags.is_subset(&self.tags)
+        }
+    }
+}
+
+/// A set of runners available to execute jobs.
+#[derive(Debug, Clone, Default)]
+pub struct RunnerFleet {
+    runners: Vec<SimulatedRunner>,
+}
+
+impl RunnerFleet {
+    /// Create a fleet from a set of runners.
+    pub fn new<I>(runners: I) -> Self
+    where
+        I: IntoIterator<Item = SimulatedRunner>,
+    {
+        Self {
+            runners: runners.into_iter().collect(),
+        }
+    }
+
+    /// The runners in the fleet.
+    pub fn runners(&self) -> &[SimulatedRunner] {
+        &self.runners
+    }
+
+    /// Add `count` copies of a runner to the fleet.
+    pub fn add(&mut self, count: usize, runner: SimulatedRunner) -> &mut Self {
+        self.runners.extend(iter::repeat_n(runner, count));
+        self
+    }
+
+    /// Remove up to `count` runners which have exactly the given tags.
+    ///
+    /// Returns the number of runners removed.
+    pub fn remove<I, T>(&mut self, count: usize, tags: I) -> usize
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        let tags: BTreeSet<String> = tags.into_iter().map(Into::into).collect();
+        let mut removed = 0;
+        self.runners.retain(|runner| {
+            if removed < count && runner.tags == tags {
+                removed += 1;
+                false
+            } else {
+                true
+            }
+        });
+        removed
+    }
+
+    /// Simulate scheduling jobs on the fleet.
+    ///
+    /// Jobs are assigned in the order they become ready to the eligible runner which becomes
+    /// free first. This approximates first-come, first-served scheduling; the forge's actual
+    /// scheduling depends on when runners poll for work.
+    pub fn simulate<'a, I>(&self, jobs: I) -> SimulationResult
+    where
+        I: IntoIterator<Item = &'a SimulatedJob>,
+    {
+        let mut jobs: Vec<_> = jobs.into_iter().collect();
+        jobs.sort_by_key(|job| job.ready_at);
+
+        let mut free_at: Vec<Option<DateTime<Utc>>> = vec![None; self.runners.len()];
+        let mut queue_times = Vec::with_capacity(jobs.len());
+        let mut unschedulable = 0;
+
+        for job in jobs {
+            let runner = self
+                .runners
+                .iter()
+                .zip(free_at.iter_mut())
+                .filter(|(runner, _)| runner.can_run(job))
+                .min_by_key(|(_, free_at)| **free_at);
+            let free_at = if let Some((_, free_at)) = runner {
+                free_at
+            } else {
+                unschedulable += 1;
+                continue;
+            };
+
+            let start = free_at.map_or(job.ready_at, |free| free.max(job.ready_at));
+            *free_at = Some(start + job.duration);
+            queue_times.push(start - job.ready_at);
+        }
+        queue_times.sort();
+
+        SimulationResult {
+            queue_times,
+            unschedulable,
+        }
+    }
+}
+
+/// The outcome of a scheduling simulation.
+#[derive(Debug, Clone, Default)]
+pub struct SimulationResult {
+    /// The time each scheduled job spent queued (sorted).
+    queue_times: Vec<Duration>,
+    /// The number of jobs which no runner could execute.
+    pub unschedulable: usize,
+}
+
+impl SimulationResult {
+    /// The number of jobs which were scheduled.
+    pub fn scheduled(&self) -> usize {
+        self.queue_times.len()
+    }
+
+    /// The average time jobs spent queued.
+    pub fn mean_queue_time(&self) -> Duration {
+        if self.queue_times.is_empty() {
+            return Duration::zero();
+        }
+
+        let total: Duration = self.queue_times.iter().copied().sum();
+        total / self.queue_times.len() as i32
+    }
+
+    /// The longest time a job spent queued.
+    pub fn max_queue_time(&self) -> Duration {
+        self.queue_times
+            .last()
+            .copied()
+            .unwrap_or_else(Duration::zero)
+    }
+
+    /// The queue time which is not exceeded by `percentile` percent of jobs.
+    ///
+    /// Percentiles are clamped to the range `[0, 100]`.
+    pub fn percentile(&self, percentile: f64) -> Duration {
+        if self.queue_times.is_empty() {
+            return Duration::zero();
+        }
+
+        let fraction = percentile.clamp(0., 100.) / 100.;
+        let rank = (fraction * self.queue_times.len() as f64).ceil() as usize;
+        self.queue_times[rank.saturating_sub(1)]
+    }
+}
+
+/// Collect the historical load of each instance for simulation.
+///
+/// Jobs are considered ready when they were queued, which is derived from their start time and
+/// queued duration. Only jobs with both start and finish times are considered.
+pub fn historical_load<L>(storage: &L) -> BTreeMap<String, Vec<SimulatedJob>>
+where
+    L: AnalysisLookup<L>,
+{
+    let mut load: BTreeMap<String, Vec<_>> = BTreeMap::new();
+
+    for idx in <L as DiscoverableLookup<Job<L>>>::all_indices(storage) {
+        let job = if let Some(job) = <L as Lookup<Job<L>>>::lookup(storage, &idx) {
+            job
+        } else {
+            continue;
+        };
+        if job.cim_deleted_at.is_some() {
+            continue;
+        }
+        let (started_at, finished_at) =
+            if let (Some(start), Some(end)) = (job.started_at, job.finished_at) {
+                (start, end)
+            } else {
+                continue;
+            };
+        let instance = if let Some(instance) = job_instance(storage, job) {
+            instance
+        } else {
+            continue;
+        };
+
+        let queued = job
+            .queued_duration
+            .map_or_else(Duration::zero, Elapsed::duration);
+        load.entry(instance.url.clone())
+            .or_default()
+            .push(SimulatedJob::new(
+                started_at - queued,
+                finished_at - started_at,
+                job.tags.iter().cloned(),
+            ));
+    }
+
+    load
+}
+
+/// Collect the current runner fleet of each instance.
+///
+/// Paused runners are not included.
+pub fn runner_fleets<L>(storage: &L) -> BTreeMap<String, RunnerFleet>
+where
+    L: AnalysisLookup<L>,
+{
+    let mut fleets: BTreeMap<String, RunnerFleet> = BTreeMap::new();
+
+    for idx in <L as DiscoverableLookup<Runner<L>>>::all_indices(storage) {
+        let runner = if let Some(runner) = <L as Lookup<Runner<L>>>::lookup(storage, &idx) {
+            runner
+        } else {
+            continue;
+        };
+        if runner.paused {
+            continue;
+        }
+        let instance =
+            if let Some(instance) = <L as Lookup<Instance>>::lookup(storage, &runner.instance) {
+                instance
+            } else {
+                continue;
+            };
+
+        fleets.entry(instance.url.clone()).or_default().add(
+            1,
+            SimulatedRunner::new(runner.tags.iter().cloned(), runner.run_untagged),
+        );
+    }
+
+    fleets
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use ci_monitor_core::data::{
+        Elapsed, Job, JobState, PipelineStatus, Runner, RunnerProtectionLevel, RunnerType,
+    };
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test::{self, at};
+    use crate::{RunnerFleet, SimulatedJob, SimulatedRunner};
+
+    fn job(ready: i64, minutes: i64, tags: &[&str]) -> SimulatedJob {
+        SimulatedJob::new(at(ready), Duration::minutes(minutes), tags.iter().copied())
+    }
+
+    #[test]
+    fn runner_eligibility() {
+        let runner = SimulatedRunner::new(["linux", "docker"], false);
+
+        assert!(runner.can_run(&job(0, 1, &["linux"])));
+        assert!(runner.can_run(&job(0, 1, &["linux", "docker"])));
+        assert!(!runner.can_run(&job(0, 1, &["linux", "cuda"])));
+        assert!(!runner.can_run(&job(0, 1, &[])));
+        assert!(SimulatedRunner::new(["linux"], true).can_run(&job(0, 1, &[])));
+    }
+
+    #[test]
+    fn queue_times() {
+        let fleet = RunnerFleet::new([SimulatedRunner::new(["linux"], false)]);
+        let jobs = [
+            job(0, 10, &["linux"]),
+            job(0, 10, &["linux"]),
+            job(5, 10, &["linux"]),
+            job(0, 10, &["windows"]),
+        ];
+
+        let result = fleet.simulate(&jobs);
+
+        assert_eq!(result.scheduled(), 3);
+        assert_eq!(result.unschedulable, 1);
+        // Queued for 0, 10, and 15 minutes.
+        assert_eq!(result.mean_queue_time(), Duration::minutes(25) / 3);
+        assert_eq!(result.max_queue_time(), Duration::minutes(15));
+        assert_eq!(result.percentile(50.), Duration::minutes(10));
+        assert_eq!(result.percentile(0.), Duration::zero());
+    }
+
+    #[test]
+    fn fleet_changes() {
+        let linux = SimulatedRunner::new(["linux"], false);
+        let mut fleet = RunnerFleet::new([linux.clone()]);
+        let jobs = [
+            job(0, 10, &["linux"]),
+            job(0, 10, &["linux"]),
+            job(0, 10, &["linux"]),
+        ];
+
+        let baseline = fleet.simulate(&jobs);
+        fleet.add(2, linux);
+        let expanded = fleet.simulate(&jobs);
+
+        assert_eq!(baseline.max_queue_time(), Duration::minutes(20));
+        assert_eq!(expanded.max_queue_time(), Duration::zero());
+
+        assert_eq!(fleet.remove(5, ["linux"]), 3);
+        assert_eq!(fleet.remove(1, ["linux"]), 0);
+        let removed = fleet.simulate(&jobs);
+        assert_eq!(removed.scheduled(), 0);
+        assert_eq!(removed.unschedulable, 3);
+    }
+
+    #[test]
+    fn load_from_storage() {
+        let mut lookup = VecLookup::default();
+        let instance = test::instance(&mut lookup);
+        let user = test::user(&mut lookup, instance, 0);
+        let project = test::project(&mut lookup, instance, 0, "project");
+        let pipeline = test::pipeline(project, 0, PipelineStatus::Success, at(0));
+        let pipeline = lookup.store(pipeline);
+
+        let jobs = [
+            (0, Some(at(5)), Some(at(15)), Elapsed::from_seconds(120.)),
+            (1, Some(at(5)), Some(at(10)), None),
+            // Still running; ignored.
+            (2, Some(at(5)), None, None),
+        ];
+        for (id, started_at, finished_at, queued_duration) in jobs {
+            let job = Job::builder()
+                .user(user)
+                .state(JobState::Success)
+                .created_at(at(0))
+                .started_at(started_at)
+                .finished_at(finished_at)
+                .queued_duration(queued_duration)
+                .forge_id(id)
+                .pipeline(pipeline)
+                .tags(vec!["linux".into()])
+                .build()
+                .unwrap();
+            lookup.store(job);
+        }
+        for (id, paused) in [(0, false), (1, true)] {
+            let runner = Runner::builder()
+                .runner_type(RunnerType::Instance)
+                .protection_level(RunnerProtectionLevel::Any)
+                .tags(vec!["linux".into()])
+                .forge_id(id)
+                .paused(paused)
+                .instance(instance)
+                .build()
+                .unwrap();
+            lookup.store(runner);
+        }
+
+        let load = super::historical_load(&lookup);
+        let fleets = super::runner_fleets(&lookup);
+
+        assert_eq!(
+            load["url"],
+            vec![job(3, 10, &["linux"]), job(5, 5, &["linux"]),],
+        );
+        assert_eq!(fleets["url"].runners().len(), 1);
+
+        let result = fleets["url"].simulate(&load["url"]);
+        assert_eq!(result.max_queue_time(), Duration::minutes(8));
+    }
+}
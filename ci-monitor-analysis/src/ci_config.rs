@@ -0,0 +1,454 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+
+use chrono::{DateTime, Duration, Utc};
+use ci_monitor_core::data::{BlobReference, Pipeline, PipelineStatus, Project};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::{BlobPersistence, BlobPersistenceError, DiscoverableLookup};
+
+use crate::AnalysisLookup;
+
+/// The number of unchanged lines to show around changes in a diff.
+const DIFF_CONTEXT: usize = 3;
+
+/// Pipeline statistics over a set of runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PipelineRunStats {
+    /// The number of pipelines considered.
+    pub count: usize,
+    /// The number of failed pipelines.
+    pub failed: usize,
+    /// The mean duration of the pipelines.
+    pub mean_duration: Duration,
+}
+
+impl PipelineRunStats {
+    fn new(runs: &[PipelineRun]) -> Self {
+        let count = runs.len();
+        let failed = runs.iter().filter(|run| run.failed).count();
+        let total = runs
+            .iter()
+            .fold(Duration::zero(), |total, run| total + run.duration);
+        let mean_duration = if count == 0 {
+            Duration::zero()
+        } else {
+            total / count as i32
+        };
+
+        Self {
+            count,
+            failed,
+            mean_duration,
+        }
+    }
+
+    /// The fraction of pipelines which failed.
+    pub fn failure_rate(&self) -> f64 {
+        if self.count == 0 {
+            0.
+        } else {
+            self.failed as f64 / self.count as f64
+        }
+    }
+}
+
+/// A change in the CI configuration used by pipelines on a ref.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct ConfigChange {
+    /// The URL of the project.
+    pub project: String,
+    /// The ref the pipelines built.
+    pub refname: String,
+    /// The commit which introduced the new configuration.
+    pub sha: String,
+    /// The URL of the first pipeline using the new configuration.
+    pub pipeline: String,
+    /// When the first pipeline using the new configuration was created.
+    pub created_at: DateTime<Utc>,
+    /// The configuration before the change.
+    pub before: BlobReference,
+    /// The configuration after the change.
+    pub after: BlobReference,
+    /// Statistics for pipelines using the previous configuration.
+    pub stats_before: PipelineRunStats,
+    /// Statistics for pipelines using the new configuration.
+    pub stats_after: PipelineRunStats,
+}
+
+impl ConfigChange {
+    /// The ratio of mean pipeline durations after and before the change.
+    pub fn duration_ratio(&self) -> Option<f64> {
+        let before = self.stats_before.mean_duration.num_milliseconds();
+        if before == 0 {
+            None
+        } else {
+            Some(self.stats_after.mean_duration.num_milliseconds() as f64 / before as f64)
+        }
+    }
+
+    /// Whether the change made pipelines slower or less reliable.
+    ///
+    /// A regression is a mean duration increase by more than `duration_threshold` (e.g., `1.2` for
+    /// 20% slower) or an increase in the failure rate by more than `failure_threshold` (e.g.,
+    /// `0.1` for 10 percentage points).
+    pub fn is_regression(&self, duration_threshold: f64, failure_threshold: f64) -> bool {
+        let slower = self
+            .duration_ratio()
+            .is_some_and(|ratio| ratio > duration_threshold);
+        let flakier =
+            self.stats_after.failure_rate() - self.stats_before.failure_rate() > failure_threshold;
+
+        slower || flakier
+    }
+
+    /// Compute the difference between the configurations.
+    pub fn diff(&self, blobs: &dyn BlobPersistence) -> Result<ConfigDiff, BlobPersistenceError> {
+        let before = blobs.fetch(&self.before)?;
+        let after = blobs.fetch(&self.after)?;
+
+        Ok(ConfigDiff::new(
+            &String::from_utf8_lossy(&before),
+            &String::from_utf8_lossy(&after),
+        ))
+    }
+
+    /// Render a report section describing the change.
+    pub fn report(&self, blobs: &dyn BlobPersistence) -> Result<String, BlobPersistenceError> {
+        let diff = self.diff(blobs)?;
+        let mut report = String::new();
+
+        // Writing to a `String` cannot fail.
+        let _ = writeln!(report, "## {} ({})", self.project, self.refname);
+        let _ = writeln!(report);
+        let _ = writeln!(
+            report,
+            "Configuration changed in {} ({}) on {}.",
+            self.sha, self.pipeline, self.created_at,
+        );
+        let _ = writeln!(report);
+        let _ = writeln!(
+            report,
+            "- duration: {}s -> {}s",
+            self.stats_before.mean_duration.num_seconds(),
+            self.stats_after.mean_duration.num_seconds(),
+        );
+        let _ = writeln!(
+            report,
+            "- failure rate: {:.0}% -> {:.0}%",
+            self.stats_before.failure_rate() * 100.,
+            self.stats_after.failure_rate() * 100.,
+        );
+        let _ = writeln!(report);
+        let _ = writeln!(report, "```diff");
+        let _ = write!(report, "{}", diff);
+        let _ = writeln!(report, "```");
+
+        Ok(report)
+    }
+}
+
+/// A line in a configuration diff.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine {
+    /// A line present in both configurations.
+    Context(String),
+    /// A line only in the previous configuration.
+    Removed(String),
+    /// A line only in the new configuration.
+    Added(String),
+    /// Unchanged lines which have been elided.
+    Elided,
+}
+
+/// A line-based difference between two configurations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigDiff {
+    lines: Vec<DiffLine>,
+}
+
+impl ConfigDiff {
+    /// Compute the difference between two texts.
+    ///
+    /// Unchanged lines far from any change are elided.
+    pub fn new(before: &str, after: &str) -> Self {
+        let old: Vec<&str> = before.lines().collect();
+        let new: Vec<&str> = after.lines().collect();
+
+        // Longest common subsequence table of the suffixes.
+        let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+        for i in (0..old.len()).rev() {
+            for j in (0..new.len()).rev() {
+                lcs[i][j] = if old[i] == new[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+
+        let mut full = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < old.len() || j < new.len() {
+            if i < old.len() && j < new.len() && old[i] == new[j] {
+                full.push(DiffLine::Context(old[i].into()));
+                i += 1;
+                j += 1;
+            } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+                full.push(DiffLine::Removed(old[i].into()));
+                i += 1;
+            } else {
+                full.push(DiffLine::Added(new[j].into()));
+                j += 1;
+            }
+        }
+
+        let changed: Vec<usize> = full
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| !matches!(line, DiffLine::Context(_)))
+            .map(|(idx, _)| idx)
+            .collect();
+        let mut lines = Vec::new();
+        for (idx, line) in full.into_iter().enumerate() {
+            let near_change = changed.iter().any(|&c| c.abs_diff(idx) <= DIFF_CONTEXT);
+            if near_change {
+                lines.push(line);
+            } else if lines.last() != Some(&DiffLine::Elided) {
+                lines.push(DiffLine::Elided);
+            }
+        }
+
+        Self {
+            lines,
+        }
+    }
+
+    /// The lines of the diff.
+    pub fn lines(&self) -> &[DiffLine] {
+        &self.lines
+    }
+
+    /// Whether the configurations are identical.
+    pub fn is_empty(&self) -> bool {
+        self.lines
+            .iter()
+            .all(|line| matches!(line, DiffLine::Context(_) | DiffLine::Elided))
+    }
+}
+
+impl fmt::Display for ConfigDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for line in &self.lines {
+            match line {
+                DiffLine::Context(line) => writeln!(f, " {}", line)?,
+                DiffLine::Removed(line) => writeln!(f, "-{}", line)?,
+                DiffLine::Added(line) => writeln!(f, "+{}", line)?,
+                DiffLine::Elided => writeln!(f, "@@")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+struct PipelineRun {
+    sha: String,
+    url: String,
+    created_at: DateTime<Utc>,
+    config: BlobReference,
+    duration: Duration,
+    failed: bool,
+}
+
+/// Find changes to the CI configuration and their effect on pipelines.
+///
+/// Finished pipelines with a captured configuration are grouped by project and ref. Whenever the
+/// configuration differs from the previous pipeline, up to `window` pipelines on either side of
+/// the change (using the respective configuration) are compared. Results are ordered by project,
+/// ref, and time.
+pub fn ci_config_changes<L>(storage: &L, window: usize) -> Vec<ConfigChange>
+where
+    L: AnalysisLookup<L>,
+{
+    let mut runs: BTreeMap<(String, String), Vec<PipelineRun>> = BTreeMap::new();
+
+    for idx in <L as DiscoverableLookup<Pipeline<L>>>::all_indices(storage) {
+        let pipeline = if let Some(pipeline) = <L as Lookup<Pipeline<L>>>::lookup(storage, &idx) {
+            pipeline
+        } else {
+            continue;
+        };
+        if !pipeline.status.is_finished() || pipeline.cim_deleted_at.is_some() {
+            continue;
+        }
+        let config = if let Some(config) = pipeline.ci_config.as_ref() {
+            config
+        } else {
+            continue;
+        };
+        let duration = if let (Some(started_at), Some(finished_at)) =
+            (pipeline.started_at, pipeline.finished_at)
+        {
+            finished_at - started_at
+        } else {
+            continue;
+        };
+        let project =
+            if let Some(project) = <L as Lookup<Project<L>>>::lookup(storage, &pipeline.project) {
+                project
+            } else {
+                continue;
+            };
+        let refname = pipeline.refname.clone().unwrap_or_default();
+
+        runs.entry((project.url.clone(), refname))
+            .or_default()
+            .push(PipelineRun {
+                sha: pipeline.sha.clone(),
+                url: pipeline.url.clone(),
+                created_at: pipeline.created_at,
+                config: config.clone(),
+                duration,
+                failed: pipeline.status == PipelineStatus::Failed,
+            });
+    }
+
+    let mut changes = Vec::new();
+    for ((project, refname), mut runs) in runs {
+        runs.sort_by_key(|run| run.created_at);
+
+        // Split the runs into segments using the same configuration.
+        let mut segments: Vec<&[PipelineRun]> = Vec::new();
+        let mut start = 0;
+        for idx in 1..=runs.len() {
+            if idx == runs.len() || runs[idx].config != runs[start].config {
+                segments.push(&runs[start..idx]);
+                start = idx;
+            }
+        }
+
+        for pair in segments.windows(2) {
+            let (before, after) = (pair[0], pair[1]);
+            let first = &after[0];
+            let before_window = &before[before.len().saturating_sub(window)..];
+            let after_window = &after[..after.len().min(window)];
+
+            changes.push(ConfigChange {
+                project: project.clone(),
+                refname: refname.clone(),
+                sha: first.sha.clone(),
+                pipeline: first.url.clone(),
+                created_at: first.created_at,
+                before: before[0].config.clone(),
+                after: first.config.clone(),
+                stats_before: PipelineRunStats::new(before_window),
+                stats_after: PipelineRunStats::new(after_window),
+            });
+        }
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use ci_monitor_core::data::{
+        Blob, BlobReference, ContentHash, Pipeline, PipelineSource, PipelineStatus,
+    };
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test::{self, at};
+    use crate::{ConfigDiff, DiffLine};
+
+    fn config(contents: &str) -> BlobReference {
+        BlobReference::for_blob(&Blob::new(contents.into()), ContentHash::Sha256)
+    }
+
+    #[test]
+    fn ci_config_changes() {
+        let mut lookup = VecLookup::default();
+        let instance = test::instance(&mut lookup);
+        let project = test::project(&mut lookup, instance, 0, "project");
+
+        let old = config("old");
+        let new = config("new");
+        let pipelines = [
+            (0, &old, 10, PipelineStatus::Success),
+            (1, &old, 12, PipelineStatus::Success),
+            (2, &new, 30, PipelineStatus::Success),
+            (3, &new, 30, PipelineStatus::Failed),
+        ];
+        for (id, config, minutes, status) in pipelines {
+            let created_at = at(id as i64 * 100);
+            let pipeline = Pipeline::builder()
+                .project(project)
+                .sha(format!("{}", id))
+                .refname(Some("refs/heads/main".into()))
+                .ci_config(Some(config.clone()))
+                .source(PipelineSource::Push)
+                .status(status)
+                .forge_id(id)
+                .url(format!("pipeline{}", id))
+                .created_at(created_at)
+                .updated_at(created_at)
+                .started_at(Some(created_at))
+                .finished_at(Some(created_at + Duration::minutes(minutes)))
+                .build()
+                .unwrap();
+            lookup.store(pipeline);
+        }
+
+        let changes = super::ci_config_changes(&lookup, 5);
+
+        assert_eq!(changes.len(), 1);
+        let change = &changes[0];
+        assert_eq!(change.project, "project");
+        assert_eq!(change.refname, "refs/heads/main");
+        assert_eq!(change.sha, "2");
+        assert_eq!(change.pipeline, "pipeline2");
+        assert_eq!(change.before, old);
+        assert_eq!(change.after, new);
+        assert_eq!(change.stats_before.count, 2);
+        assert_eq!(change.stats_before.mean_duration, Duration::minutes(11));
+        assert_eq!(change.stats_after.mean_duration, Duration::minutes(30));
+        assert_eq!(change.stats_after.failure_rate(), 0.5);
+        assert!(change.is_regression(2., 0.1));
+        assert!(change.is_regression(3., 0.1));
+        assert!(!change.is_regression(3., 0.6));
+    }
+
+    #[test]
+    fn config_diff() {
+        let before = "a\nb\nc\nd\ne\nf\ng\nh\ni\n";
+        let after = "a\nb\nc\nd\ne\nf\ng\nH\ni\nj\n";
+
+        let diff = ConfigDiff::new(before, after);
+
+        assert!(!diff.is_empty());
+        assert_eq!(
+            diff.lines(),
+            [
+                DiffLine::Elided,
+                DiffLine::Context("e".into()),
+                DiffLine::Context("f".into()),
+                DiffLine::Context("g".into()),
+                DiffLine::Removed("h".into()),
+                DiffLine::Added("H".into()),
+                DiffLine::Context("i".into()),
+                DiffLine::Added("j".into()),
+            ],
+        );
+        assert_eq!(diff.to_string(), "@@\n e\n f\n g\n-h\n+H\n i\n+j\n",);
+        assert!(ConfigDiff::new(before, before).is_empty());
+    }
+}
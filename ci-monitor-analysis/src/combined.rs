@@ -0,0 +1,370 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use ci_monitor_core::data::{Instance, Job, JobState, Pipeline, PipelineStatus, Project, Runner};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::AnalysisLookup;
+
+/// The health of a set of runners.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct RunnerHealth {
+    /// The number of runners.
+    pub total: usize,
+    /// The number of runners which are online.
+    pub online: usize,
+    /// The number of runners which are paused.
+    pub paused: usize,
+}
+
+impl RunnerHealth {
+    fn add(&mut self, online: bool, paused: bool) {
+        self.total += 1;
+        self.online += usize::from(online);
+        self.paused += usize::from(paused);
+    }
+
+    /// The number of runners which are offline.
+    pub fn offline(&self) -> usize {
+        self.total - self.online
+    }
+
+    /// Accumulate the health of another set of runners.
+    pub fn merge(&mut self, other: &Self) {
+        self.total += other.total;
+        self.online += other.online;
+        self.paused += other.paused;
+    }
+}
+
+/// How often finished entities failed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct FailureRate {
+    /// The number of finished entities.
+    pub finished: usize,
+    /// The number of failed entities.
+    pub failed: usize,
+}
+
+impl FailureRate {
+    pub(crate) fn add(&mut self, failed: bool) {
+        self.finished += 1;
+        self.failed += usize::from(failed);
+    }
+
+    /// Accumulate the failures of other entities.
+    pub fn merge(&mut self, other: &Self) {
+        self.finished += other.finished;
+        self.failed += other.failed;
+    }
+
+    /// The fraction of finished entities which failed.
+    pub fn rate(&self) -> f64 {
+        if self.finished == 0 {
+            0.
+        } else {
+            self.failed as f64 / self.finished as f64
+        }
+    }
+}
+
+/// A report on a single store.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct StoreReport {
+    /// The name of the store.
+    pub name: String,
+    /// The health of the store's runners.
+    pub runners: RunnerHealth,
+    /// The failure rate of the store's pipelines.
+    pub pipelines: FailureRate,
+    /// The failure rate of the store's jobs.
+    pub jobs: FailureRate,
+}
+
+/// A report combining several stores.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct CombinedReport {
+    /// Reports for each store.
+    pub stores: Vec<StoreReport>,
+    /// The health of all runners.
+    pub runners: RunnerHealth,
+    /// The failure rate of all pipelines.
+    pub pipelines: FailureRate,
+    /// The failure rate of all jobs.
+    pub jobs: FailureRate,
+}
+
+/// An entity identified across stores by its instance and forge ID.
+type GlobalId = (String, u64);
+
+#[derive(Default)]
+struct Entities {
+    runners: BTreeMap<GlobalId, (DateTime<Utc>, (bool, bool))>,
+    pipelines: BTreeMap<GlobalId, (DateTime<Utc>, bool)>,
+    jobs: BTreeMap<GlobalId, (DateTime<Utc>, bool)>,
+}
+
+impl Entities {
+    /// Record an entity, keeping the most recently refreshed copy.
+    fn record<T>(
+        map: &mut BTreeMap<GlobalId, (DateTime<Utc>, T)>,
+        id: GlobalId,
+        at: DateTime<Utc>,
+        data: T,
+    ) {
+        if map.get(&id).is_none_or(|(seen, _)| *seen < at) {
+            map.insert(id, (at, data));
+        }
+    }
+}
+
+fn instance_url<L>(storage: &L, idx: &<L as Lookup<Instance>>::Index) -> Option<String>
+where
+    L: AnalysisLookup<L>,
+{
+    <L as Lookup<Instance>>::lookup(storage, idx).map(|instance| instance.url.clone())
+}
+
+fn pipeline_instance<L>(storage: &L, pipeline: &Pipeline<L>) -> Option<String>
+where
+    L: AnalysisLookup<L>,
+{
+    <L as Lookup<Project<L>>>::lookup(storage, &pipeline.project)
+        .and_then(|project| instance_url(storage, &project.instance))
+}
+
+fn scan_store<L>(name: &str, storage: &L, entities: &mut Entities) -> StoreReport
+where
+    L: AnalysisLookup<L>,
+{
+    let mut report = StoreReport {
+        name: name.into(),
+        runners: RunnerHealth::default(),
+        pipelines: FailureRate::default(),
+        jobs: FailureRate::default(),
+    };
+
+    let runners = <L as DiscoverableLookup<Runner<L>>>::all_indices(storage);
+    for idx in &runners {
+        let runner = if let Some(runner) = <L as Lookup<Runner<L>>>::lookup(storage, idx) {
+            runner
+        } else {
+            continue;
+        };
+        report.runners.add(runner.online, runner.paused);
+
+        if let Some(url) = instance_url(storage, &runner.instance) {
+            Entities::record(
+                &mut entities.runners,
+                (url, runner.forge_id),
+                runner.cim_refreshed_at,
+                (runner.online, runner.paused),
+            );
+        }
+    }
+
+    let pipelines = <L as DiscoverableLookup<Pipeline<L>>>::all_indices(storage);
+    for idx in &pipelines {
+        let pipeline = if let Some(pipeline) = <L as Lookup<Pipeline<L>>>::lookup(storage, idx) {
+            pipeline
+        } else {
+            continue;
+        };
+        if !pipeline.status.is_finished() || pipeline.cim_deleted_at.is_some() {
+            continue;
+        }
+        let failed = pipeline.status == PipelineStatus::Failed;
+        report.pipelines.add(failed);
+
+        if let Some(url) = pipeline_instance(storage, pipeline) {
+            Entities::record(
+                &mut entities.pipelines,
+                (url, pipeline.forge_id),
+                pipeline.cim_refreshed_at,
+                failed,
+            );
+        }
+    }
+
+    let jobs = <L as DiscoverableLookup<Job<L>>>::all_indices(storage);
+    for idx in &jobs {
+        let job = if let Some(job) = <L as Lookup<Job<L>>>::lookup(storage, idx) {
+            job
+        } else {
+            continue;
+        };
+        let is_finished = matches!(
+            job.state,
+            JobState::Success | JobState::Failed | JobState::Canceled,
+        );
+        if !is_finished || job.cim_deleted_at.is_some() {
+            continue;
+        }
+        let failed = job.state == JobState::Failed && !job.allow_failure;
+        report.jobs.add(failed);
+
+        let url = <L as Lookup<Pipeline<L>>>::lookup(storage, &job.pipeline)
+            .and_then(|pipeline| pipeline_instance(storage, pipeline));
+        if let Some(url) = url {
+            Entities::record(
+                &mut entities.jobs,
+                (url, job.forge_id),
+                job.cim_refreshed_at,
+                failed,
+            );
+        }
+    }
+
+    report
+}
+
+/// Combine reports on runner health and failure rates across several stores.
+///
+/// Stores are only read. Entities which appear in multiple stores (e.g., instance runners seen
+/// by stores for different groups) are identified by their instance URL and forge ID and only
+/// counted once in the combined totals using the most recently refreshed copy.
+pub fn combined_report<'a, L, I>(stores: I) -> CombinedReport
+where
+    L: AnalysisLookup<L> + 'a,
+    I: IntoIterator<Item = (&'a str, &'a L)>,
+{
+    let mut entities = Entities::default();
+    let stores = stores
+        .into_iter()
+        .map(|(name, storage)| scan_store(name, storage, &mut entities))
+        .collect();
+
+    let mut runners = RunnerHealth::default();
+    for (_, (online, paused)) in entities.runners.values() {
+        runners.add(*online, *paused);
+    }
+    let mut pipelines = FailureRate::default();
+    for (_, failed) in entities.pipelines.values() {
+        pipelines.add(*failed);
+    }
+    let mut jobs = FailureRate::default();
+    for (_, failed) in entities.jobs.values() {
+        jobs.add(*failed);
+    }
+
+    CombinedReport {
+        stores,
+        runners,
+        pipelines,
+        jobs,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ci_monitor_core::data::{
+        Instance, Job, JobState, PipelineStatus, Runner, RunnerProtectionLevel, RunnerType,
+    };
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test::{self, at};
+
+    fn store(project_id: u64, statuses: &[(u64, PipelineStatus)], online: bool) -> VecLookup {
+        let mut lookup = VecLookup::default();
+        let instance = Instance::builder()
+            .unique_id(0)
+            .forge("gitlab")
+            .url("gitlab.example.com")
+            .build()
+            .unwrap();
+        let instance = lookup.store(instance);
+        let project = test::project(&mut lookup, instance, project_id, "project");
+        let user = test::user(&mut lookup, instance, 0);
+        // An instance runner seen by every store.
+        let mut runner = Runner::builder()
+            .runner_type(RunnerType::Instance)
+            .protection_level(RunnerProtectionLevel::Any)
+            .forge_id(1)
+            .online(online)
+            .instance(instance)
+            .build()
+            .unwrap();
+        runner.cim_refreshed_at = at(project_id as i64);
+        lookup.store(runner);
+
+        for &(id, status) in statuses {
+            let pipeline = test::pipeline(project, id, status, at(0));
+            let pipeline = lookup.store(pipeline);
+            let state = if status == PipelineStatus::Failed {
+                JobState::Failed
+            } else {
+                JobState::Success
+            };
+            let job = Job::builder()
+                .user(user)
+                .state(state)
+                .created_at(at(0))
+                .forge_id(id)
+                .pipeline(pipeline)
+                .build()
+                .unwrap();
+            lookup.store(job);
+        }
+
+        lookup
+    }
+
+    #[test]
+    fn combined_report() {
+        let first = store(
+            1,
+            &[
+                (10, PipelineStatus::Success),
+                (11, PipelineStatus::Failed),
+                (12, PipelineStatus::Running),
+            ],
+            false,
+        );
+        let second = store(
+            2,
+            &[(20, PipelineStatus::Failed), (21, PipelineStatus::Failed)],
+            true,
+        );
+
+        let report = super::combined_report([("first", &first), ("second", &second)]);
+
+        assert_eq!(report.stores.len(), 2);
+        assert_eq!(report.stores[0].name, "first");
+        assert_eq!(report.stores[0].pipelines.finished, 2);
+        assert_eq!(report.stores[0].pipelines.failed, 1);
+        assert_eq!(report.stores[0].runners.online, 0);
+        assert_eq!(report.stores[1].jobs.failed, 2);
+        assert_eq!(report.stores[1].runners.online, 1);
+
+        // The shared runner is only counted once using the latest state.
+        assert_eq!(report.runners.total, 1);
+        assert_eq!(report.runners.online, 1);
+        assert_eq!(report.runners.offline(), 0);
+        assert_eq!(report.pipelines.finished, 4);
+        assert_eq!(report.pipelines.failed, 3);
+        assert_eq!(report.pipelines.rate(), 0.75);
+        assert_eq!(report.jobs.finished, 5);
+    }
+
+    #[test]
+    fn combined_report_empty() {
+        let report = super::combined_report::<VecLookup, _>([]);
+
+        assert!(report.stores.is_empty());
+        assert_eq!(report.pipelines.rate(), 0.);
+    }
+}
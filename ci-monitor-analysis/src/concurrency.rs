@@ -0,0 +1,522 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, Utc};
+use ci_monitor_core::data::{Instance, Job, Pipeline, Project};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+
+use crate::AnalysisLookup;
+
+/// The grouping of jobs for concurrency analysis.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub struct ConcurrencyKey {
+    /// The URL of the instance the jobs belong to.
+    pub instance: String,
+    /// The runner tag requested by the jobs.
+    ///
+    /// If `None`, all jobs on the instance are considered.
+    pub tag: Option<String>,
+}
+
+impl ConcurrencyKey {
+    /// A key for all jobs on an instance.
+    pub fn instance<I>(instance: I) -> Self
+    where
+        I: Into<String>,
+    {
+        Self {
+            instance: instance.into(),
+            tag: None,
+        }
+    }
+
+    /// A key for jobs requesting a given tag on an instance.
+    pub fn tag<I, T>(instance: I, tag: T) -> Self
+    where
+        I: Into<String>,
+        T: Into<String>,
+    {
+        Self {
+            instance: instance.into(),
+            tag: Some(tag.into()),
+        }
+    }
+}
+
+/// A period of time during which concurrency was at or above a given level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ConcurrencyPeak {
+    /// The highest concurrency during the period.
+    pub concurrency: usize,
+    /// When the period started.
+    pub start: DateTime<Utc>,
+    /// When the period ended.
+    pub end: DateTime<Utc>,
+}
+
+/// The number of concurrently running jobs over time.
+#[derive(Debug, Clone, Default)]
+pub struct ConcurrencyCurve {
+    /// Points at which the concurrency changed and its new value.
+    ///
+    /// The concurrency is zero before the first and after the last step.
+    steps: Vec<(DateTime<Utc>, usize)>,
+}
+
+impl ConcurrencyCurve {
+    /// Compute the concurrency curve of a set of `(start, end)` intervals.
+    ///
+    /// Intervals which are empty or end before they start are ignored. An interval ending at the
+    /// same time as another starts is not considered to overlap with it.
+    pub fn from_intervals<I>(intervals: I) -> Self
+    where
+        I: IntoIterator<Item = (DateTime<Utc>, DateTime<Utc>)>,
+    {
+        let mut events = intervals
+            .into_iter()
+            .filter(|(start, end)| start < end)
+            .flat_map(|(start, end)| [(start, 1), (end, -1)])
+            .collect::<Vec<(_, isize)>>();
+        // Sort endings before starts at the same time so that back-to-back jobs do not count as
+        // running at the same time.
+        events.sort();
+
+        let mut steps: Vec<(DateTime<Utc>, usize)> = Vec::new();
+        let mut current: isize = 0;
+        for (when, delta) in events {
+            current += delta;
+            let level = current as usize;
+
+            match steps.last_mut() {
+                Some((last_when, last_level)) if *last_when == when => *last_level = level,
+                _ => steps.push((when, level)),
+            }
+        }
+        // Remove any redundant steps from simultaneous events.
+        steps.dedup_by(|next, prev| next.1 == prev.1);
+
+        Self {
+            steps,
+        }
+    }
+
+    /// The points at which concurrency changed along with the new concurrency.
+    pub fn steps(&self) -> &[(DateTime<Utc>, usize)] {
+        &self.steps
+    }
+
+    /// Whether the curve contains any activity.
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// The concurrency at a given point in time.
+    pub fn concurrency_at(&self, when: DateTime<Utc>) -> usize {
+        let pos = self.steps.partition_point(|(step, _)| *step <= when);
+        if pos == 0 {
+            0
+        } else {
+            self.steps[pos - 1].1
+        }
+    }
+
+    /// The time span covered by the curve.
+    pub fn span(&self) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        self.steps
+            .first()
+            .and_then(|(first, _)| self.steps.last().map(|(last, _)| (*first, *last)))
+    }
+
+    /// The time-weighted mean concurrency between two points in time.
+    pub fn mean_between(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> f64 {
+        let total = (end - start).num_milliseconds();
+        if total <= 0 {
+            return 0.;
+        }
+
+        let busy: i64 = self
+            .periods()
+            .map(|(period_start, period_end, level)| {
+                let overlap = period_end.min(end) - period_start.max(start);
+                overlap.num_milliseconds().max(0) * level as i64
+            })
+            .sum();
+
+        busy as f64 / total as f64
+    }
+
+    /// The highest concurrency between two points in time.
+    pub fn peak_between(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> usize {
+        self.periods()
+            .filter(|(period_start, period_end, _)| *period_start < end && start < *period_end)
+            .map(|(_, _, level)| level)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Iterate over periods of constant concurrency.
+    fn periods(&self) -> impl Iterator<Item = (DateTime<Utc>, DateTime<Utc>, usize)> + '_ {
+        self.steps
+            .windows(2)
+            .map(|window| (window[0].0, window[1].0, window[0].1))
+    }
+
+    /// The first period with the highest concurrency.
+    pub fn peak(&self) -> Option<ConcurrencyPeak> {
+        self.periods().fold(
+            None,
+            |peak: Option<ConcurrencyPeak>, (start, end, level)| {
+                match peak {
+                    Some(peak) if peak.concurrency >= level => Some(peak),
+                    _ => {
+                        Some(ConcurrencyPeak {
+                            concurrency: level,
+                            start,
+                            end,
+                        })
+                    },
+                }
+            },
+        )
+    }
+
+    /// Periods during which concurrency was at least `threshold`.
+    ///
+    /// Each period reports the highest concurrency seen during it.
+    pub fn peaks(&self, threshold: usize) -> Vec<ConcurrencyPeak> {
+        let mut peaks: Vec<ConcurrencyPeak> = Vec::new();
+        let mut in_peak = false;
+
+        for (start, end, level) in self.periods() {
+            if level < threshold || level == 0 {
+                in_peak = false;
+                continue;
+            }
+
+            match peaks.last_mut() {
+                Some(peak) if in_peak => {
+                    peak.concurrency = peak.concurrency.max(level);
+                    peak.end = end;
+                },
+                _ => {
+                    peaks.push(ConcurrencyPeak {
+                        concurrency: level,
+                        start,
+                        end,
+                    })
+                },
+            }
+            in_peak = true;
+        }
+
+        peaks
+    }
+
+    /// How long the curve spent at each concurrency level.
+    ///
+    /// Idle time between the first and last activity is included at level `0`.
+    pub fn load_distribution(&self) -> BTreeMap<usize, Duration> {
+        let mut distribution = BTreeMap::new();
+        for (start, end, level) in self.periods() {
+            *distribution.entry(level).or_insert_with(Duration::zero) += end - start;
+        }
+        distribution
+    }
+
+    /// The concurrency which is not exceeded for `percentile` percent of the time.
+    ///
+    /// Percentiles are clamped to the range `[0, 100]`.
+    pub fn percentile(&self, percentile: f64) -> usize {
+        let distribution = self.load_distribution();
+        let total: Duration = distribution.values().copied().sum();
+        if total.is_zero() {
+            return 0;
+        }
+
+        let fraction = percentile.clamp(0., 100.) / 100.;
+        let target = total.num_milliseconds() as f64 * fraction;
+        let mut seen = 0;
+        for (level, duration) in &distribution {
+            seen += duration.num_milliseconds();
+            if seen as f64 >= target {
+                return *level;
+            }
+        }
+
+        distribution.keys().last().copied().unwrap_or(0)
+    }
+
+    /// Compute a load curve at the given percentiles.
+    ///
+    /// The result pairs each requested percentile with the concurrency needed to satisfy it.
+    pub fn load_curve(&self, percentiles: &[f64]) -> Vec<(f64, usize)> {
+        percentiles
+            .iter()
+            .map(|&percentile| (percentile, self.percentile(percentile)))
+            .collect()
+    }
+}
+
+/// Compute the concurrency of running jobs over time.
+///
+/// Curves are computed for each instance as a whole as well as for each runner tag requested by
+/// jobs on the instance. Only jobs with both start and finish times are considered.
+pub fn job_concurrency<L>(storage: &L) -> BTreeMap<ConcurrencyKey, ConcurrencyCurve>
+where
+    L: AnalysisLookup<L>,
+{
+    let mut intervals: BTreeMap<ConcurrencyKey, Vec<_>> = BTreeMap::new();
+
+    for idx in <L as DiscoverableLookup<Job<L>>>::all_indices(storage) {
+        let job = if let Some(job) = <L as Lookup<Job<L>>>::lookup(storage, &idx) {
+            job
+        } else {
+            continue;
+        };
+        if job.cim_deleted_at.is_some() {
+            continue;
+        }
+        let interval = if let (Some(start), Some(end)) = (job.started_at, job.finished_at) {
+            (start, end)
+        } else {
+            continue;
+        };
+        let instance = if let Some(instance) = job_instance(storage, job) {
+            instance
+        } else {
+            continue;
+        };
+
+        intervals
+            .entry(ConcurrencyKey::instance(&instance.url))
+            .or_default()
+            .push(interval);
+        for tag in &job.tags {
+            intervals
+                .entry(ConcurrencyKey::tag(&instance.url, tag))
+                .or_default()
+                .push(interval);
+        }
+    }
+
+    intervals
+        .into_iter()
+        .map(|(key, intervals)| (key, ConcurrencyCurve::from_intervals(intervals)))
+        .collect()
+}
+
+pub(crate) fn job_instance<'a, L>(storage: &'a L, job: &'a Job<L>) -> Option<&'a Instance>
+where
+    L: AnalysisLookup<L>,
+{
+    let pipeline = <L as Lookup<Pipeline<L>>>::lookup(storage, &job.pipeline)?;
+    let project = <L as Lookup<Project<L>>>::lookup(storage, &pipeline.project)?;
+    <L as Lookup<Instance>>::lookup(storage, &project.instance)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use ci_monitor_core::data::{Job, JobState, PipelineStatus};
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test::{self, at};
+    use crate::{ConcurrencyCurve, ConcurrencyKey, ConcurrencyPeak};
+
+    #[test]
+    fn empty_curve() {
+        let curve = ConcurrencyCurve::from_intervals([]);
+
+        assert!(curve.is_empty());
+        assert_eq!(curve.peak(), None);
+        assert_eq!(curve.percentile(50.), 0);
+        assert_eq!(curve.span(), None);
+    }
+
+    #[test]
+    fn invalid_intervals_ignored() {
+        let curve = ConcurrencyCurve::from_intervals([(at(5), at(5)), (at(5), at(0))]);
+
+        assert!(curve.is_empty());
+    }
+
+    #[test]
+    fn overlapping_intervals() {
+        let curve = ConcurrencyCurve::from_intervals([
+            (at(0), at(10)),
+            (at(2), at(6)),
+            (at(4), at(8)),
+            (at(20), at(30)),
+        ]);
+
+        assert_eq!(
+            curve.steps(),
+            &[
+                (at(0), 1),
+                (at(2), 2),
+                (at(4), 3),
+                (at(6), 2),
+                (at(8), 1),
+                (at(10), 0),
+                (at(20), 1),
+                (at(30), 0),
+            ],
+        );
+        assert_eq!(curve.concurrency_at(at(5)), 3);
+        assert_eq!(curve.concurrency_at(at(15)), 0);
+        assert_eq!(curve.concurrency_at(at(40)), 0);
+        assert_eq!(curve.span(), Some((at(0), at(30))));
+        assert_eq!(
+            curve.peak(),
+            Some(ConcurrencyPeak {
+                concurrency: 3,
+                start: at(4),
+                end: at(6),
+            }),
+        );
+    }
+
+    #[test]
+    fn concurrency_between() {
+        let curve = ConcurrencyCurve::from_intervals([
+            (at(0), at(10)),
+            (at(2), at(6)),
+            (at(4), at(8)),
+            (at(20), at(30)),
+        ]);
+
+        assert_eq!(curve.mean_between(at(0), at(10)), 1.8);
+        assert_eq!(curve.mean_between(at(0), at(20)), 0.9);
+        assert_eq!(curve.mean_between(at(40), at(50)), 0.);
+        assert_eq!(curve.mean_between(at(10), at(0)), 0.);
+        assert_eq!(curve.peak_between(at(5), at(7)), 3);
+        assert_eq!(curve.peak_between(at(10), at(20)), 0);
+    }
+
+    #[test]
+    fn back_to_back_intervals() {
+        let curve = ConcurrencyCurve::from_intervals([(at(0), at(5)), (at(5), at(10))]);
+
+        assert_eq!(curve.steps(), &[(at(0), 1), (at(10), 0)]);
+        assert_eq!(curve.peak().unwrap().concurrency, 1);
+    }
+
+    #[test]
+    fn peaks_above_threshold() {
+        let curve = ConcurrencyCurve::from_intervals([
+            (at(0), at(10)),
+            (at(2), at(6)),
+            (at(4), at(8)),
+            (at(20), at(30)),
+            (at(22), at(24)),
+        ]);
+
+        assert_eq!(
+            curve.peaks(2),
+            vec![
+                ConcurrencyPeak {
+                    concurrency: 3,
+                    start: at(2),
+                    end: at(8),
+                },
+                ConcurrencyPeak {
+                    concurrency: 2,
+                    start: at(22),
+                    end: at(24),
+                },
+            ],
+        );
+        assert_eq!(curve.peaks(4), vec![]);
+    }
+
+    #[test]
+    fn percentiles() {
+        // 0-60: 1 job, 60-90: 2 jobs, 90-100: 4 jobs.
+        let curve = ConcurrencyCurve::from_intervals([
+            (at(0), at(100)),
+            (at(60), at(100)),
+            (at(90), at(100)),
+            (at(90), at(100)),
+        ]);
+
+        let distribution = curve.load_distribution();
+        assert_eq!(distribution[&1], Duration::minutes(60));
+        assert_eq!(distribution[&2], Duration::minutes(30));
+        assert_eq!(distribution[&4], Duration::minutes(10));
+
+        assert_eq!(curve.percentile(0.), 1);
+        assert_eq!(curve.percentile(50.), 1);
+        assert_eq!(curve.percentile(60.), 1);
+        assert_eq!(curve.percentile(75.), 2);
+        assert_eq!(curve.percentile(95.), 4);
+        assert_eq!(curve.percentile(200.), 4);
+        assert_eq!(
+            curve.load_curve(&[50., 90., 99.]),
+            vec![(50., 1), (90., 2), (99., 4)],
+        );
+    }
+
+    #[test]
+    fn job_concurrency_by_tag() {
+        let mut lookup = VecLookup::default();
+        let instance = test::instance(&mut lookup);
+        let user = test::user(&mut lookup, instance, 0);
+        let project = test::project(&mut lookup, instance, 0, "project");
+        let pipeline = test::pipeline(project, 0, PipelineStatus::Success, at(0));
+        let pipeline = lookup.store(pipeline);
+
+        let jobs = [
+            (0, vec!["linux"], Some(at(0)), Some(at(10))),
+            (1, vec!["linux", "docker"], Some(at(5)), Some(at(15))),
+            (2, vec!["windows"], Some(at(5)), Some(at(20))),
+            // Still running; ignored.
+            (3, vec!["linux"], Some(at(5)), None),
+        ];
+        for (id, tags, started_at, finished_at) in jobs {
+            let job = Job::builder()
+                .user(user)
+                .state(JobState::Success)
+                .created_at(at(0))
+                .started_at(started_at)
+                .finished_at(finished_at)
+                .forge_id(id)
+                .pipeline(pipeline)
+                .tags(tags.into_iter().map(Into::into).collect())
+                .build()
+                .unwrap();
+            lookup.store(job);
+        }
+        // Deleted; ignored.
+        let mut deleted = Job::builder()
+            .user(user)
+            .state(JobState::Success)
+            .created_at(at(0))
+            .started_at(Some(at(0)))
+            .finished_at(Some(at(30)))
+            .forge_id(4)
+            .pipeline(pipeline)
+            .tags(vec!["windows".into()])
+            .build()
+            .unwrap();
+        deleted.cim_deleted_at = Some(at(40));
+        lookup.store(deleted);
+
+        let curves = super::job_concurrency(&lookup);
+
+        assert_eq!(curves.len(), 4);
+        let peak = |key| curves[&key].peak().unwrap().concurrency;
+        assert_eq!(peak(ConcurrencyKey::instance("url")), 3);
+        assert_eq!(peak(ConcurrencyKey::tag("url", "linux")), 2);
+        assert_eq!(peak(ConcurrencyKey::tag("url", "docker")), 1);
+        assert_eq!(peak(ConcurrencyKey::tag("url", "windows")), 1);
+    }
+}
@@ -0,0 +1,396 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use chrono::Duration;
+use ci_monitor_core::data::{
+    Job, MergeRequest, MergeRequestStatus, Pipeline, Project, Runner, RunnerHost,
+};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+
+use crate::AnalysisLookup;
+
+/// The cost of CI for a merged merge request.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct MergeRequestCost {
+    /// The URL of the merge request.
+    pub url: String,
+    /// The title of the merge request.
+    pub title: String,
+    /// The number of pipelines run for the merge request.
+    pub pipelines: usize,
+    /// The number of jobs run for the merge request.
+    pub jobs: usize,
+    /// The total time jobs spent running.
+    pub machine_time: Duration,
+    /// The estimated cost of the machine time.
+    ///
+    /// Only includes jobs which ran on runner hosts with a known cost.
+    pub estimated_cost: f64,
+    /// Machine time spent on runners without a known cost.
+    pub unpriced_time: Duration,
+}
+
+impl MergeRequestCost {
+    fn new(url: String, title: String) -> Self {
+        Self {
+            url,
+            title,
+            pipelines: 0,
+            jobs: 0,
+            machine_time: Duration::zero(),
+            estimated_cost: 0.,
+            unpriced_time: Duration::zero(),
+        }
+    }
+}
+
+/// Aggregate costs over a set of merge requests.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct MergeRequestCostSummary {
+    /// The number of merge requests.
+    pub merge_requests: usize,
+    /// The total machine time.
+    pub machine_time: Duration,
+    /// The total estimated cost.
+    pub estimated_cost: f64,
+    /// The total machine time without a known cost.
+    pub unpriced_time: Duration,
+}
+
+impl MergeRequestCostSummary {
+    /// Summarize the costs of a set of merge requests.
+    pub fn new(costs: &[MergeRequestCost]) -> Self {
+        costs.iter().fold(
+            Self {
+                merge_requests: 0,
+                machine_time: Duration::zero(),
+                estimated_cost: 0.,
+                unpriced_time: Duration::zero(),
+            },
+            |summary, cost| {
+                Self {
+                    merge_requests: summary.merge_requests + 1,
+                    machine_time: summary.machine_time + cost.machine_time,
+                    estimated_cost: summary.estimated_cost + cost.estimated_cost,
+                    unpriced_time: summary.unpriced_time + cost.unpriced_time,
+                }
+            },
+        )
+    }
+
+    /// The mean machine time per merge request.
+    pub fn mean_machine_time(&self) -> Duration {
+        if self.merge_requests == 0 {
+            Duration::zero()
+        } else {
+            self.machine_time / self.merge_requests as i32
+        }
+    }
+
+    /// The mean estimated cost per merge request.
+    pub fn mean_estimated_cost(&self) -> f64 {
+        if self.merge_requests == 0 {
+            0.
+        } else {
+            self.estimated_cost / self.merge_requests as f64
+        }
+    }
+}
+
+impl fmt::Display for MergeRequestCostSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "merged merge requests: {}", self.merge_requests)?;
+        writeln!(
+            f,
+            "machine time: {}h total, {}m per merge request",
+            self.machine_time.num_hours(),
+            self.mean_machine_time().num_minutes(),
+        )?;
+        writeln!(
+            f,
+            "estimated cost: {:.2} total, {:.2} per merge request",
+            self.estimated_cost,
+            self.mean_estimated_cost(),
+        )?;
+        if !self.unpriced_time.is_zero() {
+            writeln!(
+                f,
+                "machine time without a known cost: {}h",
+                self.unpriced_time.num_hours(),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parse the merge request ID from a merge request pipeline ref.
+fn merge_request_ref_id(refname: &str) -> Option<u64> {
+    refname
+        .strip_prefix("refs/merge-requests/")
+        .and_then(|rest| rest.split('/').next())
+        .and_then(|id| id.parse().ok())
+}
+
+/// Find the URL of the merge request a pipeline was run for.
+///
+/// Pipelines without an explicit link are matched using their ref.
+pub(crate) fn pipeline_merge_request<L>(
+    storage: &L,
+    pipeline: &Pipeline<L>,
+    by_ref: &BTreeMap<(String, u64), String>,
+) -> Option<String>
+where
+    L: AnalysisLookup<L>,
+{
+    if let Some(mr) = pipeline.merge_request.as_ref() {
+        return <L as Lookup<MergeRequest<L>>>::lookup(storage, mr).map(|mr| mr.url.clone());
+    }
+
+    let id = merge_request_ref_id(pipeline.refname.as_deref()?)?;
+    let project = <L as Lookup<Project<L>>>::lookup(storage, &pipeline.project)?;
+    by_ref.get(&(project.url.clone(), id)).cloned()
+}
+
+/// Compute the CI cost of each merged merge request.
+///
+/// Machine time is the time jobs spent running. Money spent is estimated from the cost of the
+/// runner host each job ran on. Results are sorted by estimated cost, most expensive first.
+pub fn merge_request_costs<L>(storage: &L) -> Vec<MergeRequestCost>
+where
+    L: AnalysisLookup<L>,
+{
+    let mut costs = BTreeMap::new();
+    let mut by_ref = BTreeMap::new();
+
+    for idx in <L as DiscoverableLookup<MergeRequest<L>>>::all_indices(storage) {
+        let mr = if let Some(mr) = <L as Lookup<MergeRequest<L>>>::lookup(storage, &idx) {
+            mr
+        } else {
+            continue;
+        };
+        if mr.state != MergeRequestStatus::Merged || mr.cim_deleted_at.is_some() {
+            continue;
+        }
+        // Merge request pipelines run in the target project.
+        if let Some(project) = <L as Lookup<Project<L>>>::lookup(storage, &mr.target_project) {
+            by_ref.insert((project.url.clone(), mr.id), mr.url.clone());
+        }
+        costs.insert(
+            mr.url.clone(),
+            MergeRequestCost::new(mr.url.clone(), mr.title.clone()),
+        );
+    }
+
+    for idx in <L as DiscoverableLookup<Pipeline<L>>>::all_indices(storage) {
+        let pipeline = if let Some(pipeline) = <L as Lookup<Pipeline<L>>>::lookup(storage, &idx) {
+            pipeline
+        } else {
+            continue;
+        };
+        if pipeline.cim_deleted_at.is_some() {
+            continue;
+        }
+        let cost =
+            pipeline_merge_request(storage, pipeline, &by_ref).and_then(|url| costs.get_mut(&url));
+        if let Some(cost) = cost {
+            cost.pipelines += 1;
+        }
+    }
+
+    for idx in <L as DiscoverableLookup<Job<L>>>::all_indices(storage) {
+        let job = if let Some(job) = <L as Lookup<Job<L>>>::lookup(storage, &idx) {
+            job
+        } else {
+            continue;
+        };
+        if job.cim_deleted_at.is_some() {
+            continue;
+        }
+        let duration =
+            if let (Some(started_at), Some(finished_at)) = (job.started_at, job.finished_at) {
+                finished_at - started_at
+            } else {
+                continue;
+            };
+        let cost = <L as Lookup<Pipeline<L>>>::lookup(storage, &job.pipeline)
+            .and_then(|pipeline| pipeline_merge_request(storage, pipeline, &by_ref))
+            .and_then(|url| costs.get_mut(&url));
+        let cost = if let Some(cost) = cost {
+            cost
+        } else {
+            continue;
+        };
+        let cost_per_hour = job
+            .runner
+            .as_ref()
+            .and_then(|runner| <L as Lookup<Runner<L>>>::lookup(storage, runner))
+            .and_then(|runner| runner.runner_host.as_ref())
+            .and_then(|host| <L as Lookup<RunnerHost>>::lookup(storage, host))
+            .and_then(|host| host.estimated_cost_per_hour);
+
+        cost.jobs += 1;
+        cost.machine_time += duration;
+        if let Some(cost_per_hour) = cost_per_hour {
+            cost.estimated_cost += cost_per_hour * duration.num_seconds() as f64 / 3600.;
+        } else {
+            cost.unpriced_time += duration;
+        }
+    }
+
+    let mut costs: Vec<_> = costs.into_values().collect();
+    costs.sort_by(|lhs, rhs| rhs.estimated_cost.total_cmp(&lhs.estimated_cost));
+    costs
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use ci_monitor_core::data::{
+        Job, JobState, MergeRequest, MergeRequestStatus, Pipeline, PipelineSource, PipelineStatus,
+        Runner, RunnerHost, RunnerProtectionLevel, RunnerType,
+    };
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test::{self, at};
+    use crate::MergeRequestCostSummary;
+
+    #[test]
+    fn merge_request_costs() {
+        let mut lookup = VecLookup::default();
+        let instance = test::instance(&mut lookup);
+        let user = test::user(&mut lookup, instance, 0);
+        let project = test::project(&mut lookup, instance, 0, "project");
+        let host = RunnerHost::builder()
+            .name("host")
+            .unique_id(0)
+            .estimated_cost_per_hour(Some(2.))
+            .build()
+            .unwrap();
+        let host = lookup.store(host);
+        let priced = Runner::builder()
+            .runner_type(RunnerType::Instance)
+            .protection_level(RunnerProtectionLevel::Any)
+            .forge_id(0)
+            .instance(instance)
+            .runner_host(Some(host))
+            .build()
+            .unwrap();
+        let priced = lookup.store(priced);
+        let unpriced = Runner::builder()
+            .runner_type(RunnerType::Instance)
+            .protection_level(RunnerProtectionLevel::Any)
+            .forge_id(1)
+            .instance(instance)
+            .build()
+            .unwrap();
+        let unpriced = lookup.store(unpriced);
+
+        let mrs = [
+            (1, MergeRequestStatus::Merged),
+            (2, MergeRequestStatus::Merged),
+            (3, MergeRequestStatus::Open),
+        ];
+        let mrs = mrs.map(|(id, state)| {
+            let mr = MergeRequest::builder()
+                .id(id)
+                .source_project(project)
+                .target_project(project)
+                .forge_id(id)
+                .title(format!("mr{}", id))
+                .state(state)
+                .author(user)
+                .url(format!("mr{}", id))
+                .build()
+                .unwrap();
+            lookup.store(mr)
+        });
+
+        // Pipelines are linked either explicitly or through their ref.
+        let pipelines = [
+            (0, Some(mrs[0]), "refs/heads/topic"),
+            (1, None, "refs/merge-requests/1/head"),
+            (2, None, "refs/merge-requests/2/head"),
+            (3, Some(mrs[2]), "refs/merge-requests/3/head"),
+            (4, None, "refs/heads/main"),
+        ];
+        let pipelines = pipelines.map(|(id, mr, refname)| {
+            let pipeline = Pipeline::builder()
+                .project(project)
+                .sha("0000000000000000000000000000000000000000")
+                .refname(Some(refname.into()))
+                .merge_request(mr)
+                .source(PipelineSource::MergeRequestEvent)
+                .status(PipelineStatus::Success)
+                .forge_id(id)
+                .url("url")
+                .created_at(at(0))
+                .updated_at(at(0))
+                .build()
+                .unwrap();
+            lookup.store(pipeline)
+        });
+
+        let jobs = [
+            (0, pipelines[0], Some(priced), 60),
+            (1, pipelines[1], Some(priced), 30),
+            (2, pipelines[1], None, 15),
+            (3, pipelines[2], Some(unpriced), 90),
+            (4, pipelines[3], Some(priced), 600),
+            (5, pipelines[4], Some(priced), 600),
+        ];
+        for (id, pipeline, runner, minutes) in jobs {
+            let job = Job::builder()
+                .user(user)
+                .name("job")
+                .state(JobState::Success)
+                .created_at(at(0))
+                .started_at(Some(at(0)))
+                .finished_at(Some(at(minutes)))
+                .runner(runner)
+                .forge_id(id)
+                .pipeline(pipeline)
+                .build()
+                .unwrap();
+            lookup.store(job);
+        }
+
+        let costs = super::merge_request_costs(&lookup);
+
+        assert_eq!(costs.len(), 2);
+        assert_eq!(costs[0].url, "mr1");
+        assert_eq!(costs[0].title, "mr1");
+        assert_eq!(costs[0].pipelines, 2);
+        assert_eq!(costs[0].jobs, 3);
+        assert_eq!(costs[0].machine_time, Duration::minutes(105));
+        assert_eq!(costs[0].estimated_cost, 3.);
+        assert_eq!(costs[0].unpriced_time, Duration::minutes(15));
+        assert_eq!(costs[1].url, "mr2");
+        assert_eq!(costs[1].pipelines, 1);
+        assert_eq!(costs[1].estimated_cost, 0.);
+        assert_eq!(costs[1].unpriced_time, Duration::minutes(90));
+
+        let summary = MergeRequestCostSummary::new(&costs);
+
+        assert_eq!(summary.merge_requests, 2);
+        assert_eq!(summary.machine_time, Duration::minutes(195));
+        assert_eq!(summary.mean_estimated_cost(), 1.5);
+        assert_eq!(
+            summary.to_string(),
+            "merged merge requests: 2\n\
+             machine time: 3h total, 97m per merge request\n\
+             estimated cost: 3.00 total, 1.50 per merge request\n\
+             machine time without a known cost: 1h\n",
+        );
+    }
+}
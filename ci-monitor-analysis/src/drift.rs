@@ -0,0 +1,381 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, Utc};
+use ci_monitor_core::data::{
+    Deployment, DeploymentStatus, Environment, EnvironmentState, EnvironmentTier, Pipeline,
+    PipelineSource, Project,
+};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::{AnalysisLookup, DeployedRevision};
+
+/// How far an environment is behind a branch.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct EnvironmentDrift {
+    /// The path of the project.
+    pub project: String,
+    /// The name of the environment.
+    pub environment: String,
+    /// The tier of the environment.
+    ///
+    /// One of `production`, `staging`, `testing`, `development`, or `other`.
+    #[serde(serialize_with = "crate::serialize::name")]
+    #[schemars(with = "String")]
+    pub tier: EnvironmentTier,
+    /// The branch the environment is compared against.
+    pub branch: String,
+    /// The latest successful deployment into the environment.
+    pub deployed: DeployedRevision,
+    /// The ID of the pipeline which deployed the revision.
+    pub pipeline: u64,
+    /// The latest commit built on the branch.
+    pub head: Option<String>,
+    /// The number of commits built on the branch which have not been deployed.
+    pub commits_behind: usize,
+    /// How long the oldest commit which has not been deployed has been waiting.
+    #[serde(serialize_with = "crate::serialize::seconds")]
+    #[schemars(with = "i64")]
+    pub behind: Duration,
+}
+
+/// Commits built on a branch of a project.
+#[derive(Default)]
+struct BranchCommits<'a> {
+    /// The first time each commit was built.
+    built_at: BTreeMap<&'a str, DateTime<Utc>>,
+}
+
+impl<'a> BranchCommits<'a> {
+    fn add(&mut self, sha: &'a str, created_at: DateTime<Utc>) {
+        let built_at = self.built_at.entry(sha).or_insert(created_at);
+        if created_at < *built_at {
+            *built_at = created_at;
+        }
+    }
+
+    /// The most recently built commit.
+    fn head(&self) -> Option<&'a str> {
+        self.built_at
+            .iter()
+            .max_by_key(|(_, &built_at)| built_at)
+            .map(|(&sha, _)| sha)
+    }
+
+    /// When the commits built after a commit were first built.
+    fn after(&self, sha: &str, since: DateTime<Utc>) -> impl Iterator<Item = DateTime<Utc>> + '_ {
+        let since = self.built_at.get(sha).copied().unwrap_or(since);
+        let sha = sha.to_string();
+        self.built_at
+            .iter()
+            .filter(move |(&built, &built_at)| built != sha && since < built_at)
+            .map(|(_, &built_at)| built_at)
+    }
+}
+
+/// Strip the branch namespace from a refname.
+fn short_refname(refname: &str) -> &str {
+    refname.strip_prefix("refs/heads/").unwrap_or(refname)
+}
+
+/// Measure how far each environment is behind a branch.
+///
+/// The revision of an environment is its latest successful deployment. Commits are known through
+/// pipelines for `branch` (merge request pipelines are ignored), so commits which were pushed
+/// together or skipped CI are not counted. Without a `branch`, each project is compared against
+/// its default branch (as it was when each pipeline was created) and environments of projects
+/// without a default branch are not reported. Commits built after the deployed commit are
+/// considered to be behind; `behind` is measured from when the oldest of them was first built
+/// until `now`. Stopped environments and environments without a successful deployment are not
+/// reported. Results are sorted by the number of commits behind (most first).
+pub fn environment_drift<L>(
+    storage: &L,
+    branch: Option<&str>,
+    now: DateTime<Utc>,
+) -> Vec<EnvironmentDrift>
+where
+    L: AnalysisLookup<L>,
+{
+    let mut branches: BTreeMap<u64, BranchCommits> = BTreeMap::new();
+    let pipelines = <L as DiscoverableLookup<Pipeline<L>>>::all_indices(storage);
+    for idx in &pipelines {
+        let pipeline = if let Some(pipeline) = <L as Lookup<Pipeline<L>>>::lookup(storage, idx) {
+            pipeline
+        } else {
+            continue;
+        };
+        if pipeline.cim_deleted_at.is_some() || pipeline.source == PipelineSource::MergeRequestEvent
+        {
+            continue;
+        }
+        let project =
+            if let Some(project) = <L as Lookup<Project<L>>>::lookup(storage, &pipeline.project) {
+                project
+            } else {
+                continue;
+            };
+        let tracked = branch.or_else(|| project.default_branch_at(pipeline.created_at));
+        if tracked.is_none() || pipeline.refname.as_deref().map(short_refname) != tracked {
+            continue;
+        }
+
+        branches
+            .entry(project.forge_id)
+            .or_default()
+            .add(&pipeline.sha, pipeline.created_at);
+    }
+
+    let mut latest = BTreeMap::new();
+    let deployments = <L as DiscoverableLookup<Deployment<L>>>::all_indices(storage);
+    for idx in &deployments {
+        let deployment =
+            if let Some(deployment) = <L as Lookup<Deployment<L>>>::lookup(storage, idx) {
+                deployment
+            } else {
+                continue;
+            };
+        if deployment.cim_deleted_at.is_some() || deployment.status != DeploymentStatus::Success {
+            continue;
+        }
+        let environment = if let Some(environment) =
+            <L as Lookup<Environment<L>>>::lookup(storage, &deployment.environment)
+        {
+            environment
+        } else {
+            continue;
+        };
+        if environment.cim_deleted_at.is_some() || environment.state == EnvironmentState::Stopped {
+            continue;
+        }
+
+        let finished_at = deployment.finished_at.unwrap_or(deployment.updated_at);
+        match latest.entry(environment.forge_id) {
+            Entry::Vacant(entry) => {
+                entry.insert((finished_at, deployment, environment));
+            },
+            Entry::Occupied(mut entry) => {
+                if entry.get().0 < finished_at {
+                    entry.insert((finished_at, deployment, environment));
+                }
+            },
+        }
+    }
+
+    let mut drift = Vec::new();
+    for (finished_at, deployment, environment) in latest.into_values() {
+        let pipeline = <L as Lookup<Pipeline<L>>>::lookup(storage, &deployment.pipeline);
+        let pipeline = if let Some(pipeline) = pipeline {
+            pipeline
+        } else {
+            continue;
+        };
+        let project = <L as Lookup<Project<L>>>::lookup(storage, &environment.project);
+        let project = if let Some(project) = project {
+            project
+        } else {
+            continue;
+        };
+        let branch = if let Some(branch) = branch.or(project.default_branch.as_deref()) {
+            branch
+        } else {
+            continue;
+        };
+
+        let commits = branches.get(&project.forge_id);
+        let (head, commits_behind, oldest) = if let Some(commits) = commits {
+            let mut count = 0;
+            let mut oldest: Option<DateTime<Utc>> = None;
+            for built_at in commits.after(&pipeline.sha, pipeline.created_at) {
+                count += 1;
+                oldest = Some(oldest.map_or(built_at, |oldest| oldest.min(built_at)));
+            }
+            (commits.head().map(Into::into), count, oldest)
+        } else {
+            (None, 0, None)
+        };
+
+        drift.push(EnvironmentDrift {
+            project: project.instance_path.clone(),
+            environment: environment.name.clone(),
+            tier: environment.tier,
+            branch: branch.into(),
+            deployed: DeployedRevision {
+                deployment: deployment.forge_id,
+                sha: pipeline.sha.clone(),
+                finished_at,
+            },
+            pipeline: pipeline.forge_id,
+            head,
+            commits_behind,
+            behind: oldest.map_or_else(Duration::zero, |oldest| now - oldest),
+        });
+    }
+
+    drift.sort_by(|lhs, rhs| {
+        rhs.commits_behind
+            .cmp(&lhs.commits_behind)
+            .then_with(|| lhs.project.cmp(&rhs.project))
+            .then_with(|| lhs.environment.cmp(&rhs.environment))
+    });
+    drift
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use ci_monitor_core::data::{
+        Deployment, DeploymentStatus, Environment, EnvironmentState, EnvironmentTier, Pipeline,
+        PipelineSource, PipelineStatus, Project,
+    };
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test::{self, at_hour};
+
+    #[test]
+    fn environment_drift() {
+        let mut lookup = VecLookup::default();
+        let instance = test::instance(&mut lookup);
+        let project = test::project(&mut lookup, instance, 1, "group/project");
+
+        // (sha, refname, source, created)
+        let pipelines = [
+            ("a", "main", PipelineSource::Push, 0),
+            ("b", "main", PipelineSource::Push, 2),
+            ("c", "refs/heads/main", PipelineSource::Push, 4),
+            ("d", "main", PipelineSource::Push, 6),
+            // Rebuilding a commit does not make it newer.
+            ("b", "main", PipelineSource::Web, 7),
+            ("e", "main", PipelineSource::MergeRequestEvent, 7),
+            ("f", "feature", PipelineSource::Push, 8),
+        ];
+        let pipelines = pipelines
+            .into_iter()
+            .enumerate()
+            .map(|(id, (sha, refname, source, created))| {
+                let pipeline = Pipeline::builder()
+                    .project(project)
+                    .sha(sha)
+                    .refname(Some(refname.into()))
+                    .source(source)
+                    .status(PipelineStatus::Success)
+                    .forge_id(id as u64)
+                    .url(format!("pipeline{}", id))
+                    .created_at(at_hour(created))
+                    .updated_at(at_hour(created + 1))
+                    .build()
+                    .unwrap();
+                lookup.store(pipeline)
+            })
+            .collect::<Vec<_>>();
+
+        // (name, state, tier)
+        let environments = [
+            (
+                "production",
+                EnvironmentState::Available,
+                EnvironmentTier::Production,
+            ),
+            (
+                "staging",
+                EnvironmentState::Available,
+                EnvironmentTier::Staging,
+            ),
+            (
+                "review",
+                EnvironmentState::Stopped,
+                EnvironmentTier::Development,
+            ),
+        ];
+        let environments = environments
+            .into_iter()
+            .enumerate()
+            .map(|(id, (name, state, tier))| {
+                let environment = Environment::builder()
+                    .name(name)
+                    .external_url("https://example.com")
+                    .state(state)
+                    .tier(tier)
+                    .forge_id(id as u64)
+                    .project(project)
+                    .created_at(at_hour(0))
+                    .updated_at(at_hour(0))
+                    .build()
+                    .unwrap();
+                lookup.store(environment)
+            })
+            .collect::<Vec<_>>();
+
+        // (pipeline, environment, status, finished)
+        let deployments = [
+            (0, 0, DeploymentStatus::Success, 1),
+            (2, 0, DeploymentStatus::Failed, 5),
+            (0, 1, DeploymentStatus::Success, 1),
+            (2, 1, DeploymentStatus::Success, 5),
+            (3, 2, DeploymentStatus::Success, 7),
+        ];
+        for (id, (pipeline, environment, status, finished)) in deployments.into_iter().enumerate() {
+            let deployment = Deployment::builder()
+                .pipeline(pipelines[pipeline])
+                .environment(environments[environment])
+                .forge_id(id as u64)
+                .created_at(at_hour(finished - 1))
+                .updated_at(at_hour(finished))
+                .finished_at(Some(at_hour(finished)))
+                .status(status)
+                .build()
+                .unwrap();
+            lookup.store(deployment);
+        }
+
+        let drift = super::environment_drift(&lookup, Some("main"), at_hour(10));
+
+        assert_eq!(drift.len(), 2);
+        let production = &drift[0];
+        assert_eq!(production.project, "group/project");
+        assert_eq!(production.environment, "production");
+        assert_eq!(production.tier, EnvironmentTier::Production);
+        assert_eq!(production.deployed.deployment, 0);
+        assert_eq!(production.deployed.sha, "a");
+        assert_eq!(production.deployed.finished_at, at_hour(1));
+        assert_eq!(production.pipeline, 0);
+        assert_eq!(production.head.as_deref(), Some("d"));
+        assert_eq!(production.commits_behind, 3);
+        assert_eq!(production.behind, Duration::hours(8));
+        let staging = &drift[1];
+        assert_eq!(staging.environment, "staging");
+        assert_eq!(staging.deployed.deployment, 3);
+        assert_eq!(staging.deployed.sha, "c");
+        assert_eq!(staging.commits_behind, 1);
+        assert_eq!(staging.behind, Duration::hours(4));
+
+        // Without a branch, projects without a default branch are skipped.
+        assert!(super::environment_drift(&lookup, None, at_hour(10)).is_empty());
+
+        // Projects are compared against their default branch at the time of each pipeline.
+        let mut renamed = <VecLookup as Lookup<Project<VecLookup>>>::lookup(&lookup, &project)
+            .unwrap()
+            .clone();
+        renamed.set_default_branch(Some("feature".into()), at_hour(0));
+        renamed.set_default_branch(Some("main".into()), at_hour(5));
+        lookup.store(renamed);
+        let drift = super::environment_drift(&lookup, None, at_hour(10));
+
+        assert_eq!(drift.len(), 2);
+        assert_eq!(drift[0].branch, "main");
+        // Only pipelines since the default branch became `main` count (`d` and the rebuilt `b`).
+        assert_eq!(drift[0].environment, "production");
+        assert_eq!(drift[0].commits_behind, 2);
+        assert_eq!(drift[0].head.as_deref(), Some("b"));
+    }
+}
@@ -0,0 +1,322 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, Utc};
+use ci_monitor_core::data::{Pipeline, PipelineStatus, Project};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::time_to_green::percentile;
+use crate::variables::VariableState;
+use crate::AnalysisLookup;
+
+/// Options for reporting on an experiment.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct ExperimentOptions {
+    /// The value of the variable used by the control group.
+    ///
+    /// If unset, pipelines without the variable are the control group.
+    pub control: Option<String>,
+    /// Only consider pipelines of the project with this path.
+    pub project: Option<String>,
+    /// Only consider pipelines created at or after this time.
+    pub since: Option<DateTime<Utc>>,
+}
+
+impl ExperimentOptions {
+    /// Set the value of the variable used by the control group.
+    pub fn control<V>(mut self, value: V) -> Self
+    where
+        V: Into<String>,
+    {
+        self.control = Some(value.into());
+        self
+    }
+
+    /// Only consider pipelines of a project.
+    pub fn project<P>(mut self, path: P) -> Self
+    where
+        P: Into<String>,
+    {
+        self.project = Some(path.into());
+        self
+    }
+
+    /// Only consider pipelines created at or after a time.
+    pub fn since(mut self, since: DateTime<Utc>) -> Self {
+        self.since = Some(since);
+        self
+    }
+}
+
+/// Pipelines which ran with one value of an experiment variable.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct ExperimentGroup {
+    /// The value of the variable.
+    ///
+    /// `None` for pipelines without the variable. For sensitive variables, this is a hash of the
+    /// value.
+    pub value: Option<String>,
+    /// Whether the value has been replaced by its hash.
+    pub redacted: bool,
+    /// Whether this is the control group.
+    pub control: bool,
+    /// The number of pipelines which succeeded or failed.
+    pub pipelines: usize,
+    /// The number of pipelines which failed.
+    pub failed: usize,
+    /// The fraction of pipelines which failed.
+    pub failure_rate: f64,
+    /// The median duration of the pipelines.
+    #[serde(serialize_with = "crate::serialize::seconds")]
+    #[schemars(with = "i64")]
+    pub median: Duration,
+    /// The duration which is not exceeded by 90% of the pipelines.
+    #[serde(serialize_with = "crate::serialize::seconds")]
+    #[schemars(with = "i64")]
+    pub p90: Duration,
+    /// The longest duration of the pipelines.
+    #[serde(serialize_with = "crate::serialize::seconds")]
+    #[schemars(with = "i64")]
+    pub max: Duration,
+    /// The difference in median duration from the control group.
+    #[serde(serialize_with = "crate::serialize::optional_seconds")]
+    #[schemars(with = "Option<i64>")]
+    pub median_change: Option<Duration>,
+    /// The difference in failure rate from the control group.
+    pub failure_rate_change: Option<f64>,
+}
+
+/// A comparison of pipelines grouped by the value of a variable.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct ExperimentReport {
+    /// The name of the variable.
+    pub variable: String,
+    /// The groups sorted by value (pipelines without the variable first).
+    pub groups: Vec<ExperimentGroup>,
+}
+
+impl ExperimentReport {
+    /// The control group (if any pipelines ran in it).
+    pub fn control(&self) -> Option<&ExperimentGroup> {
+        self.groups.iter().find(|group| group.control)
+    }
+}
+
+/// Pipelines gathered for a group.
+#[derive(Default)]
+struct GroupPipelines {
+    redacted: bool,
+    control: bool,
+    pipelines: usize,
+    failed: usize,
+    durations: Vec<Duration>,
+}
+
+/// Compare pipelines grouped by the value of a pipeline variable.
+///
+/// Infrastructure experiments are run by setting a variable for some pipelines; this compares
+/// the durations and failure rates of each value against the control group. Only pipelines which
+/// succeeded or failed are considered since canceled and skipped pipelines say little about the
+/// experiment. Durations are measured from the start to the end of pipelines; pipelines missing
+/// either only count towards the failure rate. Values of sensitive variables (see
+/// [`crate::is_sensitive_variable`]) are grouped by their hash.
+pub fn pipeline_experiment<L>(
+    storage: &L,
+    variable: &str,
+    options: &ExperimentOptions,
+) -> ExperimentReport
+where
+    L: AnalysisLookup<L>,
+{
+    let mut groups: BTreeMap<Option<String>, GroupPipelines> = BTreeMap::new();
+
+    for idx in <L as DiscoverableLookup<Pipeline<L>>>::all_indices(storage) {
+        let pipeline = if let Some(pipeline) = <L as Lookup<Pipeline<L>>>::lookup(storage, &idx) {
+            pipeline
+        } else {
+            continue;
+        };
+        let concluded = matches!(
+            pipeline.status,
+            PipelineStatus::Success | PipelineStatus::Failed,
+        );
+        if pipeline.cim_deleted_at.is_some()
+            || !concluded
+            || options
+                .since
+                .is_some_and(|since| pipeline.created_at < since)
+        {
+            continue;
+        }
+        if let Some(path) = options.project.as_ref() {
+            let project = <L as Lookup<Project<L>>>::lookup(storage, &pipeline.project);
+            if project.is_none_or(|project| project.instance_path != *path) {
+                continue;
+            }
+        }
+
+        let var = pipeline.variables.variables.get(variable);
+        let state = var.map(|var| VariableState::new(variable, var));
+        let group = groups
+            .entry(state.as_ref().map(|state| state.value.clone()))
+            .or_default();
+        group.redacted = state.is_some_and(|state| state.redacted);
+        // Compare raw values so that the control group may be named for sensitive variables.
+        group.control = var.map(|var| &var.value) == options.control.as_ref();
+        group.pipelines += 1;
+        if pipeline.status == PipelineStatus::Failed {
+            group.failed += 1;
+        }
+        if let (Some(started_at), Some(finished_at)) = (pipeline.started_at, pipeline.finished_at) {
+            group.durations.push(finished_at - started_at);
+        }
+    }
+
+    let mut groups = groups
+        .into_iter()
+        .map(|(value, mut group)| {
+            group.durations.sort();
+            ExperimentGroup {
+                value,
+                redacted: group.redacted,
+                control: group.control,
+                pipelines: group.pipelines,
+                failed: group.failed,
+                failure_rate: group.failed as f64 / group.pipelines as f64,
+                median: percentile(&group.durations, 50.),
+                p90: percentile(&group.durations, 90.),
+                max: group
+                    .durations
+                    .last()
+                    .copied()
+                    .unwrap_or_else(Duration::zero),
+                median_change: None,
+                failure_rate_change: None,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let control = groups
+        .iter()
+        .find(|group| group.control)
+        .map(|group| (group.median, group.failure_rate));
+    if let Some((median, failure_rate)) = control {
+        for group in &mut groups {
+            group.median_change = Some(group.median - median);
+            group.failure_rate_change = Some(group.failure_rate - failure_rate);
+        }
+    }
+
+    ExperimentReport {
+        variable: variable.into(),
+        groups,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use ci_monitor_core::data::{
+        Pipeline, PipelineSource, PipelineStatus, PipelineVariable, PipelineVariableType,
+    };
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test::{self, at_hour};
+    use crate::ExperimentOptions;
+
+    #[test]
+    fn pipeline_experiment() {
+        let mut lookup = VecLookup::default();
+        let instance = test::instance(&mut lookup);
+        let project = test::project(&mut lookup, instance, 1, "group/project");
+
+        // (value, status, created, duration in minutes)
+        let pipelines = [
+            (None, PipelineStatus::Success, 0, 30),
+            (None, PipelineStatus::Success, 1, 40),
+            (None, PipelineStatus::Failed, 2, 20),
+            (None, PipelineStatus::Success, 3, 50),
+            (Some("new"), PipelineStatus::Success, 4, 10),
+            (Some("new"), PipelineStatus::Success, 5, 20),
+            (Some("new"), PipelineStatus::Canceled, 6, 5),
+            (Some("old"), PipelineStatus::Failed, 7, 60),
+        ];
+        for (id, (value, status, created, minutes)) in pipelines.into_iter().enumerate() {
+            let variables = value
+                .map(|value| {
+                    let var = PipelineVariable::builder()
+                        .value(value)
+                        .type_(PipelineVariableType::String)
+                        .build()
+                        .unwrap();
+                    ("CACHE_BACKEND".into(), var)
+                })
+                .into_iter()
+                .collect();
+            let pipeline = Pipeline::builder()
+                .project(project)
+                .sha("0000000000000000000000000000000000000000")
+                .variables(variables)
+                .source(PipelineSource::Push)
+                .status(status)
+                .forge_id(id as u64)
+                .url(format!("pipeline{}", id))
+                .created_at(at_hour(created))
+                .updated_at(at_hour(created))
+                .started_at(Some(at_hour(created)))
+                .finished_at(Some(at_hour(created) + Duration::minutes(minutes)))
+                .build()
+                .unwrap();
+            lookup.store(pipeline);
+        }
+
+        let report =
+            super::pipeline_experiment(&lookup, "CACHE_BACKEND", &ExperimentOptions::default());
+
+        assert_eq!(report.variable, "CACHE_BACKEND");
+        let values = report
+            .groups
+            .iter()
+            .map(|group| group.value.as_deref())
+            .collect::<Vec<_>>();
+        assert_eq!(values, [None, Some("new"), Some("old")]);
+        let control = report.control().unwrap();
+        assert_eq!(control.value, None);
+        assert_eq!(control.pipelines, 4);
+        assert_eq!(control.failed, 1);
+        assert_eq!(control.failure_rate, 0.25);
+        assert_eq!(control.median, Duration::minutes(30));
+        assert_eq!(control.p90, Duration::minutes(50));
+        assert_eq!(control.median_change, Some(Duration::zero()));
+        let new = &report.groups[1];
+        assert!(!new.control);
+        assert_eq!(new.pipelines, 2);
+        assert_eq!(new.failed, 0);
+        assert_eq!(new.median, Duration::minutes(10));
+        assert_eq!(new.median_change, Some(Duration::minutes(-20)));
+        assert_eq!(new.failure_rate_change, Some(-0.25));
+
+        // A value may be used as the control group instead.
+        let options = ExperimentOptions::default()
+            .control("old")
+            .since(at_hour(4));
+        let report = super::pipeline_experiment(&lookup, "CACHE_BACKEND", &options);
+
+        assert_eq!(report.groups.len(), 2);
+        assert_eq!(report.control().unwrap().value.as_deref(), Some("old"));
+        assert_eq!(report.groups[0].failure_rate_change, Some(-1.));
+        assert_eq!(report.groups[0].median_change, Some(Duration::minutes(-50)));
+    }
+}
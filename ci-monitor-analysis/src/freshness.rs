@@ -0,0 +1,107 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use chrono::{DateTime, Utc};
+use ci_monitor_core::data::{CrawlSession, Instance};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+
+use crate::AnalysisLookup;
+
+/// How fresh and complete the stored data is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DataFreshness {
+    /// The latest revision of the store.
+    pub revision: u64,
+    /// When the latest crawl started.
+    pub last_crawl: DateTime<Utc>,
+    /// Whether the latest crawl finished without failed or canceled tasks.
+    pub complete: bool,
+    /// When the latest complete crawl started.
+    pub last_complete_crawl: Option<DateTime<Utc>>,
+    /// The URLs of the instances covered by the latest crawl.
+    pub instances: Vec<String>,
+}
+
+/// Determine how fresh the stored data is from its crawl sessions.
+///
+/// Returns `None` if no crawls have been recorded.
+pub fn data_freshness<L>(storage: &L) -> Option<DataFreshness>
+where
+    L: AnalysisLookup<L>,
+{
+    let sessions = <L as DiscoverableLookup<CrawlSession<L>>>::all_indices(storage);
+    let sessions = sessions
+        .iter()
+        .filter_map(|idx| <L as Lookup<CrawlSession<L>>>::lookup(storage, idx))
+        .collect::<Vec<_>>();
+
+    let latest = sessions.iter().max_by_key(|session| session.revision)?;
+    let last_complete_crawl = sessions
+        .iter()
+        .filter(|session| session.is_complete())
+        .max_by_key(|session| session.revision)
+        .map(|session| session.started_at);
+    let instances = latest
+        .instances
+        .iter()
+        .filter_map(|idx| <L as Lookup<Instance>>::lookup(storage, idx))
+        .map(|instance| instance.url.clone())
+        .collect();
+
+    Some(DataFreshness {
+        revision: latest.revision,
+        last_crawl: latest.started_at,
+        complete: latest.is_complete(),
+        last_complete_crawl,
+        instances,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use ci_monitor_core::data::CrawlSession;
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test::{self, at};
+
+    #[test]
+    fn data_freshness_empty() {
+        let lookup = VecLookup::default();
+
+        assert_eq!(super::data_freshness(&lookup), None);
+    }
+
+    #[test]
+    fn data_freshness() {
+        let mut lookup = VecLookup::default();
+        let instance = test::instance(&mut lookup);
+
+        let sessions = [(1, 0, 0), (2, 60, 3)];
+        for (revision, start, failed) in sessions {
+            let session = CrawlSession::builder()
+                .started_at(at(start))
+                .finished_at(Some(at(start + 10)))
+                .tasks_executed(10)
+                .tasks_failed(failed)
+                .instances(vec![instance])
+                .revision(revision)
+                .build()
+                .unwrap();
+            lookup.store(session);
+        }
+
+        let freshness = super::data_freshness(&lookup).unwrap();
+
+        assert_eq!(freshness.revision, 2);
+        assert_eq!(freshness.last_crawl, at(60));
+        assert!(!freshness.complete);
+        assert_eq!(freshness.last_complete_crawl, Some(at(0)));
+        assert_eq!(freshness.instances, ["url"]);
+    }
+}
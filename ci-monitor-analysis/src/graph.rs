@@ -0,0 +1,431 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use ci_monitor_core::data::{Deployment, Environment, Job, Pipeline};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+
+use crate::AnalysisLookup;
+
+/// Text formats for rendering entity graphs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum GraphFormat {
+    /// GraphViz `dot` syntax.
+    GraphViz,
+    /// Mermaid flowchart syntax.
+    Mermaid,
+}
+
+/// The kind of entity a node represents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum GraphNodeKind {
+    /// A pipeline.
+    Pipeline,
+    /// A job.
+    Job,
+    /// A deployment.
+    Deployment,
+    /// An environment.
+    Environment,
+}
+
+impl GraphNodeKind {
+    fn prefix(self) -> &'static str {
+        match self {
+            Self::Pipeline => "pipeline",
+            Self::Job => "job",
+            Self::Deployment => "deployment",
+            Self::Environment => "environment",
+        }
+    }
+
+    fn graphviz_shape(self) -> &'static str {
+        match self {
+            Self::Pipeline => "box",
+            Self::Job => "ellipse",
+            Self::Deployment => "diamond",
+            Self::Environment => "cylinder",
+        }
+    }
+
+    fn mermaid_shape(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Pipeline => ("[", "]"),
+            Self::Job => ("(", ")"),
+            Self::Deployment => ("{", "}"),
+            Self::Environment => ("[(", ")]"),
+        }
+    }
+}
+
+/// An entity within a graph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct GraphNode {
+    /// The identifier of the node within the graph.
+    pub id: String,
+    /// The kind of entity.
+    pub kind: GraphNodeKind,
+    /// A description of the entity.
+    pub label: String,
+}
+
+/// A reference from one entity to another.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct GraphEdge {
+    /// The identifier of the referring node.
+    pub from: String,
+    /// The identifier of the referenced node.
+    pub to: String,
+    /// A description of the reference.
+    pub label: &'static str,
+}
+
+/// A graph of entities linked to a pipeline.
+#[derive(Debug, Clone, Default)]
+pub struct EntityGraph {
+    /// The entities in the graph.
+    pub nodes: Vec<GraphNode>,
+    /// References between the entities.
+    pub edges: Vec<GraphEdge>,
+    seen: BTreeSet<String>,
+}
+
+impl EntityGraph {
+    /// Build the graph of a pipeline.
+    ///
+    /// The graph includes child pipelines, jobs, deployments, and the environments deployed to.
+    /// Returns `None` if the pipeline is not in the storage.
+    pub fn pipeline<L>(storage: &L, pipeline: &<L as Lookup<Pipeline<L>>>::Index) -> Option<Self>
+    where
+        L: AnalysisLookup<L>,
+        <L as Lookup<Pipeline<L>>>::Index: PartialEq,
+    {
+        let mut graph = Self::default();
+        graph.add_pipeline(storage, pipeline)?;
+        Some(graph)
+    }
+
+    pub(crate) fn add_node(
+        &mut self,
+        kind: GraphNodeKind,
+        forge_id: u64,
+        label: String,
+    ) -> (String, bool) {
+        let id = format!("{}_{}", kind.prefix(), forge_id);
+        let is_new = self.seen.insert(id.clone());
+        if is_new {
+            self.nodes.push(GraphNode {
+                id: id.clone(),
+                kind,
+                label,
+            });
+        }
+        (id, is_new)
+    }
+
+    pub(crate) fn add_edge(&mut self, from: &str, to: &str, label: &'static str) {
+        self.edges.push(GraphEdge {
+            from: from.into(),
+            to: to.into(),
+            label,
+        });
+    }
+
+    fn add_pipeline<L>(
+        &mut self,
+        storage: &L,
+        idx: &<L as Lookup<Pipeline<L>>>::Index,
+    ) -> Option<String>
+    where
+        L: AnalysisLookup<L>,
+        <L as Lookup<Pipeline<L>>>::Index: PartialEq,
+    {
+        let pipeline = <L as Lookup<Pipeline<L>>>::lookup(storage, idx)?;
+        let mut label = format!("pipeline #{}", pipeline.forge_id);
+        if let Some(refname) = pipeline.refname.as_ref() {
+            let _ = write!(label, " ({})", refname);
+        }
+        let _ = write!(label, "\n{:?}", pipeline.status);
+        let (id, is_new) = self.add_node(GraphNodeKind::Pipeline, pipeline.forge_id, label);
+        if !is_new {
+            return Some(id);
+        }
+
+        for job_idx in <L as DiscoverableLookup<Job<L>>>::all_indices(storage) {
+            let job = if let Some(job) = <L as Lookup<Job<L>>>::lookup(storage, &job_idx) {
+                job
+            } else {
+                continue;
+            };
+            if job.pipeline != *idx || job.cim_deleted_at.is_some() {
+                continue;
+            }
+
+            let label = format!("{}: {}\n{:?}", job.stage, job.name, job.state);
+            let (job_id, _) = self.add_node(GraphNodeKind::Job, job.forge_id, label);
+            self.add_edge(&id, &job_id, "job");
+
+            let deployment_id = job
+                .deployment
+                .as_ref()
+                .and_then(|deployment| self.add_deployment(storage, deployment));
+            if let Some(deployment_id) = deployment_id {
+                self.add_edge(&job_id, &deployment_id, "deploys");
+            }
+        }
+
+        for child_idx in <L as DiscoverableLookup<Pipeline<L>>>::all_indices(storage) {
+            let is_child =
+                <L as Lookup<Pipeline<L>>>::lookup(storage, &child_idx).is_some_and(|child| {
+                    child.parent_pipeline.as_ref() == Some(idx) && child.cim_deleted_at.is_none()
+                });
+            if !is_child {
+                continue;
+            }
+
+            if let Some(child_id) = self.add_pipeline(storage, &child_idx) {
+                self.add_edge(&id, &child_id, "child");
+            }
+        }
+
+        Some(id)
+    }
+
+    fn add_deployment<L>(
+        &mut self,
+        storage: &L,
+        idx: &<L as Lookup<Deployment<L>>>::Index,
+    ) -> Option<String>
+    where
+        L: AnalysisLookup<L>,
+    {
+        let deployment = <L as Lookup<Deployment<L>>>::lookup(storage, idx)?;
+        let label = format!(
+            "deployment #{}\n{:?}",
+            deployment.forge_id, deployment.status,
+        );
+        let (id, is_new) = self.add_node(GraphNodeKind::Deployment, deployment.forge_id, label);
+        if !is_new {
+            return Some(id);
+        }
+
+        if let Some(environment) =
+            <L as Lookup<Environment<L>>>::lookup(storage, &deployment.environment)
+        {
+            let (env_id, _) = self.add_node(
+                GraphNodeKind::Environment,
+                environment.forge_id,
+                environment.name.clone(),
+            );
+            self.add_edge(&id, &env_id, "environment");
+        }
+
+        Some(id)
+    }
+
+    /// Render the graph as text.
+    pub fn render(&self, format: GraphFormat) -> String {
+        match format {
+            GraphFormat::GraphViz => self.render_graphviz(),
+            GraphFormat::Mermaid => self.render_mermaid(),
+        }
+    }
+
+    fn render_graphviz(&self) -> String {
+        let escape = |s: &str| {
+            s.replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n")
+        };
+
+        let mut out = String::from("digraph pipeline {\n");
+        for node in &self.nodes {
+            let _ = writeln!(
+                out,
+                "    {} [shape={}, label=\"{}\"];",
+                node.id,
+                node.kind.graphviz_shape(),
+                escape(&node.label),
+            );
+        }
+        for edge in &self.edges {
+            let _ = writeln!(
+                out,
+                "    {} -> {} [label=\"{}\"];",
+                edge.from, edge.to, edge.label,
+            );
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    fn render_mermaid(&self) -> String {
+        let escape = |s: &str| {
+            s.replace('#', "#35;")
+                .replace('"', "#quot;")
+                .replace('\n', "<br>")
+        };
+
+        let mut out = String::from("flowchart TD\n");
+        for node in &self.nodes {
+            let (open, close) = node.kind.mermaid_shape();
+            let _ = writeln!(
+                out,
+                "    {}{}\"{}\"{}",
+                node.id,
+                open,
+                escape(&node.label),
+                close,
+            );
+        }
+        for edge in &self.edges {
+            let _ = writeln!(out, "    {} -->|{}| {}", edge.from, edge.label, edge.to);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use ci_monitor_core::data::{
+        Deployment, DeploymentStatus, Environment, EnvironmentState, EnvironmentTier, Job,
+        JobState, Pipeline, PipelineSource, PipelineStatus,
+    };
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test;
+    use crate::{EntityGraph, GraphFormat};
+
+    #[test]
+    fn pipeline_graph() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut lookup = VecLookup::default();
+        let instance = test::instance(&mut lookup);
+        let user = test::user(&mut lookup, instance, 0);
+        let project = test::project(&mut lookup, instance, 0, "project");
+        let pipeline = |id, parent| {
+            Pipeline::builder()
+                .project(project)
+                .sha("0000000000000000000000000000000000000000")
+                .source(PipelineSource::Push)
+                .status(PipelineStatus::Success)
+                .forge_id(id)
+                .parent_pipeline(parent)
+                .url("url")
+                .created_at(now)
+                .updated_at(now)
+                .build()
+                .unwrap()
+        };
+        let parent = lookup.store(pipeline(1, None));
+        let child = lookup.store(pipeline(2, Some(parent)));
+        // Unrelated pipelines are not included.
+        lookup.store(pipeline(3, None));
+
+        let environment = Environment::builder()
+            .name("production")
+            .state(EnvironmentState::Available)
+            .tier(EnvironmentTier::Production)
+            .forge_id(4)
+            .project(project)
+            .created_at(now)
+            .updated_at(now)
+            .build()
+            .unwrap();
+        let environment = lookup.store(environment);
+        let deployment = Deployment::builder()
+            .pipeline(child)
+            .environment(environment)
+            .forge_id(5)
+            .created_at(now)
+            .updated_at(now)
+            .status(DeploymentStatus::Success)
+            .build()
+            .unwrap();
+        let deployment = lookup.store(deployment);
+
+        let jobs = [
+            (10, "build", parent, None),
+            (11, "deploy", child, Some(deployment)),
+        ];
+        for (id, name, pipeline, deployment) in jobs {
+            let job = Job::builder()
+                .user(user)
+                .name(name)
+                .stage("test")
+                .state(JobState::Success)
+                .created_at(now)
+                .forge_id(id)
+                .pipeline(pipeline)
+                .deployment(deployment)
+                .build()
+                .unwrap();
+            lookup.store(job);
+        }
+
+        let graph = EntityGraph::pipeline(&lookup, &parent).unwrap();
+
+        assert_eq!(
+            graph
+                .nodes
+                .iter()
+                .map(|node| node.id.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "pipeline_1",
+                "job_10",
+                "pipeline_2",
+                "job_11",
+                "deployment_5",
+                "environment_4",
+            ],
+        );
+        assert_eq!(
+            graph.render(GraphFormat::GraphViz),
+            concat!(
+                "digraph pipeline {\n",
+                "    pipeline_1 [shape=box, label=\"pipeline #1\\nSuccess\"];\n",
+                "    job_10 [shape=ellipse, label=\"test: build\\nSuccess\"];\n",
+                "    pipeline_2 [shape=box, label=\"pipeline #2\\nSuccess\"];\n",
+                "    job_11 [shape=ellipse, label=\"test: deploy\\nSuccess\"];\n",
+                "    deployment_5 [shape=diamond, label=\"deployment #5\\nSuccess\"];\n",
+                "    environment_4 [shape=cylinder, label=\"production\"];\n",
+                "    pipeline_1 -> job_10 [label=\"job\"];\n",
+                "    pipeline_2 -> job_11 [label=\"job\"];\n",
+                "    deployment_5 -> environment_4 [label=\"environment\"];\n",
+                "    job_11 -> deployment_5 [label=\"deploys\"];\n",
+                "    pipeline_1 -> pipeline_2 [label=\"child\"];\n",
+                "}\n",
+            ),
+        );
+        assert_eq!(
+            graph.render(GraphFormat::Mermaid),
+            concat!(
+                "flowchart TD\n",
+                "    pipeline_1[\"pipeline #35;1<br>Success\"]\n",
+                "    job_10(\"test: build<br>Success\")\n",
+                "    pipeline_2[\"pipeline #35;2<br>Success\"]\n",
+                "    job_11(\"test: deploy<br>Success\")\n",
+                "    deployment_5{\"deployment #35;5<br>Success\"}\n",
+                "    environment_4[(\"production\")]\n",
+                "    pipeline_1 -->|job| job_10\n",
+                "    pipeline_2 -->|job| job_11\n",
+                "    deployment_5 -->|environment| environment_4\n",
+                "    job_11 -->|deploys| deployment_5\n",
+                "    pipeline_1 -->|child| pipeline_2\n",
+            ),
+        );
+    }
+}
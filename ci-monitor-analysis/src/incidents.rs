@@ -0,0 +1,403 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use ci_monitor_core::data::{
+    Deployment, DeploymentStatus, Environment, EnvironmentTier, Job, JobState, MergeRequest,
+    Pipeline, Project, User,
+};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::{AnalysisLookup, NotificationRouter, Route};
+
+/// A successful deployment into an environment.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct DeployedRevision {
+    /// The ID of the deployment.
+    pub deployment: u64,
+    /// The commit which was deployed.
+    pub sha: String,
+    /// When the deployment completed.
+    pub finished_at: DateTime<Utc>,
+}
+
+/// Everything known about a failed deployment in one place for whoever is on call.
+///
+/// Links the deployment back to the change which triggered it and forward to the environment it
+/// affected.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct DeploymentIncident {
+    /// The ID of the deployment.
+    pub deployment: u64,
+    /// When the deployment failed.
+    pub failed_at: DateTime<Utc>,
+    /// The path of the project on its instance.
+    pub project: String,
+
+    // The affected environment.
+    /// The name of the environment.
+    pub environment: String,
+    /// The external URL of the environment.
+    pub environment_url: String,
+    /// The tier of the environment.
+    ///
+    /// One of `production`, `staging`, `testing`, `development`, or `other`.
+    #[serde(serialize_with = "crate::serialize::name")]
+    #[schemars(with = "String")]
+    pub tier: EnvironmentTier,
+    /// The last successful deployment into the environment before the failure.
+    ///
+    /// This is the revision the environment is likely still running (or may be rolled back to).
+    pub last_success: Option<DeployedRevision>,
+
+    // The originating change.
+    /// The ID of the pipeline which created the deployment.
+    pub pipeline: u64,
+    /// The URL of the pipeline.
+    pub pipeline_url: String,
+    /// The commit being deployed.
+    pub sha: String,
+    /// The ref of the pipeline.
+    pub refname: Option<String>,
+    /// The URL of the merge request of the pipeline.
+    pub merge_request: Option<String>,
+    /// The title of the merge request of the pipeline.
+    pub merge_request_title: Option<String>,
+    /// The handle of the author of the merge request (or of whoever triggered the pipeline).
+    pub author: Option<String>,
+
+    // The failure.
+    /// The names of the jobs deploying into the environment.
+    pub deploy_jobs: Vec<String>,
+    /// The names of the failed jobs of the pipeline.
+    pub failed_jobs: Vec<String>,
+    /// Where to deliver the notification.
+    pub routes: Vec<Route>,
+}
+
+/// Collect the context of deployments which failed since a given time.
+///
+/// Notifications are routed to the owners of the deploying jobs (or of the project if the
+/// deploying jobs are not known). When a job has been retried, only the latest attempt is
+/// considered. Results are sorted by when the deployments failed.
+pub fn deployment_incidents<L>(
+    storage: &L,
+    router: &NotificationRouter,
+    since: DateTime<Utc>,
+) -> Vec<DeploymentIncident>
+where
+    L: AnalysisLookup<L>,
+{
+    let mut failed = Vec::new();
+    let mut successes: BTreeMap<u64, Vec<_>> = BTreeMap::new();
+
+    let deployments = <L as DiscoverableLookup<Deployment<L>>>::all_indices(storage);
+    for idx in &deployments {
+        let deployment =
+            if let Some(deployment) = <L as Lookup<Deployment<L>>>::lookup(storage, idx) {
+                deployment
+            } else {
+                continue;
+            };
+        if deployment.cim_deleted_at.is_some() {
+            continue;
+        }
+        let environment = if let Some(environment) =
+            <L as Lookup<Environment<L>>>::lookup(storage, &deployment.environment)
+        {
+            environment
+        } else {
+            continue;
+        };
+        let finished_at = deployment.finished_at.unwrap_or(deployment.updated_at);
+
+        match deployment.status {
+            DeploymentStatus::Success => {
+                successes
+                    .entry(environment.forge_id)
+                    .or_default()
+                    .push((finished_at, deployment));
+            },
+            DeploymentStatus::Failed if since <= finished_at => {
+                failed.push((finished_at, deployment, environment));
+            },
+            _ => (),
+        }
+    }
+    if failed.is_empty() {
+        return Vec::new();
+    }
+
+    // Find the latest attempt of each job by name for each pipeline with a failed deployment.
+    let pipelines = failed
+        .iter()
+        .filter_map(|(_, deployment, _)| {
+            <L as Lookup<Pipeline<L>>>::lookup(storage, &deployment.pipeline)
+        })
+        .map(|pipeline| pipeline.forge_id)
+        .collect::<Vec<_>>();
+    let mut pipeline_jobs: BTreeMap<u64, BTreeMap<&str, &Job<L>>> = BTreeMap::new();
+    let job_indices = <L as DiscoverableLookup<Job<L>>>::all_indices(storage);
+    for idx in &job_indices {
+        let job = if let Some(job) = <L as Lookup<Job<L>>>::lookup(storage, idx) {
+            job
+        } else {
+            continue;
+        };
+        let pipeline =
+            if let Some(pipeline) = <L as Lookup<Pipeline<L>>>::lookup(storage, &job.pipeline) {
+                pipeline
+            } else {
+                continue;
+            };
+        if !pipelines.contains(&pipeline.forge_id) {
+            continue;
+        }
+
+        let latest = pipeline_jobs
+            .entry(pipeline.forge_id)
+            .or_default()
+            .entry(job.name.as_str())
+            .or_insert(job);
+        if latest.forge_id < job.forge_id {
+            *latest = job;
+        }
+    }
+
+    let mut incidents = Vec::new();
+    for (failed_at, deployment, environment) in failed {
+        let pipeline = <L as Lookup<Pipeline<L>>>::lookup(storage, &deployment.pipeline);
+        let pipeline = if let Some(pipeline) = pipeline {
+            pipeline
+        } else {
+            continue;
+        };
+        let project =
+            if let Some(project) = <L as Lookup<Project<L>>>::lookup(storage, &pipeline.project) {
+                project
+            } else {
+                continue;
+            };
+
+        let merge_request = pipeline
+            .merge_request
+            .as_ref()
+            .and_then(|mr| <L as Lookup<MergeRequest<L>>>::lookup(storage, mr));
+        let author = merge_request
+            .map(|mr| &mr.author)
+            .or(pipeline.user.as_ref())
+            .and_then(|user| <L as Lookup<User<L>>>::lookup(storage, user))
+            .map(|user| user.handle.clone());
+
+        let jobs = pipeline_jobs.get(&pipeline.forge_id);
+        let deploy_jobs = jobs
+            .into_iter()
+            .flat_map(|jobs| jobs.iter())
+            .filter(|(_, job)| {
+                job.deployment
+                    .as_ref()
+                    .and_then(|idx| <L as Lookup<Deployment<L>>>::lookup(storage, idx))
+                    .is_some_and(|job_deployment| job_deployment.forge_id == deployment.forge_id)
+            })
+            .map(|(&name, _)| name)
+            .collect::<Vec<_>>();
+        let failed_jobs = jobs
+            .into_iter()
+            .flat_map(|jobs| jobs.iter())
+            .filter(|(_, job)| job.state == JobState::Failed && !job.allow_failure)
+            .map(|(&name, _)| String::from(name))
+            .collect();
+
+        let last_success = successes
+            .get(&environment.forge_id)
+            .into_iter()
+            .flatten()
+            .filter(|(finished_at, _)| *finished_at < failed_at)
+            .max_by_key(|(finished_at, _)| *finished_at)
+            .and_then(|&(finished_at, success)| {
+                <L as Lookup<Pipeline<L>>>::lookup(storage, &success.pipeline).map(|pipeline| {
+                    DeployedRevision {
+                        deployment: success.forge_id,
+                        sha: pipeline.sha.clone(),
+                        finished_at,
+                    }
+                })
+            });
+
+        let routes = router.route(&project.instance_path, &deploy_jobs, failed_at);
+
+        incidents.push(DeploymentIncident {
+            deployment: deployment.forge_id,
+            failed_at,
+            project: project.instance_path.clone(),
+            environment: environment.name.clone(),
+            environment_url: environment.external_url.clone(),
+            tier: environment.tier,
+            last_success,
+            pipeline: pipeline.forge_id,
+            pipeline_url: pipeline.url.clone(),
+            sha: pipeline.sha.clone(),
+            refname: pipeline.refname.clone(),
+            merge_request: merge_request.map(|mr| mr.url.clone()),
+            merge_request_title: merge_request.map(|mr| mr.title.clone()),
+            author,
+            deploy_jobs: deploy_jobs.into_iter().map(Into::into).collect(),
+            failed_jobs,
+            routes,
+        });
+    }
+
+    incidents.sort_by_key(|incident| incident.failed_at);
+    incidents
+}
+
+#[cfg(test)]
+mod tests {
+    use ci_monitor_core::data::{
+        Deployment, DeploymentStatus, Environment, EnvironmentState, EnvironmentTier, Job,
+        JobState, MergeRequest, MergeRequestStatus, Pipeline, PipelineSource, PipelineStatus, User,
+    };
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test::{self, at_hour};
+    use crate::{NotificationRouter, OwnershipRule, RouteKind};
+
+    #[test]
+    fn deployment_incidents() {
+        let mut lookup = VecLookup::default();
+        let instance = test::instance(&mut lookup);
+        let user = User::builder()
+            .forge_id(0)
+            .instance(instance)
+            .handle("dev")
+            .build()
+            .unwrap();
+        let user = lookup.store(user);
+        let project = test::project(&mut lookup, instance, 1, "group/project");
+        let environment = Environment::builder()
+            .name("production")
+            .external_url("https://example.com")
+            .state(EnvironmentState::Available)
+            .tier(EnvironmentTier::Production)
+            .forge_id(1)
+            .project(project)
+            .created_at(at_hour(0))
+            .updated_at(at_hour(0))
+            .build()
+            .unwrap();
+        let environment = lookup.store(environment);
+        let mr = MergeRequest::builder()
+            .id(1)
+            .source_project(project)
+            .target_project(project)
+            .forge_id(1)
+            .title("Add a feature")
+            .state(MergeRequestStatus::Merged)
+            .author(user)
+            .url("mr1")
+            .build()
+            .unwrap();
+        let mr = lookup.store(mr);
+
+        // (sha, merge request, deployment status, finished)
+        let deployments = [
+            ("good", None, DeploymentStatus::Success, 1),
+            ("bad", Some(mr), DeploymentStatus::Failed, 3),
+            ("newer", None, DeploymentStatus::Success, 5),
+            ("old", None, DeploymentStatus::Failed, -5),
+        ];
+        for (id, (sha, mr, status, finished)) in deployments.into_iter().enumerate() {
+            let id = id as u64;
+            let pipeline = Pipeline::builder()
+                .project(project)
+                .sha(sha)
+                .refname(Some("main".into()))
+                .source(PipelineSource::Push)
+                .status(PipelineStatus::Failed)
+                .merge_request(mr)
+                .forge_id(id)
+                .url(format!("pipeline{}", id))
+                .created_at(at_hour(finished - 1))
+                .updated_at(at_hour(finished))
+                .build()
+                .unwrap();
+            let pipeline = lookup.store(pipeline);
+            let deployment = Deployment::builder()
+                .pipeline(pipeline)
+                .environment(environment)
+                .forge_id(id)
+                .created_at(at_hour(finished - 1))
+                .updated_at(at_hour(finished))
+                .finished_at(Some(at_hour(finished)))
+                .status(status)
+                .build()
+                .unwrap();
+            let deployment = lookup.store(deployment);
+
+            // (name, state, deployment)
+            let jobs = [
+                ("test", JobState::Failed, None),
+                ("deploy", JobState::Failed, Some(deployment)),
+            ];
+            for (job_id, (name, state, deployment)) in jobs.into_iter().enumerate() {
+                let job = Job::builder()
+                    .user(user)
+                    .name(name)
+                    .state(state)
+                    .created_at(at_hour(finished - 1))
+                    .deployment(deployment)
+                    .forge_id(10 * id + job_id as u64)
+                    .pipeline(pipeline)
+                    .build()
+                    .unwrap();
+                lookup.store(job);
+            }
+        }
+
+        let router = NotificationRouter::new("#ops").rules([
+            OwnershipRule::new("group/*", ["dev"]).unwrap(),
+            OwnershipRule::new("group/*", ["ops"])
+                .unwrap()
+                .jobs("deploy")
+                .unwrap(),
+        ]);
+        let incidents = super::deployment_incidents(&lookup, &router, at_hour(0));
+
+        assert_eq!(incidents.len(), 1);
+        let incident = &incidents[0];
+        assert_eq!(incident.deployment, 1);
+        assert_eq!(incident.failed_at, at_hour(3));
+        assert_eq!(incident.project, "group/project");
+        assert_eq!(incident.environment, "production");
+        assert_eq!(incident.environment_url, "https://example.com");
+        assert_eq!(incident.tier, EnvironmentTier::Production);
+        let last_success = incident.last_success.as_ref().unwrap();
+        assert_eq!(last_success.deployment, 0);
+        assert_eq!(last_success.sha, "good");
+        assert_eq!(incident.pipeline_url, "pipeline1");
+        assert_eq!(incident.sha, "bad");
+        assert_eq!(incident.refname.as_deref(), Some("main"));
+        assert_eq!(incident.merge_request.as_deref(), Some("mr1"));
+        assert_eq!(
+            incident.merge_request_title.as_deref(),
+            Some("Add a feature")
+        );
+        assert_eq!(incident.author.as_deref(), Some("dev"));
+        assert_eq!(incident.deploy_jobs, ["deploy"]);
+        assert_eq!(incident.failed_jobs, ["deploy", "test"]);
+        assert_eq!(incident.routes.len(), 1);
+        assert_eq!(incident.routes[0].channel, "ops");
+        assert_eq!(incident.routes[0].kind, RouteKind::Immediate);
+    }
+}
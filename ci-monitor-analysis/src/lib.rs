@@ -0,0 +1,237 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! CI monitoring analysis
+//!
+//! This crate provides analyses of stored CI monitoring data in order to answer questions about
+//! the health and capacity of a CI setup.
+
+#![warn(missing_docs)]
+
+mod actions_usage;
+mod alerts;
+mod api_usage;
+mod artifact_graph;
+mod autoscaler;
+mod backfill;
+mod capacity;
+mod ci_config;
+mod combined;
+mod concurrency;
+mod cost;
+mod drift;
+mod experiments;
+mod freshness;
+mod graph;
+mod incidents;
+mod log_clusters;
+mod lookup;
+mod manual;
+mod merge_latency;
+mod os_lifecycle;
+mod platforms;
+mod policy;
+mod push_coverage;
+mod quarantine;
+mod queue_time;
+mod reconciliation;
+mod releases;
+mod retention;
+mod routing;
+mod saturation;
+mod sections;
+mod serialize;
+mod services;
+mod tag_routing;
+mod time_to_green;
+mod timeline;
+mod variables;
+
+#[cfg(test)]
+mod test;
+
+pub use self::actions_usage::parse_actions_usage;
+pub use self::actions_usage::reconcile_actions_usage;
+pub use self::actions_usage::ActionsUsage;
+pub use self::actions_usage::ActionsUsageError;
+pub use self::actions_usage::UsageReconciliation;
+
+pub use self::alerts::evaluate_alerts;
+pub use self::alerts::Alert;
+pub use self::alerts::AlertCondition;
+pub use self::alerts::AlertEntity;
+pub use self::alerts::AlertError;
+pub use self::alerts::AlertRule;
+
+pub use self::api_usage::api_usage_history;
+pub use self::api_usage::ApiUsageBucket;
+pub use self::api_usage::UsagePeriod;
+
+pub use self::artifact_graph::ArtifactEdge;
+pub use self::artifact_graph::ArtifactGraph;
+pub use self::artifact_graph::ArtifactGraphJob;
+
+pub use self::autoscaler::autoscaler_activity;
+pub use self::autoscaler::AutoscalerActivity;
+
+pub use self::backfill::log_backfill;
+pub use self::backfill::LogBackfill;
+pub use self::backfill::MissingLog;
+
+pub use self::capacity::historical_load;
+pub use self::capacity::runner_fleets;
+pub use self::capacity::RunnerFleet;
+pub use self::capacity::SimulatedJob;
+pub use self::capacity::SimulatedRunner;
+pub use self::capacity::SimulationResult;
+
+pub use self::ci_config::ci_config_changes;
+pub use self::ci_config::ConfigChange;
+pub use self::ci_config::ConfigDiff;
+pub use self::ci_config::DiffLine;
+pub use self::ci_config::PipelineRunStats;
+
+pub use self::combined::combined_report;
+pub use self::combined::CombinedReport;
+pub use self::combined::FailureRate;
+pub use self::combined::RunnerHealth;
+pub use self::combined::StoreReport;
+
+pub use self::concurrency::job_concurrency;
+pub use self::concurrency::ConcurrencyCurve;
+pub use self::concurrency::ConcurrencyKey;
+pub use self::concurrency::ConcurrencyPeak;
+
+pub use self::cost::merge_request_costs;
+pub use self::cost::MergeRequestCost;
+pub use self::cost::MergeRequestCostSummary;
+
+pub use self::drift::environment_drift;
+pub use self::drift::EnvironmentDrift;
+
+pub use self::experiments::pipeline_experiment;
+pub use self::experiments::ExperimentGroup;
+pub use self::experiments::ExperimentOptions;
+pub use self::experiments::ExperimentReport;
+
+pub use self::freshness::data_freshness;
+pub use self::freshness::DataFreshness;
+
+pub use self::graph::EntityGraph;
+pub use self::graph::GraphEdge;
+pub use self::graph::GraphFormat;
+pub use self::graph::GraphNode;
+pub use self::graph::GraphNodeKind;
+
+pub use self::incidents::deployment_incidents;
+pub use self::incidents::DeployedRevision;
+pub use self::incidents::DeploymentIncident;
+
+pub use self::log_clusters::cluster_logs;
+pub use self::log_clusters::failure_log_clusters;
+pub use self::log_clusters::FailureClusters;
+pub use self::log_clusters::LogCluster;
+pub use self::log_clusters::LogClusterOptions;
+
+pub use self::lookup::AnalysisLookup;
+
+pub use self::manual::slowest_manual_gates;
+pub use self::manual::ManualGate;
+
+pub use self::merge_latency::merge_request_latencies;
+pub use self::merge_latency::MergeRequestLatency;
+
+pub use self::os_lifecycle::end_of_life_hosts;
+pub use self::os_lifecycle::EndOfLifeHost;
+pub use self::os_lifecycle::OsCatalog;
+
+pub use self::platforms::platform_matrix;
+pub use self::platforms::PlatformCell;
+pub use self::platforms::PlatformKey;
+
+pub use self::policy::required_job_violations;
+pub use self::policy::PolicyError;
+pub use self::policy::PolicyViolation;
+pub use self::policy::PolicyViolationKind;
+pub use self::policy::ProjectPolicyViolations;
+pub use self::policy::RequiredJob;
+
+pub use self::push_coverage::push_coverage;
+pub use self::push_coverage::NoPipelineReason;
+pub use self::push_coverage::ProjectPushCoverage;
+pub use self::push_coverage::PushCoverage;
+pub use self::push_coverage::UnbuiltPush;
+
+pub use self::quarantine::quarantine_report;
+pub use self::quarantine::Quarantine;
+pub use self::quarantine::QuarantineError;
+pub use self::quarantine::QuarantineStatus;
+pub use self::quarantine::QuarantinedJob;
+
+pub use self::queue_time::queue_time_breakdown;
+pub use self::queue_time::QueueTimeBreakdown;
+
+pub use self::reconciliation::pipeline_divergence;
+pub use self::reconciliation::pipeline_reconciliation_sample;
+pub use self::reconciliation::PipelineDivergence;
+pub use self::reconciliation::SampledPipeline;
+
+pub use self::releases::release_history;
+pub use self::releases::ProjectReleases;
+pub use self::releases::ReleasePipelineState;
+pub use self::releases::ReleaseSummary;
+
+pub use self::retention::artifact_retention;
+pub use self::retention::ArtifactRetention;
+pub use self::retention::ArtifactRetentionOptions;
+pub use self::retention::ArtifactStorage;
+pub use self::retention::ProjectArtifactStorage;
+
+pub use self::routing::failure_notifications;
+pub use self::routing::parse_owners;
+pub use self::routing::parse_utc_offset;
+pub use self::routing::FailureNotification;
+pub use self::routing::NotificationRouter;
+pub use self::routing::Owner;
+pub use self::routing::OwnershipRule;
+pub use self::routing::QuietHours;
+pub use self::routing::Route;
+pub use self::routing::RouteKind;
+pub use self::routing::RoutingError;
+
+pub use self::saturation::runner_saturation;
+pub use self::saturation::RunnerSaturation;
+pub use self::saturation::SaturationSample;
+
+pub use self::sections::section_timings;
+pub use self::sections::SectionTiming;
+
+pub use self::services::service_reports;
+pub use self::services::ServiceError;
+pub use self::services::ServiceMap;
+pub use self::services::ServiceReport;
+
+pub use self::tag_routing::tag_routing_report;
+pub use self::tag_routing::JobTagRouting;
+pub use self::tag_routing::TagPool;
+pub use self::tag_routing::TagRoutingReport;
+
+pub use self::time_to_green::time_to_green;
+pub use self::time_to_green::MergeRequestTimeToGreen;
+pub use self::time_to_green::ProjectTimeToGreen;
+pub use self::time_to_green::TimeToGreen;
+
+pub use self::timeline::event_timeline;
+pub use self::timeline::Timeline;
+pub use self::timeline::TimelineEvent;
+pub use self::timeline::TimelineEventKind;
+
+pub use self::variables::compare_pipeline_variables;
+pub use self::variables::diff_pipeline_variables;
+pub use self::variables::is_sensitive_variable;
+pub use self::variables::VariableChange;
+pub use self::variables::VariableComparisonError;
+pub use self::variables::VariableState;
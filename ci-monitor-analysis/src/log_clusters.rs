@@ -0,0 +1,376 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::cmp::Reverse;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet};
+use std::hash::{Hash, Hasher};
+
+use ci_monitor_core::data::{ArtifactKind, Job, JobArtifact, JobState};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::{BlobPersistence, DiscoverableLookup};
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::AnalysisLookup;
+
+/// The number of hashes in a log's signature.
+const SIGNATURE_SIZE: usize = 64;
+/// The number of words in each shingle.
+const SHINGLE_SIZE: usize = 3;
+
+/// Options for clustering job logs.
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub struct LogClusterOptions {
+    /// The number of lines from the end of each log to compare.
+    pub tail_lines: usize,
+    /// The estimated similarity (between `0` and `1`) required to join a cluster.
+    pub threshold: f64,
+    /// The number of lines of each cluster's representative log to keep as an excerpt.
+    pub excerpt_lines: usize,
+}
+
+impl Default for LogClusterOptions {
+    fn default() -> Self {
+        Self {
+            tail_lines: 50,
+            threshold: 0.5,
+            excerpt_lines: 10,
+        }
+    }
+}
+
+impl LogClusterOptions {
+    /// Set the number of lines from the end of each log to compare.
+    pub fn tail_lines(mut self, tail_lines: usize) -> Self {
+        self.tail_lines = tail_lines;
+        self
+    }
+
+    /// Set the similarity required to join a cluster.
+    pub fn threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold.clamp(0., 1.);
+        self
+    }
+
+    /// Set the number of lines to keep as an excerpt.
+    pub fn excerpt_lines(mut self, excerpt_lines: usize) -> Self {
+        self.excerpt_lines = excerpt_lines;
+        self
+    }
+}
+
+/// A group of similar job logs.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct LogCluster {
+    /// The IDs of the jobs in the cluster.
+    pub jobs: Vec<u64>,
+    /// The ID of the job whose log represents the cluster.
+    pub representative: u64,
+    /// The end of the representative log.
+    pub excerpt: String,
+}
+
+/// Failing job logs grouped by similarity.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct FailureClusters {
+    /// Clusters of similar logs, largest first.
+    pub clusters: Vec<LogCluster>,
+    /// The IDs of failed jobs whose logs could not be read.
+    pub unreadable: Vec<u64>,
+}
+
+/// Remove escape sequences and carriage return overwrites from a line.
+fn strip_escapes(line: &str) -> String {
+    // Only the final overwrite of a line is visible.
+    let line = line
+        .rsplit('\r')
+        .find(|part| !part.is_empty())
+        .unwrap_or("");
+
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skip the sequence up to and including its final byte.
+            if chars.next() == Some('[') {
+                for c in chars.by_ref() {
+                    if c.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+            }
+        } else if !c.is_control() || c == '\t' {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Whether a line is a marker for a collapsible section.
+fn is_section_marker(line: &str) -> bool {
+    line.starts_with("section_start:") || line.starts_with("section_end:")
+}
+
+/// The visible lines at the end of a log.
+fn tail(log: &str, lines: usize) -> Vec<String> {
+    let mut tail = log
+        .lines()
+        .rev()
+        .map(strip_escapes)
+        .filter(|line| !line.trim().is_empty() && !is_section_marker(line))
+        .take(lines)
+        .collect::<Vec<_>>();
+    tail.reverse();
+    tail
+}
+
+/// Normalize a word so that volatile details do not affect similarity.
+///
+/// Numbers, hashes, and the like are replaced by a placeholder.
+fn normalize_word(word: &str) -> String {
+    let word = word.to_lowercase();
+    if word.chars().any(|c| c.is_ascii_digit()) {
+        "#".into()
+    } else {
+        word
+    }
+}
+
+fn hash_with_seed<T>(seed: usize, value: &T) -> u64
+where
+    T: Hash + ?Sized,
+{
+    let mut hasher = DefaultHasher::new();
+    seed.hash(&mut hasher);
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Compute the MinHash signature of a set of lines.
+fn signature(lines: &[String]) -> [u64; SIGNATURE_SIZE] {
+    let words = lines
+        .iter()
+        .flat_map(|line| line.split_whitespace())
+        .map(normalize_word)
+        .collect::<Vec<_>>();
+    let shingles = words
+        .windows(SHINGLE_SIZE.min(words.len()).max(1))
+        .map(|shingle| hash_with_seed(0, shingle))
+        .collect::<BTreeSet<_>>();
+
+    let mut signature = [u64::MAX; SIGNATURE_SIZE];
+    for shingle in shingles {
+        for (seed, min) in signature.iter_mut().enumerate() {
+            *min = (*min).min(hash_with_seed(seed + 1, &shingle));
+        }
+    }
+    signature
+}
+
+/// Estimate the similarity of two logs from their signatures.
+fn similarity(lhs: &[u64; SIGNATURE_SIZE], rhs: &[u64; SIGNATURE_SIZE]) -> f64 {
+    let same = lhs.iter().zip(rhs).filter(|(l, r)| l == r).count();
+    same as f64 / SIGNATURE_SIZE as f64
+}
+
+/// Cluster job logs by similarity.
+///
+/// Logs are compared using MinHash signatures of word shingles over the end of each log after
+/// removing escape sequences and numbers. Each log joins the first cluster whose representative
+/// is similar enough or starts a new cluster. Clusters are sorted by size (largest first).
+pub fn cluster_logs<'a, I>(logs: I, options: LogClusterOptions) -> Vec<LogCluster>
+where
+    I: IntoIterator<Item = (u64, &'a str)>,
+{
+    let mut clusters: Vec<(LogCluster, [u64; SIGNATURE_SIZE])> = Vec::new();
+
+    for (job, log) in logs {
+        let lines = tail(log, options.tail_lines);
+        let sig = signature(&lines);
+
+        let existing = clusters
+            .iter_mut()
+            .find(|(_, rep)| similarity(rep, &sig) >= options.threshold);
+        if let Some((cluster, _)) = existing {
+            cluster.jobs.push(job);
+        } else {
+            let excerpt_start = lines.len().saturating_sub(options.excerpt_lines);
+            let cluster = LogCluster {
+                jobs: vec![job],
+                representative: job,
+                excerpt: lines[excerpt_start..].join("\n"),
+            };
+            clusters.push((cluster, sig));
+        }
+    }
+
+    let mut clusters = clusters
+        .into_iter()
+        .map(|(cluster, _)| cluster)
+        .collect::<Vec<_>>();
+    clusters.sort_by_key(|cluster| Reverse(cluster.jobs.len()));
+    clusters
+}
+
+/// Cluster the logs of failed jobs by similarity.
+///
+/// Jobs which are allowed to fail are ignored. Only logs which have been stored are considered.
+pub fn failure_log_clusters<L>(
+    storage: &L,
+    blobs: &dyn BlobPersistence,
+    options: LogClusterOptions,
+) -> FailureClusters
+where
+    L: AnalysisLookup<L>,
+{
+    let artifacts = <L as DiscoverableLookup<JobArtifact<L>>>::all_indices(storage);
+    let mut jobs = Vec::new();
+    let mut refs = Vec::new();
+    let mut unreadable = Vec::new();
+
+    for idx in &artifacts {
+        let artifact = if let Some(artifact) = <L as Lookup<JobArtifact<L>>>::lookup(storage, idx) {
+            artifact
+        } else {
+            continue;
+        };
+        if artifact.kind != ArtifactKind::JobLog || !artifact.state.is_stored() {
+            continue;
+        }
+        let job = if let Some(job) = <L as Lookup<Job<L>>>::lookup(storage, &artifact.job) {
+            job
+        } else {
+            continue;
+        };
+        if job.state != JobState::Failed || job.allow_failure || job.cim_deleted_at.is_some() {
+            continue;
+        }
+
+        if let Some(blob) = artifact.blob.as_ref() {
+            jobs.push(job.forge_id);
+            refs.push(blob.clone());
+        } else {
+            unreadable.push(job.forge_id);
+        }
+    }
+
+    // Fetch all logs in a single batch.
+    let mut logs = BTreeMap::new();
+    for (job, data) in jobs.into_iter().zip(blobs.fetch_many(&refs)) {
+        if let Ok(data) = data {
+            logs.insert(job, String::from_utf8_lossy(&data).into_owned());
+        } else {
+            unreadable.push(job);
+        }
+    }
+
+    let clusters = cluster_logs(logs.iter().map(|(&job, log)| (job, log.as_str())), options);
+
+    FailureClusters {
+        clusters,
+        unreadable,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::LogClusterOptions;
+
+    const COMPILE_ERROR: &str = "\
+\x1b[0KRunning with gitlab-runner 16.0.0\n\
+section_start:1700000000:build\r\x1b[0K\x1b[0K\x1b[36;1mBuilding\x1b[0;m\n\
+[12/340] Building CXX object src/CMakeFiles/lib.dir/file.cxx.o\n\
+FAILED: src/CMakeFiles/lib.dir/file.cxx.o\n\
+src/file.cxx:42:7: error: use of undeclared identifier 'frobnicate'\n\
+ninja: build stopped: subcommand failed.\n\
+section_end:1700000100:build\r\x1b[0K\n\
+ERROR: Job failed: exit code 1\n";
+
+    const TEST_FAILURE: &str = "\
+Running with gitlab-runner 16.0.0\n\
+Start 17: test_network\n\
+17/20 Test #17: test_network .....................***Failed    3.21 sec\n\
+The following tests FAILED:\n\
+\t 17 - test_network (Failed)\n\
+Errors while running CTest\n\
+ERROR: Job failed: exit code 8\n";
+
+    #[test]
+    fn strip_escapes() {
+        assert_eq!(
+            super::strip_escapes("\x1b[0K\x1b[36;1mBuilding\x1b[0;m"),
+            "Building",
+        );
+        assert_eq!(
+            super::strip_escapes("progress 10%\rprogress 100%"),
+            "progress 100%"
+        );
+        assert_eq!(super::strip_escapes("done\r"), "done");
+    }
+
+    #[test]
+    fn tail() {
+        let tail = super::tail(COMPILE_ERROR, 3);
+
+        assert_eq!(
+            tail,
+            [
+                "src/file.cxx:42:7: error: use of undeclared identifier 'frobnicate'",
+                "ninja: build stopped: subcommand failed.",
+                "ERROR: Job failed: exit code 1",
+            ],
+        );
+    }
+
+    #[test]
+    fn cluster_logs() {
+        let compile_error_other_line = COMPILE_ERROR
+            .replace("[12/340]", "[99/340]")
+            .replace(":42:7:", ":108:3:");
+        let test_failure_other_time = TEST_FAILURE.replace("3.21 sec", "4.02 sec");
+        let logs = [
+            (1, COMPILE_ERROR),
+            (2, TEST_FAILURE),
+            (3, compile_error_other_line.as_str()),
+            (4, test_failure_other_time.as_str()),
+            (5, COMPILE_ERROR),
+        ];
+
+        let options = LogClusterOptions::default().excerpt_lines(2);
+        let clusters = super::cluster_logs(logs, options);
+
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters[0].jobs, [1, 3, 5]);
+        assert_eq!(clusters[0].representative, 1);
+        assert_eq!(
+            clusters[0].excerpt,
+            "ninja: build stopped: subcommand failed.\nERROR: Job failed: exit code 1",
+        );
+        assert_eq!(clusters[1].jobs, [2, 4]);
+    }
+
+    #[test]
+    fn cluster_logs_threshold() {
+        let logs = [(1, COMPILE_ERROR), (2, TEST_FAILURE)];
+
+        let options = LogClusterOptions::default().threshold(0.);
+        let clusters = super::cluster_logs(logs, options);
+
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].jobs, [1, 2]);
+    }
+
+    #[test]
+    fn cluster_logs_empty() {
+        let clusters = super::cluster_logs([], LogClusterOptions::default());
+
+        assert!(clusters.is_empty());
+    }
+}
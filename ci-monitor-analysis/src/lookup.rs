@@ -0,0 +1,50 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use ci_monitor_core::data::{
+    CrawlSession, Deployment, Environment, Instance, Job, JobArtifact, MergeRequest, Pipeline,
+    PipelineSchedule, Project, Push, Release, Runner, RunnerHost, User,
+};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::{DiscoverableLookup, VecLookup};
+
+/// Storage which may be analyzed.
+///
+/// Analyses need to be able to walk all of the stored data.
+pub trait AnalysisLookup<L>:
+    DiscoverableLookup<CrawlSession<L>>
+    + DiscoverableLookup<Deployment<L>>
+    + DiscoverableLookup<Environment<L>>
+    + DiscoverableLookup<Job<L>>
+    + DiscoverableLookup<JobArtifact<L>>
+    + DiscoverableLookup<MergeRequest<L>>
+    + DiscoverableLookup<Pipeline<L>>
+    + DiscoverableLookup<PipelineSchedule<L>>
+    + DiscoverableLookup<Project<L>>
+    + DiscoverableLookup<Push<L>>
+    + DiscoverableLookup<Release<L>>
+    + DiscoverableLookup<Runner<L>>
+    + DiscoverableLookup<RunnerHost>
+    + DiscoverableLookup<User<L>>
+    + DiscoverableLookup<Instance>
+where
+    L: Lookup<Deployment<L>>,
+    L: Lookup<Environment<L>>,
+    L: Lookup<Job<L>>,
+    L: Lookup<MergeRequest<L>>,
+    L: Lookup<Pipeline<L>>,
+    L: Lookup<PipelineSchedule<L>>,
+    L: Lookup<Project<L>>,
+    L: Lookup<Push<L>>,
+    L: Lookup<Release<L>>,
+    L: Lookup<Runner<L>>,
+    L: Lookup<RunnerHost>,
+    L: Lookup<User<L>>,
+    L: Lookup<Instance>,
+{
+}
+
+impl AnalysisLookup<Self> for VecLookup {}
@@ -0,0 +1,155 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+use chrono::Duration;
+use ci_monitor_core::data::{Job, Pipeline, Project, User};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+
+use crate::AnalysisLookup;
+
+/// A manual job which was started after waiting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ManualGate {
+    /// The name of the job.
+    pub name: String,
+    /// The URL of the job.
+    pub url: String,
+    /// How long the job waited before being started.
+    pub wait: Duration,
+    /// The handle of the user who started the job.
+    pub played_by: Option<String>,
+}
+
+/// Find the manual jobs which waited the longest in each project.
+///
+/// Results are keyed by the URL of the project and contain at most `limit` jobs, slowest first.
+/// Only manual jobs which have been started are considered.
+pub fn slowest_manual_gates<L>(storage: &L, limit: usize) -> BTreeMap<String, Vec<ManualGate>>
+where
+    L: AnalysisLookup<L>,
+{
+    let mut gates: BTreeMap<String, Vec<ManualGate>> = BTreeMap::new();
+
+    for idx in <L as DiscoverableLookup<Job<L>>>::all_indices(storage) {
+        let job = if let Some(job) = <L as Lookup<Job<L>>>::lookup(storage, &idx) {
+            job
+        } else {
+            continue;
+        };
+        if job.cim_deleted_at.is_some() {
+            continue;
+        }
+        let wait = if let Some(wait) = job.manual_wait() {
+            wait
+        } else {
+            continue;
+        };
+        let project = <L as Lookup<Pipeline<L>>>::lookup(storage, &job.pipeline)
+            .and_then(|pipeline| <L as Lookup<Project<L>>>::lookup(storage, &pipeline.project));
+        let project = if let Some(project) = project {
+            project
+        } else {
+            continue;
+        };
+        let played_by = job
+            .played_by
+            .as_ref()
+            .and_then(|user| <L as Lookup<User<L>>>::lookup(storage, user))
+            .map(|user| user.handle.clone());
+
+        gates
+            .entry(project.url.clone())
+            .or_default()
+            .push(ManualGate {
+                name: job.name.clone(),
+                url: job.url.clone(),
+                wait,
+                played_by,
+            });
+    }
+
+    for project_gates in gates.values_mut() {
+        project_gates.sort_by_key(|gate| Reverse(gate.wait));
+        project_gates.truncate(limit);
+    }
+
+    gates
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use ci_monitor_core::data::{Job, JobState, PipelineStatus, User};
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test::{self, at};
+    use crate::ManualGate;
+
+    #[test]
+    fn slowest_manual_gates() {
+        let mut lookup = VecLookup::default();
+        let instance = test::instance(&mut lookup);
+        let user = User::builder()
+            .forge_id(0)
+            .instance(instance)
+            .handle("releaser")
+            .build()
+            .unwrap();
+        let user = lookup.store(user);
+        let project = test::project(&mut lookup, instance, 0, "project");
+        let pipeline = test::pipeline(project, 0, PipelineStatus::Success, at(0));
+        let pipeline = lookup.store(pipeline);
+
+        let jobs = [
+            (0, "deploy", Some(at(30))),
+            (1, "release", Some(at(90))),
+            (2, "publish", Some(at(10))),
+            // Never started; ignored.
+            (3, "cleanup", None),
+        ];
+        for (id, name, played_at) in jobs {
+            let job = Job::builder()
+                .user(user)
+                .name(name)
+                .state(JobState::Success)
+                .created_at(at(0))
+                .forge_id(id)
+                .pipeline(pipeline)
+                .manual(true)
+                .played_at(played_at)
+                .played_by(Some(user))
+                .build()
+                .unwrap();
+            lookup.store(job);
+        }
+
+        let gates = super::slowest_manual_gates(&lookup, 2);
+
+        assert_eq!(
+            gates["project"],
+            vec![
+                ManualGate {
+                    name: "release".into(),
+                    url: String::new(),
+                    wait: Duration::minutes(90),
+                    played_by: Some("releaser".into()),
+                },
+                ManualGate {
+                    name: "deploy".into(),
+                    url: String::new(),
+                    wait: Duration::minutes(30),
+                    played_by: Some("releaser".into()),
+                },
+            ],
+        );
+    }
+}
@@ -0,0 +1,254 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, Utc};
+use ci_monitor_core::data::{MergeRequest, MergeRequestStatus, Pipeline, Project};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::cost::pipeline_merge_request;
+use crate::AnalysisLookup;
+
+/// How long a merged merge request spent waiting on CI and on people.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct MergeRequestLatency {
+    /// The URL of the merge request.
+    pub url: String,
+    /// The title of the merge request.
+    pub title: String,
+    /// When the merge request was created.
+    pub created_at: DateTime<Utc>,
+    /// When the merge request was merged.
+    pub merged_at: DateTime<Utc>,
+    /// How long the merge request waited for its first review.
+    #[serde(serialize_with = "crate::serialize::optional_seconds")]
+    #[schemars(with = "Option<i64>")]
+    pub first_review: Option<Duration>,
+    /// How long the merge request waited to be approved.
+    #[serde(serialize_with = "crate::serialize::optional_seconds")]
+    #[schemars(with = "Option<i64>")]
+    pub approval: Option<Duration>,
+    /// How long at least one pipeline for the merge request was running.
+    #[serde(serialize_with = "crate::serialize::seconds")]
+    #[schemars(with = "i64")]
+    pub ci_time: Duration,
+}
+
+impl MergeRequestLatency {
+    /// How long the merge request took to be merged.
+    pub fn time_to_merge(&self) -> Duration {
+        self.merged_at - self.created_at
+    }
+
+    /// How long the merge request spent waiting without a pipeline running.
+    pub fn human_time(&self) -> Duration {
+        (self.time_to_merge() - self.ci_time).max(Duration::zero())
+    }
+}
+
+/// The total length of a set of intervals, counting overlapping periods once.
+fn covered_time(mut intervals: Vec<(DateTime<Utc>, DateTime<Utc>)>) -> Duration {
+    intervals.sort_unstable();
+
+    let mut total = Duration::zero();
+    let mut current: Option<(DateTime<Utc>, DateTime<Utc>)> = None;
+    for (start, end) in intervals {
+        current = match current {
+            Some((cur_start, cur_end)) if start <= cur_end => Some((cur_start, cur_end.max(end))),
+            Some((cur_start, cur_end)) => {
+                total += cur_end - cur_start;
+                Some((start, end))
+            },
+            None => Some((start, end)),
+        };
+    }
+    if let Some((start, end)) = current {
+        total += end - start;
+    }
+
+    total
+}
+
+/// Break down the time from creation to merge of merged merge requests.
+///
+/// CI time is the wall-clock time during which at least one pipeline for the merge request was
+/// running between its creation and merge; the rest is attributed to people. Only merge requests
+/// merged at or after `since` are considered. Results are sorted by when they were merged.
+pub fn merge_request_latencies<L>(
+    storage: &L,
+    since: Option<DateTime<Utc>>,
+) -> Vec<MergeRequestLatency>
+where
+    L: AnalysisLookup<L>,
+{
+    let mut latencies = BTreeMap::new();
+    let mut by_ref = BTreeMap::new();
+
+    for idx in <L as DiscoverableLookup<MergeRequest<L>>>::all_indices(storage) {
+        let mr = if let Some(mr) = <L as Lookup<MergeRequest<L>>>::lookup(storage, &idx) {
+            mr
+        } else {
+            continue;
+        };
+        if mr.state != MergeRequestStatus::Merged || mr.cim_deleted_at.is_some() {
+            continue;
+        }
+        let (created_at, merged_at) =
+            if let (Some(created_at), Some(merged_at)) = (mr.created_at, mr.merged_at) {
+                (created_at, merged_at)
+            } else {
+                continue;
+            };
+        if since.is_some_and(|since| merged_at < since) {
+            continue;
+        }
+        // Merge request pipelines run in the target project.
+        if let Some(project) = <L as Lookup<Project<L>>>::lookup(storage, &mr.target_project) {
+            by_ref.insert((project.url.clone(), mr.id), mr.url.clone());
+        }
+
+        let latency = MergeRequestLatency {
+            url: mr.url.clone(),
+            title: mr.title.clone(),
+            created_at,
+            merged_at,
+            first_review: mr.review_latency(),
+            approval: mr.approved_at.map(|approved_at| approved_at - created_at),
+            ci_time: Duration::zero(),
+        };
+        latencies.insert(mr.url.clone(), (latency, Vec::new()));
+    }
+
+    for idx in <L as DiscoverableLookup<Pipeline<L>>>::all_indices(storage) {
+        let pipeline = if let Some(pipeline) = <L as Lookup<Pipeline<L>>>::lookup(storage, &idx) {
+            pipeline
+        } else {
+            continue;
+        };
+        let entry = pipeline_merge_request(storage, pipeline, &by_ref)
+            .and_then(|url| latencies.get_mut(&url));
+        let (latency, intervals) = if let Some(entry) = entry {
+            entry
+        } else {
+            continue;
+        };
+
+        let start = pipeline.created_at.max(latency.created_at);
+        let end = pipeline
+            .finished_at
+            .unwrap_or(pipeline.updated_at)
+            .min(latency.merged_at);
+        if start < end {
+            intervals.push((start, end));
+        }
+    }
+
+    let mut latencies = latencies
+        .into_values()
+        .map(|(mut latency, intervals)| {
+            latency.ci_time = covered_time(intervals);
+            latency
+        })
+        .collect::<Vec<_>>();
+    latencies.sort_by_key(|latency| latency.merged_at);
+    latencies
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use ci_monitor_core::data::{
+        MergeRequest, MergeRequestStatus, Pipeline, PipelineSource, PipelineStatus,
+    };
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test::{self, at};
+
+    #[test]
+    fn covered_time() {
+        let intervals = vec![(at(0), at(10)), (at(5), at(15)), (at(20), at(30))];
+        assert_eq!(super::covered_time(intervals), Duration::minutes(25));
+        assert_eq!(super::covered_time(Vec::new()), Duration::zero());
+    }
+
+    #[test]
+    fn merge_request_latencies() {
+        let mut lookup = VecLookup::default();
+        let instance = test::instance(&mut lookup);
+        let user = test::user(&mut lookup, instance, 0);
+        let project = test::project(&mut lookup, instance, 1, "project");
+
+        // (id, state, merged)
+        let mrs = [
+            (1, MergeRequestStatus::Merged, Some(240)),
+            (2, MergeRequestStatus::Open, None),
+            (3, MergeRequestStatus::Merged, Some(60)),
+        ];
+        let mrs = mrs.map(|(id, state, merged)| {
+            let mut mr = MergeRequest::builder()
+                .id(id)
+                .source_project(project)
+                .target_project(project)
+                .forge_id(id)
+                .title(format!("mr{}", id))
+                .state(state)
+                .author(user)
+                .url(format!("mr{}", id))
+                .created_at(Some(at(0)))
+                .merged_at(merged.map(at))
+                .build()
+                .unwrap();
+            if id == 1 {
+                mr.first_review_at = Some(at(30));
+                mr.approved_at = Some(at(200));
+            }
+            lookup.store(mr)
+        });
+
+        // (merge request, created, finished)
+        let pipelines = [(0, 0, 20), (0, 10, 40), (0, 100, 300), (1, 0, 20)];
+        for (id, (mr, created, finished)) in pipelines.into_iter().enumerate() {
+            let pipeline = Pipeline::builder()
+                .project(project)
+                .sha("0000000000000000000000000000000000000000")
+                .source(PipelineSource::MergeRequestEvent)
+                .status(PipelineStatus::Success)
+                .merge_request(Some(mrs[mr]))
+                .forge_id(id as u64)
+                .url("url")
+                .created_at(at(created))
+                .updated_at(at(finished))
+                .finished_at(Some(at(finished)))
+                .build()
+                .unwrap();
+            lookup.store(pipeline);
+        }
+
+        let latencies = super::merge_request_latencies(&lookup, None);
+
+        assert_eq!(latencies.len(), 2);
+        assert_eq!(latencies[0].url, "mr3");
+        assert_eq!(latencies[0].first_review, None);
+        assert_eq!(latencies[0].ci_time, Duration::zero());
+        assert_eq!(latencies[0].human_time(), Duration::minutes(60));
+        assert_eq!(latencies[1].url, "mr1");
+        assert_eq!(latencies[1].first_review, Some(Duration::minutes(30)));
+        assert_eq!(latencies[1].approval, Some(Duration::minutes(200)));
+        assert_eq!(latencies[1].time_to_merge(), Duration::minutes(240));
+        assert_eq!(latencies[1].ci_time, Duration::minutes(180));
+        assert_eq!(latencies[1].human_time(), Duration::minutes(60));
+
+        let latencies = super::merge_request_latencies(&lookup, Some(at(120)));
+        assert_eq!(latencies.len(), 1);
+        assert_eq!(latencies[0].url, "mr1");
+    }
+}
@@ -0,0 +1,330 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, Utc};
+use ci_monitor_core::data::{Job, Runner, RunnerHost};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::AnalysisLookup;
+
+/// When operating system versions reach their end of life.
+///
+/// Operating systems are matched case-insensitively. Versions match host versions with additional
+/// components (e.g., a patch level), so `20.04` covers hosts running `20.04.6`. The most specific
+/// version in the catalog wins.
+#[derive(Debug, Clone, Default)]
+pub struct OsCatalog {
+    releases: BTreeMap<String, BTreeMap<String, DateTime<Utc>>>,
+}
+
+impl OsCatalog {
+    /// Add when a version of an operating system reaches its end of life.
+    pub fn release<O, V>(mut self, os: O, version: V, end_of_life: DateTime<Utc>) -> Self
+    where
+        O: AsRef<str>,
+        V: Into<String>,
+    {
+        self.releases
+            .entry(os.as_ref().to_lowercase())
+            .or_default()
+            .insert(version.into(), end_of_life);
+        self
+    }
+
+    /// Whether the catalog has any releases.
+    pub fn is_empty(&self) -> bool {
+        self.releases.is_empty()
+    }
+
+    /// When a version of an operating system reaches its end of life.
+    pub fn end_of_life(&self, os: &str, version: &str) -> Option<DateTime<Utc>> {
+        self.releases
+            .get(&os.to_lowercase())?
+            .iter()
+            .filter(|(release, _)| covers_version(release, version))
+            .max_by_key(|(release, _)| release.len())
+            .map(|(_, &end_of_life)| end_of_life)
+    }
+}
+
+/// Whether a release version covers a (possibly more specific) host version.
+fn covers_version(release: &str, version: &str) -> bool {
+    version.strip_prefix(release).is_some_and(|rest| {
+        rest.is_empty() || rest.starts_with(|c: char| !c.is_ascii_alphanumeric())
+    })
+}
+
+/// A runner host running an operating system which is at or near its end of life.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct EndOfLifeHost {
+    /// The name of the host.
+    pub host: String,
+    /// The operating system.
+    pub os: String,
+    /// The version of the operating system.
+    pub os_version: String,
+    /// When the operating system version reaches its end of life.
+    pub end_of_life: DateTime<Utc>,
+    /// Whether the end of life has passed.
+    pub expired: bool,
+    /// The number of runners on the host.
+    pub runners: usize,
+    /// The number of jobs which ran on the host.
+    pub jobs: usize,
+    /// The total time jobs spent running on the host.
+    #[serde(serialize_with = "crate::serialize::seconds")]
+    #[schemars(with = "i64")]
+    pub machine_time: Duration,
+}
+
+/// Find runner hosts whose operating system is past (or within `warning` of) its end of life.
+///
+/// Job volume counts jobs which started at or after `since`. Hosts without a known operating
+/// system version or whose version is not in the catalog are not reported. Results are sorted by
+/// end of life (earliest first) and then by job volume (busiest first).
+pub fn end_of_life_hosts<L>(
+    storage: &L,
+    catalog: &OsCatalog,
+    now: DateTime<Utc>,
+    warning: Duration,
+    since: DateTime<Utc>,
+) -> Vec<EndOfLifeHost>
+where
+    L: AnalysisLookup<L>,
+{
+    let mut hosts = BTreeMap::new();
+    for idx in <L as DiscoverableLookup<RunnerHost>>::all_indices(storage) {
+        let host = if let Some(host) = <L as Lookup<RunnerHost>>::lookup(storage, &idx) {
+            host
+        } else {
+            continue;
+        };
+        if host.os.is_empty() || host.os_version.is_empty() {
+            continue;
+        }
+        let end_of_life = if let Some(end_of_life) = catalog.end_of_life(&host.os, &host.os_version)
+        {
+            end_of_life
+        } else {
+            continue;
+        };
+        if now + warning < end_of_life {
+            continue;
+        }
+
+        let entry = EndOfLifeHost {
+            host: host.name.clone(),
+            os: host.os.clone(),
+            os_version: host.os_version.clone(),
+            end_of_life,
+            expired: end_of_life <= now,
+            runners: 0,
+            jobs: 0,
+            machine_time: Duration::zero(),
+        };
+        hosts.insert(host.unique_id, entry);
+    }
+
+    let runner_host = |runner: &Runner<L>| {
+        runner
+            .runner_host
+            .as_ref()
+            .and_then(|host| <L as Lookup<RunnerHost>>::lookup(storage, host))
+            .map(|host| host.unique_id)
+    };
+
+    for idx in <L as DiscoverableLookup<Runner<L>>>::all_indices(storage) {
+        let entry = <L as Lookup<Runner<L>>>::lookup(storage, &idx)
+            .and_then(runner_host)
+            .and_then(|host| hosts.get_mut(&host));
+        if let Some(entry) = entry {
+            entry.runners += 1;
+        }
+    }
+
+    for idx in <L as DiscoverableLookup<Job<L>>>::all_indices(storage) {
+        let job = if let Some(job) = <L as Lookup<Job<L>>>::lookup(storage, &idx) {
+            job
+        } else {
+            continue;
+        };
+        let started_at = if let Some(started_at) = job.started_at {
+            started_at
+        } else {
+            continue;
+        };
+        if started_at < since || job.cim_deleted_at.is_some() {
+            continue;
+        }
+        let entry = job
+            .runner
+            .as_ref()
+            .and_then(|runner| <L as Lookup<Runner<L>>>::lookup(storage, runner))
+            .and_then(runner_host)
+            .and_then(|host| hosts.get_mut(&host));
+        if let Some(entry) = entry {
+            entry.jobs += 1;
+            if let Some(finished_at) = job.finished_at {
+                entry.machine_time += finished_at - started_at;
+            }
+        }
+    }
+
+    let mut hosts = hosts.into_values().collect::<Vec<_>>();
+    hosts.sort_by(|lhs, rhs| {
+        lhs.end_of_life
+            .cmp(&rhs.end_of_life)
+            .then_with(|| rhs.jobs.cmp(&lhs.jobs))
+    });
+    hosts
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use ci_monitor_core::data::{
+        Job, JobState, PipelineStatus, Runner, RunnerHost, RunnerProtectionLevel, RunnerType,
+    };
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test::{self, at_day};
+    use crate::OsCatalog;
+
+    fn catalog() -> OsCatalog {
+        OsCatalog::default()
+            .release("Ubuntu", "20.04", at_day(-10))
+            .release("ubuntu", "22.04", at_day(30))
+            .release("ubuntu", "24.04", at_day(1000))
+            .release("windows", "10", at_day(0))
+            .release("windows", "10.0.19045", at_day(500))
+    }
+
+    #[test]
+    fn covers_version() {
+        assert!(super::covers_version("20.04", "20.04"));
+        assert!(super::covers_version("20.04", "20.04.6"));
+        assert!(super::covers_version("20.04", "20.04 LTS"));
+        assert!(!super::covers_version("20.04", "20.041"));
+        assert!(!super::covers_version("20.04", "20.0"));
+    }
+
+    #[test]
+    fn os_catalog() {
+        let catalog = catalog();
+        assert!(!catalog.is_empty());
+        assert_eq!(catalog.end_of_life("ubuntu", "20.04.6"), Some(at_day(-10)));
+        assert_eq!(catalog.end_of_life("UBUNTU", "22.04"), Some(at_day(30)));
+        assert_eq!(catalog.end_of_life("ubuntu", "18.04"), None);
+        assert_eq!(catalog.end_of_life("debian", "12"), None);
+        // The most specific version wins.
+        assert_eq!(
+            catalog.end_of_life("windows", "10.0.17763"),
+            Some(at_day(0))
+        );
+        assert_eq!(
+            catalog.end_of_life("windows", "10.0.19045.1"),
+            Some(at_day(500))
+        );
+    }
+
+    #[test]
+    fn end_of_life_hosts() {
+        let mut lookup = VecLookup::default();
+        let instance = test::instance(&mut lookup);
+        let user = test::user(&mut lookup, instance, 0);
+        let project = test::project(&mut lookup, instance, 1, "group/project");
+        let pipeline = test::pipeline(project, 1, PipelineStatus::Success, at_day(0));
+        let pipeline = lookup.store(pipeline);
+
+        // (name, os, version)
+        let hosts = [
+            ("old", "ubuntu", "20.04.6"),
+            ("soon", "ubuntu", "22.04.4"),
+            ("current", "ubuntu", "24.04"),
+            ("unknown", "ubuntu", ""),
+        ];
+        let hosts = hosts
+            .into_iter()
+            .enumerate()
+            .map(|(id, (name, os, os_version))| {
+                let host = RunnerHost::builder()
+                    .name(name)
+                    .os(os)
+                    .os_version(os_version)
+                    .unique_id(id as u64)
+                    .build()
+                    .unwrap();
+                lookup.store(host)
+            })
+            .collect::<Vec<_>>();
+        let runners = [0, 0, 1, 2]
+            .into_iter()
+            .enumerate()
+            .map(|(id, host)| {
+                let runner = Runner::builder()
+                    .runner_type(RunnerType::Instance)
+                    .protection_level(RunnerProtectionLevel::Any)
+                    .runner_host(Some(hosts[host]))
+                    .forge_id(id as u64)
+                    .instance(instance)
+                    .build()
+                    .unwrap();
+                lookup.store(runner)
+            })
+            .collect::<Vec<_>>();
+
+        // (runner, start, end)
+        let jobs = [(0, 1, 2), (1, 1, 3), (2, 1, 2), (3, 1, 2), (2, -10, -9)];
+        for (id, (runner, start, end)) in jobs.into_iter().enumerate() {
+            let job = Job::builder()
+                .user(user)
+                .name("job")
+                .state(JobState::Success)
+                .created_at(at_day(start))
+                .started_at(Some(at_day(start)))
+                .finished_at(Some(at_day(end)))
+                .runner(Some(runners[runner]))
+                .forge_id(id as u64)
+                .pipeline(pipeline)
+                .build()
+                .unwrap();
+            lookup.store(job);
+        }
+
+        let hosts = super::end_of_life_hosts(
+            &lookup,
+            &catalog(),
+            at_day(0),
+            Duration::days(60),
+            at_day(0),
+        );
+
+        assert_eq!(hosts.len(), 2);
+        assert_eq!(hosts[0].host, "old");
+        assert_eq!(hosts[0].end_of_life, at_day(-10));
+        assert!(hosts[0].expired);
+        assert_eq!(hosts[0].runners, 2);
+        assert_eq!(hosts[0].jobs, 2);
+        assert_eq!(hosts[0].machine_time, Duration::days(3));
+        assert_eq!(hosts[1].host, "soon");
+        assert!(!hosts[1].expired);
+        assert_eq!(hosts[1].runners, 1);
+        assert_eq!(hosts[1].jobs, 1);
+
+        // Without a warning period, only expired hosts are reported.
+        let hosts =
+            super::end_of_life_hosts(&lookup, &catalog(), at_day(0), Duration::zero(), at_day(0));
+        assert_eq!(hosts.len(), 1);
+        assert_eq!(hosts[0].host, "old");
+    }
+}
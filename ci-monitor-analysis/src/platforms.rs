@@ -0,0 +1,272 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, Utc};
+use ci_monitor_core::data::{Job, Runner, RunnerHost};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::{AnalysisLookup, ConcurrencyCurve};
+
+/// A platform targeted by runners.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct PlatformKey {
+    /// The operating system.
+    pub os: String,
+    /// The CPU architecture.
+    pub architecture: String,
+    /// The version of the operating system.
+    ///
+    /// Empty if unknown.
+    pub os_version: String,
+}
+
+impl PlatformKey {
+    /// The platform of a runner.
+    ///
+    /// Runner host information is preferred over what the runner reports about its platform.
+    fn of<L>(storage: &L, runner: &Runner<L>) -> Self
+    where
+        L: AnalysisLookup<L>,
+    {
+        let host = runner
+            .runner_host
+            .as_ref()
+            .and_then(|host| <L as Lookup<RunnerHost>>::lookup(storage, host));
+        let os = host
+            .map(|host| host.os.as_str())
+            .filter(|os| !os.is_empty())
+            .unwrap_or(&runner.platform);
+        let os_version = host.map(|host| host.os_version.clone()).unwrap_or_default();
+
+        Self {
+            os: os.into(),
+            architecture: runner.architecture.clone(),
+            os_version,
+        }
+    }
+}
+
+/// The runners and job volume of a platform.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct PlatformCell {
+    /// The platform.
+    #[serde(flatten)]
+    pub platform: PlatformKey,
+    /// The number of runners targeting the platform.
+    pub runners: usize,
+    /// The number of those runners which are online.
+    pub online: usize,
+    /// The number of jobs which ran on the platform.
+    pub jobs: usize,
+    /// The total time jobs spent running on the platform.
+    #[serde(serialize_with = "crate::serialize::seconds")]
+    #[schemars(with = "i64")]
+    pub machine_time: Duration,
+    /// The highest number of jobs running on the platform at once.
+    pub peak_concurrency: usize,
+}
+
+impl PlatformCell {
+    fn new(platform: PlatformKey) -> Self {
+        Self {
+            platform,
+            runners: 0,
+            online: 0,
+            jobs: 0,
+            machine_time: Duration::zero(),
+            peak_concurrency: 0,
+        }
+    }
+
+    /// Whether no jobs ran on the platform.
+    pub fn is_idle(&self) -> bool {
+        self.jobs == 0
+    }
+}
+
+/// Build a matrix of the runner fleet by operating system, architecture, and OS version.
+///
+/// Job volume counts jobs which started at or after `since`. Every platform with a runner is
+/// reported, even if it is idle. Results are sorted by platform.
+pub fn platform_matrix<L>(storage: &L, since: DateTime<Utc>) -> Vec<PlatformCell>
+where
+    L: AnalysisLookup<L>,
+{
+    let mut cells: BTreeMap<PlatformKey, PlatformCell> = BTreeMap::new();
+    let mut runner_platforms = BTreeMap::new();
+
+    let runners = <L as DiscoverableLookup<Runner<L>>>::all_indices(storage);
+    for idx in &runners {
+        let runner = if let Some(runner) = <L as Lookup<Runner<L>>>::lookup(storage, idx) {
+            runner
+        } else {
+            continue;
+        };
+        let platform = PlatformKey::of(storage, runner);
+        let cell = cells
+            .entry(platform.clone())
+            .or_insert_with(|| PlatformCell::new(platform.clone()));
+        cell.runners += 1;
+        cell.online += usize::from(runner.online);
+        runner_platforms.insert(runner.forge_id, platform);
+    }
+
+    let mut intervals: BTreeMap<&PlatformKey, Vec<_>> = BTreeMap::new();
+    let jobs = <L as DiscoverableLookup<Job<L>>>::all_indices(storage);
+    for idx in &jobs {
+        let job = if let Some(job) = <L as Lookup<Job<L>>>::lookup(storage, idx) {
+            job
+        } else {
+            continue;
+        };
+        let started_at = if let Some(started_at) = job.started_at {
+            started_at
+        } else {
+            continue;
+        };
+        if started_at < since || job.cim_deleted_at.is_some() {
+            continue;
+        }
+        let platform = job
+            .runner
+            .as_ref()
+            .and_then(|runner| <L as Lookup<Runner<L>>>::lookup(storage, runner))
+            .and_then(|runner| runner_platforms.get(&runner.forge_id));
+        let platform = if let Some(platform) = platform {
+            platform
+        } else {
+            continue;
+        };
+        let cell = if let Some(cell) = cells.get_mut(platform) {
+            cell
+        } else {
+            continue;
+        };
+
+        cell.jobs += 1;
+        if let Some(finished_at) = job.finished_at {
+            cell.machine_time += finished_at - started_at;
+            intervals
+                .entry(platform)
+                .or_default()
+                .push((started_at, finished_at));
+        }
+    }
+
+    for (platform, intervals) in intervals {
+        if let Some(cell) = cells.get_mut(platform) {
+            cell.peak_concurrency = ConcurrencyCurve::from_intervals(intervals)
+                .peak()
+                .map_or(0, |peak| peak.concurrency);
+        }
+    }
+
+    cells.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use ci_monitor_core::data::{
+        Job, JobState, PipelineStatus, Runner, RunnerHost, RunnerProtectionLevel, RunnerType,
+    };
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test::{self, at};
+
+    #[test]
+    fn platform_matrix() {
+        let mut lookup = VecLookup::default();
+        let instance = test::instance(&mut lookup);
+        let user = test::user(&mut lookup, instance, 0);
+        let host = RunnerHost::builder()
+            .name("host")
+            .os("Ubuntu")
+            .os_version("22.04")
+            .unique_id(0)
+            .build()
+            .unwrap();
+        let host = lookup.store(host);
+
+        // (id, platform, architecture, host, online)
+        let runners = [
+            (1, "linux", "amd64", Some(host), true),
+            (2, "linux", "amd64", Some(host), false),
+            (3, "linux", "arm64", None, true),
+            (4, "windows", "amd64", None, true),
+        ];
+        let runners = runners.map(|(id, platform, architecture, host, online)| {
+            let runner = Runner::builder()
+                .runner_type(RunnerType::Instance)
+                .protection_level(RunnerProtectionLevel::Any)
+                .forge_id(id)
+                .instance(instance)
+                .platform(platform)
+                .architecture(architecture)
+                .runner_host(host)
+                .online(online)
+                .build()
+                .unwrap();
+            lookup.store(runner)
+        });
+
+        let project = test::project(&mut lookup, instance, 1, "project");
+        let pipeline = test::pipeline(project, 1, PipelineStatus::Success, at(0));
+        let pipeline = lookup.store(pipeline);
+
+        // (runner, start, minutes)
+        let jobs = [
+            (0, 0, 10),
+            (1, 5, 10),
+            (0, 20, 10),
+            (2, 0, 30),
+            (2, -60, 30),
+        ];
+        for (id, (runner, start, minutes)) in jobs.into_iter().enumerate() {
+            let job = Job::builder()
+                .user(user)
+                .name("job")
+                .state(JobState::Success)
+                .created_at(at(start))
+                .started_at(Some(at(start)))
+                .finished_at(Some(at(start + minutes)))
+                .runner(Some(runners[runner]))
+                .forge_id(id as u64)
+                .pipeline(pipeline)
+                .build()
+                .unwrap();
+            lookup.store(job);
+        }
+
+        let matrix = super::platform_matrix(&lookup, at(0));
+
+        assert_eq!(matrix.len(), 3);
+        assert_eq!(matrix[0].platform.os, "Ubuntu");
+        assert_eq!(matrix[0].platform.architecture, "amd64");
+        assert_eq!(matrix[0].platform.os_version, "22.04");
+        assert_eq!(matrix[0].runners, 2);
+        assert_eq!(matrix[0].online, 1);
+        assert_eq!(matrix[0].jobs, 3);
+        assert_eq!(matrix[0].machine_time, Duration::minutes(30));
+        assert_eq!(matrix[0].peak_concurrency, 2);
+        assert_eq!(matrix[1].platform.os, "linux");
+        assert_eq!(matrix[1].platform.architecture, "arm64");
+        assert_eq!(matrix[1].platform.os_version, "");
+        assert_eq!(matrix[1].jobs, 1);
+        assert_eq!(matrix[1].peak_concurrency, 1);
+        assert_eq!(matrix[2].platform.os, "windows");
+        assert!(matrix[2].is_idle());
+        assert_eq!(matrix[2].peak_concurrency, 0);
+    }
+}
@@ -0,0 +1,387 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+
+use ci_monitor_core::data::{Job, JobState, Pipeline, Project};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+use glob::Pattern;
+use schemars::JsonSchema;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::AnalysisLookup;
+
+/// Errors which may occur when declaring a policy.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum PolicyError {
+    /// A pattern is invalid.
+    #[error("invalid pattern '{}': {}", pattern, source)]
+    InvalidPattern {
+        /// The pattern.
+        pattern: String,
+        /// The source of the error.
+        #[source]
+        source: glob::PatternError,
+    },
+}
+
+impl PolicyError {
+    fn invalid_pattern(pattern: String, source: glob::PatternError) -> Self {
+        Self::InvalidPattern {
+            pattern,
+            source,
+        }
+    }
+}
+
+fn pattern(pattern: &str) -> Result<Pattern, PolicyError> {
+    Pattern::new(pattern).map_err(|err| PolicyError::invalid_pattern(pattern.into(), err))
+}
+
+/// A job which must exist in pipelines for certain refs.
+#[derive(Debug, Clone)]
+pub struct RequiredJob {
+    job: String,
+    refs: Pattern,
+    projects: Option<Pattern>,
+    must_pass: bool,
+}
+
+impl RequiredJob {
+    /// Require a job in pipelines for refs matching a glob pattern.
+    ///
+    /// By default, the job must also pass.
+    pub fn new<J>(job: J, refs: &str) -> Result<Self, PolicyError>
+    where
+        J: Into<String>,
+    {
+        Ok(Self {
+            job: job.into(),
+            refs: pattern(refs)?,
+            projects: None,
+            must_pass: true,
+        })
+    }
+
+    /// Only apply to projects whose path matches a glob pattern.
+    pub fn projects(mut self, projects: &str) -> Result<Self, PolicyError> {
+        self.projects = Some(pattern(projects)?);
+        Ok(self)
+    }
+
+    /// Whether the job must pass or only exist.
+    pub fn must_pass(mut self, must_pass: bool) -> Self {
+        self.must_pass = must_pass;
+        self
+    }
+
+    /// The name of the required job.
+    pub fn job(&self) -> &str {
+        &self.job
+    }
+
+    fn applies_to(&self, project: &str, refname: &str) -> bool {
+        self.refs.matches(refname)
+            && self
+                .projects
+                .as_ref()
+                .is_none_or(|projects| projects.matches(project))
+    }
+}
+
+/// How a pipeline violates a policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[non_exhaustive]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PolicyViolationKind {
+    /// The required job did not run.
+    Missing,
+    /// The required job did not pass.
+    NotPassed {
+        /// The state of the job.
+        #[serde(serialize_with = "crate::serialize::name")]
+        #[schemars(with = "String")]
+        state: JobState,
+    },
+}
+
+/// A pipeline which violates a policy.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct PolicyViolation {
+    /// The ID of the pipeline.
+    pub pipeline: u64,
+    /// The URL of the pipeline.
+    pub url: String,
+    /// The ref of the pipeline.
+    pub refname: String,
+    /// The name of the required job.
+    pub job: String,
+    /// How the policy was violated.
+    #[serde(flatten)]
+    pub kind: PolicyViolationKind,
+}
+
+/// Policy violations within a project.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct ProjectPolicyViolations {
+    /// The ID of the project.
+    pub project: u64,
+    /// The path of the project on its instance.
+    pub path: String,
+    /// Violations ordered by pipeline.
+    pub violations: Vec<PolicyViolation>,
+}
+
+/// Check finished pipelines against required job policies.
+///
+/// Jobs of child pipelines count towards their parent pipeline. When a job has been retried, only
+/// the latest attempt is considered. Results are sorted by project ID and only projects with
+/// violations are reported.
+pub fn required_job_violations<L>(
+    storage: &L,
+    policy: &[RequiredJob],
+) -> Vec<ProjectPolicyViolations>
+where
+    L: AnalysisLookup<L>,
+{
+    // Find the latest attempt of each job by name for each pipeline.
+    let mut pipeline_jobs: BTreeMap<u64, BTreeMap<&str, &Job<L>>> = BTreeMap::new();
+    let jobs = <L as DiscoverableLookup<Job<L>>>::all_indices(storage);
+    for idx in &jobs {
+        let job = if let Some(job) = <L as Lookup<Job<L>>>::lookup(storage, idx) {
+            job
+        } else {
+            continue;
+        };
+        let mut pipeline = <L as Lookup<Pipeline<L>>>::lookup(storage, &job.pipeline);
+        while let Some(parent) = pipeline
+            .and_then(|pipeline| pipeline.parent_pipeline.as_ref())
+            .and_then(|idx| <L as Lookup<Pipeline<L>>>::lookup(storage, idx))
+        {
+            pipeline = Some(parent);
+        }
+        let pipeline = if let Some(pipeline) = pipeline {
+            pipeline
+        } else {
+            continue;
+        };
+
+        let latest = pipeline_jobs
+            .entry(pipeline.forge_id)
+            .or_default()
+            .entry(job.name.as_str())
+            .or_insert(job);
+        if latest.forge_id < job.forge_id {
+            *latest = job;
+        }
+    }
+
+    let mut projects: BTreeMap<u64, ProjectPolicyViolations> = BTreeMap::new();
+    let pipelines = <L as DiscoverableLookup<Pipeline<L>>>::all_indices(storage);
+    for idx in &pipelines {
+        let pipeline = if let Some(pipeline) = <L as Lookup<Pipeline<L>>>::lookup(storage, idx) {
+            pipeline
+        } else {
+            continue;
+        };
+        if !pipeline.status.is_finished()
+            || pipeline.parent_pipeline.is_some()
+            || pipeline.cim_deleted_at.is_some()
+        {
+            continue;
+        }
+        let refname = if let Some(refname) = pipeline.refname.as_ref() {
+            refname
+        } else {
+            continue;
+        };
+        let project =
+            if let Some(project) = <L as Lookup<Project<L>>>::lookup(storage, &pipeline.project) {
+                project
+            } else {
+                continue;
+            };
+
+        let jobs = pipeline_jobs.get(&pipeline.forge_id);
+        for rule in policy {
+            if !rule.applies_to(&project.instance_path, refname) {
+                continue;
+            }
+
+            let job = jobs.and_then(|jobs| jobs.get(rule.job.as_str()));
+            let kind = match job {
+                None => PolicyViolationKind::Missing,
+                Some(job) if rule.must_pass && job.state != JobState::Success => {
+                    PolicyViolationKind::NotPassed {
+                        state: job.state,
+                    }
+                },
+                Some(_) => continue,
+            };
+
+            projects
+                .entry(project.forge_id)
+                .or_insert_with(|| {
+                    ProjectPolicyViolations {
+                        project: project.forge_id,
+                        path: project.instance_path.clone(),
+                        violations: Vec::new(),
+                    }
+                })
+                .violations
+                .push(PolicyViolation {
+                    pipeline: pipeline.forge_id,
+                    url: pipeline.url.clone(),
+                    refname: refname.clone(),
+                    job: rule.job.clone(),
+                    kind,
+                });
+        }
+    }
+
+    projects
+        .into_values()
+        .map(|mut project| {
+            project
+                .violations
+                .sort_by_key(|violation| violation.pipeline);
+            project
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use ci_monitor_core::data::{Job, JobState, Pipeline, PipelineSource, PipelineStatus, Project};
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test::{self, at};
+    use crate::{PolicyViolationKind, RequiredJob};
+
+    #[test]
+    fn required_job_invalid_pattern() {
+        let err = RequiredJob::new("security-scan", "[").unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "invalid pattern '[': Pattern syntax error near position 0: invalid range pattern",
+        );
+    }
+
+    #[test]
+    fn required_job_violations() {
+        let mut lookup = VecLookup::default();
+        let instance = test::instance(&mut lookup);
+        let project = |id, path: &str| {
+            Project::builder()
+                .forge_id(id)
+                .instance(instance)
+                .instance_path(path)
+                .url("project")
+                .build()
+                .unwrap()
+        };
+        let app = lookup.store(project(1, "group/app"));
+        let docs = lookup.store(project(2, "other/docs"));
+        let user = test::user(&mut lookup, instance, 0);
+
+        let pipeline = |id, project, refname: &str, status| {
+            Pipeline::builder()
+                .project(project)
+                .sha("0000000000000000000000000000000000000000")
+                .refname(Some(refname.into()))
+                .source(PipelineSource::Push)
+                .status(status)
+                .forge_id(id)
+                .url(format!("pipeline/{}", id))
+                .created_at(at(0))
+                .updated_at(at(0))
+                .build()
+                .unwrap()
+        };
+        // Passing scan.
+        let passing = lookup.store(pipeline(10, app, "main", PipelineStatus::Success));
+        // Scan passes after a retry.
+        let retried = lookup.store(pipeline(11, app, "main", PipelineStatus::Success));
+        // Scan fails.
+        let failing = lookup.store(pipeline(12, app, "main", PipelineStatus::Failed));
+        // Scan is missing.
+        lookup.store(pipeline(13, app, "main", PipelineStatus::Success));
+        // Scan runs in a child pipeline.
+        let parent = lookup.store(pipeline(14, app, "release/1.0", PipelineStatus::Success));
+        let mut child = pipeline(15, app, "release/1.0", PipelineStatus::Success);
+        child.parent_pipeline = Some(parent);
+        let child = lookup.store(child);
+        // Other refs are not checked.
+        lookup.store(pipeline(16, app, "topic", PipelineStatus::Success));
+        // Running pipelines are not checked.
+        lookup.store(pipeline(17, app, "main", PipelineStatus::Running));
+        // Other projects are not checked.
+        lookup.store(pipeline(18, docs, "main", PipelineStatus::Success));
+
+        let job = |id, pipeline, state| {
+            Job::builder()
+                .user(user)
+                .name("security-scan")
+                .state(state)
+                .created_at(at(0))
+                .forge_id(id)
+                .pipeline(pipeline)
+                .build()
+                .unwrap()
+        };
+        lookup.store(job(100, passing, JobState::Success));
+        lookup.store(job(101, retried, JobState::Failed));
+        lookup.store(job(102, retried, JobState::Success));
+        lookup.store(job(103, failing, JobState::Failed));
+        lookup.store(job(104, child, JobState::Success));
+
+        let policy = [RequiredJob::new("security-scan", "main")
+            .unwrap()
+            .projects("group/*")
+            .unwrap()];
+        let violations = super::required_job_violations(&lookup, &policy);
+
+        assert_eq!(violations.len(), 1);
+        let project = &violations[0];
+        assert_eq!(project.project, 1);
+        assert_eq!(project.path, "group/app");
+        assert_eq!(project.violations.len(), 2);
+        assert_eq!(project.violations[0].pipeline, 12);
+        assert_eq!(project.violations[0].url, "pipeline/12");
+        assert_eq!(
+            project.violations[0].kind,
+            PolicyViolationKind::NotPassed {
+                state: JobState::Failed,
+            },
+        );
+        assert_eq!(project.violations[1].pipeline, 13);
+        assert_eq!(project.violations[1].kind, PolicyViolationKind::Missing);
+
+        // Only existence is required.
+        let policy = [RequiredJob::new("security-scan", "main")
+            .unwrap()
+            .must_pass(false)];
+        let violations = super::required_job_violations(&lookup, &policy);
+
+        assert_eq!(violations.len(), 2);
+        assert_eq!(violations[0].violations.len(), 1);
+        assert_eq!(violations[0].violations[0].pipeline, 13);
+        assert_eq!(violations[1].project, 2);
+
+        // Child pipelines count towards their parent.
+        let policy = [RequiredJob::new("security-scan", "release/*").unwrap()];
+        let violations = super::required_job_violations(&lookup, &policy);
+
+        assert!(violations.is_empty());
+    }
+}
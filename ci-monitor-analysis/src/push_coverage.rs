@@ -0,0 +1,333 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::{DateTime, Utc};
+use ci_monitor_core::data::{Pipeline, PipelineSource, Project, Push, User};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::AnalysisLookup;
+
+/// Why a push did not produce a pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[non_exhaustive]
+pub enum NoPipelineReason {
+    /// The commit requested that CI be skipped.
+    SkipCi,
+    /// The commit was only built by merge request pipelines.
+    MergeRequestOnly,
+    /// The reason could not be determined (e.g., workflow rules or a missing CI configuration).
+    Unknown,
+}
+
+impl NoPipelineReason {
+    /// The name of the reason.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::SkipCi => "skip_ci",
+            Self::MergeRequestOnly => "merge_request_only",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+/// A push which did not produce a pipeline.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct UnbuiltPush {
+    /// The path of the project.
+    pub project: String,
+    /// The ref which was pushed.
+    pub refname: String,
+    /// Whether the ref is a tag.
+    pub tag: bool,
+    /// The commit the ref was updated to.
+    pub sha: String,
+    /// The title of the commit.
+    pub title: String,
+    /// The handle of the user who pushed.
+    pub author: Option<String>,
+    /// When the push happened.
+    pub pushed_at: DateTime<Utc>,
+    /// Why the push did not produce a pipeline.
+    ///
+    /// One of `skip_ci`, `merge_request_only`, or `unknown`.
+    #[serde(serialize_with = "crate::serialize::name")]
+    #[schemars(with = "String")]
+    pub reason: NoPipelineReason,
+}
+
+/// How many pushes to a project produced pipelines.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct ProjectPushCoverage {
+    /// The path of the project.
+    pub project: String,
+    /// The number of pushes.
+    pub pushes: usize,
+    /// The number of pushes which produced a pipeline.
+    pub built: usize,
+    /// The number of pushes which did not produce a pipeline.
+    pub unbuilt: usize,
+}
+
+/// How many pushes produced pipelines.
+#[derive(Debug, Clone, Default, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct PushCoverage {
+    /// Projects sorted by the number of pushes without a pipeline (most first).
+    pub projects: Vec<ProjectPushCoverage>,
+    /// Pushes without a pipeline sorted by when they happened.
+    pub unbuilt: Vec<UnbuiltPush>,
+}
+
+/// Strip the namespace from a refname.
+fn short_refname(refname: &str) -> &str {
+    refname
+        .strip_prefix("refs/heads/")
+        .or_else(|| refname.strip_prefix("refs/tags/"))
+        .unwrap_or(refname)
+}
+
+/// Whether a commit title asks for CI to be skipped.
+fn requests_skip_ci(title: &str) -> bool {
+    let title = title.to_lowercase();
+    title.contains("[skip ci]") || title.contains("[ci skip]")
+}
+
+/// Find pushes which did not produce a pipeline.
+///
+/// A push is built if a pipeline exists for its commit on the pushed ref. Reasons for missing
+/// pipelines are inferred where possible: commits which ask for CI to be skipped and commits which
+/// were only built by merge request pipelines. Only pushes at or after `since` are considered.
+/// Pipelines must have been discovered for the report to be accurate; pushes newer than the last
+/// pipeline discovery will appear as unbuilt.
+pub fn push_coverage<L>(storage: &L, since: Option<DateTime<Utc>>) -> PushCoverage
+where
+    L: AnalysisLookup<L>,
+{
+    let mut branch_pipelines = BTreeSet::new();
+    let mut merge_request_pipelines = BTreeSet::new();
+    for idx in <L as DiscoverableLookup<Pipeline<L>>>::all_indices(storage) {
+        let pipeline = if let Some(pipeline) = <L as Lookup<Pipeline<L>>>::lookup(storage, &idx) {
+            pipeline
+        } else {
+            continue;
+        };
+        let project =
+            if let Some(project) = <L as Lookup<Project<L>>>::lookup(storage, &pipeline.project) {
+                project.forge_id
+            } else {
+                continue;
+            };
+
+        if pipeline.source == PipelineSource::MergeRequestEvent {
+            merge_request_pipelines.insert((project, pipeline.sha.clone()));
+        } else if let Some(refname) = pipeline.refname.as_deref() {
+            branch_pipelines.insert((
+                project,
+                short_refname(refname).to_string(),
+                pipeline.sha.clone(),
+            ));
+        }
+    }
+
+    let mut projects: BTreeMap<_, ProjectPushCoverage> = BTreeMap::new();
+    let mut unbuilt = Vec::new();
+    for idx in <L as DiscoverableLookup<Push<L>>>::all_indices(storage) {
+        let push = if let Some(push) = <L as Lookup<Push<L>>>::lookup(storage, &idx) {
+            push
+        } else {
+            continue;
+        };
+        if push.cim_deleted_at.is_some() || since.is_some_and(|since| push.pushed_at < since) {
+            continue;
+        }
+        let project =
+            if let Some(project) = <L as Lookup<Project<L>>>::lookup(storage, &push.project) {
+                project
+            } else {
+                continue;
+            };
+
+        let entry = projects.entry(project.forge_id).or_insert_with(|| {
+            ProjectPushCoverage {
+                project: project.instance_path.clone(),
+                pushes: 0,
+                built: 0,
+                unbuilt: 0,
+            }
+        });
+        entry.pushes += 1;
+
+        let refname = short_refname(&push.refname);
+        if branch_pipelines.contains(&(project.forge_id, refname.to_string(), push.sha.clone())) {
+            entry.built += 1;
+            continue;
+        }
+        entry.unbuilt += 1;
+
+        let reason = if requests_skip_ci(&push.commit_title) {
+            NoPipelineReason::SkipCi
+        } else if merge_request_pipelines.contains(&(project.forge_id, push.sha.clone())) {
+            NoPipelineReason::MergeRequestOnly
+        } else {
+            NoPipelineReason::Unknown
+        };
+        let author = push
+            .author
+            .as_ref()
+            .and_then(|author| <L as Lookup<User<L>>>::lookup(storage, author))
+            .map(|author| author.handle.clone());
+        unbuilt.push(UnbuiltPush {
+            project: project.instance_path.clone(),
+            refname: refname.into(),
+            tag: push.tag,
+            sha: push.sha.clone(),
+            title: push.commit_title.clone(),
+            author,
+            pushed_at: push.pushed_at,
+            reason,
+        });
+    }
+
+    let mut projects = projects.into_values().collect::<Vec<_>>();
+    projects.sort_by(|lhs, rhs| {
+        rhs.unbuilt
+            .cmp(&lhs.unbuilt)
+            .then_with(|| lhs.project.cmp(&rhs.project))
+    });
+    unbuilt.sort_by_key(|push| push.pushed_at);
+
+    PushCoverage {
+        projects,
+        unbuilt,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ci_monitor_core::data::{Pipeline, PipelineSource, PipelineStatus, Project, Push, User};
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test::{self, at};
+    use crate::NoPipelineReason;
+
+    #[test]
+    fn requests_skip_ci() {
+        assert!(super::requests_skip_ci("docs: fix typo [skip ci]"));
+        assert!(super::requests_skip_ci("[CI SKIP] docs: fix typo"));
+        assert!(!super::requests_skip_ci("ci: skip slow tests"));
+    }
+
+    #[test]
+    fn push_coverage() {
+        let mut lookup = VecLookup::default();
+        let instance = test::instance(&mut lookup);
+        let user = User::builder()
+            .handle("user")
+            .forge_id(0)
+            .instance(instance)
+            .build()
+            .unwrap();
+        let user = lookup.store(user);
+        let projects = [1, 2].map(|id| {
+            let project = Project::builder()
+                .forge_id(id)
+                .instance(instance)
+                .instance_path(format!("group/project{id}"))
+                .url(format!("project{id}"))
+                .build()
+                .unwrap();
+            lookup.store(project)
+        });
+
+        // (project, refname, sha, source)
+        let pipelines = [
+            (0, Some("main"), "a", PipelineSource::Push),
+            (
+                0,
+                Some("refs/merge-requests/1/head"),
+                "d",
+                PipelineSource::MergeRequestEvent,
+            ),
+            (1, Some("refs/heads/topic"), "a", PipelineSource::Push),
+        ];
+        for (id, (project, refname, sha, source)) in pipelines.into_iter().enumerate() {
+            let pipeline = Pipeline::builder()
+                .project(projects[project])
+                .sha(sha)
+                .refname(refname.map(Into::into))
+                .source(source)
+                .status(PipelineStatus::Success)
+                .forge_id(id as u64)
+                .url("url")
+                .created_at(at(0))
+                .updated_at(at(0))
+                .build()
+                .unwrap();
+            lookup.store(pipeline);
+        }
+
+        // (project, refname, sha, title, pushed_at)
+        let pushes = [
+            (0, "main", "a", "built", 1),
+            (0, "topic", "a", "other ref", 2),
+            (0, "main", "b", "docs [skip ci]", 3),
+            (0, "topic", "d", "merge request", 4),
+            (1, "topic", "a", "built", 5),
+            (0, "main", "e", "old", -10),
+        ];
+        for (id, (project, refname, sha, title, pushed_at)) in pushes.into_iter().enumerate() {
+            let push = Push::builder()
+                .author(Some(user))
+                .commit_title(title)
+                .project(projects[project])
+                .refname(refname)
+                .sha(sha)
+                .forge_id(id as u64)
+                .pushed_at(at(pushed_at))
+                .build()
+                .unwrap();
+            lookup.store(push);
+        }
+
+        let coverage = super::push_coverage(&lookup, Some(at(0)));
+
+        assert_eq!(coverage.projects.len(), 2);
+        assert_eq!(coverage.projects[0].project, "group/project1");
+        assert_eq!(coverage.projects[0].pushes, 4);
+        assert_eq!(coverage.projects[0].built, 1);
+        assert_eq!(coverage.projects[0].unbuilt, 3);
+        assert_eq!(coverage.projects[1].project, "group/project2");
+        assert_eq!(coverage.projects[1].pushes, 1);
+        assert_eq!(coverage.projects[1].built, 1);
+
+        let reasons = coverage
+            .unbuilt
+            .iter()
+            .map(|push| (push.title.as_str(), push.reason))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            reasons,
+            [
+                ("other ref", NoPipelineReason::Unknown),
+                ("docs [skip ci]", NoPipelineReason::SkipCi),
+                ("merge request", NoPipelineReason::MergeRequestOnly),
+            ],
+        );
+        assert_eq!(coverage.unbuilt[0].author.as_deref(), Some("user"));
+
+        let coverage = super::push_coverage(&lookup, None);
+        assert_eq!(coverage.unbuilt.len(), 4);
+        assert_eq!(coverage.unbuilt[0].title, "old");
+    }
+}
@@ -0,0 +1,320 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::cmp::Reverse;
+
+use chrono::{DateTime, Duration, Utc};
+use ci_monitor_core::data::{Job, JobState, Pipeline, Project};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+use glob::Pattern;
+use schemars::JsonSchema;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::AnalysisLookup;
+
+/// Errors which may occur when declaring quarantined jobs.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum QuarantineError {
+    /// A pattern is invalid.
+    #[error("invalid quarantine pattern '{}': {}", pattern, source)]
+    InvalidPattern {
+        /// The pattern.
+        pattern: String,
+        /// The source of the error.
+        #[source]
+        source: glob::PatternError,
+    },
+    /// A quarantine ends before it starts.
+    #[error("quarantine of '{}' ends before it starts", job)]
+    InvalidPeriod {
+        /// The job pattern.
+        job: String,
+    },
+}
+
+impl QuarantineError {
+    fn invalid_pattern(pattern: String, source: glob::PatternError) -> Self {
+        Self::InvalidPattern {
+            pattern,
+            source,
+        }
+    }
+
+    fn invalid_period(job: String) -> Self {
+        Self::InvalidPeriod {
+            job,
+        }
+    }
+}
+
+fn pattern(pattern: &str) -> Result<Pattern, QuarantineError> {
+    Pattern::new(pattern).map_err(|err| QuarantineError::invalid_pattern(pattern.into(), err))
+}
+
+/// A known-broken job whose failures are suppressed for a period of time.
+#[derive(Debug, Clone)]
+pub struct QuarantinedJob {
+    projects: Pattern,
+    job: Pattern,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    reason: Option<String>,
+}
+
+impl QuarantinedJob {
+    /// Quarantine jobs matching a glob pattern in projects whose path matches a glob pattern.
+    ///
+    /// Failures between `start` and `end` are suppressed.
+    pub fn new(
+        projects: &str,
+        job: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Self, QuarantineError> {
+        if end < start {
+            return Err(QuarantineError::invalid_period(job.into()));
+        }
+
+        Ok(Self {
+            projects: pattern(projects)?,
+            job: pattern(job)?,
+            start,
+            end,
+            reason: None,
+        })
+    }
+
+    /// Record why the job is quarantined.
+    pub fn reason<R>(mut self, reason: R) -> Self
+    where
+        R: Into<String>,
+    {
+        self.reason = Some(reason.into());
+        self
+    }
+
+    fn applies_to(&self, project: &str, job: &str, at: DateTime<Utc>) -> bool {
+        self.start <= at && at < self.end && self.projects.matches(project) && self.job.matches(job)
+    }
+}
+
+/// A list of quarantined jobs.
+#[derive(Debug, Clone, Default)]
+pub struct Quarantine {
+    jobs: Vec<QuarantinedJob>,
+}
+
+impl Quarantine {
+    /// Add a quarantined job.
+    pub fn job(mut self, job: QuarantinedJob) -> Self {
+        self.jobs.push(job);
+        self
+    }
+
+    /// Whether any jobs are quarantined.
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    /// Whether a failure of a job at a given time is suppressed.
+    pub fn is_quarantined(&self, project: &str, job: &str, at: DateTime<Utc>) -> bool {
+        self.jobs
+            .iter()
+            .any(|quarantined| quarantined.applies_to(project, job, at))
+    }
+
+    /// Whether a failed job is suppressed.
+    pub(crate) fn suppresses<L>(&self, project: &Project<L>, job: &Job<L>) -> bool
+    where
+        L: AnalysisLookup<L>,
+    {
+        job.state == JobState::Failed
+            && self.is_quarantined(
+                &project.instance_path,
+                &job.name,
+                job.finished_at.unwrap_or(job.created_at),
+            )
+    }
+}
+
+/// The state of a quarantined job.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct QuarantineStatus {
+    /// The glob pattern for project paths.
+    pub projects: String,
+    /// The glob pattern for job names.
+    pub job: String,
+    /// Why the job is quarantined.
+    pub reason: Option<String>,
+    /// When the quarantine started.
+    pub start: DateTime<Utc>,
+    /// When the quarantine ends.
+    pub end: DateTime<Utc>,
+    /// How long the job has been quarantined.
+    #[serde(serialize_with = "crate::serialize::seconds")]
+    #[schemars(with = "i64")]
+    pub quarantined_for: Duration,
+    /// Whether the quarantine had ended when the report was made.
+    ///
+    /// Expired quarantines should either be removed or extended deliberately.
+    pub expired: bool,
+    /// The number of failures which have been suppressed.
+    pub suppressed_failures: usize,
+}
+
+/// Report on how long jobs have been quarantined and how many failures were suppressed.
+///
+/// Results are sorted by how long the jobs have been quarantined, longest first.
+pub fn quarantine_report<L>(
+    storage: &L,
+    quarantine: &Quarantine,
+    now: DateTime<Utc>,
+) -> Vec<QuarantineStatus>
+where
+    L: AnalysisLookup<L>,
+{
+    let mut statuses = quarantine
+        .jobs
+        .iter()
+        .map(|quarantined| {
+            QuarantineStatus {
+                projects: quarantined.projects.as_str().into(),
+                job: quarantined.job.as_str().into(),
+                reason: quarantined.reason.clone(),
+                start: quarantined.start,
+                end: quarantined.end,
+                quarantined_for: (now.min(quarantined.end) - quarantined.start)
+                    .max(Duration::zero()),
+                expired: quarantined.end <= now,
+                suppressed_failures: 0,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let jobs = <L as DiscoverableLookup<Job<L>>>::all_indices(storage);
+    for idx in &jobs {
+        let job = if let Some(job) = <L as Lookup<Job<L>>>::lookup(storage, idx) {
+            job
+        } else {
+            continue;
+        };
+        if job.state != JobState::Failed || job.cim_deleted_at.is_some() {
+            continue;
+        }
+        let project = <L as Lookup<Pipeline<L>>>::lookup(storage, &job.pipeline)
+            .and_then(|pipeline| <L as Lookup<Project<L>>>::lookup(storage, &pipeline.project));
+        let project = if let Some(project) = project {
+            project
+        } else {
+            continue;
+        };
+
+        let failed_at = job.finished_at.unwrap_or(job.created_at);
+        for (quarantined, status) in quarantine.jobs.iter().zip(statuses.iter_mut()) {
+            if quarantined.applies_to(&project.instance_path, &job.name, failed_at) {
+                status.suppressed_failures += 1;
+            }
+        }
+    }
+
+    statuses.sort_by_key(|status| Reverse(status.quarantined_for));
+    statuses
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use ci_monitor_core::data::{Job, JobState, PipelineStatus};
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test::{self, at_hour};
+    use crate::{Quarantine, QuarantineError, QuarantinedJob};
+
+    #[test]
+    fn is_quarantined() {
+        let quarantine = Quarantine::default()
+            .job(QuarantinedJob::new("group/*", "test:flaky*", at_hour(0), at_hour(24)).unwrap());
+
+        assert!(quarantine.is_quarantined("group/project", "test:flaky-network", at_hour(1)));
+        assert!(!quarantine.is_quarantined("group/project", "test:flaky-network", at_hour(24)));
+        assert!(!quarantine.is_quarantined("group/project", "test:flaky-network", at_hour(-1)));
+        assert!(!quarantine.is_quarantined("group/project", "build", at_hour(1)));
+        assert!(!quarantine.is_quarantined("other/project", "test:flaky-network", at_hour(1)));
+    }
+
+    #[test]
+    fn invalid_quarantine() {
+        let err = QuarantinedJob::new("[", "job", at_hour(0), at_hour(1)).unwrap_err();
+        if let QuarantineError::InvalidPattern {
+            pattern, ..
+        } = err
+        {
+            assert_eq!(pattern, "[");
+        } else {
+            panic!("unexpected error: {:?}", err);
+        }
+
+        let err = QuarantinedJob::new("*", "job", at_hour(1), at_hour(0)).unwrap_err();
+        assert_eq!(err.to_string(), "quarantine of 'job' ends before it starts");
+    }
+
+    #[test]
+    fn quarantine_report() {
+        let mut lookup = VecLookup::default();
+        let instance = test::instance(&mut lookup);
+        let user = test::user(&mut lookup, instance, 0);
+        let project = test::project(&mut lookup, instance, 1, "group/project");
+        let pipeline = test::pipeline(project, 1, PipelineStatus::Failed, at_hour(0));
+        let pipeline = lookup.store(pipeline);
+
+        // (name, state, finished)
+        let jobs = [
+            ("flaky", JobState::Failed, 2),
+            ("flaky", JobState::Failed, 30),
+            ("flaky", JobState::Success, 3),
+            ("flaky", JobState::Failed, -2),
+            ("broken", JobState::Failed, 4),
+        ];
+        for (id, (name, state, finished)) in jobs.into_iter().enumerate() {
+            let job = Job::builder()
+                .user(user)
+                .name(name)
+                .state(state)
+                .created_at(at_hour(finished - 1))
+                .finished_at(Some(at_hour(finished)))
+                .forge_id(id as u64)
+                .pipeline(pipeline)
+                .build()
+                .unwrap();
+            lookup.store(job);
+        }
+
+        let quarantine = Quarantine::default()
+            .job(
+                QuarantinedJob::new("group/*", "flaky", at_hour(0), at_hour(24))
+                    .unwrap()
+                    .reason("network issues"),
+            )
+            .job(QuarantinedJob::new("group/*", "broken", at_hour(1), at_hour(72)).unwrap());
+        let report = super::quarantine_report(&lookup, &quarantine, at_hour(48));
+
+        assert_eq!(report.len(), 2);
+        assert_eq!(report[0].job, "broken");
+        assert_eq!(report[0].quarantined_for, Duration::hours(47));
+        assert_eq!(report[0].suppressed_failures, 1);
+        assert!(!report[0].expired);
+        assert_eq!(report[1].job, "flaky");
+        assert_eq!(report[1].reason.as_deref(), Some("network issues"));
+        assert_eq!(report[1].quarantined_for, Duration::hours(24));
+        assert_eq!(report[1].suppressed_failures, 1);
+        assert!(report[1].expired);
+    }
+}
@@ -0,0 +1,266 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, Utc};
+use ci_monitor_core::data::{Job, Runner};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::sections::median;
+use crate::AnalysisLookup;
+
+/// How the queue time of jobs requesting a tag splits between waiting and runner startup.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct QueueTimeBreakdown {
+    /// The tag (empty for untagged jobs).
+    pub tag: String,
+    /// The number of jobs which requested the tag.
+    pub jobs: usize,
+    /// The total time (in seconds) jobs waited for any runner to have capacity.
+    pub waiting: f64,
+    /// The total time (in seconds) jobs waited for their runner to start.
+    pub starting: f64,
+    /// The median time (in seconds) jobs waited for any runner to have capacity.
+    pub median_waiting: f64,
+    /// The median time (in seconds) jobs waited for their runner to start.
+    pub median_starting: f64,
+}
+
+impl QueueTimeBreakdown {
+    /// The fraction of queue time spent waiting for runners to start.
+    pub fn starting_fraction(&self) -> f64 {
+        let total = self.waiting + self.starting;
+        if total > 0. {
+            self.starting / total
+        } else {
+            0.
+        }
+    }
+}
+
+fn seconds(duration: Duration) -> f64 {
+    duration.num_milliseconds() as f64 / 1000.
+}
+
+/// Split the time a job was queued into waiting for capacity and waiting for the runner to start.
+///
+/// `finishes` are the (sorted) times at which jobs on the runner which picked up the job finished.
+fn split_queue_time(
+    queued_at: DateTime<Utc>,
+    started_at: DateTime<Utc>,
+    finishes: &[DateTime<Utc>],
+) -> (f64, f64) {
+    let before_start = finishes.partition_point(|&finished_at| finished_at <= started_at);
+    let freed_at = before_start
+        .checked_sub(1)
+        .map(|idx| finishes[idx])
+        .filter(|&finished_at| queued_at < finished_at);
+
+    if let Some(freed_at) = freed_at {
+        (
+            seconds(freed_at - queued_at),
+            seconds(started_at - freed_at),
+        )
+    } else {
+        (0., seconds(started_at - queued_at))
+    }
+}
+
+#[derive(Default)]
+struct Samples {
+    waiting: Vec<f64>,
+    starting: Vec<f64>,
+}
+
+/// Decompose the queue time of jobs into waiting for any runner and waiting for a runner to start.
+///
+/// The forge only records when a runner last contacted it, so contacts are reconstructed from the
+/// transitions of the jobs each runner executed. If a job on the runner which picked up a job
+/// finished while the job was queued, the job waited for capacity until then and the remainder
+/// is attributed to the runner starting (e.g., cleaning up or provisioning a new instance).
+/// Otherwise, the runner had capacity but had yet to start (as is the case for autoscaled runners
+/// provisioning an instance), so all of the queue time is attributed to it starting.
+///
+/// Only jobs which started at or after `since` with a known queue time and runner are considered.
+/// Jobs count towards each tag they request and untagged jobs use an empty tag. Results are sorted
+/// by the total time spent waiting for runners to start (longest first).
+pub fn queue_time_breakdown<L>(storage: &L, since: DateTime<Utc>) -> Vec<QueueTimeBreakdown>
+where
+    L: AnalysisLookup<L>,
+{
+    let indices = <L as DiscoverableLookup<Job<L>>>::all_indices(storage);
+    let jobs = indices
+        .iter()
+        .filter_map(|idx| <L as Lookup<Job<L>>>::lookup(storage, idx))
+        .filter(|job| job.cim_deleted_at.is_none())
+        .collect::<Vec<_>>();
+
+    let runner_id = |job: &Job<L>| {
+        job.runner
+            .as_ref()
+            .and_then(|idx| <L as Lookup<Runner<L>>>::lookup(storage, idx))
+            .map(|runner| runner.forge_id)
+    };
+
+    let mut finishes: BTreeMap<u64, Vec<DateTime<Utc>>> = BTreeMap::new();
+    for job in &jobs {
+        if let (Some(runner), Some(finished_at)) = (runner_id(job), job.finished_at) {
+            finishes.entry(runner).or_default().push(finished_at);
+        }
+    }
+    for runner_finishes in finishes.values_mut() {
+        runner_finishes.sort();
+    }
+
+    let mut samples: BTreeMap<String, Samples> = BTreeMap::new();
+    for job in &jobs {
+        let (runner, started_at, queued) = if let (Some(runner), Some(started_at), Some(queued)) =
+            (runner_id(job), job.started_at, job.queued_duration)
+        {
+            (runner, started_at, queued)
+        } else {
+            continue;
+        };
+        if started_at < since {
+            continue;
+        }
+
+        let queued_at = started_at - queued.duration();
+        let runner_finishes = finishes.get(&runner).map(Vec::as_slice).unwrap_or_default();
+        let (waiting, starting) = split_queue_time(queued_at, started_at, runner_finishes);
+
+        let untagged = [String::new()];
+        let tags = if job.tags.is_empty() {
+            &untagged[..]
+        } else {
+            &job.tags[..]
+        };
+        for tag in tags {
+            let entry = samples.entry(tag.clone()).or_default();
+            entry.waiting.push(waiting);
+            entry.starting.push(starting);
+        }
+    }
+
+    let mut breakdowns = samples
+        .into_iter()
+        .map(|(tag, mut samples)| {
+            QueueTimeBreakdown {
+                tag,
+                jobs: samples.waiting.len(),
+                waiting: samples.waiting.iter().sum(),
+                starting: samples.starting.iter().sum(),
+                median_waiting: median(&mut samples.waiting),
+                median_starting: median(&mut samples.starting),
+            }
+        })
+        .collect::<Vec<_>>();
+    breakdowns.sort_by(|lhs, rhs| rhs.starting.total_cmp(&lhs.starting));
+    breakdowns
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use ci_monitor_core::data::{
+        Elapsed, Job, JobState, PipelineStatus, Runner, RunnerProtectionLevel, RunnerType,
+    };
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test::{self, at};
+
+    #[test]
+    fn split_queue_time() {
+        let finishes = [at(2), at(5), at(20)];
+
+        // A job on the runner finished while queued.
+        assert_eq!(
+            super::split_queue_time(at(0), at(10), &finishes),
+            (300., 300.),
+        );
+        // No job on the runner finished while queued.
+        assert_eq!(
+            super::split_queue_time(at(6), at(10), &finishes),
+            (0., 240.),
+        );
+        assert_eq!(super::split_queue_time(at(0), at(1), &[]), (0., 60.));
+    }
+
+    #[test]
+    fn queue_time_breakdown() {
+        let mut lookup = VecLookup::default();
+        let instance = test::instance(&mut lookup);
+        let user = test::user(&mut lookup, instance, 0);
+        let project = test::project(&mut lookup, instance, 1, "group/project");
+        let runners = [1, 2].map(|id| {
+            let runner = Runner::builder()
+                .runner_type(RunnerType::Instance)
+                .protection_level(RunnerProtectionLevel::Any)
+                .forge_id(id)
+                .instance(instance)
+                .build()
+                .unwrap();
+            lookup.store(runner)
+        });
+        let pipeline = test::pipeline(project, 1, PipelineStatus::Success, at(0));
+        let pipeline = lookup.store(pipeline);
+
+        // (tags, runner, queued minutes, start, end)
+        let jobs = [
+            (vec!["docker"], 0, 0, 0, 10),
+            (vec!["docker"], 0, 6, 12, 20),
+            (vec!["autoscale"], 1, 5, 5, 10),
+            (vec![], 1, 1, 10, 12),
+            (vec!["docker"], 0, 1, -30, -20),
+        ];
+        for (id, (tags, runner, queued, start, end)) in jobs.into_iter().enumerate() {
+            let job = Job::builder()
+                .user(user)
+                .name("job")
+                .tags(tags.into_iter().map(String::from).collect::<Vec<_>>())
+                .state(JobState::Success)
+                .created_at(at(start - queued))
+                .started_at(Some(at(start)))
+                .finished_at(Some(at(end)))
+                .queued_duration(Elapsed::new(Duration::minutes(queued)))
+                .runner(Some(runners[runner]))
+                .forge_id(id as u64)
+                .pipeline(pipeline)
+                .build()
+                .unwrap();
+            lookup.store(job);
+        }
+
+        let breakdowns = super::queue_time_breakdown(&lookup, at(0));
+
+        // (tag, jobs, waiting, starting)
+        let breakdowns = breakdowns
+            .iter()
+            .map(|breakdown| {
+                (
+                    breakdown.tag.as_str(),
+                    breakdown.jobs,
+                    breakdown.waiting,
+                    breakdown.starting,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            breakdowns,
+            [
+                ("autoscale", 1, 0., 300.),
+                ("docker", 2, 240., 120.),
+                ("", 1, 60., 0.),
+            ],
+        );
+    }
+}
@@ -0,0 +1,241 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use ci_monitor_core::data::{Pipeline, PipelineStatus, Project};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::AnalysisLookup;
+
+/// A stored pipeline to check against the forge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SampledPipeline {
+    /// The path of the project.
+    pub project_path: String,
+    /// The ID of the project on the forge.
+    pub project: u64,
+    /// The ID of the pipeline on the forge.
+    pub pipeline: u64,
+    /// The status of the pipeline when it was sampled.
+    pub status: PipelineStatus,
+}
+
+/// How far the stored pipelines of a project have diverged from the forge.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct PipelineDivergence {
+    /// The path of the project.
+    pub project: String,
+    /// The number of pipelines sampled.
+    pub sampled: usize,
+    /// The number of sampled pipelines which were checked against the forge.
+    pub checked: usize,
+    /// The number of checked pipelines which have been removed from the forge.
+    pub removed: usize,
+    /// The number of checked pipelines whose stored status was out of date.
+    pub stale: usize,
+}
+
+impl PipelineDivergence {
+    /// The fraction of checked pipelines which differed from the forge.
+    pub fn rate(&self) -> f64 {
+        if self.checked == 0 {
+            0.
+        } else {
+            (self.removed + self.stale) as f64 / self.checked as f64
+        }
+    }
+}
+
+/// Sample stored pipelines of each project to check against the forge.
+///
+/// Only finished pipelines of projects which have not been deleted are sampled since active
+/// pipelines are refreshed by crawls anyway. Up to `per_project` pipelines are sampled from each
+/// project, preferring those which were refreshed the longest time ago so that repeated samples
+/// cover the entire store.
+pub fn pipeline_reconciliation_sample<L>(storage: &L, per_project: usize) -> Vec<SampledPipeline>
+where
+    L: AnalysisLookup<L>,
+{
+    let mut candidates: BTreeMap<_, Vec<_>> = BTreeMap::new();
+    let pipelines = <L as DiscoverableLookup<Pipeline<L>>>::all_indices(storage);
+    for idx in &pipelines {
+        let pipeline = if let Some(pipeline) = <L as Lookup<Pipeline<L>>>::lookup(storage, idx) {
+            pipeline
+        } else {
+            continue;
+        };
+        if pipeline.cim_deleted_at.is_some() || !pipeline.status.is_finished() {
+            continue;
+        }
+        let project =
+            if let Some(project) = <L as Lookup<Project<L>>>::lookup(storage, &pipeline.project) {
+                project
+            } else {
+                continue;
+            };
+        if project.cim_deleted_at.is_some() {
+            continue;
+        }
+
+        candidates
+            .entry((project.instance_path.as_str(), project.forge_id))
+            .or_default()
+            .push(pipeline);
+    }
+
+    candidates
+        .into_iter()
+        .flat_map(|((path, project), mut pipelines)| {
+            pipelines.sort_by_key(|pipeline| (pipeline.cim_refreshed_at, pipeline.forge_id));
+            pipelines
+                .into_iter()
+                .take(per_project)
+                .map(move |pipeline| {
+                    SampledPipeline {
+                        project_path: path.into(),
+                        project,
+                        pipeline: pipeline.forge_id,
+                        status: pipeline.status,
+                    }
+                })
+        })
+        .collect()
+}
+
+/// Compare sampled pipelines against the store after checking them against the forge.
+///
+/// A sampled pipeline counts as checked if it has been refreshed (or deleted) since `since`. Checked
+/// pipelines have diverged if they have been deleted or if their status changed. Results are sorted
+/// by divergence rate (highest first).
+pub fn pipeline_divergence<L>(
+    storage: &L,
+    samples: &[SampledPipeline],
+    since: DateTime<Utc>,
+) -> Vec<PipelineDivergence>
+where
+    L: AnalysisLookup<L>,
+{
+    let mut projects: BTreeMap<&str, PipelineDivergence> = BTreeMap::new();
+    for sample in samples {
+        let entry = projects.entry(&sample.project_path).or_insert_with(|| {
+            PipelineDivergence {
+                project: sample.project_path.clone(),
+                sampled: 0,
+                checked: 0,
+                removed: 0,
+                stale: 0,
+            }
+        });
+        entry.sampled += 1;
+
+        let idx = if let Some(idx) =
+            <L as DiscoverableLookup<Pipeline<L>>>::find(storage, sample.pipeline)
+        {
+            idx
+        } else {
+            continue;
+        };
+        let pipeline = if let Some(pipeline) = <L as Lookup<Pipeline<L>>>::lookup(storage, &idx) {
+            pipeline
+        } else {
+            continue;
+        };
+
+        if let Some(deleted_at) = pipeline.cim_deleted_at {
+            if since <= deleted_at {
+                entry.checked += 1;
+                entry.removed += 1;
+            }
+        } else if since <= pipeline.cim_refreshed_at {
+            entry.checked += 1;
+            if pipeline.status != sample.status {
+                entry.stale += 1;
+            }
+        }
+    }
+
+    let mut divergences = projects.into_values().collect::<Vec<_>>();
+    divergences.sort_by(|lhs, rhs| rhs.rate().total_cmp(&lhs.rate()));
+    divergences
+}
+
+#[cfg(test)]
+mod tests {
+    use ci_monitor_core::data::{Pipeline, PipelineStatus};
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::{DiscoverableLookup, VecLookup};
+
+    use crate::test::{self, at};
+
+    #[test]
+    fn pipeline_reconciliation() {
+        let mut lookup = VecLookup::default();
+        let instance = test::instance(&mut lookup);
+        let busy = test::project(&mut lookup, instance, 1, "group/busy");
+        let quiet = test::project(&mut lookup, instance, 2, "group/quiet");
+
+        // (project, id, status, refreshed minute)
+        let pipelines = [
+            (busy, 10, PipelineStatus::Success, 30),
+            (busy, 11, PipelineStatus::Failed, 10),
+            (busy, 12, PipelineStatus::Success, 20),
+            (busy, 13, PipelineStatus::Running, 0),
+            (quiet, 20, PipelineStatus::Success, 0),
+        ];
+        for (project, id, status, refreshed) in pipelines {
+            let mut pipeline = test::pipeline(project, id, status, at(0));
+            pipeline.cim_refreshed_at = at(refreshed);
+            lookup.store(pipeline);
+        }
+
+        let samples = super::pipeline_reconciliation_sample(&lookup, 2);
+        // Running pipelines are skipped and the least recently refreshed are preferred.
+        let sampled = samples
+            .iter()
+            .map(|sample| (sample.project, sample.pipeline))
+            .collect::<Vec<_>>();
+        assert_eq!(sampled, [(1, 11), (1, 12), (2, 20)]);
+
+        // Pipeline 11 was removed upstream and 12 finished differently than stored; 20 was not
+        // checked.
+        let stored = |lookup: &VecLookup, id| {
+            let idx =
+                <VecLookup as DiscoverableLookup<Pipeline<VecLookup>>>::find(lookup, id).unwrap();
+            <VecLookup as Lookup<Pipeline<VecLookup>>>::lookup(lookup, &idx)
+                .cloned()
+                .unwrap()
+        };
+        let mut removed = stored(&lookup, 11);
+        removed.cim_deleted_at = Some(at(60));
+        lookup.store(removed);
+        let mut stale = stored(&lookup, 12);
+        stale.status = PipelineStatus::Failed;
+        stale.cim_refreshed_at = at(60);
+        lookup.store(stale);
+
+        let divergences = super::pipeline_divergence(&lookup, &samples, at(50));
+        assert_eq!(divergences.len(), 2);
+        let busy = &divergences[0];
+        assert_eq!(busy.project, "group/busy");
+        assert_eq!(busy.sampled, 2);
+        assert_eq!(busy.checked, 2);
+        assert_eq!(busy.removed, 1);
+        assert_eq!(busy.stale, 1);
+        assert_eq!(busy.rate(), 1.);
+        let quiet = &divergences[1];
+        assert_eq!(quiet.project, "group/quiet");
+        assert_eq!(quiet.sampled, 1);
+        assert_eq!(quiet.checked, 0);
+        assert_eq!(quiet.rate(), 0.);
+    }
+}
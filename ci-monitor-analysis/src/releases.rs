@@ -0,0 +1,328 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, Utc};
+use ci_monitor_core::data::{Pipeline, PipelineStatus, Project, Release};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::AnalysisLookup;
+
+/// The state of a release's pipeline when the release was published.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ReleasePipelineState {
+    /// No pipeline for the release is known.
+    Unknown,
+    /// The pipeline had not finished.
+    Unfinished,
+    /// The pipeline had finished.
+    Finished {
+        /// The status of the pipeline.
+        status: PipelineStatus,
+    },
+}
+
+impl ReleasePipelineState {
+    /// The name of the state.
+    ///
+    /// Either the status of the finished pipeline, `unfinished`, or `unknown`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Unknown => "unknown",
+            Self::Unfinished => "unfinished",
+            Self::Finished {
+                status,
+            } => status.name(),
+        }
+    }
+}
+
+/// A release and the state of its pipeline.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct ReleaseSummary {
+    /// The tag of the release.
+    pub tag: String,
+    /// When the release was published.
+    pub released_at: DateTime<Utc>,
+    /// The ID of the release's pipeline.
+    pub pipeline: Option<u64>,
+    /// The state of the pipeline when the release was published.
+    ///
+    /// Either the status of the finished pipeline, `unfinished`, or `unknown`.
+    #[serde(serialize_with = "crate::serialize::name")]
+    #[schemars(with = "String")]
+    pub pipeline_state: ReleasePipelineState,
+}
+
+/// The releases of a project.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct ProjectReleases {
+    /// The ID of the project.
+    pub project: u64,
+    /// The path of the project on its instance.
+    pub path: String,
+    /// Releases ordered by when they were published.
+    pub releases: Vec<ReleaseSummary>,
+}
+
+impl ProjectReleases {
+    /// The time between consecutive releases.
+    pub fn intervals(&self) -> impl Iterator<Item = Duration> + '_ {
+        self.releases
+            .windows(2)
+            .map(|pair| pair[1].released_at - pair[0].released_at)
+    }
+
+    /// The mean time between consecutive releases.
+    pub fn mean_interval(&self) -> Option<Duration> {
+        let count = self
+            .releases
+            .len()
+            .checked_sub(1)
+            .filter(|&count| count > 0)?;
+        let total = self
+            .intervals()
+            .fold(Duration::zero(), |total, interval| total + interval);
+        Some(total / count as i32)
+    }
+}
+
+/// Find the pipeline of a release.
+///
+/// Releases which were stored before their pipeline was discovered fall back to a pipeline for
+/// the same commit in the project (preferring pipelines for the tag itself).
+fn release_pipeline<L>(
+    storage: &L,
+    release: &Release<L>,
+) -> Option<<L as Lookup<Pipeline<L>>>::Index>
+where
+    L: AnalysisLookup<L>,
+{
+    if let Some(idx) = release.pipeline.as_ref() {
+        if <L as Lookup<Pipeline<L>>>::lookup(storage, idx).is_some() {
+            return Some(idx.clone());
+        }
+    }
+
+    let project = <L as Lookup<Project<L>>>::lookup(storage, &release.project)?.forge_id;
+    let pipelines = <L as DiscoverableLookup<Pipeline<L>>>::all_indices(storage);
+    pipelines
+        .iter()
+        .filter_map(|idx| {
+            <L as Lookup<Pipeline<L>>>::lookup(storage, idx).map(|pipeline| (idx, pipeline))
+        })
+        .filter(|(_, pipeline)| {
+            pipeline.sha == release.sha
+                && pipeline.parent_pipeline.is_none()
+                && <L as Lookup<Project<L>>>::lookup(storage, &pipeline.project)
+                    .is_some_and(|pipeline_project| pipeline_project.forge_id == project)
+        })
+        .max_by_key(|(_, pipeline)| {
+            (
+                pipeline.refname.as_deref() == Some(release.tag.as_str()),
+                pipeline.forge_id,
+            )
+        })
+        .map(|(idx, _)| idx.clone())
+}
+
+/// Summarize the releases of each project.
+///
+/// Results are sorted by project ID and only projects with releases are reported. Deleted
+/// releases are ignored.
+pub fn release_history<L>(storage: &L) -> Vec<ProjectReleases>
+where
+    L: AnalysisLookup<L>,
+{
+    let mut projects: BTreeMap<u64, ProjectReleases> = BTreeMap::new();
+
+    let releases = <L as DiscoverableLookup<Release<L>>>::all_indices(storage);
+    for idx in &releases {
+        let release = if let Some(release) = <L as Lookup<Release<L>>>::lookup(storage, idx) {
+            release
+        } else {
+            continue;
+        };
+        if release.cim_deleted_at.is_some() {
+            continue;
+        }
+        let project =
+            if let Some(project) = <L as Lookup<Project<L>>>::lookup(storage, &release.project) {
+                project
+            } else {
+                continue;
+            };
+
+        let pipeline_idx = release_pipeline(storage, release);
+        let pipeline = pipeline_idx
+            .as_ref()
+            .and_then(|idx| <L as Lookup<Pipeline<L>>>::lookup(storage, idx));
+        let pipeline_state = match pipeline {
+            None => ReleasePipelineState::Unknown,
+            Some(pipeline) => {
+                let finished = pipeline.status.is_finished()
+                    && pipeline
+                        .finished_at
+                        .is_some_and(|finished_at| finished_at <= release.released_at);
+                if finished {
+                    ReleasePipelineState::Finished {
+                        status: pipeline.status,
+                    }
+                } else {
+                    ReleasePipelineState::Unfinished
+                }
+            },
+        };
+
+        projects
+            .entry(project.forge_id)
+            .or_insert_with(|| {
+                ProjectReleases {
+                    project: project.forge_id,
+                    path: project.instance_path.clone(),
+                    releases: Vec::new(),
+                }
+            })
+            .releases
+            .push(ReleaseSummary {
+                tag: release.tag.clone(),
+                released_at: release.released_at,
+                pipeline: pipeline.map(|pipeline| pipeline.forge_id),
+                pipeline_state,
+            });
+    }
+
+    projects
+        .into_values()
+        .map(|mut project| {
+            project.releases.sort_by_key(|release| release.released_at);
+            project
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use ci_monitor_core::data::{Pipeline, PipelineSource, PipelineStatus, Release};
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test::{self, at};
+    use crate::ReleasePipelineState;
+
+    #[test]
+    fn release_history() {
+        let mut lookup = VecLookup::default();
+        let instance = test::instance(&mut lookup);
+        let project = test::project(&mut lookup, instance, 1, "group/app");
+
+        let pipeline = |id, sha: &str, refname: &str, status, finished_at| {
+            let mut pipeline = Pipeline::builder()
+                .project(project)
+                .sha(sha)
+                .refname(Some(refname.into()))
+                .source(PipelineSource::Push)
+                .status(status)
+                .forge_id(id)
+                .url("url")
+                .created_at(at(0))
+                .updated_at(at(0))
+                .build()
+                .unwrap();
+            pipeline.finished_at = finished_at;
+            pipeline
+        };
+        // Finished before the release.
+        let finished = lookup.store(pipeline(
+            10,
+            "a",
+            "v1.0",
+            PipelineStatus::Failed,
+            Some(at(5)),
+        ));
+        // Finished after the release.
+        lookup.store(pipeline(
+            11,
+            "b",
+            "v1.1",
+            PipelineStatus::Success,
+            Some(at(65)),
+        ));
+        // A branch pipeline for the same commit is not preferred.
+        lookup.store(pipeline(
+            12,
+            "b",
+            "main",
+            PipelineStatus::Success,
+            Some(at(30)),
+        ));
+
+        let release = |unique_id, tag: &str, sha: &str, released_at| {
+            Release::builder()
+                .project(project)
+                .tag(tag)
+                .sha(sha)
+                .unique_id(unique_id)
+                .created_at(released_at)
+                .released_at(released_at)
+                .build()
+                .unwrap()
+        };
+        let mut with_pipeline = release(0, "v1.0", "a", at(10));
+        with_pipeline.pipeline = Some(finished);
+        lookup.store(with_pipeline);
+        lookup.store(release(2, "v1.2", "c", at(250)));
+        lookup.store(release(1, "v1.1", "b", at(60)));
+
+        let history = super::release_history(&lookup);
+
+        assert_eq!(history.len(), 1);
+        let project = &history[0];
+        assert_eq!(project.project, 1);
+        assert_eq!(project.path, "group/app");
+        assert_eq!(project.releases.len(), 3);
+        assert_eq!(project.releases[0].tag, "v1.0");
+        assert_eq!(project.releases[0].pipeline, Some(10));
+        assert_eq!(
+            project.releases[0].pipeline_state,
+            ReleasePipelineState::Finished {
+                status: PipelineStatus::Failed,
+            },
+        );
+        assert_eq!(project.releases[1].tag, "v1.1");
+        assert_eq!(project.releases[1].pipeline, Some(11));
+        assert_eq!(
+            project.releases[1].pipeline_state,
+            ReleasePipelineState::Unfinished,
+        );
+        assert_eq!(project.releases[2].pipeline, None);
+        assert_eq!(
+            project.releases[2].pipeline_state,
+            ReleasePipelineState::Unknown,
+        );
+
+        assert_eq!(
+            project.intervals().collect::<Vec<_>>(),
+            [Duration::minutes(50), Duration::minutes(190)],
+        );
+        assert_eq!(project.mean_interval(), Some(Duration::minutes(120)));
+    }
+
+    #[test]
+    fn release_history_empty() {
+        let lookup = VecLookup::default();
+
+        assert!(super::release_history(&lookup).is_empty());
+    }
+}
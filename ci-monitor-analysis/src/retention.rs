@@ -0,0 +1,405 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, Utc};
+use ci_monitor_core::data::{
+    ArtifactExpiration, ArtifactKind, ArtifactState, Job, JobArtifact, Pipeline, Project,
+};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::AnalysisLookup;
+
+/// The number of bytes in a GiB.
+const GIB: f64 = 1024. * 1024. * 1024.;
+
+/// The period used for monthly costs and forecasts.
+fn month() -> Duration {
+    Duration::days(30)
+}
+
+/// Options for estimating artifact retention costs.
+#[derive(Debug, Clone, Copy, Default)]
+#[non_exhaustive]
+pub struct ArtifactRetentionOptions {
+    /// The price of storing a GiB for a month.
+    pub price_per_gib_month: f64,
+    /// A maximum artifact age to evaluate as an expiry policy.
+    pub max_age: Option<Duration>,
+}
+
+impl ArtifactRetentionOptions {
+    /// Set the price of storing a GiB for a month.
+    pub fn price_per_gib_month(mut self, price: f64) -> Self {
+        self.price_per_gib_month = price.max(0.);
+        self
+    }
+
+    /// Set a maximum artifact age to evaluate as an expiry policy.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+}
+
+/// Artifact storage used by a set of jobs.
+///
+/// Forge storage covers artifacts which have not expired from the forge. Files extracted from
+/// archives only occupy local storage. All sizes are in bytes.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct ArtifactStorage {
+    /// The number of artifacts.
+    pub artifacts: usize,
+    /// The size of artifacts retained by the forge.
+    pub retained: u64,
+    /// The size of retained artifacts which never expire.
+    pub never_expires: u64,
+    /// The size of artifacts held in the local blob store.
+    pub stored: u64,
+    /// The size of retained artifacts produced during the last month.
+    pub produced_last_month: u64,
+    /// The size of artifacts added to the local blob store during the last month.
+    pub stored_last_month: u64,
+    /// The size of retained artifacts which expire during the next month.
+    pub expiring_next_month: u64,
+    /// The size of retained artifacts older than the evaluated maximum age.
+    pub reclaimable: u64,
+    /// The estimated cost of storage for a month.
+    pub monthly_cost: f64,
+    /// The estimated monthly cost of storage a month from now.
+    pub forecast_cost: f64,
+    /// The estimated monthly savings of expiring artifacts at the evaluated maximum age.
+    pub reclaimable_cost: f64,
+}
+
+impl ArtifactStorage {
+    /// The total size of forge and local storage.
+    pub fn total(&self) -> u64 {
+        self.retained + self.stored
+    }
+
+    /// The forecast total size of forge and local storage a month from now.
+    ///
+    /// Assumes that artifacts continue to be produced at the rate of the last month.
+    pub fn forecast(&self) -> u64 {
+        (self.retained + self.produced_last_month).saturating_sub(self.expiring_next_month)
+            + self.stored
+            + self.stored_last_month
+    }
+
+    fn add(&mut self, other: &Self) {
+        self.artifacts += other.artifacts;
+        self.retained += other.retained;
+        self.never_expires += other.never_expires;
+        self.stored += other.stored;
+        self.produced_last_month += other.produced_last_month;
+        self.stored_last_month += other.stored_last_month;
+        self.expiring_next_month += other.expiring_next_month;
+        self.reclaimable += other.reclaimable;
+    }
+
+    fn price(&mut self, price_per_gib_month: f64) {
+        let cost = |bytes: u64| bytes as f64 / GIB * price_per_gib_month;
+        self.monthly_cost = cost(self.total());
+        self.forecast_cost = cost(self.forecast());
+        self.reclaimable_cost = cost(self.reclaimable);
+    }
+}
+
+/// Artifact storage used by a project.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct ProjectArtifactStorage {
+    /// The path of the project.
+    pub path: String,
+    /// The storage used by the project's artifacts.
+    pub storage: ArtifactStorage,
+}
+
+/// Estimated artifact retention costs.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct ArtifactRetention {
+    /// The storage used by all artifacts.
+    pub total: ArtifactStorage,
+    /// The storage used by each project, largest first.
+    pub projects: Vec<ProjectArtifactStorage>,
+}
+
+/// Estimate the storage cost of job artifacts.
+///
+/// Artifacts count against forge storage until they expire and against local storage once stored
+/// in the blob store. Artifacts are dated by when their job finished. Job logs are not subject to
+/// artifact expiry and are excluded.
+pub fn artifact_retention<L>(
+    storage: &L,
+    now: DateTime<Utc>,
+    options: ArtifactRetentionOptions,
+) -> ArtifactRetention
+where
+    L: AnalysisLookup<L>,
+{
+    let month_ago = now - month();
+    let next_month = now + month();
+
+    let mut projects: BTreeMap<String, ArtifactStorage> = BTreeMap::new();
+    for idx in <L as DiscoverableLookup<JobArtifact<L>>>::all_indices(storage) {
+        let artifact = if let Some(artifact) = <L as Lookup<JobArtifact<L>>>::lookup(storage, &idx)
+        {
+            artifact
+        } else {
+            continue;
+        };
+        if artifact.kind == ArtifactKind::JobLog {
+            continue;
+        }
+        let job = if let Some(job) = <L as Lookup<Job<L>>>::lookup(storage, &artifact.job) {
+            job
+        } else {
+            continue;
+        };
+        if job.cim_deleted_at.is_some() {
+            continue;
+        }
+        let project = <L as Lookup<Pipeline<L>>>::lookup(storage, &job.pipeline)
+            .and_then(|pipeline| <L as Lookup<Project<L>>>::lookup(storage, &pipeline.project));
+        let project = if let Some(project) = project {
+            project
+        } else {
+            continue;
+        };
+
+        let produced_at = job.finished_at.unwrap_or(job.created_at);
+        let recent = month_ago < produced_at;
+        let entry = projects.entry(project.instance_path.clone()).or_default();
+        entry.artifacts += 1;
+
+        if artifact.blob.is_some() {
+            entry.stored += artifact.size;
+            if recent {
+                entry.stored_last_month += artifact.size;
+            }
+        }
+
+        // Files extracted from archives only exist locally.
+        let on_forge = artifact.state != ArtifactState::Expired
+            && !matches!(artifact.kind, ArtifactKind::ArchiveFile { .. });
+        let expires_at = match artifact.expire_at {
+            ArtifactExpiration::At(expires_at) => Some(expires_at),
+            _ => None,
+        };
+        if !on_forge || expires_at.is_some_and(|expires_at| expires_at <= now) {
+            continue;
+        }
+
+        entry.retained += artifact.size;
+        if artifact.expire_at == ArtifactExpiration::Never {
+            entry.never_expires += artifact.size;
+        }
+        if recent {
+            entry.produced_last_month += artifact.size;
+        }
+        if expires_at.is_some_and(|expires_at| expires_at <= next_month) {
+            entry.expiring_next_month += artifact.size;
+        }
+        if options
+            .max_age
+            .is_some_and(|max_age| produced_at + max_age <= now)
+        {
+            entry.reclaimable += artifact.size;
+        }
+    }
+
+    let mut total = ArtifactStorage::default();
+    let mut projects = projects
+        .into_iter()
+        .map(|(path, mut storage)| {
+            storage.price(options.price_per_gib_month);
+            total.add(&storage);
+            ProjectArtifactStorage {
+                path,
+                storage,
+            }
+        })
+        .collect::<Vec<_>>();
+    total.price(options.price_per_gib_month);
+    projects.sort_by(|lhs, rhs| {
+        rhs.storage
+            .total()
+            .cmp(&lhs.storage.total())
+            .then_with(|| lhs.path.cmp(&rhs.path))
+    });
+
+    ArtifactRetention {
+        total,
+        projects,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use ci_monitor_core::data::{
+        ArtifactExpiration, ArtifactKind, ArtifactState, BlobReference, ContentHash, Job,
+        JobArtifact, JobState, PipelineStatus,
+    };
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test::{self, at_day};
+    use crate::ArtifactRetentionOptions;
+
+    const GIB: u64 = 1024 * 1024 * 1024;
+
+    #[test]
+    fn artifact_retention_empty() {
+        let lookup = VecLookup::default();
+        let retention =
+            super::artifact_retention(&lookup, at_day(0), ArtifactRetentionOptions::default());
+
+        assert_eq!(retention.total.artifacts, 0);
+        assert_eq!(retention.total.forecast(), 0);
+        assert!(retention.projects.is_empty());
+    }
+
+    #[test]
+    fn artifact_retention() {
+        let mut lookup = VecLookup::default();
+        let instance = test::instance(&mut lookup);
+        let user = test::user(&mut lookup, instance, 0);
+        let mut pipelines = Vec::new();
+        for (id, path) in [(1, "group/small"), (2, "group/large")] {
+            let project = test::project(&mut lookup, instance, id, path);
+            let pipeline = test::pipeline(project, id, PipelineStatus::Success, at_day(0));
+            pipelines.push(lookup.store(pipeline));
+        }
+
+        let now = at_day(100);
+        let blob = BlobReference::new(ContentHash::Sha256, "hash".into());
+        // (pipeline, finished day, kind, state, expiration, stored, GiB)
+        let artifacts = [
+            // Recent and expiring soon.
+            (
+                0,
+                95,
+                ArtifactKind::Archive,
+                ArtifactState::Present,
+                ArtifactExpiration::At(at_day(110)),
+                false,
+                1,
+            ),
+            // Old and never expiring.
+            (
+                1,
+                10,
+                ArtifactKind::Archive,
+                ArtifactState::Stored,
+                ArtifactExpiration::Never,
+                true,
+                4,
+            ),
+            // Expired from the forge, but stored locally.
+            (
+                1,
+                20,
+                ArtifactKind::JUnit,
+                ArtifactState::Stored,
+                ArtifactExpiration::At(at_day(50)),
+                true,
+                2,
+            ),
+            // Extracted files are only stored locally.
+            (
+                1,
+                90,
+                ArtifactKind::ArchiveFile {
+                    path: "file".into(),
+                },
+                ArtifactState::Stored,
+                ArtifactExpiration::Unknown,
+                true,
+                1,
+            ),
+            // Logs are ignored.
+            (
+                1,
+                90,
+                ArtifactKind::JobLog,
+                ArtifactState::Stored,
+                ArtifactExpiration::Never,
+                true,
+                8,
+            ),
+        ];
+        for (id, (pipeline, finished, kind, state, expire_at, stored, size)) in
+            artifacts.into_iter().enumerate()
+        {
+            let job = Job::builder()
+                .user(user)
+                .state(JobState::Success)
+                .created_at(at_day(finished))
+                .finished_at(Some(at_day(finished)))
+                .forge_id(id as u64)
+                .pipeline(pipelines[pipeline])
+                .build()
+                .unwrap();
+            let job = lookup.store(job);
+            let artifact = JobArtifact::builder()
+                .state(state)
+                .kind(kind)
+                .expire_at(expire_at)
+                .name("artifact")
+                .blob(stored.then(|| blob.clone()))
+                .size(size * GIB)
+                .unique_id(id as u64)
+                .job(job)
+                .build()
+                .unwrap();
+            lookup.store(artifact);
+        }
+
+        let options = ArtifactRetentionOptions::default()
+            .price_per_gib_month(0.5)
+            .max_age(Duration::days(30));
+        let retention = super::artifact_retention(&lookup, now, options);
+
+        let paths = retention
+            .projects
+            .iter()
+            .map(|project| project.path.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(paths, ["group/large", "group/small"]);
+
+        let large = &retention.projects[0].storage;
+        assert_eq!(large.artifacts, 3);
+        assert_eq!(large.retained, 4 * GIB);
+        assert_eq!(large.never_expires, 4 * GIB);
+        assert_eq!(large.stored, 7 * GIB);
+        assert_eq!(large.stored_last_month, GIB);
+        assert_eq!(large.produced_last_month, 0);
+        assert_eq!(large.reclaimable, 4 * GIB);
+        assert_eq!(large.forecast(), 12 * GIB);
+        assert_eq!(large.monthly_cost, 5.5);
+        assert_eq!(large.reclaimable_cost, 2.);
+
+        let small = &retention.projects[1].storage;
+        assert_eq!(small.retained, GIB);
+        assert_eq!(small.produced_last_month, GIB);
+        assert_eq!(small.expiring_next_month, GIB);
+        assert_eq!(small.reclaimable, 0);
+        assert_eq!(small.forecast(), GIB);
+
+        assert_eq!(retention.total.artifacts, 4);
+        assert_eq!(retention.total.total(), 12 * GIB);
+        assert_eq!(retention.total.monthly_cost, 6.);
+        assert_eq!(retention.total.forecast_cost, 6.5);
+    }
+}
@@ -0,0 +1,743 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, FixedOffset, NaiveTime, TimeZone, Utc};
+use ci_monitor_core::data::{Job, JobState, Pipeline, PipelineStatus, Project};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+use glob::Pattern;
+use schemars::JsonSchema;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{AnalysisLookup, Quarantine};
+
+/// Errors which may occur when declaring notification routing.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum RoutingError {
+    /// A pattern is invalid.
+    #[error("invalid pattern '{}': {}", pattern, source)]
+    InvalidPattern {
+        /// The pattern.
+        pattern: String,
+        /// The source of the error.
+        #[source]
+        source: glob::PatternError,
+    },
+    /// Quiet hours are invalid.
+    #[error("invalid quiet hours '{}': expected `HH:MM-HH:MM`", value)]
+    InvalidQuietHours {
+        /// The quiet hours.
+        value: String,
+    },
+    /// A UTC offset is invalid.
+    #[error("invalid UTC offset '{}': expected `+HH:MM` or `-HH:MM`", value)]
+    InvalidUtcOffset {
+        /// The offset.
+        value: String,
+    },
+    /// A line in an owners file is invalid.
+    #[error("invalid owners entry on line {}: {}", line, reason)]
+    InvalidOwnersLine {
+        /// The line number (starting at 1).
+        line: usize,
+        /// Why the line is invalid.
+        reason: &'static str,
+    },
+}
+
+impl RoutingError {
+    fn invalid_pattern(pattern: String, source: glob::PatternError) -> Self {
+        Self::InvalidPattern {
+            pattern,
+            source,
+        }
+    }
+
+    fn invalid_quiet_hours(value: String) -> Self {
+        Self::InvalidQuietHours {
+            value,
+        }
+    }
+
+    fn invalid_utc_offset(value: String) -> Self {
+        Self::InvalidUtcOffset {
+            value,
+        }
+    }
+
+    fn invalid_owners_line(line: usize, reason: &'static str) -> Self {
+        Self::InvalidOwnersLine {
+            line,
+            reason,
+        }
+    }
+}
+
+fn pattern(pattern: &str) -> Result<Pattern, RoutingError> {
+    Pattern::new(pattern).map_err(|err| RoutingError::invalid_pattern(pattern.into(), err))
+}
+
+/// Parse a UTC offset such as `+02:00`.
+pub fn parse_utc_offset(value: &str) -> Result<FixedOffset, RoutingError> {
+    value
+        .parse()
+        .map_err(|_| RoutingError::invalid_utc_offset(value.into()))
+}
+
+/// A daily period during which an owner should not be notified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuietHours {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl QuietHours {
+    /// Quiet hours between two local times.
+    ///
+    /// If `end` is before `start`, the period spans midnight.
+    pub fn new(start: NaiveTime, end: NaiveTime) -> Self {
+        Self {
+            start,
+            end,
+        }
+    }
+
+    /// Parse quiet hours such as `22:00-07:00`.
+    pub fn parse(value: &str) -> Result<Self, RoutingError> {
+        let err = || RoutingError::invalid_quiet_hours(value.into());
+        let (start, end) = value.split_once('-').ok_or_else(err)?;
+        let time = |time: &str| NaiveTime::parse_from_str(time.trim(), "%H:%M").map_err(|_| err());
+
+        Ok(Self::new(time(start)?, time(end)?))
+    }
+
+    fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            self.start <= time || time < self.end
+        }
+    }
+
+    /// When the quiet hours containing a local time end.
+    fn end_after<Tz>(&self, local: DateTime<Tz>) -> DateTime<Utc>
+    where
+        Tz: TimeZone,
+    {
+        let mut end = local.date_naive().and_time(self.end);
+        if end <= local.naive_local() {
+            end += Duration::days(1);
+        }
+        local
+            .timezone()
+            .from_local_datetime(&end)
+            .earliest()
+            .map_or_else(|| local.with_timezone(&Utc), |end| end.with_timezone(&Utc))
+    }
+}
+
+/// A recipient of notifications.
+#[derive(Debug, Clone)]
+pub struct Owner {
+    name: String,
+    channel: String,
+    offset: FixedOffset,
+    quiet_hours: Option<QuietHours>,
+    away: Vec<(DateTime<Utc>, DateTime<Utc>)>,
+}
+
+impl Owner {
+    /// An owner notified through a channel.
+    ///
+    /// By default, owners are in UTC without quiet hours.
+    pub fn new<N, C>(name: N, channel: C) -> Self
+    where
+        N: Into<String>,
+        C: Into<String>,
+    {
+        Self {
+            name: name.into(),
+            channel: channel.into(),
+            offset: FixedOffset::east_opt(0).expect("zero is a valid offset"),
+            quiet_hours: None,
+            away: Vec::new(),
+        }
+    }
+
+    /// The offset of the owner's local time from UTC.
+    pub fn utc_offset(mut self, offset: FixedOffset) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Local times during which the owner should not be notified.
+    pub fn quiet_hours(mut self, quiet_hours: QuietHours) -> Self {
+        self.quiet_hours = Some(quiet_hours);
+        self
+    }
+
+    /// A period during which the owner is out of office.
+    pub fn away(mut self, start: DateTime<Utc>, end: DateTime<Utc>) -> Self {
+        self.away.push((start, end));
+        self
+    }
+
+    /// The name of the owner.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn is_away(&self, at: DateTime<Utc>) -> bool {
+        self.away
+            .iter()
+            .any(|&(start, end)| start <= at && at < end)
+    }
+
+    /// When a notification at the given time may be delivered.
+    fn available_at(&self, at: DateTime<Utc>) -> DateTime<Utc> {
+        let local = at.with_timezone(&self.offset);
+        match self.quiet_hours {
+            Some(quiet) if quiet.contains(local.time()) => quiet.end_after(local),
+            _ => at,
+        }
+    }
+}
+
+/// A rule assigning owners to projects and jobs.
+#[derive(Debug, Clone)]
+pub struct OwnershipRule {
+    projects: Pattern,
+    jobs: Option<Pattern>,
+    owners: Vec<String>,
+}
+
+impl OwnershipRule {
+    /// Assign owners to projects whose path matches a glob pattern.
+    pub fn new<I, O>(projects: &str, owners: I) -> Result<Self, RoutingError>
+    where
+        I: IntoIterator<Item = O>,
+        O: Into<String>,
+    {
+        Ok(Self {
+            projects: pattern(projects)?,
+            jobs: None,
+            owners: owners.into_iter().map(Into::into).collect(),
+        })
+    }
+
+    /// Only apply to jobs whose name matches a glob pattern.
+    pub fn jobs(mut self, jobs: &str) -> Result<Self, RoutingError> {
+        self.jobs = Some(pattern(jobs)?);
+        Ok(self)
+    }
+
+    fn applies_to(&self, project: &str, job: Option<&str>) -> bool {
+        self.projects.matches(project)
+            && self
+                .jobs
+                .as_ref()
+                .is_none_or(|jobs| job.is_some_and(|job| jobs.matches(job)))
+    }
+}
+
+/// Parse ownership rules from a `CODEOWNERS`-like file.
+///
+/// Each line contains a project pattern, optionally followed by `:` and a job pattern, and then
+/// the owners separated by whitespace. A leading `@` on owner names is ignored. Blank lines and
+/// lines starting with `#` are skipped.
+pub fn parse_owners(contents: &str) -> Result<Vec<OwnershipRule>, RoutingError> {
+    let mut rules = Vec::new();
+
+    for (idx, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut words = line.split_whitespace();
+        let target = words.next().unwrap_or_default();
+        let owners = words
+            .map(|owner| owner.trim_start_matches('@'))
+            .collect::<Vec<_>>();
+        if owners.is_empty() {
+            return Err(RoutingError::invalid_owners_line(idx + 1, "no owners"));
+        }
+
+        let rule = if let Some((projects, jobs)) = target.split_once(':') {
+            OwnershipRule::new(projects, owners)?.jobs(jobs)?
+        } else {
+            OwnershipRule::new(target, owners)?
+        };
+        rules.push(rule);
+    }
+
+    Ok(rules)
+}
+
+/// How a notification is routed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RouteKind {
+    /// Delivered to an owner right away.
+    Immediate,
+    /// Delivered to an owner once their quiet hours end.
+    Deferred,
+    /// Delivered to the fallback channel because no owner is available.
+    Escalated,
+}
+
+impl RouteKind {
+    /// The name of the kind of route.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Immediate => "immediate",
+            Self::Deferred => "deferred",
+            Self::Escalated => "escalated",
+        }
+    }
+}
+
+/// Where and when to deliver a notification.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct Route {
+    /// The owner to notify (`None` for the fallback channel).
+    pub owner: Option<String>,
+    /// The channel to deliver to.
+    pub channel: String,
+    /// When to deliver the notification.
+    pub deliver_at: DateTime<Utc>,
+    /// How the notification is routed.
+    ///
+    /// One of `immediate`, `deferred` (until quiet hours end), or `escalated`.
+    #[serde(serialize_with = "crate::serialize::name")]
+    #[schemars(with = "String")]
+    pub kind: RouteKind,
+}
+
+/// Routing of notifications to owners.
+#[derive(Debug, Clone)]
+pub struct NotificationRouter {
+    rules: Vec<OwnershipRule>,
+    owners: BTreeMap<String, Owner>,
+    fallback: String,
+    pub(crate) quarantine: Quarantine,
+}
+
+impl NotificationRouter {
+    /// A router which escalates to a fallback channel.
+    pub fn new<F>(fallback: F) -> Self
+    where
+        F: Into<String>,
+    {
+        Self {
+            rules: Vec::new(),
+            owners: BTreeMap::new(),
+            fallback: fallback.into(),
+            quarantine: Quarantine::default(),
+        }
+    }
+
+    /// Add ownership rules.
+    ///
+    /// As with `CODEOWNERS`, the last matching rule determines the owners.
+    pub fn rules<I>(mut self, rules: I) -> Self
+    where
+        I: IntoIterator<Item = OwnershipRule>,
+    {
+        self.rules.extend(rules);
+        self
+    }
+
+    /// Describe an owner.
+    ///
+    /// Owners named by rules without a description are notified through a channel of the same
+    /// name at any time.
+    pub fn owner(mut self, owner: Owner) -> Self {
+        self.owners.insert(owner.name.clone(), owner);
+        self
+    }
+
+    /// Suppress failures of quarantined jobs.
+    pub fn quarantine(mut self, quarantine: Quarantine) -> Self {
+        self.quarantine = quarantine;
+        self
+    }
+
+    fn owners_of(&self, project: &str, job: Option<&str>) -> &[String] {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.applies_to(project, job))
+            .map_or(&[], |rule| rule.owners.as_slice())
+    }
+
+    /// Route a notification about jobs within a project.
+    ///
+    /// The owners of each job (or of the project if no jobs are given) are notified unless they
+    /// are away. Owners in their quiet hours are notified once their quiet hours end. If no owner
+    /// can be notified right away, the notification is also escalated to the fallback channel.
+    pub fn route(&self, project: &str, jobs: &[&str], at: DateTime<Utc>) -> Vec<Route> {
+        let mut names: Vec<&str> = Vec::new();
+        if jobs.is_empty() {
+            names.extend(self.owners_of(project, None).iter().map(String::as_str));
+        }
+        for job in jobs {
+            names.extend(
+                self.owners_of(project, Some(job))
+                    .iter()
+                    .map(String::as_str),
+            );
+        }
+        names.sort_unstable();
+        names.dedup();
+
+        let mut routes = Vec::new();
+        for name in names {
+            let (channel, deliver_at) = if let Some(owner) = self.owners.get(name) {
+                if owner.is_away(at) {
+                    continue;
+                }
+                (owner.channel.clone(), owner.available_at(at))
+            } else {
+                (name.into(), at)
+            };
+            let kind = if deliver_at > at {
+                RouteKind::Deferred
+            } else {
+                RouteKind::Immediate
+            };
+
+            routes.push(Route {
+                owner: Some(name.into()),
+                channel,
+                deliver_at,
+                kind,
+            });
+        }
+
+        if !routes
+            .iter()
+            .any(|route| route.kind == RouteKind::Immediate)
+        {
+            routes.push(Route {
+                owner: None,
+                channel: self.fallback.clone(),
+                deliver_at: at,
+                kind: RouteKind::Escalated,
+            });
+        }
+
+        routes
+    }
+}
+
+/// A notification about a failed pipeline.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct FailureNotification {
+    /// The ID of the pipeline.
+    pub pipeline: u64,
+    /// The URL of the pipeline.
+    pub url: String,
+    /// The path of the project on its instance.
+    pub project: String,
+    /// The ref of the pipeline.
+    pub refname: Option<String>,
+    /// When the pipeline failed.
+    pub failed_at: DateTime<Utc>,
+    /// The names of the failed jobs.
+    pub jobs: Vec<String>,
+    /// Where to deliver the notification.
+    pub routes: Vec<Route>,
+}
+
+/// Route notifications for pipelines which failed since a given time.
+///
+/// Jobs of child pipelines count towards their parent pipeline. When a job has been retried, only
+/// the latest attempt is considered and jobs which are allowed to fail are ignored. Pipelines
+/// which only failed because of quarantined jobs are not reported. Results are sorted by pipeline
+/// ID.
+pub fn failure_notifications<L>(
+    storage: &L,
+    router: &NotificationRouter,
+    since: DateTime<Utc>,
+) -> Vec<FailureNotification>
+where
+    L: AnalysisLookup<L>,
+{
+    // Find the latest attempt of each job by name for each pipeline.
+    let mut pipeline_jobs: BTreeMap<u64, BTreeMap<&str, &Job<L>>> = BTreeMap::new();
+    let jobs = <L as DiscoverableLookup<Job<L>>>::all_indices(storage);
+    for idx in &jobs {
+        let job = if let Some(job) = <L as Lookup<Job<L>>>::lookup(storage, idx) {
+            job
+        } else {
+            continue;
+        };
+        let mut pipeline = <L as Lookup<Pipeline<L>>>::lookup(storage, &job.pipeline);
+        while let Some(parent) = pipeline
+            .and_then(|pipeline| pipeline.parent_pipeline.as_ref())
+            .and_then(|idx| <L as Lookup<Pipeline<L>>>::lookup(storage, idx))
+        {
+            pipeline = Some(parent);
+        }
+        let pipeline = if let Some(pipeline) = pipeline {
+            pipeline
+        } else {
+            continue;
+        };
+
+        let latest = pipeline_jobs
+            .entry(pipeline.forge_id)
+            .or_default()
+            .entry(job.name.as_str())
+            .or_insert(job);
+        if latest.forge_id < job.forge_id {
+            *latest = job;
+        }
+    }
+
+    let mut notifications = Vec::new();
+    let pipelines = <L as DiscoverableLookup<Pipeline<L>>>::all_indices(storage);
+    for idx in &pipelines {
+        let pipeline = if let Some(pipeline) = <L as Lookup<Pipeline<L>>>::lookup(storage, idx) {
+            pipeline
+        } else {
+            continue;
+        };
+        if pipeline.status != PipelineStatus::Failed
+            || pipeline.parent_pipeline.is_some()
+            || pipeline.cim_deleted_at.is_some()
+        {
+            continue;
+        }
+        let failed_at = pipeline.finished_at.unwrap_or(pipeline.updated_at);
+        if failed_at < since {
+            continue;
+        }
+        let project =
+            if let Some(project) = <L as Lookup<Project<L>>>::lookup(storage, &pipeline.project) {
+                project
+            } else {
+                continue;
+            };
+
+        let (suppressed, failed_jobs): (Vec<_>, Vec<_>) = pipeline_jobs
+            .get(&pipeline.forge_id)
+            .into_iter()
+            .flat_map(|jobs| jobs.iter())
+            .filter(|(_, job)| job.state == JobState::Failed && !job.allow_failure)
+            .partition(|(_, job)| router.quarantine.suppresses(project, job));
+        if failed_jobs.is_empty() && !suppressed.is_empty() {
+            continue;
+        }
+        let failed_jobs = failed_jobs
+            .into_iter()
+            .map(|(&name, _)| name)
+            .collect::<Vec<_>>();
+        let routes = router.route(&project.instance_path, &failed_jobs, failed_at);
+
+        notifications.push(FailureNotification {
+            pipeline: pipeline.forge_id,
+            url: pipeline.url.clone(),
+            project: project.instance_path.clone(),
+            refname: pipeline.refname.clone(),
+            failed_at,
+            jobs: failed_jobs.into_iter().map(Into::into).collect(),
+            routes,
+        });
+    }
+
+    notifications.sort_by_key(|notification| notification.pipeline);
+    notifications
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveTime;
+    use ci_monitor_core::data::{Job, JobState, Pipeline, PipelineSource, PipelineStatus};
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test::{self, at_hour};
+    use crate::{
+        NotificationRouter, Owner, OwnershipRule, Quarantine, QuarantinedJob, QuietHours, RouteKind,
+    };
+
+    #[test]
+    fn quiet_hours_parse() {
+        let quiet = QuietHours::parse("22:00-07:30").unwrap();
+        assert_eq!(
+            quiet,
+            QuietHours::new(
+                NaiveTime::from_hms_opt(22, 0, 0).unwrap(),
+                NaiveTime::from_hms_opt(7, 30, 0).unwrap(),
+            ),
+        );
+        assert!(quiet.contains(NaiveTime::from_hms_opt(23, 0, 0).unwrap()));
+        assert!(quiet.contains(NaiveTime::from_hms_opt(3, 0, 0).unwrap()));
+        assert!(!quiet.contains(NaiveTime::from_hms_opt(12, 0, 0).unwrap()));
+
+        let err = QuietHours::parse("22:00").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid quiet hours '22:00': expected `HH:MM-HH:MM`",
+        );
+    }
+
+    #[test]
+    fn parse_owners() {
+        let rules = super::parse_owners(
+            "# Ownership\n\
+             \n\
+             group/* @alice\n\
+             group/app:docs* bob carol\n",
+        )
+        .unwrap();
+
+        assert_eq!(rules.len(), 2);
+        assert!(rules[0].applies_to("group/app", None));
+        assert!(!rules[1].applies_to("group/app", None));
+        assert!(rules[1].applies_to("group/app", Some("docs:build")));
+        assert_eq!(rules[1].owners, ["bob", "carol"]);
+
+        let err = super::parse_owners("group/*\n").unwrap_err();
+        assert_eq!(err.to_string(), "invalid owners entry on line 1: no owners",);
+    }
+
+    #[test]
+    fn route() {
+        let rules = super::parse_owners("group/* alice\ngroup/app:docs* bob\n").unwrap();
+        let router = NotificationRouter::new("#ci")
+            .rules(rules)
+            .owner(
+                Owner::new("alice", "@alice")
+                    .utc_offset(super::parse_utc_offset("+02:00").unwrap())
+                    .quiet_hours(QuietHours::parse("22:00-08:00").unwrap()),
+            )
+            .owner(Owner::new("bob", "@bob").away(at_hour(0), at_hour(24)));
+
+        // Owners are notified during the day.
+        let routes = router.route("group/app", &["build"], at_hour(12));
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0].owner.as_deref(), Some("alice"));
+        assert_eq!(routes[0].channel, "@alice");
+        assert_eq!(routes[0].kind, RouteKind::Immediate);
+        assert_eq!(routes[0].deliver_at, at_hour(12));
+
+        // Notifications during quiet hours are deferred and escalated.
+        let routes = router.route("group/app", &["build"], at_hour(21));
+        assert_eq!(routes.len(), 2);
+        assert_eq!(routes[0].kind, RouteKind::Deferred);
+        // 08:00 local time is 06:00 UTC.
+        assert_eq!(routes[0].deliver_at, at_hour(30));
+        assert_eq!(routes[1].owner, None);
+        assert_eq!(routes[1].channel, "#ci");
+        assert_eq!(routes[1].kind, RouteKind::Escalated);
+
+        // Owners who are away are skipped.
+        let routes = router.route("group/app", &["docs"], at_hour(12));
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0].kind, RouteKind::Escalated);
+        let routes = router.route("group/app", &["docs"], at_hour(36));
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0].owner.as_deref(), Some("bob"));
+
+        // Unowned projects go to the fallback channel.
+        let routes = router.route("other/docs", &[], at_hour(12));
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0].kind, RouteKind::Escalated);
+    }
+
+    #[test]
+    fn failure_notifications() {
+        let mut lookup = VecLookup::default();
+        let instance = test::instance(&mut lookup);
+        let project = test::project(&mut lookup, instance, 1, "group/app");
+        let user = test::user(&mut lookup, instance, 0);
+
+        let pipeline = |id, status, hour| {
+            Pipeline::builder()
+                .project(project)
+                .sha("0000000000000000000000000000000000000000")
+                .refname(Some("main".into()))
+                .source(PipelineSource::Push)
+                .status(status)
+                .forge_id(id)
+                .url(format!("pipeline/{}", id))
+                .created_at(at_hour(0))
+                .updated_at(at_hour(hour))
+                .finished_at(Some(at_hour(hour)))
+                .build()
+                .unwrap()
+        };
+        let failed = lookup.store(pipeline(10, PipelineStatus::Failed, 12));
+        // Old failures are not reported.
+        lookup.store(pipeline(11, PipelineStatus::Failed, 1));
+        // Passing pipelines are not reported.
+        lookup.store(pipeline(12, PipelineStatus::Success, 12));
+
+        let job = |id, name: &str, state, allow_failure| {
+            Job::builder()
+                .user(user)
+                .name(name)
+                .state(state)
+                .allow_failure(allow_failure)
+                .created_at(at_hour(0))
+                .forge_id(id)
+                .pipeline(failed)
+                .build()
+                .unwrap()
+        };
+        lookup.store(job(100, "build", JobState::Failed, false));
+        lookup.store(job(101, "docs", JobState::Failed, false));
+        // Retried successfully.
+        lookup.store(job(102, "test", JobState::Failed, false));
+        lookup.store(job(103, "test", JobState::Success, false));
+        // Allowed to fail.
+        lookup.store(job(104, "lint", JobState::Failed, true));
+
+        let router = NotificationRouter::new("#ci").rules([
+            OwnershipRule::new("group/*", ["alice"]).unwrap(),
+            OwnershipRule::new("group/*", ["bob"])
+                .unwrap()
+                .jobs("docs")
+                .unwrap(),
+        ]);
+        let notifications = super::failure_notifications(&lookup, &router, at_hour(6));
+
+        assert_eq!(notifications.len(), 1);
+        let notification = &notifications[0];
+        assert_eq!(notification.pipeline, 10);
+        assert_eq!(notification.project, "group/app");
+        assert_eq!(notification.failed_at, at_hour(12));
+        assert_eq!(notification.jobs, ["build", "docs"]);
+        assert_eq!(notification.routes.len(), 2);
+        assert_eq!(notification.routes[0].owner.as_deref(), Some("alice"));
+        assert_eq!(notification.routes[1].owner.as_deref(), Some("bob"));
+
+        // Quarantined failures are suppressed.
+        let quarantine = |jobs: &[&str]| {
+            jobs.iter().fold(Quarantine::default(), |quarantine, job| {
+                quarantine
+                    .job(QuarantinedJob::new("group/*", job, at_hour(0), at_hour(24)).unwrap())
+            })
+        };
+        let router = router.quarantine(quarantine(&["docs"]));
+        let notifications = super::failure_notifications(&lookup, &router, at_hour(6));
+        assert_eq!(notifications.len(), 1);
+        assert_eq!(notifications[0].jobs, ["build"]);
+
+        let router = router.quarantine(quarantine(&["build", "docs"]));
+        let notifications = super::failure_notifications(&lookup, &router, at_hour(6));
+        assert!(notifications.is_empty());
+    }
+}
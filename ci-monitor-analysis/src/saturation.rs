@@ -0,0 +1,264 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, Utc};
+use ci_monitor_core::data::{Instance, Job, Runner, RunnerHost};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::{AnalysisLookup, ConcurrencyCurve};
+
+/// The saturation of a runner over a window of time.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct SaturationSample {
+    /// The start of the window.
+    pub start: DateTime<Utc>,
+    /// The end of the window.
+    pub end: DateTime<Utc>,
+    /// The highest number of concurrent jobs during the window.
+    pub peak_concurrency: usize,
+    /// The time-weighted mean number of concurrent jobs during the window.
+    pub mean_concurrency: f64,
+    /// The peak concurrency as a fraction of the runner's limit.
+    pub peak_saturation: f64,
+    /// The mean concurrency as a fraction of the runner's limit.
+    pub mean_saturation: f64,
+}
+
+/// The utilization of a runner relative to its concurrency limit.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct RunnerSaturation {
+    /// The ID of the runner on the forge.
+    pub runner: u64,
+    /// The description of the runner.
+    pub description: String,
+    /// The URL of the instance the runner belongs to.
+    pub instance: String,
+    /// The maximum number of jobs the runner may run at once.
+    pub max_concurrent_jobs: u64,
+    /// The number of jobs observed on the runner.
+    pub jobs: usize,
+    /// The highest number of concurrent jobs observed.
+    pub peak_concurrency: usize,
+    /// The peak concurrency as a fraction of the limit.
+    ///
+    /// Values above `1` indicate that the configured limit is out of date.
+    pub peak_saturation: f64,
+    /// The mean concurrency between the first and last job as a fraction of the limit.
+    pub mean_saturation: f64,
+    /// Saturation over consecutive windows of time.
+    pub samples: Vec<SaturationSample>,
+}
+
+/// The concurrency limit of a runner.
+///
+/// The runner's own limit takes precedence over that of its host.
+fn max_concurrent_jobs<L>(storage: &L, runner: &Runner<L>) -> Option<u64>
+where
+    L: AnalysisLookup<L>,
+{
+    runner.max_concurrent_jobs.or_else(|| {
+        runner
+            .runner_host
+            .as_ref()
+            .and_then(|idx| <L as Lookup<RunnerHost>>::lookup(storage, idx))
+            .and_then(|host| host.max_concurrent_jobs)
+    })
+}
+
+/// Compute the saturation of runners with a known concurrency limit.
+///
+/// Saturation is sampled over consecutive windows of the given length starting with the first
+/// observed job on each runner. Only jobs with both start and finish times are considered and
+/// runners without a limit (or with a limit of zero) are skipped. Results are sorted by runner
+/// ID.
+pub fn runner_saturation<L>(storage: &L, window: Duration) -> Vec<RunnerSaturation>
+where
+    L: AnalysisLookup<L>,
+{
+    let mut intervals: BTreeMap<u64, (&Runner<L>, Vec<_>)> = BTreeMap::new();
+
+    let jobs = <L as DiscoverableLookup<Job<L>>>::all_indices(storage);
+    for idx in &jobs {
+        let job = if let Some(job) = <L as Lookup<Job<L>>>::lookup(storage, idx) {
+            job
+        } else {
+            continue;
+        };
+        let runner = if let Some(runner) = job
+            .runner
+            .as_ref()
+            .and_then(|idx| <L as Lookup<Runner<L>>>::lookup(storage, idx))
+        {
+            runner
+        } else {
+            continue;
+        };
+        let interval = if let (Some(start), Some(end)) = (job.started_at, job.finished_at) {
+            (start, end)
+        } else {
+            continue;
+        };
+
+        intervals
+            .entry(runner.forge_id)
+            .or_insert_with(|| (runner, Vec::new()))
+            .1
+            .push(interval);
+    }
+
+    intervals
+        .into_values()
+        .filter_map(|(runner, intervals)| {
+            let limit = max_concurrent_jobs(storage, runner).filter(|&limit| limit > 0)?;
+            let jobs = intervals.len();
+            let curve = ConcurrencyCurve::from_intervals(intervals);
+            let (first, last) = curve.span()?;
+            let limit_f = limit as f64;
+
+            let mut samples = Vec::new();
+            let mut start = first;
+            while start < last {
+                let end = if window > Duration::zero() {
+                    (start + window).min(last)
+                } else {
+                    last
+                };
+                let peak_concurrency = curve.peak_between(start, end);
+                let mean_concurrency = curve.mean_between(start, end);
+                samples.push(SaturationSample {
+                    start,
+                    end,
+                    peak_concurrency,
+                    mean_concurrency,
+                    peak_saturation: peak_concurrency as f64 / limit_f,
+                    mean_saturation: mean_concurrency / limit_f,
+                });
+                start = end;
+            }
+
+            let peak_concurrency = curve.peak().map_or(0, |peak| peak.concurrency);
+            let instance = <L as Lookup<Instance>>::lookup(storage, &runner.instance)
+                .map(|instance| instance.url.clone())
+                .unwrap_or_default();
+
+            Some(RunnerSaturation {
+                runner: runner.forge_id,
+                description: runner.description.clone(),
+                instance,
+                max_concurrent_jobs: limit,
+                jobs,
+                peak_concurrency,
+                peak_saturation: peak_concurrency as f64 / limit_f,
+                mean_saturation: curve.mean_between(first, last) / limit_f,
+                samples,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use ci_monitor_core::data::{
+        Job, JobState, PipelineStatus, Runner, RunnerHost, RunnerProtectionLevel, RunnerType,
+    };
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test::{self, at};
+
+    #[test]
+    fn runner_saturation() {
+        let mut lookup = VecLookup::default();
+        let instance = test::instance(&mut lookup);
+        let project = test::project(&mut lookup, instance, 0, "project");
+        let user = test::user(&mut lookup, instance, 0);
+        let pipeline = test::pipeline(project, 0, PipelineStatus::Success, at(0));
+        let pipeline = lookup.store(pipeline);
+
+        let host = RunnerHost::builder()
+            .name("host")
+            .unique_id(0)
+            .max_concurrent_jobs(Some(4))
+            .build()
+            .unwrap();
+        let host = lookup.store(host);
+        let runner = |id: u64, limit: Option<u64>| {
+            Runner::builder()
+                .forge_id(id)
+                .instance(instance)
+                .runner_type(RunnerType::Instance)
+                .protection_level(RunnerProtectionLevel::Any)
+                .max_concurrent_jobs(limit)
+                .runner_host(Some(host))
+                .build()
+                .unwrap()
+        };
+
+        let limited = lookup.store(runner(1, Some(2)));
+        let hosted = lookup.store(runner(2, None));
+        let unlimited = Runner::builder()
+            .forge_id(3)
+            .instance(instance)
+            .runner_type(RunnerType::Instance)
+            .protection_level(RunnerProtectionLevel::Any)
+            .build()
+            .unwrap();
+        let unlimited = lookup.store(unlimited);
+
+        let jobs = [
+            (limited, 0, 0, 20),
+            (limited, 1, 0, 10),
+            (hosted, 2, 0, 10),
+            (unlimited, 3, 0, 10),
+        ];
+        for (runner, id, start, end) in jobs {
+            let job = Job::builder()
+                .user(user)
+                .state(JobState::Success)
+                .created_at(at(start))
+                .started_at(Some(at(start)))
+                .finished_at(Some(at(end)))
+                .runner(Some(runner))
+                .forge_id(id)
+                .pipeline(pipeline)
+                .build()
+                .unwrap();
+            lookup.store(job);
+        }
+
+        let saturation = super::runner_saturation(&lookup, Duration::minutes(10));
+
+        assert_eq!(saturation.len(), 2);
+
+        let limited = &saturation[0];
+        assert_eq!(limited.runner, 1);
+        assert_eq!(limited.instance, "url");
+        assert_eq!(limited.max_concurrent_jobs, 2);
+        assert_eq!(limited.jobs, 2);
+        assert_eq!(limited.peak_concurrency, 2);
+        assert_eq!(limited.peak_saturation, 1.);
+        assert_eq!(limited.mean_saturation, 0.75);
+        assert_eq!(limited.samples.len(), 2);
+        assert_eq!(limited.samples[0].start, at(0));
+        assert_eq!(limited.samples[0].mean_saturation, 1.);
+        assert_eq!(limited.samples[1].start, at(10));
+        assert_eq!(limited.samples[1].peak_concurrency, 1);
+        assert_eq!(limited.samples[1].mean_saturation, 0.5);
+
+        let hosted = &saturation[1];
+        assert_eq!(hosted.runner, 2);
+        assert_eq!(hosted.max_concurrent_jobs, 4);
+        assert_eq!(hosted.peak_saturation, 0.25);
+    }
+}
@@ -0,0 +1,188 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use ci_monitor_core::data::{Job, Pipeline, Project};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::AnalysisLookup;
+
+/// How the duration of a log section changed over time.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct SectionTiming {
+    /// The path of the project on its instance.
+    pub project: String,
+    /// The name of the job.
+    pub job: String,
+    /// The name of the section.
+    pub section: String,
+    /// The median duration (in seconds) before the split.
+    pub before: f64,
+    /// The number of sections timed before the split.
+    pub before_samples: usize,
+    /// The median duration (in seconds) after the split.
+    pub after: f64,
+    /// The number of sections timed after the split.
+    pub after_samples: usize,
+}
+
+impl SectionTiming {
+    /// How much longer (in seconds) the section takes after the split.
+    pub fn change(&self) -> f64 {
+        self.after - self.before
+    }
+}
+
+pub(crate) fn median(durations: &mut [f64]) -> f64 {
+    durations.sort_by(f64::total_cmp);
+    let mid = durations.len() / 2;
+    if durations.len().is_multiple_of(2) {
+        (durations[mid - 1] + durations[mid]) / 2.
+    } else {
+        durations[mid]
+    }
+}
+
+#[derive(Default)]
+struct Samples {
+    before: Vec<f64>,
+    after: Vec<f64>,
+}
+
+/// Compare the duration of job log sections before and after a point in time.
+///
+/// Sections are grouped by project, job name, and section name. Sections which started before
+/// `split` are compared against those which started after it using median durations. Only groups
+/// with sections on both sides are reported and results are sorted by how much slower the
+/// section became (slowest first).
+pub fn section_timings<L>(storage: &L, split: DateTime<Utc>) -> Vec<SectionTiming>
+where
+    L: AnalysisLookup<L>,
+{
+    let mut samples: BTreeMap<(String, String, String), Samples> = BTreeMap::new();
+    let jobs = <L as DiscoverableLookup<Job<L>>>::all_indices(storage);
+    for idx in &jobs {
+        let job = if let Some(job) = <L as Lookup<Job<L>>>::lookup(storage, idx) {
+            job
+        } else {
+            continue;
+        };
+        if job.sections.is_empty() || job.cim_deleted_at.is_some() {
+            continue;
+        }
+        let project = <L as Lookup<Pipeline<L>>>::lookup(storage, &job.pipeline)
+            .and_then(|pipeline| <L as Lookup<Project<L>>>::lookup(storage, &pipeline.project));
+        let project = if let Some(project) = project {
+            project
+        } else {
+            continue;
+        };
+
+        for section in &job.sections {
+            let key = (
+                project.instance_path.clone(),
+                job.name.clone(),
+                section.name.clone(),
+            );
+            let duration = section.duration().num_milliseconds() as f64 / 1000.;
+            let entry = samples.entry(key).or_default();
+            if section.started_at < split {
+                entry.before.push(duration);
+            } else {
+                entry.after.push(duration);
+            }
+        }
+    }
+
+    let mut timings = samples
+        .into_iter()
+        .filter(|(_, samples)| !samples.before.is_empty() && !samples.after.is_empty())
+        .map(|((project, job, section), mut samples)| {
+            SectionTiming {
+                project,
+                job,
+                section,
+                before: median(&mut samples.before),
+                before_samples: samples.before.len(),
+                after: median(&mut samples.after),
+                after_samples: samples.after.len(),
+            }
+        })
+        .collect::<Vec<_>>();
+    timings.sort_by(|lhs, rhs| rhs.change().total_cmp(&lhs.change()));
+    timings
+}
+
+#[cfg(test)]
+mod tests {
+    use ci_monitor_core::data::{Job, JobSection, JobState, PipelineStatus};
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test::{self, at};
+
+    #[test]
+    fn median() {
+        assert_eq!(super::median(&mut [3., 1., 2.]), 2.);
+        assert_eq!(super::median(&mut [4., 1., 3., 2.]), 2.5);
+    }
+
+    #[test]
+    fn section_timings() {
+        let mut lookup = VecLookup::default();
+        let instance = test::instance(&mut lookup);
+        let project = test::project(&mut lookup, instance, 1, "group/project");
+        let user = test::user(&mut lookup, instance, 0);
+        let pipeline = test::pipeline(project, 1, PipelineStatus::Success, at(0));
+        let pipeline = lookup.store(pipeline);
+
+        // (job, start, build minutes, test minutes)
+        let jobs = [
+            (1, 0, 10, 5),
+            (2, 60, 12, 5),
+            (3, 120, 30, 4),
+            (4, 180, 32, 6),
+        ];
+        for (id, start, build, test) in jobs {
+            let mut job = Job::builder()
+                .user(user)
+                .name("build")
+                .state(JobState::Success)
+                .created_at(at(start))
+                .forge_id(id)
+                .pipeline(pipeline)
+                .build()
+                .unwrap();
+            job.sections = vec![
+                JobSection::new("build", at(start), at(start + build)),
+                JobSection::new("test", at(start + build), at(start + build + test)),
+            ];
+            lookup.store(job);
+        }
+
+        let timings = super::section_timings(&lookup, at(100));
+
+        assert_eq!(timings.len(), 2);
+        assert_eq!(timings[0].project, "group/project");
+        assert_eq!(timings[0].job, "build");
+        assert_eq!(timings[0].section, "build");
+        assert_eq!(timings[0].before, 660.);
+        assert_eq!(timings[0].before_samples, 2);
+        assert_eq!(timings[0].after, 1860.);
+        assert_eq!(timings[0].change(), 1200.);
+        assert_eq!(timings[1].section, "test");
+        assert_eq!(timings[1].change(), 0.);
+
+        // Without sections on both sides, nothing is compared.
+        assert!(super::section_timings(&lookup, at(0)).is_empty());
+    }
+}
@@ -0,0 +1,152 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Serialization of fields of analysis results.
+//!
+//! Durations are serialized as a number of seconds and enumerations as their names. Fields using
+//! these must also declare their schema with `#[schemars(with = "...")]`.
+
+use chrono::Duration;
+use ci_monitor_core::data::{EnvironmentTier, JobState, PipelineStatus, PipelineVariableType};
+use serde::Serializer;
+
+use crate::{
+    AlertCondition, AlertEntity, NoPipelineReason, ReleasePipelineState, RouteKind,
+    TimelineEventKind,
+};
+
+/// An enumeration which is serialized as its name.
+pub(crate) trait Named: Copy {
+    /// The name of the value.
+    fn name(self) -> &'static str;
+}
+
+macro_rules! impl_named {
+    ($($ty:ty,)*) => {
+        $(
+            impl Named for $ty {
+                fn name(self) -> &'static str {
+                    <$ty>::name(self)
+                }
+            }
+        )*
+    };
+}
+
+impl_named!(
+    AlertCondition,
+    AlertEntity,
+    EnvironmentTier,
+    JobState,
+    NoPipelineReason,
+    PipelineStatus,
+    PipelineVariableType,
+    ReleasePipelineState,
+    RouteKind,
+    TimelineEventKind,
+);
+
+/// Serialize an enumeration as its name.
+pub(crate) fn name<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Named,
+    S: Serializer,
+{
+    serializer.serialize_str(value.name())
+}
+
+/// Serialize a list as its length.
+pub(crate) fn count<T, S>(items: &[T], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_u64(items.len() as u64)
+}
+
+/// Serialize a duration as a number of seconds.
+pub(crate) fn seconds<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_i64(duration.num_seconds())
+}
+
+/// Serialize an optional duration as a number of seconds.
+pub(crate) fn optional_seconds<S>(
+    duration: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match duration {
+        Some(duration) => serializer.serialize_some(&duration.num_seconds()),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use ci_monitor_core::data::{JobState, PipelineStatus};
+    use serde_json::json;
+
+    use crate::test::at;
+    use crate::{
+        MergeRequestLatency, PolicyViolation, PolicyViolationKind, ReleasePipelineState,
+        ReleaseSummary, RouteKind,
+    };
+
+    #[test]
+    fn serialize_durations() {
+        let latency = MergeRequestLatency {
+            url: "url".into(),
+            title: "title".into(),
+            created_at: at(0),
+            merged_at: at(90),
+            first_review: Some(Duration::minutes(30)),
+            approval: None,
+            ci_time: Duration::minutes(20),
+        };
+
+        let value = serde_json::to_value(&latency).unwrap();
+        assert_eq!(value["first_review"], json!(1800));
+        assert_eq!(value["approval"], json!(null));
+        assert_eq!(value["ci_time"], json!(1200));
+    }
+
+    #[test]
+    fn serialize_names() {
+        let release = ReleaseSummary {
+            tag: "v1.0".into(),
+            released_at: at(0),
+            pipeline: Some(1),
+            pipeline_state: ReleasePipelineState::Finished {
+                status: PipelineStatus::WaitingForResource,
+            },
+        };
+        let value = serde_json::to_value(&release).unwrap();
+        assert_eq!(value["pipeline_state"], json!("waiting_for_resource"));
+
+        let violation = PolicyViolation {
+            pipeline: 1,
+            url: "url".into(),
+            refname: "main".into(),
+            job: "test".into(),
+            kind: PolicyViolationKind::NotPassed {
+                state: JobState::Failed,
+            },
+        };
+        let value = serde_json::to_value(&violation).unwrap();
+        assert_eq!(value["kind"], json!("not_passed"));
+        assert_eq!(value["state"], json!("failed"));
+
+        assert_eq!(
+            super::name(&RouteKind::Deferred, serde_json::value::Serializer).unwrap(),
+            json!("deferred")
+        );
+    }
+}
@@ -0,0 +1,378 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::{DateTime, Duration, Utc};
+use ci_monitor_core::data::{Job, JobState, Pipeline, PipelineStatus, Project, Runner, RunnerHost};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+use glob::Pattern;
+use schemars::JsonSchema;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{AnalysisLookup, FailureRate, Quarantine};
+
+/// Errors which may occur when declaring services.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum ServiceError {
+    /// A pattern is invalid.
+    #[error("invalid pattern '{}' for service '{}': {}", pattern, service, source)]
+    InvalidPattern {
+        /// The service.
+        service: String,
+        /// The pattern.
+        pattern: String,
+        /// The source of the error.
+        #[source]
+        source: glob::PatternError,
+    },
+}
+
+impl ServiceError {
+    fn invalid_pattern(service: String, pattern: String, source: glob::PatternError) -> Self {
+        Self::InvalidPattern {
+            service,
+            pattern,
+            source,
+        }
+    }
+}
+
+/// A mapping from projects to the services they belong to.
+#[derive(Debug, Clone, Default)]
+pub struct ServiceMap {
+    services: Vec<(String, Vec<Pattern>)>,
+}
+
+impl ServiceMap {
+    /// Add a service made up of projects whose path matches any of the given glob patterns.
+    ///
+    /// Projects matching several services belong to the first service added.
+    pub fn service<N, I, P>(mut self, name: N, projects: I) -> Result<Self, ServiceError>
+    where
+        N: Into<String>,
+        I: IntoIterator<Item = P>,
+        P: AsRef<str>,
+    {
+        let name = name.into();
+        let patterns = projects
+            .into_iter()
+            .map(|project| {
+                let project = project.as_ref();
+                Pattern::new(project)
+                    .map_err(|err| ServiceError::invalid_pattern(name.clone(), project.into(), err))
+            })
+            .collect::<Result<_, _>>()?;
+        self.services.push((name, patterns));
+        Ok(self)
+    }
+
+    /// Whether any services have been declared.
+    pub fn is_empty(&self) -> bool {
+        self.services.is_empty()
+    }
+
+    /// The service a project belongs to.
+    pub fn service_of(&self, project: &str) -> Option<&str> {
+        self.services
+            .iter()
+            .find(|(_, patterns)| patterns.iter().any(|pattern| pattern.matches(project)))
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+/// Aggregate CI statistics for a service.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct ServiceReport {
+    /// The name of the service.
+    pub service: String,
+    /// The paths of the projects in the service.
+    pub projects: BTreeSet<String>,
+    /// The failure rate of the service's pipelines.
+    pub pipelines: FailureRate,
+    /// The failure rate of the service's jobs.
+    ///
+    /// Failures of quarantined jobs are not counted.
+    pub jobs: FailureRate,
+    /// The number of failures of quarantined jobs.
+    pub quarantined_failures: usize,
+    /// The total time jobs spent running.
+    #[serde(serialize_with = "crate::serialize::seconds")]
+    #[schemars(with = "i64")]
+    pub machine_time: Duration,
+    /// The estimated cost of the machine time.
+    ///
+    /// Only includes jobs which ran on runner hosts with a known cost.
+    pub estimated_cost: f64,
+    /// Machine time spent on runners without a known cost.
+    #[serde(serialize_with = "crate::serialize::seconds")]
+    #[schemars(with = "i64")]
+    pub unpriced_time: Duration,
+}
+
+impl ServiceReport {
+    fn new(service: String) -> Self {
+        Self {
+            service,
+            projects: BTreeSet::new(),
+            pipelines: FailureRate::default(),
+            jobs: FailureRate::default(),
+            quarantined_failures: 0,
+            machine_time: Duration::zero(),
+            estimated_cost: 0.,
+            unpriced_time: Duration::zero(),
+        }
+    }
+}
+
+fn report_for<'a, L>(
+    reports: &'a mut BTreeMap<String, ServiceReport>,
+    services: &ServiceMap,
+    project: &Project<L>,
+) -> Option<&'a mut ServiceReport>
+where
+    L: AnalysisLookup<L>,
+{
+    let service = services.service_of(&project.instance_path)?;
+    let report = reports
+        .entry(service.into())
+        .or_insert_with(|| ServiceReport::new(service.into()));
+    report.projects.insert(project.instance_path.clone());
+    Some(report)
+}
+
+/// Aggregate pipelines and jobs by the service their project belongs to.
+///
+/// Only pipelines created at or after `since` are considered. Projects which do not belong to any
+/// service are ignored. Failed jobs which are quarantined at the time of their failure are
+/// excluded from job failure rates. Results are sorted by service name.
+pub fn service_reports<L>(
+    storage: &L,
+    services: &ServiceMap,
+    quarantine: &Quarantine,
+    since: Option<DateTime<Utc>>,
+) -> Vec<ServiceReport>
+where
+    L: AnalysisLookup<L>,
+{
+    let mut reports = BTreeMap::new();
+    let in_range = |pipeline: &Pipeline<L>| {
+        pipeline.cim_deleted_at.is_none() && since.is_none_or(|since| since <= pipeline.created_at)
+    };
+
+    let pipelines = <L as DiscoverableLookup<Pipeline<L>>>::all_indices(storage);
+    for idx in &pipelines {
+        let pipeline = if let Some(pipeline) = <L as Lookup<Pipeline<L>>>::lookup(storage, idx) {
+            pipeline
+        } else {
+            continue;
+        };
+        if !pipeline.status.is_finished() || !in_range(pipeline) {
+            continue;
+        }
+        let report = <L as Lookup<Project<L>>>::lookup(storage, &pipeline.project)
+            .and_then(|project| report_for(&mut reports, services, project));
+        if let Some(report) = report {
+            report
+                .pipelines
+                .add(pipeline.status == PipelineStatus::Failed);
+        }
+    }
+
+    let jobs = <L as DiscoverableLookup<Job<L>>>::all_indices(storage);
+    for idx in &jobs {
+        let job = if let Some(job) = <L as Lookup<Job<L>>>::lookup(storage, idx) {
+            job
+        } else {
+            continue;
+        };
+        if job.cim_deleted_at.is_some() {
+            continue;
+        }
+        let project = <L as Lookup<Pipeline<L>>>::lookup(storage, &job.pipeline)
+            .filter(|pipeline| in_range(pipeline))
+            .and_then(|pipeline| <L as Lookup<Project<L>>>::lookup(storage, &pipeline.project));
+        let project = if let Some(project) = project {
+            project
+        } else {
+            continue;
+        };
+        let report = if let Some(report) = report_for(&mut reports, services, project) {
+            report
+        } else {
+            continue;
+        };
+
+        if quarantine.suppresses(project, job) {
+            report.quarantined_failures += 1;
+        } else if matches!(
+            job.state,
+            JobState::Success | JobState::Failed | JobState::Canceled,
+        ) {
+            report.jobs.add(job.state == JobState::Failed);
+        }
+
+        let duration =
+            if let (Some(started_at), Some(finished_at)) = (job.started_at, job.finished_at) {
+                finished_at - started_at
+            } else {
+                continue;
+            };
+        let cost_per_hour = job
+            .runner
+            .as_ref()
+            .and_then(|runner| <L as Lookup<Runner<L>>>::lookup(storage, runner))
+            .and_then(|runner| runner.runner_host.as_ref())
+            .and_then(|host| <L as Lookup<RunnerHost>>::lookup(storage, host))
+            .and_then(|host| host.estimated_cost_per_hour);
+
+        report.machine_time += duration;
+        if let Some(cost_per_hour) = cost_per_hour {
+            report.estimated_cost += cost_per_hour * duration.num_seconds() as f64 / 3600.;
+        } else {
+            report.unpriced_time += duration;
+        }
+    }
+
+    reports.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use ci_monitor_core::data::{
+        Job, JobState, PipelineStatus, Runner, RunnerHost, RunnerProtectionLevel, RunnerType,
+    };
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test::{self, at};
+    use crate::{Quarantine, QuarantinedJob, ServiceError, ServiceMap};
+
+    #[test]
+    fn service_of() {
+        let services = ServiceMap::default()
+            .service("web", ["group/web-*", "group/frontend"])
+            .unwrap()
+            .service("all", ["group/*"])
+            .unwrap();
+
+        assert_eq!(services.service_of("group/web-api"), Some("web"));
+        assert_eq!(services.service_of("group/frontend"), Some("web"));
+        assert_eq!(services.service_of("group/backend"), Some("all"));
+        assert_eq!(services.service_of("other/backend"), None);
+    }
+
+    #[test]
+    fn service_invalid_pattern() {
+        let err = ServiceMap::default().service("web", ["["]).unwrap_err();
+
+        let ServiceError::InvalidPattern {
+            service,
+            pattern,
+            ..
+        } = err;
+        assert_eq!(service, "web");
+        assert_eq!(pattern, "[");
+    }
+
+    #[test]
+    fn service_reports() {
+        let mut lookup = VecLookup::default();
+        let instance = test::instance(&mut lookup);
+        let user = test::user(&mut lookup, instance, 0);
+        let host = RunnerHost::builder()
+            .name("host")
+            .unique_id(0)
+            .estimated_cost_per_hour(Some(2.))
+            .build()
+            .unwrap();
+        let host = lookup.store(host);
+        let runner = Runner::builder()
+            .runner_type(RunnerType::Instance)
+            .protection_level(RunnerProtectionLevel::Any)
+            .forge_id(0)
+            .instance(instance)
+            .runner_host(Some(host))
+            .build()
+            .unwrap();
+        let runner = lookup.store(runner);
+
+        let projects = [(1, "group/api"), (2, "group/web"), (3, "other/tool")]
+            .map(|(id, path)| test::project(&mut lookup, instance, id, path));
+
+        // (project, created, status, job states)
+        let pipelines = [
+            (0, 0, PipelineStatus::Success, &[JobState::Success][..]),
+            (
+                0,
+                10,
+                PipelineStatus::Failed,
+                &[JobState::Failed, JobState::Success][..],
+            ),
+            (1, 20, PipelineStatus::Success, &[JobState::Success][..]),
+            (2, 30, PipelineStatus::Failed, &[JobState::Failed][..]),
+            (0, -60, PipelineStatus::Failed, &[JobState::Failed][..]),
+        ];
+        for (id, (project, created, status, states)) in pipelines.into_iter().enumerate() {
+            let pipeline = test::pipeline(projects[project], id as u64, status, at(created));
+            let pipeline = lookup.store(pipeline);
+            for (job_id, state) in states.iter().enumerate() {
+                let job = Job::builder()
+                    .user(user)
+                    .name("job")
+                    .state(*state)
+                    .created_at(at(created))
+                    .started_at(Some(at(created)))
+                    .finished_at(Some(at(created + 30)))
+                    .runner(Some(runner))
+                    .forge_id((id * 10 + job_id) as u64)
+                    .pipeline(pipeline)
+                    .build()
+                    .unwrap();
+                lookup.store(job);
+            }
+        }
+
+        let services = ServiceMap::default()
+            .service("api", ["group/api"])
+            .unwrap()
+            .service("web", ["group/web"])
+            .unwrap();
+        let reports =
+            super::service_reports(&lookup, &services, &Quarantine::default(), Some(at(0)));
+
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].service, "api");
+        assert_eq!(reports[0].projects.len(), 1);
+        assert_eq!(reports[0].pipelines.finished, 2);
+        assert_eq!(reports[0].pipelines.failed, 1);
+        assert_eq!(reports[0].jobs.finished, 3);
+        assert_eq!(reports[0].jobs.failed, 1);
+        assert_eq!(reports[0].machine_time, Duration::minutes(90));
+        assert_eq!(reports[0].estimated_cost, 3.);
+        assert!(reports[0].unpriced_time.is_zero());
+        assert_eq!(reports[1].service, "web");
+        assert_eq!(reports[1].pipelines.finished, 1);
+        assert_eq!(reports[1].pipelines.failed, 0);
+
+        let reports = super::service_reports(&lookup, &services, &Quarantine::default(), None);
+        assert_eq!(reports[0].pipelines.finished, 3);
+        assert_eq!(reports[0].pipelines.failed, 2);
+
+        let quarantine = Quarantine::default()
+            .job(QuarantinedJob::new("group/api", "job", at(0), at(60)).unwrap());
+        let reports = super::service_reports(&lookup, &services, &quarantine, Some(at(0)));
+        assert_eq!(reports[0].jobs.finished, 2);
+        assert_eq!(reports[0].jobs.failed, 0);
+        assert_eq!(reports[0].quarantined_failures, 1);
+        assert_eq!(reports[0].machine_time, Duration::minutes(90));
+    }
+}
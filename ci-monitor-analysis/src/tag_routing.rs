@@ -0,0 +1,376 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::{DateTime, Duration, Utc};
+use ci_monitor_core::data::{Instance, Job, Pipeline, Project, Runner};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::{AnalysisLookup, SimulatedRunner};
+
+/// How the jobs of a given name in a project are routed to runners by their tags.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct JobTagRouting {
+    /// The path of the project.
+    pub project: String,
+    /// The name of the job.
+    pub job: String,
+    /// The tags requested by the job.
+    pub tags: BTreeSet<String>,
+    /// The number of jobs which ran.
+    pub jobs: usize,
+    /// The IDs of the active runners which could execute the job.
+    pub eligible_runners: BTreeSet<u64>,
+    /// The IDs of the runners which executed the job.
+    pub runners_used: BTreeSet<u64>,
+    /// The number of jobs executed by runners which no longer accept the job's tags.
+    pub mismatched: usize,
+}
+
+impl JobTagRouting {
+    /// Whether only a single runner may execute the job.
+    pub fn is_over_constrained(&self) -> bool {
+        self.eligible_runners.len() == 1
+    }
+
+    /// Whether no active runner may execute the job.
+    pub fn is_unroutable(&self) -> bool {
+        self.eligible_runners.is_empty()
+    }
+}
+
+/// The runners carrying a tag and the jobs requesting it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct TagPool {
+    /// The URL of the instance.
+    pub instance: String,
+    /// The tag.
+    pub tag: String,
+    /// The number of active runners with the tag.
+    pub runners: usize,
+    /// The number of jobs which requested the tag.
+    pub jobs: usize,
+    /// The total time jobs requesting the tag spent running.
+    #[serde(serialize_with = "crate::serialize::seconds")]
+    #[schemars(with = "i64")]
+    pub machine_time: Duration,
+}
+
+impl TagPool {
+    /// Whether no jobs requested the tag.
+    pub fn is_unused(&self) -> bool {
+        self.jobs == 0
+    }
+
+    /// The machine time used per runner in the pool.
+    pub fn machine_time_per_runner(&self) -> Duration {
+        if self.runners == 0 {
+            Duration::zero()
+        } else {
+            self.machine_time / (self.runners as i32)
+        }
+    }
+}
+
+/// A report on how job tags route jobs to runners.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct TagRoutingReport {
+    /// Jobs sorted by the number of runners which could execute them, fewest first.
+    pub jobs: Vec<JobTagRouting>,
+    /// Tag pools sorted by the machine time used per runner, least first.
+    pub pools: Vec<TagPool>,
+}
+
+impl TagRoutingReport {
+    /// Jobs which may only be executed by a single runner.
+    pub fn over_constrained(&self) -> impl Iterator<Item = &JobTagRouting> {
+        self.jobs.iter().filter(|job| job.is_over_constrained())
+    }
+}
+
+/// An active runner considered for routing.
+struct RoutingRunner {
+    forge_id: u64,
+    accepts: SimulatedRunner,
+    /// The projects the runner is restricted to (if any).
+    projects: BTreeSet<u64>,
+}
+
+impl RoutingRunner {
+    fn can_run(&self, project: u64, tags: &BTreeSet<String>) -> bool {
+        (self.projects.is_empty() || self.projects.contains(&project))
+            && self.accepts.accepts_tags(tags)
+    }
+}
+
+/// Compare the tags of jobs against the runners which executed them and those which could have.
+///
+/// Only jobs which started at or after `since` are considered. Paused runners are not eligible
+/// and runners restricted to projects are only eligible for jobs within them. Eligibility uses the
+/// current tags of the runners.
+pub fn tag_routing_report<L>(storage: &L, since: DateTime<Utc>) -> TagRoutingReport
+where
+    L: AnalysisLookup<L>,
+{
+    let mut runners: BTreeMap<String, Vec<RoutingRunner>> = BTreeMap::new();
+    let mut pools: BTreeMap<(String, String), TagPool> = BTreeMap::new();
+
+    for idx in <L as DiscoverableLookup<Runner<L>>>::all_indices(storage) {
+        let runner = if let Some(runner) = <L as Lookup<Runner<L>>>::lookup(storage, &idx) {
+            runner
+        } else {
+            continue;
+        };
+        if runner.paused {
+            continue;
+        }
+        let instance =
+            if let Some(instance) = <L as Lookup<Instance>>::lookup(storage, &runner.instance) {
+                instance
+            } else {
+                continue;
+            };
+
+        for tag in &runner.tags {
+            pools
+                .entry((instance.url.clone(), tag.clone()))
+                .or_insert_with(|| {
+                    TagPool {
+                        instance: instance.url.clone(),
+                        tag: tag.clone(),
+                        runners: 0,
+                        jobs: 0,
+                        machine_time: Duration::zero(),
+                    }
+                })
+                .runners += 1;
+        }
+        let projects = runner
+            .projects
+            .iter()
+            .filter_map(|project| <L as Lookup<Project<L>>>::lookup(storage, project))
+            .map(|project| project.forge_id)
+            .collect();
+        runners
+            .entry(instance.url.clone())
+            .or_default()
+            .push(RoutingRunner {
+                forge_id: runner.forge_id,
+                accepts: SimulatedRunner::new(runner.tags.iter().cloned(), runner.run_untagged),
+                projects,
+            });
+    }
+
+    let mut routings: BTreeMap<(String, String, BTreeSet<String>), JobTagRouting> = BTreeMap::new();
+    for idx in <L as DiscoverableLookup<Job<L>>>::all_indices(storage) {
+        let job = if let Some(job) = <L as Lookup<Job<L>>>::lookup(storage, &idx) {
+            job
+        } else {
+            continue;
+        };
+        let started_at = if let Some(started_at) = job.started_at {
+            started_at
+        } else {
+            continue;
+        };
+        if started_at < since || job.cim_deleted_at.is_some() {
+            continue;
+        }
+        let project = <L as Lookup<Pipeline<L>>>::lookup(storage, &job.pipeline)
+            .and_then(|pipeline| <L as Lookup<Project<L>>>::lookup(storage, &pipeline.project));
+        let project = if let Some(project) = project {
+            project
+        } else {
+            continue;
+        };
+        let instance =
+            if let Some(instance) = <L as Lookup<Instance>>::lookup(storage, &project.instance) {
+                instance
+            } else {
+                continue;
+            };
+        let tags = job.tags.iter().cloned().collect::<BTreeSet<_>>();
+        let instance_runners = runners
+            .get(&instance.url)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+        let routing = routings
+            .entry((
+                project.instance_path.clone(),
+                job.name.clone(),
+                tags.clone(),
+            ))
+            .or_insert_with(|| {
+                JobTagRouting {
+                    project: project.instance_path.clone(),
+                    job: job.name.clone(),
+                    eligible_runners: instance_runners
+                        .iter()
+                        .filter(|runner| runner.can_run(project.forge_id, &tags))
+                        .map(|runner| runner.forge_id)
+                        .collect(),
+                    tags: tags.clone(),
+                    jobs: 0,
+                    runners_used: BTreeSet::new(),
+                    mismatched: 0,
+                }
+            });
+        routing.jobs += 1;
+        let runner = job
+            .runner
+            .as_ref()
+            .and_then(|runner| <L as Lookup<Runner<L>>>::lookup(storage, runner));
+        if let Some(runner) = runner {
+            routing.runners_used.insert(runner.forge_id);
+            let accepts = SimulatedRunner::new(runner.tags.iter().cloned(), runner.run_untagged);
+            if !accepts.accepts_tags(&tags) {
+                routing.mismatched += 1;
+            }
+        }
+
+        let machine_time = job
+            .finished_at
+            .map_or_else(Duration::zero, |finished_at| finished_at - started_at);
+        for tag in &tags {
+            if let Some(pool) = pools.get_mut(&(instance.url.clone(), tag.clone())) {
+                pool.jobs += 1;
+                pool.machine_time += machine_time;
+            }
+        }
+    }
+
+    let mut jobs = routings.into_values().collect::<Vec<_>>();
+    jobs.sort_by_key(|routing| routing.eligible_runners.len());
+    let mut pools = pools.into_values().collect::<Vec<_>>();
+    pools.sort_by_key(TagPool::machine_time_per_runner);
+
+    TagRoutingReport {
+        jobs,
+        pools,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ci_monitor_core::data::{
+        Job, JobState, PipelineStatus, Runner, RunnerProtectionLevel, RunnerType,
+    };
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test::{self, at};
+
+    #[test]
+    fn tag_routing_report() {
+        let mut lookup = VecLookup::default();
+        let instance = test::instance(&mut lookup);
+        let user = test::user(&mut lookup, instance, 0);
+        let project = test::project(&mut lookup, instance, 1, "group/project");
+        let other = test::project(&mut lookup, instance, 2, "group/other");
+
+        // (id, tags, paused, projects)
+        let runners = [
+            (1, vec!["linux", "docker"], false, vec![]),
+            (2, vec!["linux", "docker"], false, vec![]),
+            (3, vec!["linux", "cuda"], false, vec![]),
+            (4, vec!["linux", "cuda"], true, vec![]),
+            (5, vec!["linux", "cuda"], false, vec![other]),
+            (6, vec!["macos"], false, vec![]),
+        ];
+        let runners = runners.map(|(id, tags, paused, projects)| {
+            let runner = Runner::builder()
+                .runner_type(RunnerType::Instance)
+                .protection_level(RunnerProtectionLevel::Any)
+                .forge_id(id)
+                .instance(instance)
+                .tags(tags.into_iter().map(String::from).collect::<Vec<_>>())
+                .paused(paused)
+                .projects(projects)
+                .build()
+                .unwrap();
+            lookup.store(runner)
+        });
+
+        let pipeline = test::pipeline(project, 1, PipelineStatus::Success, at(0));
+        let pipeline = lookup.store(pipeline);
+
+        // (name, tags, runner, start)
+        let jobs = [
+            ("build", vec!["linux"], 0, 0),
+            ("build", vec!["linux"], 1, 10),
+            ("gpu", vec!["linux", "cuda"], 2, 0),
+            ("gpu", vec!["linux", "cuda"], 0, 20),
+            ("gpu", vec!["linux", "cuda"], 2, -60),
+        ];
+        for (id, (name, tags, runner, start)) in jobs.into_iter().enumerate() {
+            let job = Job::builder()
+                .user(user)
+                .name(name)
+                .tags(tags.into_iter().map(String::from).collect::<Vec<_>>())
+                .state(JobState::Success)
+                .created_at(at(start))
+                .started_at(Some(at(start)))
+                .finished_at(Some(at(start + 10)))
+                .runner(Some(runners[runner]))
+                .forge_id(id as u64)
+                .pipeline(pipeline)
+                .build()
+                .unwrap();
+            lookup.store(job);
+        }
+
+        let report = super::tag_routing_report(&lookup, at(0));
+
+        assert_eq!(report.jobs.len(), 2);
+        let gpu = &report.jobs[0];
+        assert_eq!(gpu.job, "gpu");
+        assert_eq!(gpu.jobs, 2);
+        assert_eq!(
+            gpu.eligible_runners.iter().copied().collect::<Vec<_>>(),
+            [3]
+        );
+        assert_eq!(gpu.runners_used.len(), 2);
+        assert_eq!(gpu.mismatched, 1);
+        assert!(gpu.is_over_constrained());
+        let build = &report.jobs[1];
+        assert_eq!(build.job, "build");
+        assert_eq!(build.eligible_runners.len(), 3);
+        assert!(!build.is_over_constrained());
+        assert_eq!(report.over_constrained().count(), 1);
+
+        // (tag, runners, jobs, machine time)
+        let pools = report
+            .pools
+            .iter()
+            .map(|pool| {
+                (
+                    pool.tag.as_str(),
+                    pool.runners,
+                    pool.jobs,
+                    pool.machine_time.num_minutes(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            pools,
+            [
+                ("docker", 2, 0, 0),
+                ("macos", 1, 0, 0),
+                ("cuda", 2, 2, 20),
+                ("linux", 4, 4, 40),
+            ],
+        );
+        assert!(report.pools[0].is_unused());
+    }
+}
@@ -0,0 +1,94 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Fixtures shared by analysis tests.
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use ci_monitor_core::data::{Instance, Pipeline, PipelineSource, PipelineStatus, Project, User};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::{VecIndex, VecLookup};
+
+/// The time at which test data starts.
+fn epoch() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()
+}
+
+/// A time a number of minutes after the start of test data.
+pub fn at(minute: i64) -> DateTime<Utc> {
+    epoch() + Duration::minutes(minute)
+}
+
+/// A time a number of hours after the start of test data.
+pub fn at_hour(hour: i64) -> DateTime<Utc> {
+    epoch() + Duration::hours(hour)
+}
+
+/// A time a number of days after the start of test data.
+pub fn at_day(day: i64) -> DateTime<Utc> {
+    epoch() + Duration::days(day)
+}
+
+/// Store the forge instance of test data.
+pub fn instance(lookup: &mut VecLookup) -> VecIndex<Instance> {
+    let instance = Instance::builder()
+        .unique_id(0)
+        .forge("forge")
+        .url("url")
+        .build()
+        .unwrap();
+    lookup.store(instance)
+}
+
+/// Store a user.
+pub fn user(
+    lookup: &mut VecLookup,
+    instance: VecIndex<Instance>,
+    forge_id: u64,
+) -> VecIndex<User<VecLookup>> {
+    let user = User::builder()
+        .forge_id(forge_id)
+        .instance(instance)
+        .build()
+        .unwrap();
+    lookup.store(user)
+}
+
+/// Store a project.
+pub fn project(
+    lookup: &mut VecLookup,
+    instance: VecIndex<Instance>,
+    forge_id: u64,
+    path: &str,
+) -> VecIndex<Project<VecLookup>> {
+    let project = Project::builder()
+        .forge_id(forge_id)
+        .instance(instance)
+        .instance_path(path)
+        .url(path)
+        .build()
+        .unwrap();
+    lookup.store(project)
+}
+
+/// A push pipeline which was last updated when it was created.
+pub fn pipeline(
+    project: VecIndex<Project<VecLookup>>,
+    forge_id: u64,
+    status: PipelineStatus,
+    created_at: DateTime<Utc>,
+) -> Pipeline<VecLookup> {
+    Pipeline::builder()
+        .project(project)
+        .sha("0000000000000000000000000000000000000000")
+        .source(PipelineSource::Push)
+        .status(status)
+        .forge_id(forge_id)
+        .url("url")
+        .created_at(created_at)
+        .updated_at(created_at)
+        .build()
+        .unwrap()
+}
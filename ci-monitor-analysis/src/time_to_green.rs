@@ -0,0 +1,388 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::{DateTime, Duration, Utc};
+use ci_monitor_core::data::{Job, JobState, MergeRequest, Pipeline, PipelineStatus, Project, Push};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::cost::pipeline_merge_request;
+use crate::AnalysisLookup;
+
+/// How long a merge request waited for its first fully green pipeline.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct MergeRequestTimeToGreen {
+    /// The URL of the merge request.
+    pub url: String,
+    /// The title of the merge request.
+    pub title: String,
+    /// The path of the target project.
+    pub project: String,
+    /// When the merge request was created.
+    pub created_at: DateTime<Utc>,
+    /// When the wait for a green pipeline started.
+    ///
+    /// This is the creation of the merge request or, if later, the push of the commit which was
+    /// (or is yet to be) built green.
+    pub started_at: DateTime<Utc>,
+    /// When the first fully green pipeline finished.
+    pub green_at: Option<DateTime<Utc>>,
+}
+
+impl MergeRequestTimeToGreen {
+    /// How long the merge request waited for a green pipeline.
+    pub fn time_to_green(&self) -> Option<Duration> {
+        self.green_at.map(|green_at| green_at - self.started_at)
+    }
+}
+
+/// The distribution of the time to green of merge requests in a project.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct ProjectTimeToGreen {
+    /// The path of the project.
+    pub project: String,
+    /// The number of merge requests.
+    pub merge_requests: usize,
+    /// The number of merge requests which have had a green pipeline.
+    pub green: usize,
+    /// The median time to green.
+    #[serde(serialize_with = "crate::serialize::seconds")]
+    #[schemars(with = "i64")]
+    pub median: Duration,
+    /// The time to green which is not exceeded by 90% of green merge requests.
+    #[serde(serialize_with = "crate::serialize::seconds")]
+    #[schemars(with = "i64")]
+    pub p90: Duration,
+    /// The longest time to green.
+    #[serde(serialize_with = "crate::serialize::seconds")]
+    #[schemars(with = "i64")]
+    pub max: Duration,
+}
+
+/// The time to green of merge requests.
+#[derive(Debug, Clone, Default, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct TimeToGreen {
+    /// Merge requests sorted by when they started waiting.
+    pub merge_requests: Vec<MergeRequestTimeToGreen>,
+    /// Projects sorted by their median time to green (longest first).
+    pub projects: Vec<ProjectTimeToGreen>,
+}
+
+/// The duration which is not exceeded by `percentile` percent of sorted durations.
+pub(crate) fn percentile(durations: &[Duration], percentile: f64) -> Duration {
+    let rank = (percentile / 100. * durations.len() as f64).ceil() as usize;
+    durations
+        .get(rank.saturating_sub(1))
+        .copied()
+        .unwrap_or_else(Duration::zero)
+}
+
+/// A pipeline run for a merge request.
+struct MergeRequestPipeline {
+    sha: String,
+    created_at: DateTime<Utc>,
+    finished_at: Option<DateTime<Utc>>,
+    green: bool,
+}
+
+/// Compute how long merge requests waited for their first fully green pipeline.
+///
+/// A pipeline is fully green if it succeeded without any failed jobs (including those which are
+/// allowed to fail). A commit is considered pushed at its earliest recorded push or, if its push
+/// was not recorded, when its first pipeline for the merge request was created. Merge requests
+/// which have yet to see a green pipeline are included with the wait starting at the push of their
+/// latest commit. Only merge requests created at or after `since` are considered.
+pub fn time_to_green<L>(storage: &L, since: Option<DateTime<Utc>>) -> TimeToGreen
+where
+    L: AnalysisLookup<L>,
+    <L as Lookup<Pipeline<L>>>::Index: Ord,
+{
+    let mut merge_requests = BTreeMap::new();
+    let mut by_ref = BTreeMap::new();
+
+    for idx in <L as DiscoverableLookup<MergeRequest<L>>>::all_indices(storage) {
+        let mr = if let Some(mr) = <L as Lookup<MergeRequest<L>>>::lookup(storage, &idx) {
+            mr
+        } else {
+            continue;
+        };
+        if mr.cim_deleted_at.is_some() {
+            continue;
+        }
+        let created_at = if let Some(created_at) = mr.created_at {
+            created_at
+        } else {
+            continue;
+        };
+        if since.is_some_and(|since| created_at < since) {
+            continue;
+        }
+        // Merge request pipelines run in the target project.
+        let project =
+            if let Some(project) = <L as Lookup<Project<L>>>::lookup(storage, &mr.target_project) {
+                project
+            } else {
+                continue;
+            };
+        by_ref.insert((project.url.clone(), mr.id), mr.url.clone());
+
+        let entry = MergeRequestTimeToGreen {
+            url: mr.url.clone(),
+            title: mr.title.clone(),
+            project: project.instance_path.clone(),
+            created_at,
+            started_at: created_at,
+            green_at: None,
+        };
+        merge_requests.insert(mr.url.clone(), (entry, Vec::new()));
+    }
+
+    let with_failures = <L as DiscoverableLookup<Job<L>>>::all_indices(storage)
+        .iter()
+        .filter_map(|idx| <L as Lookup<Job<L>>>::lookup(storage, idx))
+        .filter(|job| job.state == JobState::Failed && job.cim_deleted_at.is_none())
+        .map(|job| job.pipeline.clone())
+        .collect::<BTreeSet<_>>();
+
+    let mut pushed: BTreeMap<String, DateTime<Utc>> = BTreeMap::new();
+    for idx in <L as DiscoverableLookup<Push<L>>>::all_indices(storage) {
+        let push = if let Some(push) = <L as Lookup<Push<L>>>::lookup(storage, &idx) {
+            push
+        } else {
+            continue;
+        };
+        if push.cim_deleted_at.is_some() {
+            continue;
+        }
+        pushed
+            .entry(push.sha.clone())
+            .and_modify(|pushed_at| *pushed_at = (*pushed_at).min(push.pushed_at))
+            .or_insert(push.pushed_at);
+    }
+
+    for idx in <L as DiscoverableLookup<Pipeline<L>>>::all_indices(storage) {
+        let pipeline = if let Some(pipeline) = <L as Lookup<Pipeline<L>>>::lookup(storage, &idx) {
+            pipeline
+        } else {
+            continue;
+        };
+        if pipeline.cim_deleted_at.is_some() {
+            continue;
+        }
+        let entry = pipeline_merge_request(storage, pipeline, &by_ref)
+            .and_then(|url| merge_requests.get_mut(&url));
+        if let Some((_, pipelines)) = entry {
+            pipelines.push(MergeRequestPipeline {
+                sha: pipeline.sha.clone(),
+                created_at: pipeline.created_at,
+                finished_at: pipeline.finished_at,
+                green: pipeline.status == PipelineStatus::Success && !with_failures.contains(&idx),
+            });
+        }
+    }
+
+    let mut merge_requests = merge_requests
+        .into_values()
+        .map(|(mut entry, mut pipelines)| {
+            pipelines.sort_by_key(|pipeline| pipeline.created_at);
+
+            let mut first_built = BTreeMap::new();
+            for pipeline in &pipelines {
+                first_built
+                    .entry(pipeline.sha.as_str())
+                    .or_insert(pipeline.created_at);
+            }
+
+            let green = pipelines
+                .iter()
+                .filter(|pipeline| pipeline.green)
+                .filter_map(|pipeline| pipeline.finished_at.map(|at| (at, pipeline)))
+                .min_by_key(|&(finished_at, _)| finished_at);
+            let built = if let Some((finished_at, pipeline)) = green {
+                entry.green_at = Some(finished_at);
+                Some(pipeline)
+            } else {
+                pipelines.last()
+            };
+            let pushed_at = built.and_then(|pipeline| {
+                let sha = pipeline.sha.as_str();
+                pushed.get(sha).or_else(|| first_built.get(sha)).copied()
+            });
+            if let Some(pushed_at) = pushed_at {
+                entry.started_at = entry.created_at.max(pushed_at);
+            }
+
+            entry
+        })
+        .collect::<Vec<_>>();
+    merge_requests.sort_by_key(|entry| entry.started_at);
+
+    let mut by_project: BTreeMap<_, (usize, Vec<Duration>)> = BTreeMap::new();
+    for entry in &merge_requests {
+        let (count, durations) = by_project.entry(entry.project.clone()).or_default();
+        *count += 1;
+        durations.extend(entry.time_to_green());
+    }
+    let mut projects = by_project
+        .into_iter()
+        .map(|(project, (count, mut durations))| {
+            durations.sort();
+            ProjectTimeToGreen {
+                project,
+                merge_requests: count,
+                green: durations.len(),
+                median: percentile(&durations, 50.),
+                p90: percentile(&durations, 90.),
+                max: durations.last().copied().unwrap_or_else(Duration::zero),
+            }
+        })
+        .collect::<Vec<_>>();
+    projects.sort_by_key(|project| Reverse(project.median));
+
+    TimeToGreen {
+        merge_requests,
+        projects,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use ci_monitor_core::data::{
+        Job, JobState, MergeRequest, MergeRequestStatus, Pipeline, PipelineSource, PipelineStatus,
+        Push,
+    };
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test::{self, at};
+
+    #[test]
+    fn percentile() {
+        let durations = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10].map(Duration::minutes);
+        assert_eq!(super::percentile(&durations, 50.), Duration::minutes(5));
+        assert_eq!(super::percentile(&durations, 90.), Duration::minutes(9));
+        assert_eq!(super::percentile(&[], 50.), Duration::zero());
+    }
+
+    #[test]
+    fn time_to_green() {
+        let mut lookup = VecLookup::default();
+        let instance = test::instance(&mut lookup);
+        let user = test::user(&mut lookup, instance, 0);
+        let project = test::project(&mut lookup, instance, 1, "group/project");
+
+        let mrs = [1, 2, 3].map(|id| {
+            let mr = MergeRequest::builder()
+                .id(id)
+                .source_project(project)
+                .target_project(project)
+                .forge_id(id)
+                .title(format!("mr{}", id))
+                .state(MergeRequestStatus::Open)
+                .author(user)
+                .url(format!("mr{}", id))
+                .created_at(Some(at(id as i64)))
+                .build()
+                .unwrap();
+            lookup.store(mr)
+        });
+
+        // (merge request, sha, status, failed job, created, finished)
+        let pipelines = [
+            // Green on the first push.
+            (0, "a", PipelineStatus::Success, false, 1, 31),
+            (0, "a", PipelineStatus::Success, false, 40, 50),
+            // Fails, is fixed by a push, and then has an allowed failure before going green.
+            (1, "b", PipelineStatus::Failed, true, 2, 12),
+            (1, "c", PipelineStatus::Success, true, 60, 70),
+            (1, "c", PipelineStatus::Success, false, 80, 100),
+            // Never green.
+            (2, "d", PipelineStatus::Failed, true, 3, 10),
+            (2, "e", PipelineStatus::Running, false, 90, 100),
+        ];
+        for (id, (mr, sha, status, failed, created, finished)) in pipelines.into_iter().enumerate()
+        {
+            let finished_at = status.is_finished().then(|| at(finished));
+            let pipeline = Pipeline::builder()
+                .project(project)
+                .sha(sha)
+                .source(PipelineSource::MergeRequestEvent)
+                .status(status)
+                .merge_request(Some(mrs[mr]))
+                .forge_id(id as u64)
+                .url("url")
+                .created_at(at(created))
+                .updated_at(at(finished))
+                .finished_at(finished_at)
+                .build()
+                .unwrap();
+            let pipeline = lookup.store(pipeline);
+
+            if failed {
+                let job = Job::builder()
+                    .user(user)
+                    .name("job")
+                    .state(JobState::Failed)
+                    .created_at(at(created))
+                    .forge_id(id as u64)
+                    .pipeline(pipeline)
+                    .build()
+                    .unwrap();
+                lookup.store(job);
+            }
+        }
+
+        // The fix was pushed well before its pipeline was created.
+        let push = Push::builder()
+            .project(project)
+            .refname("topic")
+            .sha("c")
+            .forge_id(1)
+            .pushed_at(at(55))
+            .build()
+            .unwrap();
+        lookup.store(push);
+
+        let report = super::time_to_green(&lookup, None);
+
+        // (url, started, time to green)
+        let merge_requests = report
+            .merge_requests
+            .iter()
+            .map(|mr| (mr.url.as_str(), mr.started_at, mr.time_to_green()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            merge_requests,
+            [
+                ("mr1", at(1), Some(Duration::minutes(30))),
+                ("mr2", at(55), Some(Duration::minutes(45))),
+                ("mr3", at(90), None),
+            ],
+        );
+
+        assert_eq!(report.projects.len(), 1);
+        let project = &report.projects[0];
+        assert_eq!(project.project, "group/project");
+        assert_eq!(project.merge_requests, 3);
+        assert_eq!(project.green, 2);
+        assert_eq!(project.median, Duration::minutes(30));
+        assert_eq!(project.p90, Duration::minutes(45));
+        assert_eq!(project.max, Duration::minutes(45));
+
+        let report = super::time_to_green(&lookup, Some(at(2)));
+        assert_eq!(report.merge_requests.len(), 2);
+        assert_eq!(report.merge_requests[0].url, "mr2");
+    }
+}
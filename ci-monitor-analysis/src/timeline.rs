@@ -0,0 +1,434 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use chrono::{DateTime, Utc};
+use ci_monitor_core::data::{Deployment, Environment, Pipeline, Project, Runner};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::AnalysisLookup;
+
+/// The maximum length of a line in an iCalendar file (in octets).
+const ICS_LINE_LENGTH: usize = 75;
+
+/// The kind of an event on a timeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum TimelineEventKind {
+    /// A pipeline started.
+    PipelineStarted,
+    /// A pipeline finished.
+    PipelineFinished,
+    /// A deployment into an environment was created.
+    DeploymentStarted,
+    /// A deployment into an environment finished.
+    DeploymentFinished,
+    /// A runner stopped contacting the forge.
+    RunnerOffline,
+}
+
+impl TimelineEventKind {
+    /// The name of the kind of event.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::PipelineStarted => "pipeline_started",
+            Self::PipelineFinished => "pipeline_finished",
+            Self::DeploymentStarted => "deployment_started",
+            Self::DeploymentFinished => "deployment_finished",
+            Self::RunnerOffline => "runner_offline",
+        }
+    }
+}
+
+/// An event on a timeline.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct TimelineEvent {
+    /// When the event occurred.
+    pub at: DateTime<Utc>,
+    /// The kind of event.
+    ///
+    /// One of `pipeline_started`, `pipeline_finished`, `deployment_started`,
+    /// `deployment_finished`, or `runner_offline`.
+    #[serde(serialize_with = "crate::serialize::name")]
+    #[schemars(with = "String")]
+    pub kind: TimelineEventKind,
+    /// An identifier for the event which is stable across exports.
+    pub id: String,
+    /// The path of the project the event belongs to.
+    pub project: Option<String>,
+    /// A summary of the event.
+    pub summary: String,
+    /// A URL with details of the event.
+    pub url: Option<String>,
+}
+
+/// A chronological timeline of CI activity during a time window.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct Timeline {
+    /// The start of the window.
+    pub start: DateTime<Utc>,
+    /// The end of the window.
+    pub end: DateTime<Utc>,
+    /// The events during the window in chronological order.
+    pub events: Vec<TimelineEvent>,
+}
+
+fn ics_time(at: DateTime<Utc>) -> String {
+    at.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn ics_escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Write a content line, folding it to the maximum line length.
+fn ics_line(out: &mut String, line: &str) {
+    let mut length = 0;
+    for c in line.chars() {
+        if ICS_LINE_LENGTH < length + c.len_utf8() {
+            out.push_str("\r\n ");
+            // The leading space counts towards the length of continuation lines.
+            length = 1;
+        }
+        out.push(c);
+        length += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+impl Timeline {
+    /// Render the timeline as an iCalendar file.
+    ///
+    /// Each event becomes an instantaneous calendar event categorized by its kind.
+    pub fn render_ics(&self) -> String {
+        let mut out = String::new();
+        ics_line(&mut out, "BEGIN:VCALENDAR");
+        ics_line(&mut out, "VERSION:2.0");
+        ics_line(&mut out, "PRODID:-//ci-monitor//timeline//EN");
+        for event in &self.events {
+            let at = ics_time(event.at);
+            ics_line(&mut out, "BEGIN:VEVENT");
+            ics_line(&mut out, &format!("UID:{}@ci-monitor", event.id));
+            ics_line(&mut out, &format!("DTSTAMP:{}", at));
+            ics_line(&mut out, &format!("DTSTART:{}", at));
+            let summary = if let Some(project) = event.project.as_ref() {
+                format!("{}: {}", project, event.summary)
+            } else {
+                event.summary.clone()
+            };
+            ics_line(&mut out, &format!("SUMMARY:{}", ics_escape(&summary)));
+            ics_line(&mut out, &format!("CATEGORIES:{}", event.kind.name()));
+            if let Some(url) = event.url.as_ref() {
+                ics_line(&mut out, &format!("URL:{}", url));
+            }
+            ics_line(&mut out, "END:VEVENT");
+        }
+        ics_line(&mut out, "END:VCALENDAR");
+        out
+    }
+}
+
+/// Collect the CI activity between `start` and `end` into a timeline.
+///
+/// Pipelines contribute when they started and finished and deployments when they were created and
+/// finished. The forge only reports whether runners are currently online, so runners which are
+/// offline contribute when they last contacted the forge. Events at the same time are ordered by
+/// their kind.
+pub fn event_timeline<L>(storage: &L, start: DateTime<Utc>, end: DateTime<Utc>) -> Timeline
+where
+    L: AnalysisLookup<L>,
+{
+    let mut events = Vec::new();
+    let in_window = |at: &DateTime<Utc>| start <= *at && *at < end;
+    let project_path = |idx: &<L as Lookup<Project<L>>>::Index| {
+        <L as Lookup<Project<L>>>::lookup(storage, idx).map(|project| project.instance_path.clone())
+    };
+
+    for idx in <L as DiscoverableLookup<Pipeline<L>>>::all_indices(storage) {
+        let pipeline = if let Some(pipeline) = <L as Lookup<Pipeline<L>>>::lookup(storage, &idx) {
+            pipeline
+        } else {
+            continue;
+        };
+        if pipeline.cim_deleted_at.is_some() {
+            continue;
+        }
+        let refname = pipeline.refname.as_deref().unwrap_or(&pipeline.sha);
+
+        if let Some(started_at) = pipeline.started_at.filter(in_window) {
+            events.push(TimelineEvent {
+                at: started_at,
+                kind: TimelineEventKind::PipelineStarted,
+                id: format!("pipeline-{}-started", pipeline.forge_id),
+                project: project_path(&pipeline.project),
+                summary: format!("pipeline {} started on {}", pipeline.forge_id, refname),
+                url: Some(pipeline.url.clone()),
+            });
+        }
+        if let Some(finished_at) = pipeline.finished_at.filter(in_window) {
+            events.push(TimelineEvent {
+                at: finished_at,
+                kind: TimelineEventKind::PipelineFinished,
+                id: format!("pipeline-{}-finished", pipeline.forge_id),
+                project: project_path(&pipeline.project),
+                summary: format!(
+                    "pipeline {} finished ({}) on {}",
+                    pipeline.forge_id,
+                    pipeline.status.name(),
+                    refname,
+                ),
+                url: Some(pipeline.url.clone()),
+            });
+        }
+    }
+
+    for idx in <L as DiscoverableLookup<Deployment<L>>>::all_indices(storage) {
+        let deployment =
+            if let Some(deployment) = <L as Lookup<Deployment<L>>>::lookup(storage, &idx) {
+                deployment
+            } else {
+                continue;
+            };
+        if deployment.cim_deleted_at.is_some() {
+            continue;
+        }
+        let environment = if let Some(environment) =
+            <L as Lookup<Environment<L>>>::lookup(storage, &deployment.environment)
+        {
+            environment
+        } else {
+            continue;
+        };
+        let pipeline = <L as Lookup<Pipeline<L>>>::lookup(storage, &deployment.pipeline);
+        let url = pipeline.map(|pipeline| pipeline.url.clone());
+
+        if in_window(&deployment.created_at) {
+            events.push(TimelineEvent {
+                at: deployment.created_at,
+                kind: TimelineEventKind::DeploymentStarted,
+                id: format!("deployment-{}-started", deployment.forge_id),
+                project: project_path(&environment.project),
+                summary: format!(
+                    "deployment {} to {} started",
+                    deployment.forge_id, environment.name,
+                ),
+                url: url.clone(),
+            });
+        }
+        if let Some(finished_at) = deployment.finished_at.filter(in_window) {
+            events.push(TimelineEvent {
+                at: finished_at,
+                kind: TimelineEventKind::DeploymentFinished,
+                id: format!("deployment-{}-finished", deployment.forge_id),
+                project: project_path(&environment.project),
+                summary: format!(
+                    "deployment {} to {} finished ({})",
+                    deployment.forge_id,
+                    environment.name,
+                    deployment.status.name(),
+                ),
+                url,
+            });
+        }
+    }
+
+    for idx in <L as DiscoverableLookup<Runner<L>>>::all_indices(storage) {
+        let runner = if let Some(runner) = <L as Lookup<Runner<L>>>::lookup(storage, &idx) {
+            runner
+        } else {
+            continue;
+        };
+        if runner.online {
+            continue;
+        }
+        if let Some(contacted_at) = runner.contacted_at.filter(in_window) {
+            events.push(TimelineEvent {
+                at: contacted_at,
+                kind: TimelineEventKind::RunnerOffline,
+                id: format!(
+                    "runner-{}-offline-{}",
+                    runner.forge_id,
+                    contacted_at.timestamp(),
+                ),
+                project: None,
+                summary: format!(
+                    "runner {} ({}) went offline",
+                    runner.forge_id, runner.description,
+                ),
+                url: None,
+            });
+        }
+    }
+
+    events.sort_by(|lhs, rhs| {
+        lhs.at
+            .cmp(&rhs.at)
+            .then(lhs.kind.cmp(&rhs.kind))
+            .then_with(|| lhs.id.cmp(&rhs.id))
+    });
+
+    Timeline {
+        start,
+        end,
+        events,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ci_monitor_core::data::{
+        Deployment, DeploymentStatus, Environment, EnvironmentState, EnvironmentTier, Pipeline,
+        PipelineSource, PipelineStatus, Runner, RunnerProtectionLevel, RunnerType,
+    };
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test::{self, at};
+    use crate::TimelineEventKind;
+
+    #[test]
+    fn event_timeline() {
+        let mut lookup = VecLookup::default();
+        let instance = test::instance(&mut lookup);
+        let project = test::project(&mut lookup, instance, 1, "group/project");
+        // (id, start, end)
+        let pipelines = [(1, -20, -5), (2, 5, 30), (3, 50, 70)].map(|(id, start, end)| {
+            let pipeline = Pipeline::builder()
+                .project(project)
+                .sha("0000000000000000000000000000000000000000")
+                .refname(Some("main".into()))
+                .source(PipelineSource::Push)
+                .status(PipelineStatus::Success)
+                .forge_id(id)
+                .url(format!("pipeline/{}", id))
+                .created_at(at(start))
+                .updated_at(at(end))
+                .started_at(Some(at(start)))
+                .finished_at(Some(at(end)))
+                .build()
+                .unwrap();
+            lookup.store(pipeline)
+        });
+        let environment = Environment::builder()
+            .name("production")
+            .external_url("https://example.com")
+            .state(EnvironmentState::Available)
+            .tier(EnvironmentTier::Production)
+            .forge_id(1)
+            .project(project)
+            .created_at(at(-100))
+            .updated_at(at(-100))
+            .build()
+            .unwrap();
+        let environment = lookup.store(environment);
+        let deployment = Deployment::builder()
+            .pipeline(pipelines[1])
+            .environment(environment)
+            .forge_id(7)
+            .created_at(at(30))
+            .updated_at(at(40))
+            .finished_at(Some(at(40)))
+            .status(DeploymentStatus::Failed)
+            .build()
+            .unwrap();
+        lookup.store(deployment);
+        // (id, online, contacted)
+        let runners = [(1, false, 35), (2, true, 35), (3, false, -30)];
+        for (id, online, contacted) in runners {
+            let runner = Runner::builder()
+                .runner_type(RunnerType::Instance)
+                .protection_level(RunnerProtectionLevel::Any)
+                .description(format!("runner-{}", id))
+                .forge_id(id)
+                .online(online)
+                .contacted_at(Some(at(contacted)))
+                .instance(instance)
+                .build()
+                .unwrap();
+            lookup.store(runner);
+        }
+
+        let timeline = super::event_timeline(&lookup, at(0), at(60));
+
+        let events = timeline
+            .events
+            .iter()
+            .map(|event| (event.at, event.kind, event.summary.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            [
+                (
+                    at(5),
+                    TimelineEventKind::PipelineStarted,
+                    "pipeline 2 started on main",
+                ),
+                (
+                    at(30),
+                    TimelineEventKind::PipelineFinished,
+                    "pipeline 2 finished (success) on main",
+                ),
+                (
+                    at(30),
+                    TimelineEventKind::DeploymentStarted,
+                    "deployment 7 to production started",
+                ),
+                (
+                    at(35),
+                    TimelineEventKind::RunnerOffline,
+                    "runner 1 (runner-1) went offline",
+                ),
+                (
+                    at(40),
+                    TimelineEventKind::DeploymentFinished,
+                    "deployment 7 to production finished (failed)",
+                ),
+                (
+                    at(50),
+                    TimelineEventKind::PipelineStarted,
+                    "pipeline 3 started on main",
+                ),
+            ],
+        );
+        assert_eq!(timeline.events[0].project.as_deref(), Some("group/project"));
+        assert_eq!(timeline.events[2].url.as_deref(), Some("pipeline/2"));
+        assert_eq!(timeline.events[3].project, None);
+
+        let ics = timeline.render_ics();
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(ics.matches("BEGIN:VEVENT\r\n").count(), 6);
+        assert!(ics.contains("UID:pipeline-2-started@ci-monitor\r\n"));
+        assert!(ics.contains("DTSTART:20240101T000500Z\r\n"));
+        assert!(ics.contains("SUMMARY:group/project: pipeline 2 started on main\r\n"));
+        assert!(ics.contains("CATEGORIES:runner_offline\r\n"));
+    }
+
+    #[test]
+    fn ics_folding() {
+        let mut out = String::new();
+        super::ics_line(&mut out, &"x".repeat(160));
+
+        let lines = out.split_terminator("\r\n").collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].len(), 75);
+        assert_eq!(lines[1].len(), 75);
+        assert!(lines[1].starts_with(' '));
+        assert_eq!(lines[2], format!(" {}", "x".repeat(11)));
+    }
+
+    #[test]
+    fn ics_escape() {
+        assert_eq!(super::ics_escape("a, b; c\\d\ne"), "a\\, b\\; c\\\\d\\ne");
+    }
+}
@@ -0,0 +1,348 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+
+use ci_monitor_core::data::{
+    Blob, BlobReference, ContentHash, Pipeline, PipelineVariable, PipelineVariableType,
+    PipelineVariables, Project,
+};
+use ci_monitor_core::Lookup;
+use schemars::JsonSchema;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::AnalysisLookup;
+
+/// Variable name fragments which indicate a secret value.
+const SENSITIVE_NAMES: &[&str] = &["TOKEN", "SECRET", "PASSWORD", "PASSWD", "KEY", "CREDENTIAL"];
+
+/// Whether a variable's value should not be revealed.
+///
+/// Protected variables and those with names suggesting secrets are considered sensitive.
+pub fn is_sensitive_variable(name: &str, variable: &PipelineVariable) -> bool {
+    let name = name.to_uppercase();
+    variable.protected || SENSITIVE_NAMES.iter().any(|frag| name.contains(frag))
+}
+
+/// The state of a pipeline variable for comparison.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct VariableState {
+    /// The value of the variable.
+    ///
+    /// For sensitive variables, this is a hash of the value.
+    pub value: String,
+    /// Whether the value has been replaced by its hash.
+    pub redacted: bool,
+    /// How the variable is made available to jobs.
+    ///
+    /// One of `file` or `string`.
+    #[serde(rename = "type", serialize_with = "crate::serialize::name")]
+    #[schemars(rename = "type", with = "String")]
+    pub type_: PipelineVariableType,
+    /// Whether the variable is protected.
+    pub protected: bool,
+    /// The environment the variable is made available to.
+    pub environment: Option<String>,
+}
+
+impl VariableState {
+    pub(crate) fn new(name: &str, variable: &PipelineVariable) -> Self {
+        let redacted = is_sensitive_variable(name, variable);
+        let value = if redacted {
+            let blob = Blob::new(variable.value.clone().into_bytes());
+            let hash = BlobReference::for_blob(&blob, ContentHash::Sha256);
+            format!("{}:{}", hash.algo().name(), hash.hash())
+        } else {
+            variable.value.clone()
+        };
+
+        Self {
+            value,
+            redacted,
+            type_: variable.type_,
+            protected: variable.protected,
+            environment: variable.environment.clone(),
+        }
+    }
+}
+
+impl fmt::Display for VariableState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.redacted {
+            write!(f, "<{}>", self.value)?;
+        } else {
+            write!(f, "{:?}", self.value)?;
+        }
+        if self.type_ == PipelineVariableType::File {
+            write!(f, " (file)")?;
+        }
+        if self.protected {
+            write!(f, " (protected)")?;
+        }
+        if let Some(environment) = self.environment.as_ref() {
+            write!(f, " (environment: {})", environment)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A difference in a variable between two pipelines.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct VariableChange {
+    /// The name of the variable.
+    pub name: String,
+    /// The variable in the first pipeline.
+    ///
+    /// `None` if the variable was added.
+    pub before: Option<VariableState>,
+    /// The variable in the second pipeline.
+    ///
+    /// `None` if the variable was removed.
+    pub after: Option<VariableState>,
+}
+
+impl fmt::Display for VariableChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.before, &self.after) {
+            (None, Some(after)) => write!(f, "+{} = {}", self.name, after),
+            (Some(before), None) => write!(f, "-{} = {}", self.name, before),
+            (Some(before), Some(after)) => write!(f, "~{}: {} -> {}", self.name, before, after),
+            (None, None) => write!(f, " {}", self.name),
+        }
+    }
+}
+
+/// Compute the differences between two sets of pipeline variables.
+///
+/// Values of sensitive variables (see [`is_sensitive_variable`]) are only compared and reported
+/// by their hash. Changes are sorted by variable name.
+pub fn diff_pipeline_variables(
+    before: &PipelineVariables,
+    after: &PipelineVariables,
+) -> Vec<VariableChange> {
+    let mut names: Vec<&String> = before
+        .variables
+        .keys()
+        .chain(after.variables.keys())
+        .collect();
+    names.sort();
+    names.dedup();
+
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let before = before
+                .variables
+                .get(name)
+                .map(|var| VariableState::new(name, var));
+            let after = after
+                .variables
+                .get(name)
+                .map(|var| VariableState::new(name, var));
+
+            if before == after {
+                None
+            } else {
+                Some(VariableChange {
+                    name: name.clone(),
+                    before,
+                    after,
+                })
+            }
+        })
+        .collect()
+}
+
+/// Errors which may occur when comparing pipeline variables.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum VariableComparisonError {
+    /// A pipeline could not be found.
+    #[error("unknown pipeline: {}", pipeline)]
+    MissingPipeline {
+        /// A description of the pipeline.
+        pipeline: String,
+    },
+    /// The pipelines are for different projects.
+    #[error("pipelines are for different projects: {} vs. {}", before, after)]
+    DifferentProject {
+        /// The project of the first pipeline.
+        before: String,
+        /// The project of the second pipeline.
+        after: String,
+    },
+    /// The pipelines are for different refs.
+    #[error("pipelines are for different refs: {} vs. {}", before, after)]
+    DifferentRef {
+        /// The ref of the first pipeline.
+        before: String,
+        /// The ref of the second pipeline.
+        after: String,
+    },
+}
+
+impl VariableComparisonError {
+    fn missing_pipeline<I>(idx: &I) -> Self
+    where
+        I: fmt::Debug,
+    {
+        Self::MissingPipeline {
+            pipeline: format!("{:?}", idx),
+        }
+    }
+}
+
+/// Compare the variables of two pipelines for the same project and ref.
+pub fn compare_pipeline_variables<L>(
+    storage: &L,
+    before: &<L as Lookup<Pipeline<L>>>::Index,
+    after: &<L as Lookup<Pipeline<L>>>::Index,
+) -> Result<Vec<VariableChange>, VariableComparisonError>
+where
+    L: AnalysisLookup<L>,
+{
+    let before = <L as Lookup<Pipeline<L>>>::lookup(storage, before)
+        .ok_or_else(|| VariableComparisonError::missing_pipeline(before))?;
+    let after = <L as Lookup<Pipeline<L>>>::lookup(storage, after)
+        .ok_or_else(|| VariableComparisonError::missing_pipeline(after))?;
+
+    let project_url = |pipeline: &Pipeline<L>| {
+        <L as Lookup<Project<L>>>::lookup(storage, &pipeline.project)
+            .map(|project| project.url.clone())
+            .unwrap_or_default()
+    };
+    let before_project = project_url(before);
+    let after_project = project_url(after);
+    if before_project != after_project {
+        return Err(VariableComparisonError::DifferentProject {
+            before: before_project,
+            after: after_project,
+        });
+    }
+
+    let before_ref = before.refname.clone().unwrap_or_default();
+    let after_ref = after.refname.clone().unwrap_or_default();
+    if before_ref != after_ref {
+        return Err(VariableComparisonError::DifferentRef {
+            before: before_ref,
+            after: after_ref,
+        });
+    }
+
+    Ok(diff_pipeline_variables(&before.variables, &after.variables))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use ci_monitor_core::data::{
+        Pipeline, PipelineSource, PipelineStatus, PipelineVariable, PipelineVariableType,
+        PipelineVariables,
+    };
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    use crate::test;
+    use crate::VariableComparisonError;
+
+    fn variables(vars: &[(&str, &str, bool)]) -> PipelineVariables {
+        vars.iter()
+            .map(|&(name, value, protected)| {
+                let var = PipelineVariable::builder()
+                    .value(value)
+                    .type_(PipelineVariableType::String)
+                    .protected(protected)
+                    .build()
+                    .unwrap();
+                (name.into(), var)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn diff_pipeline_variables() {
+        let before = variables(&[
+            ("BUILD_TYPE", "Debug", false),
+            ("DEPLOY_TOKEN", "hunter2", false),
+            ("REMOVED", "value", false),
+            ("SAME", "value", false),
+            ("SIGNING", "old", true),
+        ]);
+        let after = variables(&[
+            ("ADDED", "value", false),
+            ("BUILD_TYPE", "Release", false),
+            ("DEPLOY_TOKEN", "hunter2", false),
+            ("SAME", "value", false),
+            ("SIGNING", "new", true),
+        ]);
+
+        let changes = super::diff_pipeline_variables(&before, &after);
+        let names = changes
+            .iter()
+            .map(|change| change.name.as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(names, ["ADDED", "BUILD_TYPE", "REMOVED", "SIGNING"]);
+        assert_eq!(changes[0].to_string(), "+ADDED = \"value\"");
+        assert_eq!(
+            changes[1].to_string(),
+            "~BUILD_TYPE: \"Debug\" -> \"Release\"",
+        );
+        assert_eq!(changes[2].to_string(), "-REMOVED = \"value\"");
+
+        let signing = &changes[3];
+        let before = signing.before.as_ref().unwrap();
+        let after = signing.after.as_ref().unwrap();
+        assert!(before.redacted);
+        assert!(before.value.starts_with("sha256:"));
+        assert_ne!(before.value, after.value);
+        assert!(!signing.to_string().contains("old"));
+        assert!(!signing.to_string().contains("new"));
+    }
+
+    #[test]
+    fn compare_pipeline_variables() {
+        let mut lookup = VecLookup::default();
+        let instance = test::instance(&mut lookup);
+        let project = test::project(&mut lookup, instance, 0, "project");
+
+        let created_at = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let pipelines = [
+            (0, "refs/heads/main", "Debug"),
+            (1, "refs/heads/main", "Release"),
+            (2, "refs/heads/topic", "Release"),
+        ];
+        let pipelines = pipelines.map(|(id, refname, build_type)| {
+            let pipeline = Pipeline::builder()
+                .project(project)
+                .sha("0000000000000000000000000000000000000000")
+                .refname(Some(refname.into()))
+                .variables(variables(&[("BUILD_TYPE", build_type, false)]))
+                .source(PipelineSource::Push)
+                .status(PipelineStatus::Success)
+                .forge_id(id)
+                .url("url")
+                .created_at(created_at)
+                .updated_at(created_at)
+                .build()
+                .unwrap();
+            lookup.store(pipeline)
+        });
+
+        let changes =
+            super::compare_pipeline_variables(&lookup, &pipelines[0], &pipelines[1]).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].name, "BUILD_TYPE");
+
+        let err =
+            super::compare_pipeline_variables(&lookup, &pipelines[0], &pipelines[2]).unwrap_err();
+        assert!(matches!(err, VariableComparisonError::DifferentRef { .. }));
+    }
+}
@@ -8,36 +8,60 @@
 //!
 //! With some convenience methods for managing them.
 
+mod artifact_dependencies;
+mod audit_entry;
 mod blob;
+mod crawl_session;
 mod deployment;
+mod elapsed;
 mod environment;
+mod fidelity;
 mod instance;
 mod job;
 mod job_artifact;
 mod merge_request;
+mod named_enum;
 mod pipeline;
 mod pipeline_schedule;
 mod pipeline_variables;
 mod project;
+mod push;
+mod release;
 mod runner;
 mod runner_host;
 mod user;
 
+pub use artifact_dependencies::ArtifactDependencies;
+
+pub use audit_entry::AuditEntry;
+pub use audit_entry::AuditEntryBuilder;
+pub use audit_entry::AuditEntryBuilderError;
+pub use audit_entry::AuditTrigger;
+
 pub use blob::Blob;
 pub use blob::BlobReference;
 pub use blob::ContentHash;
 
+pub use crawl_session::ApiUsage;
+pub use crawl_session::CrawlSession;
+pub use crawl_session::CrawlSessionBuilder;
+pub use crawl_session::CrawlSessionBuilderError;
+
 pub use deployment::Deployment;
 pub use deployment::DeploymentBuilder;
 pub use deployment::DeploymentBuilderError;
 pub use deployment::DeploymentStatus;
 
+pub use elapsed::Elapsed;
+
 pub use environment::Environment;
 pub use environment::EnvironmentBuilder;
 pub use environment::EnvironmentBuilderError;
 pub use environment::EnvironmentState;
 pub use environment::EnvironmentTier;
 
+pub use fidelity::DataFidelity;
+
 pub use instance::Instance;
 pub use instance::InstanceBuilder;
 pub use instance::InstanceBuilderError;
@@ -45,6 +69,7 @@ pub use instance::InstanceBuilderError;
 pub use job::Job;
 pub use job::JobBuilder;
 pub use job::JobBuilderError;
+pub use job::JobSection;
 pub use job::JobState;
 
 pub use job_artifact::ArtifactExpiration;
@@ -75,16 +100,30 @@ pub use pipeline_variables::PipelineVariableBuilderError;
 pub use pipeline_variables::PipelineVariableType;
 pub use pipeline_variables::PipelineVariables;
 
+pub use project::PreviousDefaultBranch;
 pub use project::Project;
 pub use project::ProjectBuilder;
 pub use project::ProjectBuilderError;
 
+pub use push::Push;
+pub use push::PushBuilder;
+pub use push::PushBuilderError;
+
+pub use release::Release;
+pub use release::ReleaseAsset;
+pub use release::ReleaseAssetBuilder;
+pub use release::ReleaseAssetBuilderError;
+pub use release::ReleaseBuilder;
+pub use release::ReleaseBuilderError;
+
 pub use runner::Runner;
 pub use runner::RunnerBuilder;
 pub use runner::RunnerBuilderError;
 pub use runner::RunnerProtectionLevel;
 pub use runner::RunnerType;
 
+pub use runner_host::AutoscaledInstance;
+pub use runner_host::AutoscalerEventKind;
 pub use runner_host::RunnerHost;
 pub use runner_host::RunnerHostBuilder;
 pub use runner_host::RunnerHostBuilderError;
@@ -0,0 +1,61 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+
+/// The stage which runs before all others.
+const PRE_STAGE: &str = ".pre";
+/// The stage which runs after all others.
+const POST_STAGE: &str = ".post";
+
+/// How jobs within a pipeline consume the artifacts of other jobs.
+///
+/// Jobs without an explicit list consume the artifacts of all jobs in earlier stages.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ArtifactDependencies {
+    /// The stages of the pipeline in order.
+    pub stages: Vec<String>,
+    /// The names of jobs whose artifacts a job consumes keyed by the name of the job.
+    pub jobs: BTreeMap<String, Vec<String>>,
+}
+
+impl ArtifactDependencies {
+    /// The position of a stage within the pipeline.
+    ///
+    /// The `.pre` and `.post` stages are always the first and last stages.
+    pub fn stage_index(&self, stage: &str) -> Option<usize> {
+        if stage == PRE_STAGE {
+            Some(0)
+        } else if stage == POST_STAGE {
+            Some(self.stages.len() + 1)
+        } else {
+            self.stages
+                .iter()
+                .position(|name| name == stage)
+                .map(|idx| idx + 1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::ArtifactDependencies;
+
+    #[test]
+    fn stage_index() {
+        let deps = ArtifactDependencies {
+            stages: vec!["build".into(), "test".into()],
+            ..Default::default()
+        };
+
+        assert_eq!(deps.stage_index(".pre"), Some(0));
+        assert_eq!(deps.stage_index("build"), Some(1));
+        assert_eq!(deps.stage_index("test"), Some(2));
+        assert_eq!(deps.stage_index(".post"), Some(3));
+        assert_eq!(deps.stage_index("deploy"), None);
+    }
+}
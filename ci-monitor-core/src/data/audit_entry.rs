@@ -0,0 +1,110 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use chrono::{DateTime, Utc};
+use derive_builder::Builder;
+
+use crate::data::named_enum::named_enum;
+
+named_enum! {
+    /// How a manual action reached the monitor.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[non_exhaustive]
+    pub enum AuditTrigger {
+        /// The command line.
+        Cli => "cli",
+        /// A webhook delivered by a forge.
+        Webhook => "webhook",
+        /// A request to an API of the monitor.
+        Api => "api",
+    }
+}
+
+/// A record of a manual action taken on a store.
+///
+/// Crawls are recorded as crawl sessions; entries record actions requested outside of crawls
+/// (e.g., refreshes or deletions) so that stores shared by several operators can trace them.
+#[derive(Debug, Builder, Clone)]
+#[builder(pattern = "owned")]
+#[non_exhaustive]
+pub struct AuditEntry {
+    /// When the action was taken.
+    pub at: DateTime<Utc>,
+    /// Who requested the action.
+    #[builder(setter(into))]
+    pub operator: String,
+    /// How the action was requested.
+    pub trigger: AuditTrigger,
+    /// The action which was taken (e.g., `refresh` or `delete`).
+    #[builder(setter(into))]
+    pub action: String,
+    /// A description of the entity the action was taken on.
+    #[builder(default, setter(into))]
+    pub target: String,
+    /// The names of the tasks injected for the action.
+    #[builder(default)]
+    pub tasks: Vec<String>,
+    /// The number of entities changed by the action.
+    ///
+    /// For actions which inject tasks, this is the number of tasks executed.
+    #[builder(default)]
+    pub affected: u64,
+
+    /// A unique ID for the entry.
+    ///
+    /// Entries are numbered in the order they were recorded.
+    pub unique_id: u64,
+}
+
+impl AuditEntry {
+    /// Create a builder for the structure.
+    pub fn builder() -> AuditEntryBuilder {
+        AuditEntryBuilder::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use crate::data::{AuditEntry, AuditEntryBuilderError, AuditTrigger};
+
+    #[test]
+    fn operator_is_required() {
+        let err = AuditEntry::builder()
+            .at(Utc::now())
+            .trigger(AuditTrigger::Cli)
+            .action("refresh")
+            .unique_id(0)
+            .build()
+            .unwrap_err();
+        crate::test::assert_missing_field!(err, AuditEntryBuilderError, "operator");
+    }
+
+    #[test]
+    fn action_is_required() {
+        let err = AuditEntry::builder()
+            .at(Utc::now())
+            .operator("operator")
+            .trigger(AuditTrigger::Cli)
+            .unique_id(0)
+            .build()
+            .unwrap_err();
+        crate::test::assert_missing_field!(err, AuditEntryBuilderError, "action");
+    }
+
+    #[test]
+    fn sufficient_fields() {
+        AuditEntry::builder()
+            .at(Utc::now())
+            .operator("operator")
+            .trigger(AuditTrigger::Cli)
+            .action("refresh")
+            .unique_id(0)
+            .build()
+            .unwrap();
+    }
+}
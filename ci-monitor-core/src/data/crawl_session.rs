@@ -0,0 +1,195 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use chrono::{DateTime, Utc};
+use derive_builder::Builder;
+use perfect_derive::perfect_derive;
+
+use crate::data::Instance;
+use crate::Lookup;
+
+/// API requests made to an instance during an hour of a crawl.
+#[perfect_derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ApiUsage<L>
+where
+    L: Lookup<Instance>,
+{
+    /// The instance the requests were made to.
+    pub instance: <L as Lookup<Instance>>::Index,
+    /// The start of the hour.
+    pub hour: DateTime<Utc>,
+    /// The number of requests made.
+    pub requests: u64,
+}
+
+impl<L> ApiUsage<L>
+where
+    L: Lookup<Instance>,
+{
+    /// Record API usage for an hour.
+    pub fn new(
+        instance: <L as Lookup<Instance>>::Index,
+        hour: DateTime<Utc>,
+        requests: u64,
+    ) -> Self {
+        Self {
+            instance,
+            hour,
+            requests,
+        }
+    }
+}
+
+/// A crawl of one or more forge instances.
+///
+/// Sessions record how fresh and complete the stored data is.
+#[derive(Builder)]
+#[perfect_derive(Debug, Clone)]
+#[builder(pattern = "owned")]
+#[non_exhaustive]
+pub struct CrawlSession<L>
+where
+    L: Lookup<Instance>,
+{
+    /// When the crawl started.
+    pub started_at: DateTime<Utc>,
+    /// When the crawl ended.
+    ///
+    /// `None` if the crawl is still running.
+    #[builder(default)]
+    pub finished_at: Option<DateTime<Utc>>,
+
+    // Task metadata.
+    /// The number of tasks executed.
+    #[builder(default)]
+    pub tasks_executed: u64,
+    /// The number of tasks which failed.
+    #[builder(default)]
+    pub tasks_failed: u64,
+    /// The number of tasks which were canceled before running.
+    #[builder(default)]
+    pub tasks_canceled: u64,
+    /// The instances which were crawled.
+    #[builder(default)]
+    pub instances: Vec<<L as Lookup<Instance>>::Index>,
+    /// API requests made during the crawl by instance and hour.
+    #[builder(default)]
+    pub api_usage: Vec<ApiUsage<L>>,
+
+    /// The revision of the store produced by the crawl.
+    ///
+    /// Each crawl saving a store produces a new, larger revision.
+    pub revision: u64,
+}
+
+impl<L> CrawlSession<L>
+where
+    L: Lookup<Instance>,
+{
+    /// Create a builder for the structure.
+    pub fn builder() -> CrawlSessionBuilder<L> {
+        CrawlSessionBuilder::default()
+    }
+
+    /// The total number of API requests made during the crawl.
+    pub fn api_requests(&self) -> u64 {
+        self.api_usage.iter().map(|usage| usage.requests).sum()
+    }
+
+    /// Whether the crawl finished without any failed or canceled tasks.
+    pub fn is_complete(&self) -> bool {
+        self.finished_at.is_some() && self.tasks_failed == 0 && self.tasks_canceled == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use crate::data::{ApiUsage, CrawlSession, CrawlSessionBuilderError, Instance};
+    use crate::Lookup;
+
+    use crate::test::TestLookup;
+
+    #[test]
+    fn started_at_is_required() {
+        let err = CrawlSession::<TestLookup>::builder()
+            .revision(0)
+            .build()
+            .unwrap_err();
+        crate::test::assert_missing_field!(err, CrawlSessionBuilderError, "started_at");
+    }
+
+    #[test]
+    fn revision_is_required() {
+        let err = CrawlSession::<TestLookup>::builder()
+            .started_at(Utc::now())
+            .build()
+            .unwrap_err();
+        crate::test::assert_missing_field!(err, CrawlSessionBuilderError, "revision");
+    }
+
+    #[test]
+    fn sufficient_fields() {
+        CrawlSession::<TestLookup>::builder()
+            .started_at(Utc::now())
+            .revision(0)
+            .build()
+            .unwrap();
+    }
+
+    #[test]
+    fn is_complete() {
+        let session = CrawlSession::<TestLookup>::builder()
+            .started_at(Utc::now())
+            .revision(0)
+            .build()
+            .unwrap();
+        assert!(!session.is_complete());
+
+        let finished = CrawlSession::<TestLookup>::builder()
+            .started_at(Utc::now())
+            .finished_at(Some(Utc::now()))
+            .revision(0)
+            .build()
+            .unwrap();
+        assert!(finished.is_complete());
+
+        let failed = CrawlSession::<TestLookup>::builder()
+            .started_at(Utc::now())
+            .finished_at(Some(Utc::now()))
+            .tasks_failed(1)
+            .revision(0)
+            .build()
+            .unwrap();
+        assert!(!failed.is_complete());
+    }
+
+    #[test]
+    fn api_requests() {
+        let mut lookup = TestLookup::default();
+        let instance = Instance::builder()
+            .unique_id(0)
+            .forge("forge")
+            .url("url")
+            .build()
+            .unwrap();
+        let idx = lookup.store(instance);
+        let hour = Utc::now();
+
+        let session = CrawlSession::<TestLookup>::builder()
+            .started_at(hour)
+            .api_usage(vec![
+                ApiUsage::new(idx.clone(), hour, 10),
+                ApiUsage::new(idx, hour, 5),
+            ])
+            .revision(0)
+            .build()
+            .unwrap();
+        assert_eq!(session.api_requests(), 15);
+    }
+}
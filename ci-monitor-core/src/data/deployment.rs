@@ -8,25 +8,28 @@ use chrono::{DateTime, Utc};
 use derive_builder::Builder;
 use perfect_derive::perfect_derive;
 
+use crate::data::named_enum::named_enum;
 use crate::data::{Environment, Instance, MergeRequest, Pipeline, PipelineSchedule, Project, User};
 use crate::Lookup;
 
-/// The status of a deployment.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[non_exhaustive]
-pub enum DeploymentStatus {
-    /// The deployment has been created.
-    Created,
-    /// The deployment is running.
-    Running,
-    /// The deployment completed successfully.
-    Success,
-    /// The deployment completed with failure.
-    Failed,
-    /// The deployment was canceled.
-    Canceled,
-    /// The deployment is blocked.
-    Blocked,
+named_enum! {
+    /// The status of a deployment.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[non_exhaustive]
+    pub enum DeploymentStatus {
+        /// The deployment has been created.
+        Created => "created",
+        /// The deployment is running.
+        Running => "running",
+        /// The deployment completed successfully.
+        Success => "success",
+        /// The deployment completed with failure.
+        Failed => "failed",
+        /// The deployment was canceled.
+        Canceled => "canceled",
+        /// The deployment is blocked.
+        Blocked => "blocked",
+    }
 }
 
 /// A deployment into an environment.
@@ -70,6 +73,11 @@ where
     /// When the monitoring tool last updated this information.
     #[builder(default = "Utc::now()", setter(skip))]
     pub cim_refreshed_at: DateTime<Utc>,
+    /// When the entity was marked as deleted.
+    ///
+    /// Deleted entities remain in the store until purged.
+    #[builder(default, setter(skip))]
+    pub cim_deleted_at: Option<DateTime<Utc>>,
 }
 
 impl<L> Deployment<L>
@@ -0,0 +1,94 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use chrono::Duration;
+
+/// A length of time which has passed.
+///
+/// Forges report durations as fractional seconds. Keeping them as a non-negative `Duration`
+/// instead of a bare number keeps the unit with the value and rejects values which cannot be a
+/// duration (negative, NaN, or infinite).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Elapsed(Duration);
+
+impl Elapsed {
+    /// The time elapsed over a duration.
+    ///
+    /// Returns `None` if the duration is negative.
+    pub fn new(duration: Duration) -> Option<Self> {
+        (duration >= Duration::zero()).then_some(Self(duration))
+    }
+
+    /// The time elapsed over a number of seconds.
+    ///
+    /// Precision is kept to the millisecond. Returns `None` if the number of seconds is negative,
+    /// not finite, or too large to represent.
+    pub fn from_seconds(seconds: f64) -> Option<Self> {
+        if !seconds.is_finite() || seconds < 0. {
+            return None;
+        }
+
+        let milliseconds = (seconds * 1000.).round();
+        if milliseconds > i64::MAX as f64 {
+            return None;
+        }
+        Duration::try_milliseconds(milliseconds as i64).map(Self)
+    }
+
+    /// The elapsed time as a duration.
+    pub fn duration(self) -> Duration {
+        self.0
+    }
+
+    /// The elapsed time in (fractional) seconds.
+    pub fn as_seconds(self) -> f64 {
+        self.0.num_milliseconds() as f64 / 1000.
+    }
+
+    /// The elapsed time in (fractional) hours.
+    pub fn as_hours(self) -> f64 {
+        self.as_seconds() / 3600.
+    }
+}
+
+impl From<Elapsed> for Duration {
+    fn from(elapsed: Elapsed) -> Self {
+        elapsed.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use crate::data::Elapsed;
+
+    #[test]
+    fn from_seconds() {
+        let elapsed = Elapsed::from_seconds(1.5).unwrap();
+        assert_eq!(elapsed.duration(), Duration::milliseconds(1500));
+        assert_eq!(elapsed.as_seconds(), 1.5);
+        assert_eq!(Elapsed::from_seconds(5400.).unwrap().as_hours(), 1.5);
+        assert_eq!(Elapsed::from_seconds(0.).unwrap(), Elapsed::default());
+    }
+
+    #[test]
+    fn invalid_seconds() {
+        assert_eq!(Elapsed::from_seconds(-1.), None);
+        assert_eq!(Elapsed::from_seconds(f64::NAN), None);
+        assert_eq!(Elapsed::from_seconds(f64::INFINITY), None);
+        assert_eq!(Elapsed::from_seconds(1e300), None);
+    }
+
+    #[test]
+    fn from_duration() {
+        assert_eq!(
+            Elapsed::new(Duration::minutes(2)).map(Elapsed::as_seconds),
+            Some(120.),
+        );
+        assert_eq!(Elapsed::new(Duration::minutes(-2)), None);
+    }
+}
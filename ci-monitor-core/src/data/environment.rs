@@ -8,35 +8,40 @@ use chrono::{DateTime, Utc};
 use derive_builder::Builder;
 use perfect_derive::perfect_derive;
 
+use crate::data::named_enum::named_enum;
 use crate::data::{Instance, Project};
 use crate::Lookup;
 
-/// The state of an environment.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[non_exhaustive]
-pub enum EnvironmentState {
-    /// The environment is available.
-    Available,
-    /// The environment is shutting down.
-    Stopping,
-    /// The environment is stopped.
-    Stopped,
+named_enum! {
+    /// The state of an environment.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[non_exhaustive]
+    pub enum EnvironmentState {
+        /// The environment is available.
+        Available => "available",
+        /// The environment is shutting down.
+        Stopping => "stopping",
+        /// The environment is stopped.
+        Stopped => "stopped",
+    }
 }
 
-/// The environment tier.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[non_exhaustive]
-pub enum EnvironmentTier {
-    /// An environment intended for production.
-    Production,
-    /// An environment for staging before production.
-    Staging,
-    /// An environment for testing.
-    Testing,
-    /// An environment for development.
-    Development,
-    /// An environment for other purposes.
-    Other,
+named_enum! {
+    /// The environment tier.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[non_exhaustive]
+    pub enum EnvironmentTier {
+        /// An environment intended for production.
+        Production => "production",
+        /// An environment for staging before production.
+        Staging => "staging",
+        /// An environment for testing.
+        Testing => "testing",
+        /// An environment for development.
+        Development => "development",
+        /// An environment for other purposes.
+        Other => "other",
+    }
 }
 
 /// An environment into which deployments may be made.
@@ -81,6 +86,11 @@ where
     /// When the monitoring tool last updated this information.
     #[builder(default = "Utc::now()", setter(skip))]
     pub cim_refreshed_at: DateTime<Utc>,
+    /// When the entity was marked as deleted.
+    ///
+    /// Deleted entities remain in the store until purged.
+    #[builder(default, setter(skip))]
+    pub cim_deleted_at: Option<DateTime<Utc>>,
 }
 
 impl<L> Environment<L>
@@ -0,0 +1,23 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::data::named_enum::named_enum;
+
+named_enum! {
+    /// How trustworthy stored data is.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+    #[non_exhaustive]
+    pub enum DataFidelity {
+        /// The data was retrieved from the forge directly.
+        #[default]
+        Forge => "forge",
+        /// The data was inferred from a notification (e.g., an email).
+        ///
+        /// Fields may be missing or approximated and should be refreshed from the forge when
+        /// possible.
+        Notification => "notification",
+    }
+}
@@ -4,40 +4,74 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use derive_builder::Builder;
 use perfect_derive::perfect_derive;
 
+use crate::data::named_enum::named_enum;
 use crate::data::{
-    Deployment, Environment, Instance, MergeRequest, Pipeline, PipelineSchedule, PipelineVariables,
-    Project, Runner, RunnerHost, User,
+    Deployment, Elapsed, Environment, Instance, MergeRequest, Pipeline, PipelineSchedule,
+    PipelineVariables, Project, Runner, RunnerHost, User,
 };
 use crate::Lookup;
 
-/// The state of a job.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+named_enum! {
+    /// The state of a job.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[non_exhaustive]
+    pub enum JobState {
+        /// The job was created.
+        Created => "created",
+        /// The job is waiting for a runner.
+        Pending => "pending",
+        /// The job is running.
+        Running => "running",
+        /// The job failed.
+        Failed => "failed",
+        /// The job completed successfully.
+        Success => "success",
+        /// The job was canceled.
+        Canceled => "canceled",
+        /// The job was skipped.
+        Skipped => "skipped",
+        /// The job is waiting for a resource.
+        WaitingForResource => "waiting_for_resource",
+        /// The job is waiting for manual interaction.
+        Manual => "manual",
+        /// The job is scheduled to start in the future.
+        Scheduled => "scheduled",
+    }
+}
+
+/// A timed section of a job's log.
+#[derive(Debug, Clone, PartialEq, Eq)]
 #[non_exhaustive]
-pub enum JobState {
-    /// The job was created.
-    Created,
-    /// The job is waiting for a runner.
-    Pending,
-    /// The job is running.
-    Running,
-    /// The job failed.
-    Failed,
-    /// The job completed successfully.
-    Success,
-    /// The job was canceled.
-    Canceled,
-    /// The job was skipped.
-    Skipped,
-    /// The job is waiting for a resource.
-    WaitingForResource,
-    /// The job is waiting for manual interaction.
-    Manual,
-    /// The job is scheduled to start in the future.
-    Scheduled,
+pub struct JobSection {
+    /// The name of the section.
+    pub name: String,
+    /// When the section started.
+    pub started_at: DateTime<Utc>,
+    /// When the section finished.
+    pub finished_at: DateTime<Utc>,
+}
+
+impl JobSection {
+    /// Create a new section.
+    pub fn new<N>(name: N, started_at: DateTime<Utc>, finished_at: DateTime<Utc>) -> Self
+    where
+        N: Into<String>,
+    {
+        Self {
+            name: name.into(),
+            started_at,
+            finished_at,
+        }
+    }
+
+    /// How long the section took.
+    pub fn duration(&self) -> Duration {
+        self.finished_at - self.started_at
+    }
 }
 
 /// A job within a pipeline.
@@ -93,13 +127,22 @@ where
     pub erased_at: Option<DateTime<Utc>>,
     /// How long the job was queued.
     #[builder(default)]
-    pub queued_duration: Option<f64>,
+    pub queued_duration: Option<Elapsed>,
     /// The runner for the job.
     #[builder(default)]
     pub runner: Option<<L as Lookup<Runner<L>>>::Index>,
     /// The deployment the job publishes to.
     #[builder(default)]
     pub deployment: Option<<L as Lookup<Deployment<L>>>::Index>,
+    /// Whether the job waited for manual interaction before starting.
+    #[builder(default)]
+    pub manual: bool,
+    /// When the job was started manually.
+    #[builder(default)]
+    pub played_at: Option<DateTime<Utc>>,
+    /// The user who started the job manually.
+    #[builder(default)]
+    pub played_by: Option<<L as Lookup<User<L>>>::Index>,
 
     // Forge metadata.
     /// The ID of the job.
@@ -117,6 +160,11 @@ where
     /// The coverage reported by the job.
     #[builder(default)]
     pub coverage: Option<f64>,
+    /// Timed sections of the job's log.
+    ///
+    /// Empty until the log has been fetched.
+    #[builder(default)]
+    pub sections: Vec<JobSection>,
 
     // Monitoring metadata.
     /// When the monitoring tool first fetched information.
@@ -125,6 +173,11 @@ where
     /// When the monitoring tool last updated this information.
     #[builder(default = "Utc::now()", setter(skip))]
     pub cim_refreshed_at: DateTime<Utc>,
+    /// When the entity was marked as deleted.
+    ///
+    /// Deleted entities remain in the store until purged.
+    #[builder(default, setter(skip))]
+    pub cim_deleted_at: Option<DateTime<Utc>>,
 }
 
 impl<L> Job<L>
@@ -144,11 +197,16 @@ where
     pub fn builder() -> JobBuilder<L> {
         JobBuilder::default()
     }
+
+    /// How long a manual job waited to be started.
+    pub fn manual_wait(&self) -> Option<Duration> {
+        self.played_at.map(|played_at| played_at - self.created_at)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use chrono::Utc;
+    use chrono::{Duration, Utc};
 
     use crate::data::{
         Instance, Job, JobBuilderError, JobState, Pipeline, PipelineSource, PipelineStatus,
@@ -312,4 +370,30 @@ mod tests {
             .build()
             .unwrap();
     }
+
+    #[test]
+    fn manual_wait() {
+        let mut lookup = TestLookup::default();
+        let proj = project(&mut lookup);
+        let user = user(proj.instance.clone());
+        let user_idx = lookup.store(user);
+        let proj_idx = lookup.store(proj);
+        let pipeline = pipeline(proj_idx.clone());
+        let pipeline_idx = lookup.store(pipeline);
+
+        let created_at = Utc::now();
+        let mut job = Job::<TestLookup>::builder()
+            .user(user_idx)
+            .state(JobState::Manual)
+            .created_at(created_at)
+            .forge_id(0)
+            .pipeline(pipeline_idx)
+            .manual(true)
+            .build()
+            .unwrap();
+        assert_eq!(job.manual_wait(), None);
+
+        job.played_at = Some(created_at + Duration::minutes(5));
+        assert_eq!(job.manual_wait(), Some(Duration::minutes(5)));
+    }
 }
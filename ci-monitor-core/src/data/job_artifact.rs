@@ -11,26 +11,38 @@ use perfect_derive::perfect_derive;
 
 use chrono::{DateTime, Utc};
 
+use crate::data::named_enum::named_enum;
 use crate::data::{
     BlobReference, Deployment, Environment, Instance, Job, MergeRequest, Pipeline,
     PipelineSchedule, Project, Runner, RunnerHost, User,
 };
 use crate::Lookup;
 
-/// The state of an artifact within the monitoring infrastructure.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[non_exhaustive]
-pub enum ArtifactState {
-    /// The state is unknown.
-    Unknown,
-    /// The artifact is pending.
-    Pending,
-    /// The artifact has expired from the forge.
-    Expired,
-    /// The artifact is present on the forge.
-    Present,
-    /// The artifact is stored in local persistence.
-    Stored,
+named_enum! {
+    /// The state of an artifact within the monitoring infrastructure.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[non_exhaustive]
+    pub enum ArtifactState {
+        /// The state is unknown.
+        Unknown => "unknown",
+        /// The artifact is pending.
+        Pending => "pending",
+        /// The artifact has expired from the forge.
+        Expired => "expired",
+        /// The artifact is present on the forge.
+        Present => "present",
+        /// The artifact is stored in local persistence.
+        Stored => "stored",
+        /// The artifact has been moved to cold persistence.
+        Archived => "archived",
+    }
+}
+
+impl ArtifactState {
+    /// Whether the contents of the artifact are available from blob persistence.
+    pub fn is_stored(self) -> bool {
+        matches!(self, Self::Stored | Self::Archived)
+    }
 }
 
 /// A classification of an artifact.
@@ -4,23 +4,26 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use derive_builder::Builder;
 use perfect_derive::perfect_derive;
 
+use crate::data::named_enum::named_enum;
 use crate::data::{Instance, Project, User};
 use crate::Lookup;
 
-/// The status of a merge request.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[non_exhaustive]
-pub enum MergeRequestStatus {
-    /// The merge request is open.
-    Open,
-    /// The merge request has been closed without merging.
-    Closed,
-    /// The merge request has been merged.
-    Merged,
+named_enum! {
+    /// The status of a merge request.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[non_exhaustive]
+    pub enum MergeRequestStatus {
+        /// The merge request is open.
+        Open => "open",
+        /// The merge request has been closed without merging.
+        Closed => "closed",
+        /// The merge request has been merged.
+        Merged => "merged",
+    }
 }
 
 /// A merge request.
@@ -67,6 +70,22 @@ where
     /// The URL of the pipeline webpage.
     #[builder(setter(into))]
     pub url: String,
+    /// When the merge request was created.
+    #[builder(default)]
+    pub created_at: Option<DateTime<Utc>>,
+    /// When the merge request was merged.
+    #[builder(default)]
+    pub merged_at: Option<DateTime<Utc>>,
+
+    // Review metadata.
+    /// When someone other than the author first commented on the merge request.
+    #[builder(default)]
+    pub first_review_at: Option<DateTime<Utc>>,
+    /// When the merge request was last approved.
+    ///
+    /// `None` if the merge request is not approved.
+    #[builder(default)]
+    pub approved_at: Option<DateTime<Utc>>,
 
     // Monitoring metadata.
     /// When the monitoring tool first fetched information.
@@ -75,6 +94,11 @@ where
     /// When the monitoring tool last updated this information.
     #[builder(default = "Utc::now()", setter(skip))]
     pub cim_refreshed_at: DateTime<Utc>,
+    /// When the entity was marked as deleted.
+    ///
+    /// Deleted entities remain in the store until purged.
+    #[builder(default, setter(skip))]
+    pub cim_deleted_at: Option<DateTime<Utc>>,
 }
 
 impl<L> MergeRequest<L>
@@ -87,10 +111,22 @@ where
     pub fn builder() -> MergeRequestBuilder<L> {
         MergeRequestBuilder::default()
     }
+
+    /// How long the merge request waited for its first review.
+    pub fn review_latency(&self) -> Option<Duration> {
+        Some(self.first_review_at? - self.created_at?)
+    }
+
+    /// How long the merge request took to be merged.
+    pub fn time_to_merge(&self) -> Option<Duration> {
+        Some(self.merged_at? - self.created_at?)
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use chrono::{Duration, Utc};
+
     use crate::data::{
         Instance, MergeRequest, MergeRequestBuilderError, MergeRequestStatus, Project, User,
     };
@@ -279,4 +315,33 @@ mod tests {
             .build()
             .unwrap();
     }
+
+    #[test]
+    fn latencies() {
+        let mut lookup = TestLookup::default();
+        let proj = project(&mut lookup);
+        let user = user(proj.instance.clone());
+        let proj_idx = lookup.store(proj);
+        let user_idx = lookup.store(user);
+
+        let created_at = Utc::now();
+        let mut merge_request = MergeRequest::<TestLookup>::builder()
+            .id(0)
+            .source_project(proj_idx.clone())
+            .target_project(proj_idx)
+            .forge_id(0)
+            .state(MergeRequestStatus::Open)
+            .author(user_idx)
+            .url("url")
+            .created_at(Some(created_at))
+            .build()
+            .unwrap();
+        assert_eq!(merge_request.review_latency(), None);
+        assert_eq!(merge_request.time_to_merge(), None);
+
+        merge_request.first_review_at = Some(created_at + Duration::hours(2));
+        merge_request.merged_at = Some(created_at + Duration::hours(30));
+        assert_eq!(merge_request.review_latency(), Some(Duration::hours(2)));
+        assert_eq!(merge_request.time_to_merge(), Some(Duration::hours(30)));
+    }
 }
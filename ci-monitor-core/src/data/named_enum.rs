@@ -0,0 +1,76 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/// Declare an enumeration of unit variants with stable names.
+///
+/// Each variant is given the name used to refer to it outside of the process (e.g., in stores).
+/// The enumeration gains an `ALL` constant listing its variants along with `name` and `from_name`
+/// methods so that consumers never need to keep their own tables in sync with the variants.
+macro_rules! named_enum {
+    (
+        $(#[$attr:meta])*
+        $vis:vis enum $enum:ident {
+            $(
+                $(#[$variant_attr:meta])*
+                $variant:ident => $name:literal,
+            )*
+        }
+    ) => {
+        $(#[$attr])*
+        $vis enum $enum {
+            $(
+                $(#[$variant_attr])*
+                $variant,
+            )*
+        }
+
+        impl $enum {
+            /// All variants.
+            pub const ALL: &'static [Self] = &[$(Self::$variant,)*];
+
+            /// The name of the variant.
+            ///
+            /// Names are stable and may be persisted.
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Self::$variant => $name,)*
+                }
+            }
+
+            /// Look up a variant by its name.
+            pub fn from_name(name: &str) -> Option<Self> {
+                match name {
+                    $($name => Some(Self::$variant),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+pub(crate) use named_enum;
+
+#[cfg(test)]
+mod tests {
+    named_enum! {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        enum Color {
+            /// Red.
+            Red => "red",
+            /// Light blue.
+            LightBlue => "light_blue",
+        }
+    }
+
+    #[test]
+    fn named_enum_names() {
+        assert_eq!(Color::ALL, [Color::Red, Color::LightBlue]);
+        for &color in Color::ALL {
+            assert_eq!(Color::from_name(color.name()), Some(color));
+        }
+        assert_eq!(Color::LightBlue.name(), "light_blue");
+        assert_eq!(Color::from_name("LightBlue"), None);
+    }
+}
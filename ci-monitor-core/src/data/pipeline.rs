@@ -8,81 +8,109 @@ use chrono::{DateTime, Utc};
 use derive_builder::Builder;
 use perfect_derive::perfect_derive;
 
-use crate::data::{Instance, MergeRequest, PipelineSchedule, PipelineVariables, Project, User};
+use crate::data::named_enum::named_enum;
+use crate::data::{
+    ArtifactDependencies, BlobReference, DataFidelity, Instance, MergeRequest, PipelineSchedule,
+    PipelineVariables, Project, User,
+};
 use crate::Lookup;
 
-/// The source of a pipeline.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[non_exhaustive]
-pub enum PipelineSource {
-    /// Created via the API.
-    Api,
-    /// Created via a chatbot.
-    Chat,
-    /// Created via an external event.
-    External,
-    /// Created via an external pull request event.
-    ExternalPullRequestEvent,
-    /// Created due to a merge request event.
-    MergeRequestEvent,
-    /// Created to perform a DAST scan.
-    OnDemandDastScan,
-    /// Created to perform a DAST validation.
-    OnDemandDastValidation,
-    /// Created as a child of another pipeline.
-    ParentPipeline,
-    /// Created through the action of another pipeline.
-    Pipeline,
-    /// Created due to a push to a ref.
-    Push,
-    /// Created due to a schedule.
-    Schedule,
-    /// Created for a security orchestration.
-    SecurityOrchestrationPolicy,
-    /// Created via a trigger token.
-    Trigger,
-    /// Created via the web interface.
-    Web,
-    /// Created via the web IDE.
-    WebIde,
+named_enum! {
+    /// The source of a pipeline.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[non_exhaustive]
+    pub enum PipelineSource {
+        /// Created via the API.
+        Api => "api",
+        /// Created via a chatbot.
+        Chat => "chat",
+        /// Created via an external event.
+        External => "external",
+        /// Created via an external pull request event.
+        ExternalPullRequestEvent => "external_pull_request_event",
+        /// Created due to a merge request event.
+        MergeRequestEvent => "merge_request_event",
+        /// Created to perform a DAST scan.
+        OnDemandDastScan => "on_demand_dast_scan",
+        /// Created to perform a DAST validation.
+        OnDemandDastValidation => "on_demand_dast_validation",
+        /// Created as a child of another pipeline.
+        ParentPipeline => "parent_pipeline",
+        /// Created through the action of another pipeline.
+        Pipeline => "pipeline",
+        /// Created due to a push to a ref.
+        Push => "push",
+        /// Created due to a schedule.
+        Schedule => "schedule",
+        /// Created for a security orchestration.
+        SecurityOrchestrationPolicy => "security_orchestration_policy",
+        /// Created via a trigger token.
+        Trigger => "trigger",
+        /// Created via the web interface.
+        Web => "web",
+        /// Created via the web IDE.
+        WebIde => "web_ide",
+        /// The source is not known.
+        Unknown => "unknown",
+    }
 }
 
-/// The overall status of a pipeline.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[non_exhaustive]
-pub enum PipelineStatus {
-    /// The pipeline has been created.
-    Created,
-    /// The pipeline is waiting for a resource to be available.
-    WaitingForResource,
-    /// The jobs in the pipeline are being constructed.
-    Preparing,
-    /// The pipeline is waiting for jobs to be executed.
-    Pending,
-    /// The pipeline is running.
-    Running,
-    /// The pipeline has completed successfully.
-    Success,
-    /// The pipeline has failed.
-    Failed,
-    /// The pipeline has been canceled.
-    Canceled,
-    /// The pipeline has been skipped.
-    Skipped,
-    /// The pipeline is waiting for manual interaction.
-    Manual,
-    /// The pipeline is scheduled.
-    Scheduled,
-    /// The pipeline has completed.
-    Completed,
-    /// The pipeline has completed without success or failure.
-    Neutral,
-    /// The pipeline is stale.
-    Stale,
-    /// The pipeline failed to start.
-    StartupFailure,
-    /// The pipeline has timed out.
-    TimedOut,
+named_enum! {
+    /// The overall status of a pipeline.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[non_exhaustive]
+    pub enum PipelineStatus {
+        /// The pipeline has been created.
+        Created => "created",
+        /// The pipeline is waiting for a resource to be available.
+        WaitingForResource => "waiting_for_resource",
+        /// The jobs in the pipeline are being constructed.
+        Preparing => "preparing",
+        /// The pipeline is waiting for jobs to be executed.
+        Pending => "pending",
+        /// The pipeline is running.
+        Running => "running",
+        /// The pipeline has completed successfully.
+        Success => "success",
+        /// The pipeline has failed.
+        Failed => "failed",
+        /// The pipeline has been canceled.
+        Canceled => "canceled",
+        /// The pipeline has been skipped.
+        Skipped => "skipped",
+        /// The pipeline is waiting for manual interaction.
+        Manual => "manual",
+        /// The pipeline is scheduled.
+        Scheduled => "scheduled",
+        /// The pipeline has completed.
+        Completed => "completed",
+        /// The pipeline has completed without success or failure.
+        Neutral => "neutral",
+        /// The pipeline is stale.
+        Stale => "stale",
+        /// The pipeline failed to start.
+        StartupFailure => "startup_failure",
+        /// The pipeline has timed out.
+        TimedOut => "timed_out",
+    }
+}
+
+impl PipelineStatus {
+    /// Whether the pipeline has finished running.
+    pub fn is_finished(self) -> bool {
+        matches!(
+            self,
+            Self::Success
+                | Self::Failed
+                | Self::Canceled
+                | Self::Skipped
+                | Self::Completed
+                | Self::Neutral
+                | Self::Stale
+                | Self::StartupFailure
+                | Self::TimedOut,
+        )
+    }
 }
 
 /// A pipeline which performs CI tasks for a project.
@@ -119,6 +147,12 @@ where
     /// The stable refname for the pipeline.
     #[builder(default, setter(into))]
     pub stable_refname: Option<String>,
+    /// The CI configuration file used by the pipeline.
+    #[builder(default)]
+    pub ci_config: Option<BlobReference>,
+    /// How jobs consume artifacts of other jobs according to the CI configuration.
+    #[builder(default)]
+    pub artifact_dependencies: Option<ArtifactDependencies>,
 
     // Execution metadata.
     /// The reason the pipeline was created.
@@ -169,12 +203,20 @@ where
     pub finished_at: Option<DateTime<Utc>>,
 
     // Monitoring metadata.
+    /// How trustworthy the information is.
+    #[builder(default)]
+    pub cim_fidelity: DataFidelity,
     /// When the monitoring tool first fetched information.
     #[builder(default = "Utc::now()", setter(skip))]
     pub cim_fetched_at: DateTime<Utc>,
     /// When the monitoring tool last updated this information.
     #[builder(default = "Utc::now()", setter(skip))]
     pub cim_refreshed_at: DateTime<Utc>,
+    /// When the entity was marked as deleted.
+    ///
+    /// Deleted entities remain in the store until purged.
+    #[builder(default, setter(skip))]
+    pub cim_deleted_at: Option<DateTime<Utc>>,
 }
 
 impl<L> Pipeline<L>
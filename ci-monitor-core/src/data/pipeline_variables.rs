@@ -8,16 +8,20 @@ use std::collections::BTreeMap;
 
 use derive_builder::Builder;
 
-/// How the pipeline variable is available.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[non_exhaustive]
-pub enum PipelineVariableType {
-    /// The value is placed as contents within a file.
-    ///
-    /// The environment variable contains the path to the file.
-    File,
-    /// The environment variable contains the contents of the variable.
-    String,
+use crate::data::named_enum::named_enum;
+
+named_enum! {
+    /// How the pipeline variable is available.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[non_exhaustive]
+    pub enum PipelineVariableType {
+        /// The value is placed as contents within a file.
+        ///
+        /// The environment variable contains the path to the file.
+        File => "file",
+        /// The environment variable contains the contents of the variable.
+        String => "string",
+    }
 }
 
 /// A pipeline variable value.
@@ -8,9 +8,34 @@ use chrono::{DateTime, Utc};
 use derive_builder::Builder;
 use perfect_derive::perfect_derive;
 
-use crate::data::Instance;
+use crate::data::{DataFidelity, Instance};
 use crate::Lookup;
 
+/// A branch which used to be the default branch of a project.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PreviousDefaultBranch {
+    /// The name of the branch.
+    pub branch: String,
+    /// When the branch stopped being the default branch.
+    ///
+    /// This is when the monitor noticed the change rather than when it was made.
+    pub until: DateTime<Utc>,
+}
+
+impl PreviousDefaultBranch {
+    /// Record a branch which stopped being the default branch at a time.
+    pub fn new<B>(branch: B, until: DateTime<Utc>) -> Self
+    where
+        B: Into<String>,
+    {
+        Self {
+            branch: branch.into(),
+            until,
+        }
+    }
+}
+
 /// An instance of a project.
 ///
 /// This represents an instance of a project. There may be multiple instances of the project on
@@ -41,14 +66,36 @@ where
     /// The path to the repository on the instance.
     #[builder(default, setter(into))]
     pub instance_path: String,
+    /// The default branch of the project.
+    ///
+    /// `None` if the repository is empty or the branch is not yet known.
+    #[builder(default, setter(into))]
+    pub default_branch: Option<String>,
+    /// Branches which used to be the default branch (oldest first).
+    #[builder(default)]
+    pub previous_default_branches: Vec<PreviousDefaultBranch>,
 
     // Monitoring metadata.
+    /// How trustworthy the information is.
+    #[builder(default)]
+    pub cim_fidelity: DataFidelity,
     /// When the monitoring tool first fetched information.
     #[builder(default = "Utc::now()", setter(skip))]
     pub cim_fetched_at: DateTime<Utc>,
     /// When the monitoring tool last updated this information.
     #[builder(default = "Utc::now()", setter(skip))]
     pub cim_refreshed_at: DateTime<Utc>,
+    /// When the entity was marked as deleted.
+    ///
+    /// Deleted entities remain in the store until purged.
+    #[builder(default, setter(skip))]
+    pub cim_deleted_at: Option<DateTime<Utc>>,
+    /// When the project was noticed to be missing from the watched groups.
+    ///
+    /// This is separate from `cim_deleted_at` so that operator deletions can be told apart from
+    /// projects which moved out of (or were deleted from) a watched group.
+    #[builder(default, setter(skip))]
+    pub cim_removed_from_group_at: Option<DateTime<Utc>>,
 }
 
 impl<L> Project<L>
@@ -59,10 +106,39 @@ where
     pub fn builder() -> ProjectBuilder<L> {
         ProjectBuilder::default()
     }
+
+    /// Change the default branch of the project.
+    ///
+    /// The current default branch is remembered as a previous default branch until `at`. Returns
+    /// `true` if the default branch changed.
+    pub fn set_default_branch(&mut self, branch: Option<String>, at: DateTime<Utc>) -> bool {
+        if branch == self.default_branch {
+            return false;
+        }
+
+        if let Some(previous) = std::mem::replace(&mut self.default_branch, branch) {
+            self.previous_default_branches
+                .push(PreviousDefaultBranch::new(previous, at));
+        }
+        true
+    }
+
+    /// The default branch of the project at a time.
+    ///
+    /// Times before the history of the project was recorded use the oldest known default branch.
+    pub fn default_branch_at(&self, at: DateTime<Utc>) -> Option<&str> {
+        self.previous_default_branches
+            .iter()
+            .find(|previous| at < previous.until)
+            .map(|previous| previous.branch.as_str())
+            .or(self.default_branch.as_deref())
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use chrono::{Duration, Utc};
+
     use crate::data::{Instance, Project, ProjectBuilderError};
     use crate::Lookup;
 
@@ -111,4 +187,30 @@ mod tests {
             .build()
             .unwrap();
     }
+
+    #[test]
+    fn default_branch_history() {
+        let mut lookup = TestLookup::default();
+        let inst = instance();
+        let idx = lookup.store(inst);
+
+        let mut project = Project::<TestLookup>::builder()
+            .forge_id(0)
+            .instance(idx)
+            .build()
+            .unwrap();
+        let start = Utc::now();
+        let renamed = start + Duration::hours(1);
+
+        assert!(project.set_default_branch(Some("master".into()), start));
+        assert!(project.previous_default_branches.is_empty());
+        assert!(!project.set_default_branch(Some("master".into()), start));
+        assert!(project.set_default_branch(Some("main".into()), renamed));
+        assert_eq!(project.default_branch.as_deref(), Some("main"));
+        assert_eq!(project.previous_default_branches.len(), 1);
+        assert_eq!(project.previous_default_branches[0].branch, "master");
+
+        assert_eq!(project.default_branch_at(start), Some("master"));
+        assert_eq!(project.default_branch_at(renamed), Some("main"));
+    }
 }
@@ -0,0 +1,199 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use chrono::{DateTime, Utc};
+use derive_builder::Builder;
+use perfect_derive::perfect_derive;
+
+use crate::data::{Instance, Project, User};
+use crate::Lookup;
+
+/// A push to a ref of a project.
+///
+/// Pushes are recorded so that those which did not produce a pipeline can be found. Pushes which
+/// delete a ref are not recorded.
+#[derive(Builder)]
+#[perfect_derive(Debug, Clone)]
+#[builder(pattern = "owned")]
+#[non_exhaustive]
+pub struct Push<L>
+where
+    L: Lookup<Instance>,
+    L: Lookup<Project<L>>,
+    L: Lookup<User<L>>,
+{
+    // Metadata.
+    /// The user who pushed.
+    #[builder(default)]
+    pub author: Option<<L as Lookup<User<L>>>::Index>,
+    /// The number of commits which were pushed.
+    #[builder(default)]
+    pub commit_count: u64,
+    /// The title of the commit the ref was updated to.
+    #[builder(default, setter(into))]
+    pub commit_title: String,
+
+    // Repository metadata.
+    /// The project which was pushed to.
+    pub project: <L as Lookup<Project<L>>>::Index,
+    /// The name of the ref which was pushed (without its namespace).
+    #[builder(setter(into))]
+    pub refname: String,
+    /// Whether the ref is a tag.
+    #[builder(default)]
+    pub tag: bool,
+    /// The commit the ref was updated to.
+    #[builder(setter(into))]
+    pub sha: String,
+    /// The commit the ref pointed to before the push (if it existed).
+    #[builder(default)]
+    pub before: Option<String>,
+
+    // Forge metadata.
+    /// The ID of the push event.
+    pub forge_id: u64,
+    /// When the push happened.
+    pub pushed_at: DateTime<Utc>,
+
+    // Monitoring metadata.
+    /// When the monitoring tool first fetched information.
+    #[builder(default = "Utc::now()", setter(skip))]
+    pub cim_fetched_at: DateTime<Utc>,
+    /// When the monitoring tool last updated this information.
+    #[builder(default = "Utc::now()", setter(skip))]
+    pub cim_refreshed_at: DateTime<Utc>,
+    /// When the entity was marked as deleted.
+    ///
+    /// Deleted entities remain in the store until purged.
+    #[builder(default, setter(skip))]
+    pub cim_deleted_at: Option<DateTime<Utc>>,
+}
+
+impl<L> Push<L>
+where
+    L: Lookup<Instance>,
+    L: Lookup<Project<L>>,
+    L: Lookup<User<L>>,
+{
+    /// Create a builder for the structure.
+    pub fn builder() -> PushBuilder<L> {
+        PushBuilder::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use crate::data::{Instance, Project, Push, PushBuilderError};
+    use crate::Lookup;
+
+    use crate::test::TestLookup;
+
+    fn project(lookup: &mut TestLookup) -> <TestLookup as Lookup<Project<TestLookup>>>::Index {
+        let instance = Instance::builder()
+            .unique_id(0)
+            .forge("forge")
+            .url("url")
+            .build()
+            .unwrap();
+        let idx = lookup.store(instance);
+
+        let project = Project::builder()
+            .forge_id(0)
+            .instance(idx)
+            .build()
+            .unwrap();
+        lookup.store(project)
+    }
+
+    #[test]
+    fn project_is_required() {
+        let err = Push::<TestLookup>::builder()
+            .refname("main")
+            .sha("0000000000000000000000000000000000000000")
+            .forge_id(0)
+            .pushed_at(Utc::now())
+            .build()
+            .unwrap_err();
+        crate::test::assert_missing_field!(err, PushBuilderError, "project");
+    }
+
+    #[test]
+    fn refname_is_required() {
+        let mut lookup = TestLookup::default();
+        let proj_idx = project(&mut lookup);
+
+        let err = Push::<TestLookup>::builder()
+            .project(proj_idx)
+            .sha("0000000000000000000000000000000000000000")
+            .forge_id(0)
+            .pushed_at(Utc::now())
+            .build()
+            .unwrap_err();
+        crate::test::assert_missing_field!(err, PushBuilderError, "refname");
+    }
+
+    #[test]
+    fn sha_is_required() {
+        let mut lookup = TestLookup::default();
+        let proj_idx = project(&mut lookup);
+
+        let err = Push::<TestLookup>::builder()
+            .project(proj_idx)
+            .refname("main")
+            .forge_id(0)
+            .pushed_at(Utc::now())
+            .build()
+            .unwrap_err();
+        crate::test::assert_missing_field!(err, PushBuilderError, "sha");
+    }
+
+    #[test]
+    fn forge_id_is_required() {
+        let mut lookup = TestLookup::default();
+        let proj_idx = project(&mut lookup);
+
+        let err = Push::<TestLookup>::builder()
+            .project(proj_idx)
+            .refname("main")
+            .sha("0000000000000000000000000000000000000000")
+            .pushed_at(Utc::now())
+            .build()
+            .unwrap_err();
+        crate::test::assert_missing_field!(err, PushBuilderError, "forge_id");
+    }
+
+    #[test]
+    fn pushed_at_is_required() {
+        let mut lookup = TestLookup::default();
+        let proj_idx = project(&mut lookup);
+
+        let err = Push::<TestLookup>::builder()
+            .project(proj_idx)
+            .refname("main")
+            .sha("0000000000000000000000000000000000000000")
+            .forge_id(0)
+            .build()
+            .unwrap_err();
+        crate::test::assert_missing_field!(err, PushBuilderError, "pushed_at");
+    }
+
+    #[test]
+    fn sufficient_fields() {
+        let mut lookup = TestLookup::default();
+        let proj_idx = project(&mut lookup);
+
+        Push::<TestLookup>::builder()
+            .project(proj_idx)
+            .refname("main")
+            .sha("0000000000000000000000000000000000000000")
+            .forge_id(0)
+            .pushed_at(Utc::now())
+            .build()
+            .unwrap();
+    }
+}
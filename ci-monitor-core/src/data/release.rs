@@ -0,0 +1,262 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use chrono::{DateTime, Utc};
+use derive_builder::Builder;
+use perfect_derive::perfect_derive;
+
+use crate::data::{Instance, MergeRequest, Pipeline, PipelineSchedule, Project, User};
+use crate::Lookup;
+
+/// An asset attached to a release.
+#[derive(Debug, Builder, Clone, PartialEq, Eq)]
+#[builder(pattern = "owned")]
+#[non_exhaustive]
+pub struct ReleaseAsset {
+    /// The name of the asset.
+    #[builder(setter(into))]
+    pub name: String,
+    /// The URL of the asset.
+    #[builder(setter(into))]
+    pub url: String,
+    /// The kind of asset (e.g., `package` or `image`).
+    #[builder(default)]
+    pub link_type: Option<String>,
+}
+
+impl ReleaseAsset {
+    /// Create a builder for the structure.
+    pub fn builder() -> ReleaseAssetBuilder {
+        ReleaseAssetBuilder::default()
+    }
+}
+
+/// A release of a project.
+#[derive(Builder)]
+#[perfect_derive(Debug, Clone)]
+#[builder(pattern = "owned")]
+#[non_exhaustive]
+pub struct Release<L>
+where
+    L: Lookup<Instance>,
+    L: Lookup<MergeRequest<L>>,
+    L: Lookup<Pipeline<L>>,
+    L: Lookup<PipelineSchedule<L>>,
+    L: Lookup<Project<L>>,
+    L: Lookup<User<L>>,
+{
+    // Metadata.
+    /// The name of the release.
+    #[builder(default, setter(into))]
+    pub name: String,
+    /// The description of the release.
+    #[builder(default, setter(into))]
+    pub description: String,
+    /// The user who created the release.
+    #[builder(default)]
+    pub author: Option<<L as Lookup<User<L>>>::Index>,
+    /// Assets attached to the release.
+    #[builder(default)]
+    pub assets: Vec<ReleaseAsset>,
+
+    // Repository metadata.
+    /// The project the release belongs to.
+    pub project: <L as Lookup<Project<L>>>::Index,
+    /// The tag of the release.
+    #[builder(setter(into))]
+    pub tag: String,
+    /// The commit the tag points to.
+    #[builder(setter(into))]
+    pub sha: String,
+    /// The pipeline which built the tag.
+    #[builder(default)]
+    pub pipeline: Option<<L as Lookup<Pipeline<L>>>::Index>,
+
+    // Forge metadata.
+    /// A unique ID for the release.
+    ///
+    /// Forges identify releases by their tag within a project.
+    pub unique_id: u64,
+    /// The URL of the release.
+    #[builder(default, setter(into))]
+    pub url: String,
+    /// When the release was created.
+    pub created_at: DateTime<Utc>,
+    /// When the release was (or will be) published.
+    pub released_at: DateTime<Utc>,
+
+    // Monitoring metadata.
+    /// When the monitoring tool first fetched information.
+    #[builder(default = "Utc::now()", setter(skip))]
+    pub cim_fetched_at: DateTime<Utc>,
+    /// When the monitoring tool last updated this information.
+    #[builder(default = "Utc::now()", setter(skip))]
+    pub cim_refreshed_at: DateTime<Utc>,
+    /// When the entity was marked as deleted.
+    ///
+    /// Deleted entities remain in the store until purged.
+    #[builder(default, setter(skip))]
+    pub cim_deleted_at: Option<DateTime<Utc>>,
+}
+
+impl<L> Release<L>
+where
+    L: Lookup<Instance>,
+    L: Lookup<MergeRequest<L>>,
+    L: Lookup<Pipeline<L>>,
+    L: Lookup<PipelineSchedule<L>>,
+    L: Lookup<Project<L>>,
+    L: Lookup<User<L>>,
+{
+    /// Create a builder for the structure.
+    pub fn builder() -> ReleaseBuilder<L> {
+        ReleaseBuilder::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use crate::data::{
+        Instance, Project, Release, ReleaseAsset, ReleaseAssetBuilderError, ReleaseBuilderError,
+    };
+    use crate::Lookup;
+
+    use crate::test::TestLookup;
+
+    fn project(lookup: &mut TestLookup) -> <TestLookup as Lookup<Project<TestLookup>>>::Index {
+        let instance = Instance::builder()
+            .unique_id(0)
+            .forge("forge")
+            .url("url")
+            .build()
+            .unwrap();
+        let idx = lookup.store(instance);
+
+        let project = Project::builder()
+            .forge_id(0)
+            .instance(idx)
+            .build()
+            .unwrap();
+        lookup.store(project)
+    }
+
+    #[test]
+    fn project_is_required() {
+        let err = Release::<TestLookup>::builder()
+            .tag("v1.0.0")
+            .sha("0000000000000000000000000000000000000000")
+            .unique_id(0)
+            .created_at(Utc::now())
+            .released_at(Utc::now())
+            .build()
+            .unwrap_err();
+        crate::test::assert_missing_field!(err, ReleaseBuilderError, "project");
+    }
+
+    #[test]
+    fn tag_is_required() {
+        let mut lookup = TestLookup::default();
+        let proj_idx = project(&mut lookup);
+
+        let err = Release::<TestLookup>::builder()
+            .project(proj_idx)
+            .sha("0000000000000000000000000000000000000000")
+            .unique_id(0)
+            .created_at(Utc::now())
+            .released_at(Utc::now())
+            .build()
+            .unwrap_err();
+        crate::test::assert_missing_field!(err, ReleaseBuilderError, "tag");
+    }
+
+    #[test]
+    fn sha_is_required() {
+        let mut lookup = TestLookup::default();
+        let proj_idx = project(&mut lookup);
+
+        let err = Release::<TestLookup>::builder()
+            .project(proj_idx)
+            .tag("v1.0.0")
+            .unique_id(0)
+            .created_at(Utc::now())
+            .released_at(Utc::now())
+            .build()
+            .unwrap_err();
+        crate::test::assert_missing_field!(err, ReleaseBuilderError, "sha");
+    }
+
+    #[test]
+    fn unique_id_is_required() {
+        let mut lookup = TestLookup::default();
+        let proj_idx = project(&mut lookup);
+
+        let err = Release::<TestLookup>::builder()
+            .project(proj_idx)
+            .tag("v1.0.0")
+            .sha("0000000000000000000000000000000000000000")
+            .created_at(Utc::now())
+            .released_at(Utc::now())
+            .build()
+            .unwrap_err();
+        crate::test::assert_missing_field!(err, ReleaseBuilderError, "unique_id");
+    }
+
+    #[test]
+    fn created_at_is_required() {
+        let mut lookup = TestLookup::default();
+        let proj_idx = project(&mut lookup);
+
+        let err = Release::<TestLookup>::builder()
+            .project(proj_idx)
+            .tag("v1.0.0")
+            .sha("0000000000000000000000000000000000000000")
+            .unique_id(0)
+            .released_at(Utc::now())
+            .build()
+            .unwrap_err();
+        crate::test::assert_missing_field!(err, ReleaseBuilderError, "created_at");
+    }
+
+    #[test]
+    fn released_at_is_required() {
+        let mut lookup = TestLookup::default();
+        let proj_idx = project(&mut lookup);
+
+        let err = Release::<TestLookup>::builder()
+            .project(proj_idx)
+            .tag("v1.0.0")
+            .sha("0000000000000000000000000000000000000000")
+            .unique_id(0)
+            .created_at(Utc::now())
+            .build()
+            .unwrap_err();
+        crate::test::assert_missing_field!(err, ReleaseBuilderError, "released_at");
+    }
+
+    #[test]
+    fn sufficient_fields() {
+        let mut lookup = TestLookup::default();
+        let proj_idx = project(&mut lookup);
+
+        Release::<TestLookup>::builder()
+            .project(proj_idx)
+            .tag("v1.0.0")
+            .sha("0000000000000000000000000000000000000000")
+            .unique_id(0)
+            .created_at(Utc::now())
+            .released_at(Utc::now())
+            .build()
+            .unwrap();
+    }
+
+    #[test]
+    fn asset_url_is_required() {
+        let err = ReleaseAsset::builder().name("tarball").build().unwrap_err();
+        crate::test::assert_missing_field!(err, ReleaseAssetBuilderError, "url");
+    }
+}
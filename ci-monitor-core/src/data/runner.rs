@@ -8,29 +8,34 @@ use chrono::{DateTime, Utc};
 use derive_builder::Builder;
 use perfect_derive::perfect_derive;
 
+use crate::data::named_enum::named_enum;
 use crate::data::{Instance, Project, RunnerHost};
 use crate::Lookup;
 
-/// The scope at which a runner is registered.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[non_exhaustive]
-pub enum RunnerType {
-    /// Can accept instance-wide jobs.
-    Instance,
-    /// Can accept jobs from a specific group.
-    Group,
-    /// Can accept jobs from a specific project.
-    Project,
+named_enum! {
+    /// The scope at which a runner is registered.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[non_exhaustive]
+    pub enum RunnerType {
+        /// Can accept instance-wide jobs.
+        Instance => "instance",
+        /// Can accept jobs from a specific group.
+        Group => "group",
+        /// Can accept jobs from a specific project.
+        Project => "project",
+    }
 }
 
-/// Types of refs the runner may run.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[non_exhaustive]
-pub enum RunnerProtectionLevel {
-    /// Only jobs for protected refs may use this runner.
-    Protected,
-    /// Any job can use this runner.
-    Any,
+named_enum! {
+    /// Types of refs the runner may run.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[non_exhaustive]
+    pub enum RunnerProtectionLevel {
+        /// Only jobs for protected refs may use this runner.
+        Protected => "protected",
+        /// Any job can use this runner.
+        Any => "any",
+    }
 }
 
 /// A runner which can perform jobs for CI tasks.
@@ -53,6 +58,11 @@ where
     /// The maximum timeout for jobs on the runner (in seconds).
     #[builder(default, setter(into))]
     pub maximum_timeout: Option<u64>,
+    /// The maximum number of jobs the runner may run at once.
+    ///
+    /// Forges do not usually report this, so it is provided by configuration.
+    #[builder(default, setter(into))]
+    pub max_concurrent_jobs: Option<u64>,
     /// Protection level of refs that can use this runner.
     pub protection_level: RunnerProtectionLevel,
 
@@ -102,6 +112,9 @@ where
     /// When the runner last contacted the forge.
     #[builder(default, setter(into))]
     pub contacted_at: Option<DateTime<Utc>>,
+    /// The IP address from which the runner last contacted the forge.
+    #[builder(default, setter(into))]
+    pub ip_address: Option<String>,
     /// The maintenance note of the runner.
     #[builder(default, setter(into))]
     pub maintenance_note: Option<String>,
@@ -4,9 +4,65 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use derive_builder::Builder;
 
+use crate::data::named_enum::named_enum;
+
+named_enum! {
+    /// An event in the lifetime of an autoscaled instance.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[non_exhaustive]
+    pub enum AutoscalerEventKind {
+        /// The instance was requested.
+        Created => "created",
+        /// The instance became able to run jobs.
+        Ready => "ready",
+        /// The instance was removed.
+        Removed => "removed",
+    }
+}
+
+/// A VM or pod spun up by an autoscaling executor on a host.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct AutoscaledInstance {
+    /// The name of the instance.
+    pub name: String,
+    /// The executor which created the instance (e.g., `docker+machine` or `kubernetes`).
+    pub executor: String,
+    /// When the instance was requested.
+    pub created_at: DateTime<Utc>,
+    /// When the instance became able to run jobs.
+    pub ready_at: Option<DateTime<Utc>>,
+    /// When the instance was removed.
+    pub removed_at: Option<DateTime<Utc>>,
+}
+
+impl AutoscaledInstance {
+    /// Record an instance which was requested at a time.
+    pub fn new<N, E>(name: N, executor: E, created_at: DateTime<Utc>) -> Self
+    where
+        N: Into<String>,
+        E: Into<String>,
+    {
+        Self {
+            name: name.into(),
+            executor: executor.into(),
+            created_at,
+            ready_at: None,
+            removed_at: None,
+        }
+    }
+
+    /// How long the instance has existed.
+    ///
+    /// Instances which have not been removed are measured until `now`.
+    pub fn lifetime(&self, now: DateTime<Utc>) -> Duration {
+        self.removed_at.unwrap_or(now) - self.created_at
+    }
+}
+
 /// Information about a machine that performs jobs.
 #[derive(Debug, Builder, Clone)]
 #[builder(pattern = "owned")]
@@ -34,6 +90,16 @@ pub struct RunnerHost {
     /// An estimate of the cost of tasks on this host per hour.
     #[builder(default)]
     pub estimated_cost_per_hour: Option<f64>,
+    /// The maximum number of jobs the host may run at once.
+    ///
+    /// Used for runners on the host which do not have their own limit.
+    #[builder(default)]
+    pub max_concurrent_jobs: Option<u64>,
+    /// Instances spun up on the host by autoscaling executors.
+    ///
+    /// Sorted by creation time.
+    #[builder(default)]
+    pub autoscaled_instances: Vec<AutoscaledInstance>,
 
     /// A unique ID for the runner host.
     pub unique_id: u64,
@@ -52,11 +118,68 @@ impl RunnerHost {
     pub fn builder() -> RunnerHostBuilder {
         RunnerHostBuilder::default()
     }
+
+    /// Record an event from an autoscaling executor.
+    ///
+    /// Events for an instance apply to the most recent instance of that name created before the
+    /// event. Events for instances which were never seen being created are ignored since their
+    /// lifetime is unknown. Recording an event again has no effect so that overlapping logs may
+    /// be ingested. Returns whether the host changed.
+    pub fn record_autoscaler_event(
+        &mut self,
+        instance: &str,
+        executor: &str,
+        kind: AutoscalerEventKind,
+        at: DateTime<Utc>,
+    ) -> bool {
+        if kind == AutoscalerEventKind::Created {
+            let known = self
+                .autoscaled_instances
+                .iter()
+                .any(|autoscaled| autoscaled.name == instance && autoscaled.created_at == at);
+            if known {
+                return false;
+            }
+
+            let pos = self
+                .autoscaled_instances
+                .partition_point(|autoscaled| autoscaled.created_at <= at);
+            self.autoscaled_instances
+                .insert(pos, AutoscaledInstance::new(instance, executor, at));
+            return true;
+        }
+
+        let autoscaled = self
+            .autoscaled_instances
+            .iter_mut()
+            .rev()
+            .find(|autoscaled| autoscaled.name == instance && autoscaled.created_at <= at);
+        let autoscaled = if let Some(autoscaled) = autoscaled {
+            autoscaled
+        } else {
+            return false;
+        };
+        let field = match kind {
+            AutoscalerEventKind::Ready => &mut autoscaled.ready_at,
+            AutoscalerEventKind::Removed | AutoscalerEventKind::Created => {
+                &mut autoscaled.removed_at
+            },
+        };
+
+        if field.is_some() {
+            false
+        } else {
+            *field = Some(at);
+            true
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::data::{RunnerHost, RunnerHostBuilderError};
+    use chrono::{Duration, Utc};
+
+    use crate::data::{AutoscalerEventKind, RunnerHost, RunnerHostBuilderError};
 
     #[test]
     fn name_is_required() {
@@ -78,4 +201,44 @@ mod tests {
             .build()
             .unwrap();
     }
+
+    #[test]
+    fn autoscaler_events() {
+        let mut host = RunnerHost::builder()
+            .name("name")
+            .unique_id(0)
+            .build()
+            .unwrap();
+        let start = Utc::now();
+        let at = |minutes| start + Duration::minutes(minutes);
+        let machine = "docker+machine";
+
+        // Events for unknown instances are ignored.
+        assert!(!host.record_autoscaler_event("a", machine, AutoscalerEventKind::Ready, at(1)));
+        assert!(host.autoscaled_instances.is_empty());
+
+        assert!(host.record_autoscaler_event("b", machine, AutoscalerEventKind::Created, at(5)));
+        assert!(host.record_autoscaler_event("a", machine, AutoscalerEventKind::Created, at(0)));
+        assert!(!host.record_autoscaler_event("a", machine, AutoscalerEventKind::Created, at(0)));
+        assert!(host.record_autoscaler_event("a", machine, AutoscalerEventKind::Ready, at(2)));
+        assert!(!host.record_autoscaler_event("a", machine, AutoscalerEventKind::Ready, at(3)));
+        assert!(host.record_autoscaler_event("a", machine, AutoscalerEventKind::Removed, at(30)));
+        assert!(!host.record_autoscaler_event("a", machine, AutoscalerEventKind::Removed, at(30)));
+
+        // Names may be reused.
+        assert!(host.record_autoscaler_event("a", machine, AutoscalerEventKind::Created, at(40)));
+        assert!(host.record_autoscaler_event("a", machine, AutoscalerEventKind::Removed, at(50)));
+
+        let instances = &host.autoscaled_instances;
+        assert_eq!(instances.len(), 3);
+        assert_eq!(instances[0].name, "a");
+        assert_eq!(instances[0].ready_at, Some(at(2)));
+        assert_eq!(instances[0].removed_at, Some(at(30)));
+        assert_eq!(instances[0].lifetime(at(59)).num_minutes(), 30);
+        assert_eq!(instances[1].name, "b");
+        assert_eq!(instances[1].lifetime(at(59)).num_minutes(), 54);
+        assert_eq!(instances[2].created_at, at(40));
+        assert_eq!(instances[2].ready_at, None);
+        assert_eq!(instances[2].removed_at, Some(at(50)));
+    }
 }
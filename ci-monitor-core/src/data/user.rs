@@ -23,6 +23,9 @@ where
     /// The handle of the user.
     #[builder(default, setter(into))]
     pub handle: String,
+    /// Handles the user was previously known by (oldest first).
+    #[builder(default)]
+    pub previous_handles: Vec<String>,
     /// The display name of the user.
     #[builder(default, setter(into))]
     pub name: String,
@@ -56,6 +59,47 @@ where
     pub fn builder() -> UserBuilder<L> {
         UserBuilder::default()
     }
+
+    /// Whether the user is or was known by a handle.
+    pub fn is_known_as(&self, handle: &str) -> bool {
+        self.handle == handle
+            || self
+                .previous_handles
+                .iter()
+                .any(|previous| previous == handle)
+    }
+
+    /// Record a handle the user was previously known by.
+    ///
+    /// Handles which the user is already known by are ignored.
+    pub fn add_previous_handle<H>(&mut self, handle: H)
+    where
+        H: Into<String>,
+    {
+        let handle = handle.into();
+        if !handle.is_empty() && !self.is_known_as(&handle) {
+            self.previous_handles.push(handle);
+        }
+    }
+
+    /// Change the handle of the user.
+    ///
+    /// The current handle is remembered as a previous handle. Returns `true` if the handle
+    /// changed.
+    pub fn rename<H>(&mut self, handle: H) -> bool
+    where
+        H: Into<String>,
+    {
+        let handle = handle.into();
+        if handle == self.handle {
+            return false;
+        }
+
+        let previous = std::mem::replace(&mut self.handle, handle);
+        self.previous_handles.retain(|known| *known != self.handle);
+        self.add_previous_handle(previous);
+        true
+    }
 }
 
 #[cfg(test)]
@@ -96,6 +140,40 @@ mod tests {
         crate::test::assert_missing_field!(err, UserBuilderError, "instance");
     }
 
+    #[test]
+    fn rename() {
+        let mut lookup = TestLookup::default();
+        let inst = instance();
+        let idx = lookup.store(inst);
+
+        let mut user = User::<TestLookup>::builder()
+            .handle("old")
+            .forge_id(0)
+            .instance(idx)
+            .build()
+            .unwrap();
+
+        assert!(!user.rename("old"));
+        assert!(user.previous_handles.is_empty());
+
+        assert!(user.rename("new"));
+        assert_eq!(user.handle, "new");
+        assert_eq!(user.previous_handles, ["old"]);
+        assert!(user.is_known_as("old"));
+        assert!(user.is_known_as("new"));
+        assert!(!user.is_known_as("other"));
+
+        // Returning to a previous handle does not duplicate it.
+        assert!(user.rename("old"));
+        assert_eq!(user.handle, "old");
+        assert_eq!(user.previous_handles, ["new"]);
+
+        user.add_previous_handle("old");
+        user.add_previous_handle("");
+        user.add_previous_handle("alias");
+        assert_eq!(user.previous_handles, ["new", "alias"]);
+    }
+
     #[test]
     fn sufficient_fields() {
         let mut lookup = TestLookup::default();
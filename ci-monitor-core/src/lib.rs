@@ -14,6 +14,7 @@
 pub mod data;
 mod lookup;
 
+/// Utilities for testing.
 #[cfg(test)]
 pub mod test;
 
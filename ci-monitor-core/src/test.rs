@@ -25,6 +25,7 @@ macro_rules! assert_missing_field {
 }
 pub(crate) use assert_missing_field;
 
+/// A lookup which stores entities behind `Arc` pointers.
 #[derive(Debug, Default, Clone)]
 pub struct TestLookup {}
 
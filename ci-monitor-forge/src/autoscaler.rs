@@ -0,0 +1,345 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use chrono::{DateTime, Duration, Utc};
+use ci_monitor_core::data::AutoscalerEventKind;
+use serde::Deserialize;
+use thiserror::Error;
+
+/// The executor which logs instances in GitLab Runner logs.
+const DOCKER_MACHINE: &str = "docker+machine";
+
+/// Errors which may occur when parsing autoscaler events.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum AutoscalerLogError {
+    /// A line could not be parsed.
+    #[error("invalid autoscaler event on line {}: {}", line, source)]
+    InvalidLine {
+        /// The line number (starting at 1).
+        line: usize,
+        /// The source of the error.
+        #[source]
+        source: serde_json::Error,
+    },
+    /// An event has an unknown kind.
+    #[error("unknown autoscaler event '{}' on line {}", event, line)]
+    UnknownEvent {
+        /// The line number (starting at 1).
+        line: usize,
+        /// The event.
+        event: String,
+    },
+}
+
+impl AutoscalerLogError {
+    fn invalid_line(line: usize, source: serde_json::Error) -> Self {
+        Self::InvalidLine {
+            line,
+            source,
+        }
+    }
+
+    fn unknown_event(line: usize, event: String) -> Self {
+        Self::UnknownEvent {
+            line,
+            event,
+        }
+    }
+}
+
+/// The format of autoscaler events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AutoscalerLogFormat {
+    /// One JSON object per line with `instance`, `executor`, `event`, and `at` fields.
+    ///
+    /// Events are `created`, `ready`, or `removed` and times are RFC 3339 timestamps. Other
+    /// sources (e.g., Kubernetes pod events) may be converted into this format.
+    Events,
+    /// GitLab Runner logs using the JSON log format.
+    ///
+    /// The `Machine created` and `Machine removed` messages of the `docker+machine` executor are
+    /// used; all other lines are ignored.
+    RunnerLog,
+}
+
+impl AutoscalerLogFormat {
+    /// All formats.
+    pub const ALL: &'static [Self] = &[Self::Events, Self::RunnerLog];
+
+    /// The name of the format.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Events => "events",
+            Self::RunnerLog => "runner-log",
+        }
+    }
+
+    /// Look up a format by its name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|format| format.name() == name)
+    }
+}
+
+/// An event in the lifetime of an autoscaled instance.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct AutoscalerEvent {
+    /// The name of the instance.
+    pub instance: String,
+    /// The executor which manages the instance.
+    pub executor: String,
+    /// What happened.
+    pub kind: AutoscalerEventKind,
+    /// When it happened.
+    pub at: DateTime<Utc>,
+}
+
+impl AutoscalerEvent {
+    fn new(instance: &str, executor: &str, kind: AutoscalerEventKind, at: DateTime<Utc>) -> Self {
+        Self {
+            instance: instance.into(),
+            executor: executor.into(),
+            kind,
+            at,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct EventLine {
+    instance: String,
+    executor: String,
+    event: String,
+    at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+struct RunnerLogLine {
+    #[serde(default)]
+    msg: String,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    duration: Option<String>,
+}
+
+/// Parse a Go duration (e.g., `1m2.5s`).
+fn go_duration(duration: &str) -> Option<Duration> {
+    let mut rest = duration;
+    let mut seconds = 0.;
+    while !rest.is_empty() {
+        let unit_start = rest.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+        let (value, tail) = rest.split_at(unit_start);
+        let value: f64 = value.parse().ok()?;
+        let unit_end = tail
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_end);
+        let scale = match unit {
+            "h" => 3600.,
+            "m" => 60.,
+            "s" => 1.,
+            "ms" => 1e-3,
+            "us" | "µs" => 1e-6,
+            "ns" => 1e-9,
+            _ => return None,
+        };
+        seconds += value * scale;
+        rest = tail;
+    }
+
+    Some(Duration::milliseconds((seconds * 1000.) as i64))
+}
+
+fn runner_log_events(line: &str) -> Vec<AutoscalerEvent> {
+    let entry: RunnerLogLine = if let Ok(entry) = serde_json::from_str(line) {
+        entry
+    } else {
+        return Vec::new();
+    };
+    let (name, time) = if let (Some(name), Some(time)) = (&entry.name, entry.time) {
+        (name, time)
+    } else {
+        return Vec::new();
+    };
+
+    match entry.msg.as_str() {
+        // The machine is logged once it has been provisioned; the duration covers provisioning.
+        "Machine created" => {
+            let provisioning = entry
+                .duration
+                .as_deref()
+                .and_then(go_duration)
+                .unwrap_or_else(Duration::zero);
+            vec![
+                AutoscalerEvent::new(
+                    name,
+                    DOCKER_MACHINE,
+                    AutoscalerEventKind::Created,
+                    time - provisioning,
+                ),
+                AutoscalerEvent::new(name, DOCKER_MACHINE, AutoscalerEventKind::Ready, time),
+            ]
+        },
+        "Machine removed" => {
+            vec![AutoscalerEvent::new(
+                name,
+                DOCKER_MACHINE,
+                AutoscalerEventKind::Removed,
+                time,
+            )]
+        },
+        _ => Vec::new(),
+    }
+}
+
+/// Parse autoscaler events.
+///
+/// Blank lines are ignored. Events are returned in the order they appear.
+pub fn parse_autoscaler_events(
+    contents: &str,
+    format: AutoscalerLogFormat,
+) -> Result<Vec<AutoscalerEvent>, AutoscalerLogError> {
+    let mut events = Vec::new();
+    for (idx, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        match format {
+            AutoscalerLogFormat::Events => {
+                let event: EventLine = serde_json::from_str(line)
+                    .map_err(|err| AutoscalerLogError::invalid_line(idx + 1, err))?;
+                let kind = AutoscalerEventKind::from_name(&event.event)
+                    .ok_or_else(|| AutoscalerLogError::unknown_event(idx + 1, event.event))?;
+                events.push(AutoscalerEvent::new(
+                    &event.instance,
+                    &event.executor,
+                    kind,
+                    event.at,
+                ));
+            },
+            AutoscalerLogFormat::RunnerLog => events.extend(runner_log_events(line)),
+        }
+    }
+
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+    use ci_monitor_core::data::AutoscalerEventKind;
+
+    use crate::{parse_autoscaler_events, AutoscalerLogError, AutoscalerLogFormat};
+
+    #[test]
+    fn format_names() {
+        for format in AutoscalerLogFormat::ALL {
+            assert_eq!(AutoscalerLogFormat::from_name(format.name()), Some(*format));
+        }
+        assert_eq!(AutoscalerLogFormat::from_name("unknown"), None);
+    }
+
+    #[test]
+    fn go_duration() {
+        assert_eq!(
+            super::go_duration("1m2.5s"),
+            Some(Duration::milliseconds(62500)),
+        );
+        assert_eq!(super::go_duration("1h"), Some(Duration::hours(1)));
+        assert_eq!(
+            super::go_duration("250ms"),
+            Some(Duration::milliseconds(250)),
+        );
+        assert_eq!(super::go_duration("1d"), None);
+        assert_eq!(super::go_duration("s"), None);
+    }
+
+    #[test]
+    fn parse_events() {
+        let contents = concat!(
+            r#"{"instance": "pod-1", "executor": "kubernetes", "event": "created", "at": "2024-01-01T00:00:00Z"}"#,
+            "\n\n",
+            r#"{"instance": "pod-1", "executor": "kubernetes", "event": "removed", "at": "2024-01-01T01:00:00+01:00"}"#,
+            "\n",
+        );
+
+        let events = parse_autoscaler_events(contents, AutoscalerLogFormat::Events).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].instance, "pod-1");
+        assert_eq!(events[0].executor, "kubernetes");
+        assert_eq!(events[0].kind, AutoscalerEventKind::Created);
+        assert_eq!(events[1].kind, AutoscalerEventKind::Removed);
+        assert_eq!(events[0].at, events[1].at);
+
+        let err = parse_autoscaler_events(
+            r#"{"instance": "pod-1", "executor": "kubernetes", "event": "evicted", "at": "2024-01-01T00:00:00Z"}"#,
+            AutoscalerLogFormat::Events,
+        )
+        .unwrap_err();
+        if let AutoscalerLogError::UnknownEvent {
+            line,
+            event,
+        } = err
+        {
+            assert_eq!(line, 1);
+            assert_eq!(event, "evicted");
+        } else {
+            panic!("unexpected error: {:?}", err);
+        }
+
+        let err = parse_autoscaler_events("\nnot json", AutoscalerLogFormat::Events).unwrap_err();
+        if let AutoscalerLogError::InvalidLine {
+            line, ..
+        } = err
+        {
+            assert_eq!(line, 2);
+        } else {
+            panic!("unexpected error: {:?}", err);
+        }
+    }
+
+    #[test]
+    fn parse_runner_log() {
+        let contents = concat!(
+            "Starting multi-runner...\n",
+            r#"{"level":"info","msg":"Machine created","name":"runner-abc-1","time":"2024-01-01T00:01:30Z","duration":"1m30s"}"#,
+            "\n",
+            r#"{"level":"info","msg":"Checking for jobs...","time":"2024-01-01T00:02:00Z"}"#,
+            "\n",
+            r#"{"level":"info","msg":"Machine removed","name":"runner-abc-1","reason":"too many idle machines","time":"2024-01-01T00:30:00Z"}"#,
+            "\n",
+        );
+
+        let events = parse_autoscaler_events(contents, AutoscalerLogFormat::RunnerLog).unwrap();
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let events = events
+            .iter()
+            .map(|event| {
+                assert_eq!(event.instance, "runner-abc-1");
+                assert_eq!(event.executor, "docker+machine");
+                (event.kind, (event.at - start).num_seconds())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            [
+                (AutoscalerEventKind::Created, 0),
+                (AutoscalerEventKind::Ready, 90),
+                (AutoscalerEventKind::Removed, 1800),
+            ],
+        );
+    }
+}
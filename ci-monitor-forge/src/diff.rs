@@ -0,0 +1,154 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt::Debug;
+
+/// Fields which may hold secrets or personal information.
+const SENSITIVE_FIELDS: &[&str] = &["email", "variables"];
+/// Fields which change with every update.
+const IGNORED_FIELDS: &[&str] = &["cim_refreshed_at"];
+/// The value shown in place of sensitive values.
+const MASKED: &str = "<masked>";
+
+/// A field whose value was changed by an update.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FieldChange {
+    /// The name of the field.
+    pub field: String,
+    /// The previous value of the field.
+    pub old: String,
+    /// The new value of the field.
+    pub new: String,
+}
+
+/// Split the pretty `Debug` representation of a struct into its top-level fields.
+fn fields<T>(entity: &T) -> Vec<(String, String)>
+where
+    T: Debug,
+{
+    let repr = format!("{:#?}", entity);
+    let lines = repr.lines().collect::<Vec<_>>();
+    // Anything other than a struct with fields is treated as a single value.
+    if lines.len() < 3 {
+        return vec![(String::new(), repr)];
+    }
+
+    let mut fields: Vec<(String, String)> = Vec::new();
+    for line in &lines[1..lines.len() - 1] {
+        let is_field = line.starts_with("    ") && !line[4..].starts_with(' ');
+        if let Some((name, value)) = line.trim().split_once(": ").filter(|_| is_field) {
+            fields.push((name.into(), value.into()));
+        } else if let Some((_, value)) = fields.last_mut() {
+            value.push(' ');
+            value.push_str(line.trim());
+        }
+    }
+
+    for (_, value) in &mut fields {
+        if let Some(stripped) = value.strip_suffix(',') {
+            value.truncate(stripped.len());
+        }
+    }
+
+    fields
+}
+
+/// Compute the fields which differ between two versions of an entity.
+///
+/// Fields are compared using their `Debug` representations. Values of sensitive fields (such as
+/// email addresses and variables) are masked and bookkeeping fields which change on every update
+/// are ignored.
+pub fn field_changes<T>(old: &T, new: &T) -> Vec<FieldChange>
+where
+    T: Debug,
+{
+    fields(old)
+        .into_iter()
+        .zip(fields(new))
+        .filter(|((field, old), (_, new))| old != new && !IGNORED_FIELDS.contains(&field.as_str()))
+        .map(|((field, old), (_, new))| {
+            if SENSITIVE_FIELDS.contains(&field.as_str()) {
+                FieldChange {
+                    field,
+                    old: MASKED.into(),
+                    new: MASKED.into(),
+                }
+            } else {
+                FieldChange {
+                    field,
+                    old,
+                    new,
+                }
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FieldChange;
+
+    #[derive(Debug, Clone)]
+    struct Entity {
+        tags: Vec<String>,
+        email: Option<String>,
+        cim_refreshed_at: u64,
+    }
+
+    fn entity() -> Entity {
+        Entity {
+            tags: vec!["a".into()],
+            email: Some("user@example.com".into()),
+            cim_refreshed_at: 0,
+        }
+    }
+
+    #[test]
+    fn no_changes() {
+        let old = entity();
+        let mut new = entity();
+        new.cim_refreshed_at = 1;
+
+        assert_eq!(super::field_changes(&old, &new), []);
+    }
+
+    #[test]
+    fn changed_fields() {
+        let old = entity();
+        let mut new = entity();
+        new.tags.push("b".into());
+        new.email = None;
+
+        assert_eq!(
+            super::field_changes(&old, &new),
+            [
+                FieldChange {
+                    field: "tags".into(),
+                    old: "[ \"a\", ]".into(),
+                    new: "[ \"a\", \"b\", ]".into(),
+                },
+                FieldChange {
+                    field: "email".into(),
+                    old: "<masked>".into(),
+                    new: "<masked>".into(),
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn non_struct() {
+        assert_eq!(
+            super::field_changes(&1, &2),
+            [FieldChange {
+                field: String::new(),
+                old: "1".into(),
+                new: "2".into(),
+            }],
+        );
+    }
+}
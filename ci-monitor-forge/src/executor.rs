@@ -0,0 +1,1826 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::panic;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use governor::{Jitter, Quota, RateLimiter};
+use tokio::sync::{oneshot, watch};
+use tokio::task::JoinSet;
+use tokio::time::Instant;
+
+use crate::queue::{self, TaskQueue, TaskQueueError};
+use crate::{
+    EndpointLatency, Forge, ForgeError, ForgeTask, Heartbeat, InstanceTask, RateClass, RunBudget,
+    RunReport, RunStall, StalledTask, TaskHandlers,
+};
+
+/// A handle to cancel a running `TaskExecutor`.
+///
+/// Once canceled, no further tasks are started and running tasks are aborted at their next
+/// suspension point.
+#[derive(Debug, Clone)]
+pub struct TaskCanceller {
+    canceled: Arc<watch::Sender<bool>>,
+}
+
+impl Default for TaskCanceller {
+    fn default() -> Self {
+        Self {
+            canceled: Arc::new(watch::Sender::new(false)),
+        }
+    }
+}
+
+impl TaskCanceller {
+    /// Cancel the run.
+    pub fn cancel(&self) {
+        self.canceled.send_replace(true);
+    }
+
+    /// Whether the run has been canceled.
+    pub fn is_canceled(&self) -> bool {
+        *self.canceled.borrow()
+    }
+
+    async fn canceled(&self) {
+        let mut receiver = self.canceled.subscribe();
+        // The sender is owned by `self`, so this cannot fail.
+        let _ = receiver.wait_for(|canceled| *canceled).await;
+    }
+}
+
+/// A task which is currently running.
+struct InFlightTask {
+    task: InstanceTask,
+    started_at: Instant,
+    abort: Option<oneshot::Sender<Duration>>,
+}
+
+/// The forges of the instances tasks are routed to.
+struct Forges<F> {
+    forges: BTreeMap<String, Arc<F>>,
+}
+
+impl<F> Forges<F>
+where
+    F: Forge,
+{
+    fn get(&self, instance: &str) -> Option<&Arc<F>> {
+        self.forges.get(instance)
+    }
+
+    fn rate_class(&self, task: &InstanceTask) -> RateClass {
+        // Tasks without a forge fail without making any requests.
+        self.get(&task.instance)
+            .map_or(RateClass::Free, |forge| forge.rate_class(&task.task))
+    }
+
+    fn api_requests(&self) -> u64 {
+        self.forges.values().map(|forge| forge.api_requests()).sum()
+    }
+
+    fn api_latency(&self) -> Vec<EndpointLatency> {
+        self.forges
+            .values()
+            .flat_map(|forge| forge.api_latency())
+            .collect()
+    }
+}
+
+/// Limits which isolate the tasks of an instance from those of other instances.
+#[derive(Debug, Clone, Default)]
+struct InstanceLimits {
+    max_in_flight: Option<NonZeroUsize>,
+    rate_limit: Option<NonZeroU32>,
+    queue_depth: Option<usize>,
+}
+
+/// The tasks of an instance which are running or waiting for a free slot.
+#[derive(Default)]
+struct InstanceSlot {
+    in_flight: usize,
+    waiting: VecDeque<InstanceTask>,
+    /// Tasks beyond the queue depth which are held until there is room for them to wait.
+    held: Option<TaskQueue>,
+}
+
+impl InstanceSlot {
+    fn held(&self) -> usize {
+        self.held.as_ref().map_or(0, TaskQueue::len)
+    }
+}
+
+/// Tracks the tasks of each instance against its concurrency limit.
+struct InstanceSlots<'a> {
+    limits: &'a BTreeMap<String, InstanceLimits>,
+    default_queue_depth: Option<usize>,
+    spill_dir: Option<PathBuf>,
+    slots: BTreeMap<String, InstanceSlot>,
+}
+
+impl<'a> InstanceSlots<'a> {
+    fn new(
+        limits: &'a BTreeMap<String, InstanceLimits>,
+        default_queue_depth: Option<usize>,
+        spill_dir: Option<PathBuf>,
+    ) -> Self {
+        Self {
+            limits,
+            default_queue_depth,
+            spill_dir,
+            slots: BTreeMap::new(),
+        }
+    }
+
+    fn queue_depth(&self, instance: &str) -> Option<usize> {
+        self.limits
+            .get(instance)
+            .and_then(|limits| limits.queue_depth)
+            .or(self.default_queue_depth)
+    }
+
+    fn is_full(&self, instance: &str) -> bool {
+        let max_in_flight = self
+            .limits
+            .get(instance)
+            .and_then(|limits| limits.max_in_flight);
+        let in_flight = self.slots.get(instance).map_or(0, |slot| slot.in_flight);
+        max_in_flight.is_some_and(|max| in_flight >= max.get())
+    }
+
+    /// Hold a task until its instance has a free slot.
+    ///
+    /// The task is given back if the instance already has as many tasks waiting as it allows.
+    fn wait(&mut self, task: InstanceTask) -> Result<(), InstanceTask> {
+        let queue_depth = self.queue_depth(&task.instance);
+        let slot = self.slots.entry(task.instance.clone()).or_default();
+        if queue_depth.is_some_and(|depth| slot.waiting.len() >= depth) {
+            return Err(task);
+        }
+        slot.waiting.push_back(task);
+        Ok(())
+    }
+
+    /// Hold a task beyond the queue depth of its instance.
+    ///
+    /// Held tasks follow the waiting tasks of their instance without holding up tasks for other
+    /// instances. They are spilled to the spill directory beyond the queue depth.
+    fn hold(&mut self, task: InstanceTask) -> Result<(), TaskQueueError> {
+        let capacity = self.queue_depth(&task.instance);
+        let spill_dir = self.spill_dir.clone();
+        self.slots
+            .entry(task.instance.clone())
+            .or_default()
+            .held
+            .get_or_insert_with(|| TaskQueue::new(capacity, spill_dir))
+            .push_back(task)
+    }
+
+    /// A waiting task whose instance has a free slot.
+    ///
+    /// The next held task of the instance takes its place among the waiting tasks.
+    fn next_ready(&mut self) -> Result<Option<InstanceTask>, TaskQueueError> {
+        let instance = self
+            .slots
+            .iter()
+            .find(|(instance, slot)| !slot.waiting.is_empty() && !self.is_full(instance))
+            .map(|(instance, _)| instance.clone());
+        let slot = match instance.and_then(|instance| self.slots.get_mut(&instance)) {
+            Some(slot) => slot,
+            None => return Ok(None),
+        };
+        if let Some(held) = slot.held.as_mut() {
+            if let Some(task) = held.pop_front()? {
+                slot.waiting.push_back(task);
+            }
+        }
+        Ok(slot.waiting.pop_front())
+    }
+
+    /// Whether none of the given instances have a free slot.
+    fn all_full<'b, I>(&self, instances: I) -> bool
+    where
+        I: IntoIterator<Item = &'b String>,
+    {
+        let mut instances = instances.into_iter().peekable();
+        instances.peek().is_some() && instances.all(|instance| self.is_full(instance))
+    }
+
+    fn start(&mut self, instance: &str) {
+        self.slots.entry(instance.into()).or_default().in_flight += 1;
+    }
+
+    fn finish(&mut self, instance: &str) {
+        if let Some(slot) = self.slots.get_mut(instance) {
+            slot.in_flight = slot.in_flight.saturating_sub(1);
+        }
+    }
+
+    /// The number of tasks waiting for a free slot (including held tasks).
+    fn waiting(&self) -> usize {
+        self.slots
+            .values()
+            .map(|slot| slot.waiting.len() + slot.held())
+            .sum()
+    }
+
+    /// Remove all waiting and held tasks.
+    fn drain(&mut self, report: &mut RunReport) -> Vec<InstanceTask> {
+        let mut remaining = Vec::new();
+        for slot in self.slots.values_mut() {
+            remaining.extend(slot.waiting.drain(..));
+            if let Some(held) = slot.held.as_mut() {
+                loop {
+                    match held.pop_front() {
+                        Ok(Some(task)) => remaining.push(task),
+                        Ok(None) => break,
+                        Err(err) => record_queue_error(report, err),
+                    }
+                }
+            }
+        }
+        remaining
+    }
+
+    fn clear(&mut self) {
+        self.slots.clear();
+    }
+}
+
+/// Log a queue error, recording any tasks which were lost.
+fn record_queue_error(report: &mut RunReport, err: TaskQueueError) {
+    if let TaskQueueError::Restore {
+        lost, ..
+    } = &err
+    {
+        report.record_lost(*lost as u64);
+    }
+    tracing::warn!(%err, "queue error");
+}
+
+/// Run forge tasks until no more work is discovered.
+#[derive(Debug, Clone)]
+pub struct TaskExecutor {
+    rate_limit: NonZeroU32,
+    class_costs: BTreeMap<RateClass, u32>,
+    class_rate_limits: BTreeMap<RateClass, NonZeroU32>,
+    instances: BTreeMap<String, InstanceLimits>,
+    jitter: Duration,
+    deduplicate: bool,
+    timeout: Option<Duration>,
+    task_timeouts: BTreeMap<String, Duration>,
+    skipped: BTreeSet<String>,
+    queue_capacity: Option<usize>,
+    spill_dir: Option<PathBuf>,
+    discovery_backlog: Option<usize>,
+    watchdog: Option<Duration>,
+    restart_stalled: bool,
+    heartbeat: Option<(PathBuf, Duration)>,
+    max_duration: Option<Duration>,
+    max_api_requests: Option<u64>,
+    pending: Option<PathBuf>,
+    warm_start: Option<PathBuf>,
+    handlers: TaskHandlers,
+    canceller: TaskCanceller,
+}
+
+impl Default for TaskExecutor {
+    fn default() -> Self {
+        Self {
+            rate_limit: NonZeroU32::new(50).expect("non-zero literal"),
+            class_costs: BTreeMap::new(),
+            class_rate_limits: BTreeMap::new(),
+            instances: BTreeMap::new(),
+            jitter: Duration::from_secs(2),
+            deduplicate: false,
+            timeout: None,
+            task_timeouts: BTreeMap::new(),
+            skipped: BTreeSet::new(),
+            queue_capacity: None,
+            spill_dir: None,
+            discovery_backlog: None,
+            watchdog: None,
+            restart_stalled: false,
+            heartbeat: None,
+            max_duration: None,
+            max_api_requests: None,
+            pending: None,
+            warm_start: None,
+            handlers: TaskHandlers::default(),
+            canceller: TaskCanceller::default(),
+        }
+    }
+}
+
+impl TaskExecutor {
+    /// Set the number of rate limit units which may be used per second.
+    ///
+    /// Each task uses units according to the cost of its rate class as reported by the forge.
+    pub fn rate_limit(mut self, per_second: NonZeroU32) -> Self {
+        self.rate_limit = per_second;
+        self
+    }
+
+    /// Set the number of rate limit units used by tasks of a rate class.
+    ///
+    /// Costs beyond the rate limit are capped to it. Defaults to `RateClass::default_cost`.
+    pub fn class_cost(mut self, class: RateClass, cost: u32) -> Self {
+        self.class_costs.insert(class, cost);
+        self
+    }
+
+    /// Set the number of tasks of a rate class which may be started per second.
+    ///
+    /// Applies in addition to the overall rate limit.
+    pub fn class_rate_limit(mut self, class: RateClass, per_second: NonZeroU32) -> Self {
+        self.class_rate_limits.insert(class, per_second);
+        self
+    }
+
+    /// Set the maximum number of tasks of an instance which may run at once.
+    ///
+    /// Further tasks for the instance wait for one of its running tasks to complete while tasks
+    /// for other instances keep starting, so a slow instance cannot take over the run.
+    pub fn instance_concurrency<I>(mut self, instance: I, max: NonZeroUsize) -> Self
+    where
+        I: Into<String>,
+    {
+        self.instances
+            .entry(instance.into())
+            .or_default()
+            .max_in_flight = Some(max);
+        self
+    }
+
+    /// Give an instance its own rate limit.
+    ///
+    /// Tasks for the instance use units from this limit instead of the overall rate limit and
+    /// wait for them without holding up tasks for other instances. Rate class limits still apply
+    /// across all instances.
+    pub fn instance_rate_limit<I>(mut self, instance: I, per_second: NonZeroU32) -> Self
+    where
+        I: Into<String>,
+    {
+        self.instances
+            .entry(instance.into())
+            .or_default()
+            .rate_limit = Some(per_second);
+        self
+    }
+
+    /// Set the maximum number of tasks which may wait for a free slot of an instance.
+    ///
+    /// Tasks beyond this are left for a later run if `pending_tasks` is set. Otherwise, they are
+    /// held for the instance (spilling to the spill directory, if any) while tasks for other
+    /// instances continue. Defaults to the queue capacity. Requires a concurrency limit for the
+    /// instance in order to have any effect.
+    pub fn instance_queue_depth<I>(mut self, instance: I, depth: usize) -> Self
+    where
+        I: Into<String>,
+    {
+        self.instances
+            .entry(instance.into())
+            .or_default()
+            .queue_depth = Some(depth);
+        self
+    }
+
+    /// Set the maximum random delay added before starting a task.
+    pub fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Skip tasks which are identical to a task already run.
+    ///
+    /// Useful when the initial tasks overlap with tasks discovered while running.
+    pub fn deduplicate(mut self, deduplicate: bool) -> Self {
+        self.deduplicate = deduplicate;
+        self
+    }
+
+    /// Set the maximum time a task may run.
+    ///
+    /// Tasks which exceed the timeout fail with a retryable error. By default, tasks may run
+    /// indefinitely.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set the maximum time a kind of task may run.
+    ///
+    /// The kind is the name of the task as given by `ForgeTask::name`. Overrides the general
+    /// timeout for the kind of task.
+    pub fn task_timeout<K>(mut self, kind: K, timeout: Duration) -> Self
+    where
+        K: Into<String>,
+    {
+        self.task_timeouts.insert(kind.into(), timeout);
+        self
+    }
+
+    /// Skip a kind of task.
+    ///
+    /// The kind is the name of the task as given by `ForgeTask::name`. Tasks of the kind are
+    /// dropped without running, whether given to the run or discovered during it.
+    pub fn skip_task<K>(mut self, kind: K) -> Self
+    where
+        K: Into<String>,
+    {
+        self.skipped.insert(kind.into());
+        self
+    }
+
+    /// Set the maximum number of queued tasks to hold in memory.
+    ///
+    /// Tasks beyond this are spilled to the spill directory. Without a spill directory, the queue
+    /// is unbounded.
+    pub fn queue_capacity(mut self, capacity: usize) -> Self {
+        self.queue_capacity = Some(capacity);
+        self
+    }
+
+    /// Set the directory to spill queued tasks into once the queue capacity is reached.
+    pub fn spill_directory<P>(mut self, dir: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.spill_dir = Some(dir.into());
+        self
+    }
+
+    /// Defer discovery tasks while at least this many tasks are queued.
+    ///
+    /// Discovery tasks tend to schedule many more tasks, so holding them back lets the backlog
+    /// drain before it grows further. Deferred tasks run once the backlog is smaller or no other
+    /// tasks remain.
+    ///
+    /// This throttle counts tasks; it does not measure memory use. Use `queue_capacity` with a
+    /// spill directory to bound the memory held by queued tasks.
+    pub fn throttle_discovery_backlog(mut self, tasks: usize) -> Self {
+        self.discovery_backlog = Some(tasks);
+        self
+    }
+
+    /// Watch for periods during which no task completes.
+    ///
+    /// When no task completes within the interval while tasks are running, the in-flight tasks
+    /// are recorded in the report and logged.
+    pub fn watchdog(mut self, interval: Duration) -> Self {
+        self.watchdog = Some(interval);
+        self
+    }
+
+    /// Abort tasks which have been running for the whole of a stalled interval.
+    ///
+    /// Aborted tasks fail with a retryable error and are queued again once. Requires a watchdog
+    /// in order to have any effect.
+    pub fn restart_stalled(mut self, restart: bool) -> Self {
+        self.restart_stalled = restart;
+        self
+    }
+
+    /// Periodically write a heartbeat to a path while running.
+    ///
+    /// A final heartbeat is written when the run finishes. Crawl times recorded in an existing
+    /// heartbeat are kept.
+    pub fn heartbeat<P>(mut self, path: P, interval: Duration) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.heartbeat = Some((path.into(), interval));
+        self
+    }
+
+    /// Stop starting tasks once a run has taken this long.
+    ///
+    /// Running tasks are allowed to complete. Tasks still queued are left for a later run (see
+    /// `pending_tasks`).
+    pub fn max_duration(mut self, duration: Duration) -> Self {
+        self.max_duration = Some(duration);
+        self
+    }
+
+    /// Stop starting tasks once a run has made this many API requests.
+    ///
+    /// Running tasks are allowed to complete, so the budget may be exceeded by the requests they
+    /// make. Tasks still queued are left for a later run (see `pending_tasks`).
+    pub fn max_api_requests(mut self, requests: u64) -> Self {
+        self.max_api_requests = Some(requests);
+        self
+    }
+
+    /// Persist tasks left queued by an exhausted budget to a path.
+    ///
+    /// Tasks in the file are run before the given tasks at the start of the next run. The file is
+    /// removed once a run leaves no tasks behind. Canceled runs leave the file untouched.
+    pub fn pending_tasks<P>(mut self, path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.pending = Some(path.into());
+        self
+    }
+
+    /// Persist tasks for entities which were referenced but unknown to a path.
+    ///
+    /// Entities referred to by other entities (users, projects, and runners) are fetched when a
+    /// task first finds them missing, which usually requires the referring task to run again. The
+    /// fetches scheduled this way are written to the file at the end of a run and queued ahead of
+    /// the given tasks at the start of the next run so that crawls converge sooner. Canceled runs
+    /// leave the file untouched.
+    pub fn warm_start<P>(mut self, path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.warm_start = Some(path.into());
+        self
+    }
+
+    /// A handle which may be used to cancel runs of the executor.
+    pub fn canceller(&self) -> TaskCanceller {
+        self.canceller.clone()
+    }
+
+    fn timeout_for(&self, task: &ForgeTask) -> Option<Duration> {
+        self.task_timeouts
+            .get(task.name())
+            .copied()
+            .or(self.timeout)
+    }
+
+    fn cost_of(&self, class: RateClass, rate_limit: NonZeroU32) -> Option<NonZeroU32> {
+        let cost = self
+            .class_costs
+            .get(&class)
+            .copied()
+            .unwrap_or_else(|| class.default_cost());
+        NonZeroU32::new(cost.min(rate_limit.get()))
+    }
+
+    fn exhausted_budget(&self, started_at: Instant, api_requests: u64) -> Option<RunBudget> {
+        if self
+            .max_duration
+            .is_some_and(|max| started_at.elapsed() >= max)
+        {
+            Some(RunBudget::Duration)
+        } else if self.max_api_requests.is_some_and(|max| api_requests >= max) {
+            Some(RunBudget::ApiRequests)
+        } else {
+            None
+        }
+    }
+
+    /// Collect the tasks which were not started.
+    fn drain_remaining(
+        &self,
+        queue: &mut TaskQueue,
+        deferred: &mut VecDeque<InstanceTask>,
+        report: &mut RunReport,
+    ) -> Vec<InstanceTask> {
+        let mut remaining = Vec::new();
+        loop {
+            match queue.pop_front() {
+                Ok(Some(task)) => remaining.push(task),
+                Ok(None) => break,
+                Err(err) => record_queue_error(report, err),
+            }
+        }
+        remaining.extend(deferred.drain(..));
+        remaining
+    }
+
+    /// Whether discovery is held back; tasks waiting for their instance count towards the
+    /// backlog.
+    fn is_throttled(&self, queue: &TaskQueue, waiting: usize) -> bool {
+        self.discovery_backlog
+            .is_some_and(|backlog| queue.len() + waiting >= backlog)
+    }
+
+    fn next_task(
+        &self,
+        queue: &mut TaskQueue,
+        deferred: &mut VecDeque<InstanceTask>,
+        waiting: usize,
+        report: &mut RunReport,
+    ) -> Option<InstanceTask> {
+        if !self.is_throttled(queue, waiting) {
+            if let Some(task) = deferred.pop_front() {
+                return Some(task);
+            }
+        }
+
+        loop {
+            match queue.pop_front() {
+                Ok(Some(task)) => {
+                    if task.task.is_discovery() && self.is_throttled(queue, waiting) {
+                        report.record_throttled();
+                        deferred.push_back(task);
+                    } else {
+                        return Some(task);
+                    }
+                },
+                Ok(None) => return deferred.pop_front(),
+                Err(err) => record_queue_error(report, err),
+            }
+        }
+    }
+
+    fn enqueue<I>(&self, queue: &mut TaskQueue, tasks: I, report: &mut RunReport)
+    where
+        I: IntoIterator<Item = InstanceTask>,
+    {
+        for task in tasks {
+            if self.skipped.contains(task.task.name()) {
+                continue;
+            }
+            if let Err(err) = queue.push_back(task) {
+                tracing::warn!(%err, "queue error");
+            }
+        }
+        report.record_spilled(queue.spilled() as u64);
+    }
+
+    fn write_heartbeat(
+        &self,
+        heartbeat: &mut Heartbeat,
+        queued: usize,
+        in_flight: usize,
+        report: &RunReport,
+    ) {
+        let path = if let Some((path, _)) = self.heartbeat.as_ref() {
+            path
+        } else {
+            return;
+        };
+
+        heartbeat.at = Utc::now();
+        heartbeat.queued = queued as u64;
+        heartbeat.in_flight = in_flight as u64;
+        heartbeat.executed = report.executed();
+        heartbeat.failed = report.failed();
+        if let Err(err) = heartbeat.write(path) {
+            tracing::warn!(%err, "heartbeat error");
+        }
+    }
+
+    /// Handle a period during which no task completed.
+    fn handle_stall(
+        &self,
+        interval: Duration,
+        in_flight: &mut BTreeMap<u64, InFlightTask>,
+        report: &mut RunReport,
+    ) {
+        let now = Instant::now();
+        let mut restarted = 0;
+        let tasks = in_flight
+            .values_mut()
+            .map(|running| {
+                let elapsed = now.duration_since(running.started_at);
+                if self.restart_stalled && elapsed >= interval {
+                    if let Some(abort) = running.abort.take() {
+                        // The task may have completed in the meantime.
+                        if abort.send(interval).is_ok() {
+                            restarted += 1;
+                        }
+                    }
+                }
+                StalledTask {
+                    kind: running.task.task.name(),
+                    task: running.task.description(),
+                    running: elapsed.as_secs_f64(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        tracing::warn!(?interval, in_flight = tasks.len(), "no progress");
+        for task in &tasks {
+            tracing::warn!(running = task.running, task = %task.task, "stalled task");
+        }
+
+        report.record_stall(RunStall {
+            detected_at: Utc::now(),
+            idle: interval.as_secs_f64(),
+            in_flight: tasks,
+            restarted,
+        });
+    }
+
+    /// Use the given handlers for custom tasks.
+    ///
+    /// Custom tasks without a registered handler are passed to the forge.
+    pub fn handlers(mut self, handlers: TaskHandlers) -> Self {
+        self.handlers = handlers;
+        self
+    }
+
+    /// Run tasks against a forge.
+    ///
+    /// Tasks discovered while running are also executed. Returns a report once all tasks have
+    /// completed or the run has been canceled.
+    pub async fn run<F, I>(&self, forge: Arc<F>, tasks: I) -> RunReport
+    where
+        F: Forge + Send + Sync + 'static,
+        I: IntoIterator<Item = ForgeTask>,
+    {
+        let forges = [(String::new(), forge)].into_iter().collect();
+        let tasks = tasks.into_iter().map(|task| InstanceTask::new("", task));
+        self.run_instances(forges, tasks).await
+    }
+
+    /// Run tasks against the forges of several instances.
+    ///
+    /// Each task is run by the forge of its instance. Tasks discovered while running belong to the
+    /// same instance as the task which discovered them. Tasks for instances without a forge fail.
+    /// Returns a report once all tasks have completed or the run has been canceled.
+    pub async fn run_instances<F, I>(&self, forges: BTreeMap<String, Arc<F>>, tasks: I) -> RunReport
+    where
+        F: Forge + Send + Sync + 'static,
+        I: IntoIterator<Item = InstanceTask>,
+    {
+        let forges = Forges {
+            forges,
+        };
+        let mut report = RunReport::start();
+        let mut queue = TaskQueue::new(self.queue_capacity, self.spill_dir.clone());
+        let mut deferred = VecDeque::new();
+        let mut slots =
+            InstanceSlots::new(&self.instances, self.queue_capacity, self.spill_dir.clone());
+        let mut shed = Vec::new();
+        if let Some(path) = self.pending.as_ref() {
+            match queue::read_pending(path) {
+                Ok(pending) => self.enqueue(&mut queue, pending, &mut report),
+                Err(err) => {
+                    tracing::warn!(%err, "queue error");
+                },
+            }
+        }
+        if let Some(path) = self.warm_start.as_ref() {
+            match queue::read_pending(path) {
+                Ok(misses) => {
+                    report.record_warm_start(misses.len() as u64);
+                    self.enqueue(&mut queue, misses, &mut report);
+                },
+                Err(err) => {
+                    tracing::warn!(%err, "queue error");
+                },
+            }
+        }
+        self.enqueue(&mut queue, tasks, &mut report);
+        let mut misses = BTreeMap::new();
+        let mut running = JoinSet::new();
+        let mut count = 0;
+        let mut seen = HashSet::new();
+        let mut in_flight = BTreeMap::new();
+        let mut restarted = HashSet::new();
+        let mut retried = HashSet::new();
+        let mut resumed: BTreeMap<String, HashSet<String>> = BTreeMap::new();
+        let mut last_progress = Instant::now();
+        let mut canceled = false;
+        let started_at = Instant::now();
+        let initial_api_requests = forges.api_requests();
+        let mut budget = None;
+        let mut heartbeat = Heartbeat::start(report.started_at);
+        if let Some((path, _)) = self.heartbeat.as_ref() {
+            if let Ok(Some(previous)) = Heartbeat::read(path) {
+                heartbeat.last_complete_crawls = previous.last_complete_crawls;
+            }
+        }
+        let heartbeat_interval = self.heartbeat.as_ref().map(|(_, interval)| *interval);
+        let mut next_heartbeat = Instant::now();
+        let governor = RateLimiter::direct(Quota::per_second(self.rate_limit));
+        let class_governors = self
+            .class_rate_limits
+            .iter()
+            .map(|(&class, &limit)| (class, RateLimiter::direct(Quota::per_second(limit))))
+            .collect::<BTreeMap<_, _>>();
+        let instance_governors = self
+            .instances
+            .iter()
+            .filter_map(|(instance, limits)| {
+                let limit = limits.rate_limit?;
+                let governor = Arc::new(RateLimiter::direct(Quota::per_second(limit)));
+                Some((instance.as_str(), (governor, limit)))
+            })
+            .collect::<BTreeMap<_, _>>();
+        let jitter = Jitter::up_to(self.jitter);
+
+        loop {
+            loop {
+                // Waiting tasks go first. Nothing can start until a task completes once every
+                // instance is at its limit, so the queue is left alone until then.
+                let ready = match slots.next_ready() {
+                    Ok(ready) => ready,
+                    Err(err) => {
+                        record_queue_error(&mut report, err);
+                        continue;
+                    },
+                };
+                let task = if let Some(task) = ready {
+                    task
+                } else if slots.all_full(forges.forges.keys()) {
+                    break;
+                } else if let Some(task) =
+                    self.next_task(&mut queue, &mut deferred, slots.waiting(), &mut report)
+                {
+                    task
+                } else {
+                    break;
+                };
+
+                if self.canceller.is_canceled() || budget.is_some() {
+                    queue.push_front(task);
+                    break;
+                }
+
+                let api_requests = forges.api_requests().saturating_sub(initial_api_requests);
+                budget = self.exhausted_budget(started_at, api_requests);
+                if let Some(exhausted) = budget {
+                    tracing::info!(
+                        budget = ?exhausted,
+                        running = in_flight.len(),
+                        "budget exhausted; waiting for running tasks"
+                    );
+                    queue.push_front(task);
+                    break;
+                }
+
+                if slots.is_full(&task.instance) {
+                    if let Err(task) = slots.wait(task) {
+                        if self.pending.is_some() {
+                            report.record_shed(&task.instance);
+                            shed.push(task);
+                        } else if let Err(err) = slots.hold(task) {
+                            // There is nowhere to leave the task for a later run, so it is held
+                            // until its instance has room for it.
+                            tracing::warn!(%err, "queue error");
+                        }
+                    }
+                    continue;
+                }
+
+                // `ForgeTask` is not hashable due to custom payloads; use its representation.
+                if self.deduplicate && !seen.insert(task.description()) {
+                    continue;
+                }
+
+                let class = forges.rate_class(&task);
+                if let Some(class_governor) = class_governors.get(&class) {
+                    class_governor.until_ready_with_jitter(jitter).await;
+                }
+                let instance_governor = instance_governors.get(task.instance.as_str());
+                let throttle = if let Some((instance_governor, limit)) = instance_governor {
+                    // Wait within the task so that other instances are not held up.
+                    self.cost_of(class, *limit)
+                        .map(|cost| (instance_governor.clone(), cost))
+                } else {
+                    if let Some(cost) = self.cost_of(class, self.rate_limit) {
+                        // The cost is capped to the burst size, so there is always enough
+                        // capacity.
+                        let _ = governor.until_n_ready_with_jitter(cost, jitter).await;
+                    }
+                    None
+                };
+
+                tracing::info!(
+                    count,
+                    remaining = queue.len() + deferred.len() + slots.waiting(),
+                    task = %task.description(),
+                    "performing task"
+                );
+                count += 1;
+
+                let handler = if let ForgeTask::Custom {
+                    name, ..
+                } = &task.task
+                {
+                    self.handlers.get(name).cloned()
+                } else {
+                    None
+                };
+                let timeout = self.timeout_for(&task.task);
+                let inner_forge = forges.get(&task.instance).cloned();
+                let (abort, aborted) = oneshot::channel();
+                let id = count;
+                slots.start(&task.instance);
+                in_flight.insert(
+                    id,
+                    InFlightTask {
+                        task: task.clone(),
+                        started_at: Instant::now(),
+                        abort: Some(abort),
+                    },
+                );
+                running.spawn(async move {
+                    if let Some((governor, cost)) = throttle {
+                        // The cost is capped to the burst size, so there is always enough capacity.
+                        let _ = governor.until_n_ready_with_jitter(cost, jitter).await;
+                    }
+                    let run = async {
+                        match (handler, &task.task, inner_forge) {
+                            (
+                                Some(handler),
+                                ForgeTask::Custom {
+                                    payload, ..
+                                },
+                                _,
+                            ) => handler.run_task_async(payload).await,
+                            (_, _, Some(forge)) => {
+                                if let Some(page) = task.resume_page {
+                                    forge.resume_task_async(task.task.clone(), page).await
+                                } else {
+                                    forge.run_task_async(task.task.clone()).await
+                                }
+                            },
+                            (_, _, None) => {
+                                Err(ForgeError::UnknownInstance {
+                                    instance: task.instance.clone(),
+                                })
+                            },
+                        }
+                    };
+                    let run = async {
+                        if let Some(timeout) = timeout {
+                            tokio::time::timeout(timeout, run).await.unwrap_or(Err(
+                                ForgeError::Timeout {
+                                    timeout,
+                                },
+                            ))
+                        } else {
+                            run.await
+                        }
+                    };
+                    let res = tokio::select! {
+                        res = run => res,
+                        Ok(idle) = aborted => {
+                            Err(ForgeError::Stalled {
+                                idle,
+                            })
+                        },
+                    };
+                    (id, task, res)
+                });
+            }
+
+            let joined = tokio::select! {
+                biased;
+
+                () = self.canceller.canceled(), if !canceled => {
+                    canceled = true;
+                    let queued = queue.len() + deferred.len() + slots.waiting();
+                    report.record_canceled(queued as u64);
+                    queue.clear();
+                    deferred.clear();
+                    slots.clear();
+                    running.abort_all();
+                    in_flight.clear();
+                    continue;
+                },
+                () = tokio::time::sleep_until(
+                    last_progress + self.watchdog.unwrap_or_default(),
+                ), if self.watchdog.is_some() && !running.is_empty() => {
+                    let interval = self.watchdog.unwrap_or_default();
+                    self.handle_stall(interval, &mut in_flight, &mut report);
+                    last_progress = Instant::now();
+                    continue;
+                },
+                () = tokio::time::sleep_until(next_heartbeat), if heartbeat_interval.is_some() => {
+                    let queued = queue.len() + deferred.len() + slots.waiting();
+                    self.write_heartbeat(&mut heartbeat, queued, in_flight.len(), &report);
+                    next_heartbeat = Instant::now() + heartbeat_interval.unwrap_or_default();
+                    continue;
+                },
+                joined = running.join_next() => joined,
+            };
+            last_progress = Instant::now();
+            let (id, task, res) = match joined {
+                Some(Ok(joined)) => joined,
+                Some(Err(err)) if err.is_cancelled() => {
+                    report.record_canceled(1);
+                    continue;
+                },
+                Some(Err(err)) => panic::resume_unwind(err.into_panic()),
+                None => break,
+            };
+
+            in_flight.remove(&id);
+            slots.finish(&task.instance);
+            report.record_task(&task.task, res.as_ref());
+            report.record_api_requests(forges.api_requests());
+            match res {
+                Ok(outcome) => {
+                    let description = task.description();
+                    // Tasks discovered by an earlier, partially failed run were already queued.
+                    let discovered = resumed.remove(&description).unwrap_or_default();
+                    let tasks = outcome
+                        .additional_tasks
+                        .into_iter()
+                        .map(|additional| InstanceTask::new(task.instance.clone(), additional))
+                        .filter(|additional| !discovered.contains(&additional.description()))
+                        .collect::<Vec<_>>();
+                    if let Some(partial) = outcome.partial.as_ref() {
+                        report.record_partial(&task.task, partial);
+                        tracing::warn!(
+                            processed = partial.processed,
+                            reason = %partial.reason,
+                            "task partially failed"
+                        );
+                        // Retryable partial failures resume from the failing page as long as
+                        // each run gets further. Tasks which cannot be resumed are retried once
+                        // to fetch the remaining items.
+                        let resume_page = partial
+                            .resume_page
+                            .filter(|&page| task.resume_page.is_none_or(|start| start < page));
+                        if partial.retryable
+                            && !canceled
+                            && (resume_page.is_some() || retried.insert(description.clone()))
+                        {
+                            let discovered = discovered
+                                .into_iter()
+                                .chain(tasks.iter().map(InstanceTask::description))
+                                .collect();
+                            resumed.insert(description.clone(), discovered);
+                            // The retry must not be mistaken for a duplicate of the first run.
+                            seen.remove(&description);
+                            let mut retry = task.clone();
+                            retry.resume_page = resume_page;
+                            self.enqueue(&mut queue, [retry], &mut report);
+                        }
+                    }
+                    if !canceled {
+                        if self.warm_start.is_some() && !task.task.is_discovery() {
+                            let referenced = tasks
+                                .iter()
+                                .filter(|additional| additional.task.is_reference_update())
+                                .map(|additional| (additional.description(), additional.clone()));
+                            misses.extend(referenced);
+                        }
+                        self.enqueue(&mut queue, tasks, &mut report);
+                    }
+                },
+                Err(err) => {
+                    tracing::warn!(?err, "task failed");
+                    // Stalled tasks are only restarted once.
+                    if let ForgeError::Stalled {
+                        ..
+                    } = err
+                    {
+                        let description = task.description();
+                        if !canceled && restarted.insert(description.clone()) {
+                            // The restart must not be mistaken for a duplicate of the stalled run.
+                            seen.remove(&description);
+                            self.enqueue(&mut queue, [task], &mut report);
+                        }
+                    }
+                },
+            }
+        }
+
+        let mut remaining = self.drain_remaining(&mut queue, &mut deferred, &mut report);
+        remaining.extend(slots.drain(&mut report));
+        if let Some(exhausted) = budget {
+            report.record_budget_exhausted(exhausted, remaining.len() as u64);
+        }
+        remaining.extend(shed);
+        if let Some(path) = self.pending.as_ref().filter(|_| !canceled) {
+            if let Err(err) = queue::write_pending(path, &remaining) {
+                tracing::warn!(%err, "queue error");
+            }
+        }
+        if let Some(path) = self.warm_start.as_ref().filter(|_| !canceled) {
+            let misses = misses.into_values().collect::<Vec<_>>();
+            if let Err(err) = queue::write_pending(path, &misses) {
+                tracing::warn!(%err, "queue error");
+            }
+        }
+
+        report.record_api_latency(forges.api_latency());
+        report.finish(forges.api_requests());
+        heartbeat.finished = true;
+        self.write_heartbeat(&mut heartbeat, 0, 0, &report);
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::future;
+    use std::num::{NonZeroU32, NonZeroUsize};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+    use chrono::Utc;
+    use tempfile::TempDir;
+
+    use crate::{
+        Forge, ForgeError, ForgeErrorKind, ForgeTask, ForgeTaskOutcome, Heartbeat, InstanceTask,
+        PartialFailure, RateClass, RunBudget, TaskExecutor, TaskHandler, TaskHandlers,
+    };
+
+    #[derive(Default)]
+    struct TestForge {
+        requests: AtomicU64,
+        user_listings: AtomicU64,
+    }
+
+    #[async_trait]
+    impl Forge for TestForge {
+        async fn run_task_async(&self, task: ForgeTask) -> Result<ForgeTaskOutcome, ForgeError> {
+            self.requests.fetch_add(1, Ordering::Relaxed);
+            match task {
+                ForgeTask::DiscoverRunners => {
+                    let mut outcome = ForgeTaskOutcome::default();
+                    outcome.additional_tasks.push(ForgeTask::UpdateRunner {
+                        id: 1,
+                    });
+                    outcome.additional_tasks.push(ForgeTask::UpdateRunner {
+                        id: 2,
+                    });
+                    Ok(outcome)
+                },
+                ForgeTask::UpdateRunner {
+                    id: 2,
+                } => {
+                    Err(ForgeError::Other {
+                        details: "runner 2 is broken".into(),
+                    })
+                },
+                ForgeTask::UpdateRunner {
+                    id: 3,
+                } => future::pending().await,
+                ForgeTask::UpdateRunner {
+                    ..
+                } => Ok(ForgeTaskOutcome::default()),
+                ForgeTask::UpdateJob {
+                    job, ..
+                } => {
+                    let mut outcome = ForgeTaskOutcome::default();
+                    outcome.additional_tasks.push(ForgeTask::UpdateUser {
+                        user: job,
+                    });
+                    Ok(outcome)
+                },
+                ForgeTask::UpdateUser {
+                    ..
+                } => Ok(ForgeTaskOutcome::default()),
+                ForgeTask::DiscoverUsers => self.list_users(1),
+                task => {
+                    Err(ForgeError::Unknown {
+                        task,
+                    })
+                },
+            }
+        }
+
+        async fn resume_task_async(
+            &self,
+            task: ForgeTask,
+            page: u64,
+        ) -> Result<ForgeTaskOutcome, ForgeError> {
+            self.requests.fetch_add(1, Ordering::Relaxed);
+            match task {
+                ForgeTask::DiscoverUsers => self.list_users(page),
+                task => {
+                    Err(ForgeError::Unknown {
+                        task,
+                    })
+                },
+            }
+        }
+
+        fn api_requests(&self) -> u64 {
+            self.requests.load(Ordering::Relaxed)
+        }
+    }
+
+    impl TestForge {
+        // Users are listed with one per page over three pages. Each listing fails after its first
+        // page until the last page is reached.
+        fn list_users(&self, page: u64) -> Result<ForgeTaskOutcome, ForgeError> {
+            self.user_listings.fetch_add(1, Ordering::Relaxed);
+            let mut outcome = ForgeTaskOutcome::default();
+            outcome.additional_tasks.push(ForgeTask::UpdateUser {
+                user: page,
+            });
+            if page < 3 {
+                let err = ForgeError::Connection {
+                    details: "reset".into(),
+                };
+                outcome.partial = Some(PartialFailure::new(1, &err).resume_from(page + 1));
+            }
+            Ok(outcome)
+        }
+    }
+
+    #[tokio::test]
+    async fn run_discovered_tasks() {
+        let executor = TaskExecutor::default().jitter(Duration::ZERO);
+        let forge = Arc::new(TestForge::default());
+
+        let report = executor.run(forge, [ForgeTask::DiscoverRunners]).await;
+
+        assert_eq!(report.executed(), 3);
+        assert_eq!(report.failed(), 1);
+        assert_eq!(report.tasks["discover_runners"].executed, 1);
+        assert_eq!(report.tasks["update_runner"].executed, 2);
+        assert_eq!(report.tasks["update_runner"].failed, 1);
+        assert_eq!(report.failures[0].reason, "runner 2 is broken");
+        assert_eq!(report.api_requests, 3);
+    }
+
+    #[tokio::test]
+    async fn run_deduplicated_tasks() {
+        let executor = TaskExecutor::default()
+            .jitter(Duration::ZERO)
+            .deduplicate(true);
+        let forge = Arc::new(TestForge::default());
+
+        let tasks = [
+            ForgeTask::DiscoverRunners,
+            ForgeTask::UpdateRunner {
+                id: 1,
+            },
+            ForgeTask::DiscoverRunners,
+        ];
+        let report = executor.run(forge, tasks).await;
+
+        assert_eq!(report.tasks["discover_runners"].executed, 1);
+        assert_eq!(report.tasks["update_runner"].executed, 2);
+        assert_eq!(report.api_requests, 3);
+    }
+
+    #[tokio::test]
+    async fn run_skipped_tasks() {
+        let executor = TaskExecutor::default()
+            .jitter(Duration::ZERO)
+            .skip_task("update_runner");
+        let forge = Arc::new(TestForge::default());
+
+        let tasks = [
+            ForgeTask::DiscoverRunners,
+            ForgeTask::UpdateRunner {
+                id: 2,
+            },
+        ];
+        let report = executor.run(forge, tasks).await;
+
+        assert_eq!(report.executed(), 1);
+        assert_eq!(report.failed(), 0);
+        assert!(!report.tasks.contains_key("update_runner"));
+        assert_eq!(report.api_requests, 1);
+    }
+
+    #[tokio::test]
+    async fn run_instance_tasks() {
+        let executor = TaskExecutor::default().jitter(Duration::ZERO);
+        let forge_a = Arc::new(TestForge::default());
+        let forge_b = Arc::new(TestForge::default());
+        let forges = [("a".into(), forge_a.clone()), ("b".into(), forge_b.clone())]
+            .into_iter()
+            .collect();
+
+        let tasks = [
+            InstanceTask::new("a", ForgeTask::DiscoverRunners),
+            InstanceTask::new(
+                "b",
+                ForgeTask::UpdateRunner {
+                    id: 1,
+                },
+            ),
+            InstanceTask::new("c", ForgeTask::DiscoverRunners),
+        ];
+        let report = executor.run_instances(forges, tasks).await;
+
+        // Discovered tasks are routed to the forge which discovered them.
+        assert_eq!(forge_a.api_requests(), 3);
+        assert_eq!(forge_b.api_requests(), 1);
+        assert_eq!(report.api_requests, 4);
+        assert_eq!(report.executed(), 5);
+        assert_eq!(report.failed(), 2);
+        assert!(report
+            .failures
+            .iter()
+            .any(|failure| failure.error == ForgeErrorKind::UnknownInstance));
+    }
+
+    #[tokio::test]
+    async fn run_isolated_instances() {
+        let dir = TempDir::new().unwrap();
+        let executor = TaskExecutor::default()
+            .jitter(Duration::ZERO)
+            .queue_capacity(1)
+            .spill_directory(dir.path())
+            .task_timeout("update_runner", Duration::from_millis(50))
+            .instance_concurrency("slow", NonZeroUsize::MIN)
+            .instance_queue_depth("slow", 2);
+        let healthy = Arc::new(TestForge::default());
+        let slow = Arc::new(TestForge::default());
+        let forges = [
+            ("healthy".into(), healthy.clone()),
+            ("slow".into(), slow.clone()),
+        ]
+        .into_iter()
+        .collect();
+
+        let stuck = || {
+            InstanceTask::new(
+                "slow",
+                ForgeTask::UpdateRunner {
+                    id: 3,
+                },
+            )
+        };
+        let tasks = [
+            stuck(),
+            stuck(),
+            stuck(),
+            InstanceTask::new("healthy", ForgeTask::DiscoverRunners),
+        ];
+        let run = executor.run_instances(forges, tasks);
+        let progress = async {
+            tokio::time::sleep(Duration::from_millis(25)).await;
+            (healthy.api_requests(), slow.api_requests())
+        };
+        let (report, progress) = tokio::join!(run, progress);
+
+        // The healthy instance finishes while the saturated instance runs one task at a time.
+        assert_eq!(progress, (3, 1));
+        // Tasks held back for the saturated instance run once it has a free slot.
+        assert_eq!(slow.api_requests(), 3);
+        assert_eq!(healthy.api_requests(), 3);
+        assert_eq!(report.executed(), 6);
+        assert!(report.shed.is_empty());
+        assert_eq!(report.completed, 1.);
+        assert!(report.max_spilled > 0);
+    }
+
+    #[tokio::test]
+    async fn run_shed_instance_tasks() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("pending.jsonl");
+        let executor = TaskExecutor::default()
+            .jitter(Duration::ZERO)
+            .task_timeout("update_runner", Duration::from_millis(20))
+            .instance_concurrency("slow", NonZeroUsize::MIN)
+            .instance_queue_depth("slow", 1)
+            .pending_tasks(&path);
+        let healthy = Arc::new(TestForge::default());
+        let slow = Arc::new(TestForge::default());
+        let forges = [
+            ("healthy".into(), healthy.clone()),
+            ("slow".into(), slow.clone()),
+        ]
+        .into_iter()
+        .collect();
+
+        let stuck = || {
+            InstanceTask::new(
+                "slow",
+                ForgeTask::UpdateRunner {
+                    id: 3,
+                },
+            )
+        };
+        let tasks = [
+            stuck(),
+            stuck(),
+            stuck(),
+            InstanceTask::new("healthy", ForgeTask::DiscoverRunners),
+        ];
+        let report = executor.run_instances(forges, tasks).await;
+
+        // The slow instance runs one task at a time and holds one more back.
+        assert_eq!(slow.api_requests(), 2);
+        assert_eq!(healthy.api_requests(), 3);
+        assert_eq!(report.executed(), 5);
+        assert_eq!(report.shed["slow"], 1);
+        assert!(report.completed < 1.);
+        // The shed task is left for the next run.
+        let pending = crate::queue::read_pending(&path).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].instance, "slow");
+    }
+
+    #[tokio::test]
+    async fn run_held_instance_tasks() {
+        let executor = TaskExecutor::default()
+            .jitter(Duration::ZERO)
+            .task_timeout("update_runner", Duration::from_millis(200))
+            .instance_concurrency("slow", NonZeroUsize::MIN)
+            .instance_queue_depth("slow", 1);
+        let healthy = Arc::new(TestForge::default());
+        let slow = Arc::new(TestForge::default());
+        let forges = [
+            ("healthy".into(), healthy.clone()),
+            ("slow".into(), slow.clone()),
+        ]
+        .into_iter()
+        .collect();
+
+        let stuck = || {
+            InstanceTask::new(
+                "slow",
+                ForgeTask::UpdateRunner {
+                    id: 3,
+                },
+            )
+        };
+        let tasks = [
+            stuck(),
+            stuck(),
+            stuck(),
+            InstanceTask::new("healthy", ForgeTask::DiscoverRunners),
+        ];
+        let check = async {
+            // The first stuck task is still running; the others are waiting or held.
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            (healthy.api_requests(), slow.api_requests())
+        };
+        let (report, during) = tokio::join!(executor.run_instances(forges, tasks), check);
+
+        // Tasks for the healthy instance complete while the slow instance is saturated.
+        assert_eq!(during, (3, 1));
+        // Without pending tasks, tasks beyond the queue depth are held for their instance.
+        assert_eq!(slow.api_requests(), 3);
+        assert_eq!(healthy.api_requests(), 3);
+        assert_eq!(report.executed(), 6);
+        assert!(report.shed.is_empty());
+        assert_eq!(report.completed, 1.);
+    }
+
+    #[tokio::test]
+    async fn run_instance_rate_limits() {
+        // The overall rate limit only allows one task per second, but tasks for the `fast`
+        // instance use their own budget.
+        let executor = TaskExecutor::default()
+            .rate_limit(NonZeroU32::MIN)
+            .instance_rate_limit("fast", NonZeroU32::new(50).unwrap())
+            .jitter(Duration::ZERO);
+        let forge_a = Arc::new(TestForge::default());
+        let forge_b = Arc::new(TestForge::default());
+        let forges = [("a".into(), forge_a), ("fast".into(), forge_b)]
+            .into_iter()
+            .collect();
+
+        let tasks = [
+            InstanceTask::new(
+                "a",
+                ForgeTask::UpdateRunner {
+                    id: 1,
+                },
+            ),
+            InstanceTask::new("fast", ForgeTask::DiscoverRunners),
+        ];
+        let run = executor.run_instances(forges, tasks);
+        let report = tokio::time::timeout(Duration::from_millis(500), run)
+            .await
+            .unwrap();
+
+        assert_eq!(report.executed(), 4);
+        assert_eq!(report.api_requests, 4);
+    }
+
+    #[tokio::test]
+    async fn run_spilled_tasks() {
+        let dir = TempDir::new().unwrap();
+        let executor = TaskExecutor::default()
+            .jitter(Duration::ZERO)
+            .queue_capacity(1)
+            .spill_directory(dir.path());
+        let forge = Arc::new(TestForge::default());
+
+        let report = executor.run(forge, [ForgeTask::DiscoverRunners]).await;
+
+        assert_eq!(report.executed(), 3);
+        assert_eq!(report.tasks["update_runner"].executed, 2);
+        assert_eq!(report.max_spilled, 1);
+        assert_eq!(report.lost, 0);
+    }
+
+    #[tokio::test]
+    async fn run_throttled_discovery() {
+        let executor = TaskExecutor::default()
+            .jitter(Duration::ZERO)
+            .throttle_discovery_backlog(1);
+        let forge = Arc::new(TestForge::default());
+
+        let tasks = [
+            ForgeTask::DiscoverRunners,
+            ForgeTask::UpdateRunner {
+                id: 1,
+            },
+            ForgeTask::UpdateRunner {
+                id: 4,
+            },
+        ];
+        let report = executor.run(forge, tasks).await;
+
+        assert_eq!(report.throttled, 1);
+        assert_eq!(report.tasks["discover_runners"].executed, 1);
+        assert_eq!(report.tasks["update_runner"].executed, 4);
+    }
+
+    #[tokio::test]
+    async fn run_timed_out_tasks() {
+        let executor = TaskExecutor::default()
+            .jitter(Duration::ZERO)
+            .timeout(Duration::from_secs(3600))
+            .task_timeout("update_runner", Duration::from_millis(10));
+        let forge = Arc::new(TestForge::default());
+
+        let tasks = [
+            ForgeTask::UpdateRunner {
+                id: 1,
+            },
+            ForgeTask::UpdateRunner {
+                id: 3,
+            },
+        ];
+        let report = executor.run(forge, tasks).await;
+
+        assert_eq!(report.executed(), 2);
+        assert_eq!(report.failed(), 1);
+        assert_eq!(report.failures[0].error, ForgeErrorKind::Timeout);
+        assert!(report.failures[0].retryable);
+        assert_eq!(report.canceled, 0);
+    }
+
+    #[tokio::test]
+    async fn run_stalled_tasks() {
+        // Restarts are not dropped as duplicates when deduplicating.
+        for deduplicate in [false, true] {
+            let executor = TaskExecutor::default()
+                .jitter(Duration::ZERO)
+                .deduplicate(deduplicate)
+                .watchdog(Duration::from_millis(20))
+                .restart_stalled(true);
+            let forge = Arc::new(TestForge::default());
+
+            let tasks = [
+                ForgeTask::UpdateRunner {
+                    id: 1,
+                },
+                ForgeTask::UpdateRunner {
+                    id: 3,
+                },
+            ];
+            let report = executor.run(forge.clone(), tasks).await;
+
+            // The stalled task is restarted once and then abandoned.
+            assert_eq!(forge.api_requests(), 3);
+            assert_eq!(report.executed(), 3);
+            assert_eq!(report.failed(), 2);
+            assert_eq!(report.failures[0].error, ForgeErrorKind::Stalled);
+            assert!(report.failures[0].retryable);
+            assert_eq!(report.stalls.len(), 2);
+            assert_eq!(report.stalls[0].in_flight.len(), 1);
+            assert_eq!(report.stalls[0].in_flight[0].kind, "update_runner");
+            assert_eq!(report.stalls[0].restarted, 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn run_partially_failed_tasks() {
+        let executor = TaskExecutor::default().jitter(Duration::ZERO);
+        let forge = Arc::new(TestForge::default());
+
+        let report = executor
+            .run(forge.clone(), [ForgeTask::DiscoverUsers])
+            .await;
+
+        // The listing is resumed from each failing page until it completes.
+        assert_eq!(report.tasks["discover_users"].executed, 3);
+        assert_eq!(report.tasks["discover_users"].partial, 2);
+        assert_eq!(report.tasks["update_user"].executed, 3);
+        assert_eq!(report.partial_failures.len(), 2);
+        assert_eq!(report.partial_failures[0].processed, 1);
+        assert_eq!(report.partial_failures[0].resume_page, Some(2));
+        assert_eq!(report.partial_failures[1].resume_page, Some(3));
+        assert_eq!(forge.user_listings.load(Ordering::Relaxed), 3);
+        assert_eq!(forge.api_requests(), 6);
+    }
+
+    #[tokio::test]
+    async fn run_rate_classes() {
+        // Single requests are free and paged requests are capped to the rate limit, so only the
+        // initial burst is used.
+        let executor = TaskExecutor::default()
+            .rate_limit(NonZeroU32::new(1).unwrap())
+            .class_cost(RateClass::Single, 0)
+            .jitter(Duration::ZERO);
+        let forge = Arc::new(TestForge::default());
+
+        let run = executor.run(forge, [ForgeTask::DiscoverRunners]);
+        let report = tokio::time::timeout(Duration::from_millis(500), run)
+            .await
+            .unwrap();
+
+        assert_eq!(report.executed(), 3);
+        assert_eq!(report.api_requests, 3);
+    }
+
+    #[tokio::test]
+    async fn run_heartbeat() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("heartbeat.json");
+        let mut previous = Heartbeat::start(Utc::now());
+        previous.record_complete_crawl("gitlab.example.com", previous.run_started_at);
+        previous.write(&path).unwrap();
+
+        let executor = TaskExecutor::default()
+            .jitter(Duration::ZERO)
+            .heartbeat(&path, Duration::from_millis(10));
+        let forge = Arc::new(TestForge::default());
+
+        let report = executor.run(forge, [ForgeTask::DiscoverRunners]).await;
+
+        let heartbeat = Heartbeat::read(&path).unwrap().unwrap();
+        assert!(heartbeat.finished);
+        assert_eq!(heartbeat.run_started_at, report.started_at);
+        assert_eq!(heartbeat.queued, 0);
+        assert_eq!(heartbeat.executed, 3);
+        assert_eq!(heartbeat.failed, 1);
+        assert_eq!(
+            heartbeat.last_complete_crawls,
+            previous.last_complete_crawls,
+        );
+    }
+
+    #[tokio::test]
+    async fn run_canceled() {
+        let executor = TaskExecutor::default().jitter(Duration::ZERO);
+        let forge = Arc::new(TestForge::default());
+        let canceller = executor.canceller();
+
+        let tasks = [
+            ForgeTask::UpdateRunner {
+                id: 1,
+            },
+            ForgeTask::UpdateRunner {
+                id: 3,
+            },
+        ];
+        let run = executor.run(forge, tasks);
+        let cancel = async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            canceller.cancel();
+        };
+        let (report, ()) = tokio::join!(run, cancel);
+
+        assert!(canceller.is_canceled());
+        assert_eq!(report.executed(), 1);
+        assert_eq!(report.failed(), 0);
+        assert_eq!(report.canceled, 1);
+
+        // Later runs are canceled immediately.
+        let report = executor
+            .run(Arc::new(TestForge::default()), [ForgeTask::DiscoverRunners])
+            .await;
+        assert_eq!(report.executed(), 0);
+        assert_eq!(report.canceled, 1);
+    }
+
+    #[tokio::test]
+    async fn run_api_request_budget() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("pending.jsonl");
+        let executor = TaskExecutor::default()
+            .jitter(Duration::ZERO)
+            .max_api_requests(1)
+            .pending_tasks(&path);
+        let forge = Arc::new(TestForge::default());
+
+        let report = executor.run(forge, [ForgeTask::DiscoverRunners]).await;
+
+        assert_eq!(report.executed(), 1);
+        assert_eq!(report.budget_exhausted, Some(RunBudget::ApiRequests));
+        assert_eq!(report.remaining, 2);
+        assert!((report.completed - 1. / 3.).abs() < f64::EPSILON);
+        assert!(path.exists());
+
+        // The next run picks up where the previous one left off.
+        let executor = TaskExecutor::default()
+            .jitter(Duration::ZERO)
+            .pending_tasks(&path);
+        let report = executor.run(Arc::new(TestForge::default()), []).await;
+
+        assert_eq!(report.executed(), 2);
+        assert_eq!(report.tasks["update_runner"].executed, 2);
+        assert_eq!(report.budget_exhausted, None);
+        assert_eq!(report.remaining, 0);
+        assert_eq!(report.completed, 1.);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn run_warm_start() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("misses.jsonl");
+        let executor = TaskExecutor::default()
+            .jitter(Duration::ZERO)
+            .warm_start(&path);
+        let tasks = [
+            ForgeTask::DiscoverRunners,
+            ForgeTask::UpdateJob {
+                project: 1,
+                job: 10,
+            },
+            ForgeTask::UpdateJob {
+                project: 1,
+                job: 10,
+            },
+        ];
+
+        let report = executor.run(Arc::new(TestForge::default()), tasks).await;
+
+        assert_eq!(report.warm_started, 0);
+        assert_eq!(report.tasks["update_user"].executed, 2);
+        // Runners scheduled by discovery are not misses and each miss is only kept once.
+        assert!(path.exists());
+        let misses = fs::read_to_string(&path).unwrap();
+        assert_eq!(misses.lines().count(), 1);
+
+        // The next run fetches the missed entities up front.
+        let report = executor.run(Arc::new(TestForge::default()), []).await;
+
+        assert_eq!(report.warm_started, 1);
+        assert_eq!(report.executed(), 1);
+        assert_eq!(report.tasks["update_user"].executed, 1);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn run_duration_budget() {
+        let executor = TaskExecutor::default()
+            .jitter(Duration::ZERO)
+            .max_duration(Duration::ZERO);
+        let forge = Arc::new(TestForge::default());
+
+        let report = executor.run(forge, [ForgeTask::DiscoverRunners]).await;
+
+        assert_eq!(report.executed(), 0);
+        assert_eq!(report.budget_exhausted, Some(RunBudget::Duration));
+        assert_eq!(report.remaining, 1);
+        assert_eq!(report.completed, 0.);
+    }
+
+    struct DiscoverRunnersHandler;
+
+    #[async_trait]
+    impl TaskHandler for DiscoverRunnersHandler {
+        async fn run_task_async(
+            &self,
+            payload: &serde_json::Value,
+        ) -> Result<ForgeTaskOutcome, ForgeError> {
+            assert_eq!(payload["runners"], true);
+            let mut outcome = ForgeTaskOutcome::default();
+            outcome.additional_tasks.push(ForgeTask::DiscoverRunners);
+            Ok(outcome)
+        }
+    }
+
+    #[tokio::test]
+    async fn run_custom_tasks() {
+        let mut handlers = TaskHandlers::default();
+        handlers.register("discover", Arc::new(DiscoverRunnersHandler));
+        let executor = TaskExecutor::default()
+            .jitter(Duration::ZERO)
+            .handlers(handlers);
+        let forge = Arc::new(TestForge::default());
+
+        let tasks = [
+            ForgeTask::Custom {
+                name: "discover".into(),
+                payload: serde_json::json!({
+                    "runners": true,
+                }),
+            },
+            ForgeTask::Custom {
+                name: "unregistered".into(),
+                payload: serde_json::Value::Null,
+            },
+        ];
+        let report = executor.run(forge, tasks).await;
+
+        assert_eq!(report.tasks["custom"].executed, 2);
+        assert_eq!(report.tasks["custom"].failed, 1);
+        assert_eq!(report.tasks["discover_runners"].executed, 1);
+        assert_eq!(report.tasks["update_runner"].executed, 2);
+        assert_eq!(report.failures.len(), 2);
+        // The handler does not talk to the forge.
+        assert_eq!(report.api_requests, 4);
+    }
+}
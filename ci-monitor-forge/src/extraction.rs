@@ -0,0 +1,349 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::io::{self, Cursor, Read};
+
+use glob::{MatchOptions, Pattern, PatternError};
+use thiserror::Error;
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+/// Errors which may occur when extracting files from archive artifacts.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum ExtractionError {
+    /// An extraction pattern is invalid.
+    #[error("invalid extraction pattern '{}': {}", pattern, source)]
+    InvalidPattern {
+        /// The pattern.
+        pattern: String,
+        /// The source of the error.
+        #[source]
+        source: PatternError,
+    },
+    /// The archive could not be read.
+    #[error("failed to read archive: {}", source)]
+    Archive {
+        /// The source of the error.
+        #[from]
+        source: ZipError,
+    },
+    /// A file within the archive could not be read.
+    #[error("failed to read '{}' from the archive: {}", path, source)]
+    Read {
+        /// The path within the archive.
+        path: String,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// A file within the archive is larger than allowed.
+    #[error("'{}' in the archive exceeds the limit of {} bytes per file", path, limit)]
+    FileTooLarge {
+        /// The path within the archive.
+        path: String,
+        /// The maximum size of an extracted file.
+        limit: u64,
+    },
+    /// The files extracted from the archive are larger than allowed.
+    #[error(
+        "extracting '{}' from the archive exceeds the limit of {} bytes per archive",
+        path,
+        limit
+    )]
+    TotalTooLarge {
+        /// The path within the archive which exceeded the limit.
+        path: String,
+        /// The maximum size of all extracted files.
+        limit: u64,
+    },
+}
+
+impl ExtractionError {
+    fn invalid_pattern(pattern: String, source: PatternError) -> Self {
+        Self::InvalidPattern {
+            pattern,
+            source,
+        }
+    }
+
+    fn read(path: String, source: io::Error) -> Self {
+        Self::Read {
+            path,
+            source,
+        }
+    }
+}
+
+/// A file extracted from an archive artifact.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ExtractedFile {
+    /// The path of the file within the archive.
+    pub path: String,
+    /// The contents of the file.
+    pub contents: Vec<u8>,
+}
+
+/// Rules for files which should be extracted from archive artifacts.
+///
+/// Files which match are stored as separate artifacts so that analysis does not need to access
+/// the full archive. The sizes declared by the archive are not trusted; extraction stops once a
+/// file or the extracted files as a whole exceed their limits.
+#[derive(Debug, Clone)]
+pub struct ArtifactExtractionRules {
+    patterns: Vec<Pattern>,
+    max_file_size: u64,
+    max_total_size: u64,
+}
+
+/// The default maximum size of an extracted file.
+const DEFAULT_MAX_FILE_SIZE: u64 = 64 * 1024 * 1024;
+/// The default maximum size of the files extracted from an archive.
+const DEFAULT_MAX_TOTAL_SIZE: u64 = 256 * 1024 * 1024;
+
+impl Default for ArtifactExtractionRules {
+    fn default() -> Self {
+        Self {
+            patterns: Vec::new(),
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            max_total_size: DEFAULT_MAX_TOTAL_SIZE,
+        }
+    }
+}
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+impl ArtifactExtractionRules {
+    /// Add a glob pattern for files to extract.
+    ///
+    /// Patterns match against the full path within the archive. Use `**/` to match a file name
+    /// in any directory.
+    pub fn add_pattern(&mut self, pattern: &str) -> Result<&mut Self, ExtractionError> {
+        let pattern = Pattern::new(pattern)
+            .map_err(|err| ExtractionError::invalid_pattern(pattern.into(), err))?;
+        self.patterns.push(pattern);
+        Ok(self)
+    }
+
+    /// Set the maximum size of an extracted file (in bytes).
+    ///
+    /// Defaults to 64 MiB.
+    pub fn max_file_size(&mut self, size: u64) -> &mut Self {
+        self.max_file_size = size;
+        self
+    }
+
+    /// Set the maximum size of all files extracted from an archive (in bytes).
+    ///
+    /// Defaults to 256 MiB.
+    pub fn max_total_size(&mut self, size: u64) -> &mut Self {
+        self.max_total_size = size;
+        self
+    }
+
+    /// Whether any rules are present.
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Whether a path within an archive should be extracted.
+    pub fn matches(&self, path: &str) -> bool {
+        self.patterns
+            .iter()
+            .any(|pattern| pattern.matches_with(path, MATCH_OPTIONS))
+    }
+
+    /// Extract files matching the rules from a zip archive.
+    pub fn extract(&self, archive: &[u8]) -> Result<Vec<ExtractedFile>, ExtractionError> {
+        if self.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut archive = ZipArchive::new(Cursor::new(archive))?;
+        let mut extracted = Vec::new();
+        let mut total = 0;
+
+        for idx in 0..archive.len() {
+            let file = archive.by_index(idx)?;
+            if !file.is_file() || !self.matches(file.name()) {
+                continue;
+            }
+
+            let path = file.name().to_string();
+            let limit = self
+                .max_file_size
+                .min(self.max_total_size.saturating_sub(total));
+            // Read one byte past the limit to detect files which exceed it.
+            let mut contents = Vec::new();
+            file.take(limit.saturating_add(1))
+                .read_to_end(&mut contents)
+                .map_err(|err| ExtractionError::read(path.clone(), err))?;
+            let size = contents.len() as u64;
+            if size > self.max_file_size {
+                return Err(ExtractionError::FileTooLarge {
+                    path,
+                    limit: self.max_file_size,
+                });
+            } else if size > limit {
+                return Err(ExtractionError::TotalTooLarge {
+                    path,
+                    limit: self.max_total_size,
+                });
+            }
+            total += size;
+
+            extracted.push(ExtractedFile {
+                path,
+                contents,
+            });
+        }
+
+        Ok(extracted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    use crate::{ArtifactExtractionRules, ExtractionError};
+
+    fn archive(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (path, contents) in files {
+            writer
+                .start_file(*path, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn no_rules() {
+        let rules = ArtifactExtractionRules::default();
+        let archive = archive(&[("compile_commands.json", "[]")]);
+
+        assert!(rules.is_empty());
+        assert!(rules.extract(&archive).unwrap().is_empty());
+    }
+
+    #[test]
+    fn invalid_pattern() {
+        let mut rules = ArtifactExtractionRules::default();
+        let err = rules.add_pattern("[").unwrap_err();
+
+        if let ExtractionError::InvalidPattern {
+            pattern, ..
+        } = err
+        {
+            assert_eq!(pattern, "[");
+        } else {
+            panic!("unexpected error: {:?}", err);
+        }
+    }
+
+    #[test]
+    fn pattern_matching() {
+        let mut rules = ArtifactExtractionRules::default();
+        rules
+            .add_pattern("**/compile_commands.json")
+            .unwrap()
+            .add_pattern("coverage/*.xml")
+            .unwrap();
+
+        assert!(rules.matches("compile_commands.json"));
+        assert!(rules.matches("build/compile_commands.json"));
+        assert!(rules.matches("coverage/report.xml"));
+        assert!(!rules.matches("coverage/nested/report.xml"));
+        assert!(!rules.matches("report.xml"));
+    }
+
+    #[test]
+    fn extract_matching() {
+        let mut rules = ArtifactExtractionRules::default();
+        rules
+            .add_pattern("**/compile_commands.json")
+            .unwrap()
+            .add_pattern("coverage/*.xml")
+            .unwrap();
+        let archive = archive(&[
+            ("build/compile_commands.json", "[]"),
+            ("build/libfoo.so", "binary"),
+            ("coverage/report.xml", "<coverage/>"),
+        ]);
+
+        let extracted = rules.extract(&archive).unwrap();
+
+        assert_eq!(extracted.len(), 2);
+        assert_eq!(extracted[0].path, "build/compile_commands.json");
+        assert_eq!(extracted[0].contents, b"[]");
+        assert_eq!(extracted[1].path, "coverage/report.xml");
+        assert_eq!(extracted[1].contents, b"<coverage/>");
+    }
+
+    #[test]
+    fn extract_file_too_large() {
+        let mut rules = ArtifactExtractionRules::default();
+        rules.add_pattern("*.json").unwrap().max_file_size(4);
+        let archive = archive(&[("small.json", "[]"), ("large.json", "[1, 2]")]);
+
+        let err = rules.extract(&archive).unwrap_err();
+
+        if let ExtractionError::FileTooLarge {
+            path,
+            limit,
+        } = err
+        {
+            assert_eq!(path, "large.json");
+            assert_eq!(limit, 4);
+        } else {
+            panic!("unexpected error: {:?}", err);
+        }
+    }
+
+    #[test]
+    fn extract_total_too_large() {
+        let mut rules = ArtifactExtractionRules::default();
+        rules.add_pattern("*.json").unwrap().max_total_size(6);
+        let archive = archive(&[("a.json", "[1]"), ("b.json", "[2]"), ("c.json", "[3]")]);
+
+        let err = rules.extract(&archive).unwrap_err();
+
+        if let ExtractionError::TotalTooLarge {
+            path,
+            limit,
+        } = err
+        {
+            assert_eq!(path, "c.json");
+            assert_eq!(limit, 6);
+        } else {
+            panic!("unexpected error: {:?}", err);
+        }
+
+        // Files within the limits are extracted.
+        rules.max_total_size(9);
+        assert_eq!(rules.extract(&archive).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn extract_invalid_archive() {
+        let mut rules = ArtifactExtractionRules::default();
+        rules.add_pattern("*").unwrap();
+
+        let err = rules.extract(b"not a zip file").unwrap_err();
+
+        assert!(matches!(err, ExtractionError::Archive { .. }));
+    }
+}
@@ -0,0 +1,365 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use ci_monitor_core::data::Instance;
+
+use crate::{
+    EndpointLatency, Forge, ForgeCore, ForgeError, ForgeTask, ForgeTaskOutcome, PartialFailure,
+    RateClass,
+};
+
+/// The increment of the SplitMix64 generator.
+const GOLDEN_GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// A fault which may be injected into a forge task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum Fault {
+    /// The connection to the forge fails before the task does any work.
+    Error,
+    /// The forge responds with a payload which cannot be parsed.
+    Malformed,
+    /// The task fails after doing part of its work.
+    Partial,
+}
+
+impl Fault {
+    /// All faults.
+    pub const ALL: &'static [Self] = &[Self::Error, Self::Malformed, Self::Partial];
+
+    /// The name of the fault.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Malformed => "malformed",
+            Self::Partial => "partial",
+        }
+    }
+
+    fn index(self) -> usize {
+        match self {
+            Self::Error => 0,
+            Self::Malformed => 1,
+            Self::Partial => 2,
+        }
+    }
+}
+
+/// The faults to inject into forge tasks.
+///
+/// Rates are the probability (between 0 and 1) of each task suffering the fault; at most one
+/// fault is injected into any task. Faults are drawn from a seeded generator so that runs over
+/// the same sequence of tasks are reproducible.
+#[derive(Debug, Clone, Default)]
+pub struct FaultInjection {
+    rates: [f64; 3],
+    latency: Duration,
+    latency_jitter: Duration,
+    seed: u64,
+}
+
+impl FaultInjection {
+    /// Set the rate at which a fault is injected.
+    ///
+    /// Rates are clamped so that the total rate of all faults does not exceed 1.
+    pub fn rate(mut self, fault: Fault, rate: f64) -> Self {
+        let others: f64 = Fault::ALL
+            .iter()
+            .filter(|&&other| other != fault)
+            .map(|other| self.rates[other.index()])
+            .sum();
+        self.rates[fault.index()] = rate.max(0.).min((1. - others).max(0.));
+        self
+    }
+
+    /// Delay each task by a latency.
+    ///
+    /// A random delay of up to `jitter` is added to the latency.
+    pub fn latency(mut self, latency: Duration, jitter: Duration) -> Self {
+        self.latency = latency;
+        self.latency_jitter = jitter;
+        self
+    }
+
+    /// Set the seed of the fault generator.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Whether any faults are injected.
+    pub fn is_enabled(&self) -> bool {
+        self.rates.iter().any(|&rate| rate > 0.)
+            || !self.latency.is_zero()
+            || !self.latency_jitter.is_zero()
+    }
+
+    fn fault(&self, draw: f64) -> Option<Fault> {
+        let mut threshold = 0.;
+        Fault::ALL.iter().copied().find(|fault| {
+            threshold += self.rates[fault.index()];
+            draw < threshold
+        })
+    }
+}
+
+/// A forge which injects faults into the tasks it runs.
+///
+/// Used to validate retry logic, partial-failure handling, and store consistency under adverse
+/// conditions in tests and staging deployments. Errors and malformed payloads fail the task
+/// without running it. Partial failures run the task and then discard the second half of the
+/// tasks it discovered.
+#[derive(Debug)]
+pub struct FaultInjectingForge<F> {
+    forge: F,
+    faults: FaultInjection,
+    state: AtomicU64,
+    injected: [AtomicU64; 3],
+}
+
+impl<F> FaultInjectingForge<F> {
+    /// Wrap a forge with fault injection.
+    pub fn new(forge: F, faults: FaultInjection) -> Self {
+        let state = AtomicU64::new(faults.seed);
+        Self {
+            forge,
+            faults,
+            state,
+            injected: Default::default(),
+        }
+    }
+
+    /// The wrapped forge.
+    pub fn inner(&self) -> &F {
+        &self.forge
+    }
+
+    /// Unwrap the forge.
+    pub fn into_inner(self) -> F {
+        self.forge
+    }
+
+    /// The number of times a fault has been injected.
+    pub fn injected(&self, fault: Fault) -> u64 {
+        self.injected[fault.index()].load(Ordering::Relaxed)
+    }
+
+    /// Draw a uniform value in `[0, 1)`.
+    fn draw(&self) -> f64 {
+        // SplitMix64 advances its state by a constant, so concurrent draws remain distinct.
+        let mut z = self
+            .state
+            .fetch_add(GOLDEN_GAMMA, Ordering::Relaxed)
+            .wrapping_add(GOLDEN_GAMMA);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl<F> ForgeCore for FaultInjectingForge<F>
+where
+    F: ForgeCore,
+{
+    fn instance(&self) -> Instance {
+        self.forge.instance()
+    }
+}
+
+#[async_trait]
+impl<F> Forge for FaultInjectingForge<F>
+where
+    F: Forge + Send + Sync,
+{
+    async fn run_task_async(&self, task: ForgeTask) -> Result<ForgeTaskOutcome, ForgeError> {
+        let jitter = self.faults.latency_jitter.mul_f64(self.draw());
+        let latency = self.faults.latency + jitter;
+        if !latency.is_zero() {
+            tokio::time::sleep(latency).await;
+        }
+
+        let fault = self.faults.fault(self.draw());
+        if let Some(fault) = fault {
+            self.injected[fault.index()].fetch_add(1, Ordering::Relaxed);
+        }
+
+        match fault {
+            Some(Fault::Error) => {
+                Err(ForgeError::Connection {
+                    details: "injected fault: connection reset".into(),
+                })
+            },
+            Some(Fault::Malformed) => {
+                Err(ForgeError::Other {
+                    details: "injected fault: malformed response payload".into(),
+                })
+            },
+            Some(Fault::Partial) => {
+                let mut outcome = self.forge.run_task_async(task).await?;
+                // Keep any partial failure reported by the forge itself.
+                if outcome.partial.is_none() {
+                    let processed = outcome.additional_tasks.len() / 2;
+                    outcome.additional_tasks.truncate(processed);
+                    let err = ForgeError::Connection {
+                        details: "injected fault: connection reset partway through".into(),
+                    };
+                    outcome.partial = Some(PartialFailure::new(processed, &err));
+                }
+                Ok(outcome)
+            },
+            None => self.forge.run_task_async(task).await,
+        }
+    }
+
+    fn rate_class(&self, task: &ForgeTask) -> RateClass {
+        self.forge.rate_class(task)
+    }
+
+    fn api_requests(&self) -> u64 {
+        self.forge.api_requests()
+    }
+
+    fn api_latency(&self) -> Vec<EndpointLatency> {
+        self.forge.api_latency()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use async_trait::async_trait;
+
+    use crate::{
+        Fault, FaultInjectingForge, FaultInjection, Forge, ForgeError, ForgeTask, ForgeTaskOutcome,
+        TaskExecutor,
+    };
+
+    #[derive(Default)]
+    struct TestForge {
+        requests: AtomicU64,
+    }
+
+    #[async_trait]
+    impl Forge for TestForge {
+        async fn run_task_async(&self, task: ForgeTask) -> Result<ForgeTaskOutcome, ForgeError> {
+            self.requests.fetch_add(1, Ordering::Relaxed);
+            let mut outcome = ForgeTaskOutcome::default();
+            if let ForgeTask::DiscoverRunners = task {
+                outcome.additional_tasks = (1..=4)
+                    .map(|id| {
+                        ForgeTask::UpdateRunner {
+                            id,
+                        }
+                    })
+                    .collect();
+            }
+            Ok(outcome)
+        }
+
+        fn api_requests(&self) -> u64 {
+            self.requests.load(Ordering::Relaxed)
+        }
+    }
+
+    #[test]
+    fn fault_rates() {
+        let faults = FaultInjection::default();
+        assert!(!faults.is_enabled());
+        assert_eq!(faults.fault(0.), None);
+
+        let faults = faults
+            .rate(Fault::Error, 0.25)
+            .rate(Fault::Malformed, 0.25)
+            .rate(Fault::Partial, 0.75);
+        assert!(faults.is_enabled());
+        assert_eq!(faults.fault(0.), Some(Fault::Error));
+        assert_eq!(faults.fault(0.3), Some(Fault::Malformed));
+        assert_eq!(faults.fault(0.6), Some(Fault::Partial));
+        // The partial rate is clamped to the remaining probability.
+        assert_eq!(faults.fault(0.999), Some(Fault::Partial));
+    }
+
+    #[test]
+    fn fault_draws() {
+        let forge = FaultInjectingForge::new(TestForge::default(), FaultInjection::default());
+        let draws = (0..1000).map(|_| forge.draw()).collect::<Vec<_>>();
+
+        assert!(draws.iter().all(|draw| (0. ..1.).contains(draw)));
+        let mean = draws.iter().sum::<f64>() / draws.len() as f64;
+        assert!((mean - 0.5).abs() < 0.05);
+
+        // The same seed reproduces the same draws.
+        let replay = FaultInjectingForge::new(TestForge::default(), FaultInjection::default());
+        assert_eq!(replay.draw(), draws[0]);
+    }
+
+    #[tokio::test]
+    async fn inject_errors() {
+        let faults = FaultInjection::default()
+            .rate(Fault::Error, 1.)
+            .latency(Duration::from_millis(10), Duration::ZERO);
+        let forge = FaultInjectingForge::new(TestForge::default(), faults);
+
+        let start = Instant::now();
+        let err = forge
+            .run_task_async(ForgeTask::DiscoverRunners)
+            .await
+            .unwrap_err();
+
+        assert!(Duration::from_millis(10) <= start.elapsed());
+        assert!(err.is_retryable());
+        assert_eq!(forge.injected(Fault::Error), 1);
+        assert_eq!(forge.api_requests(), 0);
+    }
+
+    #[tokio::test]
+    async fn inject_malformed() {
+        let faults = FaultInjection::default().rate(Fault::Malformed, 1.);
+        let forge = FaultInjectingForge::new(TestForge::default(), faults);
+
+        let err = forge
+            .run_task_async(ForgeTask::DiscoverRunners)
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.name(), "other");
+        assert!(!err.is_retryable());
+        assert_eq!(forge.injected(Fault::Malformed), 1);
+        assert_eq!(forge.api_requests(), 0);
+    }
+
+    #[tokio::test]
+    async fn inject_partial_failures() {
+        let executor = TaskExecutor::default().jitter(Duration::ZERO);
+        let faults = FaultInjection::default().rate(Fault::Partial, 1.);
+        let forge = Arc::new(FaultInjectingForge::new(TestForge::default(), faults));
+
+        let report = executor
+            .run(forge.clone(), [ForgeTask::DiscoverRunners])
+            .await;
+
+        // Every task is retried once, but the retries fail the same way and half of the
+        // discovered runners are lost.
+        assert_eq!(report.executed(), 6);
+        assert_eq!(report.tasks["discover_runners"].executed, 2);
+        assert_eq!(report.tasks["update_runner"].executed, 4);
+        assert_eq!(report.partial_failures.len(), 6);
+        assert_eq!(report.partial_failures[0].kind, "discover_runners");
+        assert_eq!(report.partial_failures[0].processed, 2);
+        assert!(report.partial_failures[0].retryable);
+        assert_eq!(forge.injected(Fault::Partial), 6);
+        assert_eq!(forge.api_requests(), 6);
+        assert_eq!(report.api_requests, 6);
+    }
+}
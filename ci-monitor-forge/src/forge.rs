@@ -9,9 +9,11 @@ use std::time::Duration;
 use async_trait::async_trait;
 use ci_monitor_core::data::Instance;
 use ci_monitor_core::Lookup;
+use schemars::JsonSchema;
+use serde::Serialize;
 use thiserror::Error;
 
-use crate::ForgeTask;
+use crate::{EndpointLatency, ForgeTask};
 
 /// The outcome of a forge task.
 #[derive(Debug, Default, Clone)]
@@ -23,6 +25,45 @@ pub struct ForgeTaskOutcome {
     ///
     /// Maybe used to avoid API rate limits.
     pub task_delay: Option<Duration>,
+    /// Set if the task failed after doing part of its work.
+    ///
+    /// Any additional tasks are those discovered before the failure.
+    pub partial: Option<PartialFailure>,
+}
+
+/// A failure partway through a task.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct PartialFailure {
+    /// The number of items processed before the failure.
+    pub processed: usize,
+    /// The kind of error.
+    pub error: ForgeErrorKind,
+    /// The reason for the failure.
+    pub reason: String,
+    /// Whether the remaining work may succeed if tried again.
+    pub retryable: bool,
+    /// The page from which the remaining work may be resumed.
+    pub resume_page: Option<u64>,
+}
+
+impl PartialFailure {
+    /// Create a partial failure from an error after processing `processed` items.
+    pub fn new(processed: usize, err: &ForgeError) -> Self {
+        Self {
+            processed,
+            error: err.kind(),
+            reason: err.to_string(),
+            retryable: err.is_retryable(),
+            resume_page: None,
+        }
+    }
+
+    /// Set the page from which the remaining work may be resumed.
+    pub fn resume_from(mut self, page: u64) -> Self {
+        self.resume_page = Some(page);
+        self
+    }
 }
 
 /// An error that may occur when performing a task.
@@ -61,6 +102,24 @@ pub enum ForgeError {
         /// The unknown task.
         task: ForgeTask,
     },
+    /// No forge is available for the instance of a task.
+    #[error("no forge for instance '{}'", instance)]
+    UnknownInstance {
+        /// The instance of the task.
+        instance: String,
+    },
+    /// The task did not complete in time.
+    #[error("task timed out after {:?}", timeout)]
+    Timeout {
+        /// How long the task was allowed to run.
+        timeout: Duration,
+    },
+    /// The task was aborted because the run stopped making progress.
+    #[error("task aborted after no progress for {:?}", idle)]
+    Stalled {
+        /// How long the run went without progress.
+        idle: Duration,
+    },
     /// An uncategorized error.
     #[error("{}", details)]
     Other {
@@ -80,6 +139,140 @@ impl ForgeError {
             idx: format!("{:?}", idx),
         }
     }
+
+    /// Whether the task may succeed if tried again.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::Connection { .. } | Self::Timeout { .. } | Self::Stalled { .. },
+        )
+    }
+
+    /// The kind of error.
+    pub fn kind(&self) -> ForgeErrorKind {
+        match self {
+            Self::Auth {
+                ..
+            } => ForgeErrorKind::Auth,
+            Self::Connection {
+                ..
+            } => ForgeErrorKind::Connection,
+            Self::Lookup {
+                ..
+            } => ForgeErrorKind::Lookup,
+            Self::Unhandled {
+                ..
+            } => ForgeErrorKind::Unhandled,
+            Self::Unknown {
+                ..
+            } => ForgeErrorKind::Unknown,
+            Self::UnknownInstance {
+                ..
+            } => ForgeErrorKind::UnknownInstance,
+            Self::Timeout {
+                ..
+            } => ForgeErrorKind::Timeout,
+            Self::Stalled {
+                ..
+            } => ForgeErrorKind::Stalled,
+            Self::Other {
+                ..
+            } => ForgeErrorKind::Other,
+        }
+    }
+
+    /// A name for the kind of error.
+    pub fn name(&self) -> &'static str {
+        self.kind().name()
+    }
+}
+
+/// The kind of a `ForgeError`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum ForgeErrorKind {
+    /// Authentication failed.
+    Auth,
+    /// The connection to the forge failed.
+    Connection,
+    /// Failure to find an object by a stored index.
+    Lookup,
+    /// The forge does not handle the task.
+    Unhandled,
+    /// The forge does not know about the task.
+    Unknown,
+    /// No forge is available for the instance of the task.
+    UnknownInstance,
+    /// The task did not complete in time.
+    Timeout,
+    /// The task was aborted because the run stopped making progress.
+    Stalled,
+    /// An uncategorized error.
+    Other,
+}
+
+impl ForgeErrorKind {
+    /// The name of the kind of error.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Auth => "auth",
+            Self::Connection => "connection",
+            Self::Lookup => "lookup",
+            Self::Unhandled => "unhandled",
+            Self::Unknown => "unknown",
+            Self::UnknownInstance => "unknown_instance",
+            Self::Timeout => "timeout",
+            Self::Stalled => "stalled",
+            Self::Other => "other",
+        }
+    }
+}
+
+/// The expected cost of a task in terms of API usage.
+///
+/// Used by the executor to budget tasks against the rate limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum RateClass {
+    /// No API requests are made.
+    Free,
+    /// A single request for a single entity.
+    Single,
+    /// A listing which may span multiple pages of requests.
+    Paged,
+    /// A large download (e.g., artifacts or logs).
+    Transfer,
+}
+
+impl RateClass {
+    /// All rate classes.
+    pub const ALL: &'static [Self] = &[Self::Free, Self::Single, Self::Paged, Self::Transfer];
+
+    /// The name of the rate class.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Free => "free",
+            Self::Single => "single",
+            Self::Paged => "paged",
+            Self::Transfer => "transfer",
+        }
+    }
+
+    /// Look up a rate class by its name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|class| class.name() == name)
+    }
+
+    /// The default number of rate limit units a task of the class uses.
+    pub fn default_cost(self) -> u32 {
+        match self {
+            Self::Free => 0,
+            Self::Single => 1,
+            Self::Paged => 5,
+            Self::Transfer => 10,
+        }
+    }
 }
 
 /// A trait describing basic `Forge` capabilities.
@@ -93,4 +286,37 @@ pub trait ForgeCore {
 pub trait Forge {
     /// Run a task.
     async fn run_task_async(&self, task: ForgeTask) -> Result<ForgeTaskOutcome, ForgeError>;
+
+    /// Run a task starting from a page left by an earlier partial failure.
+    ///
+    /// By default, the task is run from its start.
+    async fn resume_task_async(
+        &self,
+        task: ForgeTask,
+        _page: u64,
+    ) -> Result<ForgeTaskOutcome, ForgeError> {
+        self.run_task_async(task).await
+    }
+
+    /// The expected cost of a task.
+    ///
+    /// By default, discovery tasks are considered to be paged and all others to be single
+    /// requests.
+    fn rate_class(&self, task: &ForgeTask) -> RateClass {
+        if task.is_discovery() {
+            RateClass::Paged
+        } else {
+            RateClass::Single
+        }
+    }
+
+    /// The number of API requests which have been made to the forge.
+    fn api_requests(&self) -> u64 {
+        0
+    }
+
+    /// Latency statistics for each API endpoint used.
+    fn api_latency(&self) -> Vec<EndpointLatency> {
+        Vec::new()
+    }
 }
@@ -0,0 +1,58 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::{ForgeError, ForgeTaskOutcome};
+
+/// A handler for `ForgeTask::Custom` tasks.
+#[async_trait]
+pub trait TaskHandler: Send + Sync {
+    /// Run a task with the given payload.
+    async fn run_task_async(
+        &self,
+        payload: &serde_json::Value,
+    ) -> Result<ForgeTaskOutcome, ForgeError>;
+}
+
+/// A registry of handlers for custom tasks.
+#[derive(Clone, Default)]
+pub struct TaskHandlers {
+    handlers: BTreeMap<String, Arc<dyn TaskHandler>>,
+}
+
+impl fmt::Debug for TaskHandlers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.handlers.keys()).finish()
+    }
+}
+
+impl TaskHandlers {
+    /// Register a handler for custom tasks with the given name.
+    ///
+    /// Replaces any existing handler with the same name.
+    pub fn register<N>(&mut self, name: N, handler: Arc<dyn TaskHandler>) -> &mut Self
+    where
+        N: Into<String>,
+    {
+        self.handlers.insert(name.into(), handler);
+        self
+    }
+
+    /// Get the handler for custom tasks with the given name.
+    pub fn get(&self, name: &str) -> Option<&Arc<dyn TaskHandler>> {
+        self.handlers.get(name)
+    }
+
+    /// Whether any handlers are registered.
+    pub fn is_empty(&self) -> bool {
+        self.handlers.is_empty()
+    }
+}
@@ -0,0 +1,245 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Errors which may occur when reading or writing a heartbeat.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum HeartbeatError {
+    /// The heartbeat could not be read.
+    #[error("failed to read heartbeat from '{}': {}", path.display(), source)]
+    Read {
+        /// The path to the heartbeat.
+        path: PathBuf,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// The heartbeat could not be written.
+    #[error("failed to write heartbeat to '{}': {}", path.display(), source)]
+    Write {
+        /// The path to the heartbeat.
+        path: PathBuf,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// The heartbeat could not be (de)serialized.
+    #[error("failed to (de)serialize heartbeat: {}", source)]
+    Serialize {
+        /// The source of the error.
+        #[from]
+        source: serde_json::Error,
+    },
+}
+
+impl HeartbeatError {
+    fn read(path: PathBuf, source: io::Error) -> Self {
+        Self::Read {
+            path,
+            source,
+        }
+    }
+
+    fn write(path: PathBuf, source: io::Error) -> Self {
+        Self::Write {
+            path,
+            source,
+        }
+    }
+}
+
+/// A periodic record of the monitor's progress.
+///
+/// External alerting may use this to detect a monitor which has died or is wedged.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct Heartbeat {
+    /// When the heartbeat was written.
+    pub at: DateTime<Utc>,
+    /// When the current (or last) run started.
+    pub run_started_at: DateTime<Utc>,
+    /// Whether the run has finished.
+    pub finished: bool,
+    /// The number of queued tasks.
+    pub queued: u64,
+    /// The number of running tasks.
+    pub in_flight: u64,
+    /// The number of tasks executed during the run.
+    pub executed: u64,
+    /// The number of tasks which failed during the run.
+    pub failed: u64,
+    /// When the last complete crawl of each instance started, keyed by instance URL.
+    #[serde(default)]
+    pub last_complete_crawls: BTreeMap<String, DateTime<Utc>>,
+}
+
+impl Heartbeat {
+    /// Start a heartbeat for a run.
+    pub fn start(run_started_at: DateTime<Utc>) -> Self {
+        Self {
+            at: Utc::now(),
+            run_started_at,
+            finished: false,
+            queued: 0,
+            in_flight: 0,
+            executed: 0,
+            failed: 0,
+            last_complete_crawls: BTreeMap::new(),
+        }
+    }
+
+    /// Read a heartbeat.
+    ///
+    /// Returns `None` if no heartbeat has been written.
+    pub fn read(path: &Path) -> Result<Option<Self>, HeartbeatError> {
+        match fs::read(path) {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(HeartbeatError::read(path.into(), err)),
+        }
+    }
+
+    /// Write the heartbeat.
+    ///
+    /// The heartbeat is replaced atomically so that readers never see a partial heartbeat.
+    pub fn write(&self, path: &Path) -> Result<(), HeartbeatError> {
+        let tmp = path.with_extension("tmp");
+        let data = serde_json::to_vec_pretty(self)?;
+        fs::write(&tmp, data).map_err(|err| HeartbeatError::write(tmp.clone(), err))?;
+        fs::rename(&tmp, path).map_err(|err| HeartbeatError::write(path.into(), err))
+    }
+
+    /// Record the start of the last complete crawl of an instance.
+    pub fn record_complete_crawl<I>(&mut self, instance: I, started_at: DateTime<Utc>)
+    where
+        I: Into<String>,
+    {
+        let last = self
+            .last_complete_crawls
+            .entry(instance.into())
+            .or_insert(started_at);
+        *last = (*last).max(started_at);
+    }
+
+    /// Render the heartbeat in the Prometheus text exposition format.
+    pub fn prometheus_metrics(&self) -> String {
+        let mut out = String::new();
+
+        let gauges = [
+            (
+                "ci_monitor_heartbeat_timestamp_seconds",
+                "When the monitor last wrote a heartbeat.",
+                self.at.timestamp(),
+            ),
+            (
+                "ci_monitor_run_started_timestamp_seconds",
+                "When the current run started.",
+                self.run_started_at.timestamp(),
+            ),
+            (
+                "ci_monitor_run_finished",
+                "Whether the current run has finished.",
+                self.finished.into(),
+            ),
+            (
+                "ci_monitor_queued_tasks",
+                "The number of queued tasks.",
+                self.queued as i64,
+            ),
+            (
+                "ci_monitor_in_flight_tasks",
+                "The number of running tasks.",
+                self.in_flight as i64,
+            ),
+            (
+                "ci_monitor_run_tasks_executed",
+                "The number of tasks executed during the current run.",
+                self.executed as i64,
+            ),
+            (
+                "ci_monitor_run_tasks_failed",
+                "The number of tasks which failed during the current run.",
+                self.failed as i64,
+            ),
+        ];
+        for (name, help, value) in gauges {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} gauge", name);
+            let _ = writeln!(out, "{} {}", name, value);
+        }
+
+        out.push_str(
+            "# HELP ci_monitor_last_complete_crawl_timestamp_seconds When the last complete crawl \
+             of an instance started.\n",
+        );
+        out.push_str("# TYPE ci_monitor_last_complete_crawl_timestamp_seconds gauge\n");
+        for (instance, at) in &self.last_complete_crawls {
+            let _ = writeln!(
+                out,
+                "ci_monitor_last_complete_crawl_timestamp_seconds{{instance=\"{}\"}} {}",
+                crate::metrics::escape_label(instance),
+                at.timestamp(),
+            );
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, TimeZone, Utc};
+    use tempfile::TempDir;
+
+    use crate::Heartbeat;
+
+    #[test]
+    fn read_write() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("heartbeat.json");
+
+        assert_eq!(Heartbeat::read(&path).unwrap(), None);
+
+        let started = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut heartbeat = Heartbeat::start(started);
+        heartbeat.queued = 10;
+        heartbeat.record_complete_crawl("gitlab.example.com", started);
+        heartbeat.record_complete_crawl("gitlab.example.com", started - TimeDelta::hours(1));
+        heartbeat.write(&path).unwrap();
+
+        let read = Heartbeat::read(&path).unwrap().unwrap();
+        assert_eq!(read, heartbeat);
+        assert_eq!(read.last_complete_crawls["gitlab.example.com"], started);
+    }
+
+    #[test]
+    fn prometheus_metrics() {
+        let started = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut heartbeat = Heartbeat::start(started);
+        heartbeat.failed = 2;
+        heartbeat.record_complete_crawl("gitlab.example.com", started);
+
+        let metrics = heartbeat.prometheus_metrics();
+
+        assert!(metrics.contains("ci_monitor_run_started_timestamp_seconds 1704067200\n"));
+        assert!(metrics.contains("ci_monitor_run_tasks_failed 2\n"));
+        assert!(metrics.contains(
+            "ci_monitor_last_complete_crawl_timestamp_seconds{instance=\"gitlab.example.com\"} \
+             1704067200\n",
+        ));
+    }
+}
@@ -0,0 +1,282 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::sync::Arc;
+
+use ci_monitor_core::data::{
+    Deployment, Environment, Instance, Job, JobArtifact, MergeRequest, Pipeline, PipelineSchedule,
+    Project, Push, Release, Runner, RunnerHost, User,
+};
+use ci_monitor_core::Lookup;
+
+use crate::{ForgeError, ForgeTask};
+
+/// An entity which has been stored by a forge.
+#[non_exhaustive]
+pub enum StoredEntity<'a, L>
+where
+    L: Lookup<Deployment<L>>,
+    L: Lookup<Environment<L>>,
+    L: Lookup<Instance>,
+    L: Lookup<Job<L>>,
+    L: Lookup<MergeRequest<L>>,
+    L: Lookup<Pipeline<L>>,
+    L: Lookup<PipelineSchedule<L>>,
+    L: Lookup<Project<L>>,
+    L: Lookup<Runner<L>>,
+    L: Lookup<RunnerHost>,
+    L: Lookup<User<L>>,
+{
+    /// A deployment.
+    Deployment(&'a Deployment<L>),
+    /// An environment.
+    Environment(&'a Environment<L>),
+    /// A job.
+    Job(&'a Job<L>),
+    /// A job artifact.
+    JobArtifact(&'a JobArtifact<L>),
+    /// A merge request.
+    MergeRequest(&'a MergeRequest<L>),
+    /// A pipeline.
+    Pipeline(&'a Pipeline<L>),
+    /// A pipeline schedule.
+    PipelineSchedule(&'a PipelineSchedule<L>),
+    /// A project.
+    Project(&'a Project<L>),
+    /// A push.
+    Push(&'a Push<L>),
+    /// A release.
+    Release(&'a Release<L>),
+    /// A runner.
+    Runner(&'a Runner<L>),
+    /// A runner host.
+    RunnerHost(&'a RunnerHost),
+    /// A user.
+    User(&'a User<L>),
+}
+
+// Implemented manually to avoid requiring `L: Clone`.
+impl<L> Clone for StoredEntity<'_, L>
+where
+    L: Lookup<Deployment<L>>,
+    L: Lookup<Environment<L>>,
+    L: Lookup<Instance>,
+    L: Lookup<Job<L>>,
+    L: Lookup<MergeRequest<L>>,
+    L: Lookup<Pipeline<L>>,
+    L: Lookup<PipelineSchedule<L>>,
+    L: Lookup<Project<L>>,
+    L: Lookup<Runner<L>>,
+    L: Lookup<RunnerHost>,
+    L: Lookup<User<L>>,
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<L> Copy for StoredEntity<'_, L>
+where
+    L: Lookup<Deployment<L>>,
+    L: Lookup<Environment<L>>,
+    L: Lookup<Instance>,
+    L: Lookup<Job<L>>,
+    L: Lookup<MergeRequest<L>>,
+    L: Lookup<Pipeline<L>>,
+    L: Lookup<PipelineSchedule<L>>,
+    L: Lookup<Project<L>>,
+    L: Lookup<Runner<L>>,
+    L: Lookup<RunnerHost>,
+    L: Lookup<User<L>>,
+{
+}
+
+macro_rules! impl_stored_entity_from {
+    ($variant:ident, $type:ty) => {
+        impl<'a, L> From<&'a $type> for StoredEntity<'a, L>
+        where
+            L: Lookup<Deployment<L>>,
+            L: Lookup<Environment<L>>,
+            L: Lookup<Instance>,
+            L: Lookup<Job<L>>,
+            L: Lookup<MergeRequest<L>>,
+            L: Lookup<Pipeline<L>>,
+            L: Lookup<PipelineSchedule<L>>,
+            L: Lookup<Project<L>>,
+            L: Lookup<Runner<L>>,
+            L: Lookup<RunnerHost>,
+            L: Lookup<User<L>>,
+        {
+            fn from(entity: &'a $type) -> Self {
+                Self::$variant(entity)
+            }
+        }
+    };
+}
+
+impl_stored_entity_from!(Deployment, Deployment<L>);
+impl_stored_entity_from!(Environment, Environment<L>);
+impl_stored_entity_from!(Job, Job<L>);
+impl_stored_entity_from!(JobArtifact, JobArtifact<L>);
+impl_stored_entity_from!(MergeRequest, MergeRequest<L>);
+impl_stored_entity_from!(Pipeline, Pipeline<L>);
+impl_stored_entity_from!(PipelineSchedule, PipelineSchedule<L>);
+impl_stored_entity_from!(Project, Project<L>);
+impl_stored_entity_from!(Push, Push<L>);
+impl_stored_entity_from!(Release, Release<L>);
+impl_stored_entity_from!(Runner, Runner<L>);
+impl_stored_entity_from!(RunnerHost, RunnerHost);
+impl_stored_entity_from!(User, User<L>);
+
+/// Callbacks for events which occur while a forge runs tasks.
+///
+/// All methods default to doing nothing. Hooks are called while the forge's storage is locked,
+/// so they should not block.
+pub trait ForgeHooks<L>: Send + Sync {
+    /// An entity has been stored.
+    fn on_entity_stored(&self, entity: StoredEntity<L>)
+    where
+        L: Lookup<Deployment<L>>,
+        L: Lookup<Environment<L>>,
+        L: Lookup<Instance>,
+        L: Lookup<Job<L>>,
+        L: Lookup<MergeRequest<L>>,
+        L: Lookup<Pipeline<L>>,
+        L: Lookup<PipelineSchedule<L>>,
+        L: Lookup<Project<L>>,
+        L: Lookup<Runner<L>>,
+        L: Lookup<RunnerHost>,
+        L: Lookup<User<L>>,
+    {
+        let _ = entity;
+    }
+
+    /// A pipeline has been seen in a finished state for the first time.
+    fn on_pipeline_finished(&self, pipeline: &Pipeline<L>)
+    where
+        L: Lookup<Instance>,
+        L: Lookup<MergeRequest<L>>,
+        L: Lookup<Pipeline<L>>,
+        L: Lookup<PipelineSchedule<L>>,
+        L: Lookup<Project<L>>,
+        L: Lookup<User<L>>,
+    {
+        let _ = pipeline;
+    }
+
+    /// A task has failed.
+    fn on_task_failed(&self, task: &ForgeTask, error: &ForgeError) {
+        let _ = (task, error);
+    }
+}
+
+/// A registry of hooks to call on forge events.
+pub struct HookRegistry<L> {
+    hooks: Vec<Arc<dyn ForgeHooks<L>>>,
+}
+
+impl<L> Default for HookRegistry<L> {
+    fn default() -> Self {
+        Self {
+            hooks: Vec::new(),
+        }
+    }
+}
+
+impl<L> HookRegistry<L> {
+    /// Register a set of hooks.
+    pub fn register(&mut self, hooks: Arc<dyn ForgeHooks<L>>) -> &mut Self {
+        self.hooks.push(hooks);
+        self
+    }
+
+    /// Whether any hooks are registered.
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    /// Notify hooks that an entity has been stored.
+    pub fn entity_stored(&self, entity: StoredEntity<L>)
+    where
+        L: Lookup<Deployment<L>>,
+        L: Lookup<Environment<L>>,
+        L: Lookup<Instance>,
+        L: Lookup<Job<L>>,
+        L: Lookup<MergeRequest<L>>,
+        L: Lookup<Pipeline<L>>,
+        L: Lookup<PipelineSchedule<L>>,
+        L: Lookup<Project<L>>,
+        L: Lookup<Runner<L>>,
+        L: Lookup<RunnerHost>,
+        L: Lookup<User<L>>,
+    {
+        for hooks in &self.hooks {
+            hooks.on_entity_stored(entity);
+        }
+    }
+
+    /// Notify hooks that a pipeline has finished.
+    pub fn pipeline_finished(&self, pipeline: &Pipeline<L>)
+    where
+        L: Lookup<Instance>,
+        L: Lookup<MergeRequest<L>>,
+        L: Lookup<Pipeline<L>>,
+        L: Lookup<PipelineSchedule<L>>,
+        L: Lookup<Project<L>>,
+        L: Lookup<User<L>>,
+    {
+        for hooks in &self.hooks {
+            hooks.on_pipeline_finished(pipeline);
+        }
+    }
+
+    /// Notify hooks that a task has failed.
+    pub fn task_failed(&self, task: &ForgeTask, error: &ForgeError) {
+        for hooks in &self.hooks {
+            hooks.on_task_failed(task, error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use crate::{ForgeError, ForgeHooks, ForgeTask, HookRegistry};
+
+    #[derive(Default)]
+    struct CountFailures {
+        failures: AtomicUsize,
+    }
+
+    impl<L> ForgeHooks<L> for CountFailures {
+        fn on_task_failed(&self, task: &ForgeTask, error: &ForgeError) {
+            assert_eq!(task.name(), "discover_runners");
+            assert_eq!(error.name(), "connection");
+            self.failures.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn task_failed() {
+        let counter = Arc::new(CountFailures::default());
+        let mut registry = HookRegistry::<()>::default();
+        assert!(registry.is_empty());
+
+        registry.register(counter.clone()).register(counter.clone());
+        assert!(!registry.is_empty());
+
+        registry.task_failed(
+            &ForgeTask::DiscoverRunners,
+            &ForgeError::Connection {
+                details: "timeout".into(),
+            },
+        );
+
+        assert_eq!(counter.failures.load(Ordering::Relaxed), 2);
+    }
+}
@@ -12,14 +12,96 @@
 
 #![warn(missing_docs)]
 
+mod autoscaler;
+mod diff;
+#[cfg(feature = "executor")]
+mod executor;
+#[cfg(feature = "extraction")]
+mod extraction;
+#[cfg(feature = "executor")]
+mod faults;
 mod forge;
+mod handler;
+mod heartbeat;
+mod hooks;
+mod metrics;
+#[cfg(feature = "executor")]
+mod queue;
+mod report;
+#[cfg(feature = "runner-hosts")]
+mod runner_hosts;
 mod tasks;
 
+pub use self::autoscaler::parse_autoscaler_events;
+pub use self::autoscaler::AutoscalerEvent;
+pub use self::autoscaler::AutoscalerLogError;
+pub use self::autoscaler::AutoscalerLogFormat;
+
+pub use self::diff::field_changes;
+pub use self::diff::FieldChange;
+
+#[cfg(feature = "executor")]
+pub use self::executor::TaskCanceller;
+#[cfg(feature = "executor")]
+pub use self::executor::TaskExecutor;
+
+#[cfg(feature = "extraction")]
+pub use self::extraction::ArtifactExtractionRules;
+#[cfg(feature = "extraction")]
+pub use self::extraction::ExtractedFile;
+#[cfg(feature = "extraction")]
+pub use self::extraction::ExtractionError;
+
+#[cfg(feature = "executor")]
+pub use self::faults::Fault;
+#[cfg(feature = "executor")]
+pub use self::faults::FaultInjectingForge;
+#[cfg(feature = "executor")]
+pub use self::faults::FaultInjection;
+
 pub use self::forge::Forge;
 pub use self::forge::ForgeCore;
 pub use self::forge::ForgeError;
+pub use self::forge::ForgeErrorKind;
 pub use self::forge::ForgeTaskOutcome;
+pub use self::forge::PartialFailure;
+pub use self::forge::RateClass;
+
+pub use self::handler::TaskHandler;
+pub use self::handler::TaskHandlers;
+
+pub use self::heartbeat::Heartbeat;
+pub use self::heartbeat::HeartbeatError;
+
+pub use self::hooks::ForgeHooks;
+pub use self::hooks::HookRegistry;
+pub use self::hooks::StoredEntity;
+
+pub use self::metrics::escape_label;
+pub use self::metrics::prometheus_metrics;
+pub use self::metrics::ApiMetrics;
+pub use self::metrics::EndpointLatency;
+pub use self::metrics::LATENCY_BUCKETS;
+
+#[cfg(feature = "executor")]
+pub use self::queue::TaskQueueError;
+
+pub use self::report::PartialTaskFailure;
+pub use self::report::RunBudget;
+pub use self::report::RunReport;
+pub use self::report::RunReportError;
+pub use self::report::RunStall;
+pub use self::report::StalledTask;
+pub use self::report::TaskFailure;
+pub use self::report::TaskStatistics;
+
+#[cfg(feature = "runner-hosts")]
+pub use self::runner_hosts::RunnerHostRuleError;
+#[cfg(feature = "runner-hosts")]
+pub use self::runner_hosts::RunnerHostRules;
 
 pub use self::tasks::ForgeTask;
+pub use self::tasks::InstanceTask;
 pub use self::tasks::MaintenanceTask;
+pub use self::tasks::RefreshTarget;
 pub use self::tasks::RunnerHostData;
@@ -0,0 +1,238 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+/// Upper bounds (in seconds) of the buckets of API latency histograms.
+pub const LATENCY_BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1., 2.5, 5., 10., 30.];
+
+/// Latency and error statistics for an API endpoint.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct EndpointLatency {
+    /// The endpoint (with IDs replaced by placeholders).
+    pub endpoint: String,
+    /// The number of requests made.
+    pub requests: u64,
+    /// The number of requests which failed.
+    pub errors: u64,
+    /// The total time spent on requests (in seconds).
+    pub total_seconds: f64,
+    /// The number of requests within each bucket of `LATENCY_BUCKETS`.
+    ///
+    /// A final bucket counts requests slower than the last bound.
+    pub buckets: Vec<u64>,
+}
+
+impl EndpointLatency {
+    fn new(endpoint: String) -> Self {
+        Self {
+            endpoint,
+            requests: 0,
+            errors: 0,
+            total_seconds: 0.,
+            buckets: vec![0; LATENCY_BUCKETS.len() + 1],
+        }
+    }
+
+    fn record(&mut self, duration: Duration, success: bool) {
+        let seconds = duration.as_secs_f64();
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|&bound| seconds <= bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+
+        self.requests += 1;
+        if !success {
+            self.errors += 1;
+        }
+        self.total_seconds += seconds;
+        self.buckets[bucket] += 1;
+    }
+
+    /// The mean latency of requests (in seconds).
+    pub fn mean(&self) -> f64 {
+        if self.requests == 0 {
+            0.
+        } else {
+            self.total_seconds / self.requests as f64
+        }
+    }
+
+    /// The fraction of requests which failed.
+    pub fn error_rate(&self) -> f64 {
+        if self.requests == 0 {
+            0.
+        } else {
+            self.errors as f64 / self.requests as f64
+        }
+    }
+
+    /// An upper bound on the given quantile of latency (in seconds).
+    ///
+    /// Returns infinity if the quantile falls beyond the last bucket.
+    pub fn quantile(&self, quantile: f64) -> f64 {
+        let target = (quantile.clamp(0., 1.) * self.requests as f64).ceil() as u64;
+        let mut seen = 0;
+        for (count, bound) in self.buckets.iter().zip(LATENCY_BUCKETS) {
+            seen += count;
+            if seen >= target.max(1) {
+                return *bound;
+            }
+        }
+        f64::INFINITY
+    }
+}
+
+/// A recorder for API request latencies.
+#[derive(Debug, Default)]
+pub struct ApiMetrics {
+    endpoints: Mutex<BTreeMap<String, EndpointLatency>>,
+}
+
+impl ApiMetrics {
+    /// Record a request to an endpoint.
+    pub fn record(&self, endpoint: &str, duration: Duration, success: bool) {
+        let mut endpoints = self.endpoints.lock().expect("metrics poisoned");
+        if let Some(latency) = endpoints.get_mut(endpoint) {
+            latency.record(duration, success);
+        } else {
+            let mut latency = EndpointLatency::new(endpoint.into());
+            latency.record(duration, success);
+            endpoints.insert(endpoint.into(), latency);
+        }
+    }
+
+    /// The statistics for all endpoints, sorted by endpoint.
+    pub fn snapshot(&self) -> Vec<EndpointLatency> {
+        self.endpoints
+            .lock()
+            .expect("metrics poisoned")
+            .values()
+            .cloned()
+            .collect()
+    }
+}
+
+/// Escape a value for use as a Prometheus label value.
+pub fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Render API latency statistics in the Prometheus text exposition format.
+pub fn prometheus_metrics(latency: &[EndpointLatency]) -> String {
+    let mut out = String::new();
+
+    out.push_str("# HELP ci_monitor_api_request_duration_seconds Latency of forge API requests.\n");
+    out.push_str("# TYPE ci_monitor_api_request_duration_seconds histogram\n");
+    for endpoint in latency {
+        let label = escape_label(&endpoint.endpoint);
+        let mut cumulative = 0;
+        for (count, bound) in endpoint.buckets.iter().zip(LATENCY_BUCKETS) {
+            cumulative += count;
+            let _ = writeln!(
+                out,
+                "ci_monitor_api_request_duration_seconds_bucket{{endpoint=\"{}\",le=\"{}\"}} {}",
+                label, bound, cumulative,
+            );
+        }
+        let _ = writeln!(
+            out,
+            "ci_monitor_api_request_duration_seconds_bucket{{endpoint=\"{}\",le=\"+Inf\"}} {}",
+            label, endpoint.requests,
+        );
+        let _ = writeln!(
+            out,
+            "ci_monitor_api_request_duration_seconds_sum{{endpoint=\"{}\"}} {}",
+            label, endpoint.total_seconds,
+        );
+        let _ = writeln!(
+            out,
+            "ci_monitor_api_request_duration_seconds_count{{endpoint=\"{}\"}} {}",
+            label, endpoint.requests,
+        );
+    }
+
+    out.push_str("# HELP ci_monitor_api_request_errors_total Failed forge API requests.\n");
+    out.push_str("# TYPE ci_monitor_api_request_errors_total counter\n");
+    for endpoint in latency {
+        let _ = writeln!(
+            out,
+            "ci_monitor_api_request_errors_total{{endpoint=\"{}\"}} {}",
+            escape_label(&endpoint.endpoint),
+            endpoint.errors,
+        );
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::ApiMetrics;
+
+    #[test]
+    fn api_metrics() {
+        let metrics = ApiMetrics::default();
+        metrics.record("GET /projects/:id", Duration::from_millis(20), true);
+        metrics.record("GET /projects/:id", Duration::from_millis(300), true);
+        metrics.record("GET /projects/:id", Duration::from_millis(400), false);
+        metrics.record("GET /projects/:id", Duration::from_secs(60), true);
+        metrics.record("GET /runners", Duration::from_millis(70), true);
+
+        let snapshot = metrics.snapshot();
+
+        assert_eq!(snapshot.len(), 2);
+        let project = &snapshot[0];
+        assert_eq!(project.endpoint, "GET /projects/:id");
+        assert_eq!(project.requests, 4);
+        assert_eq!(project.errors, 1);
+        assert_eq!(project.error_rate(), 0.25);
+        assert_eq!(project.buckets, [1, 0, 0, 2, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(project.quantile(0.25), 0.05);
+        assert_eq!(project.quantile(0.5), 0.5);
+        assert_eq!(project.quantile(1.), f64::INFINITY);
+        assert_eq!(snapshot[1].quantile(0.5), 0.1);
+    }
+
+    #[test]
+    fn prometheus_metrics() {
+        let metrics = ApiMetrics::default();
+        metrics.record("GET /projects/:id", Duration::from_millis(200), true);
+        metrics.record("GET /projects/:id", Duration::from_secs(2), false);
+
+        let text = super::prometheus_metrics(&metrics.snapshot());
+        let has_line = |line: String| text.lines().any(|actual| actual == line);
+
+        let histogram = "ci_monitor_api_request_duration_seconds";
+        let label = "endpoint=\"GET /projects/:id\"";
+        assert!(has_line(format!("# TYPE {} histogram", histogram)));
+        assert!(has_line(format!(
+            "{}_bucket{{{},le=\"0.25\"}} 1",
+            histogram, label,
+        )));
+        assert!(has_line(format!(
+            "{}_bucket{{{},le=\"+Inf\"}} 2",
+            histogram, label,
+        )));
+        assert!(has_line(format!("{}_count{{{}}} 2", histogram, label)));
+        assert!(has_line(format!(
+            "ci_monitor_api_request_errors_total{{{}}} 1",
+            label,
+        )));
+    }
+}
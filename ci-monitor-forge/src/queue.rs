@@ -0,0 +1,487 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use thiserror::Error;
+
+use crate::InstanceTask;
+
+/// Errors which may occur when managing the task queue.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum TaskQueueError {
+    /// Tasks could not be written to the spill file.
+    ///
+    /// The task is kept in memory after any spilled tasks instead.
+    #[error("failed to spill tasks to '{}': {}", path.display(), source)]
+    Spill {
+        /// The path to the spill file.
+        path: PathBuf,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// Tasks could not be read back from the spill file.
+    ///
+    /// Any tasks remaining in the spill file are lost.
+    #[error(
+        "failed to read spilled tasks from '{}' ({} tasks lost): {}",
+        path.display(),
+        lost,
+        source
+    )]
+    Restore {
+        /// The path to the spill file.
+        path: PathBuf,
+        /// The number of tasks which were lost.
+        lost: usize,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// Tasks left for a later run could not be read or written.
+    #[error("failed to access pending tasks in '{}': {}", path.display(), source)]
+    Pending {
+        /// The path to the pending tasks.
+        path: PathBuf,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+}
+
+impl TaskQueueError {
+    fn spill(path: &Path, source: io::Error) -> Self {
+        Self::Spill {
+            path: path.into(),
+            source,
+        }
+    }
+
+    fn restore(path: &Path, lost: usize, source: io::Error) -> Self {
+        Self::Restore {
+            path: path.into(),
+            lost,
+            source,
+        }
+    }
+
+    fn pending(path: &Path, source: io::Error) -> Self {
+        Self::Pending {
+            path: path.into(),
+            source,
+        }
+    }
+}
+
+static SPILL_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Overflow storage for tasks in a file.
+#[derive(Debug)]
+struct SpillFile {
+    path: PathBuf,
+    writer: File,
+    reader: BufReader<File>,
+    len: usize,
+    end: u64,
+}
+
+impl SpillFile {
+    fn create(dir: &Path) -> io::Result<Self> {
+        let path = dir.join(format!(
+            "ci-monitor-queue-{}-{}.jsonl",
+            std::process::id(),
+            SPILL_COUNTER.fetch_add(1, Ordering::Relaxed),
+        ));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        // A separate handle is required so that reads do not move the write position.
+        let reader = File::open(&path)?;
+
+        Ok(Self {
+            path,
+            writer: file,
+            reader: BufReader::new(reader),
+            len: 0,
+            end: 0,
+        })
+    }
+
+    fn push(&mut self, task: &InstanceTask) -> io::Result<()> {
+        let mut line = serde_json::to_vec(task)?;
+        line.push(b'\n');
+
+        if let Err(err) = self.writer.write_all(&line) {
+            // Remove any partially written line.
+            let _ = self.writer.set_len(self.end);
+            let _ = self.writer.seek(SeekFrom::Start(self.end));
+            return Err(err);
+        }
+
+        self.end += line.len() as u64;
+        self.len += 1;
+        Ok(())
+    }
+
+    fn pop(&mut self) -> io::Result<InstanceTask> {
+        let mut line = String::new();
+        self.reader.read_line(&mut line)?;
+        let task = serde_json::from_str(&line)?;
+        self.len -= 1;
+
+        // Reclaim disk space once everything has been read back. The task has been read, so a
+        // failure here must not lose it; the file is just left to grow instead.
+        if self.len == 0 {
+            let _ = self.reset();
+        }
+
+        Ok(task)
+    }
+
+    fn reset(&mut self) -> io::Result<()> {
+        self.len = 0;
+        self.writer.set_len(0)?;
+        self.end = 0;
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.reader.seek(SeekFrom::Start(0))?;
+        Ok(())
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// A FIFO queue of tasks with a bounded number of tasks held in memory.
+///
+/// Tasks beyond the capacity are spilled to a file in the spill directory. Without a spill
+/// directory, the queue is unbounded. Tasks which fail to spill are kept in memory after the
+/// spilled tasks; later tasks are also kept there until the spill file has been drained.
+#[derive(Debug)]
+pub(crate) struct TaskQueue {
+    memory: VecDeque<InstanceTask>,
+    capacity: Option<usize>,
+    spill_dir: Option<PathBuf>,
+    spill: Option<SpillFile>,
+    unspilled: VecDeque<InstanceTask>,
+}
+
+impl TaskQueue {
+    pub(crate) fn new(capacity: Option<usize>, spill_dir: Option<PathBuf>) -> Self {
+        Self {
+            memory: VecDeque::new(),
+            capacity,
+            spill_dir,
+            spill: None,
+            unspilled: VecDeque::new(),
+        }
+    }
+
+    /// The number of tasks in the queue.
+    pub(crate) fn len(&self) -> usize {
+        self.memory.len() + self.spilled() + self.unspilled.len()
+    }
+
+    /// The number of tasks held on disk.
+    pub(crate) fn spilled(&self) -> usize {
+        self.spill.as_ref().map_or(0, |spill| spill.len)
+    }
+
+    fn is_full(&self) -> bool {
+        self.spill_dir.is_some()
+            && self
+                .capacity
+                .is_some_and(|capacity| self.memory.len() >= capacity)
+    }
+
+    /// Add a task to the end of the queue.
+    ///
+    /// On error, the task is kept in memory after any spilled tasks.
+    pub(crate) fn push_back(&mut self, task: InstanceTask) -> Result<(), TaskQueueError> {
+        // Once tasks have been spilled (or failed to spill), later tasks must follow them to keep
+        // the order.
+        if !self.unspilled.is_empty() {
+            self.unspilled.push_back(task);
+            return Ok(());
+        }
+        if self.spilled() == 0 && !self.is_full() {
+            self.memory.push_back(task);
+            return Ok(());
+        }
+
+        let res = self.spill_task(&task);
+        if res.is_err() {
+            self.unspilled.push_back(task);
+        }
+        res
+    }
+
+    fn spill_task(&mut self, task: &InstanceTask) -> Result<(), TaskQueueError> {
+        if self.spill.is_none() {
+            let dir = self
+                .spill_dir
+                .as_ref()
+                .expect("spilling requires a spill directory");
+            let spill = SpillFile::create(dir).map_err(|err| TaskQueueError::spill(dir, err))?;
+            self.spill = Some(spill);
+        }
+
+        let spill = self.spill.as_mut().expect("created above");
+        spill
+            .push(task)
+            .map_err(|err| TaskQueueError::spill(&spill.path, err))
+    }
+
+    /// Add a task to the front of the queue.
+    ///
+    /// This may exceed the in-memory capacity.
+    pub(crate) fn push_front(&mut self, task: InstanceTask) {
+        self.memory.push_front(task);
+    }
+
+    /// Remove the first task from the queue.
+    pub(crate) fn pop_front(&mut self) -> Result<Option<InstanceTask>, TaskQueueError> {
+        if self.memory.is_empty() {
+            self.restore()?;
+        }
+        Ok(self.memory.pop_front())
+    }
+
+    fn restore(&mut self) -> Result<(), TaskQueueError> {
+        if let Some(spill) = self.spill.as_mut() {
+            let batch = self.capacity.unwrap_or(1).max(1);
+            while spill.len > 0 && self.memory.len() < batch {
+                match spill.pop() {
+                    Ok(task) => self.memory.push_back(task),
+                    Err(err) => {
+                        let lost = spill.len;
+                        let _ = spill.reset();
+                        return Err(TaskQueueError::restore(&spill.path, lost, err));
+                    },
+                }
+            }
+        }
+
+        // Tasks which failed to spill follow the spilled tasks.
+        if self.spilled() == 0 {
+            self.memory.extend(self.unspilled.drain(..));
+        }
+
+        Ok(())
+    }
+
+    /// Remove all tasks from the queue.
+    pub(crate) fn clear(&mut self) {
+        self.memory.clear();
+        self.unspilled.clear();
+        if let Some(spill) = self.spill.as_mut() {
+            let _ = spill.reset();
+        }
+    }
+}
+
+/// Read tasks left for a later run.
+///
+/// A missing file has no tasks.
+pub(crate) fn read_pending(path: &Path) -> Result<Vec<InstanceTask>, TaskQueueError> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(TaskQueueError::pending(path, err)),
+    };
+
+    BufReader::new(file)
+        .lines()
+        .map(|line| {
+            let line = line?;
+            Ok(serde_json::from_str(&line)?)
+        })
+        .collect::<io::Result<_>>()
+        .map_err(|err| TaskQueueError::pending(path, err))
+}
+
+/// Write tasks left for a later run.
+///
+/// The file is removed if there are no tasks.
+pub(crate) fn write_pending(path: &Path, tasks: &[InstanceTask]) -> Result<(), TaskQueueError> {
+    let res = if tasks.is_empty() {
+        match fs::remove_file(path) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            res => res,
+        }
+    } else {
+        File::create(path).and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            for task in tasks {
+                serde_json::to_writer(&mut writer, task)?;
+                writer.write_all(b"\n")?;
+            }
+            writer.flush()
+        })
+    };
+
+    res.map_err(|err| TaskQueueError::pending(path, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+
+    use tempfile::TempDir;
+
+    use crate::queue::{self, TaskQueue};
+    use crate::{ForgeTask, InstanceTask};
+
+    fn runner(id: u64) -> InstanceTask {
+        InstanceTask::new(
+            "gitlab.example.com",
+            ForgeTask::UpdateRunner {
+                id,
+            },
+        )
+    }
+
+    fn drain(queue: &mut TaskQueue) -> Vec<u64> {
+        let mut ids = Vec::new();
+        while let Some(task) = queue.pop_front().unwrap() {
+            assert_eq!(task.instance, "gitlab.example.com");
+            if let ForgeTask::UpdateRunner {
+                id,
+            } = task.task
+            {
+                ids.push(id);
+            } else {
+                panic!("unexpected task: {:?}", task);
+            }
+        }
+        ids
+    }
+
+    #[test]
+    fn unbounded_without_spill_dir() {
+        let mut queue = TaskQueue::new(Some(2), None);
+        for id in 0..5 {
+            queue.push_back(runner(id)).unwrap();
+        }
+
+        assert_eq!(queue.len(), 5);
+        assert_eq!(queue.spilled(), 0);
+        assert_eq!(drain(&mut queue), [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn spill_overflow() {
+        let dir = TempDir::new().unwrap();
+        let mut queue = TaskQueue::new(Some(2), Some(dir.path().into()));
+        for id in 0..5 {
+            queue.push_back(runner(id)).unwrap();
+        }
+
+        assert_eq!(queue.len(), 5);
+        assert_eq!(queue.spilled(), 3);
+
+        // Tasks pushed while draining stay in order.
+        assert_eq!(
+            queue.pop_front().unwrap().map(|task| task.task.name()),
+            Some("update_runner"),
+        );
+        queue.push_back(runner(5)).unwrap();
+        queue.push_front(runner(0));
+        assert_eq!(drain(&mut queue), [0, 1, 2, 3, 4, 5]);
+        assert_eq!(queue.spilled(), 0);
+    }
+
+    #[test]
+    fn spill_file_removed() {
+        let dir = TempDir::new().unwrap();
+        let mut queue = TaskQueue::new(Some(1), Some(dir.path().into()));
+        for id in 0..3 {
+            queue.push_back(runner(id)).unwrap();
+        }
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        queue.clear();
+        assert_eq!(queue.len(), 0);
+
+        drop(queue);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn spill_failure_keeps_tasks() {
+        let dir = TempDir::new().unwrap();
+        let missing = dir.path().join("missing");
+        let mut queue = TaskQueue::new(Some(1), Some(missing));
+        queue.push_back(runner(0)).unwrap();
+        queue.push_back(runner(1)).unwrap_err();
+
+        assert_eq!(queue.len(), 2);
+        assert_eq!(drain(&mut queue), [0, 1]);
+    }
+
+    #[test]
+    fn spill_write_failure_keeps_order() {
+        let dir = TempDir::new().unwrap();
+        let mut queue = TaskQueue::new(Some(1), Some(dir.path().into()));
+        queue.push_back(runner(0)).unwrap();
+        queue.push_back(runner(1)).unwrap();
+        assert_eq!(queue.spilled(), 1);
+
+        // Make writes to the spill file fail.
+        let spill = queue.spill.as_mut().unwrap();
+        spill.writer = File::open(&spill.path).unwrap();
+        queue.push_back(runner(2)).unwrap_err();
+        queue.push_back(runner(3)).unwrap();
+
+        assert_eq!(queue.len(), 4);
+        assert_eq!(queue.spilled(), 1);
+        assert_eq!(drain(&mut queue), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn pending_tasks() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("pending.jsonl");
+        assert!(queue::read_pending(&path).unwrap().is_empty());
+
+        queue::write_pending(&path, &[runner(0), runner(1)]).unwrap();
+        let mut pending = TaskQueue::new(None, None);
+        for task in queue::read_pending(&path).unwrap() {
+            pending.push_back(task).unwrap();
+        }
+        assert_eq!(drain(&mut pending), [0, 1]);
+
+        queue::write_pending(&path, &[]).unwrap();
+        assert!(!path.exists());
+        queue::write_pending(&path, &[]).unwrap();
+    }
+
+    #[test]
+    fn pending_tasks_resume_page() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("pending.jsonl");
+        let mut task = InstanceTask::new("gitlab.example.com", ForgeTask::DiscoverUsers);
+        task.resume_page = Some(3);
+
+        queue::write_pending(&path, &[task, runner(0)]).unwrap();
+        let pending = queue::read_pending(&path).unwrap();
+
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].task.name(), "discover_users");
+        assert_eq!(pending[0].resume_page, Some(3));
+        assert_eq!(pending[1].resume_page, None);
+    }
+}
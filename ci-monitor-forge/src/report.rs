@@ -0,0 +1,498 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use schemars::JsonSchema;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{EndpointLatency, ForgeError, ForgeErrorKind, ForgeTask, PartialFailure};
+
+/// Errors which may occur when writing a run report.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum RunReportError {
+    /// The report could not be written.
+    #[error("failed to write run report to '{}': {}", path.display(), source)]
+    Write {
+        /// The path to the report.
+        path: PathBuf,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// The report could not be serialized.
+    #[error("failed to serialize run report: {}", source)]
+    Serialize {
+        /// The source of the error.
+        #[from]
+        source: serde_json::Error,
+    },
+}
+
+impl RunReportError {
+    fn write(path: PathBuf, source: io::Error) -> Self {
+        Self::Write {
+            path,
+            source,
+        }
+    }
+}
+
+/// Statistics for a kind of task within a run.
+#[derive(Debug, Clone, Copy, Default, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct TaskStatistics {
+    /// The number of tasks executed.
+    pub executed: u64,
+    /// The number of tasks which failed.
+    pub failed: u64,
+    /// The number of tasks which only partially completed.
+    pub partial: u64,
+}
+
+/// A task which failed during a run.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct TaskFailure {
+    /// The kind of task.
+    pub kind: &'static str,
+    /// A description of the task.
+    pub task: String,
+    /// The kind of error.
+    pub error: ForgeErrorKind,
+    /// The reason the task failed.
+    pub reason: String,
+    /// Whether the task may succeed if tried again.
+    pub retryable: bool,
+}
+
+/// A task which failed partway through during a run.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct PartialTaskFailure {
+    /// The kind of task.
+    pub kind: &'static str,
+    /// A description of the task.
+    pub task: String,
+    /// The kind of error.
+    pub error: ForgeErrorKind,
+    /// The reason the task failed.
+    pub reason: String,
+    /// Whether the task may succeed if tried again.
+    pub retryable: bool,
+    /// The number of items processed before the failure.
+    pub processed: usize,
+    /// The page from which the task may be resumed.
+    pub resume_page: Option<u64>,
+}
+
+/// A task which was running when a run stalled.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct StalledTask {
+    /// The kind of task.
+    pub kind: &'static str,
+    /// A description of the task.
+    pub task: String,
+    /// How long the task had been running (in seconds).
+    pub running: f64,
+}
+
+/// A period during which a run made no progress.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct RunStall {
+    /// When the stall was detected.
+    pub detected_at: DateTime<Utc>,
+    /// How long the run had gone without progress (in seconds).
+    pub idle: f64,
+    /// The tasks which were running.
+    pub in_flight: Vec<StalledTask>,
+    /// The number of tasks which were aborted and queued again.
+    pub restarted: u64,
+}
+
+/// A budget which limits how much work a run starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum RunBudget {
+    /// The maximum duration of the run.
+    Duration,
+    /// The maximum number of API requests made during the run.
+    ApiRequests,
+}
+
+/// A machine-readable report of a crawl.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct RunReport {
+    /// When the run started.
+    pub started_at: DateTime<Utc>,
+    /// When the run finished.
+    pub finished_at: DateTime<Utc>,
+    /// How long the run took (in seconds).
+    pub duration: f64,
+    /// Statistics for each kind of task.
+    pub tasks: BTreeMap<&'static str, TaskStatistics>,
+    /// Tasks which failed.
+    pub failures: Vec<TaskFailure>,
+    /// Tasks which failed after doing part of their work.
+    pub partial_failures: Vec<PartialTaskFailure>,
+    /// The number of entities of each type which were touched.
+    pub entities: BTreeMap<String, u64>,
+    /// The number of API requests made to the forge.
+    pub api_requests: u64,
+    /// The number of API requests made to the forge during each hour of the run.
+    ///
+    /// Keyed by the start of the hour.
+    pub api_requests_by_hour: BTreeMap<DateTime<Utc>, u64>,
+    /// Latency statistics for each API endpoint.
+    pub api_latency: Vec<EndpointLatency>,
+    /// The number of tasks which were abandoned because the run was canceled.
+    pub canceled: u64,
+    /// The largest number of queued tasks spilled to disk at once.
+    pub max_spilled: u64,
+    /// The number of times a discovery task was deferred due to a large backlog.
+    pub throttled: u64,
+    /// The number of queued tasks which were lost because they could not be read back from disk.
+    pub lost: u64,
+    /// Periods during which the run made no progress.
+    pub stalls: Vec<RunStall>,
+    /// The budget which stopped the run from starting further tasks.
+    pub budget_exhausted: Option<RunBudget>,
+    /// The number of queued tasks left for a later run because a budget was exhausted.
+    pub remaining: u64,
+    /// The number of tasks left for a later run because too many tasks of their instance were
+    /// waiting to run.
+    ///
+    /// Keyed by the name of the instance.
+    pub shed: BTreeMap<String, u64>,
+    /// The number of tasks queued for entities which the previous run found referenced but
+    /// unknown.
+    pub warm_started: u64,
+    /// The fraction of planned tasks which were executed.
+    ///
+    /// Planned tasks include those left for a later run and those abandoned due to cancellation.
+    pub completed: f64,
+}
+
+impl RunReport {
+    /// Start a new report.
+    pub fn start() -> Self {
+        let now = Utc::now();
+
+        Self {
+            started_at: now,
+            finished_at: now,
+            duration: 0.,
+            tasks: BTreeMap::new(),
+            failures: Vec::new(),
+            partial_failures: Vec::new(),
+            entities: BTreeMap::new(),
+            api_requests: 0,
+            api_requests_by_hour: BTreeMap::new(),
+            api_latency: Vec::new(),
+            canceled: 0,
+            max_spilled: 0,
+            throttled: 0,
+            lost: 0,
+            stalls: Vec::new(),
+            budget_exhausted: None,
+            remaining: 0,
+            shed: BTreeMap::new(),
+            warm_started: 0,
+            completed: 1.,
+        }
+    }
+
+    /// Record the result of a task.
+    pub fn record_task<T>(&mut self, task: &ForgeTask, result: Result<T, &ForgeError>) {
+        let stats = self.tasks.entry(task.name()).or_default();
+        stats.executed += 1;
+
+        if let Err(err) = result {
+            stats.failed += 1;
+            self.failures.push(TaskFailure {
+                kind: task.name(),
+                task: format!("{:?}", task),
+                error: err.kind(),
+                reason: err.to_string(),
+                retryable: err.is_retryable(),
+            });
+        }
+    }
+
+    /// Record a task which failed partway through.
+    ///
+    /// The task should also be recorded using `record_task`.
+    pub fn record_partial(&mut self, task: &ForgeTask, partial: &PartialFailure) {
+        let stats = self.tasks.entry(task.name()).or_default();
+        stats.partial += 1;
+
+        self.partial_failures.push(PartialTaskFailure {
+            kind: task.name(),
+            task: format!("{:?}", task),
+            error: partial.error,
+            reason: partial.reason.clone(),
+            retryable: partial.retryable,
+            processed: partial.processed,
+            resume_page: partial.resume_page,
+        });
+    }
+
+    /// Record tasks which were abandoned due to cancellation.
+    pub fn record_canceled(&mut self, count: u64) {
+        self.canceled += count;
+    }
+
+    /// Record the number of queued tasks currently spilled to disk.
+    pub fn record_spilled(&mut self, count: u64) {
+        self.max_spilled = self.max_spilled.max(count);
+    }
+
+    /// Record a discovery task deferred due to a large backlog.
+    pub fn record_throttled(&mut self) {
+        self.throttled += 1;
+    }
+
+    /// Record the total number of API requests made so far.
+    ///
+    /// Requests made since the last call are attributed to the current hour.
+    pub fn record_api_requests(&mut self, total: u64) {
+        self.record_api_requests_at(total, Utc::now());
+    }
+
+    fn record_api_requests_at(&mut self, total: u64, now: DateTime<Utc>) {
+        let recorded: u64 = self.api_requests_by_hour.values().sum();
+        let new = total.saturating_sub(recorded);
+        if new == 0 {
+            return;
+        }
+
+        let hour = now.duration_trunc(TimeDelta::hours(1)).unwrap_or(now);
+        *self.api_requests_by_hour.entry(hour).or_default() += new;
+    }
+
+    /// Record latency statistics for API endpoints.
+    pub fn record_api_latency(&mut self, latency: Vec<EndpointLatency>) {
+        self.api_latency = latency;
+    }
+
+    /// Record queued tasks which were lost.
+    pub fn record_lost(&mut self, count: u64) {
+        self.lost += count;
+    }
+
+    /// Record a period during which the run made no progress.
+    pub fn record_stall(&mut self, stall: RunStall) {
+        self.stalls.push(stall);
+    }
+
+    /// Record that a budget was exhausted with tasks still queued.
+    pub fn record_budget_exhausted(&mut self, budget: RunBudget, remaining: u64) {
+        self.budget_exhausted = Some(budget);
+        self.remaining = remaining;
+    }
+
+    /// Record a task left for a later run because its instance had too many tasks waiting.
+    pub fn record_shed(&mut self, instance: &str) {
+        *self.shed.entry(instance.into()).or_default() += 1;
+    }
+
+    /// Record tasks queued for entities the previous run found referenced but unknown.
+    pub fn record_warm_start(&mut self, count: u64) {
+        self.warm_started += count;
+    }
+
+    /// Record the number of entities of a given type which were touched.
+    pub fn record_entities<N>(&mut self, name: N, count: u64)
+    where
+        N: Into<String>,
+    {
+        *self.entities.entry(name.into()).or_default() += count;
+    }
+
+    /// Mark the run as finished.
+    pub fn finish(&mut self, api_requests: u64) {
+        self.finished_at = Utc::now();
+        self.duration = (self.finished_at - self.started_at)
+            .to_std()
+            .unwrap_or_default()
+            .as_secs_f64();
+        self.record_api_requests_at(api_requests, self.finished_at);
+        self.api_requests = api_requests;
+
+        let executed = self.executed();
+        let shed: u64 = self.shed.values().sum();
+        let planned = executed + self.remaining + self.canceled + shed;
+        self.completed = if planned > 0 {
+            executed as f64 / planned as f64
+        } else {
+            1.
+        };
+    }
+
+    /// The total number of tasks executed.
+    pub fn executed(&self) -> u64 {
+        self.tasks.values().map(|stats| stats.executed).sum()
+    }
+
+    /// The total number of tasks which failed.
+    pub fn failed(&self) -> u64 {
+        self.tasks.values().map(|stats| stats.failed).sum()
+    }
+
+    /// The total number of tasks which only partially completed.
+    pub fn partial(&self) -> u64 {
+        self.tasks.values().map(|stats| stats.partial).sum()
+    }
+
+    /// Write the report as JSON.
+    pub fn write(&self, path: &Path) -> Result<(), RunReportError> {
+        let file = File::create(path).map_err(|err| RunReportError::write(path.into(), err))?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writer
+            .flush()
+            .map_err(|err| RunReportError::write(path.into(), err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, TimeZone, Utc};
+
+    use crate::{ForgeError, ForgeErrorKind, ForgeTask, PartialFailure, RunBudget, RunReport};
+
+    #[test]
+    fn record_tasks() {
+        let mut report = RunReport::start();
+        let task = ForgeTask::UpdateUser {
+            user: 1,
+        };
+        let err = ForgeError::Auth {
+            details: "bad token".into(),
+        };
+
+        report.record_task::<()>(&task, Ok(()));
+        report.record_task::<()>(&task, Err(&err));
+        report.record_task::<()>(&ForgeTask::DiscoverRunners, Ok(()));
+        report.finish(5);
+
+        assert_eq!(report.executed(), 3);
+        assert_eq!(report.failed(), 1);
+        assert_eq!(report.tasks["update_user"].executed, 2);
+        assert_eq!(report.tasks["update_user"].failed, 1);
+        assert_eq!(report.tasks["discover_runners"].executed, 1);
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].kind, "update_user");
+        assert_eq!(report.failures[0].error, ForgeErrorKind::Auth);
+        assert_eq!(
+            report.failures[0].reason,
+            "cannot authenticate to the forge: bad token",
+        );
+        assert!(!report.failures[0].retryable);
+        assert_eq!(report.api_requests, 5);
+    }
+
+    #[test]
+    fn record_partial() {
+        let mut report = RunReport::start();
+        let task = ForgeTask::DiscoverPipelines {
+            project: 1,
+        };
+        let err = ForgeError::Connection {
+            details: "reset".into(),
+        };
+
+        report.record_task::<()>(&task, Ok(()));
+        report.record_partial(&task, &PartialFailure::new(40, &err));
+
+        assert_eq!(report.executed(), 1);
+        assert_eq!(report.failed(), 0);
+        assert_eq!(report.partial(), 1);
+        assert_eq!(report.partial_failures.len(), 1);
+        assert_eq!(report.partial_failures[0].kind, "discover_pipelines");
+        assert_eq!(report.partial_failures[0].error, ForgeErrorKind::Connection);
+        assert_eq!(report.partial_failures[0].processed, 40);
+        assert!(report.partial_failures[0].retryable);
+    }
+
+    #[test]
+    fn record_api_requests() {
+        let mut report = RunReport::start();
+        let hour = Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap();
+
+        report.record_api_requests_at(3, hour + TimeDelta::minutes(10));
+        report.record_api_requests_at(3, hour + TimeDelta::minutes(20));
+        report.record_api_requests_at(10, hour + TimeDelta::minutes(70));
+
+        assert_eq!(report.api_requests_by_hour.len(), 2);
+        assert_eq!(report.api_requests_by_hour[&hour], 3);
+        assert_eq!(
+            report.api_requests_by_hour[&(hour + TimeDelta::hours(1))],
+            7
+        );
+    }
+
+    #[test]
+    fn record_entities() {
+        let mut report = RunReport::start();
+
+        report.record_entities("job", 3);
+        report.record_entities("job", 2);
+        report.record_entities("user", 0);
+
+        assert_eq!(report.entities["job"], 5);
+        assert_eq!(report.entities["user"], 0);
+    }
+
+    #[test]
+    fn record_budget_exhausted() {
+        let mut report = RunReport::start();
+        let task = ForgeTask::UpdateUser {
+            user: 1,
+        };
+        report.record_task::<()>(&task, Ok(()));
+        report.record_budget_exhausted(RunBudget::ApiRequests, 3);
+        report.finish(1);
+
+        assert_eq!(report.budget_exhausted, Some(RunBudget::ApiRequests));
+        assert_eq!(report.remaining, 3);
+        assert_eq!(report.completed, 0.25);
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["budget_exhausted"], "api_requests");
+    }
+
+    #[test]
+    fn serialize() {
+        let mut report = RunReport::start();
+        report.record_task::<()>(
+            &ForgeTask::UpdateUser {
+                user: 1,
+            },
+            Err(&ForgeError::Other {
+                details: "oops".into(),
+            }),
+        );
+        report.finish(1);
+
+        let json = serde_json::to_value(&report).unwrap();
+
+        assert_eq!(json["tasks"]["update_user"]["failed"], 1);
+        assert_eq!(json["failures"][0]["error"], "other");
+        assert_eq!(json["failures"][0]["reason"], "oops");
+        assert_eq!(json["api_requests"], 1);
+    }
+}
@@ -0,0 +1,157 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use regex::Regex;
+use thiserror::Error;
+
+/// The capture group which names the host within a pattern.
+const HOST_GROUP: &str = "host";
+
+/// Errors which may occur when adding runner host rules.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum RunnerHostRuleError {
+    /// A host pattern is invalid.
+    #[error("invalid runner host pattern '{}': {}", pattern, source)]
+    InvalidPattern {
+        /// The pattern.
+        pattern: String,
+        /// The source of the error.
+        #[source]
+        source: regex::Error,
+    },
+}
+
+impl RunnerHostRuleError {
+    fn invalid_pattern(pattern: String, source: regex::Error) -> Self {
+        Self::InvalidPattern {
+            pattern,
+            source,
+        }
+    }
+}
+
+/// Heuristic rules which determine the host of a runner.
+///
+/// Fleets commonly encode host names in runner descriptions (e.g., `builder-01 (docker)`).
+/// Patterns are regular expressions; the `host` capture group names the host if present,
+/// otherwise the whole match does. Description patterns are tried before address patterns and
+/// the first match wins.
+#[derive(Debug, Clone, Default)]
+pub struct RunnerHostRules {
+    descriptions: Vec<Regex>,
+    addresses: Vec<Regex>,
+}
+
+fn compile(pattern: &str) -> Result<Regex, RunnerHostRuleError> {
+    Regex::new(pattern).map_err(|err| RunnerHostRuleError::invalid_pattern(pattern.into(), err))
+}
+
+fn host_name(patterns: &[Regex], haystack: &str) -> Option<String> {
+    patterns.iter().find_map(|pattern| {
+        let captures = pattern.captures(haystack)?;
+        let host = captures
+            .name(HOST_GROUP)
+            .or_else(|| captures.get(0))?
+            .as_str();
+        if host.is_empty() {
+            None
+        } else {
+            Some(host.into())
+        }
+    })
+}
+
+impl RunnerHostRules {
+    /// Add a pattern matched against runner descriptions.
+    pub fn add_description_pattern(
+        &mut self,
+        pattern: &str,
+    ) -> Result<&mut Self, RunnerHostRuleError> {
+        self.descriptions.push(compile(pattern)?);
+        Ok(self)
+    }
+
+    /// Add a pattern matched against the IP address a runner last contacted the forge from.
+    pub fn add_address_pattern(&mut self, pattern: &str) -> Result<&mut Self, RunnerHostRuleError> {
+        self.addresses.push(compile(pattern)?);
+        Ok(self)
+    }
+
+    /// Whether any rules are present.
+    pub fn is_empty(&self) -> bool {
+        self.descriptions.is_empty() && self.addresses.is_empty()
+    }
+
+    /// The name of the host a runner executes on.
+    pub fn host_name(&self, description: &str, ip_address: Option<&str>) -> Option<String> {
+        host_name(&self.descriptions, description)
+            .or_else(|| ip_address.and_then(|ip_address| host_name(&self.addresses, ip_address)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::RunnerHostRules;
+
+    #[test]
+    fn no_rules() {
+        let rules = RunnerHostRules::default();
+
+        assert!(rules.is_empty());
+        assert_eq!(rules.host_name("builder-01", Some("10.0.0.1")), None);
+    }
+
+    #[test]
+    fn invalid_pattern() {
+        let mut rules = RunnerHostRules::default();
+
+        assert!(rules.add_description_pattern("(").is_err());
+        assert!(rules.is_empty());
+    }
+
+    #[test]
+    fn description_rules() {
+        let mut rules = RunnerHostRules::default();
+        rules
+            .add_description_pattern(r"^(?P<host>[a-z]+-\d+) \(docker\)$")
+            .unwrap()
+            .add_description_pattern(r"mac-\d+")
+            .unwrap();
+
+        assert_eq!(
+            rules.host_name("builder-01 (docker)", None).as_deref(),
+            Some("builder-01"),
+        );
+        assert_eq!(
+            rules.host_name("shared mac-7 runner", None).as_deref(),
+            Some("mac-7"),
+        );
+        assert_eq!(rules.host_name("builder-01 (shell)", None), None);
+    }
+
+    #[test]
+    fn address_rules() {
+        let mut rules = RunnerHostRules::default();
+        rules
+            .add_description_pattern(r"^(?P<host>builder-\d+)")
+            .unwrap()
+            .add_address_pattern(r"^10\.0\.1\.\d+$")
+            .unwrap();
+
+        // Descriptions take precedence.
+        assert_eq!(
+            rules.host_name("builder-02", Some("10.0.1.5")).as_deref(),
+            Some("builder-02"),
+        );
+        assert_eq!(
+            rules.host_name("gpu runner", Some("10.0.1.5")).as_deref(),
+            Some("10.0.1.5"),
+        );
+        assert_eq!(rules.host_name("gpu runner", Some("10.0.2.5")), None);
+        assert_eq!(rules.host_name("gpu runner", None), None);
+    }
+}
@@ -4,6 +4,8 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+use serde::{Deserialize, Serialize};
+
 /// Metadata about a runner host that may be set.
 #[derive(Debug, Clone, Default)]
 #[non_exhaustive]
@@ -20,6 +22,8 @@ pub struct RunnerHostData {
     ///
     /// If provided, this can be used to estimate how much jobs, pipelines, projects, etc. cost.
     pub estimated_cost_per_hour: Option<Option<f64>>,
+    /// The maximum number of jobs the host may run at once.
+    pub max_concurrent_jobs: Option<Option<u64>>,
 }
 
 /// Maintenance tasks separate from forge tasks.
@@ -52,7 +56,10 @@ pub enum MaintenanceTask {
 ///
 /// All tasks are implicitly for a given `Instance`, so such information is not present within the
 /// task itself.
-#[derive(Debug, Clone)]
+///
+/// Use `InstanceTask` to scope tasks to an instance when tasks for several instances share a
+/// queue.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[non_exhaustive]
 pub enum ForgeTask {
     /// Update a project by name.
@@ -83,6 +90,20 @@ pub enum ForgeTask {
         /// The ID of the user.
         user: u64,
     },
+    /// Discover users on the forge.
+    DiscoverUsers,
+    /// Update all stored users.
+    ///
+    /// Picks up changes made on the forge such as renamed handles.
+    RefreshUsers,
+    /// Discover the projects within a group (including its subgroups).
+    ///
+    /// Stored projects within the group which are no longer listed are marked as removed from the
+    /// group.
+    DiscoverGroupProjects {
+        /// The path of the group.
+        group: String,
+    },
     /// Discover runners on the forge.
     DiscoverRunners,
     /// Update a runner.
@@ -139,6 +160,25 @@ pub enum ForgeTask {
         /// The ID of the pipeline.
         pipeline: u64,
     },
+    /// Check that a stored pipeline still exists on the forge.
+    ///
+    /// Pipelines which are gone (e.g., removed by retention policies) are marked as deleted.
+    /// Pipelines whose status differs from the stored one are updated.
+    ReconcilePipeline {
+        /// The ID of the project.
+        project: u64,
+        /// The ID of the pipeline.
+        pipeline: u64,
+    },
+    /// Fetch the CI configuration used by a pipeline.
+    ///
+    /// Requires blob storage.
+    FetchPipelineConfig {
+        /// The ID of the project.
+        project: u64,
+        /// The ID of the pipeline.
+        pipeline: u64,
+    },
     /// Discover environments on a project.
     DiscoverEnvironments {
         /// The ID of the project.
@@ -167,6 +207,18 @@ pub enum ForgeTask {
         /// The ID of the deployment.
         deployment: u64,
     },
+    /// Discover releases on a project.
+    DiscoverReleases {
+        /// The ID of the project.
+        project: u64,
+    },
+    /// Discover pushes to a project.
+    ///
+    /// Used to find pushes which did not produce a pipeline.
+    DiscoverPushes {
+        /// The ID of the project.
+        project: u64,
+    },
     /// Discover jobps on a pipeline.
     DiscoverJobs {
         /// The ID of the project.
@@ -203,4 +255,284 @@ pub enum ForgeTask {
         /// Used to grab a specific file from an archive rather than the full archive.
         sub_artifact: Option<String>,
     },
+    /// Fetch the log of a job.
+    ///
+    /// Requires blob storage.
+    FetchJobLog {
+        /// The ID of the project.
+        project: u64,
+        /// The ID of the job.
+        job: u64,
+    },
+    /// A task handled by an externally registered handler.
+    ///
+    /// See `TaskHandlers`.
+    Custom {
+        /// The name of the handler for the task.
+        name: String,
+        /// Data for the handler.
+        payload: serde_json::Value,
+    },
+}
+
+impl ForgeTask {
+    /// A name for the kind of task.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::UpdateProjectByName {
+                ..
+            } => "update_project_by_name",
+            Self::UpdateProject {
+                ..
+            } => "update_project",
+            Self::UpdateUserByName {
+                ..
+            } => "update_user_by_name",
+            Self::UpdateUser {
+                ..
+            } => "update_user",
+            Self::DiscoverUsers => "discover_users",
+            Self::RefreshUsers => "refresh_users",
+            Self::DiscoverGroupProjects {
+                ..
+            } => "discover_group_projects",
+            Self::DiscoverRunners => "discover_runners",
+            Self::UpdateRunner {
+                ..
+            } => "update_runner",
+            Self::DiscoverPipelineSchedules {
+                ..
+            } => "discover_pipeline_schedules",
+            Self::UpdatePipelineSchedule {
+                ..
+            } => "update_pipeline_schedule",
+            Self::DiscoverMergeRequests {
+                ..
+            } => "discover_merge_requests",
+            Self::UpdateMergeRequest {
+                ..
+            } => "update_merge_request",
+            Self::DiscoverPipelines {
+                ..
+            } => "discover_pipelines",
+            Self::DiscoverMergeRequestPipelines {
+                ..
+            } => "discover_merge_request_pipelines",
+            Self::UpdatePipeline {
+                ..
+            } => "update_pipeline",
+            Self::ReconcilePipeline {
+                ..
+            } => "reconcile_pipeline",
+            Self::FetchPipelineConfig {
+                ..
+            } => "fetch_pipeline_config",
+            Self::DiscoverEnvironments {
+                ..
+            } => "discover_environments",
+            Self::UpdateEnvironment {
+                ..
+            } => "update_environment",
+            Self::DiscoverDeployments {
+                ..
+            } => "discover_deployments",
+            Self::UpdateDeployments {
+                ..
+            } => "update_deployments",
+            Self::DiscoverReleases {
+                ..
+            } => "discover_releases",
+            Self::DiscoverPushes {
+                ..
+            } => "discover_pushes",
+            Self::DiscoverJobs {
+                ..
+            } => "discover_jobs",
+            Self::UpdateJob {
+                ..
+            } => "update_job",
+            Self::UpdateJobArtifacts {
+                ..
+            } => "update_job_artifacts",
+            Self::FetchJobArtifact {
+                ..
+            } => "fetch_job_artifact",
+            Self::FetchJobLog {
+                ..
+            } => "fetch_job_log",
+            Self::Custom {
+                ..
+            } => "custom",
+        }
+    }
+
+    /// Whether the task discovers other entities.
+    ///
+    /// Discovery tasks tend to schedule many more tasks.
+    pub fn is_discovery(&self) -> bool {
+        matches!(
+            self,
+            Self::DiscoverUsers
+                | Self::RefreshUsers
+                | Self::DiscoverGroupProjects { .. }
+                | Self::DiscoverRunners
+                | Self::DiscoverPipelineSchedules { .. }
+                | Self::DiscoverMergeRequests { .. }
+                | Self::DiscoverPipelines { .. }
+                | Self::DiscoverMergeRequestPipelines { .. }
+                | Self::DiscoverEnvironments { .. }
+                | Self::DiscoverDeployments { .. }
+                | Self::DiscoverReleases { .. }
+                | Self::DiscoverPushes { .. }
+                | Self::DiscoverJobs { .. },
+        )
+    }
+
+    /// Whether the task fetches a single entity which other entities refer to.
+    ///
+    /// Tasks other than discovery tasks schedule these when they refer to an unknown entity.
+    pub fn is_reference_update(&self) -> bool {
+        matches!(
+            self,
+            Self::UpdateProject { .. } | Self::UpdateUser { .. } | Self::UpdateRunner { .. },
+        )
+    }
+}
+
+/// A task scoped to the instance whose forge should run it.
+///
+/// Allows a single queue to hold tasks for several instances. Tasks discovered while running a
+/// task belong to the same instance.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct InstanceTask {
+    /// The instance the task belongs to.
+    pub instance: String,
+    /// The task.
+    pub task: ForgeTask,
+    /// The page to resume the task from after a partial failure.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resume_page: Option<u64>,
+}
+
+impl InstanceTask {
+    /// Scope a task to an instance.
+    pub fn new<I>(instance: I, task: ForgeTask) -> Self
+    where
+        I: Into<String>,
+    {
+        Self {
+            instance: instance.into(),
+            task,
+            resume_page: None,
+        }
+    }
+
+    /// A description of the task.
+    ///
+    /// The instance is omitted if it is empty.
+    pub fn description(&self) -> String {
+        if self.instance.is_empty() {
+            format!("{:?}", self.task)
+        } else {
+            format!("{}: {:?}", self.instance, self.task)
+        }
+    }
+}
+
+/// An entity which may be refreshed along with its children.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RefreshTarget {
+    /// A project along with its merge requests, pipelines, schedules, environments, deployments,
+    /// and releases.
+    Project {
+        /// The ID of the project.
+        project: u64,
+    },
+    /// A merge request along with its pipelines.
+    MergeRequest {
+        /// The ID of the project.
+        project: u64,
+        /// The ID of the merge request.
+        merge_request: u64,
+    },
+    /// A pipeline along with its jobs.
+    Pipeline {
+        /// The ID of the project.
+        project: u64,
+        /// The ID of the pipeline.
+        pipeline: u64,
+    },
+    /// All stored users.
+    Users,
+}
+
+impl RefreshTarget {
+    /// The tasks which re-fetch the entity and discover its children.
+    ///
+    /// Tasks normally only discover children when the entity has changed, so discovery is
+    /// requested explicitly.
+    pub fn tasks(self) -> Vec<ForgeTask> {
+        match self {
+            Self::Project {
+                project,
+            } => {
+                vec![
+                    ForgeTask::UpdateProject {
+                        project,
+                    },
+                    ForgeTask::DiscoverMergeRequests {
+                        project,
+                    },
+                    ForgeTask::DiscoverPipelineSchedules {
+                        project,
+                    },
+                    ForgeTask::DiscoverPipelines {
+                        project,
+                    },
+                    ForgeTask::DiscoverEnvironments {
+                        project,
+                    },
+                    ForgeTask::DiscoverDeployments {
+                        project,
+                    },
+                    ForgeTask::DiscoverReleases {
+                        project,
+                    },
+                ]
+            },
+            Self::MergeRequest {
+                project,
+                merge_request,
+            } => {
+                vec![
+                    ForgeTask::UpdateMergeRequest {
+                        project,
+                        merge_request,
+                    },
+                    ForgeTask::DiscoverMergeRequestPipelines {
+                        project,
+                        merge_request,
+                    },
+                ]
+            },
+            Self::Pipeline {
+                project,
+                pipeline,
+            } => {
+                vec![
+                    ForgeTask::UpdatePipeline {
+                        project,
+                        pipeline,
+                    },
+                    ForgeTask::DiscoverJobs {
+                        project,
+                        pipeline,
+                    },
+                ]
+            },
+            Self::Users => vec![ForgeTask::RefreshUsers],
+        }
+    }
 }
@@ -0,0 +1,418 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+
+use ci_monitor_core::data::{
+    ArtifactDependencies, PipelineVariable, PipelineVariableType, PipelineVariables,
+};
+use serde_yaml::{Mapping, Value};
+
+/// Top-level keys which do not define jobs.
+const GLOBAL_KEYWORDS: &[&str] = &[
+    "after_script",
+    "before_script",
+    "cache",
+    "default",
+    "image",
+    "include",
+    "services",
+    "spec",
+    "stages",
+    "variables",
+    "workflow",
+];
+
+/// The stages used when the configuration does not declare any.
+const DEFAULT_STAGES: &[&str] = &["build", "test", "deploy"];
+
+/// The maximum depth of `extends` chains supported by GitLab.
+const MAX_EXTENDS_DEPTH: usize = 11;
+
+/// Extract the artifact dependencies between jobs from a CI configuration.
+///
+/// Only jobs defined in the configuration itself are considered; `include` directives are not
+/// resolved. Returns `None` if the configuration cannot be parsed.
+pub fn artifact_dependencies(config: &[u8]) -> Option<ArtifactDependencies> {
+    let mut config: Value = serde_yaml::from_slice(config).ok()?;
+    config.apply_merge().ok()?;
+    let config = config.as_mapping()?;
+
+    let mut deps = ArtifactDependencies::default();
+    deps.stages = if let Some(stages) = config.get("stages").and_then(Value::as_sequence) {
+        stages
+            .iter()
+            .filter_map(Value::as_str)
+            .map(Into::into)
+            .collect()
+    } else {
+        DEFAULT_STAGES.iter().copied().map(Into::into).collect()
+    };
+
+    for (name, job) in config {
+        let name = if let Some(name) = name.as_str() {
+            name
+        } else {
+            continue;
+        };
+        // Hidden keys are templates rather than jobs.
+        if name.starts_with('.') || GLOBAL_KEYWORDS.contains(&name) || !job.is_mapping() {
+            continue;
+        }
+
+        if let Some(sources) = artifact_sources(config, job, 0) {
+            deps.jobs.insert(name.into(), sources);
+        }
+    }
+
+    Some(deps)
+}
+
+/// The jobs a job consumes artifacts from (if not the default).
+fn artifact_sources(config: &Mapping, job: &Value, depth: usize) -> Option<Vec<String>> {
+    // `dependencies` restricts which of the `needs` provide artifacts.
+    if let Some(dependencies) = job.get("dependencies") {
+        let dependencies = dependencies.as_sequence()?;
+        return Some(
+            dependencies
+                .iter()
+                .filter_map(Value::as_str)
+                .map(Into::into)
+                .collect(),
+        );
+    }
+    if let Some(needs) = job.get("needs") {
+        let needs = needs.as_sequence()?;
+        return Some(
+            needs
+                .iter()
+                .filter_map(need_artifacts)
+                .map(Into::into)
+                .collect(),
+        );
+    }
+
+    if depth >= MAX_EXTENDS_DEPTH {
+        return None;
+    }
+    // Later templates take precedence.
+    templates(job)
+        .into_iter()
+        .rev()
+        .filter_map(|template| config.get(template))
+        .find_map(|template| artifact_sources(config, template, depth + 1))
+}
+
+/// The templates a job extends.
+fn templates(job: &Value) -> Vec<&str> {
+    match job.get("extends") {
+        Some(Value::String(template)) => vec![template.as_str()],
+        Some(Value::Sequence(templates)) => templates.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Extract the variables a job is configured with from a CI configuration.
+///
+/// Global variables are included unless the job opts out of inheriting them. Variables from
+/// `extends` templates are merged with later templates (and the job itself) taking precedence.
+/// As with artifact dependencies, `include` directives are not resolved. Returns `None` if the
+/// configuration cannot be parsed or does not define the job.
+pub fn job_variables(config: &[u8], job: &str) -> Option<PipelineVariables> {
+    if job.starts_with('.') || GLOBAL_KEYWORDS.contains(&job) {
+        return None;
+    }
+    let mut config: Value = serde_yaml::from_slice(config).ok()?;
+    config.apply_merge().ok()?;
+    let config = config.as_mapping()?;
+    let definition = config
+        .get(job)
+        .filter(|definition| definition.is_mapping())?;
+
+    let mut variables = BTreeMap::new();
+    if let Some(global) = config.get("variables") {
+        match inherited_variables(config, definition, 0) {
+            Some(Value::Bool(false)) => (),
+            Some(Value::Sequence(names)) => {
+                let names = names.iter().filter_map(Value::as_str).collect::<Vec<_>>();
+                merge_variables(global, &mut variables, |name| names.contains(&name));
+            },
+            _ => merge_variables(global, &mut variables, |_| true),
+        }
+    }
+    job_definition_variables(config, definition, 0, &mut variables);
+
+    Some(
+        variables
+            .into_iter()
+            .map(|(name, value)| {
+                let variable = PipelineVariable::builder()
+                    .value(value)
+                    .type_(PipelineVariableType::String)
+                    .build()
+                    .unwrap();
+                (name, variable)
+            })
+            .collect(),
+    )
+}
+
+/// The `inherit:variables` setting of a job.
+fn inherited_variables<'a>(config: &'a Mapping, job: &'a Value, depth: usize) -> Option<&'a Value> {
+    if let Some(inherit) = job
+        .get("inherit")
+        .and_then(|inherit| inherit.get("variables"))
+    {
+        return Some(inherit);
+    }
+
+    if depth >= MAX_EXTENDS_DEPTH {
+        return None;
+    }
+    templates(job)
+        .into_iter()
+        .rev()
+        .filter_map(|template| config.get(template))
+        .find_map(|template| inherited_variables(config, template, depth + 1))
+}
+
+/// Merge the variables defined by a job and the templates it extends.
+fn job_definition_variables(
+    config: &Mapping,
+    job: &Value,
+    depth: usize,
+    variables: &mut BTreeMap<String, String>,
+) {
+    if depth < MAX_EXTENDS_DEPTH {
+        for template in templates(job) {
+            if let Some(template) = config.get(template) {
+                job_definition_variables(config, template, depth + 1, variables);
+            }
+        }
+    }
+    if let Some(own) = job.get("variables") {
+        merge_variables(own, variables, |_| true);
+    }
+}
+
+/// Merge a `variables` mapping into a set of variables.
+fn merge_variables<F>(definitions: &Value, variables: &mut BTreeMap<String, String>, filter: F)
+where
+    F: Fn(&str) -> bool,
+{
+    let definitions = if let Some(definitions) = definitions.as_mapping() {
+        definitions
+    } else {
+        return;
+    };
+
+    for (name, definition) in definitions {
+        let name = if let Some(name) = name.as_str() {
+            name
+        } else {
+            continue;
+        };
+        if !filter(name) {
+            continue;
+        }
+        if let Some(value) = variable_value(definition) {
+            variables.insert(name.into(), value);
+        }
+    }
+}
+
+/// The value of a variable definition.
+///
+/// Variables may be given as scalars or as a mapping with a `value` key.
+fn variable_value(definition: &Value) -> Option<String> {
+    match definition {
+        Value::Null => Some(String::new()),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::String(s) => Some(s.clone()),
+        Value::Mapping(definition) => {
+            match definition.get("value") {
+                Some(Value::Mapping(_)) => None,
+                Some(value) => variable_value(value),
+                None => Some(String::new()),
+            }
+        },
+        _ => None,
+    }
+}
+
+/// The job a `needs` entry consumes artifacts from.
+fn need_artifacts(need: &Value) -> Option<&str> {
+    match need {
+        Value::String(job) => Some(job),
+        Value::Mapping(need) => {
+            // Needs on other pipelines do not refer to jobs in this pipeline.
+            if need.contains_key("project") || need.contains_key("pipeline") {
+                return None;
+            }
+            if need.get("artifacts").and_then(Value::as_bool) == Some(false) {
+                return None;
+            }
+            need.get("job").and_then(Value::as_str)
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    const CONFIG: &str = "
+stages:
+  - build
+  - test
+  - package
+
+.deps:
+  dependencies:
+    - build:linux
+
+build:linux:
+  stage: build
+  script: make
+
+build:windows:
+  stage: build
+  script: make
+
+test:linux:
+  stage: test
+  needs:
+    - build:linux
+    - job: build:windows
+      artifacts: false
+    - project: other/project
+      job: build
+      ref: main
+      artifacts: true
+  script: make test
+
+test:templated:
+  stage: test
+  extends: .deps
+  script: make test
+
+package:
+  stage: package
+  script: make package
+
+upload:
+  stage: package
+  needs: []
+  script: upload
+";
+
+    #[test]
+    fn artifact_dependencies() {
+        let deps = super::artifact_dependencies(CONFIG.as_bytes()).unwrap();
+
+        assert_eq!(deps.stages, ["build", "test", "package"]);
+        assert_eq!(deps.jobs.len(), 3);
+        assert_eq!(deps.jobs["test:linux"], ["build:linux"]);
+        assert_eq!(deps.jobs["test:templated"], ["build:linux"]);
+        assert!(deps.jobs["upload"].is_empty());
+    }
+
+    #[test]
+    fn artifact_dependencies_default_stages() {
+        let deps = super::artifact_dependencies(b"build:\n  script: make\n").unwrap();
+
+        assert_eq!(deps.stages, ["build", "test", "deploy"]);
+        assert!(deps.jobs.is_empty());
+    }
+
+    const VARIABLES_CONFIG: &str = "
+variables:
+  GLOBAL: global
+  OVERRIDDEN: global
+  DESCRIBED:
+    value: described
+    description: A variable with a description.
+
+.template:
+  variables:
+    TEMPLATE: template
+    OVERRIDDEN: template
+
+build:
+  extends: .template
+  variables:
+    OVERRIDDEN: job
+    JOBS: 4
+  script: make
+
+isolated:
+  inherit:
+    variables: false
+  variables:
+    OWN: own
+  script: make
+
+selective:
+  inherit:
+    variables:
+      - GLOBAL
+  script: make
+";
+
+    fn job_variables(job: &str) -> Option<Vec<(String, String)>> {
+        super::job_variables(VARIABLES_CONFIG.as_bytes(), job).map(|variables| {
+            variables
+                .variables
+                .into_iter()
+                .map(|(name, variable)| (name, variable.value))
+                .collect()
+        })
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|&(name, value)| (name.into(), value.into()))
+            .collect()
+    }
+
+    #[test]
+    fn job_variables_merged() {
+        assert_eq!(
+            job_variables("build").unwrap(),
+            pairs(&[
+                ("DESCRIBED", "described"),
+                ("GLOBAL", "global"),
+                ("JOBS", "4"),
+                ("OVERRIDDEN", "job"),
+                ("TEMPLATE", "template"),
+            ]),
+        );
+    }
+
+    #[test]
+    fn job_variables_inherit() {
+        assert_eq!(job_variables("isolated").unwrap(), pairs(&[("OWN", "own")]));
+        assert_eq!(
+            job_variables("selective").unwrap(),
+            pairs(&[("GLOBAL", "global")]),
+        );
+    }
+
+    #[test]
+    fn job_variables_unknown_job() {
+        assert!(job_variables("missing").is_none());
+        assert!(job_variables(".template").is_none());
+        assert!(job_variables("variables").is_none());
+        assert!(super::job_variables(b"{", "build").is_none());
+    }
+
+    #[test]
+    fn artifact_dependencies_invalid() {
+        assert!(super::artifact_dependencies(b"- not a mapping").is_none());
+        assert!(super::artifact_dependencies(b"{").is_none());
+    }
+}
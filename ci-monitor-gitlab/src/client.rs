@@ -0,0 +1,147 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::time::Instant;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use ci_monitor_forge::ApiMetrics;
+use gitlab::api::{ApiError, AsyncClient, RestClient};
+use gitlab::AsyncGitlab;
+use http::request::Builder as RequestBuilder;
+use http::{Method, Response, Uri};
+use url::Url;
+
+/// A GitLab client which records the latency of each request.
+pub(crate) struct InstrumentedGitlab {
+    gitlab: AsyncGitlab,
+    metrics: ApiMetrics,
+}
+
+impl InstrumentedGitlab {
+    pub(crate) fn new(gitlab: AsyncGitlab) -> Self {
+        Self {
+            gitlab,
+            metrics: ApiMetrics::default(),
+        }
+    }
+
+    pub(crate) fn metrics(&self) -> &ApiMetrics {
+        &self.metrics
+    }
+}
+
+impl RestClient for InstrumentedGitlab {
+    type Error = <AsyncGitlab as RestClient>::Error;
+
+    fn rest_endpoint(&self, endpoint: &str) -> Result<Url, ApiError<Self::Error>> {
+        self.gitlab.rest_endpoint(endpoint)
+    }
+
+    fn instance_endpoint(&self, endpoint: &str) -> Result<Url, ApiError<Self::Error>> {
+        self.gitlab.instance_endpoint(endpoint)
+    }
+}
+
+#[async_trait]
+impl AsyncClient for InstrumentedGitlab {
+    async fn rest_async(
+        &self,
+        request: RequestBuilder,
+        body: Vec<u8>,
+    ) -> Result<Response<Bytes>, ApiError<Self::Error>> {
+        let endpoint = endpoint_name(request.method_ref(), request.uri_ref());
+        let start = Instant::now();
+        let rsp = self.gitlab.rest_async(request, body).await;
+        let success = rsp.as_ref().is_ok_and(|rsp| {
+            let status = rsp.status();
+            !status.is_client_error() && !status.is_server_error()
+        });
+        self.metrics.record(&endpoint, start.elapsed(), success);
+        rsp
+    }
+}
+
+/// A name for the endpoint of a request.
+///
+/// IDs and paths are replaced by placeholders so that requests to the same endpoint are grouped
+/// together.
+fn endpoint_name(method: Option<&Method>, uri: Option<&Uri>) -> String {
+    let method = method.map_or("GET", Method::as_str);
+    let path = uri.map_or("", Uri::path);
+    let path = path
+        .split_once("/api/v4")
+        .map_or(path, |(_, endpoint)| endpoint);
+
+    let mut name = String::from(method);
+    name.push(' ');
+    let mut segments = path.split('/').filter(|segment| !segment.is_empty());
+    while let Some(segment) = segments.next() {
+        name.push('/');
+        if segment.bytes().all(|b| b.is_ascii_digit()) || segment.contains("%2F") {
+            name.push_str(":id");
+        } else {
+            name.push_str(segment);
+        }
+
+        // Files within artifact archives are arbitrary paths.
+        if segment == "artifacts" && segments.next().is_some() {
+            name.push_str("/:path");
+            break;
+        }
+    }
+
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{Method, Uri};
+
+    fn endpoint_name(method: Method, uri: &str) -> String {
+        super::endpoint_name(Some(&method), Some(&uri.parse::<Uri>().unwrap()))
+    }
+
+    #[test]
+    fn endpoint_name_ids() {
+        assert_eq!(
+            endpoint_name(
+                Method::GET,
+                "https://gitlab.example.com/api/v4/projects/13/jobs/456/trace",
+            ),
+            "GET /projects/:id/jobs/:id/trace",
+        );
+        assert_eq!(
+            endpoint_name(
+                Method::GET,
+                "https://gitlab.example.com/api/v4/projects/group%2Fproject?page=2",
+            ),
+            "GET /projects/:id",
+        );
+        assert_eq!(
+            endpoint_name(Method::POST, "https://gitlab.example.com/api/v4/runners"),
+            "POST /runners",
+        );
+    }
+
+    #[test]
+    fn endpoint_name_artifact_path() {
+        assert_eq!(
+            endpoint_name(
+                Method::GET,
+                "https://gitlab.example.com/api/v4/projects/1/jobs/2/artifacts/build/output.log",
+            ),
+            "GET /projects/:id/jobs/:id/artifacts/:path",
+        );
+        assert_eq!(
+            endpoint_name(
+                Method::GET,
+                "https://gitlab.example.com/api/v4/projects/1/jobs/2/artifacts",
+            ),
+            "GET /projects/:id/jobs/:id/artifacts",
+        );
+    }
+}
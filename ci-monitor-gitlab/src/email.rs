@@ -0,0 +1,917 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Ingestion of GitLab pipeline notification emails.
+//!
+//! When API access to an instance is not available, the pipeline notification emails sent by
+//! GitLab can still provide some insight into the state of CI. The information available is
+//! limited, so projects and pipelines created from emails are marked with
+//! `DataFidelity::Notification`.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use ci_monitor_core::data::{
+    DataFidelity, Instance, MergeRequest, Pipeline, PipelineBuilderError, PipelineSchedule,
+    PipelineSource, PipelineStatus, Project, ProjectBuilderError, User,
+};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+use thiserror::Error;
+
+use crate::forge::find_or_create_instance;
+use crate::instance::InstanceAliases;
+
+/// Errors which may occur when ingesting notification emails.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum NotificationError {
+    /// The email is not a pipeline notification.
+    #[error("not a pipeline notification: missing the `{}` header", header)]
+    NotAPipelineNotification {
+        /// The header which is missing.
+        header: &'static str,
+    },
+    /// A header has an invalid value.
+    #[error("invalid value for the `{}` header: {}", header, value)]
+    InvalidHeader {
+        /// The header.
+        header: &'static str,
+        /// The value of the header.
+        value: String,
+    },
+    /// Failure to create a project from a notification.
+    #[error("failed to create a project from a notification: {}", source)]
+    Project {
+        /// The source of the error.
+        #[from]
+        source: ProjectBuilderError,
+    },
+    /// Failure to create a pipeline from a notification.
+    #[error("failed to create a pipeline from a notification: {}", source)]
+    Pipeline {
+        /// The source of the error.
+        #[from]
+        source: PipelineBuilderError,
+    },
+    /// Failure to read an email.
+    #[error("failed to read {}: {}", path.display(), source)]
+    Read {
+        /// The path being read.
+        path: PathBuf,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+}
+
+impl NotificationError {
+    fn missing(header: &'static str) -> Self {
+        Self::NotAPipelineNotification {
+            header,
+        }
+    }
+
+    fn invalid(header: &'static str, value: &str) -> Self {
+        Self::InvalidHeader {
+            header,
+            value: value.into(),
+        }
+    }
+
+    fn read(path: PathBuf, source: io::Error) -> Self {
+        Self::Read {
+            path,
+            source,
+        }
+    }
+}
+
+const PROJECT_ID_HEADER: &str = "X-GitLab-Project-Id";
+const PROJECT_NAME_HEADER: &str = "X-GitLab-Project";
+const PROJECT_PATH_HEADER: &str = "X-GitLab-Project-Path";
+const PIPELINE_ID_HEADER: &str = "X-GitLab-Pipeline-Id";
+const PIPELINE_REF_HEADER: &str = "X-GitLab-Pipeline-Ref";
+const PIPELINE_STATUS_HEADER: &str = "X-GitLab-Pipeline-Status";
+const DATE_HEADER: &str = "Date";
+const CONTENT_TYPE_HEADER: &str = "Content-Type";
+const CONTENT_TRANSFER_ENCODING_HEADER: &str = "Content-Transfer-Encoding";
+
+/// A pipeline notification sent by GitLab via email.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct PipelineNotification {
+    /// The ID of the project.
+    pub project_id: u64,
+    /// The name of the project.
+    pub project_name: Option<String>,
+    /// The path of the project on the instance.
+    pub project_path: Option<String>,
+    /// The ID of the pipeline.
+    pub pipeline_id: u64,
+    /// The ref the pipeline built.
+    pub refname: Option<String>,
+    /// The status of the pipeline.
+    pub status: PipelineStatus,
+    /// The commit the pipeline built (if found in the body).
+    pub sha: Option<String>,
+    /// The URL of the pipeline (if found in the body).
+    pub url: Option<String>,
+    /// When the notification was sent.
+    pub date: Option<DateTime<Utc>>,
+}
+
+struct Headers {
+    headers: Vec<(String, String)>,
+}
+
+impl Headers {
+    fn parse(raw: &str) -> Self {
+        let mut headers: Vec<(String, String)> = Vec::new();
+
+        for line in raw.lines() {
+            if line.starts_with([' ', '\t']) {
+                // Folded continuation of the previous header.
+                if let Some((_, value)) = headers.last_mut() {
+                    value.push(' ');
+                    value.push_str(line.trim());
+                }
+            } else if let Some((name, value)) = line.split_once(':') {
+                headers.push((name.trim().into(), value.trim().into()));
+            }
+        }
+
+        for (_, value) in &mut headers {
+            *value = decode_encoded_words(value);
+        }
+
+        Self {
+            headers,
+        }
+    }
+
+    /// Split a message (or a part of a multipart message) into its headers and body.
+    fn split(raw: &str) -> (Self, &str) {
+        let (raw_headers, body) =
+            if let Some(body) = raw.strip_prefix("\r\n").or_else(|| raw.strip_prefix('\n')) {
+                // A part without any headers.
+                ("", body)
+            } else {
+                raw.split_once("\r\n\r\n")
+                    .or_else(|| raw.split_once("\n\n"))
+                    .unwrap_or((raw, ""))
+            };
+        (Self::parse(raw_headers), body)
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn require(&self, name: &'static str) -> Result<&str, NotificationError> {
+        self.get(name)
+            .ok_or_else(|| NotificationError::missing(name))
+    }
+
+    fn require_id(&self, name: &'static str) -> Result<u64, NotificationError> {
+        let value = self.require(name)?;
+        value
+            .parse()
+            .map_err(|_| NotificationError::invalid(name, value))
+    }
+}
+
+fn parse_status(status: &str) -> Option<PipelineStatus> {
+    Some(match status {
+        "success" => PipelineStatus::Success,
+        "failed" => PipelineStatus::Failed,
+        "canceled" => PipelineStatus::Canceled,
+        "skipped" => PipelineStatus::Skipped,
+        "running" => PipelineStatus::Running,
+        _ => return None,
+    })
+}
+
+fn hex_digit(c: u8) -> Option<u8> {
+    (c as char).to_digit(16).map(|digit| digit as u8)
+}
+
+/// Replace `=XX` escapes with the bytes they represent.
+///
+/// Malformed escapes are kept as-is.
+fn unescape_into(data: &[u8], decoded: &mut Vec<u8>) {
+    let mut idx = 0;
+    while idx < data.len() {
+        if data[idx] == b'=' {
+            let hi = data.get(idx + 1).copied().and_then(hex_digit);
+            let lo = data.get(idx + 2).copied().and_then(hex_digit);
+            if let (Some(hi), Some(lo)) = (hi, lo) {
+                decoded.push((hi << 4) | lo);
+                idx += 3;
+                continue;
+            }
+        }
+        decoded.push(data[idx]);
+        idx += 1;
+    }
+}
+
+/// Decode a quoted-printable body (RFC 2045).
+fn decode_quoted_printable(body: &str) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(body.len());
+
+    for line in body.split_inclusive('\n') {
+        let (line, newline) = if let Some(line) = line.strip_suffix("\r\n") {
+            (line, "\r\n")
+        } else if let Some(line) = line.strip_suffix('\n') {
+            (line, "\n")
+        } else {
+            (line, "")
+        };
+        // Trailing whitespace is transport padding.
+        let line = line.trim_end_matches([' ', '\t']);
+        if let Some(line) = line.strip_suffix('=') {
+            // A soft line break.
+            unescape_into(line.as_bytes(), &mut decoded);
+        } else {
+            unescape_into(line.as_bytes(), &mut decoded);
+            decoded.extend_from_slice(newline.as_bytes());
+        }
+    }
+
+    decoded
+}
+
+/// Decode base64 data, ignoring any whitespace.
+fn decode_base64(data: &str) -> Option<Vec<u8>> {
+    let data = data
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace())
+        .collect::<String>();
+    BASE64.decode(data).ok()
+}
+
+/// Convert text in a charset to a string.
+///
+/// Latin-1 is supported directly; all other charsets are treated as UTF-8.
+fn decode_charset(data: Vec<u8>, charset: Option<&str>) -> String {
+    // Strip any RFC 2231 language suffix.
+    let charset = charset.and_then(|charset| charset.split('*').next());
+    let is_latin1 = charset.is_some_and(|charset| {
+        ["iso-8859-1", "latin1"]
+            .iter()
+            .any(|latin1| charset.eq_ignore_ascii_case(latin1))
+    });
+
+    if is_latin1 {
+        data.into_iter().map(char::from).collect()
+    } else {
+        String::from_utf8(data)
+            .unwrap_or_else(|err| String::from_utf8_lossy(err.as_bytes()).into_owned())
+    }
+}
+
+/// Decode an RFC 2047 encoded word at the start of the text.
+///
+/// Returns the decoded text and the length of the encoded word.
+fn decode_encoded_word(text: &str) -> Option<(String, usize)> {
+    let inner = text.strip_prefix("=?")?;
+    let (charset, inner) = inner.split_once('?')?;
+    let (encoding, inner) = inner.split_once('?')?;
+    let (encoded, _) = inner.split_once("?=")?;
+    if encoded.contains(|c: char| c.is_ascii_whitespace()) {
+        return None;
+    }
+
+    let data = if encoding.eq_ignore_ascii_case("q") {
+        let mut data = Vec::with_capacity(encoded.len());
+        unescape_into(encoded.replace('_', " ").as_bytes(), &mut data);
+        data
+    } else if encoding.eq_ignore_ascii_case("b") {
+        BASE64.decode(encoded).ok()?
+    } else {
+        return None;
+    };
+    let len = charset.len() + encoding.len() + encoded.len() + 6;
+
+    Some((decode_charset(data, Some(charset)), len))
+}
+
+/// Decode RFC 2047 encoded words in a header value.
+fn decode_encoded_words(value: &str) -> String {
+    let mut decoded = String::with_capacity(value.len());
+    let mut rest = value;
+    let mut after_word = false;
+
+    while let Some(start) = rest.find("=?") {
+        let (before, candidate) = rest.split_at(start);
+        if let Some((word, len)) = decode_encoded_word(candidate) {
+            // Whitespace between adjacent encoded words is not part of the text.
+            if !after_word || !before.trim().is_empty() {
+                decoded.push_str(before);
+            }
+            decoded.push_str(&word);
+            rest = &candidate[len..];
+            after_word = true;
+        } else {
+            decoded.push_str(before);
+            decoded.push_str("=?");
+            rest = &candidate[2..];
+            after_word = false;
+        }
+    }
+    decoded.push_str(rest);
+
+    decoded
+}
+
+/// Get a parameter from a header value such as `text/plain; charset="UTF-8"`.
+fn header_parameter<'a>(value: &'a str, name: &str) -> Option<&'a str> {
+    value.split(';').skip(1).find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        if key.trim().eq_ignore_ascii_case(name) {
+            Some(value.trim().trim_matches('"'))
+        } else {
+            None
+        }
+    })
+}
+
+/// Decode the body of a message to text.
+///
+/// The parts of multipart messages are decoded and joined together.
+fn decode_body(headers: &Headers, body: &str) -> String {
+    let content_type = headers.get(CONTENT_TYPE_HEADER).unwrap_or("text/plain");
+    let is_multipart = content_type
+        .trim_start()
+        .get(..10)
+        .is_some_and(|kind| kind.eq_ignore_ascii_case("multipart/"));
+    if is_multipart {
+        if let Some(boundary) = header_parameter(content_type, "boundary") {
+            let delimiter = format!("--{}", boundary);
+            return body
+                .split(delimiter.as_str())
+                // Skip the preamble.
+                .skip(1)
+                // Stop at the closing delimiter.
+                .take_while(|part| !part.starts_with("--"))
+                .map(|part| {
+                    // Skip the remainder of the delimiter line.
+                    let part = part.split_once('\n').map_or("", |(_, part)| part);
+                    let (headers, body) = Headers::split(part);
+                    decode_body(&headers, body)
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+        }
+    }
+
+    let encoding = headers
+        .get(CONTENT_TRANSFER_ENCODING_HEADER)
+        .unwrap_or("7bit");
+    let data = if encoding.eq_ignore_ascii_case("quoted-printable") {
+        decode_quoted_printable(body)
+    } else if encoding.eq_ignore_ascii_case("base64") {
+        decode_base64(body).unwrap_or_else(|| body.into())
+    } else {
+        body.into()
+    };
+
+    decode_charset(data, header_parameter(content_type, "charset"))
+}
+
+fn is_url_delimiter(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '"' | '\'' | '<' | '>' | '(' | ')')
+}
+
+/// Find a URL in the body which contains the given text.
+fn find_url<'a>(body: &'a str, needle: &str) -> Option<&'a str> {
+    body.match_indices(needle).find_map(|(pos, _)| {
+        let start = body[..pos].rfind(is_url_delimiter).map_or(0, |p| p + 1);
+        let end = body[pos..]
+            .find(is_url_delimiter)
+            .map_or(body.len(), |p| pos + p);
+        let url = &body[start..end];
+        if url.starts_with("http") {
+            Some(url)
+        } else {
+            None
+        }
+    })
+}
+
+fn find_sha(body: &str) -> Option<String> {
+    const COMMIT_PATH: &str = "/-/commit/";
+
+    body.match_indices(COMMIT_PATH).find_map(|(pos, _)| {
+        let sha = body[pos + COMMIT_PATH.len()..]
+            .chars()
+            .take_while(char::is_ascii_hexdigit)
+            .collect::<String>();
+        if sha.len() == 40 || sha.len() == 64 {
+            Some(sha)
+        } else {
+            None
+        }
+    })
+}
+
+impl PipelineNotification {
+    /// Parse a pipeline notification from the contents of an email.
+    pub fn parse(email: &[u8]) -> Result<Self, NotificationError> {
+        let email = String::from_utf8_lossy(email);
+        let (headers, body) = Headers::split(&email);
+
+        let pipeline_id = headers.require_id(PIPELINE_ID_HEADER)?;
+        let project_id = headers.require_id(PROJECT_ID_HEADER)?;
+        let status = {
+            let status = headers.require(PIPELINE_STATUS_HEADER)?;
+            parse_status(status)
+                .ok_or_else(|| NotificationError::invalid(PIPELINE_STATUS_HEADER, status))?
+        };
+        let date = headers
+            .get(DATE_HEADER)
+            .and_then(|date| DateTime::parse_from_rfc2822(date).ok())
+            .map(|date| date.with_timezone(&Utc));
+
+        let body = decode_body(&headers, body);
+        let url = find_url(&body, &format!("/-/pipelines/{}", pipeline_id)).map(Into::into);
+        let sha = find_sha(&body);
+
+        Ok(Self {
+            project_id,
+            project_name: headers.get(PROJECT_NAME_HEADER).map(Into::into),
+            project_path: headers.get(PROJECT_PATH_HEADER).map(Into::into),
+            pipeline_id,
+            refname: headers.get(PIPELINE_REF_HEADER).map(Into::into),
+            status,
+            sha,
+            url,
+            date,
+        })
+    }
+
+    /// Read a pipeline notification from a file.
+    pub fn read<P>(path: P) -> Result<Self, NotificationError>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let contents = fs::read(path).map_err(|err| NotificationError::read(path.into(), err))?;
+        Self::parse(&contents)
+    }
+}
+
+/// Find email files for ingestion.
+///
+/// The path may be a single email file, a directory of `.eml` files, or a maildir (as may be
+/// synchronized from an IMAP folder using external tools).
+pub fn find_notification_emails<P>(path: P) -> Result<Vec<PathBuf>, NotificationError>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    if !path.is_dir() {
+        return Ok(vec![path.into()]);
+    }
+
+    let read_dir = |dir: &Path| -> Result<Vec<PathBuf>, NotificationError> {
+        let mut paths = fs::read_dir(dir)
+            .map_err(|err| NotificationError::read(dir.into(), err))?
+            .map(|entry| {
+                entry
+                    .map(|entry| entry.path())
+                    .map_err(|err| NotificationError::read(dir.into(), err))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        paths.retain(|path| path.is_file());
+        paths.sort();
+        Ok(paths)
+    };
+
+    let maildir_cur = path.join("cur");
+    let maildir_new = path.join("new");
+    if maildir_cur.is_dir() || maildir_new.is_dir() {
+        let mut paths = Vec::new();
+        for dir in [maildir_cur, maildir_new] {
+            if dir.is_dir() {
+                paths.extend(read_dir(&dir)?);
+            }
+        }
+        Ok(paths)
+    } else {
+        let mut paths = read_dir(path)?;
+        paths.retain(|path| path.extension().is_some_and(|ext| ext == "eml"));
+        Ok(paths)
+    }
+}
+
+/// Store the information from a pipeline notification.
+///
+/// Projects and pipelines are looked up on the instance the notification was sent from.
+/// Pipelines which have already been fetched from the forge are left untouched since the
+/// notification cannot add any information. Otherwise, a pipeline marked as coming from a
+/// notification is stored. Projects which are not yet known are created from the notification
+/// and marked the same way.
+pub fn ingest_pipeline_notification<L>(
+    storage: &mut L,
+    instance_url: &str,
+    aliases: &InstanceAliases,
+    notification: PipelineNotification,
+) -> Result<<L as Lookup<Pipeline<L>>>::Index, NotificationError>
+where
+    L: DiscoverableLookup<Pipeline<L>>,
+    L: DiscoverableLookup<Project<L>>,
+    L: DiscoverableLookup<Instance>,
+    L: Lookup<MergeRequest<L>>,
+    L: Lookup<PipelineSchedule<L>>,
+    L: Lookup<User<L>>,
+    <L as Lookup<Instance>>::Index: PartialEq,
+{
+    let instance_idx = find_or_create_instance(storage, instance_url, aliases);
+    let existing = <L as DiscoverableLookup<Pipeline<L>>>::find_in(
+        storage,
+        &instance_idx,
+        notification.pipeline_id,
+    )
+    .and_then(|idx| {
+        <L as Lookup<Pipeline<L>>>::lookup(storage, &idx).map(|pipeline| {
+            (
+                idx.clone(),
+                pipeline.cim_fidelity,
+                pipeline.created_at,
+                pipeline.cim_fetched_at,
+            )
+        })
+    });
+    // Keep the original creation time if this is an update to a previous notification.
+    let previous_times = match existing {
+        Some((idx, DataFidelity::Forge, _, _)) => return Ok(idx),
+        Some((_, _, created_at, fetched_at)) => Some((created_at, fetched_at)),
+        None => None,
+    };
+
+    let project_idx = if let Some(idx) = <L as DiscoverableLookup<Project<L>>>::find_in(
+        storage,
+        &instance_idx,
+        notification.project_id,
+    ) {
+        idx
+    } else {
+        let project = Project::builder()
+            .forge_id(notification.project_id)
+            .instance(instance_idx)
+            .name(notification.project_name.unwrap_or_default())
+            .instance_path(notification.project_path.unwrap_or_default())
+            .cim_fidelity(DataFidelity::Notification)
+            .build()?;
+        storage.store(project)
+    };
+
+    let date = notification.date.unwrap_or_else(Utc::now);
+    let finished_at = if is_finished(notification.status) {
+        Some(date)
+    } else {
+        None
+    };
+    let mut pipeline = Pipeline::builder()
+        .forge_id(notification.pipeline_id)
+        .project(project_idx)
+        .sha(notification.sha.unwrap_or_default())
+        .refname(notification.refname)
+        .stable_refname(Some(
+            format!("refs/pipelines/{}", notification.pipeline_id,),
+        ))
+        .source(PipelineSource::Unknown)
+        .status(notification.status)
+        .url(notification.url.unwrap_or_default())
+        .created_at(date)
+        .updated_at(date)
+        .finished_at(finished_at)
+        .cim_fidelity(DataFidelity::Notification)
+        .build()?;
+    if let Some((created_at, fetched_at)) = previous_times {
+        pipeline.created_at = created_at;
+        pipeline.cim_fetched_at = fetched_at;
+    }
+
+    Ok(storage.store(pipeline))
+}
+
+fn is_finished(status: PipelineStatus) -> bool {
+    matches!(
+        status,
+        PipelineStatus::Success
+            | PipelineStatus::Failed
+            | PipelineStatus::Canceled
+            | PipelineStatus::Skipped,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use ci_monitor_core::data::{DataFidelity, Instance, Pipeline, PipelineStatus, Project};
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::{DiscoverableLookup, VecLookup};
+
+    use crate::email::{self, NotificationError, PipelineNotification};
+    use crate::instance::InstanceAliases;
+
+    const FAILED_EMAIL: &str = "From: GitLab <gitlab@example.com>\r\n\
+Date: Tue, 02 Jan 2024 03:04:05 +0000\r\n\
+Subject: Failed pipeline for main |\r\n\
+\tproject | 0123abcd\r\n\
+X-GitLab-Project: project\r\n\
+X-GitLab-Project-Id: 13\r\n\
+X-GitLab-Project-Path: group/project\r\n\
+X-GitLab-Pipeline-Id: 1000\r\n\
+X-GitLab-Pipeline-Ref: main\r\n\
+X-GitLab-Pipeline-Status: failed\r\n\
+Content-Transfer-Encoding: quoted-printable\r\n\
+\r\n\
+Your pipeline has failed.\r\n\
+\r\n\
+Commit: 0123abcd ( https://gitlab.example.com/group/project/-/commit/0123abcd0123=\r\n\
+abcd0123abcd0123abcd0123abcd )\r\n\
+\r\n\
+Pipeline #1000 ( https://gitlab.example.com/group/project/-/pipelines/1000 ) triggered =\r\n\
+by Some User\r\n";
+
+    const BASE64_EMAIL: &str = "From: GitLab <gitlab@example.com>\r\n\
+Subject: =?UTF-8?Q?Failed_pipeline_for_main_|_Proj=C3=A9t?=\r\n\
+X-GitLab-Project: =?UTF-8?B?UHJvasOpdA==?=\r\n\
+X-GitLab-Project-Id: 13\r\n\
+X-GitLab-Pipeline-Id: 1000\r\n\
+X-GitLab-Pipeline-Status: failed\r\n\
+Content-Type: text/plain; charset=UTF-8\r\n\
+Content-Transfer-Encoding: base64\r\n\
+\r\n\
+WW91ciBwaXBlbGluZSBoYXMgZmFpbGVkLg0KDQpDb21taXQ6IDAxMjNhYmNkICggaHR0cHM6Ly9n\r\n\
+aXRsYWIuZXhhbXBsZS5jb20vZ3JvdXAvcHJvamVjdC8tL2NvbW1pdC8wMTIzYWJjZDAxMjNhYmNk\r\n\
+MDEyM2FiY2QwMTIzYWJjZDAxMjNhYmNkICkNCg0KUGlwZWxpbmUgIzEwMDAgKCBodHRwczovL2dp\r\n\
+dGxhYi5leGFtcGxlLmNvbS9ncm91cC9wcm9qZWN0Ly0vcGlwZWxpbmVzLzEwMDAgKSB0cmlnZ2Vy\r\n\
+ZWQgYnkgU29tZSBVc2VyDQo=\r\n";
+
+    const MULTIPART_EMAIL: &str = "From: GitLab <gitlab@example.com>\r\n\
+X-GitLab-Project-Id: 13\r\n\
+X-GitLab-Pipeline-Id: 1000\r\n\
+X-GitLab-Pipeline-Status: failed\r\n\
+Content-Type: multipart/alternative;\r\n\
+\tboundary=\"--==_mimepart_0123\"\r\n\
+\r\n\
+This is a multi-part message in MIME format.\r\n\
+----==_mimepart_0123\r\n\
+Content-Type: text/plain; charset=UTF-8\r\n\
+Content-Transfer-Encoding: 7bit\r\n\
+\r\n\
+Your pipeline has failed.\r\n\
+----==_mimepart_0123\r\n\
+Content-Type: text/html; charset=UTF-8\r\n\
+Content-Transfer-Encoding: base64\r\n\
+\r\n\
+PGEgaHJlZj0iaHR0cHM6Ly9naXRsYWIuZXhhbXBsZS5jb20vZ3JvdXAvcHJvamVjdC8tL3BpcGVs\r\n\
+aW5lcy8xMDAwIj4jMTAwMDwvYT4gPGEgaHJlZj0iaHR0cHM6Ly9naXRsYWIuZXhhbXBsZS5jb20v\r\n\
+Z3JvdXAvcHJvamVjdC8tL2NvbW1pdC8wMTIzYWJjZDAxMjNhYmNkMDEyM2FiY2QwMTIzYWJjZDAx\r\n\
+MjNhYmNkIj4wMTIzYWJjZDwvYT4=\r\n\
+----==_mimepart_0123--\r\n";
+
+    #[test]
+    fn parse_failed_notification() {
+        let notification = PipelineNotification::parse(FAILED_EMAIL.as_bytes()).unwrap();
+
+        assert_eq!(notification.project_id, 13);
+        assert_eq!(notification.project_name.as_deref(), Some("project"));
+        assert_eq!(notification.project_path.as_deref(), Some("group/project"));
+        assert_eq!(notification.pipeline_id, 1000);
+        assert_eq!(notification.refname.as_deref(), Some("main"));
+        assert_eq!(notification.status, PipelineStatus::Failed);
+        assert_eq!(
+            notification.sha.as_deref(),
+            Some("0123abcd0123abcd0123abcd0123abcd0123abcd"),
+        );
+        assert_eq!(
+            notification.url.as_deref(),
+            Some("https://gitlab.example.com/group/project/-/pipelines/1000"),
+        );
+        assert_eq!(
+            notification.date,
+            Some(Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap()),
+        );
+    }
+
+    #[test]
+    fn parse_not_a_notification() {
+        let err = PipelineNotification::parse(b"Subject: hello\n\nbody\n").unwrap_err();
+
+        if let NotificationError::NotAPipelineNotification {
+            header,
+        } = err
+        {
+            assert_eq!(header, "X-GitLab-Pipeline-Id");
+        } else {
+            panic!("unexpected error: {:?}", err);
+        }
+    }
+
+    #[test]
+    fn parse_invalid_status() {
+        let email = FAILED_EMAIL.replace("Status: failed", "Status: exploded");
+        let err = PipelineNotification::parse(email.as_bytes()).unwrap_err();
+
+        if let NotificationError::InvalidHeader {
+            header,
+            value,
+        } = err
+        {
+            assert_eq!(header, "X-GitLab-Pipeline-Status");
+            assert_eq!(value, "exploded");
+        } else {
+            panic!("unexpected error: {:?}", err);
+        }
+    }
+
+    #[test]
+    fn parse_base64_notification() {
+        let notification = PipelineNotification::parse(BASE64_EMAIL.as_bytes()).unwrap();
+
+        assert_eq!(notification.project_name.as_deref(), Some("Projét"));
+        assert_eq!(notification.pipeline_id, 1000);
+        assert_eq!(
+            notification.sha.as_deref(),
+            Some("0123abcd0123abcd0123abcd0123abcd0123abcd"),
+        );
+        assert_eq!(
+            notification.url.as_deref(),
+            Some("https://gitlab.example.com/group/project/-/pipelines/1000"),
+        );
+    }
+
+    #[test]
+    fn parse_multipart_notification() {
+        let notification = PipelineNotification::parse(MULTIPART_EMAIL.as_bytes()).unwrap();
+
+        assert_eq!(notification.pipeline_id, 1000);
+        assert_eq!(
+            notification.sha.as_deref(),
+            Some("0123abcd0123abcd0123abcd0123abcd0123abcd"),
+        );
+        assert_eq!(
+            notification.url.as_deref(),
+            Some("https://gitlab.example.com/group/project/-/pipelines/1000"),
+        );
+    }
+
+    #[test]
+    fn ingest_marks_created_project() {
+        let mut lookup = VecLookup::default();
+        let aliases = InstanceAliases::default();
+        let notification = PipelineNotification::parse(FAILED_EMAIL.as_bytes()).unwrap();
+
+        let idx = email::ingest_pipeline_notification(
+            &mut lookup,
+            "gitlab.example.com",
+            &aliases,
+            notification,
+        )
+        .unwrap();
+        let pipeline = <VecLookup as Lookup<Pipeline<VecLookup>>>::lookup(&lookup, &idx).unwrap();
+        let project =
+            <VecLookup as Lookup<Project<VecLookup>>>::lookup(&lookup, &pipeline.project).unwrap();
+        assert_eq!(project.cim_fidelity, DataFidelity::Notification);
+    }
+
+    #[test]
+    fn ingest_keeps_known_project() {
+        let mut lookup = VecLookup::default();
+        let aliases = InstanceAliases::default();
+        let notification = PipelineNotification::parse(FAILED_EMAIL.as_bytes()).unwrap();
+        let instance = Instance::builder()
+            .unique_id(0)
+            .forge("gitlab")
+            .url("gitlab.example.com")
+            .build()
+            .unwrap();
+        let instance = lookup.store(instance);
+        let project = Project::builder()
+            .forge_id(notification.project_id)
+            .instance(instance)
+            .build()
+            .unwrap();
+        let project = lookup.store(project);
+
+        let idx = email::ingest_pipeline_notification(
+            &mut lookup,
+            "gitlab.example.com",
+            &aliases,
+            notification,
+        )
+        .unwrap();
+        let pipeline = <VecLookup as Lookup<Pipeline<VecLookup>>>::lookup(&lookup, &idx).unwrap();
+        assert_eq!(pipeline.project, project);
+        let project =
+            <VecLookup as Lookup<Project<VecLookup>>>::lookup(&lookup, &project).unwrap();
+        assert_eq!(project.cim_fidelity, DataFidelity::Forge);
+    }
+
+    #[test]
+    fn ingest_scoped_to_instance() {
+        let mut lookup = VecLookup::default();
+        let aliases = InstanceAliases::default();
+        let notification = PipelineNotification::parse(FAILED_EMAIL.as_bytes()).unwrap();
+
+        let idx = email::ingest_pipeline_notification(
+            &mut lookup,
+            "gitlab.example.com",
+            &aliases,
+            notification.clone(),
+        )
+        .unwrap();
+        let mut pipeline = <VecLookup as Lookup<Pipeline<VecLookup>>>::lookup(&lookup, &idx)
+            .cloned()
+            .unwrap();
+        assert_eq!(pipeline.cim_fidelity, DataFidelity::Notification);
+        // Pretend the pipeline has since been fetched from the forge.
+        pipeline.cim_fidelity = DataFidelity::Forge;
+        let first_idx = lookup.store(pipeline.clone());
+        let first_pipeline = pipeline;
+        let first_project =
+            <VecLookup as Lookup<Project<VecLookup>>>::lookup(&lookup, &first_pipeline.project)
+                .cloned()
+                .unwrap();
+
+        // The same IDs on another instance refer to another project and pipeline.
+        let idx = email::ingest_pipeline_notification(
+            &mut lookup,
+            "other.example.com",
+            &aliases,
+            notification,
+        )
+        .unwrap();
+        assert_ne!(idx, first_idx);
+        let pipeline = <VecLookup as Lookup<Pipeline<VecLookup>>>::lookup(&lookup, &idx)
+            .cloned()
+            .unwrap();
+        assert_eq!(pipeline.cim_fidelity, DataFidelity::Notification);
+        assert_ne!(pipeline.project, first_pipeline.project);
+        let project =
+            <VecLookup as Lookup<Project<VecLookup>>>::lookup(&lookup, &pipeline.project).unwrap();
+        let instance = <VecLookup as Lookup<Instance>>::lookup(&lookup, &project.instance).unwrap();
+        assert_eq!(instance.url, "other.example.com");
+
+        // The first instance's project and pipeline are left alone.
+        assert_eq!(
+            <VecLookup as DiscoverableLookup<Project<VecLookup>>>::all_indices(&lookup).len(),
+            2,
+        );
+        assert_eq!(
+            <VecLookup as DiscoverableLookup<Pipeline<VecLookup>>>::all_indices(&lookup).len(),
+            2,
+        );
+        let pipeline = <VecLookup as Lookup<Pipeline<VecLookup>>>::lookup(&lookup, &first_idx)
+            .unwrap();
+        assert_eq!(pipeline.forge_id, first_pipeline.forge_id);
+        assert_eq!(pipeline.project, first_pipeline.project);
+        assert_eq!(pipeline.cim_fidelity, DataFidelity::Forge);
+        assert_eq!(pipeline.created_at, first_pipeline.created_at);
+        let project =
+            <VecLookup as Lookup<Project<VecLookup>>>::lookup(&lookup, &first_pipeline.project)
+                .unwrap();
+        assert_eq!(project.forge_id, first_project.forge_id);
+        assert_eq!(project.instance, first_project.instance);
+        assert_eq!(project.name, first_project.name);
+        assert_eq!(project.instance_path, first_project.instance_path);
+        let instance = <VecLookup as Lookup<Instance>>::lookup(&lookup, &project.instance).unwrap();
+        assert_eq!(instance.url, "gitlab.example.com");
+    }
+
+    #[test]
+    fn decode_quoted_printable() {
+        let decoded = email::decode_quoted_printable("a=3Db=C3=A9 \r\nsoft=\r\nbreak=\nbad=XY\n");
+
+        assert_eq!(decoded, "a=bé\r\nsoftbreakbad=XY\n".as_bytes());
+    }
+
+    #[test]
+    fn decode_encoded_words() {
+        assert_eq!(
+            email::decode_encoded_words("=?utf-8?q?Proj=C3=A9t_A?= =?UTF-8?B?w6k=?= after"),
+            "Projét Aé after",
+        );
+        assert_eq!(
+            email::decode_encoded_words("=?ISO-8859-1?Q?caf=E9?= =?bogus"),
+            "café =?bogus",
+        );
+        assert_eq!(email::decode_encoded_words("plain"), "plain");
+    }
+}
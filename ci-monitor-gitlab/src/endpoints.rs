@@ -0,0 +1,152 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Endpoints which are not provided by the `gitlab` crate.
+//!
+//! Download endpoints return raw data and are intended to be used with `gitlab::api::raw`.
+
+use std::borrow::Cow;
+
+use chrono::NaiveDate;
+use gitlab::api::{Endpoint, Pageable, QueryParams};
+use http::Method;
+
+/// Download the log of a job.
+pub struct JobTrace {
+    project: u64,
+    job: u64,
+}
+
+impl JobTrace {
+    pub fn new(project: u64, job: u64) -> Self {
+        Self {
+            project,
+            job,
+        }
+    }
+}
+
+impl Endpoint for JobTrace {
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn endpoint(&self) -> Cow<'static, str> {
+        format!("projects/{}/jobs/{}/trace", self.project, self.job).into()
+    }
+}
+
+/// Download the artifact archive of a job.
+pub struct JobArtifactsArchive {
+    project: u64,
+    job: u64,
+}
+
+impl JobArtifactsArchive {
+    pub fn new(project: u64, job: u64) -> Self {
+        Self {
+            project,
+            job,
+        }
+    }
+}
+
+impl Endpoint for JobArtifactsArchive {
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn endpoint(&self) -> Cow<'static, str> {
+        format!("projects/{}/jobs/{}/artifacts", self.project, self.job).into()
+    }
+}
+
+/// Download a single file from the artifact archive of a job.
+pub struct JobArtifactFile<'a> {
+    project: u64,
+    job: u64,
+    path: &'a str,
+}
+
+impl<'a> JobArtifactFile<'a> {
+    pub fn new(project: u64, job: u64, path: &'a str) -> Self {
+        Self {
+            project,
+            job,
+            path,
+        }
+    }
+}
+
+impl<'a> Endpoint for JobArtifactFile<'a> {
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn endpoint(&self) -> Cow<'static, str> {
+        format!(
+            "projects/{}/jobs/{}/artifacts/{}",
+            self.project, self.job, self.path,
+        )
+        .into()
+    }
+}
+
+/// Information about the token used to access the API.
+pub struct PersonalAccessTokenSelf;
+
+impl Endpoint for PersonalAccessTokenSelf {
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn endpoint(&self) -> Cow<'static, str> {
+        "personal_access_tokens/self".into()
+    }
+}
+
+/// Push events of a project.
+pub struct ProjectPushEvents {
+    project: u64,
+    after: Option<NaiveDate>,
+}
+
+impl ProjectPushEvents {
+    pub fn new(project: u64) -> Self {
+        Self {
+            project,
+            after: None,
+        }
+    }
+
+    /// Only list events from after the given date.
+    pub fn after(mut self, after: NaiveDate) -> Self {
+        self.after = Some(after);
+        self
+    }
+}
+
+impl Endpoint for ProjectPushEvents {
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn endpoint(&self) -> Cow<'static, str> {
+        format!("projects/{}/events", self.project).into()
+    }
+
+    fn parameters(&self) -> QueryParams<'_> {
+        let mut params = QueryParams::default();
+
+        params
+            .push("action", "pushed")
+            .push_opt("after", self.after);
+
+        params
+    }
+}
+
+impl Pageable for ProjectPushEvents {}
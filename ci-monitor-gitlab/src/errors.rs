@@ -7,6 +7,23 @@
 use ci_monitor_forge::ForgeError;
 use gitlab::api::ApiError;
 use gitlab::RestError;
+use http::StatusCode;
+
+/// Whether an error indicates that the requested resource does not exist.
+///
+/// GitLab reports missing resources with a JSON message (e.g., `404 Not found`) which does not
+/// carry the status code, so the message is inspected as well.
+pub fn is_not_found(err: &ApiError<RestError>) -> bool {
+    match err {
+        ApiError::GitlabService {
+            status, ..
+        } => *status == StatusCode::NOT_FOUND,
+        ApiError::Gitlab {
+            msg,
+        } => msg.starts_with("404"),
+        _ => false,
+    }
+}
 
 pub fn forge_error(err: ApiError<RestError>) -> ForgeError {
     let details = format!("{}", err);
@@ -4,15 +4,25 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 use async_trait::async_trait;
-use ci_monitor_core::data::Instance;
+use ci_monitor_core::data::{
+    Deployment, Environment, Instance, Job, MergeRequest, Pipeline, PipelineSchedule, Project,
+    Runner, RunnerHost, User,
+};
 use ci_monitor_core::Lookup;
-use ci_monitor_forge::{Forge, ForgeCore, ForgeError, ForgeTask, ForgeTaskOutcome};
-use ci_monitor_persistence::DiscoverableLookup;
+use ci_monitor_forge::{
+    ArtifactExtractionRules, EndpointLatency, Forge, ForgeCore, ForgeError, ForgeHooks, ForgeTask,
+    ForgeTaskOutcome, HookRegistry, RateClass, StoredEntity,
+};
+use ci_monitor_persistence::{BlobPersistence, DiscoverableLookup, EntityType};
 use gitlab::AsyncGitlab;
 
+use crate::client::InstrumentedGitlab;
+use crate::instance::InstanceAliases;
 use crate::tasks;
 use crate::GitlabLookup;
 
@@ -21,30 +31,160 @@ pub struct GitlabForge<L>
 where
     L: Lookup<Instance>,
 {
-    gitlab: AsyncGitlab,
+    gitlab: InstrumentedGitlab,
+    api_requests: AtomicU64,
     storage: RwLock<L>,
     instance_idx: <L as Lookup<Instance>>::Index,
+    blobs: Option<Box<dyn BlobPersistence + Send + Sync>>,
+    artifact_extraction: ArtifactExtractionRules,
+    hooks: HookRegistry<L>,
+    update_diffs: bool,
+    job_logs: bool,
+    push_scanning: bool,
 }
 
 impl<L> GitlabForge<L>
 where
     L: Lookup<Instance>,
 {
-    pub(crate) fn gitlab(&self) -> &AsyncGitlab {
+    pub(crate) fn gitlab(&self) -> &InstrumentedGitlab {
+        self.api_requests.fetch_add(1, Ordering::Relaxed);
         &self.gitlab
     }
 
-    pub(crate) fn storage(&self) -> RwLockReadGuard<L> {
+    pub(crate) fn storage(&self) -> RwLockReadGuard<'_, L> {
         self.storage.read().unwrap()
     }
 
-    pub(crate) fn storage_mut(&self) -> RwLockWriteGuard<L> {
+    pub(crate) fn storage_mut(&self) -> RwLockWriteGuard<'_, L> {
         self.storage.write().unwrap()
     }
 
-    pub(crate) fn instance_index(&self) -> <L as Lookup<Instance>>::Index {
+    /// The index of the instance the forge communicates with.
+    pub fn instance_index(&self) -> <L as Lookup<Instance>>::Index {
         self.instance_idx.clone()
     }
+
+    pub(crate) fn blobs(&self) -> Option<&(dyn BlobPersistence + Send + Sync)> {
+        self.blobs.as_deref()
+    }
+
+    pub(crate) fn artifact_extraction(&self) -> &ArtifactExtractionRules {
+        &self.artifact_extraction
+    }
+
+    pub(crate) fn hooks(&self) -> &HookRegistry<L> {
+        &self.hooks
+    }
+
+    /// Store an entity and notify hooks about it.
+    pub(crate) fn store<T>(&self, entity: T) -> <L as Lookup<T>>::Index
+    where
+        L: Lookup<T>,
+        L: Lookup<Deployment<L>>,
+        L: Lookup<Environment<L>>,
+        L: Lookup<Job<L>>,
+        L: Lookup<MergeRequest<L>>,
+        L: Lookup<Pipeline<L>>,
+        L: Lookup<PipelineSchedule<L>>,
+        L: Lookup<Project<L>>,
+        L: Lookup<Runner<L>>,
+        L: Lookup<RunnerHost>,
+        L: Lookup<User<L>>,
+        for<'a> StoredEntity<'a, L>: From<&'a T>,
+    {
+        let mut storage = self.storage_mut();
+        let idx = storage.store(entity);
+        if !self.hooks.is_empty() {
+            if let Some(entity) = <L as Lookup<T>>::lookup(&storage, &idx) {
+                self.hooks.entity_stored(entity.into());
+            }
+        }
+        idx
+    }
+
+    /// Store artifacts using the given blob storage.
+    ///
+    /// Without blob storage, artifacts are tracked, but their contents are not fetched.
+    pub fn with_blob_storage<B>(mut self, blobs: B) -> Self
+    where
+        B: BlobPersistence + Send + Sync + 'static,
+    {
+        self.blobs = Some(Box::new(blobs));
+        self
+    }
+
+    /// Call the given hooks on events.
+    pub fn with_hooks(mut self, hooks: Arc<dyn ForgeHooks<L>>) -> Self {
+        self.hooks.register(hooks);
+        self
+    }
+
+    /// Log the fields changed whenever an update overwrites an existing entity.
+    ///
+    /// Changes are logged through `tracing` at the debug level with sensitive values masked.
+    pub fn with_update_diffs(mut self) -> Self {
+        self.update_diffs = true;
+        self
+    }
+
+    /// Whether updates to existing entities are logged.
+    pub(crate) fn logs_updates(&self) -> bool {
+        self.update_diffs && tracing::enabled!(tracing::Level::DEBUG)
+    }
+
+    /// Log the fields changed by overwriting an existing entity.
+    pub(crate) fn log_update<T>(&self, entity_type: EntityType, forge_id: u64, old: &T, new: &T)
+    where
+        T: Debug,
+    {
+        if !self.logs_updates() {
+            return;
+        }
+
+        for change in ci_monitor_forge::field_changes(old, new) {
+            tracing::debug!(
+                entity = entity_type.name(),
+                forge_id,
+                field = %change.field,
+                old = %change.old,
+                new = %change.new,
+                "updated field"
+            );
+        }
+    }
+
+    /// Fetch the log of each job once it has finished.
+    ///
+    /// Requires blob storage in order to have any effect.
+    pub fn with_job_logs(mut self) -> Self {
+        self.job_logs = true;
+        self
+    }
+
+    /// Whether logs of finished jobs are fetched.
+    pub(crate) fn fetches_job_logs(&self) -> bool {
+        self.job_logs && self.blobs.is_some()
+    }
+
+    /// Scan pushes to projects so that pushes which did not produce a pipeline can be found.
+    pub fn with_push_scanning(mut self) -> Self {
+        self.push_scanning = true;
+        self
+    }
+
+    /// Whether pushes to projects are scanned.
+    pub(crate) fn scans_pushes(&self) -> bool {
+        self.push_scanning
+    }
+
+    /// Extract files from archive artifacts according to the given rules.
+    ///
+    /// Requires blob storage in order to have any effect.
+    pub fn with_artifact_extraction(mut self, rules: ArtifactExtractionRules) -> Self {
+        self.artifact_extraction = rules;
+        self
+    }
 }
 
 impl<L> GitlabForge<L>
@@ -52,46 +192,45 @@ where
     L: DiscoverableLookup<Instance>,
 {
     /// Create a new `GitlabForge` from a GitLab client and storage.
+    ///
+    /// The URL is normalized (see [`crate::normalize_instance_url`]) before matching it against
+    /// stored instances.
     pub fn new<U>(url: U, gitlab: AsyncGitlab, storage: L) -> Self
     where
-        U: Into<String>,
+        U: AsRef<str>,
     {
-        Self::new_impl(url.into(), gitlab, storage)
-    }
-
-    fn new_impl(url: String, gitlab: AsyncGitlab, mut storage: L) -> Self {
-        let all_instance_idx = storage.all_indices();
-        let new_unique_id = all_instance_idx.len() as u64;
-        let instance_idx = all_instance_idx
-            .into_iter()
-            .filter_map(|idx| {
-                let inst = storage.lookup(&idx);
-                if let Some(inst) = inst {
-                    if inst.url == url && inst.forge == "gitlab" {
-                        Some(idx)
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
-            })
-            .next()
-            .unwrap_or_else(|| {
-                let instance = Instance::builder()
-                    .forge("gitlab")
-                    .url(url)
-                    .unique_id(new_unique_id)
-                    .build()
-                    .unwrap();
-
-                storage.store(instance)
-            });
+        Self::with_aliases(url, &InstanceAliases::default(), gitlab, storage)
+    }
+
+    /// Create a new `GitlabForge` for an instance which may be reachable under several URLs.
+    ///
+    /// Stored instances whose URL is an alias of the same instance are reused.
+    pub fn with_aliases<U>(
+        url: U,
+        aliases: &InstanceAliases,
+        gitlab: AsyncGitlab,
+        storage: L,
+    ) -> Self
+    where
+        U: AsRef<str>,
+    {
+        Self::new_impl(url.as_ref(), aliases, gitlab, storage)
+    }
+
+    fn new_impl(url: &str, aliases: &InstanceAliases, gitlab: AsyncGitlab, mut storage: L) -> Self {
+        let instance_idx = find_or_create_instance(&mut storage, url, aliases);
 
         Self {
-            gitlab,
+            gitlab: InstrumentedGitlab::new(gitlab),
+            api_requests: AtomicU64::new(0),
             storage: RwLock::new(storage),
             instance_idx,
+            blobs: None,
+            artifact_extraction: ArtifactExtractionRules::default(),
+            hooks: HookRegistry::default(),
+            update_diffs: false,
+            job_logs: false,
+            push_scanning: false,
         }
     }
 
@@ -101,6 +240,55 @@ where
     }
 }
 
+/// Find the `Instance` for a GitLab host, creating it if necessary.
+///
+/// Instances are matched by their canonical URL. Instances stored under the canonical URL itself
+/// are preferred over those stored under an alias. New instances are stored under the canonical
+/// URL.
+pub(crate) fn find_or_create_instance<L>(
+    storage: &mut L,
+    url: &str,
+    aliases: &InstanceAliases,
+) -> <L as Lookup<Instance>>::Index
+where
+    L: DiscoverableLookup<Instance>,
+{
+    let url = aliases.canonical(url);
+    let all_instance_idx = storage.all_indices();
+    let new_unique_id = all_instance_idx.len() as u64;
+    let mut matching = all_instance_idx
+        .into_iter()
+        .filter_map(|idx| {
+            let inst = storage.lookup(&idx);
+            if let Some(inst) = inst {
+                if inst.forge == "gitlab" && aliases.canonical(&inst.url) == url {
+                    Some((inst.url == url, idx))
+                } else {
+                    None
+                }
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    // Prefer exact matches; the sort is stable so that earlier instances win ties.
+    matching.sort_by_key(|(exact, _)| !exact);
+    matching
+        .into_iter()
+        .map(|(_, idx)| idx)
+        .next()
+        .unwrap_or_else(|| {
+            let instance = Instance::builder()
+                .forge("gitlab")
+                .url(url)
+                .unique_id(new_unique_id)
+                .build()
+                .unwrap();
+
+            storage.store(instance)
+        })
+}
+
 impl<L> ForgeCore for GitlabForge<L>
 where
     L: Lookup<Instance>,
@@ -122,6 +310,70 @@ where
 {
     /// Run a task.
     async fn run_task_async(&self, task: ForgeTask) -> Result<ForgeTaskOutcome, ForgeError> {
+        self.run_task_hooked(task, 1).await
+    }
+
+    /// Run a task starting from a page of its listing.
+    async fn resume_task_async(
+        &self,
+        task: ForgeTask,
+        page: u64,
+    ) -> Result<ForgeTaskOutcome, ForgeError> {
+        self.run_task_hooked(task, page).await
+    }
+
+    fn rate_class(&self, task: &ForgeTask) -> RateClass {
+        match task {
+            ForgeTask::FetchJobArtifact {
+                ..
+            }
+            | ForgeTask::FetchJobLog {
+                ..
+            } => RateClass::Transfer,
+            task if task.is_discovery() => RateClass::Paged,
+            // Artifact listings and pipeline configurations may be large, but are still single
+            // requests.
+            _ => RateClass::Single,
+        }
+    }
+
+    fn api_requests(&self) -> u64 {
+        self.api_requests.load(Ordering::Relaxed)
+    }
+
+    fn api_latency(&self) -> Vec<EndpointLatency> {
+        self.gitlab.metrics().snapshot()
+    }
+}
+
+impl<L> GitlabForge<L>
+where
+    L: GitlabLookup<L> + Clone + Send + Sync,
+{
+    async fn run_task_hooked(
+        &self,
+        task: ForgeTask,
+        page: u64,
+    ) -> Result<ForgeTaskOutcome, ForgeError> {
+        if self.hooks.is_empty() {
+            return self.run_task_impl(task, page).await;
+        }
+
+        let res = self.run_task_impl(task.clone(), page).await;
+        if let Err(err) = res.as_ref() {
+            self.hooks.task_failed(&task, err);
+        }
+        res
+    }
+
+    /// Run a task.
+    ///
+    /// Listings start from `page`; other tasks ignore it.
+    async fn run_task_impl(
+        &self,
+        task: ForgeTask,
+        page: u64,
+    ) -> Result<ForgeTaskOutcome, ForgeError> {
         match task {
             ForgeTask::UpdateProject {
                 project,
@@ -135,43 +387,76 @@ where
             ForgeTask::UpdateUser {
                 user,
             } => tasks::update_user(self, user).await,
-            ForgeTask::DiscoverRunners => tasks::discover_runners(self).await,
+            ForgeTask::DiscoverUsers => tasks::discover_users(self, page).await,
+            ForgeTask::RefreshUsers => tasks::refresh_users(self).await,
+            ForgeTask::DiscoverGroupProjects {
+                group,
+            } => tasks::discover_group_projects(self, group, page).await,
+            ForgeTask::DiscoverRunners => tasks::discover_runners(self, page).await,
             ForgeTask::UpdateRunner {
                 id,
             } => tasks::update_runner(self, id).await,
             ForgeTask::DiscoverPipelineSchedules {
                 project,
-            } => tasks::discover_pipeline_schedules(self, project).await,
+            } => tasks::discover_pipeline_schedules(self, project, page).await,
             ForgeTask::UpdatePipelineSchedule {
                 project,
                 schedule,
             } => tasks::update_pipeline_schedule(self, project, schedule).await,
             ForgeTask::DiscoverMergeRequests {
                 project,
-            } => tasks::discover_merge_requests(self, project).await,
+            } => tasks::discover_merge_requests(self, project, page).await,
             ForgeTask::UpdateMergeRequest {
                 project,
                 merge_request,
             } => tasks::update_merge_request(self, project, merge_request).await,
             ForgeTask::DiscoverPipelines {
                 project,
-            } => tasks::discover_pipelines(self, project).await,
+            } => tasks::discover_pipelines(self, project, page).await,
             ForgeTask::DiscoverMergeRequestPipelines {
                 project,
                 merge_request,
-            } => tasks::discover_merge_request_pipelines(self, project, merge_request).await,
+            } => tasks::discover_merge_request_pipelines(self, project, merge_request, page).await,
             ForgeTask::UpdatePipeline {
                 project,
                 pipeline,
             } => tasks::update_pipeline(self, project, pipeline).await,
+            ForgeTask::ReconcilePipeline {
+                project,
+                pipeline,
+            } => tasks::reconcile_pipeline(self, project, pipeline).await,
+            ForgeTask::FetchPipelineConfig {
+                project,
+                pipeline,
+            } => tasks::fetch_pipeline_config(self, project, pipeline).await,
             ForgeTask::DiscoverJobs {
                 project,
                 pipeline,
-            } => tasks::discover_jobs(self, project, pipeline).await,
+            } => tasks::discover_jobs(self, project, pipeline, page).await,
             ForgeTask::UpdateJob {
                 project,
                 job,
             } => tasks::update_job(self, project, job).await,
+            ForgeTask::UpdateJobArtifacts {
+                project,
+                job,
+            } => tasks::update_job_artifacts(self, project, job).await,
+            ForgeTask::FetchJobArtifact {
+                project,
+                job,
+                artifact,
+                sub_artifact,
+            } => tasks::fetch_job_artifact(self, project, job, artifact, sub_artifact).await,
+            ForgeTask::FetchJobLog {
+                project,
+                job,
+            } => tasks::fetch_job_log(self, project, job).await,
+            ForgeTask::DiscoverReleases {
+                project,
+            } => tasks::discover_releases(self, project, page).await,
+            ForgeTask::DiscoverPushes {
+                project,
+            } => tasks::discover_pushes(self, project).await,
             _ => {
                 Err(ForgeError::Unknown {
                     task,
@@ -0,0 +1,162 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+
+/// Normalize the URL of an instance.
+///
+/// Instances are stored by host (and path, if served from one) without a scheme. The scheme,
+/// default ports, and trailing slashes are removed and the host is lowercased so that
+/// `https://GitLab.example.com/` and `gitlab.example.com` name the same instance.
+pub fn normalize_instance_url(url: &str) -> String {
+    let url = url.trim();
+    let (default_port, rest) = if let Some((scheme, rest)) = url.split_once("://") {
+        let default_port = match scheme.to_ascii_lowercase().as_str() {
+            "http" => Some("80"),
+            "https" => Some("443"),
+            _ => None,
+        };
+        (default_port, rest)
+    } else {
+        (Some("443"), url)
+    };
+
+    let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+    let authority = authority.to_ascii_lowercase();
+    let authority = match authority.rsplit_once(':') {
+        Some((host, port)) if Some(port) == default_port => host,
+        _ => &authority,
+    };
+    let authority = authority.trim_end_matches('.');
+    let path = path.trim_end_matches('/');
+
+    if path.is_empty() {
+        authority.into()
+    } else {
+        format!("{}/{}", authority, path)
+    }
+}
+
+/// Alternate URLs of instances.
+///
+/// Instances reachable under several names (e.g., DNS aliases) are stored once under their
+/// canonical URL. All URLs are normalized using [`normalize_instance_url`].
+#[derive(Debug, Clone, Default)]
+pub struct InstanceAliases {
+    aliases: BTreeMap<String, String>,
+}
+
+impl InstanceAliases {
+    /// Add an alias for an instance.
+    pub fn alias<A, C>(mut self, alias: A, canonical: C) -> Self
+    where
+        A: AsRef<str>,
+        C: AsRef<str>,
+    {
+        self.aliases.insert(
+            normalize_instance_url(alias.as_ref()),
+            normalize_instance_url(canonical.as_ref()),
+        );
+        self
+    }
+
+    /// The canonical URL of an instance.
+    pub fn canonical(&self, url: &str) -> String {
+        let url = normalize_instance_url(url);
+        self.aliases.get(&url).cloned().unwrap_or(url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ci_monitor_core::data::Instance;
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::{DiscoverableLookup, VecLookup};
+
+    use crate::forge::find_or_create_instance;
+    use crate::{normalize_instance_url, InstanceAliases};
+
+    #[test]
+    fn normalize() {
+        let same = [
+            "gitlab.example.com",
+            "GitLab.Example.com",
+            "https://gitlab.example.com",
+            "https://gitlab.example.com:443/",
+            "http://gitlab.example.com",
+            "http://gitlab.example.com:80",
+            "gitlab.example.com.",
+            " gitlab.example.com/ ",
+        ];
+        for url in same {
+            assert_eq!(normalize_instance_url(url), "gitlab.example.com", "{}", url);
+        }
+
+        assert_eq!(
+            normalize_instance_url("http://gitlab.example.com:443"),
+            "gitlab.example.com:443",
+        );
+        assert_eq!(
+            normalize_instance_url("https://gitlab.example.com:8443"),
+            "gitlab.example.com:8443",
+        );
+        assert_eq!(
+            normalize_instance_url("https://example.com/GitLab/"),
+            "example.com/GitLab",
+        );
+    }
+
+    #[test]
+    fn aliases() {
+        let aliases =
+            InstanceAliases::default().alias("https://git.example.com/", "gitlab.example.com");
+
+        assert_eq!(aliases.canonical("GIT.example.com"), "gitlab.example.com");
+        assert_eq!(
+            aliases.canonical("https://gitlab.example.com"),
+            "gitlab.example.com",
+        );
+        assert_eq!(aliases.canonical("other.example.com"), "other.example.com");
+    }
+
+    #[test]
+    fn find_instance() {
+        let mut lookup = VecLookup::default();
+        let split = Instance::builder()
+            .unique_id(0)
+            .forge("gitlab")
+            .url("https://git.example.com")
+            .build()
+            .unwrap();
+        let split = lookup.store(split);
+        let aliases = InstanceAliases::default().alias("git.example.com", "gitlab.example.com");
+
+        // Instances stored under an alias are reused.
+        let idx = find_or_create_instance(&mut lookup, "gitlab.example.com", &aliases);
+        assert_eq!(idx, split);
+
+        // Instances stored under the canonical URL are preferred.
+        let canonical = Instance::builder()
+            .unique_id(1)
+            .forge("gitlab")
+            .url("gitlab.example.com")
+            .build()
+            .unwrap();
+        let canonical = lookup.store(canonical);
+        let idx = find_or_create_instance(&mut lookup, "https://GIT.example.com/", &aliases);
+        assert_eq!(idx, canonical);
+
+        // Other instances are created under their normalized URL.
+        let idx = find_or_create_instance(&mut lookup, "https://other.example.com/", &aliases);
+        let instance = <VecLookup as Lookup<Instance>>::lookup(&lookup, &idx).unwrap();
+        assert_eq!(instance.url, "other.example.com");
+        assert_eq!(instance.unique_id, 2);
+        assert_eq!(
+            <VecLookup as DiscoverableLookup<Instance>>::all_indices(&lookup).len(),
+            3,
+        );
+    }
+}
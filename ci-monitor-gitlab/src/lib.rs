@@ -10,13 +10,36 @@
 
 #![warn(missing_docs)]
 
+mod ci_config;
+mod client;
+mod email;
+mod endpoints;
 mod errors;
 mod forge;
+mod instance;
+mod log_sections;
 mod lookup;
+mod schedule_match;
 mod tasks;
+mod token;
+mod web_url;
+
+pub use email::find_notification_emails;
+pub use email::ingest_pipeline_notification;
+pub use email::NotificationError;
+pub use email::PipelineNotification;
 
 pub use forge::GitlabForge;
 
+pub use instance::normalize_instance_url;
+pub use instance::InstanceAliases;
+
+pub use token::token_scopes;
+pub use token::TokenFeatures;
+pub use token::TokenScopeReport;
+
+pub use web_url::PipelineUrl;
+
 use lookup::GitlabLookup;
 
 pub use gitlab;
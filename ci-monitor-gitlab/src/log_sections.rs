@@ -0,0 +1,105 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use chrono::{DateTime, Utc};
+use ci_monitor_core::data::JobSection;
+
+const MARKER_PREFIX: &str = "section_";
+
+/// Parse the remainder of a section marker into its timestamp and name.
+///
+/// Markers look like `section_start:1700000000:name[collapsed=true]\r\x1b[0K`.
+fn parse_marker(marker: &str) -> Option<(DateTime<Utc>, &str)> {
+    let (timestamp, rest) = marker.split_once(':')?;
+    let timestamp = DateTime::from_timestamp(timestamp.parse().ok()?, 0)?;
+    let end = rest
+        .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.')))
+        .unwrap_or(rest.len());
+    let name = &rest[..end];
+
+    if name.is_empty() {
+        None
+    } else {
+        Some((timestamp, name))
+    }
+}
+
+/// Extract timed sections from a job log.
+///
+/// Sections are delimited by `section_start` and `section_end` markers. Sections which are never
+/// closed (e.g., due to a timeout) are ignored, as are sections nested within them. Sections are
+/// sorted by their start time.
+pub fn job_sections(log: &[u8]) -> Vec<JobSection> {
+    let log = String::from_utf8_lossy(log);
+    let mut open: Vec<(&str, DateTime<Utc>)> = Vec::new();
+    let mut sections = Vec::new();
+
+    let mut rest = log.as_ref();
+    while let Some(pos) = rest.find(MARKER_PREFIX) {
+        rest = &rest[pos + MARKER_PREFIX.len()..];
+
+        if let Some(marker) = rest.strip_prefix("start:") {
+            if let Some((started_at, name)) = parse_marker(marker) {
+                open.push((name, started_at));
+            }
+        } else if let Some(marker) = rest.strip_prefix("end:") {
+            let (finished_at, name) = if let Some(parsed) = parse_marker(marker) {
+                parsed
+            } else {
+                continue;
+            };
+            if let Some(pos) = open.iter().rposition(|(open, _)| *open == name) {
+                let (_, started_at) = open[pos];
+                open.truncate(pos);
+                sections.push(JobSection::new(name, started_at, finished_at));
+            }
+        }
+    }
+
+    sections.sort_by_key(|section| section.started_at);
+    sections
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Duration};
+
+    const LOG: &str = "\
+\x1b[0KRunning with gitlab-runner 16.0.0\n\
+section_start:1700000000:prepare_executor\r\x1b[0K\x1b[0K\x1b[36;1mPreparing\x1b[0;m\n\
+section_end:1700000005:prepare_executor\r\x1b[0K\n\
+section_start:1700000005:step_script\r\x1b[0K\x1b[0K\x1b[36;1mExecuting\x1b[0;m\n\
+\x1b[0Ksection_start:1700000010:build[collapsed=true]\r\x1b[0KBuilding\n\
+[1/2] Building CXX object file.cxx.o\n\
+section_end:1700000070:build\r\x1b[0K\n\
+section_start:1700000070:test\r\x1b[0K\n\
+Timed out\n\
+section_end:1700000100:step_script\r\x1b[0K\n\
+section_start:1700000100:cleanup\r\x1b[0K\n";
+
+    #[test]
+    fn job_sections() {
+        let sections = super::job_sections(LOG.as_bytes());
+
+        let at = |secs: i64| DateTime::from_timestamp(1700000000 + secs, 0).unwrap();
+        assert_eq!(sections.len(), 3);
+        assert_eq!(sections[0].name, "prepare_executor");
+        assert_eq!(sections[0].started_at, at(0));
+        assert_eq!(sections[0].duration(), Duration::seconds(5));
+        assert_eq!(sections[1].name, "step_script");
+        assert_eq!(sections[1].duration(), Duration::seconds(95));
+        assert_eq!(sections[2].name, "build");
+        assert_eq!(sections[2].finished_at, at(70));
+    }
+
+    #[test]
+    fn job_sections_invalid_markers() {
+        let log =
+            "section_start:abc:name\nsection_start:1700000000:\nsection_end:1700000001:name\n";
+
+        assert!(super::job_sections(log.as_bytes()).is_empty());
+    }
+}
@@ -6,7 +6,7 @@
 
 use ci_monitor_core::data::{
     Deployment, Environment, Instance, Job, JobArtifact, MergeRequest, Pipeline, PipelineSchedule,
-    Project, Runner, RunnerHost, User,
+    Project, Push, Release, Runner, RunnerHost, User,
 };
 use ci_monitor_core::Lookup;
 use ci_monitor_persistence::{DiscoverableLookup, VecLookup};
@@ -15,11 +15,13 @@ pub trait GitlabLookup<L>:
     Lookup<Deployment<L>>
     + Lookup<Environment<L>>
     + DiscoverableLookup<Job<L>>
-    + Lookup<JobArtifact<L>>
+    + DiscoverableLookup<JobArtifact<L>>
     + DiscoverableLookup<MergeRequest<L>>
     + DiscoverableLookup<Pipeline<L>>
     + DiscoverableLookup<PipelineSchedule<L>>
     + DiscoverableLookup<Project<L>>
+    + DiscoverableLookup<Push<L>>
+    + DiscoverableLookup<Release<L>>
     + DiscoverableLookup<Runner<L>>
     + DiscoverableLookup<RunnerHost>
     + DiscoverableLookup<User<L>>
@@ -32,6 +34,8 @@ where
     L: Lookup<Pipeline<L>>,
     L: Lookup<PipelineSchedule<L>>,
     L: Lookup<Project<L>>,
+    L: Lookup<Push<L>>,
+    L: Lookup<Release<L>>,
     L: Lookup<Runner<L>>,
     L: Lookup<RunnerHost>,
     L: Lookup<User<L>>,
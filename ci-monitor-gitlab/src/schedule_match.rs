@@ -0,0 +1,358 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use ci_monitor_core::data::{
+    Instance, MergeRequest, Pipeline, PipelineSchedule, PipelineSource, Project, User,
+};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::DiscoverableLookup;
+
+/// Strip the namespace from a refname.
+fn short_refname(refname: &str) -> &str {
+    refname
+        .strip_prefix("refs/heads/")
+        .or_else(|| refname.strip_prefix("refs/tags/"))
+        .unwrap_or(refname)
+}
+
+/// How well a schedule explains a pipeline.
+///
+/// Returns `None` if the schedule cannot have started the pipeline.
+fn schedule_score<L>(
+    pipeline: &Pipeline<L>,
+    schedule: &PipelineSchedule<L>,
+    pipeline_user: Option<u64>,
+    owner: Option<u64>,
+) -> Option<u32>
+where
+    L: Lookup<Instance>,
+    L: Lookup<MergeRequest<L>>,
+    L: Lookup<Pipeline<L>>,
+    L: Lookup<PipelineSchedule<L>>,
+    L: Lookup<Project<L>>,
+    L: Lookup<User<L>>,
+{
+    // Schedules build a single ref.
+    let refname = pipeline.refname.as_deref().map(short_refname);
+    if refname != Some(short_refname(&schedule.ref_)) {
+        return None;
+    }
+
+    // Schedules only start pipelines while they exist and are active.
+    if pipeline.created_at < schedule.created_at {
+        return None;
+    }
+    if schedule
+        .cim_deleted_at
+        .is_some_and(|deleted_at| deleted_at < pipeline.created_at)
+    {
+        return None;
+    }
+    if !schedule.active && schedule.updated_at < pipeline.created_at {
+        return None;
+    }
+
+    let mut score = 0;
+
+    // Pipelines receive the variables of their schedule.
+    if !pipeline.variables.variables.is_empty() {
+        let provides_variables = schedule.variables.variables.iter().all(|(name, variable)| {
+            pipeline
+                .variables
+                .variables
+                .get(name)
+                .is_some_and(|pipeline_variable| pipeline_variable.value == variable.value)
+        });
+        if !provides_variables {
+            return None;
+        }
+        if !schedule.variables.variables.is_empty() {
+            score += 1;
+        }
+    }
+
+    // Scheduled pipelines run as the owner of the schedule. Ownership may have been taken over
+    // since the pipeline ran, so a different owner does not rule the schedule out.
+    if pipeline_user.is_some() && pipeline_user == owner {
+        score += 2;
+    }
+
+    Some(score)
+}
+
+/// Find the schedule which started a pipeline.
+///
+/// GitLab does not report the schedule which started a pipeline, so candidates are the project's
+/// schedules which build the pipeline's ref, existed and were active when the pipeline was
+/// created, and provide variables which the pipeline has (if known). Schedules owned by the user
+/// the pipeline ran as are preferred. If the best candidates cannot be distinguished, no schedule
+/// is returned.
+pub fn matching_schedule<L>(
+    storage: &L,
+    pipeline: &Pipeline<L>,
+) -> Option<<L as Lookup<PipelineSchedule<L>>>::Index>
+where
+    L: DiscoverableLookup<PipelineSchedule<L>>,
+    L: Lookup<Instance>,
+    L: Lookup<MergeRequest<L>>,
+    L: Lookup<Pipeline<L>>,
+    L: Lookup<Project<L>>,
+    L: Lookup<User<L>>,
+{
+    if pipeline.source != PipelineSource::Schedule {
+        return None;
+    }
+
+    let project_id = |idx: &<L as Lookup<Project<L>>>::Index| {
+        <L as Lookup<Project<L>>>::lookup(storage, idx).map(|p| p.forge_id)
+    };
+    let user_id = |idx: &<L as Lookup<User<L>>>::Index| {
+        <L as Lookup<User<L>>>::lookup(storage, idx).map(|u| u.forge_id)
+    };
+
+    let project = project_id(&pipeline.project)?;
+    let pipeline_user = pipeline.user.as_ref().and_then(user_id);
+
+    let mut best = None;
+    let mut ambiguous = false;
+    for idx in <L as DiscoverableLookup<PipelineSchedule<L>>>::all_indices(storage) {
+        let schedule =
+            if let Some(schedule) = <L as Lookup<PipelineSchedule<L>>>::lookup(storage, &idx) {
+                schedule
+            } else {
+                continue;
+            };
+        if project_id(&schedule.project) != Some(project) {
+            continue;
+        }
+
+        let score = if let Some(score) =
+            schedule_score(pipeline, schedule, pipeline_user, user_id(&schedule.owner))
+        {
+            score
+        } else {
+            continue;
+        };
+        match best {
+            Some((best_score, _)) if score < best_score => (),
+            Some((best_score, _)) if score == best_score => ambiguous = true,
+            _ => {
+                best = Some((score, idx));
+                ambiguous = false;
+            },
+        }
+    }
+
+    if ambiguous {
+        None
+    } else {
+        best.map(|(_, idx)| idx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Duration, TimeZone, Utc};
+    use ci_monitor_core::data::{
+        Instance, Pipeline, PipelineSchedule, PipelineScheduleBuilder, PipelineSource,
+        PipelineStatus, PipelineVariable, PipelineVariableType, PipelineVariables, Project, User,
+    };
+    use ci_monitor_core::Lookup;
+    use ci_monitor_persistence::VecLookup;
+
+    fn at(minute: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap() + Duration::minutes(minute)
+    }
+
+    fn variables(variables: &[(&str, &str)]) -> PipelineVariables {
+        variables
+            .iter()
+            .map(|&(name, value)| {
+                let variable = PipelineVariable::builder()
+                    .value(value)
+                    .type_(PipelineVariableType::String)
+                    .build()
+                    .unwrap();
+                (name.into(), variable)
+            })
+            .collect()
+    }
+
+    struct Fixture {
+        lookup: VecLookup,
+        project: <VecLookup as Lookup<Project<VecLookup>>>::Index,
+        users: [<VecLookup as Lookup<User<VecLookup>>>::Index; 2],
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            let mut lookup = VecLookup::default();
+            let instance = Instance::builder()
+                .unique_id(0)
+                .forge("forge")
+                .url("url")
+                .build()
+                .unwrap();
+            let instance = lookup.store(instance);
+            let users = [1, 2].map(|id| {
+                let user = User::builder()
+                    .forge_id(id)
+                    .instance(instance)
+                    .build()
+                    .unwrap();
+                lookup.store(user)
+            });
+            let project = Project::builder()
+                .forge_id(1)
+                .instance(instance)
+                .instance_path("group/project")
+                .url("project")
+                .build()
+                .unwrap();
+            let project = lookup.store(project);
+
+            Self {
+                lookup,
+                project,
+                users,
+            }
+        }
+
+        fn schedule_builder(
+            &self,
+            id: u64,
+            ref_: &str,
+            owner: usize,
+            vars: &[(&str, &str)],
+        ) -> PipelineScheduleBuilder<VecLookup> {
+            PipelineSchedule::builder()
+                .forge_id(id)
+                .project(self.project)
+                .ref_(ref_)
+                .variables(variables(vars))
+                .created_at(at(0))
+                .updated_at(at(0))
+                .owner(self.users[owner])
+                .active(true)
+        }
+
+        fn schedule(&mut self, id: u64, ref_: &str, owner: usize, vars: &[(&str, &str)]) {
+            let schedule = self
+                .schedule_builder(id, ref_, owner, vars)
+                .build()
+                .unwrap();
+            self.lookup.store(schedule);
+        }
+
+        fn pipeline(
+            &self,
+            refname: &str,
+            user: usize,
+            vars: &[(&str, &str)],
+        ) -> Pipeline<VecLookup> {
+            Pipeline::builder()
+                .forge_id(1)
+                .project(self.project)
+                .sha("0000000000000000000000000000000000000000")
+                .refname(Some(refname.into()))
+                .source(PipelineSource::Schedule)
+                .status(PipelineStatus::Success)
+                .variables(variables(vars))
+                .user(Some(self.users[user]))
+                .url("url")
+                .created_at(at(60))
+                .updated_at(at(60))
+                .build()
+                .unwrap()
+        }
+
+        fn matching_schedule(&self, pipeline: &Pipeline<VecLookup>) -> Option<u64> {
+            super::matching_schedule(&self.lookup, pipeline).map(|idx| {
+                <VecLookup as Lookup<PipelineSchedule<VecLookup>>>::lookup(&self.lookup, &idx)
+                    .unwrap()
+                    .forge_id
+            })
+        }
+    }
+
+    #[test]
+    fn short_refname() {
+        assert_eq!(super::short_refname("refs/heads/main"), "main");
+        assert_eq!(super::short_refname("refs/tags/v1.0"), "v1.0");
+        assert_eq!(super::short_refname("main"), "main");
+    }
+
+    #[test]
+    fn match_by_ref() {
+        let mut fixture = Fixture::new();
+        fixture.schedule(1, "main", 0, &[]);
+        fixture.schedule(2, "refs/heads/release", 0, &[]);
+
+        let pipeline = fixture.pipeline("release", 0, &[]);
+        assert_eq!(fixture.matching_schedule(&pipeline), Some(2));
+        let pipeline = fixture.pipeline("topic", 0, &[]);
+        assert_eq!(fixture.matching_schedule(&pipeline), None);
+    }
+
+    #[test]
+    fn match_by_owner() {
+        let mut fixture = Fixture::new();
+        fixture.schedule(1, "main", 0, &[]);
+        fixture.schedule(2, "main", 1, &[]);
+
+        let pipeline = fixture.pipeline("main", 1, &[]);
+        assert_eq!(fixture.matching_schedule(&pipeline), Some(2));
+    }
+
+    #[test]
+    fn match_by_variables() {
+        let mut fixture = Fixture::new();
+        fixture.schedule(1, "main", 0, &[("NIGHTLY", "1")]);
+        fixture.schedule(2, "main", 0, &[("WEEKLY", "1")]);
+
+        let pipeline = fixture.pipeline("main", 0, &[("WEEKLY", "1"), ("OTHER", "value")]);
+        assert_eq!(fixture.matching_schedule(&pipeline), Some(2));
+
+        // Without variables, the schedules cannot be distinguished.
+        let pipeline = fixture.pipeline("main", 0, &[]);
+        assert_eq!(fixture.matching_schedule(&pipeline), None);
+    }
+
+    #[test]
+    fn match_timing() {
+        let mut fixture = Fixture::new();
+        fixture.schedule(1, "main", 0, &[]);
+
+        let mut pipeline = fixture.pipeline("main", 0, &[]);
+        pipeline.created_at = at(-10);
+        assert_eq!(fixture.matching_schedule(&pipeline), None);
+
+        // Inactive schedules only match pipelines from before they were last updated.
+        let mut fixture = Fixture::new();
+        let schedule = fixture
+            .schedule_builder(1, "main", 0, &[])
+            .active(false)
+            .updated_at(at(30))
+            .build()
+            .unwrap();
+        fixture.lookup.store(schedule);
+        let pipeline = fixture.pipeline("main", 0, &[]);
+        assert_eq!(fixture.matching_schedule(&pipeline), None);
+        let mut pipeline = fixture.pipeline("main", 0, &[]);
+        pipeline.created_at = at(20);
+        assert_eq!(fixture.matching_schedule(&pipeline), Some(1));
+    }
+
+    #[test]
+    fn match_scheduled_only() {
+        let mut fixture = Fixture::new();
+        fixture.schedule(1, "main", 0, &[]);
+
+        let mut pipeline = fixture.pipeline("main", 0, &[]);
+        pipeline.source = PipelineSource::Push;
+        assert_eq!(fixture.matching_schedule(&pipeline), None);
+    }
+}
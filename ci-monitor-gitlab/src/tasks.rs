@@ -4,23 +4,39 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+mod group;
 mod job;
+mod job_artifact;
 mod merge_request;
+mod paged;
 mod pipeline;
 mod pipeline_schedule;
 mod pipeline_variables;
 mod project;
+mod push;
+mod release;
 mod runner;
 mod user;
 
+pub use self::group::discover_group_projects;
+
 pub use self::job::discover_jobs;
 pub use self::job::update_job;
 
+pub use self::job_artifact::fetch_job_artifact;
+pub use self::job_artifact::fetch_job_log;
+pub use self::job_artifact::update_job_artifacts;
+
 pub use self::merge_request::discover_merge_requests;
 pub use self::merge_request::update_merge_request;
 
+use self::paged::collect_paged;
+use self::paged::collect_paged_tasks;
+
 pub use self::pipeline::discover_merge_request_pipelines;
 pub use self::pipeline::discover_pipelines;
+pub use self::pipeline::fetch_pipeline_config;
+pub use self::pipeline::reconcile_pipeline;
 pub use self::pipeline::update_pipeline;
 
 pub use self::pipeline_schedule::discover_pipeline_schedules;
@@ -32,8 +48,14 @@ use self::pipeline_variables::GitlabPipelineVariable;
 pub use self::project::update_project;
 pub use self::project::update_project_by_name;
 
+pub use self::push::discover_pushes;
+
+pub use self::release::discover_releases;
+
 pub use self::runner::discover_runners;
 pub use self::runner::update_runner;
 
+pub use self::user::discover_users;
+pub use self::user::refresh_users;
 pub use self::user::update_user;
 pub use self::user::update_user_by_name;
@@ -0,0 +1,108 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeSet;
+use std::ops::Deref;
+
+use chrono::Utc;
+use ci_monitor_core::data::{
+    Deployment, Environment, Instance, Job, MergeRequest, Pipeline, PipelineSchedule, Project,
+    Runner, RunnerHost, User,
+};
+use ci_monitor_core::Lookup;
+use ci_monitor_forge::{ForgeError, ForgeTask, ForgeTaskOutcome};
+use ci_monitor_persistence::DiscoverableLookup;
+use serde::Deserialize;
+
+use crate::tasks::collect_paged;
+use crate::GitlabForge;
+
+#[derive(Debug, Deserialize)]
+struct GitlabProject {
+    id: u64,
+}
+
+/// Whether a project path is within a group (or one of its subgroups).
+fn is_within_group(path: &str, group: &str) -> bool {
+    path.strip_prefix(group.trim_end_matches('/'))
+        .is_some_and(|rest| rest.starts_with('/'))
+}
+
+pub async fn discover_group_projects<L>(
+    forge: &GitlabForge<L>,
+    group: String,
+    page: u64,
+) -> Result<ForgeTaskOutcome, ForgeError>
+where
+    L: DiscoverableLookup<Project<L>>,
+    L: Lookup<Deployment<L>>,
+    L: Lookup<Environment<L>>,
+    L: Lookup<Instance>,
+    L: Lookup<Job<L>>,
+    L: Lookup<MergeRequest<L>>,
+    L: Lookup<Pipeline<L>>,
+    L: Lookup<PipelineSchedule<L>>,
+    L: Lookup<Runner<L>>,
+    L: Lookup<RunnerHost>,
+    L: Lookup<User<L>>,
+    L: Send + Sync,
+{
+    let endpoint = gitlab::api::groups::projects::GroupProjects::builder()
+        .group(group.as_str())
+        .include_subgroups(true)
+        .build()
+        .unwrap();
+
+    let mut outcome = ForgeTaskOutcome::default();
+    let gl_projects: Vec<GitlabProject> =
+        collect_paged(forge, endpoint, Some(page), &mut outcome).await?;
+    let listed = gl_projects
+        .iter()
+        .map(|project| project.id)
+        .collect::<BTreeSet<_>>();
+    outcome
+        .additional_tasks
+        .extend(listed.iter().map(|&project| {
+            ForgeTask::UpdateProject {
+                project,
+            }
+        }));
+
+    // Only a complete listing can tell which projects have gone away. A resumed listing is missing
+    // the projects found by earlier runs.
+    if outcome.partial.is_some() || page > 1 {
+        return Ok(outcome);
+    }
+
+    let changed = {
+        let storage = forge.storage();
+        let projects = <L as DiscoverableLookup<Project<L>>>::all_indices(storage.deref());
+        projects
+            .iter()
+            .filter_map(|idx| <L as Lookup<Project<L>>>::lookup(storage.deref(), idx))
+            .filter(|project| is_within_group(&project.instance_path, &group))
+            .filter(|project| {
+                // Only projects whose membership changed need to be stored again.
+                listed.contains(&project.forge_id) == project.cim_removed_from_group_at.is_some()
+            })
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+
+    // Projects which were deleted (or moved out of the group) are flagged so that they are no
+    // longer monitored. Projects which reappear in the group are monitored again.
+    let now = Utc::now();
+    for mut project in changed {
+        project.cim_removed_from_group_at = if listed.contains(&project.forge_id) {
+            None
+        } else {
+            Some(now)
+        };
+        forge.store(project);
+    }
+
+    Ok(outcome)
+}
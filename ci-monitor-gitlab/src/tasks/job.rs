@@ -6,19 +6,20 @@
 
 use std::ops::Deref;
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use ci_monitor_core::data::{
-    Deployment, Environment, Instance, Job, JobState, MergeRequest, Pipeline, PipelineSchedule,
-    Project, Runner, RunnerHost, User,
+    Deployment, Elapsed, Environment, Instance, Job, JobState, MergeRequest, Pipeline,
+    PipelineSchedule, PipelineVariables, Project, Runner, RunnerHost, User,
 };
 use ci_monitor_core::Lookup;
 use ci_monitor_forge::{ForgeError, ForgeTask, ForgeTaskOutcome};
-use ci_monitor_persistence::DiscoverableLookup;
-use futures_util::stream::TryStreamExt;
+use ci_monitor_persistence::{DiscoverableLookup, EntityType};
 use gitlab::api::AsyncQuery;
 use serde::Deserialize;
 
+use crate::ci_config;
 use crate::errors;
+use crate::tasks::collect_paged_tasks;
 use crate::GitlabForge;
 
 #[derive(Debug, Deserialize)]
@@ -30,36 +31,34 @@ pub async fn discover_jobs<L>(
     forge: &GitlabForge<L>,
     project: u64,
     pipeline: u64,
+    page: u64,
 ) -> Result<ForgeTaskOutcome, ForgeError>
 where
     L: Lookup<Instance>,
     L: Send + Sync,
 {
-    let gl_jobs = {
-        let endpoint = gitlab::api::projects::pipelines::PipelineJobs::builder()
-            .project(project)
-            .pipeline(pipeline)
-            .include_retried(true)
-            .build()
-            .unwrap();
-        let endpoint = gitlab::api::paged(endpoint, gitlab::api::Pagination::All);
-        endpoint.into_iter_async::<_, GitlabJob>(forge.gitlab())
-    };
+    let endpoint = gitlab::api::projects::pipelines::PipelineJobs::builder()
+        .project(project)
+        .pipeline(pipeline)
+        .include_retried(true)
+        .build()
+        .unwrap();
 
     let mut outcome = ForgeTaskOutcome::default();
 
-    let tasks = gl_jobs
-        .map_ok(|job| {
+    collect_paged_tasks(
+        forge,
+        endpoint,
+        Some(page),
+        &mut outcome,
+        |job: GitlabJob| {
             ForgeTask::UpdateJob {
                 project,
                 job: job.id,
             }
-        })
-        .map_err(errors::forge_error)
-        .try_collect::<Vec<_>>()
-        .await?;
-
-    outcome.additional_tasks = tasks;
+        },
+    )
+    .await?;
 
     Ok(outcome)
 }
@@ -195,9 +194,11 @@ where
     let mut add_task = |task| outcome.additional_tasks.push(task);
     let job = gl_job.id;
 
-    let user_idx = if let Some(idx) =
-        <L as DiscoverableLookup<User<L>>>::find(forge.storage().deref(), gl_job.user.id)
-    {
+    let user_idx = if let Some(idx) = <L as DiscoverableLookup<User<L>>>::find_in(
+        forge.storage().deref(),
+        &forge.instance_index(),
+        gl_job.user.id,
+    ) {
         Some(idx)
     } else {
         add_task(ForgeTask::UpdateUser {
@@ -205,9 +206,11 @@ where
         });
         None
     };
-    let pipeline_idx = if let Some(idx) =
-        <L as DiscoverableLookup<Pipeline<L>>>::find(forge.storage().deref(), gl_job.pipeline.id)
-    {
+    let pipeline_idx = if let Some(idx) = <L as DiscoverableLookup<Pipeline<L>>>::find_in(
+        forge.storage().deref(),
+        &forge.instance_index(),
+        gl_job.pipeline.id,
+    ) {
         Some(idx)
     } else {
         add_task(ForgeTask::UpdatePipeline {
@@ -217,9 +220,11 @@ where
         None
     };
     let runner_idx = if let Some(runner) = gl_job.runner {
-        if let Some(idx) =
-            <L as DiscoverableLookup<Runner<L>>>::find(forge.storage().deref(), runner.id)
-        {
+        if let Some(idx) = <L as DiscoverableLookup<Runner<L>>>::find_in(
+            forge.storage().deref(),
+            &forge.instance_index(),
+            runner.id,
+        ) {
             Some(idx)
         } else {
             add_task(ForgeTask::UpdateRunner {
@@ -242,52 +247,125 @@ where
             return Ok(outcome);
         };
 
+    // The job API does not expose variables, so use those from the pipeline's CI configuration.
+    let variables = configured_variables(forge, &pipeline_idx, &gl_job.name);
+
+    // Queue times which cannot be a duration (e.g., negative) are dropped.
+    let queued_duration = gl_job.queued_duration.and_then(Elapsed::from_seconds);
+
+    // For manual jobs, the job's user is updated to whoever started it.
+    let played_by = user_idx.clone();
     let update = move |job: &mut Job<L>| {
         job.state = gl_job.status.into();
+        if job.state == JobState::Manual {
+            job.manual = true;
+        } else if job.manual && job.played_at.is_none() {
+            if let Some(started_at) = gl_job.started_at {
+                let queued = queued_duration.map_or_else(Duration::zero, Elapsed::duration);
+                job.played_at = Some(started_at - queued);
+                job.played_by = Some(played_by);
+            }
+        }
         job.started_at = gl_job.started_at;
         job.finished_at = gl_job.finished_at;
         job.erased_at = gl_job.erased_at;
-        job.queued_duration = gl_job.queued_duration;
+        job.queued_duration = queued_duration;
         job.archived = gl_job.archived;
         job.coverage = gl_job.coverage.and_then(|c| c.as_f64());
+        if let Some(variables) = variables {
+            job.variables = variables;
+        }
 
         job.cim_refreshed_at = Utc::now();
     };
 
     // Create a job entry.
-    let job =
-        if let Some(idx) = <L as DiscoverableLookup<Job<L>>>::find(forge.storage().deref(), job) {
-            if let Some(existing) = <L as Lookup<Job<L>>>::lookup(forge.storage().deref(), &idx) {
-                let mut updated = existing.clone();
-                update(&mut updated);
-                updated
-            } else {
-                return Err(ForgeError::lookup::<L, Job<L>>(&idx));
-            }
+    let mut had_artifacts = false;
+    let job = if let Some(idx) = <L as DiscoverableLookup<Job<L>>>::find_in(
+        forge.storage().deref(),
+        &forge.instance_index(),
+        job,
+    ) {
+        if let Some(existing) = <L as Lookup<Job<L>>>::lookup(forge.storage().deref(), &idx) {
+            had_artifacts = has_artifacts(existing.state);
+            let mut updated = existing.clone();
+            update(&mut updated);
+            forge.log_update(EntityType::Job, job, existing, &updated);
+            updated
         } else {
-            let mut job = Job::builder()
-                .user(user_idx)
-                .state(gl_job.status.into())
-                .created_at(gl_job.created_at)
-                .runner(runner_idx)
-                .forge_id(job)
-                .pipeline(pipeline_idx)
-                .name(gl_job.name)
-                .stage(gl_job.stage)
-                .allow_failure(gl_job.allow_failure)
-                .tags(gl_job.tag_list)
-                //.variables(gl_job.variables)
-                //.deployment
-                .url(gl_job.web_url)
-                .build()
-                .unwrap();
-
-            update(&mut job);
-            job
-        };
+            return Err(ForgeError::lookup::<L, Job<L>>(&idx));
+        }
+    } else {
+        let mut job = Job::builder()
+            .user(user_idx)
+            .state(gl_job.status.into())
+            .created_at(gl_job.created_at)
+            .runner(runner_idx)
+            .forge_id(job)
+            .pipeline(pipeline_idx)
+            .name(gl_job.name)
+            .stage(gl_job.stage)
+            .allow_failure(gl_job.allow_failure)
+            .tags(gl_job.tag_list)
+            // Variables are filled in from the CI configuration by `update`.
+            //.deployment
+            .url(gl_job.web_url)
+            .build()
+            .unwrap();
+
+        update(&mut job);
+        job
+    };
+
+    // Look for artifacts once the job has finished.
+    if !had_artifacts && has_artifacts(job.state) {
+        outcome
+            .additional_tasks
+            .push(ForgeTask::UpdateJobArtifacts {
+                project,
+                job: job.forge_id,
+            });
+        if forge.fetches_job_logs() {
+            outcome.additional_tasks.push(ForgeTask::FetchJobLog {
+                project,
+                job: job.forge_id,
+            });
+        }
+    }
 
     // Store the job in the storage.
-    forge.storage_mut().store(job);
+    forge.store(job);
 
     Ok(outcome)
 }
+
+/// The variables a job is configured with in its pipeline's CI configuration.
+///
+/// Values are stored as-is; as with pipeline variables, they are masked in update logs and
+/// sensitive names are redacted when reported.
+fn configured_variables<L>(
+    forge: &GitlabForge<L>,
+    pipeline: &<L as Lookup<Pipeline<L>>>::Index,
+    job: &str,
+) -> Option<PipelineVariables>
+where
+    L: Lookup<Instance>,
+    L: Lookup<MergeRequest<L>>,
+    L: Lookup<Pipeline<L>>,
+    L: Lookup<PipelineSchedule<L>>,
+    L: Lookup<Project<L>>,
+    L: Lookup<User<L>>,
+{
+    let blobs = forge.blobs()?;
+    let storage = forge.storage();
+    let pipeline = <L as Lookup<Pipeline<L>>>::lookup(storage.deref(), pipeline)?;
+    let config = blobs.fetch(pipeline.ci_config.as_ref()?).ok()?;
+    ci_config::job_variables(&config, job)
+}
+
+fn has_artifacts(state: JobState) -> bool {
+    matches!(
+        state,
+        JobState::Failed | JobState::Success | JobState::Canceled,
+    )
+}
@@ -0,0 +1,469 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::ops::Deref;
+
+use chrono::{DateTime, Utc};
+use ci_monitor_core::data::{
+    ArtifactExpiration, ArtifactKind, ArtifactState, Blob, Deployment, Environment, Instance, Job,
+    JobArtifact, MergeRequest, Pipeline, PipelineSchedule, Project, Runner, RunnerHost, User,
+};
+use ci_monitor_core::Lookup;
+use ci_monitor_forge::{ForgeError, ForgeTask, ForgeTaskOutcome};
+use ci_monitor_persistence::DiscoverableLookup;
+use gitlab::api::AsyncQuery;
+use serde::Deserialize;
+
+use crate::endpoints;
+use crate::errors;
+use crate::log_sections;
+use crate::GitlabForge;
+
+const ARCHIVE_FILE_TYPE: &str = "archive";
+const TRACE_FILE_TYPE: &str = "trace";
+
+#[derive(Debug, Deserialize)]
+struct GitlabJobArtifact {
+    file_type: String,
+    size: u64,
+    filename: String,
+}
+
+impl GitlabJobArtifact {
+    fn kind(&self) -> Option<ArtifactKind> {
+        Some(match self.file_type.as_str() {
+            ARCHIVE_FILE_TYPE => ArtifactKind::Archive,
+            TRACE_FILE_TYPE => ArtifactKind::JobLog,
+            "junit" => ArtifactKind::JUnit,
+            "annotations" => ArtifactKind::Annotations,
+            // The metadata is an index of the archive; it is not interesting on its own.
+            "metadata" => return None,
+            file_type => {
+                ArtifactKind::Custom {
+                    name: file_type.to_string().into(),
+                }
+            },
+        })
+    }
+
+    fn is_fetchable(&self) -> bool {
+        matches!(self.file_type.as_str(), ARCHIVE_FILE_TYPE | TRACE_FILE_TYPE)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GitlabJobArtifacts {
+    id: u64,
+    #[serde(default)]
+    artifacts: Vec<GitlabJobArtifact>,
+    artifacts_expire_at: Option<DateTime<Utc>>,
+}
+
+fn find_artifact<L>(
+    storage: &L,
+    job: u64,
+    kind: &ArtifactKind,
+) -> Option<<L as Lookup<JobArtifact<L>>>::Index>
+where
+    L: DiscoverableLookup<JobArtifact<L>>,
+    L: Lookup<Deployment<L>>,
+    L: Lookup<Environment<L>>,
+    L: Lookup<Instance>,
+    L: Lookup<Job<L>>,
+    L: Lookup<MergeRequest<L>>,
+    L: Lookup<Pipeline<L>>,
+    L: Lookup<PipelineSchedule<L>>,
+    L: Lookup<Project<L>>,
+    L: Lookup<Runner<L>>,
+    L: Lookup<RunnerHost>,
+    L: Lookup<User<L>>,
+{
+    <L as DiscoverableLookup<JobArtifact<L>>>::all_indices(storage)
+        .into_iter()
+        .find(|idx| {
+            <L as Lookup<JobArtifact<L>>>::lookup(storage, idx).is_some_and(|artifact| {
+                artifact.kind == *kind
+                    && <L as Lookup<Job<L>>>::lookup(storage, &artifact.job)
+                        .is_some_and(|artifact_job| artifact_job.forge_id == job)
+            })
+        })
+}
+
+/// Store an artifact, assigning a new unique ID if it is not yet known.
+fn store_artifact<L>(forge: &GitlabForge<L>, job: u64, mut artifact: JobArtifact<L>)
+where
+    L: DiscoverableLookup<JobArtifact<L>>,
+    L: Lookup<Deployment<L>>,
+    L: Lookup<Environment<L>>,
+    L: Lookup<Instance>,
+    L: Lookup<Job<L>>,
+    L: Lookup<MergeRequest<L>>,
+    L: Lookup<Pipeline<L>>,
+    L: Lookup<PipelineSchedule<L>>,
+    L: Lookup<Project<L>>,
+    L: Lookup<Runner<L>>,
+    L: Lookup<RunnerHost>,
+    L: Lookup<User<L>>,
+{
+    let mut storage = forge.storage_mut();
+    let existing = find_artifact(storage.deref(), job, &artifact.kind).and_then(|idx| {
+        <L as Lookup<JobArtifact<L>>>::lookup(storage.deref(), &idx)
+            .map(|existing| existing.unique_id)
+    });
+    artifact.unique_id = existing.unwrap_or_else(|| {
+        <L as DiscoverableLookup<JobArtifact<L>>>::all_indices(storage.deref()).len() as u64
+    });
+    let idx = storage.store(artifact);
+
+    if !forge.hooks().is_empty() {
+        if let Some(artifact) = <L as Lookup<JobArtifact<L>>>::lookup(storage.deref(), &idx) {
+            forge.hooks().entity_stored(artifact.into());
+        }
+    }
+}
+
+pub async fn update_job_artifacts<L>(
+    forge: &GitlabForge<L>,
+    project: u64,
+    job: u64,
+) -> Result<ForgeTaskOutcome, ForgeError>
+where
+    L: DiscoverableLookup<JobArtifact<L>>,
+    L: DiscoverableLookup<Job<L>>,
+    L: Lookup<Deployment<L>>,
+    L: Lookup<Environment<L>>,
+    L: Lookup<Instance>,
+    L: Lookup<MergeRequest<L>>,
+    L: Lookup<Pipeline<L>>,
+    L: Lookup<PipelineSchedule<L>>,
+    L: Lookup<Project<L>>,
+    L: Lookup<Runner<L>>,
+    L: Lookup<RunnerHost>,
+    L: Lookup<User<L>>,
+    L: Send + Sync,
+{
+    let gl_job: GitlabJobArtifacts = {
+        let endpoint = gitlab::api::projects::jobs::Job::builder()
+            .project(project)
+            .job(job)
+            .build()
+            .unwrap();
+        endpoint
+            .query_async(forge.gitlab())
+            .await
+            .map_err(errors::forge_error)?
+    };
+
+    let mut outcome = ForgeTaskOutcome::default();
+    let mut add_task = |task| outcome.additional_tasks.push(task);
+    let job = gl_job.id;
+
+    let job_idx = if let Some(idx) = <L as DiscoverableLookup<Job<L>>>::find_in(
+        forge.storage().deref(),
+        &forge.instance_index(),
+        job,
+    ) {
+        idx
+    } else {
+        add_task(ForgeTask::UpdateJob {
+            project,
+            job,
+        });
+        add_task(ForgeTask::UpdateJobArtifacts {
+            project,
+            job,
+        });
+        return Ok(outcome);
+    };
+
+    let expire_at = gl_job
+        .artifacts_expire_at
+        .map_or(ArtifactExpiration::Never, ArtifactExpiration::At);
+
+    for gl_artifact in gl_job.artifacts {
+        let kind = if let Some(kind) = gl_artifact.kind() {
+            kind
+        } else {
+            continue;
+        };
+        // Job logs are kept with the job rather than expiring with the artifacts.
+        let expire_at = if kind == ArtifactKind::JobLog {
+            ArtifactExpiration::Never
+        } else {
+            expire_at
+        };
+
+        let existing = find_artifact(forge.storage().deref(), job, &kind).and_then(|idx| {
+            <L as Lookup<JobArtifact<L>>>::lookup(forge.storage().deref(), &idx).cloned()
+        });
+        let artifact = if let Some(mut existing) = existing {
+            existing.expire_at = expire_at;
+            if !existing.state.is_stored() {
+                existing.size = gl_artifact.size;
+                existing.state = ArtifactState::Present;
+            }
+            existing
+        } else {
+            JobArtifact::builder()
+                .state(ArtifactState::Present)
+                .kind(kind)
+                .expire_at(expire_at)
+                .name(gl_artifact.filename.clone())
+                .size(gl_artifact.size)
+                // Assigned when stored.
+                .unique_id(0)
+                .job(job_idx.clone())
+                .build()
+                .unwrap()
+        };
+
+        if forge.blobs().is_some() && gl_artifact.is_fetchable() && !artifact.state.is_stored() {
+            add_task(ForgeTask::FetchJobArtifact {
+                project,
+                job,
+                artifact: gl_artifact.file_type.clone(),
+                sub_artifact: None,
+            });
+        }
+
+        store_artifact(forge, job, artifact);
+    }
+
+    Ok(outcome)
+}
+
+pub async fn fetch_job_log<L>(
+    forge: &GitlabForge<L>,
+    project: u64,
+    job: u64,
+) -> Result<ForgeTaskOutcome, ForgeError>
+where
+    L: DiscoverableLookup<JobArtifact<L>>,
+    L: DiscoverableLookup<Job<L>>,
+    L: Lookup<Deployment<L>>,
+    L: Lookup<Environment<L>>,
+    L: Lookup<Instance>,
+    L: Lookup<MergeRequest<L>>,
+    L: Lookup<Pipeline<L>>,
+    L: Lookup<PipelineSchedule<L>>,
+    L: Lookup<Project<L>>,
+    L: Lookup<Runner<L>>,
+    L: Lookup<RunnerHost>,
+    L: Lookup<User<L>>,
+    L: Send + Sync,
+{
+    fetch_job_artifact(forge, project, job, TRACE_FILE_TYPE.into(), None).await
+}
+
+pub async fn fetch_job_artifact<L>(
+    forge: &GitlabForge<L>,
+    project: u64,
+    job: u64,
+    artifact: String,
+    sub_artifact: Option<String>,
+) -> Result<ForgeTaskOutcome, ForgeError>
+where
+    L: DiscoverableLookup<JobArtifact<L>>,
+    L: DiscoverableLookup<Job<L>>,
+    L: Lookup<Deployment<L>>,
+    L: Lookup<Environment<L>>,
+    L: Lookup<Instance>,
+    L: Lookup<MergeRequest<L>>,
+    L: Lookup<Pipeline<L>>,
+    L: Lookup<PipelineSchedule<L>>,
+    L: Lookup<Project<L>>,
+    L: Lookup<Runner<L>>,
+    L: Lookup<RunnerHost>,
+    L: Lookup<User<L>>,
+    L: Send + Sync,
+{
+    let blobs = if let Some(blobs) = forge.blobs() {
+        blobs
+    } else {
+        return Err(ForgeError::Unhandled {
+            task: ForgeTask::FetchJobArtifact {
+                project,
+                job,
+                artifact,
+                sub_artifact,
+            },
+        });
+    };
+
+    let mut outcome = ForgeTaskOutcome::default();
+
+    let job_idx = if let Some(idx) = <L as DiscoverableLookup<Job<L>>>::find_in(
+        forge.storage().deref(),
+        &forge.instance_index(),
+        job,
+    ) {
+        idx
+    } else {
+        outcome.additional_tasks.push(ForgeTask::UpdateJob {
+            project,
+            job,
+        });
+        outcome.additional_tasks.push(ForgeTask::FetchJobArtifact {
+            project,
+            job,
+            artifact,
+            sub_artifact,
+        });
+        return Ok(outcome);
+    };
+
+    let (kind, data) = match (artifact.as_str(), sub_artifact.as_deref()) {
+        (TRACE_FILE_TYPE, None) => {
+            let endpoint = endpoints::JobTrace::new(project, job);
+            let data = gitlab::api::raw(endpoint)
+                .query_async(forge.gitlab())
+                .await
+                .map_err(errors::forge_error)?;
+            (ArtifactKind::JobLog, data)
+        },
+        (ARCHIVE_FILE_TYPE, None) => {
+            let endpoint = endpoints::JobArtifactsArchive::new(project, job);
+            let data = gitlab::api::raw(endpoint)
+                .query_async(forge.gitlab())
+                .await
+                .map_err(errors::forge_error)?;
+            (ArtifactKind::Archive, data)
+        },
+        (ARCHIVE_FILE_TYPE, Some(path)) => {
+            let endpoint = endpoints::JobArtifactFile::new(project, job, path);
+            let data = gitlab::api::raw(endpoint)
+                .query_async(forge.gitlab())
+                .await
+                .map_err(errors::forge_error)?;
+            (
+                ArtifactKind::ArchiveFile {
+                    path: path.to_string().into(),
+                },
+                data,
+            )
+        },
+        _ => {
+            return Err(ForgeError::Unhandled {
+                task: ForgeTask::FetchJobArtifact {
+                    project,
+                    job,
+                    artifact,
+                    sub_artifact,
+                },
+            });
+        },
+    };
+
+    let existing = find_artifact(forge.storage().deref(), job, &kind).and_then(|idx| {
+        <L as Lookup<JobArtifact<L>>>::lookup(forge.storage().deref(), &idx).cloned()
+    });
+    let expire_at = existing
+        .as_ref()
+        .map_or(ArtifactExpiration::Unknown, |existing| existing.expire_at);
+
+    // Extract interesting files from archives.
+    let extracted = if kind == ArtifactKind::Archive {
+        forge.artifact_extraction().extract(&data).map_err(|err| {
+            ForgeError::Other {
+                details: format!("failed to extract files from job {}: {}", job, err),
+            }
+        })?
+    } else {
+        Vec::new()
+    };
+
+    // Time the sections of job logs.
+    let sections = if kind == ArtifactKind::JobLog {
+        Some(log_sections::job_sections(&data))
+    } else {
+        None
+    };
+
+    let blob = Blob::new(data);
+    let blob_ref = blobs.store(&blob).map_err(|err| {
+        ForgeError::Other {
+            details: format!("failed to store artifact for job {}: {}", job, err),
+        }
+    })?;
+
+    let update = |artifact: &mut JobArtifact<L>| {
+        artifact.state = ArtifactState::Stored;
+        artifact.blob = Some(blob_ref.clone());
+        artifact.size = blob.len() as u64;
+    };
+
+    // Create an artifact entry.
+    let artifact = if let Some(mut existing) = existing {
+        update(&mut existing);
+        existing
+    } else {
+        let name = match &kind {
+            ArtifactKind::ArchiveFile {
+                path,
+            } => path.to_string(),
+            _ => artifact,
+        };
+        let mut artifact = JobArtifact::builder()
+            .kind(kind)
+            .expire_at(expire_at)
+            .name(name)
+            .size(0)
+            // Assigned when stored.
+            .unique_id(0)
+            .job(job_idx.clone())
+            .build()
+            .unwrap();
+
+        update(&mut artifact);
+        artifact
+    };
+
+    // Store the artifact in the storage.
+    store_artifact(forge, job, artifact);
+
+    if let Some(sections) = sections {
+        let existing = <L as Lookup<Job<L>>>::lookup(forge.storage().deref(), &job_idx).cloned();
+        if let Some(mut existing) = existing {
+            existing.sections = sections;
+            forge.store(existing);
+        }
+    }
+
+    let (paths, extracted): (Vec<_>, Vec<_>) = extracted
+        .into_iter()
+        .map(|file| (file.path, Blob::new(file.contents)))
+        .unzip();
+    let blob_refs = blobs.store_many(&extracted);
+    for ((path, blob), blob_ref) in paths.into_iter().zip(extracted).zip(blob_refs) {
+        let blob_ref = blob_ref.map_err(|err| {
+            ForgeError::Other {
+                details: format!(
+                    "failed to store extracted file '{}' for job {}: {}",
+                    path, job, err,
+                ),
+            }
+        })?;
+
+        let artifact = JobArtifact::builder()
+            .state(ArtifactState::Stored)
+            .kind(ArtifactKind::ArchiveFile {
+                path: path.clone().into(),
+            })
+            .expire_at(expire_at)
+            .name(path)
+            .blob(Some(blob_ref))
+            .size(blob.len() as u64)
+            // Assigned when stored.
+            .unique_id(0)
+            .job(job_idx.clone())
+            .build()
+            .unwrap();
+
+        store_artifact(forge, job, artifact);
+    }
+
+    Ok(outcome)
+}
@@ -6,18 +6,19 @@
 
 use std::ops::Deref;
 
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use ci_monitor_core::data::{
-    Instance, MergeRequest, MergeRequestStatus, PipelineSchedule, Project, User,
+    Deployment, Environment, Instance, Job, MergeRequest, MergeRequestStatus, Pipeline,
+    PipelineSchedule, Project, Runner, RunnerHost, User,
 };
 use ci_monitor_core::Lookup;
 use ci_monitor_forge::{ForgeError, ForgeTask, ForgeTaskOutcome};
-use ci_monitor_persistence::DiscoverableLookup;
-use futures_util::stream::TryStreamExt;
+use ci_monitor_persistence::{DiscoverableLookup, EntityType};
 use gitlab::api::AsyncQuery;
 use serde::Deserialize;
 
 use crate::errors;
+use crate::tasks::{collect_paged, collect_paged_tasks};
 use crate::GitlabForge;
 
 #[derive(Debug, Deserialize)]
@@ -28,34 +29,32 @@ struct GitlabMergeRequest {
 pub async fn discover_merge_requests<L>(
     forge: &GitlabForge<L>,
     project: u64,
+    page: u64,
 ) -> Result<ForgeTaskOutcome, ForgeError>
 where
     L: Lookup<Instance>,
     L: Send + Sync,
 {
-    let gl_merge_requests = {
-        let endpoint = gitlab::api::projects::merge_requests::MergeRequests::builder()
-            .project(project)
-            .build()
-            .unwrap();
-        let endpoint = gitlab::api::paged(endpoint, gitlab::api::Pagination::All);
-        endpoint.into_iter_async::<_, GitlabMergeRequest>(forge.gitlab())
-    };
+    let endpoint = gitlab::api::projects::merge_requests::MergeRequests::builder()
+        .project(project)
+        .build()
+        .unwrap();
 
     let mut outcome = ForgeTaskOutcome::default();
 
-    let tasks = gl_merge_requests
-        .map_ok(|merge_request| {
+    collect_paged_tasks(
+        forge,
+        endpoint,
+        Some(page),
+        &mut outcome,
+        |merge_request: GitlabMergeRequest| {
             ForgeTask::UpdateMergeRequest {
                 project,
                 merge_request: merge_request.iid,
             }
-        })
-        .map_err(errors::forge_error)
-        .try_collect::<Vec<_>>()
-        .await?;
-
-    outcome.additional_tasks = tasks;
+        },
+    )
+    .await?;
 
     Ok(outcome)
 }
@@ -103,6 +102,8 @@ struct GitlabMergeRequestDetails {
     description: Option<String>,
 
     state: GitlabMergeState,
+    created_at: DateTime<Utc>,
+    merged_at: Option<DateTime<Utc>>,
 
     source_project_id: Option<u64>,
     source_branch: String,
@@ -111,6 +112,42 @@ struct GitlabMergeRequestDetails {
     target_branch: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct GitlabNote {
+    body: String,
+    author: GitlabUser,
+    created_at: DateTime<Utc>,
+    system: bool,
+}
+
+const APPROVED_NOTE: &str = "approved this merge request";
+const UNAPPROVED_NOTE: &str = "unapproved this merge request";
+
+/// Extract when a merge request was first reviewed and when it was approved from its notes.
+fn review_times(
+    notes: &[GitlabNote],
+    author: u64,
+) -> (Option<DateTime<Utc>>, Option<DateTime<Utc>>) {
+    let first_review_at = notes
+        .iter()
+        .filter(|note| !note.system && note.author.id != author)
+        .map(|note| note.created_at)
+        .min();
+
+    // Approvals are recorded as system notes; a later revocation clears the approval.
+    let mut approvals = notes
+        .iter()
+        .filter(|note| note.system && (note.body == APPROVED_NOTE || note.body == UNAPPROVED_NOTE))
+        .collect::<Vec<_>>();
+    approvals.sort_by_key(|note| note.created_at);
+    let approved_at = approvals
+        .last()
+        .filter(|note| note.body == APPROVED_NOTE)
+        .map(|note| note.created_at);
+
+    (first_review_at, approved_at)
+}
+
 pub async fn update_merge_request<L>(
     forge: &GitlabForge<L>,
     project: u64,
@@ -120,8 +157,14 @@ where
     L: DiscoverableLookup<MergeRequest<L>>,
     L: DiscoverableLookup<Project<L>>,
     L: DiscoverableLookup<User<L>>,
+    L: Lookup<Deployment<L>>,
+    L: Lookup<Environment<L>>,
     L: Lookup<Instance>,
+    L: Lookup<Job<L>>,
+    L: Lookup<Pipeline<L>>,
     L: Lookup<PipelineSchedule<L>>,
+    L: Lookup<Runner<L>>,
+    L: Lookup<RunnerHost>,
     L: Send + Sync,
 {
     let gl_merge_request: GitlabMergeRequestDetails = {
@@ -137,11 +180,21 @@ where
     };
 
     let mut outcome = ForgeTaskOutcome::default();
+
+    let endpoint = gitlab::api::projects::merge_requests::notes::MergeRequestNotes::builder()
+        .project(project)
+        .merge_request(merge_request)
+        .build()
+        .unwrap();
+    let gl_notes: Vec<GitlabNote> = collect_paged(forge, endpoint, None, &mut outcome).await?;
+    let (first_review_at, approved_at) = review_times(&gl_notes, gl_merge_request.author.id);
+
     let mut add_task = |task| outcome.additional_tasks.push(task);
     let merge_request = gl_merge_request.id;
 
-    let author_idx = if let Some(idx) = <L as DiscoverableLookup<User<L>>>::find(
+    let author_idx = if let Some(idx) = <L as DiscoverableLookup<User<L>>>::find_in(
         forge.storage().deref(),
+        &forge.instance_index(),
         gl_merge_request.author.id,
     ) {
         Some(idx)
@@ -151,8 +204,9 @@ where
         });
         None
     };
-    let target_project_idx = if let Some(idx) = <L as DiscoverableLookup<Project<L>>>::find(
+    let target_project_idx = if let Some(idx) = <L as DiscoverableLookup<Project<L>>>::find_in(
         forge.storage().deref(),
+        &forge.instance_index(),
         gl_merge_request.target_project_id,
     ) {
         Some(idx)
@@ -165,9 +219,11 @@ where
     let source_project_idx = if let Some(source_project_id) = gl_merge_request.source_project_id {
         if source_project_id == gl_merge_request.target_project_id {
             target_project_idx.clone()
-        } else if let Some(idx) =
-            <L as DiscoverableLookup<Project<L>>>::find(forge.storage().deref(), source_project_id)
-        {
+        } else if let Some(idx) = <L as DiscoverableLookup<Project<L>>>::find_in(
+            forge.storage().deref(),
+            &forge.instance_index(),
+            source_project_id,
+        ) {
             Some(idx)
         } else {
             add_task(ForgeTask::UpdateProject {
@@ -199,15 +255,21 @@ where
         merge_request.title = gl_merge_request.title;
         merge_request.description = gl_merge_request.description.unwrap_or_default();
         merge_request.state = gl_merge_request.state.into();
+        merge_request.created_at = Some(gl_merge_request.created_at);
+        merge_request.merged_at = gl_merge_request.merged_at;
+        merge_request.first_review_at = first_review_at;
+        merge_request.approved_at = approved_at;
 
         merge_request.cim_refreshed_at = Utc::now();
     };
 
     // Create a merge request entry.
     let mut discover_pipelines = false;
-    let merge_request = if let Some(idx) =
-        <L as DiscoverableLookup<MergeRequest<L>>>::find(forge.storage().deref(), merge_request)
-    {
+    let merge_request = if let Some(idx) = <L as DiscoverableLookup<MergeRequest<L>>>::find_in(
+        forge.storage().deref(),
+        &forge.instance_index(),
+        merge_request,
+    ) {
         if let Some(existing) =
             <L as Lookup<MergeRequest<L>>>::lookup(forge.storage().deref(), &idx)
         {
@@ -216,6 +278,7 @@ where
                 discover_pipelines = true;
             }
             update(&mut updated);
+            forge.log_update(EntityType::MergeRequest, merge_request, existing, &updated);
             updated
         } else {
             return Err(ForgeError::lookup::<L, MergeRequest<L>>(&idx));
@@ -245,7 +308,7 @@ where
     }
 
     // Store the merge request in the storage.
-    forge.storage_mut().store(merge_request);
+    forge.store(merge_request);
 
     Ok(outcome)
 }
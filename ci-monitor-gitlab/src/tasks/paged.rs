@@ -0,0 +1,282 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::borrow::Cow;
+use std::future::Future;
+
+use ci_monitor_core::data::Instance;
+use ci_monitor_core::Lookup;
+use ci_monitor_forge::{ForgeError, ForgeTask, ForgeTaskOutcome, PartialFailure};
+use gitlab::api::{ApiError, AsyncQuery, BodyError, Endpoint, Pageable, QueryParams, UrlBase};
+use gitlab::RestError;
+use http::Method;
+use serde::de::DeserializeOwned;
+
+use crate::errors;
+use crate::GitlabForge;
+
+/// The number of items requested for each page.
+const PAGE_SIZE: u64 = 100;
+
+/// A single page of a paged endpoint.
+struct Page<E> {
+    endpoint: E,
+    page: u64,
+}
+
+impl<E> Endpoint for Page<E>
+where
+    E: Endpoint + Pageable,
+{
+    fn method(&self) -> Method {
+        self.endpoint.method()
+    }
+
+    fn endpoint(&self) -> Cow<'static, str> {
+        self.endpoint.endpoint()
+    }
+
+    fn url_base(&self) -> UrlBase {
+        self.endpoint.url_base()
+    }
+
+    fn parameters(&self) -> QueryParams<'_> {
+        let mut params = self.endpoint.parameters();
+        params.push("page", self.page).push("per_page", PAGE_SIZE);
+        params
+    }
+
+    fn body(&self) -> Result<Option<(&'static str, Vec<u8>)>, BodyError> {
+        self.endpoint.body()
+    }
+}
+
+/// Collect the items of a paged listing.
+///
+/// Pages are fetched one at a time starting from `start`, or the first page if it is not given.
+/// Items fetched before a failing page are kept and the failure is recorded as partial. Listings
+/// with a `start` page may be resumed, so the failing page is recorded with the failure. If no
+/// items could be fetched, the error is returned instead.
+pub async fn collect_paged<L, E, T>(
+    forge: &GitlabForge<L>,
+    endpoint: E,
+    start: Option<u64>,
+    outcome: &mut ForgeTaskOutcome,
+) -> Result<Vec<T>, ForgeError>
+where
+    L: Lookup<Instance>,
+    L: Send + Sync,
+    E: Endpoint + Pageable + Sync,
+    T: DeserializeOwned + Send + 'static,
+{
+    let endpoint = &endpoint;
+    let fetch = |page| {
+        async move {
+            let page = Page {
+                endpoint,
+                page,
+            };
+            page.query_async(forge.gitlab()).await
+        }
+    };
+
+    collect_pages(fetch, start, outcome).await
+}
+
+async fn collect_pages<F, P, T>(
+    mut fetch: F,
+    start: Option<u64>,
+    outcome: &mut ForgeTaskOutcome,
+) -> Result<Vec<T>, ForgeError>
+where
+    F: FnMut(u64) -> P,
+    P: Future<Output = Result<Vec<T>, ApiError<RestError>>>,
+{
+    let mut collected = Vec::new();
+    let mut page = start.unwrap_or(1);
+
+    loop {
+        match fetch(page).await {
+            Ok(items) => {
+                let last = (items.len() as u64) < PAGE_SIZE;
+                collected.extend(items);
+                if last {
+                    break;
+                }
+                page += 1;
+            },
+            Err(err) => {
+                let err = errors::forge_error(err);
+                if collected.is_empty() {
+                    return Err(err);
+                }
+                let partial = PartialFailure::new(collected.len(), &err);
+                outcome.partial = Some(if start.is_some() {
+                    partial.resume_from(page)
+                } else {
+                    partial
+                });
+                break;
+            },
+        }
+    }
+
+    Ok(collected)
+}
+
+/// Add tasks for each item of a paged listing to an outcome.
+///
+/// See `collect_paged` for how failures are handled.
+pub async fn collect_paged_tasks<L, E, T, F>(
+    forge: &GitlabForge<L>,
+    endpoint: E,
+    start: Option<u64>,
+    outcome: &mut ForgeTaskOutcome,
+    task: F,
+) -> Result<(), ForgeError>
+where
+    L: Lookup<Instance>,
+    L: Send + Sync,
+    E: Endpoint + Pageable + Sync,
+    T: DeserializeOwned + Send + 'static,
+    F: FnMut(T) -> ForgeTask,
+{
+    let items = collect_paged(forge, endpoint, start, outcome).await?;
+    outcome.additional_tasks.extend(items.into_iter().map(task));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future;
+
+    use ci_monitor_forge::{ForgeError, ForgeErrorKind, ForgeTaskOutcome};
+    use futures_util::FutureExt;
+    use gitlab::api::ApiError;
+    use gitlab::RestError;
+    use http::StatusCode;
+
+    use super::PAGE_SIZE;
+
+    fn service_error() -> ApiError<RestError> {
+        ApiError::GitlabService {
+            status: StatusCode::SERVICE_UNAVAILABLE,
+            data: Vec::new(),
+        }
+    }
+
+    // A listing of `count` items where the given page fails.
+    fn collect(
+        count: u64,
+        failing: Option<u64>,
+        start: Option<u64>,
+        outcome: &mut ForgeTaskOutcome,
+    ) -> (Result<Vec<u64>, ForgeError>, Vec<u64>) {
+        let mut requested = Vec::new();
+        let fetch = |page: u64| {
+            requested.push(page);
+            let res = if Some(page) == failing {
+                Err(service_error())
+            } else {
+                let first = (page - 1) * PAGE_SIZE;
+                Ok((first..count.min(first + PAGE_SIZE)).collect())
+            };
+            future::ready(res)
+        };
+        let res = super::collect_pages(fetch, start, outcome)
+            .now_or_never()
+            .unwrap();
+        (res, requested)
+    }
+
+    #[test]
+    fn collect_pages_multiple_pages() {
+        let mut outcome = ForgeTaskOutcome::default();
+
+        let (res, requested) = collect(250, None, Some(1), &mut outcome);
+
+        assert_eq!(res.unwrap(), (0..250).collect::<Vec<_>>());
+        assert_eq!(requested, [1, 2, 3]);
+        assert!(outcome.partial.is_none());
+    }
+
+    #[test]
+    fn collect_pages_full_last_page() {
+        let mut outcome = ForgeTaskOutcome::default();
+
+        let (res, requested) = collect(200, None, Some(1), &mut outcome);
+
+        // A full page may be followed by an empty one.
+        assert_eq!(res.unwrap(), (0..200).collect::<Vec<_>>());
+        assert_eq!(requested, [1, 2, 3]);
+        assert!(outcome.partial.is_none());
+    }
+
+    #[test]
+    fn collect_pages_failing_page() {
+        let mut outcome = ForgeTaskOutcome::default();
+
+        let (res, requested) = collect(250, Some(2), Some(1), &mut outcome);
+
+        // Nothing after the failing page is fetched.
+        assert_eq!(res.unwrap(), (0..100).collect::<Vec<_>>());
+        assert_eq!(requested, [1, 2]);
+        let partial = outcome.partial.unwrap();
+        assert_eq!(partial.processed, 100);
+        assert_eq!(partial.error, ForgeErrorKind::Connection);
+        assert!(partial.retryable);
+        assert_eq!(partial.resume_page, Some(2));
+    }
+
+    #[test]
+    fn collect_pages_resume() {
+        let mut outcome = ForgeTaskOutcome::default();
+
+        let (res, requested) = collect(250, None, Some(2), &mut outcome);
+
+        assert_eq!(res.unwrap(), (100..250).collect::<Vec<_>>());
+        assert_eq!(requested, [2, 3]);
+        assert!(outcome.partial.is_none());
+    }
+
+    #[test]
+    fn collect_pages_resume_failing_page() {
+        let mut outcome = ForgeTaskOutcome::default();
+
+        let (res, requested) = collect(450, Some(4), Some(2), &mut outcome);
+
+        assert_eq!(res.unwrap(), (100..300).collect::<Vec<_>>());
+        assert_eq!(requested, [2, 3, 4]);
+        let partial = outcome.partial.unwrap();
+        assert_eq!(partial.processed, 200);
+        assert_eq!(partial.resume_page, Some(4));
+    }
+
+    #[test]
+    fn collect_pages_failing_page_not_resumable() {
+        let mut outcome = ForgeTaskOutcome::default();
+
+        let (res, requested) = collect(250, Some(3), None, &mut outcome);
+
+        assert_eq!(res.unwrap(), (0..200).collect::<Vec<_>>());
+        assert_eq!(requested, [1, 2, 3]);
+        let partial = outcome.partial.unwrap();
+        assert_eq!(partial.processed, 200);
+        assert_eq!(partial.resume_page, None);
+    }
+
+    #[test]
+    fn collect_pages_failing_first_page() {
+        let mut outcome = ForgeTaskOutcome::default();
+
+        let (res, requested) = collect(250, Some(1), Some(1), &mut outcome);
+
+        assert!(matches!(res.unwrap_err(), ForgeError::Connection { .. }));
+        assert_eq!(requested, [1]);
+        assert!(outcome.partial.is_none());
+    }
+}
@@ -8,17 +8,20 @@ use std::ops::Deref;
 
 use chrono::{DateTime, Utc};
 use ci_monitor_core::data::{
-    Instance, MergeRequest, Pipeline, PipelineSchedule, PipelineSource, PipelineStatus, Project,
-    User,
+    Blob, DataFidelity, Deployment, Environment, Instance, Job, MergeRequest, Pipeline,
+    PipelineSchedule, PipelineSource, PipelineStatus, Project, Runner, RunnerHost, User,
 };
 use ci_monitor_core::Lookup;
 use ci_monitor_forge::{ForgeError, ForgeTask, ForgeTaskOutcome};
-use ci_monitor_persistence::DiscoverableLookup;
-use futures_util::stream::TryStreamExt;
-use gitlab::api::AsyncQuery;
+use ci_monitor_persistence::{DiscoverableLookup, EntityType};
+use gitlab::api::{ApiError, AsyncQuery};
+use http::StatusCode;
 use serde::Deserialize;
 
+use crate::ci_config;
 use crate::errors;
+use crate::schedule_match;
+use crate::tasks::collect_paged_tasks;
 use crate::GitlabForge;
 
 #[derive(Debug, Deserialize)]
@@ -30,34 +33,32 @@ struct GitlabPipeline {
 pub async fn discover_pipelines<L>(
     forge: &GitlabForge<L>,
     project: u64,
+    page: u64,
 ) -> Result<ForgeTaskOutcome, ForgeError>
 where
     L: Lookup<Instance>,
     L: Send + Sync,
 {
-    let gl_pipelines = {
-        let endpoint = gitlab::api::projects::pipelines::Pipelines::builder()
-            .project(project)
-            .build()
-            .unwrap();
-        let endpoint = gitlab::api::paged(endpoint, gitlab::api::Pagination::All);
-        endpoint.into_iter_async::<_, GitlabPipeline>(forge.gitlab())
-    };
+    let endpoint = gitlab::api::projects::pipelines::Pipelines::builder()
+        .project(project)
+        .build()
+        .unwrap();
 
     let mut outcome = ForgeTaskOutcome::default();
 
-    let tasks = gl_pipelines
-        .map_ok(|pipeline| {
+    collect_paged_tasks(
+        forge,
+        endpoint,
+        Some(page),
+        &mut outcome,
+        |pipeline: GitlabPipeline| {
             ForgeTask::UpdatePipeline {
                 project: pipeline.project_id,
                 pipeline: pipeline.id,
             }
-        })
-        .map_err(errors::forge_error)
-        .try_collect::<Vec<_>>()
-        .await?;
-
-    outcome.additional_tasks = tasks;
+        },
+    )
+    .await?;
 
     Ok(outcome)
 }
@@ -66,35 +67,33 @@ pub async fn discover_merge_request_pipelines<L>(
     forge: &GitlabForge<L>,
     project: u64,
     merge_request: u64,
+    page: u64,
 ) -> Result<ForgeTaskOutcome, ForgeError>
 where
     L: Lookup<Instance>,
     L: Send + Sync,
 {
-    let gl_pipelines = {
-        let endpoint = gitlab::api::projects::merge_requests::MergeRequestPipelines::builder()
-            .project(project)
-            .merge_request(merge_request)
-            .build()
-            .unwrap();
-        let endpoint = gitlab::api::paged(endpoint, gitlab::api::Pagination::All);
-        endpoint.into_iter_async::<_, GitlabPipeline>(forge.gitlab())
-    };
+    let endpoint = gitlab::api::projects::merge_requests::MergeRequestPipelines::builder()
+        .project(project)
+        .merge_request(merge_request)
+        .build()
+        .unwrap();
 
     let mut outcome = ForgeTaskOutcome::default();
 
-    let tasks = gl_pipelines
-        .map_ok(|pipeline| {
+    collect_paged_tasks(
+        forge,
+        endpoint,
+        Some(page),
+        &mut outcome,
+        |pipeline: GitlabPipeline| {
             ForgeTask::UpdatePipeline {
                 project: pipeline.project_id,
                 pipeline: pipeline.id,
             }
-        })
-        .map_err(errors::forge_error)
-        .try_collect::<Vec<_>>()
-        .await?;
-
-    outcome.additional_tasks = tasks;
+        },
+    )
+    .await?;
 
     Ok(outcome)
 }
@@ -245,11 +244,16 @@ pub async fn update_pipeline<L>(
 ) -> Result<ForgeTaskOutcome, ForgeError>
 where
     L: DiscoverableLookup<Pipeline<L>>,
+    L: DiscoverableLookup<PipelineSchedule<L>>,
     L: DiscoverableLookup<Project<L>>,
     L: DiscoverableLookup<User<L>>,
-    L: Lookup<MergeRequest<L>>,
-    L: Lookup<PipelineSchedule<L>>,
+    L: Lookup<Deployment<L>>,
+    L: Lookup<Environment<L>>,
     L: Lookup<Instance>,
+    L: Lookup<Job<L>>,
+    L: Lookup<MergeRequest<L>>,
+    L: Lookup<Runner<L>>,
+    L: Lookup<RunnerHost>,
     L: Send + Sync,
 {
     let gl_pipeline: GitlabPipelineDetails = {
@@ -269,9 +273,11 @@ where
     let pipeline = gl_pipeline.id;
 
     let user_idx = if let Some(user) = gl_pipeline.user {
-        if let Some(idx) =
-            <L as DiscoverableLookup<User<L>>>::find(forge.storage().deref(), user.id)
-        {
+        if let Some(idx) = <L as DiscoverableLookup<User<L>>>::find_in(
+            forge.storage().deref(),
+            &forge.instance_index(),
+            user.id,
+        ) {
             Some(idx)
         } else {
             add_task(ForgeTask::UpdateUser {
@@ -282,9 +288,11 @@ where
     } else {
         None
     };
-    let project_idx = if let Some(idx) =
-        <L as DiscoverableLookup<Project<L>>>::find(forge.storage().deref(), gl_pipeline.project_id)
-    {
+    let project_idx = if let Some(idx) = <L as DiscoverableLookup<Project<L>>>::find_in(
+        forge.storage().deref(),
+        &forge.instance_index(),
+        gl_pipeline.project_id,
+    ) {
         Some(idx)
     } else {
         add_task(ForgeTask::UpdateProject {
@@ -317,21 +325,39 @@ where
         pipeline.cim_refreshed_at = Utc::now();
     };
 
-    // Create a pipeline entry.
-    let mut schedule_job_update = false;
-    let pipeline = if let Some(idx) =
-        <L as DiscoverableLookup<Pipeline<L>>>::find(forge.storage().deref(), pipeline)
-    {
+    let existing = if let Some(idx) = <L as DiscoverableLookup<Pipeline<L>>>::find_in(
+        forge.storage().deref(),
+        &forge.instance_index(),
+        pipeline,
+    ) {
         if let Some(existing) = <L as Lookup<Pipeline<L>>>::lookup(forge.storage().deref(), &idx) {
-            let mut updated = existing.clone();
-            if is_active(updated.status) || updated.status != gl_pipeline.status.into() {
-                schedule_job_update = true;
-            }
-            update(&mut updated);
-            updated
+            Some(existing.clone())
         } else {
             return Err(ForgeError::lookup::<L, Pipeline<L>>(&idx));
         }
+    } else {
+        None
+    };
+    // Pipelines created from lower fidelity sources are replaced entirely.
+    let existing = existing.filter(|existing| existing.cim_fidelity == DataFidelity::Forge);
+
+    let was_finished = existing
+        .as_ref()
+        .is_some_and(|existing| existing.status.is_finished());
+
+    // Create a pipeline entry.
+    let mut schedule_job_update = false;
+    let mut schedule_config_fetch = false;
+    let mut pipeline = if let Some(mut updated) = existing {
+        if is_active(updated.status) || updated.status != gl_pipeline.status.into() {
+            schedule_job_update = true;
+        }
+        let previous = forge.logs_updates().then(|| updated.clone());
+        update(&mut updated);
+        if let Some(previous) = previous.as_ref() {
+            forge.log_update(EntityType::Pipeline, pipeline, previous, &updated);
+        }
+        updated
     } else {
         let mut pipeline = Pipeline::builder()
             .forge_id(pipeline)
@@ -342,7 +368,6 @@ where
             .stable_refname(Some(format!("refs/pipelines/{}", gl_pipeline.id)))
             .source(gl_pipeline.source.into())
             // TODO: How/where to obtain this information in this direction?
-            //.schedule???
             //.parent_pipeline???
             //.merge_request???
             .status(gl_pipeline.status.into())
@@ -353,11 +378,24 @@ where
             .build()
             .unwrap();
         schedule_job_update = true;
+        schedule_config_fetch = forge.blobs().is_some();
 
         update(&mut pipeline);
         pipeline
     };
 
+    // GitLab does not report which schedule started a pipeline.
+    if pipeline.schedule.is_none() {
+        pipeline.schedule = schedule_match::matching_schedule(forge.storage().deref(), &pipeline);
+    }
+
+    // Fetch the configuration first so that jobs may pick up their variables from it.
+    if schedule_config_fetch {
+        add_task(ForgeTask::FetchPipelineConfig {
+            project: gl_pipeline.project_id,
+            pipeline: gl_pipeline.id,
+        });
+    }
     if schedule_job_update {
         add_task(ForgeTask::DiscoverJobs {
             project: gl_pipeline.project_id,
@@ -365,8 +403,183 @@ where
         });
     }
 
+    let is_finished = pipeline.status.is_finished();
+
     // Store the pipeline in the storage.
-    forge.storage_mut().store(pipeline);
+    let idx = forge.store(pipeline);
+
+    if is_finished && !was_finished {
+        if let Some(pipeline) = <L as Lookup<Pipeline<L>>>::lookup(forge.storage().deref(), &idx) {
+            forge.hooks().pipeline_finished(pipeline);
+        }
+    }
+
+    Ok(outcome)
+}
+
+#[derive(Debug, Deserialize)]
+struct GitlabPipelineState {
+    status: GitlabPipelineStatus,
+}
+
+pub async fn reconcile_pipeline<L>(
+    forge: &GitlabForge<L>,
+    project: u64,
+    pipeline: u64,
+) -> Result<ForgeTaskOutcome, ForgeError>
+where
+    L: DiscoverableLookup<Pipeline<L>>,
+    L: Lookup<Deployment<L>>,
+    L: Lookup<Environment<L>>,
+    L: Lookup<Instance>,
+    L: Lookup<Job<L>>,
+    L: Lookup<MergeRequest<L>>,
+    L: Lookup<PipelineSchedule<L>>,
+    L: Lookup<Project<L>>,
+    L: Lookup<Runner<L>>,
+    L: Lookup<RunnerHost>,
+    L: Lookup<User<L>>,
+    L: Send + Sync,
+{
+    let mut outcome = ForgeTaskOutcome::default();
+
+    let existing = <L as DiscoverableLookup<Pipeline<L>>>::find_in(
+        forge.storage().deref(),
+        &forge.instance_index(),
+        pipeline,
+    )
+    .and_then(|idx| <L as Lookup<Pipeline<L>>>::lookup(forge.storage().deref(), &idx).cloned());
+    // Only stored pipelines which are still believed to exist need to be checked.
+    let mut existing = if let Some(existing) = existing.filter(|p| p.cim_deleted_at.is_none()) {
+        existing
+    } else {
+        return Ok(outcome);
+    };
+
+    let gl_pipeline: Option<GitlabPipelineState> = {
+        let endpoint = gitlab::api::projects::pipelines::Pipeline::builder()
+            .project(project)
+            .pipeline(pipeline)
+            .build()
+            .unwrap();
+        match endpoint.query_async(forge.gitlab()).await {
+            Ok(gl_pipeline) => Some(gl_pipeline),
+            Err(err) if errors::is_not_found(&err) => None,
+            Err(err) => return Err(errors::forge_error(err)),
+        }
+    };
+
+    let now = Utc::now();
+    if let Some(gl_pipeline) = gl_pipeline {
+        // The full update also picks up changes to the pipeline's jobs.
+        if existing.status != gl_pipeline.status.into() {
+            outcome.additional_tasks.push(ForgeTask::UpdatePipeline {
+                project,
+                pipeline,
+            });
+            return Ok(outcome);
+        }
+    } else {
+        // The pipeline has been removed upstream (e.g., by a retention policy). Only the
+        // pipeline itself is marked; the caller cascades the deletion to its jobs.
+        existing.cim_deleted_at = Some(now);
+    }
+    existing.cim_refreshed_at = now;
+    forge.store(existing);
+
+    Ok(outcome)
+}
+
+/// The path to the CI configuration within a repository.
+const CI_CONFIG_PATH: &str = ".gitlab-ci.yml";
+
+pub async fn fetch_pipeline_config<L>(
+    forge: &GitlabForge<L>,
+    project: u64,
+    pipeline: u64,
+) -> Result<ForgeTaskOutcome, ForgeError>
+where
+    L: DiscoverableLookup<Pipeline<L>>,
+    L: Lookup<Deployment<L>>,
+    L: Lookup<Environment<L>>,
+    L: Lookup<Instance>,
+    L: Lookup<Job<L>>,
+    L: Lookup<MergeRequest<L>>,
+    L: Lookup<PipelineSchedule<L>>,
+    L: Lookup<Project<L>>,
+    L: Lookup<Runner<L>>,
+    L: Lookup<RunnerHost>,
+    L: Lookup<User<L>>,
+    L: Send + Sync,
+{
+    let blobs = if let Some(blobs) = forge.blobs() {
+        blobs
+    } else {
+        return Err(ForgeError::Unhandled {
+            task: ForgeTask::FetchPipelineConfig {
+                project,
+                pipeline,
+            },
+        });
+    };
+
+    let mut outcome = ForgeTaskOutcome::default();
+
+    let existing = <L as DiscoverableLookup<Pipeline<L>>>::find_in(
+        forge.storage().deref(),
+        &forge.instance_index(),
+        pipeline,
+    )
+    .and_then(|idx| <L as Lookup<Pipeline<L>>>::lookup(forge.storage().deref(), &idx).cloned());
+    let mut existing = if let Some(existing) = existing {
+        existing
+    } else {
+        outcome.additional_tasks.push(ForgeTask::UpdatePipeline {
+            project,
+            pipeline,
+        });
+        outcome
+            .additional_tasks
+            .push(ForgeTask::FetchPipelineConfig {
+                project,
+                pipeline,
+            });
+        return Ok(outcome);
+    };
+
+    let data = {
+        let endpoint = gitlab::api::projects::repository::files::FileRaw::builder()
+            .project(project)
+            .file_path(CI_CONFIG_PATH)
+            .ref_(existing.sha.as_str())
+            .build()
+            .unwrap();
+        let res = gitlab::api::raw(endpoint).query_async(forge.gitlab()).await;
+        match res {
+            Ok(data) => data,
+            // Projects may use a configuration from elsewhere (or have none at all).
+            Err(ApiError::GitlabService {
+                status: StatusCode::NOT_FOUND,
+                ..
+            }) => return Ok(outcome),
+            Err(err) => return Err(errors::forge_error(err)),
+        }
+    };
+
+    let artifact_dependencies = ci_config::artifact_dependencies(&data);
+    let blob = Blob::new(data);
+    let blob_ref = blobs.store(&blob).map_err(|err| {
+        ForgeError::Other {
+            details: format!(
+                "failed to store CI configuration for pipeline {}: {}",
+                pipeline, err
+            ),
+        }
+    })?;
+
+    existing.ci_config = Some(blob_ref);
+    existing.artifact_dependencies = artifact_dependencies;
+    forge.store(existing);
 
     Ok(outcome)
 }
@@ -7,15 +7,19 @@
 use std::ops::Deref;
 
 use chrono::{DateTime, Utc};
-use ci_monitor_core::data::{Instance, PipelineSchedule, Project, User};
+use ci_monitor_core::data::{
+    Deployment, Environment, Instance, Job, MergeRequest, Pipeline, PipelineSchedule,
+    PipelineSource, Project, Runner, RunnerHost, User,
+};
 use ci_monitor_core::Lookup;
 use ci_monitor_forge::{ForgeError, ForgeTask, ForgeTaskOutcome};
-use ci_monitor_persistence::DiscoverableLookup;
-use futures_util::stream::TryStreamExt;
+use ci_monitor_persistence::{DiscoverableLookup, EntityType};
 use gitlab::api::AsyncQuery;
 use serde::Deserialize;
 
 use crate::errors;
+use crate::schedule_match;
+use crate::tasks::collect_paged_tasks;
 use crate::tasks::GitlabPipelineVariable;
 use crate::GitlabForge;
 
@@ -27,34 +31,32 @@ struct GitlabPipelineSchedule {
 pub async fn discover_pipeline_schedules<L>(
     forge: &GitlabForge<L>,
     project: u64,
+    page: u64,
 ) -> Result<ForgeTaskOutcome, ForgeError>
 where
     L: Lookup<Instance>,
     L: Send + Sync,
 {
-    let gl_pipeline_schedules = {
-        let endpoint = gitlab::api::projects::pipeline_schedules::PipelineSchedules::builder()
-            .project(project)
-            .build()
-            .unwrap();
-        let endpoint = gitlab::api::paged(endpoint, gitlab::api::Pagination::All);
-        endpoint.into_iter_async::<_, GitlabPipelineSchedule>(forge.gitlab())
-    };
+    let endpoint = gitlab::api::projects::pipeline_schedules::PipelineSchedules::builder()
+        .project(project)
+        .build()
+        .unwrap();
 
     let mut outcome = ForgeTaskOutcome::default();
 
-    let tasks = gl_pipeline_schedules
-        .map_ok(|pipeline_schedule| {
+    collect_paged_tasks(
+        forge,
+        endpoint,
+        Some(page),
+        &mut outcome,
+        |pipeline_schedule: GitlabPipelineSchedule| {
             ForgeTask::UpdatePipelineSchedule {
                 project,
                 schedule: pipeline_schedule.id,
             }
-        })
-        .map_err(errors::forge_error)
-        .try_collect::<Vec<_>>()
-        .await?;
-
-    outcome.additional_tasks = tasks;
+        },
+    )
+    .await?;
 
     Ok(outcome)
 }
@@ -89,10 +91,17 @@ pub async fn update_pipeline_schedule<L>(
     pipeline_schedule: u64,
 ) -> Result<ForgeTaskOutcome, ForgeError>
 where
+    L: DiscoverableLookup<Pipeline<L>>,
     L: DiscoverableLookup<PipelineSchedule<L>>,
     L: DiscoverableLookup<Project<L>>,
     L: DiscoverableLookup<User<L>>,
+    L: Lookup<Deployment<L>>,
+    L: Lookup<Environment<L>>,
     L: Lookup<Instance>,
+    L: Lookup<Job<L>>,
+    L: Lookup<MergeRequest<L>>,
+    L: Lookup<Runner<L>>,
+    L: Lookup<RunnerHost>,
     L: Send + Sync,
 {
     let gl_pipeline_schedule: GitlabPipelineScheduleDetails = {
@@ -111,8 +120,9 @@ where
     let mut add_task = |task| outcome.additional_tasks.push(task);
     let pipeline_schedule = gl_pipeline_schedule.id;
 
-    let user_idx = if let Some(idx) = <L as DiscoverableLookup<User<L>>>::find(
+    let user_idx = if let Some(idx) = <L as DiscoverableLookup<User<L>>>::find_in(
         forge.storage().deref(),
+        &forge.instance_index(),
         gl_pipeline_schedule.owner.id,
     ) {
         Some(idx)
@@ -122,9 +132,11 @@ where
         });
         None
     };
-    let project_idx = if let Some(idx) =
-        <L as DiscoverableLookup<Project<L>>>::find(forge.storage().deref(), project)
-    {
+    let project_idx = if let Some(idx) = <L as DiscoverableLookup<Project<L>>>::find_in(
+        forge.storage().deref(),
+        &forge.instance_index(),
+        project,
+    ) {
         Some(idx)
     } else {
         add_task(ForgeTask::UpdateProject {
@@ -159,15 +171,23 @@ where
     };
 
     // Create a pipeline schedule entry.
-    let pipeline_schedule = if let Some(idx) = <L as DiscoverableLookup<PipelineSchedule<L>>>::find(
-        forge.storage().deref(),
-        pipeline_schedule,
-    ) {
+    let pipeline_schedule = if let Some(idx) =
+        <L as DiscoverableLookup<PipelineSchedule<L>>>::find_in(
+            forge.storage().deref(),
+            &forge.instance_index(),
+            pipeline_schedule,
+        ) {
         if let Some(existing) =
             <L as Lookup<PipelineSchedule<L>>>::lookup(forge.storage().deref(), &idx)
         {
             let mut updated = existing.clone();
             update(&mut updated);
+            forge.log_update(
+                EntityType::PipelineSchedule,
+                pipeline_schedule,
+                existing,
+                &updated,
+            );
             updated
         } else {
             return Err(ForgeError::lookup::<L, PipelineSchedule<L>>(&idx));
@@ -188,7 +208,49 @@ where
     };
 
     // Store the pipeline schedule in the storage.
-    forge.storage_mut().store(pipeline_schedule);
+    forge.store(pipeline_schedule);
+
+    link_scheduled_pipelines(forge);
 
     Ok(outcome)
 }
+
+/// Link scheduled pipelines which were stored before the schedule which started them.
+fn link_scheduled_pipelines<L>(forge: &GitlabForge<L>)
+where
+    L: DiscoverableLookup<Pipeline<L>>,
+    L: DiscoverableLookup<PipelineSchedule<L>>,
+    L: Lookup<Deployment<L>>,
+    L: Lookup<Environment<L>>,
+    L: Lookup<Instance>,
+    L: Lookup<Job<L>>,
+    L: Lookup<MergeRequest<L>>,
+    L: Lookup<Project<L>>,
+    L: Lookup<Runner<L>>,
+    L: Lookup<RunnerHost>,
+    L: Lookup<User<L>>,
+{
+    let linked = {
+        let storage = forge.storage();
+        <L as DiscoverableLookup<Pipeline<L>>>::all_indices(storage.deref())
+            .iter()
+            .filter_map(|idx| <L as Lookup<Pipeline<L>>>::lookup(storage.deref(), idx))
+            .filter(|pipeline| {
+                pipeline.source == PipelineSource::Schedule
+                    && pipeline.schedule.is_none()
+                    && pipeline.cim_deleted_at.is_none()
+            })
+            .filter_map(|pipeline| {
+                let schedule = schedule_match::matching_schedule(storage.deref(), pipeline)?;
+                let mut updated = pipeline.clone();
+                updated.schedule = Some(schedule);
+                forge.log_update(EntityType::Pipeline, pipeline.forge_id, pipeline, &updated);
+                Some(updated)
+            })
+            .collect::<Vec<_>>()
+    };
+
+    for pipeline in linked {
+        forge.store(pipeline);
+    }
+}
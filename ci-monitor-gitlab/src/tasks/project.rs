@@ -7,10 +7,13 @@
 use std::ops::Deref;
 
 use chrono::{DateTime, Utc};
-use ci_monitor_core::data::{Instance, Project};
+use ci_monitor_core::data::{
+    DataFidelity, Deployment, Environment, Instance, Job, MergeRequest, Pipeline, PipelineSchedule, Project,
+    Runner, RunnerHost, User,
+};
 use ci_monitor_core::Lookup;
 use ci_monitor_forge::{ForgeError, ForgeTask, ForgeTaskOutcome};
-use ci_monitor_persistence::DiscoverableLookup;
+use ci_monitor_persistence::{DiscoverableLookup, EntityType};
 use gitlab::api::AsyncQuery;
 use serde::Deserialize;
 
@@ -45,11 +48,13 @@ struct GitlabProject {
     name: String,
     web_url: String,
     path_with_namespace: String,
+    default_branch: Option<String>,
 
     // Options which can discover more work.
     merge_requests_access_level: AccessLevel,
     builds_access_level: AccessLevel,
     environments_access_level: AccessLevel,
+    releases_access_level: Option<AccessLevel>,
     forked_from_project: Option<ParentProject>,
 
     updated_at: DateTime<Utc>,
@@ -61,7 +66,16 @@ async fn update_project_impl<L>(
 ) -> Result<ForgeTaskOutcome, ForgeError>
 where
     L: DiscoverableLookup<Project<L>>,
+    L: Lookup<Deployment<L>>,
+    L: Lookup<Environment<L>>,
     L: Lookup<Instance>,
+    L: Lookup<Job<L>>,
+    L: Lookup<MergeRequest<L>>,
+    L: Lookup<Pipeline<L>>,
+    L: Lookup<PipelineSchedule<L>>,
+    L: Lookup<Runner<L>>,
+    L: Lookup<RunnerHost>,
+    L: Lookup<User<L>>,
     L: Send + Sync,
 {
     let mut outcome = ForgeTaskOutcome::default();
@@ -73,15 +87,26 @@ where
         project.url = gl_project.web_url;
         project.instance_path = gl_project.path_with_namespace;
 
-        project.cim_refreshed_at = Utc::now();
+        let now = Utc::now();
+        project.set_default_branch(gl_project.default_branch, now);
+        project.cim_fidelity = DataFidelity::Forge;
+        project.cim_refreshed_at = now;
     };
 
     // Create a project entry.
-    let (project_entry, update_components) = if let Some(idx) = forge.storage().find(project) {
+    let (project_entry, update_components) = if let Some(idx) =
+        forge.storage().find_in(&forge.instance_index(), project)
+    {
         if let Some(existing) = <L as Lookup<Project<L>>>::lookup(forge.storage().deref(), &idx) {
             let mut updated = existing.clone();
             update(&mut updated);
-            (updated, existing.cim_refreshed_at < gl_project.updated_at)
+            forge.log_update(EntityType::Project, project, existing, &updated);
+            // Projects which left the watched groups are no longer monitored.
+            let monitored = existing.cim_removed_from_group_at.is_none();
+            // Projects created from notifications have never had their components discovered.
+            let stale = existing.cim_fidelity != DataFidelity::Forge
+                || existing.cim_refreshed_at < gl_project.updated_at;
+            (updated, monitored && stale)
         } else {
             return Err(ForgeError::lookup::<L, Project<L>>(&idx));
         }
@@ -110,6 +135,21 @@ where
             add_task(ForgeTask::DiscoverPipelines {
                 project,
             });
+            if forge.scans_pushes() {
+                add_task(ForgeTask::DiscoverPushes {
+                    project,
+                });
+            }
+        }
+
+        // Older instances do not report access to releases separately.
+        if gl_project
+            .releases_access_level
+            .is_none_or(AccessLevel::is_enabled)
+        {
+            add_task(ForgeTask::DiscoverReleases {
+                project,
+            });
         }
 
         if gl_project.environments_access_level.is_enabled() {
@@ -129,7 +169,7 @@ where
     }
 
     // Store the project in the storage.
-    forge.storage_mut().store(project_entry);
+    forge.store(project_entry);
 
     Ok(outcome)
 }
@@ -140,7 +180,16 @@ pub async fn update_project<L>(
 ) -> Result<ForgeTaskOutcome, ForgeError>
 where
     L: DiscoverableLookup<Project<L>>,
+    L: Lookup<Deployment<L>>,
+    L: Lookup<Environment<L>>,
     L: Lookup<Instance>,
+    L: Lookup<Job<L>>,
+    L: Lookup<MergeRequest<L>>,
+    L: Lookup<Pipeline<L>>,
+    L: Lookup<PipelineSchedule<L>>,
+    L: Lookup<Runner<L>>,
+    L: Lookup<RunnerHost>,
+    L: Lookup<User<L>>,
     L: Send + Sync,
 {
     let gl_project: GitlabProject = {
@@ -163,7 +212,16 @@ pub async fn update_project_by_name<L>(
 ) -> Result<ForgeTaskOutcome, ForgeError>
 where
     L: DiscoverableLookup<Project<L>>,
+    L: Lookup<Deployment<L>>,
+    L: Lookup<Environment<L>>,
     L: Lookup<Instance>,
+    L: Lookup<Job<L>>,
+    L: Lookup<MergeRequest<L>>,
+    L: Lookup<Pipeline<L>>,
+    L: Lookup<PipelineSchedule<L>>,
+    L: Lookup<Runner<L>>,
+    L: Lookup<RunnerHost>,
+    L: Lookup<User<L>>,
     L: Send + Sync,
 {
     let gl_project: GitlabProject = {
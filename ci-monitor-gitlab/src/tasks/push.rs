@@ -0,0 +1,192 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::ops::Deref;
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use ci_monitor_core::data::{
+    Deployment, Environment, Instance, Job, MergeRequest, Pipeline, PipelineSchedule, Project,
+    Push, Runner, RunnerHost, User,
+};
+use ci_monitor_core::Lookup;
+use ci_monitor_forge::{ForgeError, ForgeTask, ForgeTaskOutcome};
+use ci_monitor_persistence::DiscoverableLookup;
+use serde::Deserialize;
+
+use crate::endpoints;
+use crate::tasks::collect_paged;
+use crate::GitlabForge;
+
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum GitlabPushAction {
+    Pushed,
+    Created,
+    Removed,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum GitlabRefType {
+    Branch,
+    Tag,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitlabPushData {
+    commit_count: u64,
+    action: GitlabPushAction,
+    ref_type: GitlabRefType,
+    commit_from: Option<String>,
+    commit_to: Option<String>,
+    #[serde(rename = "ref")]
+    ref_: Option<String>,
+    commit_title: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitlabPushEvent {
+    id: u64,
+    author_id: Option<u64>,
+    created_at: DateTime<Utc>,
+    push_data: Option<GitlabPushData>,
+}
+
+/// The date from which to list push events for a project.
+///
+/// Pushes from before the most recently stored push are already known. The date is backed off by
+/// a day because the API only filters by date.
+fn scan_after<L>(storage: &L, project: u64) -> Option<NaiveDate>
+where
+    L: DiscoverableLookup<Push<L>>,
+    L: Lookup<Instance>,
+    L: Lookup<Project<L>>,
+    L: Lookup<User<L>>,
+{
+    let pushes = <L as DiscoverableLookup<Push<L>>>::all_indices(storage);
+    pushes
+        .iter()
+        .filter_map(|idx| <L as Lookup<Push<L>>>::lookup(storage, idx))
+        .filter(|push| {
+            <L as Lookup<Project<L>>>::lookup(storage, &push.project)
+                .is_some_and(|push_project| push_project.forge_id == project)
+        })
+        .map(|push| push.pushed_at)
+        .max()
+        .map(|pushed_at| (pushed_at - Duration::days(1)).date_naive())
+}
+
+pub async fn discover_pushes<L>(
+    forge: &GitlabForge<L>,
+    project: u64,
+) -> Result<ForgeTaskOutcome, ForgeError>
+where
+    L: DiscoverableLookup<Project<L>>,
+    L: DiscoverableLookup<Push<L>>,
+    L: DiscoverableLookup<User<L>>,
+    L: Lookup<Deployment<L>>,
+    L: Lookup<Environment<L>>,
+    L: Lookup<Instance>,
+    L: Lookup<Job<L>>,
+    L: Lookup<MergeRequest<L>>,
+    L: Lookup<Pipeline<L>>,
+    L: Lookup<PipelineSchedule<L>>,
+    L: Lookup<Runner<L>>,
+    L: Lookup<RunnerHost>,
+    L: Send + Sync,
+{
+    let project_idx = if let Some(idx) = <L as DiscoverableLookup<Project<L>>>::find_in(
+        forge.storage().deref(),
+        &forge.instance_index(),
+        project,
+    ) {
+        idx
+    } else {
+        let mut outcome = ForgeTaskOutcome::default();
+        outcome.additional_tasks.push(ForgeTask::UpdateProject {
+            project,
+        });
+        outcome.additional_tasks.push(ForgeTask::DiscoverPushes {
+            project,
+        });
+        return Ok(outcome);
+    };
+
+    let mut endpoint = endpoints::ProjectPushEvents::new(project);
+    if let Some(after) = scan_after(forge.storage().deref(), project) {
+        endpoint = endpoint.after(after);
+    }
+
+    let mut outcome = ForgeTaskOutcome::default();
+    let gl_events: Vec<GitlabPushEvent> =
+        collect_paged(forge, endpoint, None, &mut outcome).await?;
+
+    for gl_event in gl_events {
+        let push_data = if let Some(push_data) = gl_event.push_data {
+            push_data
+        } else {
+            continue;
+        };
+        // Deleted refs cannot have pipelines.
+        if push_data.action == GitlabPushAction::Removed {
+            continue;
+        }
+        // Pushes of multiple refs at once do not report which refs were updated.
+        let (refname, sha) =
+            if let (Some(refname), Some(sha)) = (push_data.ref_, push_data.commit_to) {
+                (refname, sha)
+            } else {
+                continue;
+            };
+
+        let author = {
+            let storage = forge.storage();
+            // Pushes do not change once they have happened.
+            if <L as DiscoverableLookup<Push<L>>>::find_in(
+                storage.deref(),
+                &forge.instance_index(),
+                gl_event.id,
+            )
+            .is_some()
+            {
+                continue;
+            }
+
+            gl_event.author_id.and_then(|author| {
+                let idx = <L as DiscoverableLookup<User<L>>>::find_in(
+                    storage.deref(),
+                    &forge.instance_index(),
+                    author,
+                );
+                if idx.is_none() {
+                    outcome.additional_tasks.push(ForgeTask::UpdateUser {
+                        user: author,
+                    });
+                }
+                idx
+            })
+        };
+
+        let push = Push::builder()
+            .author(author)
+            .commit_count(push_data.commit_count)
+            .commit_title(push_data.commit_title.unwrap_or_default())
+            .project(project_idx.clone())
+            .refname(refname)
+            .tag(push_data.ref_type == GitlabRefType::Tag)
+            .sha(sha)
+            .before(push_data.commit_from)
+            .forge_id(gl_event.id)
+            .pushed_at(gl_event.created_at)
+            .build()
+            .unwrap();
+
+        // Store the push in the storage.
+        forge.store(push);
+    }
+
+    Ok(outcome)
+}
@@ -0,0 +1,267 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::ops::Deref;
+
+use chrono::{DateTime, Utc};
+use ci_monitor_core::data::{
+    Deployment, Environment, Instance, Job, MergeRequest, Pipeline, PipelineSchedule, Project,
+    Release, ReleaseAsset, Runner, RunnerHost, User,
+};
+use ci_monitor_core::Lookup;
+use ci_monitor_forge::{ForgeError, ForgeTask, ForgeTaskOutcome};
+use ci_monitor_persistence::DiscoverableLookup;
+use serde::Deserialize;
+
+use crate::tasks::collect_paged;
+use crate::GitlabForge;
+
+#[derive(Debug, Deserialize)]
+struct GitlabUser {
+    id: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitlabCommit {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitlabReleaseLink {
+    name: String,
+    url: String,
+    link_type: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitlabReleaseAssets {
+    #[serde(default)]
+    links: Vec<GitlabReleaseLink>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitlabReleaseLinks {
+    #[serde(rename = "self")]
+    self_: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitlabRelease {
+    tag_name: String,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    author: Option<GitlabUser>,
+    commit: GitlabCommit,
+    assets: Option<GitlabReleaseAssets>,
+    #[serde(rename = "_links")]
+    links: Option<GitlabReleaseLinks>,
+
+    created_at: DateTime<Utc>,
+    released_at: Option<DateTime<Utc>>,
+}
+
+/// Find the pipeline which built a release's tag.
+///
+/// Pipelines for the tag itself are preferred over other pipelines for the same commit. The most
+/// recent matching pipeline is used.
+fn release_pipeline<L>(
+    storage: &L,
+    project: u64,
+    tag: &str,
+    sha: &str,
+) -> Option<<L as Lookup<Pipeline<L>>>::Index>
+where
+    L: DiscoverableLookup<Pipeline<L>>,
+    L: Lookup<Instance>,
+    L: Lookup<MergeRequest<L>>,
+    L: Lookup<PipelineSchedule<L>>,
+    L: Lookup<Project<L>>,
+    L: Lookup<User<L>>,
+{
+    let pipelines = <L as DiscoverableLookup<Pipeline<L>>>::all_indices(storage);
+    pipelines
+        .iter()
+        .filter_map(|idx| {
+            <L as Lookup<Pipeline<L>>>::lookup(storage, idx).map(|pipeline| (idx, pipeline))
+        })
+        .filter(|(_, pipeline)| {
+            pipeline.sha == sha
+                && <L as Lookup<Project<L>>>::lookup(storage, &pipeline.project)
+                    .is_some_and(|pipeline_project| pipeline_project.forge_id == project)
+        })
+        .max_by_key(|(_, pipeline)| (pipeline.refname.as_deref() == Some(tag), pipeline.forge_id))
+        .map(|(idx, _)| idx.clone())
+}
+
+/// Find the stored release for a tag in a project.
+fn find_release<L>(storage: &L, project: u64, tag: &str) -> Option<u64>
+where
+    L: DiscoverableLookup<Release<L>>,
+    L: Lookup<Instance>,
+    L: Lookup<MergeRequest<L>>,
+    L: Lookup<Pipeline<L>>,
+    L: Lookup<PipelineSchedule<L>>,
+    L: Lookup<Project<L>>,
+    L: Lookup<User<L>>,
+{
+    let releases = <L as DiscoverableLookup<Release<L>>>::all_indices(storage);
+    releases
+        .iter()
+        .filter_map(|idx| <L as Lookup<Release<L>>>::lookup(storage, idx))
+        .find(|release| {
+            release.tag == tag
+                && <L as Lookup<Project<L>>>::lookup(storage, &release.project)
+                    .is_some_and(|release_project| release_project.forge_id == project)
+        })
+        .map(|release| release.unique_id)
+}
+
+/// Store a release, assigning a new unique ID if it is not yet known.
+fn store_release<L>(forge: &GitlabForge<L>, project: u64, mut release: Release<L>)
+where
+    L: DiscoverableLookup<Release<L>>,
+    L: Lookup<Deployment<L>>,
+    L: Lookup<Environment<L>>,
+    L: Lookup<Instance>,
+    L: Lookup<Job<L>>,
+    L: Lookup<MergeRequest<L>>,
+    L: Lookup<Pipeline<L>>,
+    L: Lookup<PipelineSchedule<L>>,
+    L: Lookup<Project<L>>,
+    L: Lookup<Runner<L>>,
+    L: Lookup<RunnerHost>,
+    L: Lookup<User<L>>,
+{
+    let mut storage = forge.storage_mut();
+    release.unique_id = find_release(storage.deref(), project, &release.tag).unwrap_or_else(|| {
+        <L as DiscoverableLookup<Release<L>>>::all_indices(storage.deref()).len() as u64
+    });
+    let idx = storage.store(release);
+
+    if !forge.hooks().is_empty() {
+        if let Some(release) = <L as Lookup<Release<L>>>::lookup(storage.deref(), &idx) {
+            forge.hooks().entity_stored(release.into());
+        }
+    }
+}
+
+pub async fn discover_releases<L>(
+    forge: &GitlabForge<L>,
+    project: u64,
+    page: u64,
+) -> Result<ForgeTaskOutcome, ForgeError>
+where
+    L: DiscoverableLookup<Pipeline<L>>,
+    L: DiscoverableLookup<Project<L>>,
+    L: DiscoverableLookup<Release<L>>,
+    L: DiscoverableLookup<User<L>>,
+    L: Lookup<Deployment<L>>,
+    L: Lookup<Environment<L>>,
+    L: Lookup<Instance>,
+    L: Lookup<Job<L>>,
+    L: Lookup<MergeRequest<L>>,
+    L: Lookup<PipelineSchedule<L>>,
+    L: Lookup<Runner<L>>,
+    L: Lookup<RunnerHost>,
+    L: Send + Sync,
+{
+    let project_idx = if let Some(idx) = <L as DiscoverableLookup<Project<L>>>::find_in(
+        forge.storage().deref(),
+        &forge.instance_index(),
+        project,
+    ) {
+        idx
+    } else {
+        let mut outcome = ForgeTaskOutcome::default();
+        outcome.additional_tasks.push(ForgeTask::UpdateProject {
+            project,
+        });
+        outcome.additional_tasks.push(ForgeTask::DiscoverReleases {
+            project,
+        });
+        return Ok(outcome);
+    };
+
+    let endpoint = gitlab::api::projects::releases::ProjectReleases::builder()
+        .project(project)
+        .build()
+        .unwrap();
+
+    let mut outcome = ForgeTaskOutcome::default();
+    let gl_releases: Vec<GitlabRelease> =
+        collect_paged(forge, endpoint, Some(page), &mut outcome).await?;
+
+    for gl_release in gl_releases {
+        let (author, pipeline) = {
+            let storage = forge.storage();
+            let author = gl_release.author.as_ref().and_then(|author| {
+                let idx = <L as DiscoverableLookup<User<L>>>::find_in(
+                    storage.deref(),
+                    &forge.instance_index(),
+                    author.id,
+                );
+                if idx.is_none() {
+                    outcome.additional_tasks.push(ForgeTask::UpdateUser {
+                        user: author.id,
+                    });
+                }
+                idx
+            });
+            let pipeline = release_pipeline(
+                storage.deref(),
+                project,
+                &gl_release.tag_name,
+                &gl_release.commit.id,
+            );
+
+            (author, pipeline)
+        };
+
+        let mut release = Release::builder()
+            .project(project_idx.clone())
+            .tag(gl_release.tag_name)
+            .sha(gl_release.commit.id)
+            // Assigned when stored.
+            .unique_id(0)
+            .created_at(gl_release.created_at)
+            .released_at(gl_release.released_at.unwrap_or(gl_release.created_at))
+            .build()
+            .unwrap();
+        release.name = gl_release.name.unwrap_or_default();
+        release.description = gl_release.description.unwrap_or_default();
+        release.author = author;
+        release.pipeline = pipeline;
+        release.assets = gl_release
+            .assets
+            .map(|assets| {
+                assets
+                    .links
+                    .into_iter()
+                    .map(|link| {
+                        let mut asset = ReleaseAsset::builder()
+                            .name(link.name)
+                            .url(link.url)
+                            .build()
+                            .unwrap();
+                        asset.link_type = link.link_type;
+                        asset
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        release.url = gl_release
+            .links
+            .map(|links| links.self_)
+            .unwrap_or_default();
+
+        // Store the release in the storage.
+        store_release(forge, project, release);
+    }
+
+    Ok(outcome)
+}
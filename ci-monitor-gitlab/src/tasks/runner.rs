@@ -8,16 +8,17 @@ use std::ops::Deref;
 
 use chrono::{DateTime, Utc};
 use ci_monitor_core::data::{
-    Instance, Project, Runner, RunnerHost, RunnerProtectionLevel, RunnerType,
+    Deployment, Environment, Instance, Job, MergeRequest, Pipeline, PipelineSchedule, Project,
+    Runner, RunnerHost, RunnerProtectionLevel, RunnerType, User,
 };
 use ci_monitor_core::Lookup;
 use ci_monitor_forge::{ForgeError, ForgeTask, ForgeTaskOutcome};
-use ci_monitor_persistence::DiscoverableLookup;
-use futures_util::stream::TryStreamExt;
+use ci_monitor_persistence::{DiscoverableLookup, EntityType};
 use gitlab::api::AsyncQuery;
 use serde::Deserialize;
 
 use crate::errors;
+use crate::tasks::collect_paged_tasks;
 use crate::GitlabForge;
 
 #[derive(Debug, Deserialize)]
@@ -25,30 +26,30 @@ struct GitlabRunner {
     id: u64,
 }
 
-pub async fn discover_runners<L>(forge: &GitlabForge<L>) -> Result<ForgeTaskOutcome, ForgeError>
+pub async fn discover_runners<L>(
+    forge: &GitlabForge<L>,
+    page: u64,
+) -> Result<ForgeTaskOutcome, ForgeError>
 where
     L: Lookup<Instance>,
     L: Send + Sync,
 {
-    let gl_runners = {
-        let endpoint = gitlab::api::runners::AllRunners::builder().build().unwrap();
-        let endpoint = gitlab::api::paged(endpoint, gitlab::api::Pagination::All);
-        endpoint.into_iter_async::<_, GitlabRunner>(forge.gitlab())
-    };
+    let endpoint = gitlab::api::runners::AllRunners::builder().build().unwrap();
 
     let mut outcome = ForgeTaskOutcome::default();
 
-    let tasks = gl_runners
-        .map_ok(|runner| {
+    collect_paged_tasks(
+        forge,
+        endpoint,
+        Some(page),
+        &mut outcome,
+        |runner: GitlabRunner| {
             ForgeTask::UpdateRunner {
                 id: runner.id,
             }
-        })
-        .map_err(errors::forge_error)
-        .try_collect::<Vec<_>>()
-        .await?;
-
-    outcome.additional_tasks = tasks;
+        },
+    )
+    .await?;
 
     Ok(outcome)
 }
@@ -108,6 +109,7 @@ struct GitlabRunnerDetails {
 
     maintenance_note: Option<String>,
     contacted_at: Option<DateTime<Utc>>,
+    ip_address: Option<String>,
 
     paused: bool,
     is_shared: bool,
@@ -124,8 +126,15 @@ pub async fn update_runner<L>(
 where
     L: DiscoverableLookup<Project<L>>,
     L: DiscoverableLookup<Runner<L>>,
-    L: Lookup<RunnerHost>,
+    L: Lookup<Deployment<L>>,
+    L: Lookup<Environment<L>>,
     L: Lookup<Instance>,
+    L: Lookup<Job<L>>,
+    L: Lookup<MergeRequest<L>>,
+    L: Lookup<Pipeline<L>>,
+    L: Lookup<PipelineSchedule<L>>,
+    L: Lookup<RunnerHost>,
+    L: Lookup<User<L>>,
     L: Send + Sync,
 {
     let gl_runner: GitlabRunnerDetails = {
@@ -162,18 +171,22 @@ where
         runner.online = gl_runner.online.unwrap_or(false);
         runner.locked = gl_runner.locked;
         runner.contacted_at = gl_runner.contacted_at;
+        runner.ip_address = gl_runner.ip_address.filter(|ip| !ip.is_empty());
         runner.maintenance_note = gl_runner.maintenance_note;
 
         runner.cim_refreshed_at = Utc::now();
     };
 
     // Create a runner entry.
-    let runner_entry = if let Some(idx) =
-        <L as DiscoverableLookup<Runner<L>>>::find(forge.storage().deref(), runner)
-    {
+    let runner_entry = if let Some(idx) = <L as DiscoverableLookup<Runner<L>>>::find_in(
+        forge.storage().deref(),
+        &forge.instance_index(),
+        runner,
+    ) {
         if let Some(existing) = <L as Lookup<Runner<L>>>::lookup(forge.storage().deref(), &idx) {
             let mut updated = existing.clone();
             update(&mut updated);
+            forge.log_update(EntityType::Runner, runner, existing, &updated);
             updated
         } else {
             return Err(ForgeError::lookup::<L, Runner<L>>(&idx));
@@ -192,7 +205,7 @@ where
     };
 
     // Store the runner in the storage.
-    forge.storage_mut().store(runner_entry);
+    forge.store(runner_entry);
 
     Ok(outcome)
 }
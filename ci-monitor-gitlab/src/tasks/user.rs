@@ -7,16 +7,77 @@
 use std::ops::Deref;
 
 use chrono::Utc;
-use ci_monitor_core::data::{Instance, User};
+use ci_monitor_core::data::{
+    Deployment, Environment, Instance, Job, MergeRequest, Pipeline, PipelineSchedule, Project,
+    Runner, RunnerHost, User,
+};
 use ci_monitor_core::Lookup;
-use ci_monitor_forge::{ForgeError, ForgeTaskOutcome};
-use ci_monitor_persistence::DiscoverableLookup;
+use ci_monitor_forge::{ForgeError, ForgeTask, ForgeTaskOutcome};
+use ci_monitor_persistence::{DiscoverableLookup, EntityType};
 use gitlab::api::AsyncQuery;
 use serde::Deserialize;
 
 use crate::errors;
+use crate::tasks::collect_paged_tasks;
 use crate::GitlabForge;
 
+#[derive(Debug, Deserialize)]
+struct GitlabUserId {
+    id: u64,
+}
+
+pub async fn discover_users<L>(
+    forge: &GitlabForge<L>,
+    page: u64,
+) -> Result<ForgeTaskOutcome, ForgeError>
+where
+    L: Lookup<Instance>,
+    L: Send + Sync,
+{
+    let endpoint = gitlab::api::users::Users::builder().build().unwrap();
+
+    let mut outcome = ForgeTaskOutcome::default();
+
+    collect_paged_tasks(
+        forge,
+        endpoint,
+        Some(page),
+        &mut outcome,
+        |user: GitlabUserId| {
+            ForgeTask::UpdateUser {
+                user: user.id,
+            }
+        },
+    )
+    .await?;
+
+    Ok(outcome)
+}
+
+pub async fn refresh_users<L>(forge: &GitlabForge<L>) -> Result<ForgeTaskOutcome, ForgeError>
+where
+    L: DiscoverableLookup<User<L>>,
+    L: Lookup<Instance>,
+    L: Send + Sync,
+{
+    let mut outcome = ForgeTaskOutcome::default();
+
+    let storage = forge.storage();
+    let users = <L as DiscoverableLookup<User<L>>>::all_indices(storage.deref());
+    outcome.additional_tasks.extend(
+        users
+            .iter()
+            .filter_map(|idx| <L as Lookup<User<L>>>::lookup(storage.deref(), idx))
+            .map(|user| {
+                ForgeTask::UpdateUser {
+                    user: user.forge_id,
+                }
+            }),
+    );
+
+    Ok(outcome)
+}
+
 #[derive(Debug, Deserialize)]
 struct GitlabUser {
     // Data to fill in the storage.
@@ -35,7 +96,16 @@ pub async fn update_user<L>(
 ) -> Result<ForgeTaskOutcome, ForgeError>
 where
     L: DiscoverableLookup<User<L>>,
+    L: Lookup<Deployment<L>>,
+    L: Lookup<Environment<L>>,
     L: Lookup<Instance>,
+    L: Lookup<Job<L>>,
+    L: Lookup<MergeRequest<L>>,
+    L: Lookup<Pipeline<L>>,
+    L: Lookup<PipelineSchedule<L>>,
+    L: Lookup<Project<L>>,
+    L: Lookup<Runner<L>>,
+    L: Lookup<RunnerHost>,
     L: Send + Sync,
 {
     let gl_user: GitlabUser = {
@@ -54,7 +124,7 @@ where
 
     let update = move |user: &mut User<L>| {
         user.name = gl_user.name;
-        user.handle = gl_user.username;
+        user.rename(gl_user.username);
         user.email = gl_user.email.or(gl_user.public_email);
         //user.avatar = todo!();
 
@@ -62,10 +132,11 @@ where
     };
 
     // Create a user entry.
-    let user_entry = if let Some(idx) = forge.storage().find(user) {
+    let user_entry = if let Some(idx) = forge.storage().find_in(&forge.instance_index(), user) {
         if let Some(existing) = <L as Lookup<User<L>>>::lookup(forge.storage().deref(), &idx) {
             let mut updated = existing.clone();
             update(&mut updated);
+            forge.log_update(EntityType::User, user, existing, &updated);
             updated
         } else {
             return Err(ForgeError::lookup::<L, User<L>>(&idx));
@@ -82,7 +153,7 @@ where
     };
 
     // Store the user in the storage.
-    forge.storage_mut().store(user_entry);
+    forge.store(user_entry);
 
     Ok(outcome)
 }
@@ -98,7 +169,16 @@ pub async fn update_user_by_name<L>(
 ) -> Result<ForgeTaskOutcome, ForgeError>
 where
     L: DiscoverableLookup<User<L>>,
+    L: Lookup<Deployment<L>>,
+    L: Lookup<Environment<L>>,
     L: Lookup<Instance>,
+    L: Lookup<Job<L>>,
+    L: Lookup<MergeRequest<L>>,
+    L: Lookup<Pipeline<L>>,
+    L: Lookup<PipelineSchedule<L>>,
+    L: Lookup<Project<L>>,
+    L: Lookup<Runner<L>>,
+    L: Lookup<RunnerHost>,
     L: Send + Sync,
 {
     let gl_user: GitlabUserSearch = {
@@ -0,0 +1,188 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeSet;
+
+use ci_monitor_forge::ForgeError;
+use gitlab::api::AsyncQuery;
+use gitlab::AsyncGitlab;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::endpoints;
+use crate::errors;
+
+/// Read-only access to the API.
+const READ_API: &str = "read_api";
+/// Full access to the API.
+const API: &str = "api";
+/// Administrator access while admin mode is enabled.
+const ADMIN_MODE: &str = "admin_mode";
+
+/// Scopes which grant write access.
+const WRITE_SCOPES: &[&str] = &[
+    API,
+    "write_repository",
+    "write_registry",
+    "write_virtual_registry",
+    "create_runner",
+    "manage_runner",
+    "sudo",
+];
+
+/// Features which affect the scopes a token requires.
+#[derive(Debug, Clone, Copy, Default)]
+#[non_exhaustive]
+pub struct TokenFeatures {
+    /// Whether all runners and users on the instance are discovered.
+    ///
+    /// This requires an administrator token. When admin mode is enabled on the instance, the
+    /// token also needs the `admin_mode` scope.
+    pub all_runners: bool,
+    /// Whether job artifacts and logs are downloaded.
+    ///
+    /// The `read_api` scope is sufficient for downloads.
+    pub artifacts: bool,
+}
+
+impl TokenFeatures {
+    /// Enable discovery of all runners and users on the instance.
+    pub fn all_runners(mut self, all_runners: bool) -> Self {
+        self.all_runners = all_runners;
+        self
+    }
+
+    /// Enable downloading of artifacts.
+    pub fn artifacts(mut self, artifacts: bool) -> Self {
+        self.artifacts = artifacts;
+        self
+    }
+
+    fn required_scopes(self) -> BTreeSet<String> {
+        let mut scopes = BTreeSet::new();
+        scopes.insert(READ_API.into());
+        if self.all_runners {
+            scopes.insert(ADMIN_MODE.into());
+        }
+        scopes
+    }
+}
+
+/// A comparison of the scopes a token has against the scopes it needs.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct TokenScopeReport {
+    /// The scopes the token has.
+    pub granted: BTreeSet<String>,
+    /// The minimal set of scopes required for the enabled features.
+    pub required: BTreeSet<String>,
+    /// Required scopes which the token does not have.
+    pub missing: BTreeSet<String>,
+    /// Scopes which the token has, but does not need.
+    pub excess: BTreeSet<String>,
+}
+
+impl TokenScopeReport {
+    /// Compare granted scopes against the requirements of a set of features.
+    pub fn new<I, S>(granted: I, features: TokenFeatures) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let granted: BTreeSet<String> = granted.into_iter().map(Into::into).collect();
+        let required = features.required_scopes();
+
+        let missing = required
+            .iter()
+            .filter(|scope| {
+                // The `api` scope is a superset of `read_api`.
+                !(granted.contains(*scope) || (*scope == READ_API && granted.contains(API)))
+            })
+            .cloned()
+            .collect();
+        let excess = granted.difference(&required).cloned().collect();
+
+        Self {
+            granted,
+            required,
+            missing,
+            excess,
+        }
+    }
+
+    /// Whether the token has all of the scopes required.
+    pub fn is_sufficient(&self) -> bool {
+        self.missing.is_empty()
+    }
+
+    /// Whether the token has more scopes than required.
+    pub fn is_over_privileged(&self) -> bool {
+        !self.excess.is_empty()
+    }
+
+    /// Unneeded scopes which grant write access.
+    pub fn excess_write_scopes(&self) -> impl Iterator<Item = &str> {
+        self.excess
+            .iter()
+            .map(String::as_str)
+            .filter(|scope| WRITE_SCOPES.contains(scope))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GitlabTokenSelf {
+    scopes: Vec<String>,
+}
+
+/// Query the scopes of the token used by a GitLab client.
+pub async fn token_scopes(gitlab: &AsyncGitlab) -> Result<Vec<String>, ForgeError> {
+    let token: GitlabTokenSelf = endpoints::PersonalAccessTokenSelf
+        .query_async(gitlab)
+        .await
+        .map_err(errors::forge_error)?;
+
+    Ok(token.scopes)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{TokenFeatures, TokenScopeReport};
+
+    #[test]
+    fn minimal_token() {
+        let report = TokenScopeReport::new(["read_api"], TokenFeatures::default());
+
+        assert!(report.is_sufficient());
+        assert!(!report.is_over_privileged());
+    }
+
+    #[test]
+    fn missing_scopes() {
+        let features = TokenFeatures::default().all_runners(true);
+        let report = TokenScopeReport::new(["read_user"], features);
+
+        assert!(!report.is_sufficient());
+        assert_eq!(
+            report.missing.iter().collect::<Vec<_>>(),
+            ["admin_mode", "read_api"],
+        );
+        assert_eq!(report.excess.iter().collect::<Vec<_>>(), ["read_user"]);
+        assert_eq!(report.excess_write_scopes().count(), 0);
+    }
+
+    #[test]
+    fn over_privileged_token() {
+        let features = TokenFeatures::default().artifacts(true);
+        let report = TokenScopeReport::new(["api", "write_repository"], features);
+
+        assert!(report.is_sufficient());
+        assert!(report.is_over_privileged());
+        assert_eq!(
+            report.excess_write_scopes().collect::<Vec<_>>(),
+            ["api", "write_repository"],
+        );
+    }
+}
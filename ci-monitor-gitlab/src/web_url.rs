@@ -0,0 +1,127 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use ci_monitor_forge::ForgeError;
+use gitlab::api::AsyncQuery;
+use gitlab::AsyncGitlab;
+use serde::Deserialize;
+use url::Url;
+
+use crate::errors;
+
+/// A pipeline identified by its web URL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PipelineUrl {
+    /// The host of the GitLab instance (including any port).
+    pub host: String,
+    /// The path of the project.
+    pub project: String,
+    /// The ID of the pipeline.
+    pub pipeline: u64,
+}
+
+impl PipelineUrl {
+    /// Parse the web URL of a pipeline.
+    ///
+    /// URLs of the form `https://host/group/project/-/pipelines/ID` are supported. Trailing
+    /// components (such as the `builds` tab), queries, and fragments are ignored.
+    pub fn parse(url: &str) -> Option<Self> {
+        let url = Url::parse(url).ok()?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return None;
+        }
+
+        let mut host = url.host_str()?.to_string();
+        if let Some(port) = url.port() {
+            host = format!("{}:{}", host, port);
+        }
+
+        let (project, rest) = url
+            .path()
+            .trim_start_matches('/')
+            .split_once("/-/pipelines/")?;
+        if project.is_empty() {
+            return None;
+        }
+        let pipeline = rest.split('/').next()?.parse().ok()?;
+
+        Some(Self {
+            host,
+            project: project.into(),
+            pipeline,
+        })
+    }
+
+    /// Query the ID of the pipeline's project.
+    pub async fn project_id(&self, gitlab: &AsyncGitlab) -> Result<u64, ForgeError> {
+        let endpoint = gitlab::api::projects::Project::builder()
+            .project(self.project.as_str())
+            .build()
+            .unwrap();
+        let project: GitlabProjectId = endpoint
+            .query_async(gitlab)
+            .await
+            .map_err(errors::forge_error)?;
+
+        Ok(project.id)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GitlabProjectId {
+    id: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::PipelineUrl;
+
+    #[test]
+    fn pipeline_url() {
+        assert_eq!(
+            PipelineUrl::parse("https://gitlab.example.com/group/sub/project/-/pipelines/123"),
+            Some(PipelineUrl {
+                host: "gitlab.example.com".into(),
+                project: "group/sub/project".into(),
+                pipeline: 123,
+            }),
+        );
+    }
+
+    #[test]
+    fn pipeline_url_trailing() {
+        assert_eq!(
+            PipelineUrl::parse("http://localhost:8080/group/project/-/pipelines/5/builds?x=y#jobs"),
+            Some(PipelineUrl {
+                host: "localhost:8080".into(),
+                project: "group/project".into(),
+                pipeline: 5,
+            }),
+        );
+    }
+
+    #[test]
+    fn invalid_pipeline_urls() {
+        assert_eq!(PipelineUrl::parse("not a url"), None);
+        assert_eq!(
+            PipelineUrl::parse("ftp://gitlab.example.com/group/project/-/pipelines/1"),
+            None,
+        );
+        assert_eq!(
+            PipelineUrl::parse("https://gitlab.example.com/-/pipelines/1"),
+            None,
+        );
+        assert_eq!(
+            PipelineUrl::parse("https://gitlab.example.com/group/project/-/jobs/1"),
+            None,
+        );
+        assert_eq!(
+            PipelineUrl::parse("https://gitlab.example.com/group/project/-/pipelines/new"),
+            None,
+        );
+    }
+}
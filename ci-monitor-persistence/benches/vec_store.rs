@@ -0,0 +1,71 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Benchmarks for saving and loading large directory stores.
+
+use chrono::Utc;
+use ci_monitor_core::data::{Instance, Pipeline, PipelineSource, PipelineStatus, Project};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::{VecLookup, VecStore};
+use criterion::{criterion_group, criterion_main, Criterion};
+use tempfile::TempDir;
+
+/// The number of pipelines in the store.
+const PIPELINES: u64 = 10_000;
+
+fn large_store() -> VecLookup {
+    let mut lookup = VecLookup::default();
+    let instance = Instance::builder()
+        .unique_id(0)
+        .forge("forge")
+        .url("url")
+        .build()
+        .unwrap();
+    let instance = lookup.store(instance);
+    let project = Project::builder()
+        .forge_id(1)
+        .instance(instance)
+        .instance_path("group/project")
+        .url("project")
+        .build()
+        .unwrap();
+    let project = lookup.store(project);
+
+    let now = Utc::now();
+    for forge_id in 0..PIPELINES {
+        let pipeline = Pipeline::builder()
+            .project(project)
+            .sha("0000000000000000000000000000000000000000")
+            .source(PipelineSource::Push)
+            .status(PipelineStatus::Success)
+            .forge_id(forge_id)
+            .url("url")
+            .created_at(now)
+            .updated_at(now)
+            .build()
+            .unwrap();
+        lookup.store(pipeline);
+    }
+
+    lookup
+}
+
+fn vec_store(c: &mut Criterion) {
+    let lookup = large_store();
+    let workdir = TempDir::new().unwrap();
+    let path = workdir.path().join("store");
+
+    let mut group = c.benchmark_group("vec_store");
+    group.sample_size(10);
+    group.bench_function("store", |b| {
+        b.iter(|| VecStore::store(&path, &lookup).unwrap())
+    });
+    group.bench_function("load", |b| b.iter(|| VecStore::load(&path).unwrap()));
+    group.finish();
+}
+
+criterion_group!(benches, vec_store);
+criterion_main!(benches);
@@ -8,7 +8,16 @@ use async_trait::async_trait;
 use ci_monitor_core::data::{Blob, BlobReference};
 use thiserror::Error;
 
+#[cfg(feature = "filesystem")]
 pub mod filesystem;
+pub mod tiered;
+pub mod verify;
+
+/// The number of blobs handled at once by bulk operations.
+///
+/// Batches are large enough to spread over many shards while bounding the memory used for their
+/// contents.
+pub(crate) const BATCH_SIZE: usize = 100;
 
 /// Errors when interacting with blob persistence.
 #[derive(Debug, Error)]
@@ -55,7 +64,7 @@ pub enum BlobPersistenceVerifyError {
 }
 
 impl BlobPersistenceVerifyError {
-    fn invalid(actual: BlobReference) -> Self {
+    pub(crate) fn invalid(actual: BlobReference) -> Self {
         Self::Invalid {
             actual,
         }
@@ -70,6 +79,18 @@ pub trait BlobPersistence {
     fn contains(&self, blob: &BlobReference) -> Result<bool, BlobPersistenceError>;
     /// Fetch a blob from storage.
     fn fetch(&self, blob: &BlobReference) -> Result<Blob, BlobPersistenceError>;
+    /// Persist a batch of blobs into storage.
+    ///
+    /// Results are in the same order as the input blobs.
+    fn store_many(&self, blobs: &[Blob]) -> Vec<Result<BlobReference, BlobPersistenceError>> {
+        blobs.iter().map(|blob| self.store(blob)).collect()
+    }
+    /// Fetch a batch of blobs from storage.
+    ///
+    /// Results are in the same order as the input references.
+    fn fetch_many(&self, blobs: &[BlobReference]) -> Vec<Result<Blob, BlobPersistenceError>> {
+        blobs.iter().map(|blob| self.fetch(blob)).collect()
+    }
     /// Verify a blob in the storage.
     fn verify(&self, blob: &BlobReference) -> Result<(), BlobPersistenceVerifyError> {
         let data = self.fetch(blob)?;
@@ -80,6 +101,12 @@ pub trait BlobPersistence {
             Err(BlobPersistenceVerifyError::invalid(new_ref))
         }
     }
+    /// Verify a batch of blobs in the storage.
+    ///
+    /// Results are in the same order as the input references.
+    fn verify_many(&self, blobs: &[BlobReference]) -> Vec<Result<(), BlobPersistenceVerifyError>> {
+        blobs.iter().map(|blob| self.verify(blob)).collect()
+    }
     /// Erase a blob from storage.
     fn erase(&self, blob: BlobReference) -> Result<(), BlobPersistenceError>;
 }
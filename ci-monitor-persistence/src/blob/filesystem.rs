@@ -91,7 +91,7 @@ impl Sharding {
     fn from_slice(slice: &[usize]) -> Result<Self, ShardingError> {
         if slice.len() > MAX_BREAKS {
             Err(ShardingError::TooLong)
-        } else if slice.iter().any(|b| *b == 0) {
+        } else if slice.contains(&0) {
             Err(ShardingError::ZeroBreaks)
         } else {
             let mut breaks: [usize; MAX_BREAKS] = [0; MAX_BREAKS];
@@ -119,6 +119,8 @@ pub struct Filesystem {
     path: PathBuf,
     algo: ContentHash,
     sharding: Sharding,
+    pool: Option<Box<Filesystem>>,
+    parallelism: NonZeroUsize,
 }
 
 const CONFIG_NAME: &str = "cim_persistence.toml";
@@ -257,22 +259,29 @@ impl Filesystem {
         algo: ContentHash,
         sharding: Sharding,
     ) -> Result<Self, FilesystemError> {
+        let store = Self {
+            path,
+            algo,
+            sharding,
+            pool: None,
+            parallelism: NonZeroUsize::MIN,
+        };
+        store.write_config()?;
+
+        Ok(store)
+    }
+
+    fn write_config(&self) -> Result<(), FilesystemError> {
         let conf = FilesystemConfig {
-            algorithm: algo.name().into(),
-            sharding: sharding.to_vec(),
+            algorithm: self.algo.name().into(),
+            sharding: self.sharding.to_vec(),
         };
-        let conf_path = path.join(CONFIG_NAME);
+        let conf_path = self.path.join(CONFIG_NAME);
         let mut file = File::create(&conf_path)
             .map_err(|err| FilesystemError::open(conf_path.clone(), err))?;
         let contents = toml::to_string_pretty(&conf).map_err(FilesystemError::serialize)?;
         file.write_all(contents.as_bytes())
-            .map_err(|err| FilesystemError::write(conf_path, err))?;
-
-        Ok(Self {
-            path,
-            algo,
-            sharding,
-        })
+            .map_err(|err| FilesystemError::write(conf_path, err))
     }
 
     /// Open an existing filesystem store.
@@ -293,15 +302,13 @@ impl Filesystem {
         let conf: FilesystemConfig = toml::from_str(&contents)
             .map_err(|err| FilesystemError::parse(conf_path.clone(), err))?;
 
-        let algo = match conf.algorithm.as_str() {
-            "sha256" => ContentHash::Sha256,
-            "sha512" => ContentHash::Sha512,
-            _ => {
-                return Err(FilesystemError::invalid_content_algorithm(
-                    conf_path,
-                    conf.algorithm,
-                ))
-            },
+        let algo = if let Some(algo) = ContentHash::from_name(&conf.algorithm) {
+            algo
+        } else {
+            return Err(FilesystemError::invalid_content_algorithm(
+                conf_path,
+                conf.algorithm,
+            ));
         };
         let sharding = Sharding::from_slice(&conf.sharding)
             .map_err(|err| FilesystemError::invalid_sharding(conf_path, err))?;
@@ -310,9 +317,47 @@ impl Filesystem {
             path,
             algo,
             sharding,
+            pool: None,
+            parallelism: NonZeroUsize::MIN,
         })
     }
 
+    /// The algorithm used to hash new blobs.
+    pub fn algorithm(&self) -> ContentHash {
+        self.algo
+    }
+
+    /// Change the algorithm used to hash new blobs.
+    ///
+    /// Blobs already in the store remain available under their existing references. The change
+    /// is recorded in the store's configuration.
+    pub fn set_algorithm(&mut self, algo: ContentHash) -> Result<(), FilesystemError> {
+        self.algo = algo;
+        self.write_config()
+    }
+
+    /// Share blob contents with other stores through a pool.
+    ///
+    /// Blobs are written to the pool and hard linked into this store so that stores sharing a
+    /// pool only keep one copy of identical blobs. The pool's link count acts as a reference
+    /// count: erasing a blob removes it from the pool once no store refers to it. Both are done
+    /// under a lock on the pool so that a blob is not removed while another store links it. If a
+    /// hard link cannot be made (e.g., the pool is on a different filesystem), the blob is copied
+    /// instead.
+    pub fn with_shared_pool(mut self, pool: Filesystem) -> Self {
+        self.pool = Some(Box::new(pool));
+        self
+    }
+
+    /// Use multiple threads for batch operations.
+    ///
+    /// Batches are split by the first level of sharding so that each directory is only accessed
+    /// by a single thread. Defaults to a single thread.
+    pub fn with_parallelism(mut self, parallelism: NonZeroUsize) -> Self {
+        self.parallelism = parallelism;
+        self
+    }
+
     fn path_for(&self, blob: &BlobReference) -> PathBuf {
         let shards = self.shard_hash(blob.hash());
         let mut path = self.path.join(blob.algo().name());
@@ -335,11 +380,15 @@ mod tests {
     use std::fs::{self, File};
     use std::io::Write;
     use std::num::NonZeroUsize;
+    use std::thread;
 
-    use ci_monitor_core::data::ContentHash;
+    use ci_monitor_core::data::{Blob, BlobReference, ContentHash};
     use tempfile::TempDir;
 
-    use crate::{Filesystem, FilesystemError, Sharding, ShardingError};
+    use crate::{
+        BlobPersistence, BlobPersistenceError, BlobPersistenceVerifyError, Filesystem,
+        FilesystemError, Sharding, ShardingError,
+    };
 
     use super::{FilesystemConfig, CONFIG_NAME};
 
@@ -541,6 +590,24 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_set_algorithm() {
+        let workdir = tempdir();
+        let mut store =
+            Filesystem::create(workdir.path(), ContentHash::Sha256, Sharding::default()).unwrap();
+        let blob = Blob::new(b"blob contents".to_vec());
+        let old_ref = store.store(&blob).unwrap();
+
+        store.set_algorithm(ContentHash::Blake3).unwrap();
+        let new_ref = store.store(&blob).unwrap();
+        assert_eq!(new_ref, BlobReference::for_blob(&blob, ContentHash::Blake3));
+
+        let store = Filesystem::open(workdir.path()).unwrap();
+        assert_eq!(store.algorithm(), ContentHash::Blake3);
+        assert_eq!(*store.fetch(&old_ref).unwrap(), *blob);
+        assert_eq!(*store.fetch(&new_ref).unwrap(), *blob);
+    }
+
     #[test]
     fn test_invalid_sharding() {
         let workdir = tempdir();
@@ -563,4 +630,136 @@ mod tests {
             panic!("unexpected error: {:?}", err);
         }
     }
+
+    #[test]
+    fn test_shared_pool() {
+        let workdir = tempdir();
+        let create = |name: &str| {
+            let path = workdir.path().join(name);
+            fs::create_dir(&path).unwrap();
+            Filesystem::create(path, ContentHash::Sha256, Sharding::default()).unwrap()
+        };
+        let pool = || Filesystem::open(workdir.path().join("pool")).unwrap();
+        let pool_check = create("pool");
+        let first = create("first").with_shared_pool(pool());
+        let second = create("second").with_shared_pool(pool());
+
+        let blob = Blob::new(b"shared log contents".to_vec());
+        let blob_ref = first.store(&blob).unwrap();
+        assert_eq!(second.store(&blob).unwrap(), blob_ref);
+        // Storing again is a no-op.
+        assert_eq!(second.store(&blob).unwrap(), blob_ref);
+
+        assert!(pool_check.contains(&blob_ref).unwrap());
+        assert_eq!(*second.fetch(&blob_ref).unwrap(), *blob);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+
+            let nlink = fs::metadata(first.path_for(&blob_ref)).unwrap().nlink();
+            assert_eq!(nlink, 3);
+        }
+
+        first.erase(blob_ref.clone()).unwrap();
+        assert!(!first.contains(&blob_ref).unwrap());
+        assert!(pool_check.contains(&blob_ref).unwrap());
+        assert!(second.contains(&blob_ref).unwrap());
+
+        second.erase(blob_ref.clone()).unwrap();
+        #[cfg(unix)]
+        assert!(!pool_check.contains(&blob_ref).unwrap());
+    }
+
+    #[test]
+    fn test_shared_pool_concurrent() {
+        let workdir = tempdir();
+        let create = |name: &str| {
+            let path = workdir.path().join(name);
+            fs::create_dir(&path).unwrap();
+            Filesystem::create(path, ContentHash::Sha256, Sharding::default())
+                .unwrap()
+                .with_parallelism(NonZeroUsize::new(4).unwrap())
+        };
+        let pool = || Filesystem::open(workdir.path().join("pool")).unwrap();
+        let pool_check = create("pool");
+        let stores = (0..4)
+            .map(|i| create(&format!("store{}", i)).with_shared_pool(pool()))
+            .collect::<Vec<_>>();
+
+        let blobs = (0..20)
+            .map(|i| Blob::new(format!("shared blob {}", i).repeat(1000).into_bytes()))
+            .collect::<Vec<_>>();
+        // Stores racing to add the same blobs to the pool must not clobber each other.
+        thread::scope(|scope| {
+            for store in &stores {
+                let blobs = &blobs;
+                scope.spawn(move || {
+                    for result in store.store_many(blobs) {
+                        result.unwrap();
+                    }
+                });
+            }
+        });
+
+        for blob in &blobs {
+            let blob_ref = BlobReference::for_blob(blob, ContentHash::Sha256);
+            assert_eq!(*pool_check.fetch(&blob_ref).unwrap(), **blob);
+            for store in &stores {
+                assert_eq!(*store.fetch(&blob_ref).unwrap(), **blob);
+            }
+        }
+    }
+
+    #[test]
+    fn test_store_fetch_many() {
+        let workdir = tempdir();
+        let store = Filesystem::create(workdir.path(), ContentHash::Sha256, Sharding::default())
+            .unwrap()
+            .with_parallelism(NonZeroUsize::new(4).unwrap());
+
+        let blobs = (0..20)
+            .map(|i| Blob::new(format!("blob {}", i).into_bytes()))
+            .collect::<Vec<_>>();
+        let refs = store
+            .store_many(&blobs)
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(refs.len(), blobs.len());
+        for (blob, blob_ref) in blobs.iter().zip(&refs) {
+            assert_eq!(
+                *blob_ref,
+                BlobReference::for_blob(blob, ContentHash::Sha256)
+            );
+        }
+
+        let missing = BlobReference::for_blob(&Blob::new(b"missing".to_vec()), ContentHash::Sha256);
+        let mut fetch = refs.clone();
+        fetch.insert(3, missing);
+        let fetched = store.fetch_many(&fetch);
+        assert_eq!(fetched.len(), blobs.len() + 1);
+        assert!(matches!(fetched[3], Err(BlobPersistenceError::NotFound)));
+        let fetched = fetched
+            .into_iter()
+            .filter_map(Result::ok)
+            .map(|blob| blob.to_vec())
+            .collect::<Vec<_>>();
+        let expected = blobs.iter().map(|blob| blob.to_vec()).collect::<Vec<_>>();
+        assert_eq!(fetched, expected);
+
+        let verified = store.verify_many(&fetch);
+        assert_eq!(verified.len(), blobs.len() + 1);
+        assert!(matches!(
+            verified[3],
+            Err(BlobPersistenceVerifyError::Inner {
+                source: BlobPersistenceError::NotFound,
+            }),
+        ));
+        assert_eq!(
+            verified.iter().filter(|res| res.is_ok()).count(),
+            blobs.len()
+        );
+
+        assert!(store.fetch_many(&[]).is_empty());
+    }
 }
@@ -4,14 +4,19 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::fs::{self, File};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
 use std::io::{self, Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
 
 use ci_monitor_core::data::{Blob, BlobReference};
 use thiserror::Error;
 
-use crate::{BlobPersistence, BlobPersistenceError, Filesystem};
+use crate::{BlobPersistence, BlobPersistenceError, BlobPersistenceVerifyError, Filesystem};
 
 #[derive(Debug, Error)]
 enum FilesystemError {
@@ -117,21 +122,179 @@ impl From<FilesystemError> for BlobPersistenceError {
     }
 }
 
-impl BlobPersistence for Filesystem {
-    fn store(&self, blob: &Blob) -> Result<BlobReference, BlobPersistenceError> {
-        let new_ref = BlobReference::for_blob(blob, self.algo);
-        let path = self.path_for(&new_ref);
+/// Write a blob to a path.
+///
+/// The blob is written to a temporary file next to the path and renamed into place so that
+/// concurrent writers of the same blob never truncate a file which is being read or which is
+/// linked from elsewhere.
+fn write_blob(path: &Path, blob: &Blob) -> Result<(), FilesystemError> {
+    static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let parent = path
+        .parent()
+        .ok_or_else(|| FilesystemError::no_parent(path.into()))?;
+    fs::create_dir_all(parent).map_err(|err| FilesystemError::cannot_create(parent.into(), err))?;
+
+    let temp_path = parent.join(format!(
+        ".tmp-{}-{}",
+        process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed),
+    ));
+    let written = File::create(&temp_path)
+        .map_err(|err| FilesystemError::open(temp_path.clone(), err))
+        .and_then(|mut file| {
+            file.write_all(blob)
+                .map_err(|err| FilesystemError::write(temp_path.clone(), err))
+        })
+        .and_then(|()| {
+            fs::rename(&temp_path, path).map_err(|err| FilesystemError::write(path.into(), err))
+        });
+    if written.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    written
+}
+
+/// The name of the lock file of a shared pool.
+const POOL_LOCK_NAME: &str = "cim_pool.lock";
+
+impl Filesystem {
+    /// Lock a shared pool.
+    ///
+    /// The lock is held while a blob is linked from the pool and while the pool's copy of a blob
+    /// is released so that a copy is never removed while it is being linked. It is released when
+    /// the returned file is dropped.
+    fn lock_pool(&self) -> Result<File, FilesystemError> {
+        let lock_path = self.path.join(POOL_LOCK_NAME);
+        let lock = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&lock_path)
+            .map_err(|err| FilesystemError::open(lock_path.clone(), err))?;
+        lock.lock()
+            .map_err(|err| FilesystemError::open(lock_path, err))?;
+        Ok(lock)
+    }
+
+    /// Link a blob from the shared pool into the store.
+    ///
+    /// Returns `false` if the blob could not be linked.
+    fn link_from_pool(
+        pool: &Self,
+        blob: &Blob,
+        blob_ref: &BlobReference,
+        path: &Path,
+    ) -> Result<bool, FilesystemError> {
+        let _lock = pool.lock_pool()?;
+        let pool_path = pool.path_for(blob_ref);
+        if !pool_path.exists() {
+            write_blob(&pool_path, blob)?;
+        }
+
         let parent = path
             .parent()
-            .ok_or_else(|| FilesystemError::no_parent(path.clone()))?;
-        if let Err(err) = fs::create_dir_all(parent) {
-            return Err(FilesystemError::cannot_create(parent.into(), err).into());
+            .ok_or_else(|| FilesystemError::no_parent(path.into()))?;
+        fs::create_dir_all(parent)
+            .map_err(|err| FilesystemError::cannot_create(parent.into(), err))?;
+        match fs::hard_link(&pool_path, path) {
+            Ok(()) => Ok(true),
+            // Another writer stored the blob first.
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => Ok(true),
+            Err(_) => Ok(false),
+        }
+    }
+
+    /// Drop the pool's copy of a blob if no store refers to it anymore.
+    #[cfg(unix)]
+    fn release_from_pool(pool: &Self, blob: &BlobReference) -> Result<(), FilesystemError> {
+        use std::os::unix::fs::MetadataExt;
+
+        let _lock = pool.lock_pool()?;
+        let pool_path = pool.path_for(blob);
+        let is_unreferenced = fs::metadata(&pool_path).is_ok_and(|metadata| metadata.nlink() == 1);
+        if is_unreferenced {
+            fs::remove_file(&pool_path).map_err(|err| FilesystemError::delete(pool_path, err))?;
         }
-        let mut file =
-            File::create(&path).map_err(|err| FilesystemError::open(path.clone(), err))?;
-        file.write_all(blob)
-            .map_err(|err| FilesystemError::write(path, err))?;
-        Ok(new_ref)
+        Ok(())
+    }
+
+    /// Link counts are not available; the pool's copy is kept.
+    #[cfg(not(unix))]
+    fn release_from_pool(_: &Self, _: &BlobReference) -> Result<(), FilesystemError> {
+        Ok(())
+    }
+
+    fn store_as(
+        &self,
+        blob: &Blob,
+        blob_ref: BlobReference,
+    ) -> Result<BlobReference, BlobPersistenceError> {
+        let path = self.path_for(&blob_ref);
+        if let Some(pool) = self.pool.as_ref() {
+            // The file may be shared with other stores; its contents are already correct.
+            if path.exists() || Self::link_from_pool(pool, blob, &blob_ref, &path)? {
+                return Ok(blob_ref);
+            }
+        }
+        write_blob(&path, blob)?;
+        Ok(blob_ref)
+    }
+
+    /// Perform an operation on a batch of blobs in parallel.
+    ///
+    /// Blobs are grouped by their top-level shard and each group is handled by a single thread.
+    /// Results are in the same order as the input.
+    fn run_sharded<T, R, F>(&self, items: &[T], refs: &[BlobReference], op: F) -> Vec<R>
+    where
+        T: Sync,
+        R: Send,
+        F: Fn(&T, &BlobReference) -> R + Sync,
+    {
+        let mut groups: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        for (idx, blob_ref) in refs.iter().enumerate() {
+            let shard = self.shard_hash(blob_ref.hash())[0];
+            groups.entry(shard).or_default().push(idx);
+        }
+        let groups = groups.into_values().collect::<Vec<_>>();
+
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new(Vec::with_capacity(items.len()));
+        thread::scope(|scope| {
+            for _ in 0..self.parallelism.get().min(groups.len()) {
+                scope.spawn(|| {
+                    loop {
+                        let idx = next.fetch_add(1, Ordering::Relaxed);
+                        let group = if let Some(group) = groups.get(idx) {
+                            group
+                        } else {
+                            break;
+                        };
+
+                        let group_results = group
+                            .iter()
+                            .map(|&idx| (idx, op(&items[idx], &refs[idx])))
+                            .collect::<Vec<_>>();
+                        results
+                            .lock()
+                            .expect("result list poisoned")
+                            .extend(group_results);
+                    }
+                });
+            }
+        });
+
+        let mut results = results.into_inner().expect("result list poisoned");
+        results.sort_by_key(|(idx, _)| *idx);
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+}
+
+impl BlobPersistence for Filesystem {
+    fn store(&self, blob: &Blob) -> Result<BlobReference, BlobPersistenceError> {
+        let new_ref = BlobReference::for_blob(blob, self.algo);
+        self.store_as(blob, new_ref)
     }
 
     fn contains(&self, blob: &BlobReference) -> Result<bool, BlobPersistenceError> {
@@ -148,9 +311,30 @@ impl BlobPersistence for Filesystem {
         Ok(Blob::new(contents))
     }
 
+    fn store_many(&self, blobs: &[Blob]) -> Vec<Result<BlobReference, BlobPersistenceError>> {
+        let refs = blobs
+            .iter()
+            .map(|blob| BlobReference::for_blob(blob, self.algo))
+            .collect::<Vec<_>>();
+        self.run_sharded(blobs, &refs, |blob, blob_ref| {
+            self.store_as(blob, blob_ref.clone())
+        })
+    }
+
+    fn fetch_many(&self, blobs: &[BlobReference]) -> Vec<Result<Blob, BlobPersistenceError>> {
+        self.run_sharded(blobs, blobs, |blob, _| self.fetch(blob))
+    }
+
+    fn verify_many(&self, blobs: &[BlobReference]) -> Vec<Result<(), BlobPersistenceVerifyError>> {
+        self.run_sharded(blobs, blobs, |blob, _| self.verify(blob))
+    }
+
     fn erase(&self, blob: BlobReference) -> Result<(), BlobPersistenceError> {
         let path = self.path_for(&blob);
         fs::remove_file(&path).map_err(|err| FilesystemError::delete(path, err))?;
+        if let Some(pool) = self.pool.as_ref() {
+            Self::release_from_pool(pool, &blob)?;
+        }
         Ok(())
     }
 }
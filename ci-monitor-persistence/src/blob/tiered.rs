@@ -0,0 +1,363 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Blob storage split into hot and cold tiers.
+
+use std::slice;
+
+use ci_monitor_core::data::{Blob, BlobReference};
+
+use crate::{BlobPersistence, BlobPersistenceError, BlobPersistenceVerifyError};
+
+/// Blob storage with a fast "hot" tier and an optional slower "cold" tier.
+///
+/// New blobs are always stored in the hot tier. Blobs are moved to the cold tier explicitly using
+/// [`Tiered::demote`]. Fetches look in the hot tier first and fall through to the cold tier so
+/// that callers do not need to know where a blob lives. Since blob references are based on their
+/// contents, a blob's reference does not change when it moves between tiers.
+#[derive(Debug)]
+pub struct Tiered<H, C> {
+    hot: H,
+    cold: Option<C>,
+}
+
+impl<H, C> Tiered<H, C>
+where
+    H: BlobPersistence,
+    C: BlobPersistence,
+{
+    /// Create tiered storage without a cold tier.
+    pub fn new(hot: H) -> Self {
+        Self {
+            hot,
+            cold: None,
+        }
+    }
+
+    /// Move demoted blobs to a cold tier.
+    pub fn with_cold_tier(mut self, cold: C) -> Self {
+        self.cold = Some(cold);
+        self
+    }
+
+    /// The hot tier.
+    pub fn hot(&self) -> &H {
+        &self.hot
+    }
+
+    /// The hot tier.
+    pub fn hot_mut(&mut self) -> &mut H {
+        &mut self.hot
+    }
+
+    /// The cold tier (if any).
+    pub fn cold(&self) -> Option<&C> {
+        self.cold.as_ref()
+    }
+
+    /// Move a blob from the hot tier to the cold tier.
+    ///
+    /// The blob is verified before it is stored into the cold tier and is only erased from the
+    /// hot tier once the cold tier has a copy under the same reference. Blobs which are already
+    /// only in the cold tier are left alone.
+    pub fn demote(&self, blob: &BlobReference) -> Result<(), BlobPersistenceVerifyError> {
+        self.demote_many(slice::from_ref(blob))
+            .pop()
+            .expect("one result per blob")
+    }
+
+    /// Move a batch of blobs from the hot tier to the cold tier.
+    ///
+    /// Blobs are fetched and stored using the batch operations of the tiers. See
+    /// [`Tiered::demote`] for how each blob is handled. Results are in the same order as the input
+    /// references.
+    pub fn demote_many(
+        &self,
+        blobs: &[BlobReference],
+    ) -> Vec<Result<(), BlobPersistenceVerifyError>> {
+        let cold = if let Some(cold) = self.cold.as_ref() {
+            cold
+        } else {
+            return blobs
+                .iter()
+                .map(|_| {
+                    Err(BlobPersistenceError::Other {
+                        details: "no cold tier is configured".into(),
+                    }
+                    .into())
+                })
+                .collect();
+        };
+
+        let mut results = Vec::with_capacity(blobs.len());
+        let mut to_store = Vec::new();
+        let mut to_store_idx = Vec::new();
+        for (idx, (blob, data)) in blobs.iter().zip(self.hot.fetch_many(blobs)).enumerate() {
+            let res = match data {
+                Ok(data) => {
+                    let actual = BlobReference::for_blob(&data, blob.algo());
+                    if actual == *blob {
+                        to_store.push(data);
+                        to_store_idx.push(idx);
+                        Ok(())
+                    } else {
+                        Err(BlobPersistenceVerifyError::invalid(actual))
+                    }
+                },
+                Err(BlobPersistenceError::NotFound) => {
+                    match cold.contains(blob) {
+                        Ok(true) => Ok(()),
+                        Ok(false) => Err(BlobPersistenceError::NotFound.into()),
+                        Err(err) => Err(err.into()),
+                    }
+                },
+                Err(err) => Err(err.into()),
+            };
+            results.push(res);
+        }
+
+        // The cold tier must be able to find the blobs under their existing references.
+        for (idx, cold_ref) in to_store_idx.into_iter().zip(cold.store_many(&to_store)) {
+            let blob = &blobs[idx];
+            results[idx] = match cold_ref {
+                Ok(cold_ref) if cold_ref != *blob => {
+                    Err(BlobPersistenceVerifyError::invalid(cold_ref))
+                },
+                Ok(_) => self.hot.erase(blob.clone()).map_err(Into::into),
+                Err(err) => Err(err.into()),
+            };
+        }
+
+        results
+    }
+}
+
+/// Treat a missing blob as successfully erased.
+fn ignore_missing(res: Result<(), BlobPersistenceError>) -> Result<(), BlobPersistenceError> {
+    match res {
+        Err(BlobPersistenceError::NotFound) => Ok(()),
+        res => res,
+    }
+}
+
+impl<H, C> BlobPersistence for Tiered<H, C>
+where
+    H: BlobPersistence,
+    C: BlobPersistence,
+{
+    fn store(&self, blob: &Blob) -> Result<BlobReference, BlobPersistenceError> {
+        self.hot.store(blob)
+    }
+
+    fn contains(&self, blob: &BlobReference) -> Result<bool, BlobPersistenceError> {
+        if self.hot.contains(blob)? {
+            return Ok(true);
+        }
+        if let Some(cold) = self.cold.as_ref() {
+            cold.contains(blob)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn fetch(&self, blob: &BlobReference) -> Result<Blob, BlobPersistenceError> {
+        match (self.hot.fetch(blob), self.cold.as_ref()) {
+            (Err(BlobPersistenceError::NotFound), Some(cold)) => cold.fetch(blob),
+            (res, _) => res,
+        }
+    }
+
+    fn store_many(&self, blobs: &[Blob]) -> Vec<Result<BlobReference, BlobPersistenceError>> {
+        self.hot.store_many(blobs)
+    }
+
+    fn fetch_many(&self, blobs: &[BlobReference]) -> Vec<Result<Blob, BlobPersistenceError>> {
+        let mut fetched = self.hot.fetch_many(blobs);
+        if let Some(cold) = self.cold.as_ref() {
+            for (res, blob) in fetched.iter_mut().zip(blobs) {
+                if let Err(BlobPersistenceError::NotFound) = res {
+                    *res = cold.fetch(blob);
+                }
+            }
+        }
+        fetched
+    }
+
+    fn verify_many(&self, blobs: &[BlobReference]) -> Vec<Result<(), BlobPersistenceVerifyError>> {
+        let mut verified = self.hot.verify_many(blobs);
+        if let Some(cold) = self.cold.as_ref() {
+            let missing = verified
+                .iter()
+                .enumerate()
+                .filter_map(|(idx, res)| {
+                    if let Err(BlobPersistenceVerifyError::Inner {
+                        source: BlobPersistenceError::NotFound,
+                    }) = res
+                    {
+                        Some(idx)
+                    } else {
+                        None
+                    }
+                })
+                .collect::<Vec<_>>();
+            let missing_refs = missing
+                .iter()
+                .map(|&idx| blobs[idx].clone())
+                .collect::<Vec<_>>();
+            for (idx, res) in missing.into_iter().zip(cold.verify_many(&missing_refs)) {
+                verified[idx] = res;
+            }
+        }
+        verified
+    }
+
+    fn erase(&self, blob: BlobReference) -> Result<(), BlobPersistenceError> {
+        let cold = if let Some(cold) = self.cold.as_ref() {
+            cold
+        } else {
+            return self.hot.erase(blob);
+        };
+
+        let in_hot = self.hot.contains(&blob)?;
+        let in_cold = cold.contains(&blob)?;
+        if !in_hot && !in_cold {
+            return Err(BlobPersistenceError::NotFound);
+        }
+
+        ignore_missing(self.hot.erase(blob.clone()))?;
+        ignore_missing(cold.erase(blob))
+    }
+}
+
+#[cfg(all(test, feature = "filesystem"))]
+mod tests {
+    use std::{fs, slice};
+
+    use ci_monitor_core::data::{Blob, BlobReference, ContentHash};
+    use tempfile::TempDir;
+
+    use crate::{
+        BlobPersistence, BlobPersistenceError, BlobPersistenceVerifyError, Filesystem, Sharding,
+        Tiered,
+    };
+
+    fn tiers(workdir: &TempDir, cold_algo: ContentHash) -> Tiered<Filesystem, Filesystem> {
+        let create = |name: &str, algo| {
+            let path = workdir.path().join(name);
+            fs::create_dir(&path).unwrap();
+            Filesystem::create(path, algo, Sharding::default()).unwrap()
+        };
+
+        Tiered::new(create("hot", ContentHash::Sha256)).with_cold_tier(create("cold", cold_algo))
+    }
+
+    #[test]
+    fn test_demote() {
+        let workdir = TempDir::new().unwrap();
+        let tiers = tiers(&workdir, ContentHash::Sha256);
+
+        let blob = Blob::new(b"old job log".to_vec());
+        let blob_ref = tiers.store(&blob).unwrap();
+        assert!(tiers.hot().contains(&blob_ref).unwrap());
+        assert!(!tiers.cold().unwrap().contains(&blob_ref).unwrap());
+
+        tiers.demote(&blob_ref).unwrap();
+        assert!(!tiers.hot().contains(&blob_ref).unwrap());
+        assert!(tiers.cold().unwrap().contains(&blob_ref).unwrap());
+        // Demoting again is a no-op.
+        tiers.demote(&blob_ref).unwrap();
+
+        // Fetches fall through to the cold tier.
+        assert!(tiers.contains(&blob_ref).unwrap());
+        assert_eq!(*tiers.fetch(&blob_ref).unwrap(), *blob);
+        let fetched = tiers.fetch_many(slice::from_ref(&blob_ref));
+        assert_eq!(**fetched[0].as_ref().unwrap(), *blob);
+        tiers.verify(&blob_ref).unwrap();
+        let verified = tiers.verify_many(slice::from_ref(&blob_ref));
+        assert!(verified[0].is_ok());
+
+        tiers.erase(blob_ref.clone()).unwrap();
+        assert!(!tiers.contains(&blob_ref).unwrap());
+        assert!(matches!(
+            tiers.fetch(&blob_ref),
+            Err(BlobPersistenceError::NotFound),
+        ));
+        assert!(matches!(
+            tiers.erase(blob_ref),
+            Err(BlobPersistenceError::NotFound),
+        ));
+    }
+
+    #[test]
+    fn test_demote_many() {
+        let workdir = TempDir::new().unwrap();
+        let tiers = tiers(&workdir, ContentHash::Sha256);
+
+        let blobs = (0..3)
+            .map(|idx| tiers.store(&Blob::new(format!("job log {}", idx).into_bytes())))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        tiers.demote(&blobs[1]).unwrap();
+        let missing = BlobReference::for_blob(&Blob::new(b"missing".to_vec()), ContentHash::Sha256);
+        let mut refs = blobs.clone();
+        refs.push(missing);
+
+        let results = tiers.demote_many(&refs);
+        assert_eq!(results.len(), 4);
+        for (blob_ref, res) in blobs.iter().zip(&results) {
+            assert!(res.is_ok());
+            assert!(!tiers.hot().contains(blob_ref).unwrap());
+            assert!(tiers.cold().unwrap().contains(blob_ref).unwrap());
+        }
+        assert!(matches!(
+            results[3],
+            Err(BlobPersistenceVerifyError::Inner {
+                source: BlobPersistenceError::NotFound,
+            }),
+        ));
+    }
+
+    #[test]
+    fn test_demote_missing() {
+        let workdir = TempDir::new().unwrap();
+        let tiers = tiers(&workdir, ContentHash::Sha256);
+
+        let missing = BlobReference::for_blob(&Blob::new(b"missing".to_vec()), ContentHash::Sha256);
+        let err = tiers.demote(&missing).unwrap_err();
+        assert!(matches!(
+            err,
+            BlobPersistenceVerifyError::Inner {
+                source: BlobPersistenceError::NotFound,
+            },
+        ));
+    }
+
+    #[test]
+    fn test_demote_algorithm_mismatch() {
+        let workdir = TempDir::new().unwrap();
+        let tiers = tiers(&workdir, ContentHash::Blake3);
+
+        let blob = Blob::new(b"old job log".to_vec());
+        let blob_ref = tiers.store(&blob).unwrap();
+        let err = tiers.demote(&blob_ref).unwrap_err();
+        assert!(matches!(err, BlobPersistenceVerifyError::Invalid { .. }));
+        // The blob is kept in the hot tier.
+        assert!(tiers.hot().contains(&blob_ref).unwrap());
+    }
+
+    #[test]
+    fn test_no_cold_tier() {
+        let workdir = TempDir::new().unwrap();
+        let hot =
+            Filesystem::create(workdir.path(), ContentHash::Sha256, Sharding::default()).unwrap();
+        let tiers: Tiered<_, Filesystem> = Tiered::new(hot);
+
+        let blob = Blob::new(b"recent job log".to_vec());
+        let blob_ref = tiers.store(&blob).unwrap();
+        assert!(tiers.demote(&blob_ref).is_err());
+        assert_eq!(*tiers.fetch(&blob_ref).unwrap(), *blob);
+    }
+}
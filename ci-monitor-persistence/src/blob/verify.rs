@@ -0,0 +1,193 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Bulk verification of blob storage.
+
+use ci_monitor_core::data::BlobReference;
+
+use crate::blob::BATCH_SIZE;
+use crate::{BlobPersistence, BlobPersistenceError, BlobPersistenceVerifyError};
+
+/// A blob which failed verification.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct BlobVerifyFailure {
+    /// The blob which failed verification.
+    pub blob: BlobReference,
+    /// Why verification failed.
+    pub error: BlobPersistenceVerifyError,
+}
+
+impl BlobVerifyFailure {
+    /// Whether the blob is missing from the store.
+    pub fn is_missing(&self) -> bool {
+        matches!(
+            self.error,
+            BlobPersistenceVerifyError::Inner {
+                source: BlobPersistenceError::NotFound,
+            },
+        )
+    }
+
+    /// Whether the blob's contents do not match its hash.
+    pub fn is_corrupt(&self) -> bool {
+        matches!(self.error, BlobPersistenceVerifyError::Invalid { .. })
+    }
+}
+
+/// Verify a set of blobs.
+///
+/// Blobs are verified in batches using [`BlobPersistence::verify_many`] so that stores may verify
+/// them in parallel. The `progress` callback is called with the number of blobs checked so far
+/// after each batch. Failures are returned in the same order as the input blobs.
+pub fn verify_blobs<B, F>(
+    store: &B,
+    blobs: &[BlobReference],
+    mut progress: F,
+) -> Vec<BlobVerifyFailure>
+where
+    B: BlobPersistence,
+    F: FnMut(usize),
+{
+    let mut failures = Vec::new();
+    let mut done = 0;
+
+    for batch in blobs.chunks(BATCH_SIZE) {
+        let results = store.verify_many(batch);
+        failures.extend(batch.iter().zip(results).filter_map(|(blob, res)| {
+            res.err().map(|error| {
+                BlobVerifyFailure {
+                    blob: blob.clone(),
+                    error,
+                }
+            })
+        }));
+        done += batch.len();
+        progress(done);
+    }
+
+    failures
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    use ci_monitor_core::data::{Blob, BlobReference, ContentHash};
+
+    use crate::{BlobPersistence, BlobPersistenceError};
+
+    #[derive(Default)]
+    struct MemoryStore {
+        blobs: Mutex<HashMap<BlobReference, Blob>>,
+    }
+
+    impl MemoryStore {
+        fn insert(&mut self, blob_ref: BlobReference, blob: Blob) {
+            self.blobs.get_mut().unwrap().insert(blob_ref, blob);
+        }
+    }
+
+    impl BlobPersistence for MemoryStore {
+        fn store(&self, blob: &Blob) -> Result<BlobReference, BlobPersistenceError> {
+            let blob_ref = BlobReference::for_blob(blob, ContentHash::Sha256);
+            self.blobs
+                .lock()
+                .unwrap()
+                .insert(blob_ref.clone(), blob.clone());
+            Ok(blob_ref)
+        }
+
+        fn contains(&self, blob: &BlobReference) -> Result<bool, BlobPersistenceError> {
+            Ok(self.blobs.lock().unwrap().contains_key(blob))
+        }
+
+        fn fetch(&self, blob: &BlobReference) -> Result<Blob, BlobPersistenceError> {
+            self.blobs
+                .lock()
+                .unwrap()
+                .get(blob)
+                .cloned()
+                .ok_or(BlobPersistenceError::NotFound)
+        }
+
+        fn erase(&self, blob: BlobReference) -> Result<(), BlobPersistenceError> {
+            self.blobs
+                .lock()
+                .unwrap()
+                .remove(&blob)
+                .map(|_| ())
+                .ok_or(BlobPersistenceError::NotFound)
+        }
+    }
+
+    fn reference(data: &[u8]) -> BlobReference {
+        BlobReference::for_blob(&Blob::new(data.into()), ContentHash::Sha256)
+    }
+
+    #[test]
+    fn verify_blobs() {
+        let mut store = MemoryStore::default();
+        let good = reference(b"good");
+        store.insert(good.clone(), Blob::new(b"good".to_vec()));
+        let corrupt = reference(b"corrupt");
+        store.insert(corrupt.clone(), Blob::new(b"tampered".to_vec()));
+        let missing = reference(b"missing");
+
+        let blobs = [good, missing.clone(), corrupt.clone()];
+        let mut checked = Vec::new();
+        let failures = super::verify_blobs(&store, &blobs, |n| checked.push(n));
+
+        assert_eq!(checked, [3]);
+        assert_eq!(failures.len(), 2);
+        assert_eq!(failures[0].blob, missing);
+        assert!(failures[0].is_missing());
+        assert_eq!(failures[1].blob, corrupt);
+        assert!(failures[1].is_corrupt());
+    }
+
+    #[test]
+    fn verify_blobs_batches() {
+        let mut store = MemoryStore::default();
+        let blobs = (0..250)
+            .map(|idx| {
+                let data = format!("blob {}", idx).into_bytes();
+                let blob_ref = reference(&data);
+                store.insert(blob_ref.clone(), Blob::new(data));
+                blob_ref
+            })
+            .collect::<Vec<_>>();
+
+        let mut checked = Vec::new();
+        let failures = super::verify_blobs(&store, &blobs, |n| checked.push(n));
+
+        assert_eq!(checked, [100, 200, 250]);
+        assert!(failures.is_empty());
+    }
+
+    #[test]
+    fn verify_blobs_erased() {
+        let store = MemoryStore::default();
+        let kept = store.store(&Blob::new(b"kept".to_vec())).unwrap();
+        let erased = store.store(&Blob::new(b"erased".to_vec())).unwrap();
+        store.erase(erased.clone()).unwrap();
+
+        let failures = super::verify_blobs(&store, &[kept, erased.clone()], |_| ());
+
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].blob, erased);
+        assert!(failures[0].is_missing());
+    }
+
+    #[test]
+    fn verify_blobs_empty() {
+        let store = MemoryStore::default();
+        let failures = super::verify_blobs(&store, &[], |_| panic!("no blobs to check"));
+
+        assert!(failures.is_empty());
+    }
+}
@@ -4,6 +4,7 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+use ci_monitor_core::data::Instance;
 use ci_monitor_core::Lookup;
 
 /// A `Lookup` that can also list what it contains.
@@ -11,5 +12,17 @@ pub trait DiscoverableLookup<T>: Lookup<T> {
     /// Return all indices.
     fn all_indices(&self) -> Vec<Self::Index>;
     /// Find an object by its ID.
+    ///
+    /// Forge IDs are only unique within an instance; use `find_in` for objects from a forge.
     fn find(&self, id: u64) -> Option<Self::Index>;
+    /// Find an object by its ID on an instance.
+    ///
+    /// Objects which do not belong to an instance are found by their ID alone.
+    fn find_in(
+        &self,
+        instance: &<Self as Lookup<Instance>>::Index,
+        id: u64,
+    ) -> Option<<Self as Lookup<T>>::Index>
+    where
+        Self: Lookup<Instance>;
 }
@@ -15,23 +15,76 @@ mod discoverable;
 mod migrate;
 mod objects;
 
+#[cfg(test)]
+mod test;
+
 pub use self::blob::BlobPersistence;
 pub use self::blob::BlobPersistenceAsync;
 pub use self::blob::BlobPersistenceError;
 pub use self::blob::BlobPersistenceVerifyError;
 
+pub use self::blob::tiered::Tiered;
+
+pub use self::blob::verify::verify_blobs;
+pub use self::blob::verify::BlobVerifyFailure;
+
+#[cfg(feature = "filesystem")]
 pub use self::blob::filesystem::Filesystem;
+#[cfg(feature = "filesystem")]
 pub use self::blob::filesystem::FilesystemError;
+#[cfg(feature = "filesystem")]
 pub use self::blob::filesystem::Sharding;
+#[cfg(feature = "filesystem")]
 pub use self::blob::filesystem::ShardingError;
 
 pub use self::discoverable::DiscoverableLookup;
 
 pub use self::migrate::migrate_object_store;
+pub use self::migrate::validate_object_store_migration;
+pub use self::migrate::MigrationDuplicate;
+pub use self::migrate::MigrationError;
+pub use self::migrate::MigrationReport;
 
 pub use self::objects::ArcIndex;
 pub use self::objects::ArcLookup;
 
+pub use self::objects::EntityType;
+
+#[cfg(feature = "duckdb")]
+pub use self::objects::export_duckdb;
+#[cfg(feature = "duckdb")]
+pub use self::objects::DuckDbExportError;
+
+#[cfg(feature = "kv")]
+pub use self::objects::KvLookup;
+#[cfg(feature = "kv")]
+pub use self::objects::KvStore;
+#[cfg(feature = "kv")]
+pub use self::objects::KvStoreError;
+
+#[cfg(feature = "search")]
+pub use self::objects::SearchHit;
+#[cfg(feature = "search")]
+pub use self::objects::SearchIndex;
+#[cfg(feature = "search")]
+pub use self::objects::SearchIndexError;
+#[cfg(feature = "search")]
+pub use self::objects::SearchIndexUpdate;
+#[cfg(feature = "search")]
+pub use self::objects::SearchKind;
+
+#[cfg(feature = "signing")]
+pub use self::objects::SignatureProblem;
+#[cfg(feature = "signing")]
+pub use self::objects::StoreKeyError;
+#[cfg(feature = "signing")]
+pub use self::objects::StoreSigningKey;
+#[cfg(feature = "signing")]
+pub use self::objects::StoreVerifyingKey;
+
+pub use self::objects::ManifestProblem;
+pub use self::objects::SharedVecLookup;
+pub use self::objects::SoftDeleteCounts;
 pub use self::objects::VecIndex;
 pub use self::objects::VecLookup;
 pub use self::objects::VecStore;
@@ -7,3 +7,7 @@
 mod objects;
 
 pub use self::objects::migrate_object_store;
+pub use self::objects::validate_object_store_migration;
+pub use self::objects::MigrationDuplicate;
+pub use self::objects::MigrationError;
+pub use self::objects::MigrationReport;
@@ -5,28 +5,46 @@
 // except according to those terms.
 
 use std::any;
-use std::collections::btree_map::Entry;
 use std::collections::{BTreeMap, BTreeSet};
-use std::mem;
 
 use ci_monitor_core::data::{
-    Deployment, Environment, Instance, Job, JobArtifact, MergeRequest, Pipeline, PipelineSchedule,
-    Project, Runner, RunnerHost, User,
+    ApiUsage, AuditEntry, CrawlSession, Deployment, Environment, Instance, Job, JobArtifact,
+    MergeRequest, Pipeline, PipelineSchedule, Project, Push, Release, Runner, RunnerHost, User,
 };
 use ci_monitor_core::Lookup;
 use perfect_derive::perfect_derive;
 use thiserror::Error;
 
-use crate::DiscoverableLookup;
+use crate::{DiscoverableLookup, EntityType};
 
+/// Errors which may occur when migrating an object store.
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum MigrationError {
+    /// An entity refers to an entity which is not in the source.
     #[error("dangling source index type {}: '{}'", type_, index)]
-    DanglingSourceIndex { type_: &'static str, index: String },
+    DanglingSourceIndex {
+        /// The type of the referenced entity.
+        type_: &'static str,
+        /// The index of the referenced entity.
+        index: String,
+    },
+    /// The source lists an index more than once.
     #[error("duplicate source index of type {}: '{}'", type_, index)]
-    DuplicateSourceIndex { type_: &'static str, index: String },
+    DuplicateSourceIndex {
+        /// The type of the entity.
+        type_: &'static str,
+        /// The index of the entity.
+        index: String,
+    },
+    /// The source lists an index without any data.
     #[error("missing source data of type {} at index '{}'", type_, index)]
-    MissingData { type_: &'static str, index: String },
+    MissingData {
+        /// The type of the entity.
+        type_: &'static str,
+        /// The index of the entity.
+        index: String,
+    },
 }
 
 impl MigrationError {
@@ -61,6 +79,86 @@ impl MigrationError {
     }
 }
 
+/// An entity which would be stored more than once in the sink.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MigrationDuplicate {
+    /// The type of the entity.
+    pub entity: EntityType,
+    /// The key identifying the entity on its forge.
+    pub key: String,
+}
+
+/// A summary of an object store migration.
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct MigrationReport {
+    /// The number of entities of each type stored into the sink.
+    pub counts: BTreeMap<EntityType, usize>,
+    /// Entities which could not be migrated.
+    ///
+    /// Only validation collects errors; a migration stops at the first one. Entities referring to
+    /// entities which could not be migrated are reported as dangling as well.
+    pub errors: Vec<MigrationError>,
+    /// Entities which share their forge identity with an entity migrated before them.
+    pub duplicates: Vec<MigrationDuplicate>,
+}
+
+impl MigrationReport {
+    /// Whether the migration is free of errors and duplicates.
+    pub fn is_clean(&self) -> bool {
+        self.errors.is_empty() && self.duplicates.is_empty()
+    }
+
+    /// The total number of entities stored into the sink.
+    pub fn total(&self) -> usize {
+        self.counts.values().sum()
+    }
+}
+
+struct MigrationLog {
+    validate: bool,
+    keys: BTreeSet<(EntityType, String)>,
+    report: MigrationReport,
+}
+
+impl MigrationLog {
+    fn new(validate: bool) -> Self {
+        Self {
+            validate,
+            keys: BTreeSet::new(),
+            report: MigrationReport::default(),
+        }
+    }
+
+    /// Pass through successful results.
+    ///
+    /// When validating, errors are recorded and `None` is returned so that the migration can
+    /// continue with the next entity.
+    fn check<T>(&mut self, res: Result<T, MigrationError>) -> Result<Option<T>, MigrationError> {
+        match res {
+            Ok(value) => Ok(Some(value)),
+            Err(err) if self.validate => {
+                self.report.errors.push(err);
+                Ok(None)
+            },
+            Err(err) => Err(err),
+        }
+    }
+
+    fn stored(&mut self, entity: EntityType, key: Option<String>) {
+        *self.report.counts.entry(entity).or_default() += 1;
+        if let Some(key) = key {
+            if !self.keys.insert((entity, key.clone())) {
+                self.report.duplicates.push(MigrationDuplicate {
+                    entity,
+                    key,
+                });
+            }
+        }
+    }
+}
+
 #[perfect_derive(Default)]
 struct IndexMap<Source, Sink, T, U = T>
 where
@@ -70,9 +168,6 @@ where
     map: BTreeMap<<Source as Lookup<T>>::Index, <Sink as Lookup<U>>::Index>,
 }
 
-type IndexEntry<'a, Source, Sink, T, U = T> =
-    Entry<'a, <Source as Lookup<T>>::Index, <Sink as Lookup<U>>::Index>;
-
 impl<Source, Sink, T, U> IndexMap<Source, Sink, T, U>
 where
     Source: Lookup<T>,
@@ -94,18 +189,17 @@ where
         }
     }
 
-    fn entry(
+    fn insert(
         &mut self,
         key: <Source as Lookup<T>>::Index,
-    ) -> Result<IndexEntry<Source, Sink, T, U>, MigrationError> {
-        let entry = self.map.entry(key);
-        if matches!(entry, Entry::Occupied(_)) {
-            Ok(entry)
-        } else {
-            Err(MigrationError::duplicate_source_index::<Source, T>(
-                entry.key(),
-            ))
+        value: <Sink as Lookup<U>>::Index,
+    ) -> Result<(), MigrationError> {
+        if self.map.contains_key(&key) {
+            return Err(MigrationError::duplicate_source_index::<Source, T>(&key));
         }
+
+        self.map.insert(key, value);
+        Ok(())
     }
 }
 
@@ -114,13 +208,64 @@ where
     Source: DiscoverableLookup<T>,
     <Source as Lookup<T>>::Index: Ord,
     Sink: DiscoverableLookup<U>,
+    T: Clone,
 {
+    /// The type of entity being migrated.
+    const ENTITY: EntityType;
+
+    /// Map an entity into the sink's representation.
+    fn map(&self, data: T) -> Result<U, MigrationError>;
+
+    /// A key identifying the entity on its forge.
+    ///
+    /// Entities sharing a key would be duplicates of each other in the sink.
+    fn key(&self, data: &U) -> Option<String> {
+        let _ = data;
+        None
+    }
+
+    /// Record the keys of entities already in the sink.
+    ///
+    /// Migrated entities which share a key with an existing entity are reported as duplicates.
+    fn seed(&self, sink: &Sink, log: &mut MigrationLog) {
+        for idx in sink.all_indices() {
+            if let Some(key) = sink.lookup(&idx).and_then(|data| self.key(data)) {
+                log.keys.insert((Self::ENTITY, key));
+            }
+        }
+    }
+
+    /// Migrate all entities of the type into the sink.
     fn migrate(
         &self,
         source: &Source,
         sink: &mut Sink,
         imap: &mut IndexMap<Source, Sink, T, U>,
-    ) -> Result<(), MigrationError>;
+        log: &mut MigrationLog,
+    ) -> Result<(), MigrationError> {
+        self.seed(sink, log);
+
+        for idx in source.all_indices() {
+            if imap.contains_key(&idx) {
+                log.check::<()>(Err(MigrationError::duplicate_source_index::<Source, T>(
+                    &idx,
+                )))?;
+                continue;
+            }
+
+            let new_data = get_data(source, &idx).and_then(|data| self.map(data));
+            let new_data = if let Some(new_data) = log.check(new_data)? {
+                new_data
+            } else {
+                continue;
+            };
+
+            log.stored(Self::ENTITY, self.key(&new_data));
+            imap.insert(idx, sink.store(new_data))?;
+        }
+
+        Ok(())
+    }
 }
 
 fn get_data<Source, T>(
@@ -145,23 +290,82 @@ where
     <Source as Lookup<Instance>>::Index: Ord,
     Sink: DiscoverableLookup<Instance>,
 {
-    fn migrate(
-        &self,
-        source: &Source,
-        sink: &mut Sink,
-        imap: &mut IndexMap<Source, Sink, Instance, Instance>,
-    ) -> Result<(), MigrationError> {
-        for idx in source.all_indices() {
-            let entry = imap.entry(idx)?;
-            let data = get_data(source, entry.key())?;
+    const ENTITY: EntityType = EntityType::Instance;
 
-            // TODO: check if the sink already has this `Instance`.
+    fn map(&self, data: Instance) -> Result<Instance, MigrationError> {
+        Ok(data)
+    }
 
-            let new_index = sink.store(data.clone());
-            entry.or_insert(new_index);
-        }
+    fn key(&self, data: &Instance) -> Option<String> {
+        Some(data.url.clone())
+    }
+}
 
-        Ok(())
+struct CrawlSessionMigration<'a, Source, Sink>
+where
+    Source: Lookup<Instance>,
+    Sink: Lookup<Instance>,
+{
+    instances: &'a IndexMap<Source, Sink, Instance>,
+}
+
+impl<'a, Source, Sink> Migration<Source, Sink, CrawlSession<Source>, CrawlSession<Sink>>
+    for CrawlSessionMigration<'a, Source, Sink>
+where
+    Source: DiscoverableLookup<CrawlSession<Source>>,
+    Source: Lookup<Instance>,
+    <Source as Lookup<Instance>>::Index: Ord,
+    <Source as Lookup<CrawlSession<Source>>>::Index: Ord,
+    Sink: DiscoverableLookup<CrawlSession<Sink>>,
+    Sink: Lookup<Instance>,
+{
+    const ENTITY: EntityType = EntityType::CrawlSession;
+
+    fn map(&self, data: CrawlSession<Source>) -> Result<CrawlSession<Sink>, MigrationError> {
+        let mut new_data: CrawlSession<Sink> = CrawlSession::builder()
+            .started_at(data.started_at)
+            .revision(data.revision)
+            .build()
+            .unwrap();
+        new_data.finished_at = data.finished_at;
+        new_data.tasks_executed = data.tasks_executed;
+        new_data.tasks_failed = data.tasks_failed;
+        new_data.tasks_canceled = data.tasks_canceled;
+        new_data.instances = data
+            .instances
+            .iter()
+            .map(|idx| self.instances.get(idx))
+            .collect::<Result<_, _>>()?;
+        new_data.api_usage = data
+            .api_usage
+            .iter()
+            .map(|usage| {
+                self.instances
+                    .get(&usage.instance)
+                    .map(|instance| ApiUsage::new(instance, usage.hour, usage.requests))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(new_data)
+    }
+}
+
+struct AuditEntryMigration {}
+
+impl<Source, Sink> Migration<Source, Sink, AuditEntry, AuditEntry> for AuditEntryMigration
+where
+    Source: DiscoverableLookup<AuditEntry>,
+    <Source as Lookup<AuditEntry>>::Index: Ord,
+    Sink: DiscoverableLookup<AuditEntry>,
+{
+    const ENTITY: EntityType = EntityType::AuditEntry;
+
+    fn map(&self, data: AuditEntry) -> Result<AuditEntry, MigrationError> {
+        Ok(data)
+    }
+
+    fn key(&self, data: &AuditEntry) -> Option<String> {
+        Some(data.unique_id.to_string())
     }
 }
 
@@ -173,23 +377,14 @@ where
     <Source as Lookup<RunnerHost>>::Index: Ord,
     Sink: DiscoverableLookup<RunnerHost>,
 {
-    fn migrate(
-        &self,
-        source: &Source,
-        sink: &mut Sink,
-        imap: &mut IndexMap<Source, Sink, RunnerHost, RunnerHost>,
-    ) -> Result<(), MigrationError> {
-        for idx in source.all_indices() {
-            let entry = imap.entry(idx)?;
-            let data = get_data(source, entry.key())?;
+    const ENTITY: EntityType = EntityType::RunnerHost;
 
-            // TODO: check if the sink already has this `RunnerHost`.
-
-            let new_index = sink.store(data.clone());
-            entry.or_insert(new_index);
-        }
+    fn map(&self, data: RunnerHost) -> Result<RunnerHost, MigrationError> {
+        Ok(data)
+    }
 
-        Ok(())
+    fn key(&self, data: &RunnerHost) -> Option<String> {
+        (!data.name.is_empty()).then(|| data.name.clone())
     }
 }
 
@@ -211,35 +406,27 @@ where
     Sink: DiscoverableLookup<User<Sink>>,
     Sink: Lookup<Instance>,
 {
-    fn migrate(
-        &self,
-        source: &Source,
-        sink: &mut Sink,
-        imap: &mut IndexMap<Source, Sink, User<Source>, User<Sink>>,
-    ) -> Result<(), MigrationError> {
-        for idx in source.all_indices() {
-            let entry = imap.entry(idx)?;
-            let data: User<Source> = get_data(source, entry.key())?;
-
-            // TODO: check if the sink already has this `User`.
-
-            let mut new_data: User<Sink> = User::builder()
-                .forge_id(data.forge_id)
-                .instance(self.instances.get(&data.instance)?)
-                .build()
-                .unwrap();
-            new_data.handle = data.handle;
-            new_data.name = data.name;
-            new_data.email = data.email;
-            new_data.avatar = data.avatar;
-            new_data.cim_fetched_at = data.cim_fetched_at;
-            new_data.cim_refreshed_at = data.cim_refreshed_at;
-
-            let new_index = sink.store(new_data);
-            entry.or_insert(new_index);
-        }
+    const ENTITY: EntityType = EntityType::User;
+
+    fn map(&self, data: User<Source>) -> Result<User<Sink>, MigrationError> {
+        let mut new_data: User<Sink> = User::builder()
+            .forge_id(data.forge_id)
+            .instance(self.instances.get(&data.instance)?)
+            .build()
+            .unwrap();
+        new_data.handle = data.handle;
+        new_data.previous_handles = data.previous_handles;
+        new_data.name = data.name;
+        new_data.email = data.email;
+        new_data.avatar = data.avatar;
+        new_data.cim_fetched_at = data.cim_fetched_at;
+        new_data.cim_refreshed_at = data.cim_refreshed_at;
+
+        Ok(new_data)
+    }
 
-        Ok(())
+    fn key(&self, data: &User<Sink>) -> Option<String> {
+        Some(format!("{:?}:{}", data.instance, data.forge_id))
     }
 }
 
@@ -261,34 +448,30 @@ where
     Sink: DiscoverableLookup<Project<Sink>>,
     Sink: Lookup<Instance>,
 {
-    fn migrate(
-        &self,
-        source: &Source,
-        sink: &mut Sink,
-        imap: &mut IndexMap<Source, Sink, Project<Source>, Project<Sink>>,
-    ) -> Result<(), MigrationError> {
-        for idx in source.all_indices() {
-            let entry = imap.entry(idx)?;
-            let data: Project<Source> = get_data(source, entry.key())?;
-
-            // TODO: check if the sink already has this `Project`.
-
-            let mut new_data: Project<Sink> = Project::builder()
-                .forge_id(data.forge_id)
-                .instance(self.instances.get(&data.instance)?)
-                .build()
-                .unwrap();
-            new_data.name = data.name;
-            new_data.url = data.url;
-            new_data.instance_path = data.instance_path;
-            new_data.cim_fetched_at = data.cim_fetched_at;
-            new_data.cim_refreshed_at = data.cim_refreshed_at;
-
-            let new_index = sink.store(new_data);
-            entry.or_insert(new_index);
-        }
+    const ENTITY: EntityType = EntityType::Project;
+
+    fn map(&self, data: Project<Source>) -> Result<Project<Sink>, MigrationError> {
+        let mut new_data: Project<Sink> = Project::builder()
+            .forge_id(data.forge_id)
+            .instance(self.instances.get(&data.instance)?)
+            .build()
+            .unwrap();
+        new_data.name = data.name;
+        new_data.url = data.url;
+        new_data.instance_path = data.instance_path;
+        new_data.default_branch = data.default_branch;
+        new_data.previous_default_branches = data.previous_default_branches;
+        new_data.cim_fidelity = data.cim_fidelity;
+        new_data.cim_fetched_at = data.cim_fetched_at;
+        new_data.cim_refreshed_at = data.cim_refreshed_at;
+        new_data.cim_deleted_at = data.cim_deleted_at;
+        new_data.cim_removed_from_group_at = data.cim_removed_from_group_at;
+
+        Ok(new_data)
+    }
 
-        Ok(())
+    fn key(&self, data: &Project<Sink>) -> Option<String> {
+        Some(format!("{:?}:{}", data.instance, data.forge_id))
     }
 }
 
@@ -322,57 +505,50 @@ where
     Sink: Lookup<Project<Sink>>,
     Sink: Lookup<RunnerHost>,
 {
-    fn migrate(
-        &self,
-        source: &Source,
-        sink: &mut Sink,
-        imap: &mut IndexMap<Source, Sink, Runner<Source>, Runner<Sink>>,
-    ) -> Result<(), MigrationError> {
-        for idx in source.all_indices() {
-            let entry = imap.entry(idx)?;
-            let data: Runner<Source> = get_data(source, entry.key())?;
-
-            // TODO: check if the sink already has this `Runner`.
-
-            let mut new_data: Runner<Sink> = Runner::builder()
-                .forge_id(data.forge_id)
-                .instance(self.instances.get(&data.instance)?)
-                .runner_type(data.runner_type)
-                .protection_level(data.protection_level)
-                .build()
-                .unwrap();
-            new_data.description = data.description;
-            new_data.maximum_timeout = data.maximum_timeout;
-            new_data.implementation = data.implementation;
-            new_data.version = data.version;
-            new_data.revision = data.revision;
-            new_data.platform = data.platform;
-            new_data.architecture = data.architecture;
-            new_data.tags = data.tags;
-            new_data.run_untagged = data.run_untagged;
-            new_data.projects = data
-                .projects
-                .iter()
-                .map(|idx| self.projects.get(idx))
-                .collect::<Result<Vec<_>, _>>()?;
-            new_data.paused = data.paused;
-            new_data.shared = data.shared;
-            new_data.online = data.online;
-            new_data.locked = data.locked;
-            new_data.contacted_at = data.contacted_at;
-            new_data.maintenance_note = data.maintenance_note;
-            new_data.runner_host = data
-                .runner_host
-                .map(|idx| self.runner_hosts.get(&idx))
-                .transpose()?;
-            new_data.cim_fetched_at = data.cim_fetched_at;
-            new_data.cim_refreshed_at = data.cim_refreshed_at;
-
-            let new_index = sink.store(new_data);
-            entry.or_insert(new_index);
-        }
+    const ENTITY: EntityType = EntityType::Runner;
+
+    fn map(&self, data: Runner<Source>) -> Result<Runner<Sink>, MigrationError> {
+        let mut new_data: Runner<Sink> = Runner::builder()
+            .forge_id(data.forge_id)
+            .instance(self.instances.get(&data.instance)?)
+            .runner_type(data.runner_type)
+            .protection_level(data.protection_level)
+            .build()
+            .unwrap();
+        new_data.description = data.description;
+        new_data.maximum_timeout = data.maximum_timeout;
+        new_data.implementation = data.implementation;
+        new_data.version = data.version;
+        new_data.revision = data.revision;
+        new_data.platform = data.platform;
+        new_data.architecture = data.architecture;
+        new_data.tags = data.tags;
+        new_data.run_untagged = data.run_untagged;
+        new_data.projects = data
+            .projects
+            .iter()
+            .map(|idx| self.projects.get(idx))
+            .collect::<Result<Vec<_>, _>>()?;
+        new_data.paused = data.paused;
+        new_data.shared = data.shared;
+        new_data.online = data.online;
+        new_data.locked = data.locked;
+        new_data.contacted_at = data.contacted_at;
+        new_data.ip_address = data.ip_address;
+        new_data.maintenance_note = data.maintenance_note;
+        new_data.runner_host = data
+            .runner_host
+            .map(|idx| self.runner_hosts.get(&idx))
+            .transpose()?;
+        new_data.max_concurrent_jobs = data.max_concurrent_jobs;
+        new_data.cim_fetched_at = data.cim_fetched_at;
+        new_data.cim_refreshed_at = data.cim_refreshed_at;
+
+        Ok(new_data)
+    }
 
-        Ok(())
+    fn key(&self, data: &Runner<Sink>) -> Option<String> {
+        Some(format!("{:?}:{}", data.instance, data.forge_id))
     }
 }
 
@@ -404,41 +580,37 @@ where
     Sink: Lookup<Project<Sink>>,
     Sink: Lookup<User<Sink>>,
 {
-    fn migrate(
-        &self,
-        source: &Source,
-        sink: &mut Sink,
-        imap: &mut IndexMap<Source, Sink, MergeRequest<Source>, MergeRequest<Sink>>,
-    ) -> Result<(), MigrationError> {
-        for idx in source.all_indices() {
-            let entry = imap.entry(idx)?;
-            let data: MergeRequest<Source> = get_data(source, entry.key())?;
-
-            // TODO: check if the sink already has this `MergeRequest`.
-
-            let mut new_data: MergeRequest<Sink> = MergeRequest::builder()
-                .id(data.id)
-                .source_project(self.projects.get(&data.source_project)?)
-                .target_project(self.projects.get(&data.target_project)?)
-                .forge_id(data.forge_id)
-                .state(data.state)
-                .author(self.users.get(&data.author)?)
-                .url(data.url)
-                .build()
-                .unwrap();
-            new_data.source_branch = data.source_branch;
-            new_data.sha = data.sha;
-            new_data.target_branch = data.target_branch;
-            new_data.title = data.title;
-            new_data.description = data.description;
-            new_data.cim_fetched_at = data.cim_fetched_at;
-            new_data.cim_refreshed_at = data.cim_refreshed_at;
-
-            let new_index = sink.store(new_data);
-            entry.or_insert(new_index);
-        }
+    const ENTITY: EntityType = EntityType::MergeRequest;
+
+    fn map(&self, data: MergeRequest<Source>) -> Result<MergeRequest<Sink>, MigrationError> {
+        let mut new_data: MergeRequest<Sink> = MergeRequest::builder()
+            .id(data.id)
+            .source_project(self.projects.get(&data.source_project)?)
+            .target_project(self.projects.get(&data.target_project)?)
+            .forge_id(data.forge_id)
+            .state(data.state)
+            .author(self.users.get(&data.author)?)
+            .url(data.url)
+            .build()
+            .unwrap();
+        new_data.source_branch = data.source_branch;
+        new_data.sha = data.sha;
+        new_data.target_branch = data.target_branch;
+        new_data.title = data.title;
+        new_data.description = data.description;
+        new_data.created_at = data.created_at;
+        new_data.merged_at = data.merged_at;
+        new_data.first_review_at = data.first_review_at;
+        new_data.approved_at = data.approved_at;
+        new_data.cim_fetched_at = data.cim_fetched_at;
+        new_data.cim_refreshed_at = data.cim_refreshed_at;
+        new_data.cim_deleted_at = data.cim_deleted_at;
+
+        Ok(new_data)
+    }
 
-        Ok(())
+    fn key(&self, data: &MergeRequest<Sink>) -> Option<String> {
+        Some(format!("{:?}:{}", data.target_project, data.forge_id))
     }
 }
 
@@ -470,39 +642,34 @@ where
     Sink: Lookup<Project<Sink>>,
     Sink: Lookup<User<Sink>>,
 {
-    fn migrate(
+    const ENTITY: EntityType = EntityType::PipelineSchedule;
+
+    fn map(
         &self,
-        source: &Source,
-        sink: &mut Sink,
-        imap: &mut IndexMap<Source, Sink, PipelineSchedule<Source>, PipelineSchedule<Sink>>,
-    ) -> Result<(), MigrationError> {
-        for idx in source.all_indices() {
-            let entry = imap.entry(idx)?;
-            let data: PipelineSchedule<Source> = get_data(source, entry.key())?;
-
-            // TODO: check if the sink already has this `PipelineSchedule`.
-
-            let mut new_data: PipelineSchedule<Sink> = PipelineSchedule::builder()
-                .project(self.projects.get(&data.project)?)
-                .ref_(data.ref_)
-                .forge_id(data.forge_id)
-                .created_at(data.created_at)
-                .updated_at(data.updated_at)
-                .owner(self.users.get(&data.owner)?)
-                .build()
-                .unwrap();
-            new_data.name = data.name;
-            new_data.variables = data.variables;
-            new_data.active = data.active;
-            new_data.next_run = data.next_run;
-            new_data.cim_fetched_at = data.cim_fetched_at;
-            new_data.cim_refreshed_at = data.cim_refreshed_at;
-
-            let new_index = sink.store(new_data);
-            entry.or_insert(new_index);
-        }
+        data: PipelineSchedule<Source>,
+    ) -> Result<PipelineSchedule<Sink>, MigrationError> {
+        let mut new_data: PipelineSchedule<Sink> = PipelineSchedule::builder()
+            .project(self.projects.get(&data.project)?)
+            .ref_(data.ref_)
+            .forge_id(data.forge_id)
+            .created_at(data.created_at)
+            .updated_at(data.updated_at)
+            .owner(self.users.get(&data.owner)?)
+            .build()
+            .unwrap();
+        new_data.name = data.name;
+        new_data.variables = data.variables;
+        new_data.active = data.active;
+        new_data.next_run = data.next_run;
+        new_data.cim_fetched_at = data.cim_fetched_at;
+        new_data.cim_refreshed_at = data.cim_refreshed_at;
+        new_data.cim_deleted_at = data.cim_deleted_at;
+
+        Ok(new_data)
+    }
 
-        Ok(())
+    fn key(&self, data: &PipelineSchedule<Sink>) -> Option<String> {
+        Some(format!("{:?}:{}", data.project, data.forge_id))
     }
 }
 
@@ -547,78 +714,261 @@ where
     Sink: Lookup<Project<Sink>>,
     Sink: Lookup<User<Sink>>,
 {
+    const ENTITY: EntityType = EntityType::Pipeline;
+
+    /// Map a pipeline without its parent pipeline.
+    ///
+    /// The parent pipeline is filled in by `migrate` once it has been migrated itself.
+    fn map(&self, data: Pipeline<Source>) -> Result<Pipeline<Sink>, MigrationError> {
+        let mut new_data: Pipeline<Sink> = Pipeline::builder()
+            .project(self.projects.get(&data.project)?)
+            .sha(data.sha)
+            .source(data.source)
+            .status(data.status)
+            .forge_id(data.forge_id)
+            .url(data.url)
+            .created_at(data.created_at)
+            .updated_at(data.updated_at)
+            .build()
+            .unwrap();
+        new_data.name = data.name;
+        new_data.previous_sha = data.previous_sha;
+        new_data.refname = data.refname;
+        new_data.stable_refname = data.stable_refname;
+        new_data.ci_config = data.ci_config;
+        new_data.artifact_dependencies = data.artifact_dependencies;
+        new_data.schedule = data
+            .schedule
+            .map(|idx| self.pipeline_schedules.get(&idx))
+            .transpose()?;
+        new_data.merge_request = data
+            .merge_request
+            .map(|idx| self.merge_requests.get(&idx))
+            .transpose()?;
+        new_data.variables = data.variables;
+        new_data.user = data.user.map(|idx| self.users.get(&idx)).transpose()?;
+        new_data.coverage = data.coverage;
+        new_data.archived = data.archived;
+        new_data.started_at = data.started_at;
+        new_data.finished_at = data.finished_at;
+        new_data.cim_fidelity = data.cim_fidelity;
+        new_data.cim_fetched_at = data.cim_fetched_at;
+        new_data.cim_refreshed_at = data.cim_refreshed_at;
+        new_data.cim_deleted_at = data.cim_deleted_at;
+
+        Ok(new_data)
+    }
+
+    fn key(&self, data: &Pipeline<Sink>) -> Option<String> {
+        Some(format!("{:?}:{}", data.project, data.forge_id))
+    }
+
     fn migrate(
         &self,
         source: &Source,
         sink: &mut Sink,
         imap: &mut IndexMap<Source, Sink, Pipeline<Source>, Pipeline<Sink>>,
+        log: &mut MigrationLog,
     ) -> Result<(), MigrationError> {
-        let mut with_missing_parent = BTreeSet::new();
+        self.seed(sink, log);
+
         let mut pipelines_to_inspect = source.all_indices();
 
+        // Parent pipelines need to be migrated before their children.
         while !pipelines_to_inspect.is_empty() {
+            let inspected = pipelines_to_inspect.len();
+            let mut with_missing_parent = Vec::new();
+
             for idx in pipelines_to_inspect.drain(..) {
-                let data: Pipeline<Source> = {
-                    let entry = imap.entry(idx.clone())?;
-                    get_data(source, entry.key())?
-                };
+                if imap.contains_key(&idx) {
+                    log.check::<()>(Err(MigrationError::duplicate_source_index::<
+                        Source,
+                        Pipeline<Source>,
+                    >(&idx)))?;
+                    continue;
+                }
+
+                let data: Pipeline<Source> =
+                    if let Some(data) = log.check(get_data(source, &idx))? {
+                        data
+                    } else {
+                        continue;
+                    };
 
-                if let Some(parent_pipeline) = data.parent_pipeline.as_ref() {
-                    if !imap.contains_key(parent_pipeline) {
-                        with_missing_parent.insert(parent_pipeline.clone());
+                let parent_pipeline = if let Some(parent_pipeline) = data.parent_pipeline.as_ref() {
+                    if let Ok(parent_pipeline) = imap.get(parent_pipeline) {
+                        Some(parent_pipeline)
+                    } else {
+                        with_missing_parent.push((idx, parent_pipeline.clone()));
                         continue;
                     }
-                }
+                } else {
+                    None
+                };
+
+                let new_data = self.map(data).map(|mut new_data| {
+                    new_data.parent_pipeline = parent_pipeline;
+                    new_data
+                });
+                let new_data = if let Some(new_data) = log.check(new_data)? {
+                    new_data
+                } else {
+                    continue;
+                };
 
-                // TODO: check if the sink already has this `Pipeline`.
-
-                let mut new_data: Pipeline<Sink> = Pipeline::builder()
-                    .project(self.projects.get(&data.project)?)
-                    .sha(data.sha)
-                    .source(data.source)
-                    .status(data.status)
-                    .forge_id(data.forge_id)
-                    .url(data.url)
-                    .created_at(data.created_at)
-                    .updated_at(data.updated_at)
-                    .build()
-                    .unwrap();
-                new_data.name = data.name;
-                new_data.previous_sha = data.previous_sha;
-                new_data.refname = data.refname;
-                new_data.stable_refname = data.stable_refname;
-                new_data.schedule = data
-                    .schedule
-                    .map(|idx| self.pipeline_schedules.get(&idx))
-                    .transpose()?;
-                new_data.parent_pipeline =
-                    data.parent_pipeline.map(|idx| imap.get(&idx)).transpose()?;
-                new_data.merge_request = data
-                    .merge_request
-                    .map(|idx| self.merge_requests.get(&idx))
-                    .transpose()?;
-                new_data.variables = data.variables;
-                new_data.user = data.user.map(|idx| self.users.get(&idx)).transpose()?;
-                new_data.coverage = data.coverage;
-                new_data.archived = data.archived;
-                new_data.started_at = data.started_at;
-                new_data.finished_at = data.finished_at;
-                new_data.cim_fetched_at = data.cim_fetched_at;
-                new_data.cim_refreshed_at = data.cim_refreshed_at;
-
-                let new_index = sink.store(new_data);
-                let entry = imap.entry(idx)?;
-                entry.or_insert(new_index);
+                log.stored(Self::ENTITY, self.key(&new_data));
+                imap.insert(idx, sink.store(new_data))?;
             }
 
-            let swap = mem::take(&mut with_missing_parent);
-            pipelines_to_inspect.extend(swap.into_iter());
+            // Without any progress, the remaining parent pipelines cannot be migrated.
+            if with_missing_parent.len() == inspected {
+                for (_, parent_pipeline) in with_missing_parent {
+                    log.check::<()>(Err(MigrationError::dangling_source_index::<
+                        Source,
+                        Pipeline<Source>,
+                    >(&parent_pipeline)))?;
+                }
+                break;
+            }
+
+            pipelines_to_inspect.extend(with_missing_parent.into_iter().map(|(idx, _)| idx));
         }
 
         Ok(())
     }
 }
 
+struct ReleaseMigration<'a, Source, Sink>
+where
+    Source: Lookup<Instance>,
+    Source: Lookup<MergeRequest<Source>>,
+    Source: Lookup<Pipeline<Source>>,
+    Source: Lookup<PipelineSchedule<Source>>,
+    Source: Lookup<Project<Source>>,
+    Source: Lookup<User<Source>>,
+    Sink: Lookup<Instance>,
+    Sink: Lookup<MergeRequest<Sink>>,
+    Sink: Lookup<Pipeline<Sink>>,
+    Sink: Lookup<PipelineSchedule<Sink>>,
+    Sink: Lookup<Project<Sink>>,
+    Sink: Lookup<User<Sink>>,
+{
+    pipelines: &'a IndexMap<Source, Sink, Pipeline<Source>, Pipeline<Sink>>,
+    projects: &'a IndexMap<Source, Sink, Project<Source>, Project<Sink>>,
+    users: &'a IndexMap<Source, Sink, User<Source>, User<Sink>>,
+}
+
+impl<'a, Source, Sink> Migration<Source, Sink, Release<Source>, Release<Sink>>
+    for ReleaseMigration<'a, Source, Sink>
+where
+    Source: DiscoverableLookup<Release<Source>>,
+    Source: Lookup<Instance>,
+    Source: Lookup<MergeRequest<Source>>,
+    Source: Lookup<Pipeline<Source>>,
+    Source: Lookup<PipelineSchedule<Source>>,
+    Source: Lookup<Project<Source>>,
+    Source: Lookup<User<Source>>,
+    <Source as Lookup<Pipeline<Source>>>::Index: Ord,
+    <Source as Lookup<Project<Source>>>::Index: Ord,
+    <Source as Lookup<Release<Source>>>::Index: Ord,
+    <Source as Lookup<User<Source>>>::Index: Ord,
+    Sink: DiscoverableLookup<Release<Sink>>,
+    Sink: Lookup<Instance>,
+    Sink: Lookup<MergeRequest<Sink>>,
+    Sink: Lookup<Pipeline<Sink>>,
+    Sink: Lookup<PipelineSchedule<Sink>>,
+    Sink: Lookup<Project<Sink>>,
+    Sink: Lookup<User<Sink>>,
+{
+    const ENTITY: EntityType = EntityType::Release;
+
+    fn map(&self, data: Release<Source>) -> Result<Release<Sink>, MigrationError> {
+        let mut new_data: Release<Sink> = Release::builder()
+            .project(self.projects.get(&data.project)?)
+            .tag(data.tag)
+            .sha(data.sha)
+            .unique_id(data.unique_id)
+            .created_at(data.created_at)
+            .released_at(data.released_at)
+            .build()
+            .unwrap();
+        new_data.name = data.name;
+        new_data.description = data.description;
+        new_data.author = data.author.map(|idx| self.users.get(&idx)).transpose()?;
+        new_data.assets = data.assets;
+        new_data.pipeline = data
+            .pipeline
+            .map(|idx| self.pipelines.get(&idx))
+            .transpose()?;
+        new_data.url = data.url;
+        new_data.cim_fetched_at = data.cim_fetched_at;
+        new_data.cim_refreshed_at = data.cim_refreshed_at;
+        new_data.cim_deleted_at = data.cim_deleted_at;
+
+        Ok(new_data)
+    }
+
+    fn key(&self, data: &Release<Sink>) -> Option<String> {
+        Some(format!("{:?}:{}", data.project, data.unique_id))
+    }
+}
+
+struct PushMigration<'a, Source, Sink>
+where
+    Source: Lookup<Instance>,
+    Source: Lookup<Project<Source>>,
+    Source: Lookup<User<Source>>,
+    Sink: Lookup<Instance>,
+    Sink: Lookup<Project<Sink>>,
+    Sink: Lookup<User<Sink>>,
+{
+    projects: &'a IndexMap<Source, Sink, Project<Source>, Project<Sink>>,
+    users: &'a IndexMap<Source, Sink, User<Source>, User<Sink>>,
+}
+
+impl<'a, Source, Sink> Migration<Source, Sink, Push<Source>, Push<Sink>>
+    for PushMigration<'a, Source, Sink>
+where
+    Source: DiscoverableLookup<Push<Source>>,
+    Source: Lookup<Instance>,
+    Source: Lookup<Project<Source>>,
+    Source: Lookup<User<Source>>,
+    <Source as Lookup<Project<Source>>>::Index: Ord,
+    <Source as Lookup<Push<Source>>>::Index: Ord,
+    <Source as Lookup<User<Source>>>::Index: Ord,
+    Sink: DiscoverableLookup<Push<Sink>>,
+    Sink: Lookup<Instance>,
+    Sink: Lookup<Project<Sink>>,
+    Sink: Lookup<User<Sink>>,
+{
+    const ENTITY: EntityType = EntityType::Push;
+
+    fn map(&self, data: Push<Source>) -> Result<Push<Sink>, MigrationError> {
+        let mut new_data: Push<Sink> = Push::builder()
+            .project(self.projects.get(&data.project)?)
+            .refname(data.refname)
+            .sha(data.sha)
+            .forge_id(data.forge_id)
+            .pushed_at(data.pushed_at)
+            .build()
+            .unwrap();
+        new_data.author = data.author.map(|idx| self.users.get(&idx)).transpose()?;
+        new_data.commit_count = data.commit_count;
+        new_data.commit_title = data.commit_title;
+        new_data.tag = data.tag;
+        new_data.before = data.before;
+        new_data.cim_fetched_at = data.cim_fetched_at;
+        new_data.cim_refreshed_at = data.cim_refreshed_at;
+        new_data.cim_deleted_at = data.cim_deleted_at;
+
+        Ok(new_data)
+    }
+
+    fn key(&self, data: &Push<Sink>) -> Option<String> {
+        Some(format!("{:?}:{}", data.project, data.forge_id))
+    }
+}
+
 struct EnvironmentMigration<'a, Source, Sink>
 where
     Source: Lookup<Instance>,
@@ -641,38 +991,30 @@ where
     Sink: Lookup<Instance>,
     Sink: Lookup<Project<Sink>>,
 {
-    fn migrate(
-        &self,
-        source: &Source,
-        sink: &mut Sink,
-        imap: &mut IndexMap<Source, Sink, Environment<Source>, Environment<Sink>>,
-    ) -> Result<(), MigrationError> {
-        for idx in source.all_indices() {
-            let entry = imap.entry(idx)?;
-            let data: Environment<Source> = get_data(source, entry.key())?;
-
-            // TODO: check if the sink already has this `Environment`.
-
-            let mut new_data: Environment<Sink> = Environment::builder()
-                .name(data.name)
-                .state(data.state)
-                .tier(data.tier)
-                .forge_id(data.forge_id)
-                .project(self.projects.get(&data.project)?)
-                .created_at(data.created_at)
-                .updated_at(data.updated_at)
-                .build()
-                .unwrap();
-            new_data.external_url = data.external_url;
-            new_data.auto_stop_at = data.auto_stop_at;
-            new_data.cim_fetched_at = data.cim_fetched_at;
-            new_data.cim_refreshed_at = data.cim_refreshed_at;
-
-            let new_index = sink.store(new_data);
-            entry.or_insert(new_index);
-        }
+    const ENTITY: EntityType = EntityType::Environment;
+
+    fn map(&self, data: Environment<Source>) -> Result<Environment<Sink>, MigrationError> {
+        let mut new_data: Environment<Sink> = Environment::builder()
+            .name(data.name)
+            .state(data.state)
+            .tier(data.tier)
+            .forge_id(data.forge_id)
+            .project(self.projects.get(&data.project)?)
+            .created_at(data.created_at)
+            .updated_at(data.updated_at)
+            .build()
+            .unwrap();
+        new_data.external_url = data.external_url;
+        new_data.auto_stop_at = data.auto_stop_at;
+        new_data.cim_fetched_at = data.cim_fetched_at;
+        new_data.cim_refreshed_at = data.cim_refreshed_at;
+        new_data.cim_deleted_at = data.cim_deleted_at;
+
+        Ok(new_data)
+    }
 
-        Ok(())
+    fn key(&self, data: &Environment<Sink>) -> Option<String> {
+        Some(format!("{:?}:{}", data.project, data.forge_id))
     }
 }
 
@@ -720,36 +1062,28 @@ where
     Sink: Lookup<Project<Sink>>,
     Sink: Lookup<User<Sink>>,
 {
-    fn migrate(
-        &self,
-        source: &Source,
-        sink: &mut Sink,
-        imap: &mut IndexMap<Source, Sink, Deployment<Source>, Deployment<Sink>>,
-    ) -> Result<(), MigrationError> {
-        for idx in source.all_indices() {
-            let entry = imap.entry(idx)?;
-            let data: Deployment<Source> = get_data(source, entry.key())?;
-
-            // TODO: check if the sink already has this `Deployment`.
-
-            let mut new_data: Deployment<Sink> = Deployment::builder()
-                .pipeline(self.pipelines.get(&data.pipeline)?)
-                .environment(self.environments.get(&data.environment)?)
-                .forge_id(data.forge_id)
-                .created_at(data.created_at)
-                .updated_at(data.updated_at)
-                .status(data.status)
-                .build()
-                .unwrap();
-            new_data.finished_at = data.finished_at;
-            new_data.cim_fetched_at = data.cim_fetched_at;
-            new_data.cim_refreshed_at = data.cim_refreshed_at;
-
-            let new_index = sink.store(new_data);
-            entry.or_insert(new_index);
-        }
+    const ENTITY: EntityType = EntityType::Deployment;
+
+    fn map(&self, data: Deployment<Source>) -> Result<Deployment<Sink>, MigrationError> {
+        let mut new_data: Deployment<Sink> = Deployment::builder()
+            .pipeline(self.pipelines.get(&data.pipeline)?)
+            .environment(self.environments.get(&data.environment)?)
+            .forge_id(data.forge_id)
+            .created_at(data.created_at)
+            .updated_at(data.updated_at)
+            .status(data.status)
+            .build()
+            .unwrap();
+        new_data.finished_at = data.finished_at;
+        new_data.cim_fetched_at = data.cim_fetched_at;
+        new_data.cim_refreshed_at = data.cim_refreshed_at;
+        new_data.cim_deleted_at = data.cim_deleted_at;
+
+        Ok(new_data)
+    }
 
-        Ok(())
+    fn key(&self, data: &Deployment<Sink>) -> Option<String> {
+        Some(format!("{:?}:{}", data.environment, data.forge_id))
     }
 }
 
@@ -813,51 +1147,47 @@ where
     Sink: Lookup<RunnerHost>,
     Sink: Lookup<User<Sink>>,
 {
-    fn migrate(
-        &self,
-        source: &Source,
-        sink: &mut Sink,
-        imap: &mut IndexMap<Source, Sink, Job<Source>, Job<Sink>>,
-    ) -> Result<(), MigrationError> {
-        for idx in source.all_indices() {
-            let entry = imap.entry(idx.clone())?;
-            let data: Job<Source> = get_data(source, entry.key())?;
-
-            // TODO: check if the sink already has this `Job`.
-
-            let mut new_data: Job<Sink> = Job::builder()
-                .user(self.users.get(&data.user)?)
-                .state(data.state)
-                .created_at(data.created_at)
-                .forge_id(data.forge_id)
-                .pipeline(self.pipelines.get(&data.pipeline)?)
-                .build()
-                .unwrap();
-            new_data.name = data.name;
-            new_data.stage = data.stage;
-            new_data.allow_failure = data.allow_failure;
-            new_data.tags = data.tags;
-            new_data.variables = data.variables;
-            new_data.started_at = data.started_at;
-            new_data.finished_at = data.finished_at;
-            new_data.erased_at = data.erased_at;
-            new_data.queued_duration = data.queued_duration;
-            new_data.runner = data.runner.map(|idx| self.runners.get(&idx)).transpose()?;
-            new_data.deployment = data
-                .deployment
-                .map(|idx| self.deployments.get(&idx))
-                .transpose()?;
-            new_data.archived = data.archived;
-            new_data.url = data.url;
-            new_data.coverage = data.coverage;
-            new_data.cim_fetched_at = data.cim_fetched_at;
-            new_data.cim_refreshed_at = data.cim_refreshed_at;
-
-            let new_index = sink.store(new_data);
-            entry.or_insert(new_index);
-        }
+    const ENTITY: EntityType = EntityType::Job;
+
+    fn map(&self, data: Job<Source>) -> Result<Job<Sink>, MigrationError> {
+        let mut new_data: Job<Sink> = Job::builder()
+            .user(self.users.get(&data.user)?)
+            .state(data.state)
+            .created_at(data.created_at)
+            .forge_id(data.forge_id)
+            .pipeline(self.pipelines.get(&data.pipeline)?)
+            .build()
+            .unwrap();
+        new_data.name = data.name;
+        new_data.stage = data.stage;
+        new_data.allow_failure = data.allow_failure;
+        new_data.tags = data.tags;
+        new_data.variables = data.variables;
+        new_data.started_at = data.started_at;
+        new_data.finished_at = data.finished_at;
+        new_data.erased_at = data.erased_at;
+        new_data.queued_duration = data.queued_duration;
+        new_data.runner = data.runner.map(|idx| self.runners.get(&idx)).transpose()?;
+        new_data.deployment = data
+            .deployment
+            .map(|idx| self.deployments.get(&idx))
+            .transpose()?;
+        new_data.manual = data.manual;
+        new_data.played_at = data.played_at;
+        new_data.played_by = data.played_by.map(|idx| self.users.get(&idx)).transpose()?;
+        new_data.archived = data.archived;
+        new_data.url = data.url;
+        new_data.coverage = data.coverage;
+        new_data.sections = data.sections;
+        new_data.cim_fetched_at = data.cim_fetched_at;
+        new_data.cim_refreshed_at = data.cim_refreshed_at;
+        new_data.cim_deleted_at = data.cim_deleted_at;
+
+        Ok(new_data)
+    }
 
-        Ok(())
+    fn key(&self, data: &Job<Sink>) -> Option<String> {
+        Some(format!("{:?}:{}", data.pipeline, data.forge_id))
     }
 }
 
@@ -919,43 +1249,38 @@ where
     Sink: Lookup<RunnerHost>,
     Sink: Lookup<User<Sink>>,
 {
-    fn migrate(
-        &self,
-        source: &Source,
-        sink: &mut Sink,
-        imap: &mut IndexMap<Source, Sink, JobArtifact<Source>, JobArtifact<Sink>>,
-    ) -> Result<(), MigrationError> {
-        for idx in source.all_indices() {
-            let entry = imap.entry(idx.clone())?;
-            let data: JobArtifact<Source> = get_data(source, entry.key())?;
-
-            // TODO: check if the sink already has this `JobArtifact`.
-
-            let mut new_data: JobArtifact<Sink> = JobArtifact::builder()
-                .kind(data.kind)
-                .name(data.name)
-                .size(data.size)
-                .unique_id(data.unique_id)
-                .job(self.jobs.get(&data.job)?)
-                .build()
-                .unwrap();
-            new_data.expire_at = data.expire_at;
-            new_data.blob = data.blob;
-
-            let new_index = sink.store(new_data);
-            entry.or_insert(new_index);
-        }
+    const ENTITY: EntityType = EntityType::JobArtifact;
+
+    fn map(&self, data: JobArtifact<Source>) -> Result<JobArtifact<Sink>, MigrationError> {
+        let mut new_data: JobArtifact<Sink> = JobArtifact::builder()
+            .kind(data.kind)
+            .name(data.name)
+            .size(data.size)
+            .unique_id(data.unique_id)
+            .job(self.jobs.get(&data.job)?)
+            .build()
+            .unwrap();
+        new_data.expire_at = data.expire_at;
+        new_data.blob = data.blob;
+
+        Ok(new_data)
+    }
 
-        Ok(())
+    fn key(&self, data: &JobArtifact<Sink>) -> Option<String> {
+        Some(format!("{:?}:{}", data.job, data.unique_id))
     }
 }
 
 /// Migrate an object store's objects into another store.
+///
+/// Stops at the first entity which cannot be migrated.
 pub fn migrate_object_store<Source, Sink>(
     source: &Source,
     sink: &mut Sink,
-) -> Result<(), MigrationError>
+) -> Result<MigrationReport, MigrationError>
 where
+    Source: DiscoverableLookup<AuditEntry>,
+    Source: DiscoverableLookup<CrawlSession<Source>>,
     Source: DiscoverableLookup<Deployment<Source>>,
     Source: DiscoverableLookup<Environment<Source>>,
     Source: DiscoverableLookup<Instance>,
@@ -965,9 +1290,13 @@ where
     Source: DiscoverableLookup<Pipeline<Source>>,
     Source: DiscoverableLookup<PipelineSchedule<Source>>,
     Source: DiscoverableLookup<Project<Source>>,
+    Source: DiscoverableLookup<Push<Source>>,
+    Source: DiscoverableLookup<Release<Source>>,
     Source: DiscoverableLookup<Runner<Source>>,
     Source: DiscoverableLookup<RunnerHost>,
     Source: DiscoverableLookup<User<Source>>,
+    <Source as Lookup<AuditEntry>>::Index: Ord,
+    <Source as Lookup<CrawlSession<Source>>>::Index: Ord,
     <Source as Lookup<Deployment<Source>>>::Index: Ord,
     <Source as Lookup<Environment<Source>>>::Index: Ord,
     <Source as Lookup<Instance>>::Index: Ord,
@@ -977,9 +1306,13 @@ where
     <Source as Lookup<Pipeline<Source>>>::Index: Ord,
     <Source as Lookup<PipelineSchedule<Source>>>::Index: Ord,
     <Source as Lookup<Project<Source>>>::Index: Ord,
+    <Source as Lookup<Push<Source>>>::Index: Ord,
+    <Source as Lookup<Release<Source>>>::Index: Ord,
     <Source as Lookup<Runner<Source>>>::Index: Ord,
     <Source as Lookup<RunnerHost>>::Index: Ord,
     <Source as Lookup<User<Source>>>::Index: Ord,
+    Sink: DiscoverableLookup<AuditEntry>,
+    Sink: DiscoverableLookup<CrawlSession<Sink>>,
     Sink: DiscoverableLookup<Deployment<Sink>>,
     Sink: DiscoverableLookup<Environment<Sink>>,
     Sink: DiscoverableLookup<Instance>,
@@ -989,6 +1322,130 @@ where
     Sink: DiscoverableLookup<Pipeline<Sink>>,
     Sink: DiscoverableLookup<PipelineSchedule<Sink>>,
     Sink: DiscoverableLookup<Project<Sink>>,
+    Sink: DiscoverableLookup<Push<Sink>>,
+    Sink: DiscoverableLookup<Release<Sink>>,
+    Sink: DiscoverableLookup<Runner<Sink>>,
+    Sink: DiscoverableLookup<RunnerHost>,
+    Sink: DiscoverableLookup<User<Sink>>,
+{
+    migrate_object_store_impl(source, sink, MigrationLog::new(false))
+}
+
+/// Validate the migration of an object store's objects into another store.
+///
+/// The full migration is performed into a copy of `sink` so that no changes are made to it. Rather
+/// than stopping at the first problem, all entities which cannot be migrated are reported along with
+/// entities which would duplicate those already in the sink (or each other).
+pub fn validate_object_store_migration<Source, Sink>(
+    source: &Source,
+    sink: &Sink,
+) -> Result<MigrationReport, MigrationError>
+where
+    Source: DiscoverableLookup<AuditEntry>,
+    Source: DiscoverableLookup<CrawlSession<Source>>,
+    Source: DiscoverableLookup<Deployment<Source>>,
+    Source: DiscoverableLookup<Environment<Source>>,
+    Source: DiscoverableLookup<Instance>,
+    Source: DiscoverableLookup<Job<Source>>,
+    Source: DiscoverableLookup<JobArtifact<Source>>,
+    Source: DiscoverableLookup<MergeRequest<Source>>,
+    Source: DiscoverableLookup<Pipeline<Source>>,
+    Source: DiscoverableLookup<PipelineSchedule<Source>>,
+    Source: DiscoverableLookup<Project<Source>>,
+    Source: DiscoverableLookup<Push<Source>>,
+    Source: DiscoverableLookup<Release<Source>>,
+    Source: DiscoverableLookup<Runner<Source>>,
+    Source: DiscoverableLookup<RunnerHost>,
+    Source: DiscoverableLookup<User<Source>>,
+    <Source as Lookup<AuditEntry>>::Index: Ord,
+    <Source as Lookup<CrawlSession<Source>>>::Index: Ord,
+    <Source as Lookup<Deployment<Source>>>::Index: Ord,
+    <Source as Lookup<Environment<Source>>>::Index: Ord,
+    <Source as Lookup<Instance>>::Index: Ord,
+    <Source as Lookup<Job<Source>>>::Index: Ord,
+    <Source as Lookup<JobArtifact<Source>>>::Index: Ord,
+    <Source as Lookup<MergeRequest<Source>>>::Index: Ord,
+    <Source as Lookup<Pipeline<Source>>>::Index: Ord,
+    <Source as Lookup<PipelineSchedule<Source>>>::Index: Ord,
+    <Source as Lookup<Project<Source>>>::Index: Ord,
+    <Source as Lookup<Push<Source>>>::Index: Ord,
+    <Source as Lookup<Release<Source>>>::Index: Ord,
+    <Source as Lookup<Runner<Source>>>::Index: Ord,
+    <Source as Lookup<RunnerHost>>::Index: Ord,
+    <Source as Lookup<User<Source>>>::Index: Ord,
+    Sink: Clone,
+    Sink: DiscoverableLookup<AuditEntry>,
+    Sink: DiscoverableLookup<CrawlSession<Sink>>,
+    Sink: DiscoverableLookup<Deployment<Sink>>,
+    Sink: DiscoverableLookup<Environment<Sink>>,
+    Sink: DiscoverableLookup<Instance>,
+    Sink: DiscoverableLookup<Job<Sink>>,
+    Sink: DiscoverableLookup<JobArtifact<Sink>>,
+    Sink: DiscoverableLookup<MergeRequest<Sink>>,
+    Sink: DiscoverableLookup<Pipeline<Sink>>,
+    Sink: DiscoverableLookup<PipelineSchedule<Sink>>,
+    Sink: DiscoverableLookup<Project<Sink>>,
+    Sink: DiscoverableLookup<Push<Sink>>,
+    Sink: DiscoverableLookup<Release<Sink>>,
+    Sink: DiscoverableLookup<Runner<Sink>>,
+    Sink: DiscoverableLookup<RunnerHost>,
+    Sink: DiscoverableLookup<User<Sink>>,
+{
+    let mut sink = sink.clone();
+    migrate_object_store_impl(source, &mut sink, MigrationLog::new(true))
+}
+
+fn migrate_object_store_impl<Source, Sink>(
+    source: &Source,
+    sink: &mut Sink,
+    mut log: MigrationLog,
+) -> Result<MigrationReport, MigrationError>
+where
+    Source: DiscoverableLookup<AuditEntry>,
+    Source: DiscoverableLookup<CrawlSession<Source>>,
+    Source: DiscoverableLookup<Deployment<Source>>,
+    Source: DiscoverableLookup<Environment<Source>>,
+    Source: DiscoverableLookup<Instance>,
+    Source: DiscoverableLookup<Job<Source>>,
+    Source: DiscoverableLookup<JobArtifact<Source>>,
+    Source: DiscoverableLookup<MergeRequest<Source>>,
+    Source: DiscoverableLookup<Pipeline<Source>>,
+    Source: DiscoverableLookup<PipelineSchedule<Source>>,
+    Source: DiscoverableLookup<Project<Source>>,
+    Source: DiscoverableLookup<Push<Source>>,
+    Source: DiscoverableLookup<Release<Source>>,
+    Source: DiscoverableLookup<Runner<Source>>,
+    Source: DiscoverableLookup<RunnerHost>,
+    Source: DiscoverableLookup<User<Source>>,
+    <Source as Lookup<AuditEntry>>::Index: Ord,
+    <Source as Lookup<CrawlSession<Source>>>::Index: Ord,
+    <Source as Lookup<Deployment<Source>>>::Index: Ord,
+    <Source as Lookup<Environment<Source>>>::Index: Ord,
+    <Source as Lookup<Instance>>::Index: Ord,
+    <Source as Lookup<Job<Source>>>::Index: Ord,
+    <Source as Lookup<JobArtifact<Source>>>::Index: Ord,
+    <Source as Lookup<MergeRequest<Source>>>::Index: Ord,
+    <Source as Lookup<Pipeline<Source>>>::Index: Ord,
+    <Source as Lookup<PipelineSchedule<Source>>>::Index: Ord,
+    <Source as Lookup<Project<Source>>>::Index: Ord,
+    <Source as Lookup<Push<Source>>>::Index: Ord,
+    <Source as Lookup<Release<Source>>>::Index: Ord,
+    <Source as Lookup<Runner<Source>>>::Index: Ord,
+    <Source as Lookup<RunnerHost>>::Index: Ord,
+    <Source as Lookup<User<Source>>>::Index: Ord,
+    Sink: DiscoverableLookup<AuditEntry>,
+    Sink: DiscoverableLookup<CrawlSession<Sink>>,
+    Sink: DiscoverableLookup<Deployment<Sink>>,
+    Sink: DiscoverableLookup<Environment<Sink>>,
+    Sink: DiscoverableLookup<Instance>,
+    Sink: DiscoverableLookup<Job<Sink>>,
+    Sink: DiscoverableLookup<JobArtifact<Sink>>,
+    Sink: DiscoverableLookup<MergeRequest<Sink>>,
+    Sink: DiscoverableLookup<Pipeline<Sink>>,
+    Sink: DiscoverableLookup<PipelineSchedule<Sink>>,
+    Sink: DiscoverableLookup<Project<Sink>>,
+    Sink: DiscoverableLookup<Push<Sink>>,
+    Sink: DiscoverableLookup<Release<Sink>>,
     Sink: DiscoverableLookup<Runner<Sink>>,
     Sink: DiscoverableLookup<RunnerHost>,
     Sink: DiscoverableLookup<User<Sink>>,
@@ -997,14 +1454,31 @@ where
     let mut instance_map = IndexMap::<Source, Sink, Instance>::default();
     {
         let migration = InstanceMigration {};
-        migration.migrate(source, sink, &mut instance_map)?;
+        migration.migrate(source, sink, &mut instance_map, &mut log)?;
+    }
+
+    // Crawl sessions
+    let mut crawl_session_map =
+        IndexMap::<Source, Sink, CrawlSession<Source>, CrawlSession<Sink>>::default();
+    {
+        let migration = CrawlSessionMigration {
+            instances: &mut instance_map,
+        };
+        migration.migrate(source, sink, &mut crawl_session_map, &mut log)?;
+    }
+
+    // Audit entries
+    let mut audit_entry_map = IndexMap::<Source, Sink, AuditEntry>::default();
+    {
+        let migration = AuditEntryMigration {};
+        migration.migrate(source, sink, &mut audit_entry_map, &mut log)?;
     }
 
     // Runner hosts
     let mut runner_host_map = IndexMap::<Source, Sink, RunnerHost>::default();
     {
         let migration = RunnerHostMigration {};
-        migration.migrate(source, sink, &mut runner_host_map)?;
+        migration.migrate(source, sink, &mut runner_host_map, &mut log)?;
     }
 
     // Users
@@ -1013,7 +1487,7 @@ where
         let migration = UserMigration {
             instances: &mut instance_map,
         };
-        migration.migrate(source, sink, &mut user_map)?;
+        migration.migrate(source, sink, &mut user_map, &mut log)?;
     }
 
     // Projects
@@ -1022,7 +1496,7 @@ where
         let migration = ProjectMigration {
             instances: &mut instance_map,
         };
-        migration.migrate(source, sink, &mut project_map)?;
+        migration.migrate(source, sink, &mut project_map, &mut log)?;
     }
 
     // Runners
@@ -1033,7 +1507,7 @@ where
             projects: &mut project_map,
             runner_hosts: &mut runner_host_map,
         };
-        migration.migrate(source, sink, &mut runner_map)?;
+        migration.migrate(source, sink, &mut runner_map, &mut log)?;
     }
 
     // Merge requests
@@ -1044,7 +1518,7 @@ where
             projects: &mut project_map,
             users: &mut user_map,
         };
-        migration.migrate(source, sink, &mut merge_request_map)?;
+        migration.migrate(source, sink, &mut merge_request_map, &mut log)?;
     }
 
     // Pipeline schedules
@@ -1055,7 +1529,7 @@ where
             projects: &mut project_map,
             users: &mut user_map,
         };
-        migration.migrate(source, sink, &mut pipeline_schedule_map)?;
+        migration.migrate(source, sink, &mut pipeline_schedule_map, &mut log)?;
     }
 
     // Pipelines
@@ -1067,7 +1541,28 @@ where
             merge_requests: &mut merge_request_map,
             users: &mut user_map,
         };
-        migration.migrate(source, sink, &mut pipeline_map)?;
+        migration.migrate(source, sink, &mut pipeline_map, &mut log)?;
+    }
+
+    // Releases
+    let mut release_map = IndexMap::<Source, Sink, Release<Source>, Release<Sink>>::default();
+    {
+        let migration = ReleaseMigration {
+            pipelines: &mut pipeline_map,
+            projects: &mut project_map,
+            users: &mut user_map,
+        };
+        migration.migrate(source, sink, &mut release_map, &mut log)?;
+    }
+
+    // Pushes
+    let mut push_map = IndexMap::<Source, Sink, Push<Source>, Push<Sink>>::default();
+    {
+        let migration = PushMigration {
+            projects: &mut project_map,
+            users: &mut user_map,
+        };
+        migration.migrate(source, sink, &mut push_map, &mut log)?;
     }
 
     // Environments
@@ -1077,7 +1572,7 @@ where
         let migration = EnvironmentMigration {
             projects: &mut project_map,
         };
-        migration.migrate(source, sink, &mut environment_map)?;
+        migration.migrate(source, sink, &mut environment_map, &mut log)?;
     }
 
     // Deployments
@@ -1088,7 +1583,7 @@ where
             environments: &mut environment_map,
             pipelines: &mut pipeline_map,
         };
-        migration.migrate(source, sink, &mut deployment_map)?;
+        migration.migrate(source, sink, &mut deployment_map, &mut log)?;
     }
 
     // Jobs
@@ -1100,7 +1595,7 @@ where
             runners: &mut runner_map,
             users: &mut user_map,
         };
-        migration.migrate(source, sink, &mut job_map)?;
+        migration.migrate(source, sink, &mut job_map, &mut log)?;
     }
 
     // Job artifacts
@@ -1110,8 +1605,124 @@ where
         let migration = JobArtifactMigration {
             jobs: &mut job_map,
         };
-        migration.migrate(source, sink, &mut job_artifact_map)?;
+        migration.migrate(source, sink, &mut job_artifact_map, &mut log)?;
     }
 
-    Ok(())
+    Ok(log.report)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use ci_monitor_core::data::{
+        Instance, Job, JobState, Pipeline, PipelineSource, PipelineStatus, Project, User,
+    };
+    use ci_monitor_core::Lookup;
+
+    use crate::{DiscoverableLookup, EntityType, MigrationError, VecLookup};
+
+    #[test]
+    fn validate_object_store_migration() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut source = VecLookup::default();
+        let instance = Instance::builder()
+            .unique_id(0)
+            .forge("forge")
+            .url("url")
+            .build()
+            .unwrap();
+        let instance = source.store(instance);
+        let user = User::builder()
+            .forge_id(0)
+            .instance(instance)
+            .build()
+            .unwrap();
+        let user = source.store(user);
+        let project = |instance| {
+            Project::builder()
+                .forge_id(1)
+                .instance(instance)
+                .url("project")
+                .build()
+                .unwrap()
+        };
+        let project_idx = source.store(project(instance));
+
+        let pipeline = |id| {
+            Pipeline::builder()
+                .project(project_idx)
+                .sha("0000000000000000000000000000000000000000")
+                .source(PipelineSource::Push)
+                .status(PipelineStatus::Success)
+                .forge_id(id)
+                .url("url")
+                .created_at(now)
+                .updated_at(now)
+                .build()
+                .unwrap()
+        };
+        // Indices for pipelines which have not been stored (yet).
+        let mut scratch = VecLookup::default();
+        let indices = [0, 1, 2, 3].map(|id| scratch.store(pipeline(id)));
+
+        // A child pipeline stored before its parent.
+        let mut child = pipeline(0);
+        child.parent_pipeline = Some(indices[1]);
+        let child = source.store(child);
+        source.store(pipeline(1));
+        // A pipeline with a parent missing from the store.
+        let mut orphan = pipeline(2);
+        orphan.parent_pipeline = Some(indices[3]);
+        source.store(orphan);
+
+        for (id, pipeline) in [(0, child), (1, indices[3])] {
+            let job = Job::builder()
+                .user(user)
+                .state(JobState::Success)
+                .created_at(now)
+                .forge_id(id)
+                .pipeline(pipeline)
+                .build()
+                .unwrap();
+            source.store(job);
+        }
+
+        let err = super::migrate_object_store(&source, &mut VecLookup::default()).unwrap_err();
+        assert!(matches!(err, MigrationError::DanglingSourceIndex { .. }));
+
+        // The sink already has the instance and project.
+        let mut sink = VecLookup::default();
+        let sink_instance = Instance::builder()
+            .unique_id(0)
+            .forge("forge")
+            .url("url")
+            .build()
+            .unwrap();
+        let sink_instance = sink.store(sink_instance);
+        sink.store(project(sink_instance));
+
+        let report = super::validate_object_store_migration(&source, &sink).unwrap();
+        assert_eq!(report.counts.get(&EntityType::Instance), Some(&1));
+        assert_eq!(report.counts.get(&EntityType::User), Some(&1));
+        assert_eq!(report.counts.get(&EntityType::Project), Some(&1));
+        assert_eq!(report.counts.get(&EntityType::Pipeline), Some(&2));
+        assert_eq!(report.counts.get(&EntityType::Job), Some(&1));
+        assert_eq!(report.total(), 6);
+        assert_eq!(report.errors.len(), 2);
+        assert!(report
+            .errors
+            .iter()
+            .all(|err| matches!(err, MigrationError::DanglingSourceIndex { .. })));
+        let duplicates = report
+            .duplicates
+            .iter()
+            .map(|duplicate| duplicate.entity)
+            .collect::<Vec<_>>();
+        assert_eq!(duplicates, [EntityType::Instance, EntityType::Project]);
+        assert!(!report.is_clean());
+
+        // The sink is not modified.
+        let users = <VecLookup as DiscoverableLookup<User<VecLookup>>>::all_indices(&sink);
+        assert!(users.is_empty());
+    }
 }
@@ -5,11 +5,49 @@
 // except according to those terms.
 
 mod arc;
+mod entity_type;
 mod vec;
 
 pub use arc::ArcIndex;
 pub use arc::ArcLookup;
 
+pub use entity_type::EntityType;
+
+#[cfg(feature = "duckdb")]
+pub use vec::export_duckdb;
+#[cfg(feature = "duckdb")]
+pub use vec::DuckDbExportError;
+
+#[cfg(feature = "kv")]
+pub use vec::KvLookup;
+#[cfg(feature = "kv")]
+pub use vec::KvStore;
+#[cfg(feature = "kv")]
+pub use vec::KvStoreError;
+
+#[cfg(feature = "search")]
+pub use vec::SearchHit;
+#[cfg(feature = "search")]
+pub use vec::SearchIndex;
+#[cfg(feature = "search")]
+pub use vec::SearchIndexError;
+#[cfg(feature = "search")]
+pub use vec::SearchIndexUpdate;
+#[cfg(feature = "search")]
+pub use vec::SearchKind;
+
+#[cfg(feature = "signing")]
+pub use vec::SignatureProblem;
+#[cfg(feature = "signing")]
+pub use vec::StoreKeyError;
+#[cfg(feature = "signing")]
+pub use vec::StoreSigningKey;
+#[cfg(feature = "signing")]
+pub use vec::StoreVerifyingKey;
+
+pub use vec::ManifestProblem;
+pub use vec::SharedVecLookup;
+pub use vec::SoftDeleteCounts;
 pub use vec::VecIndex;
 pub use vec::VecLookup;
 pub use vec::VecStore;
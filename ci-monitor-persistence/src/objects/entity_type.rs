@@ -0,0 +1,129 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/// The types of entities held by object stores.
+///
+/// Used to select which entities a store materializes when loading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum EntityType {
+    /// Audit entries.
+    AuditEntry,
+    /// Crawl sessions.
+    CrawlSession,
+    /// Deployments.
+    Deployment,
+    /// Environments.
+    Environment,
+    /// Instances.
+    Instance,
+    /// Jobs.
+    Job,
+    /// Job artifacts.
+    JobArtifact,
+    /// Merge requests.
+    MergeRequest,
+    /// Pipelines.
+    Pipeline,
+    /// Pipeline schedules.
+    PipelineSchedule,
+    /// Projects.
+    Project,
+    /// Pushes.
+    Push,
+    /// Releases.
+    Release,
+    /// Runners.
+    Runner,
+    /// Runner hosts.
+    RunnerHost,
+    /// Users.
+    User,
+}
+
+impl EntityType {
+    /// All entity types.
+    pub const ALL: &'static [Self] = &[
+        Self::AuditEntry,
+        Self::CrawlSession,
+        Self::Deployment,
+        Self::Environment,
+        Self::Instance,
+        Self::Job,
+        Self::JobArtifact,
+        Self::MergeRequest,
+        Self::Pipeline,
+        Self::PipelineSchedule,
+        Self::Project,
+        Self::Push,
+        Self::Release,
+        Self::Runner,
+        Self::RunnerHost,
+        Self::User,
+    ];
+
+    /// The name of the entity type.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::AuditEntry => "audit_entry",
+            Self::CrawlSession => "crawl_session",
+            Self::Deployment => "deployment",
+            Self::Environment => "environment",
+            Self::Instance => "instance",
+            Self::Job => "job",
+            Self::JobArtifact => "job_artifact",
+            Self::MergeRequest => "merge_request",
+            Self::Pipeline => "pipeline",
+            Self::PipelineSchedule => "pipeline_schedule",
+            Self::Project => "project",
+            Self::Push => "push",
+            Self::Release => "release",
+            Self::Runner => "runner",
+            Self::RunnerHost => "runner_host",
+            Self::User => "user",
+        }
+    }
+
+    /// The directory holding entities of the type within a store.
+    pub(crate) fn directory(self) -> &'static str {
+        match self {
+            Self::AuditEntry => "audit_entries",
+            Self::CrawlSession => "crawl_sessions",
+            Self::Deployment => "deployments",
+            Self::Environment => "environments",
+            Self::Instance => "instances",
+            Self::Job => "jobs",
+            Self::JobArtifact => "job_artifacts",
+            Self::MergeRequest => "merge_requests",
+            Self::Pipeline => "pipelines",
+            Self::PipelineSchedule => "pipeline_schedules",
+            Self::Project => "projects",
+            Self::Push => "pushes",
+            Self::Release => "releases",
+            Self::Runner => "runners",
+            Self::RunnerHost => "runner_hosts",
+            Self::User => "users",
+        }
+    }
+
+    /// Look up an entity type by its name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|ty| ty.name() == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::EntityType;
+
+    #[test]
+    fn entity_type_names() {
+        for &ty in EntityType::ALL {
+            assert_eq!(EntityType::from_name(ty.name()), Some(ty));
+        }
+        assert_eq!(EntityType::from_name("pipelines"), None);
+    }
+}
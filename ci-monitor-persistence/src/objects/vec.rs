@@ -8,44 +8,101 @@ use std::fmt::Debug;
 use std::marker::PhantomData;
 
 use ci_monitor_core::data::{
-    Deployment, Environment, Instance, Job, JobArtifact, MergeRequest, Pipeline, PipelineSchedule,
-    Project, Runner, RunnerHost, User,
+    AuditEntry, CrawlSession, Deployment, Environment, Instance, Job, JobArtifact, MergeRequest,
+    Pipeline, PipelineSchedule, Project, Push, Release, Runner, RunnerHost, User,
 };
 use ci_monitor_core::Lookup;
 use perfect_derive::perfect_derive;
 
 use crate::DiscoverableLookup;
 
+use self::lazy::LazyVec;
+
+mod archive;
 mod data;
+mod deletion;
+#[cfg(feature = "duckdb")]
+mod export;
 mod json;
+#[cfg(feature = "kv")]
+mod kv;
+mod lazy;
+mod manifest;
+mod merge;
 mod persist;
+mod rehash;
+#[cfg(feature = "search")]
+mod search;
+mod shared;
+#[cfg(feature = "signing")]
+mod signature;
 
+pub use self::deletion::SoftDeleteCounts;
+#[cfg(feature = "duckdb")]
+pub use self::export::export_duckdb;
+#[cfg(feature = "duckdb")]
+pub use self::export::DuckDbExportError;
+#[cfg(feature = "kv")]
+pub use self::kv::KvLookup;
+#[cfg(feature = "kv")]
+pub use self::kv::KvStore;
+#[cfg(feature = "kv")]
+pub use self::kv::KvStoreError;
+pub use self::manifest::ManifestProblem;
 pub use self::persist::VecStore;
 pub use self::persist::VecStoreError;
+#[cfg(feature = "search")]
+pub use self::search::SearchHit;
+#[cfg(feature = "search")]
+pub use self::search::SearchIndex;
+#[cfg(feature = "search")]
+pub use self::search::SearchIndexError;
+#[cfg(feature = "search")]
+pub use self::search::SearchIndexUpdate;
+#[cfg(feature = "search")]
+pub use self::search::SearchKind;
+pub use self::shared::SharedVecLookup;
+#[cfg(feature = "signing")]
+pub use self::signature::SignatureProblem;
+#[cfg(feature = "signing")]
+pub use self::signature::StoreKeyError;
+#[cfg(feature = "signing")]
+pub use self::signature::StoreSigningKey;
+#[cfg(feature = "signing")]
+pub use self::signature::StoreVerifyingKey;
 
 /// Storage for CI monitoring data backed by `Vec`.
 ///
 /// Intended only for in-memory storage; no actual persistence is offered as removing data is
 /// infeasible due to having to rewrite all indices to account for holes.
+///
+/// Stores loaded with `VecStore::load_only` may defer loading some entity types until they are
+/// first accessed.
 #[derive(Default, Clone)]
 pub struct VecLookup {
-    deployments: Vec<Deployment<Self>>,
-    environments: Vec<Environment<Self>>,
-    instances: Vec<Instance>,
-    jobs: Vec<Job<Self>>,
-    job_artifacts: Vec<JobArtifact<Self>>,
-    merge_requests: Vec<MergeRequest<Self>>,
-    pipelines: Vec<Pipeline<Self>>,
-    pipeline_schedules: Vec<PipelineSchedule<Self>>,
-    projects: Vec<Project<Self>>,
-    runners: Vec<Runner<Self>>,
-    runner_hosts: Vec<RunnerHost>,
-    users: Vec<User<Self>>,
+    audit_entries: LazyVec<AuditEntry>,
+    crawl_sessions: LazyVec<CrawlSession<Self>>,
+    deployments: LazyVec<Deployment<Self>>,
+    environments: LazyVec<Environment<Self>>,
+    instances: LazyVec<Instance>,
+    jobs: LazyVec<Job<Self>>,
+    job_artifacts: LazyVec<JobArtifact<Self>>,
+    merge_requests: LazyVec<MergeRequest<Self>>,
+    pipelines: LazyVec<Pipeline<Self>>,
+    pipeline_schedules: LazyVec<PipelineSchedule<Self>>,
+    projects: LazyVec<Project<Self>>,
+    pushes: LazyVec<Push<Self>>,
+    releases: LazyVec<Release<Self>>,
+    runners: LazyVec<Runner<Self>>,
+    runner_hosts: LazyVec<RunnerHost>,
+    users: LazyVec<User<Self>>,
 }
 
 impl Debug for VecLookup {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         f.debug_struct("VecLookup")
+            .field("#audit_entries", &self.audit_entries.len())
+            .field("#crawl_sessions", &self.crawl_sessions.len())
             .field("#deployments", &self.deployments.len())
             .field("#environments", &self.environments.len())
             .field("#instances", &self.instances.len())
@@ -55,6 +112,8 @@ impl Debug for VecLookup {
             .field("#pipelines", &self.pipelines.len())
             .field("#pipeline_schedules", &self.pipeline_schedules.len())
             .field("#projects", &self.projects.len())
+            .field("#pushes", &self.pushes.len())
+            .field("#releases", &self.releases.len())
             .field("#runners", &self.runners.len())
             .field("#runner_hosts", &self.runner_hosts.len())
             .field("#users", &self.users.len())
@@ -77,7 +136,7 @@ impl<T> Clone for VecIndex<T> {
 
 impl<T> PartialOrd for VecIndex<T> {
     fn partial_cmp(&self, rhs: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.idx.cmp(&rhs.idx))
+        Some(self.cmp(rhs))
     }
 }
 
@@ -96,9 +155,46 @@ impl<T> VecIndex<T> {
     }
 }
 
+/// Lookups which can resolve the instance an entity belongs to.
+trait InstanceLookup:
+    Lookup<Environment<VecLookup>, Index = VecIndex<Environment<VecLookup>>>
+    + Lookup<Pipeline<VecLookup>, Index = VecIndex<Pipeline<VecLookup>>>
+    + Lookup<Project<VecLookup>, Index = VecIndex<Project<VecLookup>>>
+{
+    fn project_instance(
+        &self,
+        project: &VecIndex<Project<VecLookup>>,
+    ) -> Option<VecIndex<Instance>> {
+        Lookup::<Project<VecLookup>>::lookup(self, project).map(|project| project.instance)
+    }
+}
+
+impl InstanceLookup for VecLookup {}
+
 trait HasId {
     fn id(&self) -> u64;
     fn has_id(&self, id: u64) -> bool;
+
+    /// The instance the entity belongs to.
+    ///
+    /// Forge IDs are only unique within an instance. Entities without a forge ID are not scoped
+    /// to an instance.
+    fn instance<L>(&self, _: &L) -> Option<VecIndex<Instance>>
+    where
+        L: InstanceLookup,
+    {
+        None
+    }
+
+    /// Whether the entity has an ID on an instance.
+    ///
+    /// Entities which are not scoped to an instance only need to have the ID.
+    fn has_id_in<L>(&self, lookup: &L, instance: &VecIndex<Instance>, id: u64) -> bool
+    where
+        L: InstanceLookup,
+    {
+        self.has_id(id) && self.instance(lookup).is_none_or(|found| found == *instance)
+    }
 }
 
 macro_rules! impl_has_id_by {
@@ -114,20 +210,64 @@ macro_rules! impl_has_id_by {
             }
         }
     };
+    ($t:ty, $field:ident, |$entity:ident, $lookup:ident| $instance:expr) => {
+        impl HasId for $t {
+            #[allow(clippy::misnamed_getters)]
+            fn id(&self) -> u64 {
+                self.$field
+            }
+
+            fn has_id(&self, id: u64) -> bool {
+                self.$field == id
+            }
+
+            fn instance<L>(&self, $lookup: &L) -> Option<VecIndex<Instance>>
+            where
+                L: InstanceLookup,
+            {
+                let $entity = self;
+                $instance
+            }
+        }
+    };
 }
 
-impl_has_id_by!(Deployment<VecLookup>, forge_id);
-impl_has_id_by!(Environment<VecLookup>, forge_id);
+impl_has_id_by!(AuditEntry, unique_id);
+impl_has_id_by!(CrawlSession<VecLookup>, revision);
+impl_has_id_by!(Deployment<VecLookup>, forge_id, |deployment, lookup| {
+    Lookup::<Environment<VecLookup>>::lookup(lookup, &deployment.environment)
+        .and_then(|environment| environment.instance(lookup))
+});
+impl_has_id_by!(Environment<VecLookup>, forge_id, |environment, lookup| {
+    lookup.project_instance(&environment.project)
+});
 impl_has_id_by!(Instance, unique_id);
-impl_has_id_by!(Job<VecLookup>, forge_id);
+impl_has_id_by!(Job<VecLookup>, forge_id, |job, lookup| {
+    Lookup::<Pipeline<VecLookup>>::lookup(lookup, &job.pipeline)
+        .and_then(|pipeline| pipeline.instance(lookup))
+});
 impl_has_id_by!(JobArtifact<VecLookup>, unique_id);
-impl_has_id_by!(MergeRequest<VecLookup>, forge_id);
-impl_has_id_by!(Pipeline<VecLookup>, forge_id);
-impl_has_id_by!(PipelineSchedule<VecLookup>, forge_id);
-impl_has_id_by!(Project<VecLookup>, forge_id);
-impl_has_id_by!(Runner<VecLookup>, forge_id);
+impl_has_id_by!(MergeRequest<VecLookup>, forge_id, |merge_request, lookup| {
+    lookup.project_instance(&merge_request.target_project)
+});
+impl_has_id_by!(Pipeline<VecLookup>, forge_id, |pipeline, lookup| {
+    lookup.project_instance(&pipeline.project)
+});
+impl_has_id_by!(PipelineSchedule<VecLookup>, forge_id, |schedule, lookup| {
+    lookup.project_instance(&schedule.project)
+});
+impl_has_id_by!(Project<VecLookup>, forge_id, |project, _lookup| {
+    Some(project.instance)
+});
+impl_has_id_by!(Push<VecLookup>, forge_id, |push, lookup| {
+    lookup.project_instance(&push.project)
+});
+impl_has_id_by!(Release<VecLookup>, unique_id);
+impl_has_id_by!(Runner<VecLookup>, forge_id, |runner, _lookup| {
+    Some(runner.instance)
+});
 impl_has_id_by!(RunnerHost, unique_id);
-impl_has_id_by!(User<VecLookup>, forge_id);
+impl_has_id_by!(User<VecLookup>, forge_id, |user, _lookup| Some(user.instance));
 
 macro_rules! impl_lookup {
     ($t:ty, $field:ident) => {
@@ -135,21 +275,26 @@ macro_rules! impl_lookup {
             type Index = VecIndex<$t>;
 
             fn lookup<'a>(&'a self, idx: &'a Self::Index) -> Option<&'a $t> {
-                self.$field.get(idx.idx)
+                self.$field.entities(self).get(idx.idx)
             }
 
             fn store(&mut self, data: $t) -> Self::Index {
-                if let Some((idx, entry)) = self
+                // A failure to load is kept and reported when the store is written.
+                let _ = self.$field.force(self);
+                // The same forge ID on another instance is another entity.
+                let instance = data.instance(self);
+                let existing = self
                     .$field
-                    .iter_mut()
-                    .enumerate()
-                    .find(|(_, e)| e.has_id(data.id()))
-                {
-                    *entry = data;
+                    .entities(self)
+                    .iter()
+                    .position(|e| e.has_id(data.id()) && e.instance(self) == instance);
+                let entities = self.$field.loaded_mut();
+                if let Some(idx) = existing {
+                    entities[idx] = data;
                     Self::Index::new(idx)
                 } else {
-                    let idx = self.$field.len();
-                    self.$field.push(data);
+                    let idx = entities.len();
+                    entities.push(data);
                     Self::Index::new(idx.into())
                 }
             }
@@ -157,20 +302,33 @@ macro_rules! impl_lookup {
 
         impl DiscoverableLookup<$t> for VecLookup {
             fn all_indices(&self) -> Vec<Self::Index> {
-                (0..self.$field.len()).map(Self::Index::new).collect()
+                (0..self.$field.entities(self).len())
+                    .map(Self::Index::new)
+                    .collect()
             }
 
             fn find(&self, id: u64) -> Option<Self::Index> {
                 self.$field
+                    .entities(self)
                     .iter()
                     .enumerate()
                     .find(|(_, ent)| ent.has_id(id))
                     .map(|(idx, _)| Self::Index::new(idx))
             }
+
+            fn find_in(&self, instance: &VecIndex<Instance>, id: u64) -> Option<Self::Index> {
+                self.$field
+                    .entities(self)
+                    .iter()
+                    .position(|ent| ent.has_id_in(self, instance, id))
+                    .map(Self::Index::new)
+            }
         }
     };
 }
 
+impl_lookup!(AuditEntry, audit_entries);
+impl_lookup!(CrawlSession<Self>, crawl_sessions);
 impl_lookup!(Deployment<Self>, deployments);
 impl_lookup!(Environment<Self>, environments);
 impl_lookup!(Instance, instances);
@@ -180,6 +338,69 @@ impl_lookup!(MergeRequest<Self>, merge_requests);
 impl_lookup!(Pipeline<Self>, pipelines);
 impl_lookup!(PipelineSchedule<Self>, pipeline_schedules);
 impl_lookup!(Project<Self>, projects);
+impl_lookup!(Push<Self>, pushes);
+impl_lookup!(Release<Self>, releases);
 impl_lookup!(Runner<Self>, runners);
 impl_lookup!(RunnerHost, runner_hosts);
 impl_lookup!(User<Self>, users);
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use ci_monitor_core::data::{Instance, Pipeline, PipelineSource, PipelineStatus, Project};
+    use ci_monitor_core::Lookup;
+
+    use crate::test::{instance, project};
+    use crate::{DiscoverableLookup, VecLookup};
+
+    #[test]
+    fn find_in_instance() {
+        let mut lookup = VecLookup::default();
+        let first = instance(&mut lookup);
+        let second = Instance::builder()
+            .unique_id(1)
+            .forge("forge")
+            .url("other")
+            .build()
+            .unwrap();
+        let second = lookup.store(second);
+        let first_project = project(&mut lookup, first, 1);
+        let second_project = project(&mut lookup, second, 1);
+        let now = Utc::now();
+        let pipelines = [first_project, second_project].map(|project| {
+            let pipeline = Pipeline::builder()
+                .project(project)
+                .sha("0000000000000000000000000000000000000000")
+                .source(PipelineSource::Push)
+                .status(PipelineStatus::Success)
+                .forge_id(2)
+                .url("url")
+                .created_at(now)
+                .updated_at(now)
+                .build()
+                .unwrap();
+            lookup.store(pipeline)
+        });
+        assert_ne!(pipelines[0], pipelines[1]);
+
+        let find_project = |instance, id| {
+            <VecLookup as DiscoverableLookup<Project<VecLookup>>>::find_in(&lookup, instance, id)
+        };
+        assert_eq!(find_project(&first, 1), Some(first_project));
+        assert_eq!(find_project(&second, 1), Some(second_project));
+        assert_eq!(find_project(&first, 2), None);
+
+        // Pipelines are scoped through their project.
+        let find_pipeline = |instance, id| {
+            <VecLookup as DiscoverableLookup<Pipeline<VecLookup>>>::find_in(&lookup, instance, id)
+        };
+        assert_eq!(find_pipeline(&first, 2), Some(pipelines[0]));
+        assert_eq!(find_pipeline(&second, 2), Some(pipelines[1]));
+
+        // Instances are not scoped to an instance.
+        assert_eq!(
+            <VecLookup as DiscoverableLookup<Instance>>::find_in(&lookup, &first, 1),
+            Some(second),
+        );
+    }
+}
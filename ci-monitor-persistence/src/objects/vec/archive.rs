@@ -0,0 +1,201 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use ci_monitor_core::data::{ArtifactKind, ArtifactState};
+
+use crate::blob::BATCH_SIZE;
+use crate::{BlobPersistence, BlobPersistenceVerifyError, Tiered, VecLookup};
+
+impl VecLookup {
+    /// Move the logs of old jobs into the cold tier of blob storage.
+    ///
+    /// Stored logs of jobs which finished before `before` are demoted in batches and their
+    /// artifacts are marked as archived. Blob references are unchanged, so readers fetching through
+    /// `blobs` still find the logs. If an error occurs, blobs demoted so far remain readable and are
+    /// skipped when archiving is attempted again.
+    ///
+    /// Returns the number of artifacts which were archived.
+    pub fn archive_job_logs<H, C>(
+        &mut self,
+        blobs: &Tiered<H, C>,
+        before: DateTime<Utc>,
+    ) -> Result<usize, BlobPersistenceVerifyError>
+    where
+        H: BlobPersistence,
+        C: BlobPersistence,
+    {
+        let jobs = self.jobs.entities(self);
+        let mut old_logs = Vec::new();
+        let mut seen = HashSet::new();
+        let mut pending = Vec::new();
+
+        for (idx, artifact) in self.job_artifacts.entities(self).iter().enumerate() {
+            if artifact.kind != ArtifactKind::JobLog || artifact.state != ArtifactState::Stored {
+                continue;
+            }
+            let blob_ref = if let Some(blob_ref) = artifact.blob.as_ref() {
+                blob_ref
+            } else {
+                continue;
+            };
+            let is_old = jobs
+                .get(artifact.job.idx)
+                .and_then(|job| job.finished_at)
+                .is_some_and(|finished_at| finished_at < before);
+            if !is_old {
+                continue;
+            }
+
+            old_logs.push(idx);
+            if seen.insert(blob_ref) {
+                pending.push(blob_ref.clone());
+            }
+        }
+
+        for batch in pending.chunks(BATCH_SIZE) {
+            for res in blobs.demote_many(batch) {
+                res?;
+            }
+        }
+
+        let job_artifacts = self.job_artifacts.loaded_mut();
+        for &idx in &old_logs {
+            job_artifacts[idx].state = ArtifactState::Archived;
+        }
+
+        Ok(old_logs.len())
+    }
+}
+
+#[cfg(all(test, feature = "filesystem"))]
+mod tests {
+    use std::fs;
+
+    use chrono::{Duration, Utc};
+    use ci_monitor_core::data::{
+        ArtifactKind, ArtifactState, Blob, ContentHash, Instance, Job, JobArtifact, JobState,
+        Pipeline, PipelineSource, PipelineStatus, Project, User,
+    };
+    use ci_monitor_core::Lookup;
+    use tempfile::TempDir;
+
+    use crate::{BlobPersistence, Filesystem, Sharding, Tiered, VecLookup};
+
+    #[test]
+    fn archive_job_logs() {
+        let workdir = TempDir::new().unwrap();
+        let create = |name: &str| {
+            let path = workdir.path().join(name);
+            fs::create_dir(&path).unwrap();
+            Filesystem::create(path, ContentHash::Sha256, Sharding::default()).unwrap()
+        };
+        let blobs = Tiered::new(create("hot")).with_cold_tier(create("cold"));
+
+        let mut lookup = VecLookup::default();
+        let instance = Instance::builder()
+            .unique_id(0)
+            .forge("forge")
+            .url("url")
+            .build()
+            .unwrap();
+        let instance = lookup.store(instance);
+        let user = User::builder()
+            .forge_id(0)
+            .instance(instance)
+            .build()
+            .unwrap();
+        let user = lookup.store(user);
+        let project = Project::builder()
+            .forge_id(0)
+            .instance(instance)
+            .url("project")
+            .build()
+            .unwrap();
+        let project = lookup.store(project);
+        let now = Utc::now();
+        let pipeline = Pipeline::builder()
+            .project(project)
+            .sha("0000000000000000000000000000000000000000")
+            .source(PipelineSource::Push)
+            .status(PipelineStatus::Success)
+            .forge_id(0)
+            .url("url")
+            .created_at(now)
+            .updated_at(now)
+            .build()
+            .unwrap();
+        let pipeline = lookup.store(pipeline);
+
+        // (finished days ago, kind)
+        let jobs = [
+            (Some(30), ArtifactKind::JobLog),
+            (Some(30), ArtifactKind::JobLog),
+            (Some(1), ArtifactKind::JobLog),
+            (None, ArtifactKind::JobLog),
+            (Some(30), ArtifactKind::JUnit),
+        ];
+        let artifacts = jobs
+            .into_iter()
+            .enumerate()
+            .map(|(id, (finished, kind))| {
+                let job = Job::builder()
+                    .user(user)
+                    .state(JobState::Success)
+                    .created_at(now - Duration::days(60))
+                    .finished_at(finished.map(|days| now - Duration::days(days)))
+                    .forge_id(id as u64)
+                    .pipeline(pipeline)
+                    .build()
+                    .unwrap();
+                let job = lookup.store(job);
+
+                // The first two jobs have identical logs.
+                let log = Blob::new(format!("job log {}", id.max(1)).into_bytes());
+                let mut artifact = JobArtifact::builder()
+                    .state(ArtifactState::Stored)
+                    .kind(kind)
+                    .name("job.log")
+                    .size(log.len() as u64)
+                    .unique_id(id as u64)
+                    .job(job)
+                    .build()
+                    .unwrap();
+                artifact.blob = Some(blobs.store(&log).unwrap());
+                lookup.store(artifact)
+            })
+            .collect::<Vec<_>>();
+
+        let before = now - Duration::days(7);
+        assert_eq!(lookup.archive_job_logs(&blobs, before).unwrap(), 2);
+        // Archived logs are skipped.
+        assert_eq!(lookup.archive_job_logs(&blobs, before).unwrap(), 0);
+
+        let states = artifacts
+            .iter()
+            .map(|idx| {
+                let artifact =
+                    <VecLookup as Lookup<JobArtifact<VecLookup>>>::lookup(&lookup, idx).unwrap();
+                let blob_ref = artifact.blob.as_ref().unwrap();
+                // Logs are available regardless of their tier.
+                assert!(blobs.contains(blob_ref).unwrap());
+                (artifact.state, blobs.hot().contains(blob_ref).unwrap())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            states,
+            [
+                (ArtifactState::Archived, false),
+                (ArtifactState::Archived, false),
+                (ArtifactState::Stored, true),
+                (ArtifactState::Stored, true),
+                (ArtifactState::Stored, true),
+            ],
+        );
+    }
+}
@@ -4,12 +4,15 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+use std::io;
+
 use ci_monitor_core::data::{
-    Deployment, Environment, Instance, Job, JobArtifact, MergeRequest, Pipeline, PipelineSchedule,
-    Project, Runner, RunnerHost, User,
+    AuditEntry, CrawlSession, Deployment, Environment, Instance, Job, JobArtifact, MergeRequest,
+    Pipeline, PipelineSchedule, Project, Push, Release, Runner, RunnerHost, User,
 };
 
 use super::json::{self, JsonConvert};
+use super::lazy::LazyVec;
 use super::{VecIndex, VecLookup, VecStoreError};
 
 trait Typename {
@@ -26,6 +29,8 @@ macro_rules! impl_typename {
     };
 }
 
+impl_typename!(AuditEntry, "audit entry");
+impl_typename!(CrawlSession<VecLookup>, "crawl session");
 impl_typename!(Deployment<VecLookup>, "deployment");
 impl_typename!(Environment<VecLookup>, "environment");
 impl_typename!(Instance, "instance");
@@ -35,6 +40,8 @@ impl_typename!(MergeRequest<VecLookup>, "merge request");
 impl_typename!(Pipeline<VecLookup>, "pipeline");
 impl_typename!(PipelineSchedule<VecLookup>, "pipeline schedule");
 impl_typename!(Project<VecLookup>, "project");
+impl_typename!(Push<VecLookup>, "push");
+impl_typename!(Release<VecLookup>, "release");
 impl_typename!(Runner<VecLookup>, "runner");
 impl_typename!(RunnerHost, "runner host");
 impl_typename!(User<VecLookup>, "user");
@@ -42,13 +49,19 @@ impl_typename!(User<VecLookup>, "user");
 pub(super) trait JsonStorable: Sized {
     type Json: JsonConvert<Self>;
 
-    fn to_json(&self) -> Result<serde_json::Value, serde_json::Error> {
+    /// Serialize directly to a writer without an intermediate `serde_json::Value`.
+    fn write_json<W>(&self, writer: W) -> Result<(), serde_json::Error>
+    where
+        W: io::Write,
+    {
         let json = Self::Json::convert_to_json(self);
-        serde_json::to_value(json)
+        serde_json::to_writer_pretty(writer, &json)
     }
 
-    fn from_json(json: serde_json::Value) -> Result<Self, VecStoreError> {
-        let value: Self::Json = serde_json::from_value(json)?;
+    /// Deserialize directly from the contents of a file without an intermediate
+    /// `serde_json::Value`.
+    fn read_json(data: &[u8]) -> Result<Self, VecStoreError> {
+        let value: Self::Json = serde_json::from_slice(data)?;
         value.create_from_json()
     }
 
@@ -63,10 +76,10 @@ pub(super) trait JsonStorable: Sized {
     }
 }
 
-#[allow(clippy::ptr_arg)] // Ensure we're dealing with the entire set of entities.
+/// Entities which have not been loaded yet are not loaded to validate references to them.
 fn validate_index<T, F>(
     from_index: &VecIndex<F>,
-    storage: &Vec<T>,
+    storage: &LazyVec<T>,
     index: &VecIndex<T>,
 ) -> Result<(), VecStoreError>
 where
@@ -85,6 +98,29 @@ where
     Ok(())
 }
 
+impl JsonStorable for AuditEntry {
+    type Json = json::AuditEntryJson;
+}
+
+impl JsonStorable for CrawlSession<VecLookup> {
+    type Json = json::CrawlSessionJson;
+
+    fn validate_indices(
+        &self,
+        self_index: VecIndex<Self>,
+        storage: &VecLookup,
+    ) -> Result<(), VecStoreError> {
+        for instance in &self.instances {
+            validate_index(&self_index, &storage.instances, instance)?;
+        }
+        for usage in &self.api_usage {
+            validate_index(&self_index, &storage.instances, &usage.instance)?;
+        }
+
+        Ok(())
+    }
+}
+
 impl JsonStorable for Deployment<VecLookup> {
     type Json = json::DeploymentJson;
 
@@ -224,6 +260,43 @@ impl JsonStorable for Project<VecLookup> {
     }
 }
 
+impl JsonStorable for Push<VecLookup> {
+    type Json = json::PushJson;
+
+    fn validate_indices(
+        &self,
+        self_index: VecIndex<Self>,
+        storage: &VecLookup,
+    ) -> Result<(), VecStoreError> {
+        validate_index(&self_index, &storage.projects, &self.project)?;
+        if let Some(author) = self.author.as_ref() {
+            validate_index(&self_index, &storage.users, author)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl JsonStorable for Release<VecLookup> {
+    type Json = json::ReleaseJson;
+
+    fn validate_indices(
+        &self,
+        self_index: VecIndex<Self>,
+        storage: &VecLookup,
+    ) -> Result<(), VecStoreError> {
+        validate_index(&self_index, &storage.projects, &self.project)?;
+        if let Some(pipeline) = self.pipeline.as_ref() {
+            validate_index(&self_index, &storage.pipelines, pipeline)?;
+        }
+        if let Some(author) = self.author.as_ref() {
+            validate_index(&self_index, &storage.users, author)?;
+        }
+
+        Ok(())
+    }
+}
+
 impl JsonStorable for Runner<VecLookup> {
     type Json = json::RunnerJson;
 
@@ -0,0 +1,331 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, Utc};
+use ci_monitor_core::data::{Pipeline, Project};
+
+use crate::{VecIndex, VecLookup};
+
+/// The number of entities affected by a soft deletion (or its reversal).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SoftDeleteCounts {
+    /// The number of projects.
+    pub projects: usize,
+    /// The number of merge requests.
+    pub merge_requests: usize,
+    /// The number of pipeline schedules.
+    pub pipeline_schedules: usize,
+    /// The number of pipelines.
+    pub pipelines: usize,
+    /// The number of jobs.
+    pub jobs: usize,
+    /// The number of environments.
+    pub environments: usize,
+    /// The number of deployments.
+    pub deployments: usize,
+    /// The number of releases.
+    pub releases: usize,
+    /// The number of pushes.
+    pub pushes: usize,
+}
+
+impl SoftDeleteCounts {
+    /// The total number of entities affected.
+    pub fn total(&self) -> usize {
+        self.projects
+            + self.merge_requests
+            + self.pipeline_schedules
+            + self.pipelines
+            + self.jobs
+            + self.environments
+            + self.deployments
+            + self.releases
+            + self.pushes
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Marking {
+    /// Mark entities which are not already deleted.
+    Delete(DateTime<Utc>),
+    /// Clear markers set at the given time.
+    Undelete(DateTime<Utc>),
+}
+
+impl Marking {
+    fn apply(self, marker: &mut Option<DateTime<Utc>>) -> bool {
+        match self {
+            Self::Delete(when) => {
+                if marker.is_none() {
+                    *marker = Some(when);
+                    true
+                } else {
+                    false
+                }
+            },
+            Self::Undelete(when) => {
+                if *marker == Some(when) {
+                    *marker = None;
+                    true
+                } else {
+                    false
+                }
+            },
+        }
+    }
+}
+
+impl VecLookup {
+    /// Mark a project and everything belonging to it as deleted.
+    ///
+    /// Deletion cascades to the project's merge requests, pipeline schedules, pipelines, jobs,
+    /// environments, deployments, releases, and pushes. Entities which are already deleted keep
+    /// their original marker. Deleted entities remain in the store until purged.
+    pub fn soft_delete_project(
+        &mut self,
+        idx: &VecIndex<Project<Self>>,
+        when: DateTime<Utc>,
+    ) -> SoftDeleteCounts {
+        self.mark_project(idx.idx, Marking::Delete(when))
+    }
+
+    /// Restore a project deleted by `soft_delete_project`.
+    ///
+    /// Only entities deleted along with the project are restored.
+    pub fn undelete_project(&mut self, idx: &VecIndex<Project<Self>>) -> SoftDeleteCounts {
+        let when = self
+            .projects
+            .entities(self)
+            .get(idx.idx)
+            .and_then(|project| project.cim_deleted_at);
+        if let Some(when) = when {
+            self.mark_project(idx.idx, Marking::Undelete(when))
+        } else {
+            SoftDeleteCounts::default()
+        }
+    }
+
+    /// Mark a pipeline and its jobs and deployments as deleted.
+    ///
+    /// Entities which are already deleted keep their original marker. Deleted entities remain in
+    /// the store until purged.
+    pub fn soft_delete_pipeline(
+        &mut self,
+        idx: &VecIndex<Pipeline<Self>>,
+        when: DateTime<Utc>,
+    ) -> SoftDeleteCounts {
+        self.mark_pipelines(&[idx.idx].into(), &BTreeSet::new(), Marking::Delete(when))
+    }
+
+    /// Restore a pipeline deleted by `soft_delete_pipeline`.
+    ///
+    /// Only entities deleted along with the pipeline are restored.
+    pub fn undelete_pipeline(&mut self, idx: &VecIndex<Pipeline<Self>>) -> SoftDeleteCounts {
+        let when = self
+            .pipelines
+            .entities(self)
+            .get(idx.idx)
+            .and_then(|pipeline| pipeline.cim_deleted_at);
+        if let Some(when) = when {
+            self.mark_pipelines(&[idx.idx].into(), &BTreeSet::new(), Marking::Undelete(when))
+        } else {
+            SoftDeleteCounts::default()
+        }
+    }
+
+    fn mark_project(&mut self, project: usize, marking: Marking) -> SoftDeleteCounts {
+        self.load_for_update();
+        let mut counts = SoftDeleteCounts::default();
+        if let Some(entry) = self.projects.loaded_mut().get_mut(project) {
+            counts.projects += usize::from(marking.apply(&mut entry.cim_deleted_at));
+        } else {
+            return counts;
+        }
+
+        for merge_request in self.merge_requests.loaded_mut() {
+            if merge_request.target_project.idx == project {
+                counts.merge_requests +=
+                    usize::from(marking.apply(&mut merge_request.cim_deleted_at));
+            }
+        }
+        for schedule in self.pipeline_schedules.loaded_mut() {
+            if schedule.project.idx == project {
+                counts.pipeline_schedules +=
+                    usize::from(marking.apply(&mut schedule.cim_deleted_at));
+            }
+        }
+        for release in self.releases.loaded_mut() {
+            if release.project.idx == project {
+                counts.releases += usize::from(marking.apply(&mut release.cim_deleted_at));
+            }
+        }
+        for push in self.pushes.loaded_mut() {
+            if push.project.idx == project {
+                counts.pushes += usize::from(marking.apply(&mut push.cim_deleted_at));
+            }
+        }
+
+        let mut environments = BTreeSet::new();
+        for (idx, environment) in self.environments.loaded_mut().iter_mut().enumerate() {
+            if environment.project.idx == project {
+                environments.insert(idx);
+                counts.environments += usize::from(marking.apply(&mut environment.cim_deleted_at));
+            }
+        }
+
+        let pipelines = self
+            .pipelines
+            .entities(self)
+            .iter()
+            .enumerate()
+            .filter(|(_, pipeline)| pipeline.project.idx == project)
+            .map(|(idx, _)| idx)
+            .collect();
+        let pipeline_counts = self.mark_pipelines(&pipelines, &environments, marking);
+        counts.pipelines = pipeline_counts.pipelines;
+        counts.jobs = pipeline_counts.jobs;
+        counts.deployments = pipeline_counts.deployments;
+
+        counts
+    }
+
+    fn mark_pipelines(
+        &mut self,
+        pipelines: &BTreeSet<usize>,
+        environments: &BTreeSet<usize>,
+        marking: Marking,
+    ) -> SoftDeleteCounts {
+        self.load_for_update();
+        let mut counts = SoftDeleteCounts::default();
+
+        for &idx in pipelines {
+            if let Some(pipeline) = self.pipelines.loaded_mut().get_mut(idx) {
+                counts.pipelines += usize::from(marking.apply(&mut pipeline.cim_deleted_at));
+            }
+        }
+        for job in self.jobs.loaded_mut() {
+            if pipelines.contains(&job.pipeline.idx) {
+                counts.jobs += usize::from(marking.apply(&mut job.cim_deleted_at));
+            }
+        }
+        for deployment in self.deployments.loaded_mut() {
+            if pipelines.contains(&deployment.pipeline.idx)
+                || environments.contains(&deployment.environment.idx)
+            {
+                counts.deployments += usize::from(marking.apply(&mut deployment.cim_deleted_at));
+            }
+        }
+
+        counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Duration, TimeZone, Utc};
+    use ci_monitor_core::data::{
+        Instance, Job, JobState, Pipeline, PipelineSource, PipelineStatus, Project, User,
+    };
+    use ci_monitor_core::Lookup;
+
+    use crate::VecLookup;
+
+    fn at(minute: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap() + Duration::minutes(minute)
+    }
+
+    #[test]
+    fn soft_delete_cascades() {
+        let mut lookup = VecLookup::default();
+        let instance = Instance::builder()
+            .unique_id(0)
+            .forge("forge")
+            .url("url")
+            .build()
+            .unwrap();
+        let instance = lookup.store(instance);
+        let user = User::builder()
+            .forge_id(0)
+            .instance(instance)
+            .build()
+            .unwrap();
+        let user = lookup.store(user);
+        let projects = [0, 1].map(|id| {
+            let project = Project::builder()
+                .forge_id(id)
+                .instance(instance)
+                .url("project")
+                .build()
+                .unwrap();
+            lookup.store(project)
+        });
+        let pipelines =
+            [(0, projects[0]), (1, projects[0]), (2, projects[1])].map(|(id, project)| {
+                let pipeline = Pipeline::builder()
+                    .project(project)
+                    .sha("0000000000000000000000000000000000000000")
+                    .source(PipelineSource::Push)
+                    .status(PipelineStatus::Success)
+                    .forge_id(id)
+                    .url("url")
+                    .created_at(at(0))
+                    .updated_at(at(0))
+                    .build()
+                    .unwrap();
+                lookup.store(pipeline)
+            });
+        let jobs = pipelines.map(|pipeline| {
+            let id = <VecLookup as Lookup<Pipeline<VecLookup>>>::lookup(&lookup, &pipeline)
+                .unwrap()
+                .forge_id;
+            let job = Job::builder()
+                .user(user)
+                .state(JobState::Success)
+                .created_at(at(0))
+                .forge_id(id)
+                .pipeline(pipeline)
+                .build()
+                .unwrap();
+            lookup.store(job)
+        });
+
+        // Delete a pipeline on its own first.
+        let counts = lookup.soft_delete_pipeline(&pipelines[1], at(1));
+        assert_eq!(counts.pipelines, 1);
+        assert_eq!(counts.jobs, 1);
+
+        let counts = lookup.soft_delete_project(&projects[0], at(2));
+        assert_eq!(counts.projects, 1);
+        assert_eq!(counts.pipelines, 1);
+        assert_eq!(counts.jobs, 1);
+        assert_eq!(counts.total(), 3);
+
+        let deleted_at = |lookup: &VecLookup, job| {
+            <VecLookup as Lookup<Job<VecLookup>>>::lookup(lookup, job)
+                .unwrap()
+                .cim_deleted_at
+        };
+        assert_eq!(deleted_at(&lookup, &jobs[0]), Some(at(2)));
+        assert_eq!(deleted_at(&lookup, &jobs[1]), Some(at(1)));
+        assert_eq!(deleted_at(&lookup, &jobs[2]), None);
+
+        // Only entities deleted with the project are restored.
+        let counts = lookup.undelete_project(&projects[0]);
+        assert_eq!(counts.total(), 3);
+        assert_eq!(deleted_at(&lookup, &jobs[0]), None);
+        assert_eq!(deleted_at(&lookup, &jobs[1]), Some(at(1)));
+
+        let counts = lookup.undelete_project(&projects[0]);
+        assert_eq!(counts.total(), 0);
+
+        let counts = lookup.undelete_pipeline(&pipelines[1]);
+        assert_eq!(counts.total(), 2);
+        assert_eq!(deleted_at(&lookup, &jobs[1]), None);
+    }
+}
@@ -0,0 +1,497 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use ci_monitor_core::data::Elapsed;
+use duckdb::{params, Connection};
+use thiserror::Error;
+
+use super::{VecIndex, VecLookup, VecStoreError};
+
+/// The schema of exported databases.
+///
+/// Tables are keyed by the index of entities in the store. References between entities are
+/// foreign keys except for the parent of child pipelines (which may be stored after the child).
+/// All times are in UTC.
+const SCHEMA: &str = "
+CREATE TABLE instances (
+    id UBIGINT PRIMARY KEY,
+    unique_id UBIGINT NOT NULL,
+    forge VARCHAR NOT NULL,
+    url VARCHAR NOT NULL
+);
+CREATE TABLE users (
+    id UBIGINT PRIMARY KEY,
+    instance UBIGINT NOT NULL REFERENCES instances (id),
+    forge_id UBIGINT NOT NULL,
+    handle VARCHAR NOT NULL,
+    name VARCHAR NOT NULL
+);
+CREATE TABLE projects (
+    id UBIGINT PRIMARY KEY,
+    instance UBIGINT NOT NULL REFERENCES instances (id),
+    forge_id UBIGINT NOT NULL,
+    name VARCHAR NOT NULL,
+    instance_path VARCHAR NOT NULL,
+    url VARCHAR NOT NULL,
+    default_branch VARCHAR,
+    deleted_at TIMESTAMP,
+    removed_from_group_at TIMESTAMP
+);
+CREATE TABLE runners (
+    id UBIGINT PRIMARY KEY,
+    instance UBIGINT NOT NULL REFERENCES instances (id),
+    forge_id UBIGINT NOT NULL,
+    description VARCHAR NOT NULL,
+    runner_type VARCHAR NOT NULL,
+    platform VARCHAR NOT NULL,
+    architecture VARCHAR NOT NULL,
+    version VARCHAR NOT NULL,
+    paused BOOLEAN NOT NULL,
+    online BOOLEAN NOT NULL,
+    contacted_at TIMESTAMP
+);
+CREATE TABLE merge_requests (
+    id UBIGINT PRIMARY KEY,
+    source_project UBIGINT NOT NULL REFERENCES projects (id),
+    target_project UBIGINT NOT NULL REFERENCES projects (id),
+    author UBIGINT NOT NULL REFERENCES users (id),
+    forge_id UBIGINT NOT NULL,
+    iid UBIGINT NOT NULL,
+    title VARCHAR NOT NULL,
+    source_branch VARCHAR NOT NULL,
+    target_branch VARCHAR NOT NULL,
+    state VARCHAR NOT NULL,
+    url VARCHAR NOT NULL,
+    created_at TIMESTAMP,
+    merged_at TIMESTAMP,
+    deleted_at TIMESTAMP
+);
+CREATE TABLE pipelines (
+    id UBIGINT PRIMARY KEY,
+    project UBIGINT NOT NULL REFERENCES projects (id),
+    parent_pipeline UBIGINT,
+    merge_request UBIGINT REFERENCES merge_requests (id),
+    author UBIGINT REFERENCES users (id),
+    forge_id UBIGINT NOT NULL,
+    sha VARCHAR NOT NULL,
+    refname VARCHAR,
+    source VARCHAR NOT NULL,
+    status VARCHAR NOT NULL,
+    url VARCHAR NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL,
+    started_at TIMESTAMP,
+    finished_at TIMESTAMP,
+    deleted_at TIMESTAMP
+);
+CREATE TABLE jobs (
+    id UBIGINT PRIMARY KEY,
+    pipeline UBIGINT NOT NULL REFERENCES pipelines (id),
+    author UBIGINT NOT NULL REFERENCES users (id),
+    runner UBIGINT REFERENCES runners (id),
+    forge_id UBIGINT NOT NULL,
+    name VARCHAR NOT NULL,
+    stage VARCHAR NOT NULL,
+    state VARCHAR NOT NULL,
+    allow_failure BOOLEAN NOT NULL,
+    manual BOOLEAN NOT NULL,
+    url VARCHAR NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    started_at TIMESTAMP,
+    finished_at TIMESTAMP,
+    queued_duration DOUBLE,
+    deleted_at TIMESTAMP
+);
+CREATE TABLE environments (
+    id UBIGINT PRIMARY KEY,
+    project UBIGINT NOT NULL REFERENCES projects (id),
+    forge_id UBIGINT NOT NULL,
+    name VARCHAR NOT NULL,
+    tier VARCHAR NOT NULL,
+    state VARCHAR NOT NULL,
+    deleted_at TIMESTAMP
+);
+CREATE TABLE deployments (
+    id UBIGINT PRIMARY KEY,
+    pipeline UBIGINT NOT NULL REFERENCES pipelines (id),
+    environment UBIGINT NOT NULL REFERENCES environments (id),
+    forge_id UBIGINT NOT NULL,
+    status VARCHAR NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    finished_at TIMESTAMP,
+    deleted_at TIMESTAMP
+);
+
+CREATE VIEW pipeline_durations AS
+SELECT
+    pipelines.id AS pipeline,
+    pipelines.forge_id,
+    projects.instance_path AS project,
+    pipelines.refname,
+    pipelines.status,
+    pipelines.started_at,
+    pipelines.finished_at,
+    date_diff('millisecond', pipelines.started_at, pipelines.finished_at) / 1000 AS duration
+FROM pipelines
+JOIN projects ON projects.id = pipelines.project
+WHERE pipelines.started_at IS NOT NULL
+    AND pipelines.finished_at IS NOT NULL
+    AND pipelines.deleted_at IS NULL;
+
+CREATE VIEW job_failures AS
+SELECT
+    jobs.id AS job,
+    jobs.forge_id,
+    projects.instance_path AS project,
+    pipelines.refname,
+    jobs.name,
+    jobs.stage,
+    jobs.finished_at,
+    jobs.url
+FROM jobs
+JOIN pipelines ON pipelines.id = jobs.pipeline
+JOIN projects ON projects.id = pipelines.project
+WHERE jobs.state = 'failed'
+    AND NOT jobs.allow_failure
+    AND jobs.deleted_at IS NULL;
+";
+
+/// Errors which can occur when exporting a `VecLookup` to DuckDB.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum DuckDbExportError {
+    /// A previous export could not be removed.
+    #[error("failed to remove previous export {}: {}", path.display(), source)]
+    Remove {
+        /// The path to the previous export.
+        path: PathBuf,
+        /// The error.
+        #[source]
+        source: io::Error,
+    },
+    /// Database error.
+    #[error("database error: {}", source)]
+    Database {
+        /// The error.
+        #[from]
+        source: duckdb::Error,
+    },
+    /// The store could not be read.
+    #[error("failed to read the store: {}", source)]
+    Store {
+        /// The error.
+        #[from]
+        source: VecStoreError,
+    },
+}
+
+fn id<T>(idx: &VecIndex<T>) -> u64 {
+    idx.idx as u64
+}
+
+/// Export a `VecLookup` into a DuckDB database for ad-hoc SQL queries.
+///
+/// Any database already at the path is replaced. The export is a snapshot: it is not updated as
+/// the store changes. Besides a table for each exported entity type, the `pipeline_durations` and
+/// `job_failures` views are provided. Soft-deleted entities are exported with their deletion
+/// time, but are excluded from the views.
+pub fn export_duckdb(store: &VecLookup, path: &Path) -> Result<(), DuckDbExportError> {
+    match fs::remove_file(path) {
+        Ok(()) => (),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+        Err(err) => {
+            return Err(DuckDbExportError::Remove {
+                path: path.into(),
+                source: err,
+            });
+        },
+    }
+
+    let mut conn = Connection::open(path)?;
+    let txn = conn.transaction()?;
+    txn.execute_batch(SCHEMA)?;
+
+    // Tables are filled in dependency order so that foreign keys always refer to existing rows.
+    let mut appender = txn.appender("instances")?;
+    for (i, instance) in store.instances.force(store)?.iter().enumerate() {
+        appender.append_row(params![
+            i as u64,
+            instance.unique_id,
+            instance.forge,
+            instance.url,
+        ])?;
+    }
+    appender.flush()?;
+    drop(appender);
+
+    let mut appender = txn.appender("users")?;
+    for (i, user) in store.users.force(store)?.iter().enumerate() {
+        appender.append_row(params![
+            i as u64,
+            id(&user.instance),
+            user.forge_id,
+            user.handle,
+            user.name,
+        ])?;
+    }
+    appender.flush()?;
+    drop(appender);
+
+    let mut appender = txn.appender("projects")?;
+    for (i, project) in store.projects.force(store)?.iter().enumerate() {
+        appender.append_row(params![
+            i as u64,
+            id(&project.instance),
+            project.forge_id,
+            project.name,
+            project.instance_path,
+            project.url,
+            project.default_branch,
+            project.cim_deleted_at,
+            project.cim_removed_from_group_at,
+        ])?;
+    }
+    appender.flush()?;
+    drop(appender);
+
+    let mut appender = txn.appender("runners")?;
+    for (i, runner) in store.runners.force(store)?.iter().enumerate() {
+        appender.append_row(params![
+            i as u64,
+            id(&runner.instance),
+            runner.forge_id,
+            runner.description,
+            runner.runner_type.name(),
+            runner.platform,
+            runner.architecture,
+            runner.version,
+            runner.paused,
+            runner.online,
+            runner.contacted_at,
+        ])?;
+    }
+    appender.flush()?;
+    drop(appender);
+
+    let mut appender = txn.appender("merge_requests")?;
+    for (i, mr) in store.merge_requests.force(store)?.iter().enumerate() {
+        appender.append_row(params![
+            i as u64,
+            id(&mr.source_project),
+            id(&mr.target_project),
+            id(&mr.author),
+            mr.forge_id,
+            mr.id,
+            mr.title,
+            mr.source_branch,
+            mr.target_branch,
+            mr.state.name(),
+            mr.url,
+            mr.created_at,
+            mr.merged_at,
+            mr.cim_deleted_at,
+        ])?;
+    }
+    appender.flush()?;
+    drop(appender);
+
+    let mut appender = txn.appender("pipelines")?;
+    for (i, pipeline) in store.pipelines.force(store)?.iter().enumerate() {
+        appender.append_row(params![
+            i as u64,
+            id(&pipeline.project),
+            pipeline.parent_pipeline.as_ref().map(id),
+            pipeline.merge_request.as_ref().map(id),
+            pipeline.user.as_ref().map(id),
+            pipeline.forge_id,
+            pipeline.sha,
+            pipeline.refname,
+            pipeline.source.name(),
+            pipeline.status.name(),
+            pipeline.url,
+            pipeline.created_at,
+            pipeline.updated_at,
+            pipeline.started_at,
+            pipeline.finished_at,
+            pipeline.cim_deleted_at,
+        ])?;
+    }
+    appender.flush()?;
+    drop(appender);
+
+    let mut appender = txn.appender("jobs")?;
+    for (i, job) in store.jobs.force(store)?.iter().enumerate() {
+        appender.append_row(params![
+            i as u64,
+            id(&job.pipeline),
+            id(&job.user),
+            job.runner.as_ref().map(id),
+            job.forge_id,
+            job.name,
+            job.stage,
+            job.state.name(),
+            job.allow_failure,
+            job.manual,
+            job.url,
+            job.created_at,
+            job.started_at,
+            job.finished_at,
+            job.queued_duration.map(Elapsed::as_seconds),
+            job.cim_deleted_at,
+        ])?;
+    }
+    appender.flush()?;
+    drop(appender);
+
+    let mut appender = txn.appender("environments")?;
+    for (i, environment) in store.environments.force(store)?.iter().enumerate() {
+        appender.append_row(params![
+            i as u64,
+            id(&environment.project),
+            environment.forge_id,
+            environment.name,
+            environment.tier.name(),
+            environment.state.name(),
+            environment.cim_deleted_at,
+        ])?;
+    }
+    appender.flush()?;
+    drop(appender);
+
+    let mut appender = txn.appender("deployments")?;
+    for (i, deployment) in store.deployments.force(store)?.iter().enumerate() {
+        appender.append_row(params![
+            i as u64,
+            id(&deployment.pipeline),
+            id(&deployment.environment),
+            deployment.forge_id,
+            deployment.status.name(),
+            deployment.created_at,
+            deployment.finished_at,
+            deployment.cim_deleted_at,
+        ])?;
+    }
+    appender.flush()?;
+    drop(appender);
+
+    txn.commit()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use ci_monitor_core::data::{
+        Instance, Job, JobState, Pipeline, PipelineSource, PipelineStatus, Project, User,
+    };
+    use ci_monitor_core::Lookup;
+    use duckdb::Connection;
+    use tempfile::TempDir;
+
+    use crate::VecLookup;
+
+    #[test]
+    fn export_duckdb() {
+        let mut lookup = VecLookup::default();
+        let instance = Instance::builder()
+            .unique_id(0)
+            .forge("forge")
+            .url("url")
+            .build()
+            .unwrap();
+        let instance = lookup.store(instance);
+        let project = Project::builder()
+            .forge_id(1)
+            .instance(instance)
+            .instance_path("group/project")
+            .url("project")
+            .build()
+            .unwrap();
+        let project = lookup.store(project);
+        let user = User::builder()
+            .forge_id(2)
+            .instance(instance)
+            .build()
+            .unwrap();
+        let user = lookup.store(user);
+        let at = |minute| Utc.with_ymd_and_hms(2024, 1, 1, 0, minute, 0).unwrap();
+        let pipeline = Pipeline::builder()
+            .project(project)
+            .sha("0000000000000000000000000000000000000000")
+            .refname(Some("main".into()))
+            .source(PipelineSource::Push)
+            .status(PipelineStatus::Failed)
+            .forge_id(3)
+            .url("pipeline")
+            .created_at(at(0))
+            .updated_at(at(30))
+            .started_at(Some(at(0)))
+            .finished_at(Some(at(30)))
+            .build()
+            .unwrap();
+        let pipeline = lookup.store(pipeline);
+        let job = |id, name: &str, state, allow_failure| {
+            Job::builder()
+                .user(user)
+                .name(name)
+                .state(state)
+                .allow_failure(allow_failure)
+                .created_at(at(0))
+                .forge_id(id)
+                .url(format!("job/{}", id))
+                .pipeline(pipeline)
+                .build()
+                .unwrap()
+        };
+        lookup.store(job(4, "build", JobState::Success, false));
+        lookup.store(job(5, "test", JobState::Failed, false));
+        lookup.store(job(6, "lint", JobState::Failed, true));
+
+        let workdir = TempDir::new().unwrap();
+        let path = workdir.path().join("export.duckdb");
+        super::export_duckdb(&lookup, &path).unwrap();
+        // Exporting again replaces the previous export.
+        super::export_duckdb(&lookup, &path).unwrap();
+
+        let conn = Connection::open(&path).unwrap();
+        let jobs: i64 = conn
+            .query_row("SELECT count(*) FROM jobs", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(jobs, 3);
+        let (project, duration): (String, f64) = conn
+            .query_row(
+                "SELECT project, duration FROM pipeline_durations",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(project, "group/project");
+        assert_eq!(duration, 1800.);
+        let failures: Vec<String> = conn
+            .prepare("SELECT name FROM job_failures")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(failures, ["test"]);
+
+        // Foreign keys are enforced.
+        let err = conn
+            .execute(
+                "INSERT INTO deployments (id, pipeline, environment, forge_id, status, created_at) \
+                 VALUES (0, 0, 10, 10, 'success', now())",
+                [],
+            )
+            .unwrap_err();
+        assert!(err.to_string().contains("foreign key"), "{}", err);
+    }
+}
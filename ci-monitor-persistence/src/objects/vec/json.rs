@@ -4,16 +4,16 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::any;
 use std::collections::BTreeMap;
-use std::fmt::Debug;
 
 use chrono::{DateTime, Utc};
 use ci_monitor_core::data::{
-    ArtifactExpiration, ArtifactKind, ArtifactState, BlobReference, ContentHash, Deployment,
-    DeploymentStatus, Environment, EnvironmentState, EnvironmentTier, Instance, Job, JobArtifact,
-    JobState, MergeRequest, MergeRequestStatus, Pipeline, PipelineSchedule, PipelineSource,
-    PipelineStatus, PipelineVariable, PipelineVariableType, PipelineVariables, Project, Runner,
+    ApiUsage, ArtifactDependencies, ArtifactExpiration, ArtifactKind, ArtifactState, AuditEntry,
+    AuditTrigger, AutoscaledInstance, BlobReference, ContentHash, CrawlSession, DataFidelity,
+    Deployment, DeploymentStatus, Elapsed, Environment, EnvironmentState, EnvironmentTier,
+    Instance, Job, JobArtifact, JobSection, JobState, MergeRequest, MergeRequestStatus, Pipeline,
+    PipelineSchedule, PipelineSource, PipelineStatus, PipelineVariable, PipelineVariableType,
+    PipelineVariables, PreviousDefaultBranch, Project, Push, Release, ReleaseAsset, Runner,
     RunnerHost, RunnerProtectionLevel, RunnerType, User,
 };
 use serde::{Deserialize, Serialize};
@@ -27,53 +27,133 @@ fn invalid_enum_string<T>(value: &str) -> VecStoreError {
     }
 }
 
-fn enum_to_string_opt<T>(lut: &[(T, &'static str)], en: T) -> Option<&'static str>
-where
-    T: Debug,
-    T: PartialEq<T>,
-{
-    for (e, s) in lut {
-        if *e == en {
-            return Some(s);
-        }
+fn enum_from_name<T>(from_name: fn(&str) -> Option<T>, name: &str) -> Result<T, VecStoreError> {
+    from_name(name).ok_or_else(|| invalid_enum_string::<T>(name))
+}
+
+/// Read a stored duration.
+///
+/// Older stores kept durations as given by the forge, including negative values. These are
+/// dropped rather than failing to load the store.
+fn elapsed_from_seconds(field: &'static str, seconds: f64) -> Option<Elapsed> {
+    let elapsed = Elapsed::from_seconds(seconds);
+    if elapsed.is_none() {
+        tracing::warn!(field, seconds, "ignoring invalid stored duration");
     }
+    elapsed
+}
+
+pub(super) trait JsonConvert<T>: for<'a> Deserialize<'a> + Serialize {
+    fn convert_to_json(o: &T) -> Self;
+    fn create_from_json(&self) -> Result<T, VecStoreError>;
+}
 
-    None
+#[derive(Deserialize, Serialize)]
+pub(super) struct AuditEntryJson {
+    at: DateTime<Utc>,
+    operator: String,
+    trigger: String,
+    action: String,
+    target: String,
+    tasks: Vec<String>,
+    affected: u64,
+    unique_id: u64,
 }
 
-fn enum_to_string<T>(lut: &[(T, &'static str)], en: T) -> &'static str
-where
-    T: Copy + Debug,
-    T: PartialEq<T>,
-{
-    if let Some(s) = enum_to_string_opt(lut, en) {
-        s
-    } else {
-        panic!(
-            "unexpected enum value for {}: {:?}",
-            any::type_name::<T>(),
-            en,
-        );
-    }
-}
-
-fn enum_from_string<T>(lut: &[(T, &'static str)], st: &str) -> Result<T, VecStoreError>
-where
-    T: Copy,
-    T: PartialEq<T>,
-{
-    for (e, s) in lut {
-        if *s == st {
-            return Ok(*e);
+impl JsonConvert<AuditEntry> for AuditEntryJson {
+    fn convert_to_json(o: &AuditEntry) -> Self {
+        Self {
+            at: o.at,
+            operator: o.operator.clone(),
+            trigger: o.trigger.name().into(),
+            action: o.action.clone(),
+            target: o.target.clone(),
+            tasks: o.tasks.clone(),
+            affected: o.affected,
+            unique_id: o.unique_id,
         }
     }
 
-    Err(invalid_enum_string::<T>(st))
+    fn create_from_json(&self) -> Result<AuditEntry, VecStoreError> {
+        let mut entry = AuditEntry::builder()
+            .at(self.at)
+            .operator(&self.operator)
+            .trigger(enum_from_name(AuditTrigger::from_name, &self.trigger)?)
+            .action(&self.action)
+            .unique_id(self.unique_id)
+            .build()
+            .unwrap();
+        entry.target.clone_from(&self.target);
+        entry.tasks.clone_from(&self.tasks);
+        entry.affected = self.affected;
+
+        Ok(entry)
+    }
 }
 
-pub(super) trait JsonConvert<T>: for<'a> Deserialize<'a> + Serialize {
-    fn convert_to_json(o: &T) -> Self;
-    fn create_from_json(&self) -> Result<T, VecStoreError>;
+#[derive(Deserialize, Serialize)]
+pub(super) struct ApiUsageJson {
+    instance: usize,
+    hour: DateTime<Utc>,
+    requests: u64,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(super) struct CrawlSessionJson {
+    started_at: DateTime<Utc>,
+    finished_at: Option<DateTime<Utc>>,
+    tasks_executed: u64,
+    tasks_failed: u64,
+    tasks_canceled: u64,
+    instances: Vec<usize>,
+    #[serde(default)]
+    api_usage: Vec<ApiUsageJson>,
+    revision: u64,
+}
+
+impl JsonConvert<CrawlSession<VecLookup>> for CrawlSessionJson {
+    fn convert_to_json(o: &CrawlSession<VecLookup>) -> Self {
+        Self {
+            started_at: o.started_at,
+            finished_at: o.finished_at,
+            tasks_executed: o.tasks_executed,
+            tasks_failed: o.tasks_failed,
+            tasks_canceled: o.tasks_canceled,
+            instances: o.instances.iter().map(|i| i.idx).collect(),
+            api_usage: o
+                .api_usage
+                .iter()
+                .map(|usage| {
+                    ApiUsageJson {
+                        instance: usage.instance.idx,
+                        hour: usage.hour,
+                        requests: usage.requests,
+                    }
+                })
+                .collect(),
+            revision: o.revision,
+        }
+    }
+
+    fn create_from_json(&self) -> Result<CrawlSession<VecLookup>, VecStoreError> {
+        let mut session = CrawlSession::builder()
+            .started_at(self.started_at)
+            .revision(self.revision)
+            .build()
+            .unwrap();
+        session.finished_at = self.finished_at;
+        session.tasks_executed = self.tasks_executed;
+        session.tasks_failed = self.tasks_failed;
+        session.tasks_canceled = self.tasks_canceled;
+        session.instances = self.instances.iter().map(|i| VecIndex::new(*i)).collect();
+        session.api_usage = self
+            .api_usage
+            .iter()
+            .map(|usage| ApiUsage::new(VecIndex::new(usage.instance), usage.hour, usage.requests))
+            .collect();
+
+        Ok(session)
+    }
 }
 
 #[derive(Deserialize, Serialize)]
@@ -88,17 +168,10 @@ pub(super) struct DeploymentJson {
 
     cim_fetched_at: DateTime<Utc>,
     cim_refreshed_at: DateTime<Utc>,
+    #[serde(default)]
+    cim_deleted_at: Option<DateTime<Utc>>,
 }
 
-const DEPLOYMENT_STATUS_TABLE: &[(DeploymentStatus, &str)] = &[
-    (DeploymentStatus::Created, "created"),
-    (DeploymentStatus::Running, "running"),
-    (DeploymentStatus::Success, "success"),
-    (DeploymentStatus::Failed, "failed"),
-    (DeploymentStatus::Canceled, "canceled"),
-    (DeploymentStatus::Blocked, "blocked"),
-];
-
 impl JsonConvert<Deployment<VecLookup>> for DeploymentJson {
     fn convert_to_json(o: &Deployment<VecLookup>) -> Self {
         Self {
@@ -108,9 +181,10 @@ impl JsonConvert<Deployment<VecLookup>> for DeploymentJson {
             created_at: o.created_at,
             updated_at: o.updated_at,
             finished_at: o.finished_at,
-            status: enum_to_string(DEPLOYMENT_STATUS_TABLE, o.status).into(),
+            status: o.status.name().into(),
             cim_fetched_at: o.cim_fetched_at,
             cim_refreshed_at: o.cim_refreshed_at,
+            cim_deleted_at: o.cim_deleted_at,
         }
     }
 
@@ -121,12 +195,13 @@ impl JsonConvert<Deployment<VecLookup>> for DeploymentJson {
             .forge_id(self.forge_id)
             .created_at(self.created_at)
             .updated_at(self.updated_at)
-            .status(enum_from_string(DEPLOYMENT_STATUS_TABLE, &self.status)?)
+            .status(enum_from_name(DeploymentStatus::from_name, &self.status)?)
             .build()
             .unwrap();
         deployment.finished_at = self.finished_at;
         deployment.cim_fetched_at = self.cim_fetched_at;
         deployment.cim_refreshed_at = self.cim_refreshed_at;
+        deployment.cim_deleted_at = self.cim_deleted_at;
 
         Ok(deployment)
     }
@@ -146,29 +221,17 @@ pub(super) struct EnvironmentJson {
     auto_stop_at: Option<DateTime<Utc>>,
     cim_fetched_at: DateTime<Utc>,
     cim_refreshed_at: DateTime<Utc>,
+    #[serde(default)]
+    cim_deleted_at: Option<DateTime<Utc>>,
 }
 
-const ENVIRONMENT_STATE_TABLE: &[(EnvironmentState, &str)] = &[
-    (EnvironmentState::Available, "available"),
-    (EnvironmentState::Stopping, "stopping"),
-    (EnvironmentState::Stopped, "stopped"),
-];
-
-const ENVIRONMENT_TIER_TABLE: &[(EnvironmentTier, &str)] = &[
-    (EnvironmentTier::Production, "production"),
-    (EnvironmentTier::Staging, "staging"),
-    (EnvironmentTier::Testing, "testing"),
-    (EnvironmentTier::Development, "development"),
-    (EnvironmentTier::Other, "other"),
-];
-
 impl JsonConvert<Environment<VecLookup>> for EnvironmentJson {
     fn convert_to_json(o: &Environment<VecLookup>) -> Self {
         Self {
             name: o.name.clone(),
             external_url: o.external_url.clone(),
-            state: enum_to_string(ENVIRONMENT_STATE_TABLE, o.state).into(),
-            tier: enum_to_string(ENVIRONMENT_TIER_TABLE, o.tier).into(),
+            state: o.state.name().into(),
+            tier: o.tier.name().into(),
             forge_id: o.forge_id,
             project: o.project.idx,
             created_at: o.created_at,
@@ -176,14 +239,15 @@ impl JsonConvert<Environment<VecLookup>> for EnvironmentJson {
             auto_stop_at: o.auto_stop_at,
             cim_fetched_at: o.cim_fetched_at,
             cim_refreshed_at: o.cim_refreshed_at,
+            cim_deleted_at: o.cim_deleted_at,
         }
     }
 
     fn create_from_json(&self) -> Result<Environment<VecLookup>, VecStoreError> {
         let mut environment = Environment::builder()
             .name(&self.name)
-            .state(enum_from_string(ENVIRONMENT_STATE_TABLE, &self.state)?)
-            .tier(enum_from_string(ENVIRONMENT_TIER_TABLE, &self.tier)?)
+            .state(enum_from_name(EnvironmentState::from_name, &self.state)?)
+            .tier(enum_from_name(EnvironmentTier::from_name, &self.tier)?)
             .forge_id(self.forge_id)
             .project(VecIndex::new(self.project))
             .created_at(self.created_at)
@@ -193,6 +257,7 @@ impl JsonConvert<Environment<VecLookup>> for EnvironmentJson {
         environment.auto_stop_at = self.auto_stop_at;
         environment.cim_fetched_at = self.cim_fetched_at;
         environment.cim_refreshed_at = self.cim_refreshed_at;
+        environment.cim_deleted_at = self.cim_deleted_at;
 
         Ok(environment)
     }
@@ -232,16 +297,11 @@ struct PipelineVariableJson {
     environment: Option<String>,
 }
 
-const PIPELINE_VARIABLE_TYPE_TABLE: &[(PipelineVariableType, &str)] = &[
-    (PipelineVariableType::File, "file"),
-    (PipelineVariableType::String, "string"),
-];
-
 impl JsonConvert<PipelineVariable> for PipelineVariableJson {
     fn convert_to_json(o: &PipelineVariable) -> Self {
         Self {
             value: o.value.clone(),
-            type_: enum_to_string(PIPELINE_VARIABLE_TYPE_TABLE, o.type_).into(),
+            type_: o.type_.name().into(),
             protected: o.protected,
             environment: o.environment.clone(),
         }
@@ -250,7 +310,10 @@ impl JsonConvert<PipelineVariable> for PipelineVariableJson {
     fn create_from_json(&self) -> Result<PipelineVariable, VecStoreError> {
         let mut pipeline_variable = PipelineVariable::builder()
             .value(&self.value)
-            .type_(enum_from_string(PIPELINE_VARIABLE_TYPE_TABLE, &self.type_)?)
+            .type_(enum_from_name(
+                PipelineVariableType::from_name,
+                &self.type_,
+            )?)
             .build()
             .unwrap();
         pipeline_variable.protected = self.protected;
@@ -284,6 +347,13 @@ impl JsonConvert<PipelineVariables> for PipelineVariablesJson {
     }
 }
 
+#[derive(Deserialize, Serialize)]
+struct JobSectionJson {
+    name: String,
+    started_at: DateTime<Utc>,
+    finished_at: DateTime<Utc>,
+}
+
 #[derive(Deserialize, Serialize)]
 pub(super) struct JobJson {
     user: usize,
@@ -300,28 +370,25 @@ pub(super) struct JobJson {
     queued_duration: Option<f64>,
     runner: Option<usize>,
     deployment: Option<usize>,
+    #[serde(default)]
+    manual: bool,
+    #[serde(default)]
+    played_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    played_by: Option<usize>,
     forge_id: u64,
     archived: bool,
     url: String,
     pipeline: usize,
     coverage: Option<f64>,
+    #[serde(default)]
+    sections: Vec<JobSectionJson>,
     cim_fetched_at: DateTime<Utc>,
     cim_refreshed_at: DateTime<Utc>,
+    #[serde(default)]
+    cim_deleted_at: Option<DateTime<Utc>>,
 }
 
-const JOB_STATE_TABLE: &[(JobState, &str)] = &[
-    (JobState::Created, "created"),
-    (JobState::Pending, "pending"),
-    (JobState::Running, "running"),
-    (JobState::Failed, "failed"),
-    (JobState::Success, "success"),
-    (JobState::Canceled, "canceled"),
-    (JobState::Skipped, "skipped"),
-    (JobState::WaitingForResource, "waiting_for_resource"),
-    (JobState::Manual, "manual"),
-    (JobState::Scheduled, "scheduled"),
-];
-
 impl JsonConvert<Job<VecLookup>> for JobJson {
     fn convert_to_json(o: &Job<VecLookup>) -> Self {
         Self {
@@ -331,28 +398,43 @@ impl JsonConvert<Job<VecLookup>> for JobJson {
             user: o.user.idx,
             tags: o.tags.clone(),
             variables: PipelineVariablesJson::convert_to_json(&o.variables),
-            state: enum_to_string(JOB_STATE_TABLE, o.state).into(),
+            state: o.state.name().into(),
             created_at: o.created_at,
             started_at: o.started_at,
             finished_at: o.finished_at,
             erased_at: o.erased_at,
-            queued_duration: o.queued_duration,
+            queued_duration: o.queued_duration.map(Elapsed::as_seconds),
             runner: o.runner.map(|r| r.idx),
             deployment: o.deployment.map(|d| d.idx),
+            manual: o.manual,
+            played_at: o.played_at,
+            played_by: o.played_by.map(|u| u.idx),
             forge_id: o.forge_id,
             archived: o.archived,
             url: o.url.clone(),
             pipeline: o.pipeline.idx,
             coverage: o.coverage,
+            sections: o
+                .sections
+                .iter()
+                .map(|section| {
+                    JobSectionJson {
+                        name: section.name.clone(),
+                        started_at: section.started_at,
+                        finished_at: section.finished_at,
+                    }
+                })
+                .collect(),
             cim_fetched_at: o.cim_fetched_at,
             cim_refreshed_at: o.cim_refreshed_at,
+            cim_deleted_at: o.cim_deleted_at,
         }
     }
 
     fn create_from_json(&self) -> Result<Job<VecLookup>, VecStoreError> {
         let mut job = Job::builder()
             .user(VecIndex::new(self.user))
-            .state(enum_from_string(JOB_STATE_TABLE, &self.state)?)
+            .state(enum_from_name(JobState::from_name, &self.state)?)
             .created_at(self.created_at)
             .forge_id(self.forge_id)
             .pipeline(VecIndex::new(self.pipeline))
@@ -366,14 +448,31 @@ impl JsonConvert<Job<VecLookup>> for JobJson {
         job.started_at = self.started_at;
         job.finished_at = self.finished_at;
         job.erased_at = self.erased_at;
-        job.queued_duration = self.queued_duration;
+        job.queued_duration = self
+            .queued_duration
+            .and_then(|seconds| elapsed_from_seconds("queued_duration", seconds));
         job.runner = self.runner.map(VecIndex::new);
         job.deployment = self.deployment.map(VecIndex::new);
+        job.manual = self.manual;
+        job.played_at = self.played_at;
+        job.played_by = self.played_by.map(VecIndex::new);
         job.archived = self.archived;
         job.url.clone_from(&self.url);
         job.coverage = self.coverage;
+        job.sections = self
+            .sections
+            .iter()
+            .map(|section| {
+                JobSection::new(
+                    section.name.clone(),
+                    section.started_at,
+                    section.finished_at,
+                )
+            })
+            .collect();
         job.cim_fetched_at = self.cim_fetched_at;
         job.cim_refreshed_at = self.cim_refreshed_at;
+        job.cim_deleted_at = self.cim_deleted_at;
 
         Ok(job)
     }
@@ -385,22 +484,17 @@ struct BlobReferenceJson {
     hash: String,
 }
 
-const CONTENT_HASH_TABLE: &[(ContentHash, &str)] = &[
-    (ContentHash::Sha256, "sha256"),
-    (ContentHash::Sha512, "sha512"),
-];
-
 impl JsonConvert<BlobReference> for BlobReferenceJson {
     fn convert_to_json(o: &BlobReference) -> Self {
         Self {
-            algo: enum_to_string(CONTENT_HASH_TABLE, o.algo()).into(),
+            algo: o.algo().name().into(),
             hash: o.hash().into(),
         }
     }
 
     fn create_from_json(&self) -> Result<BlobReference, VecStoreError> {
         Ok(BlobReference::new(
-            enum_from_string(CONTENT_HASH_TABLE, &self.algo)?,
+            enum_from_name(ContentHash::from_name, &self.algo)?,
             self.hash.clone(),
         ))
     }
@@ -418,11 +512,6 @@ pub(super) struct JobArtifactJson {
     job: usize,
 }
 
-const ARTIFACT_EXPIRATION_TABLE: &[(ArtifactExpiration, &str)] = &[
-    (ArtifactExpiration::Unknown, "unknown"),
-    (ArtifactExpiration::Never, "never"),
-];
-
 fn artifact_expiration_to_string(ae: ArtifactExpiration) -> String {
     if let ArtifactExpiration::At(dt) = ae {
         let mut s = Vec::new();
@@ -431,14 +520,18 @@ fn artifact_expiration_to_string(ae: ArtifactExpiration) -> String {
             dt.serialize(&mut ser).unwrap();
         }
         String::from_utf8_lossy(&s).into_owned()
+    } else if ae == ArtifactExpiration::Never {
+        "never".into()
     } else {
-        enum_to_string(ARTIFACT_EXPIRATION_TABLE, ae).into()
+        "unknown".into()
     }
 }
 
 fn artifact_expiration_from_string(s: &str) -> Result<ArtifactExpiration, VecStoreError> {
-    if let Ok(ae) = enum_from_string(ARTIFACT_EXPIRATION_TABLE, s) {
-        Ok(ae)
+    if s == "unknown" {
+        Ok(ArtifactExpiration::Unknown)
+    } else if s == "never" {
+        Ok(ArtifactExpiration::Never)
     } else {
         let mut des = serde_json::Deserializer::from_str(s);
         let dt = DateTime::<Utc>::deserialize(&mut des)?;
@@ -446,18 +539,10 @@ fn artifact_expiration_from_string(s: &str) -> Result<ArtifactExpiration, VecSto
     }
 }
 
-const ARTIFACT_STATE_TABLE: &[(ArtifactState, &str)] = &[
-    (ArtifactState::Unknown, "unknown"),
-    (ArtifactState::Pending, "pending"),
-    (ArtifactState::Expired, "expired"),
-    (ArtifactState::Present, "present"),
-    (ArtifactState::Stored, "stored"),
-];
-
 impl JsonConvert<JobArtifact<VecLookup>> for JobArtifactJson {
     fn convert_to_json(o: &JobArtifact<VecLookup>) -> Self {
         Self {
-            state: enum_to_string(ARTIFACT_STATE_TABLE, o.state).into(),
+            state: o.state.name().into(),
             kind: o.kind.as_str().into(),
             expire_at: artifact_expiration_to_string(o.expire_at),
             name: o.name.clone(),
@@ -480,7 +565,7 @@ impl JsonConvert<JobArtifact<VecLookup>> for JobArtifactJson {
             .job(VecIndex::new(self.job))
             .build()
             .unwrap();
-        job_artifact.state = enum_from_string(ARTIFACT_STATE_TABLE, &self.state)?;
+        job_artifact.state = enum_from_name(ArtifactState::from_name, &self.state)?;
         job_artifact.expire_at = artifact_expiration_from_string(&self.expire_at)?;
         job_artifact.blob = self
             .blob
@@ -506,16 +591,20 @@ pub(super) struct MergeRequestJson {
     state: String,
     author: usize,
     url: String,
+    #[serde(default)]
+    created_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    merged_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    first_review_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    approved_at: Option<DateTime<Utc>>,
     cim_fetched_at: DateTime<Utc>,
     cim_refreshed_at: DateTime<Utc>,
+    #[serde(default)]
+    cim_deleted_at: Option<DateTime<Utc>>,
 }
 
-const MERGE_REQUEST_STATUS_TABLE: &[(MergeRequestStatus, &str)] = &[
-    (MergeRequestStatus::Open, "open"),
-    (MergeRequestStatus::Closed, "closed"),
-    (MergeRequestStatus::Merged, "merged"),
-];
-
 impl JsonConvert<MergeRequest<VecLookup>> for MergeRequestJson {
     fn convert_to_json(o: &MergeRequest<VecLookup>) -> Self {
         Self {
@@ -528,11 +617,16 @@ impl JsonConvert<MergeRequest<VecLookup>> for MergeRequestJson {
             forge_id: o.forge_id,
             title: o.title.clone(),
             description: o.description.clone(),
-            state: enum_to_string(MERGE_REQUEST_STATUS_TABLE, o.state).into(),
+            state: o.state.name().into(),
             author: o.author.idx,
             url: o.url.clone(),
+            created_at: o.created_at,
+            merged_at: o.merged_at,
+            first_review_at: o.first_review_at,
+            approved_at: o.approved_at,
             cim_fetched_at: o.cim_fetched_at,
             cim_refreshed_at: o.cim_refreshed_at,
+            cim_deleted_at: o.cim_deleted_at,
         }
     }
 
@@ -542,7 +636,7 @@ impl JsonConvert<MergeRequest<VecLookup>> for MergeRequestJson {
             .source_project(VecIndex::new(self.source_project))
             .target_project(VecIndex::new(self.target_project))
             .forge_id(self.forge_id)
-            .state(enum_from_string(MERGE_REQUEST_STATUS_TABLE, &self.state)?)
+            .state(enum_from_name(MergeRequestStatus::from_name, &self.state)?)
             .author(VecIndex::new(self.author))
             .url(&self.url)
             .build()
@@ -552,13 +646,40 @@ impl JsonConvert<MergeRequest<VecLookup>> for MergeRequestJson {
         merge_request.target_branch.clone_from(&self.target_branch);
         merge_request.title.clone_from(&self.title);
         merge_request.description.clone_from(&self.description);
+        merge_request.created_at = self.created_at;
+        merge_request.merged_at = self.merged_at;
+        merge_request.first_review_at = self.first_review_at;
+        merge_request.approved_at = self.approved_at;
         merge_request.cim_fetched_at = self.cim_fetched_at;
         merge_request.cim_refreshed_at = self.cim_refreshed_at;
+        merge_request.cim_deleted_at = self.cim_deleted_at;
 
         Ok(merge_request)
     }
 }
 
+#[derive(Deserialize, Serialize)]
+struct ArtifactDependenciesJson {
+    stages: Vec<String>,
+    jobs: BTreeMap<String, Vec<String>>,
+}
+
+impl JsonConvert<ArtifactDependencies> for ArtifactDependenciesJson {
+    fn convert_to_json(o: &ArtifactDependencies) -> Self {
+        Self {
+            stages: o.stages.clone(),
+            jobs: o.jobs.clone(),
+        }
+    }
+
+    fn create_from_json(&self) -> Result<ArtifactDependencies, VecStoreError> {
+        let mut deps = ArtifactDependencies::default();
+        deps.stages.clone_from(&self.stages);
+        deps.jobs.clone_from(&self.jobs);
+        Ok(deps)
+    }
+}
+
 #[derive(Deserialize, Serialize)]
 pub(super) struct PipelineJson {
     name: Option<String>,
@@ -567,6 +688,10 @@ pub(super) struct PipelineJson {
     previous_sha: Option<String>,
     refname: Option<String>,
     stable_refname: Option<String>,
+    #[serde(default)]
+    ci_config: Option<BlobReferenceJson>,
+    #[serde(default)]
+    artifact_dependencies: Option<ArtifactDependenciesJson>,
     source: String,
     schedule: Option<usize>,
     parent_pipeline: Option<usize>,
@@ -582,55 +707,18 @@ pub(super) struct PipelineJson {
     updated_at: DateTime<Utc>,
     started_at: Option<DateTime<Utc>>,
     finished_at: Option<DateTime<Utc>>,
+    #[serde(default = "default_fidelity")]
+    cim_fidelity: String,
     cim_fetched_at: DateTime<Utc>,
     cim_refreshed_at: DateTime<Utc>,
+    #[serde(default)]
+    cim_deleted_at: Option<DateTime<Utc>>,
 }
 
-const PIPELINE_SOURCE_TABLE: &[(PipelineSource, &str)] = &[
-    (PipelineSource::Api, "api"),
-    (PipelineSource::Chat, "chat"),
-    (PipelineSource::External, "external"),
-    (
-        PipelineSource::ExternalPullRequestEvent,
-        "external_pull_request_event",
-    ),
-    (PipelineSource::MergeRequestEvent, "merge_request_event"),
-    (PipelineSource::OnDemandDastScan, "on_demand_dast_scan"),
-    (
-        PipelineSource::OnDemandDastValidation,
-        "on_demand_dast_validation",
-    ),
-    (PipelineSource::ParentPipeline, "parent_pipeline"),
-    (PipelineSource::Pipeline, "pipeline"),
-    (PipelineSource::Push, "push"),
-    (PipelineSource::Schedule, "schedule"),
-    (
-        PipelineSource::SecurityOrchestrationPolicy,
-        "security_orchestration_policy",
-    ),
-    (PipelineSource::Trigger, "trigger"),
-    (PipelineSource::Web, "web"),
-    (PipelineSource::WebIde, "web_ide"),
-];
-
-const PIPELINE_STATUS_TABLE: &[(PipelineStatus, &str)] = &[
-    (PipelineStatus::Created, "created"),
-    (PipelineStatus::WaitingForResource, "waiting_for_resource"),
-    (PipelineStatus::Preparing, "preparing"),
-    (PipelineStatus::Pending, "pending"),
-    (PipelineStatus::Running, "running"),
-    (PipelineStatus::Success, "success"),
-    (PipelineStatus::Failed, "failed"),
-    (PipelineStatus::Canceled, "canceled"),
-    (PipelineStatus::Skipped, "skipped"),
-    (PipelineStatus::Manual, "manual"),
-    (PipelineStatus::Scheduled, "scheduled"),
-    (PipelineStatus::Completed, "completed"),
-    (PipelineStatus::Neutral, "neutral"),
-    (PipelineStatus::Stale, "stale"),
-    (PipelineStatus::StartupFailure, "startup_failure"),
-    (PipelineStatus::TimedOut, "timed_out"),
-];
+// Stores written before fidelity tracking only contain data from the forge.
+fn default_fidelity() -> String {
+    DataFidelity::Forge.name().into()
+}
 
 impl JsonConvert<Pipeline<VecLookup>> for PipelineJson {
     fn convert_to_json(o: &Pipeline<VecLookup>) -> Self {
@@ -641,13 +729,18 @@ impl JsonConvert<Pipeline<VecLookup>> for PipelineJson {
             previous_sha: o.previous_sha.clone(),
             refname: o.refname.clone(),
             stable_refname: o.stable_refname.clone(),
-            source: enum_to_string(PIPELINE_SOURCE_TABLE, o.source).into(),
+            ci_config: o.ci_config.as_ref().map(BlobReferenceJson::convert_to_json),
+            artifact_dependencies: o
+                .artifact_dependencies
+                .as_ref()
+                .map(ArtifactDependenciesJson::convert_to_json),
+            source: o.source.name().into(),
             schedule: o.schedule.map(|s| s.idx),
             parent_pipeline: o.parent_pipeline.map(|p| p.idx),
             merge_request: o.merge_request.map(|m| m.idx),
             variables: PipelineVariablesJson::convert_to_json(&o.variables),
             user: o.user.map(|u| u.idx),
-            status: enum_to_string(PIPELINE_STATUS_TABLE, o.status).into(),
+            status: o.status.name().into(),
             coverage: o.coverage,
             forge_id: o.forge_id,
             url: o.url.clone(),
@@ -656,8 +749,10 @@ impl JsonConvert<Pipeline<VecLookup>> for PipelineJson {
             updated_at: o.updated_at,
             started_at: o.started_at,
             finished_at: o.finished_at,
+            cim_fidelity: o.cim_fidelity.name().into(),
             cim_fetched_at: o.cim_fetched_at,
             cim_refreshed_at: o.cim_refreshed_at,
+            cim_deleted_at: o.cim_deleted_at,
         }
     }
 
@@ -665,8 +760,8 @@ impl JsonConvert<Pipeline<VecLookup>> for PipelineJson {
         let mut pipeline = Pipeline::builder()
             .project(VecIndex::new(self.project))
             .sha(&self.sha)
-            .source(enum_from_string(PIPELINE_SOURCE_TABLE, &self.source)?)
-            .status(enum_from_string(PIPELINE_STATUS_TABLE, &self.status)?)
+            .source(enum_from_name(PipelineSource::from_name, &self.source)?)
+            .status(enum_from_name(PipelineStatus::from_name, &self.status)?)
             .forge_id(self.forge_id)
             .url(&self.url)
             .created_at(self.created_at)
@@ -677,6 +772,16 @@ impl JsonConvert<Pipeline<VecLookup>> for PipelineJson {
         pipeline.previous_sha.clone_from(&self.previous_sha);
         pipeline.refname.clone_from(&self.refname);
         pipeline.stable_refname.clone_from(&self.stable_refname);
+        pipeline.ci_config = self
+            .ci_config
+            .as_ref()
+            .map(BlobReferenceJson::create_from_json)
+            .transpose()?;
+        pipeline.artifact_dependencies = self
+            .artifact_dependencies
+            .as_ref()
+            .map(ArtifactDependenciesJson::create_from_json)
+            .transpose()?;
         pipeline.schedule = self.schedule.map(VecIndex::new);
         pipeline.parent_pipeline = self.parent_pipeline.map(VecIndex::new);
         pipeline.merge_request = self.merge_request.map(VecIndex::new);
@@ -686,8 +791,10 @@ impl JsonConvert<Pipeline<VecLookup>> for PipelineJson {
         pipeline.archived = self.archived;
         pipeline.started_at = self.started_at;
         pipeline.finished_at = self.finished_at;
+        pipeline.cim_fidelity = enum_from_name(DataFidelity::from_name, &self.cim_fidelity)?;
         pipeline.cim_fetched_at = self.cim_fetched_at;
         pipeline.cim_refreshed_at = self.cim_refreshed_at;
+        pipeline.cim_deleted_at = self.cim_deleted_at;
 
         Ok(pipeline)
     }
@@ -707,6 +814,8 @@ pub(super) struct PipelineScheduleJson {
     next_run: Option<DateTime<Utc>>,
     cim_fetched_at: DateTime<Utc>,
     cim_refreshed_at: DateTime<Utc>,
+    #[serde(default)]
+    cim_deleted_at: Option<DateTime<Utc>>,
 }
 
 impl JsonConvert<PipelineSchedule<VecLookup>> for PipelineScheduleJson {
@@ -724,6 +833,7 @@ impl JsonConvert<PipelineSchedule<VecLookup>> for PipelineScheduleJson {
             next_run: o.next_run,
             cim_fetched_at: o.cim_fetched_at,
             cim_refreshed_at: o.cim_refreshed_at,
+            cim_deleted_at: o.cim_deleted_at,
         }
     }
 
@@ -743,11 +853,31 @@ impl JsonConvert<PipelineSchedule<VecLookup>> for PipelineScheduleJson {
         pipeline_schedule.next_run = self.next_run;
         pipeline_schedule.cim_fetched_at = self.cim_fetched_at;
         pipeline_schedule.cim_refreshed_at = self.cim_refreshed_at;
+        pipeline_schedule.cim_deleted_at = self.cim_deleted_at;
 
         Ok(pipeline_schedule)
     }
 }
 
+#[derive(Deserialize, Serialize)]
+struct PreviousDefaultBranchJson {
+    branch: String,
+    until: DateTime<Utc>,
+}
+
+impl JsonConvert<PreviousDefaultBranch> for PreviousDefaultBranchJson {
+    fn convert_to_json(o: &PreviousDefaultBranch) -> Self {
+        Self {
+            branch: o.branch.clone(),
+            until: o.until,
+        }
+    }
+
+    fn create_from_json(&self) -> Result<PreviousDefaultBranch, VecStoreError> {
+        Ok(PreviousDefaultBranch::new(&self.branch, self.until))
+    }
+}
+
 #[derive(Deserialize, Serialize)]
 pub(super) struct ProjectJson {
     name: String,
@@ -755,8 +885,18 @@ pub(super) struct ProjectJson {
     url: String,
     instance: usize,
     instance_path: String,
+    #[serde(default)]
+    default_branch: Option<String>,
+    #[serde(default)]
+    previous_default_branches: Vec<PreviousDefaultBranchJson>,
+    #[serde(default = "default_fidelity")]
+    cim_fidelity: String,
     cim_fetched_at: DateTime<Utc>,
     cim_refreshed_at: DateTime<Utc>,
+    #[serde(default)]
+    cim_deleted_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    cim_removed_from_group_at: Option<DateTime<Utc>>,
 }
 
 impl JsonConvert<Project<VecLookup>> for ProjectJson {
@@ -767,8 +907,17 @@ impl JsonConvert<Project<VecLookup>> for ProjectJson {
             url: o.url.clone(),
             instance: o.instance.idx,
             instance_path: o.instance_path.clone(),
+            default_branch: o.default_branch.clone(),
+            previous_default_branches: o
+                .previous_default_branches
+                .iter()
+                .map(PreviousDefaultBranchJson::convert_to_json)
+                .collect(),
+            cim_fidelity: o.cim_fidelity.name().into(),
             cim_fetched_at: o.cim_fetched_at,
             cim_refreshed_at: o.cim_refreshed_at,
+            cim_deleted_at: o.cim_deleted_at,
+            cim_removed_from_group_at: o.cim_removed_from_group_at,
         }
     }
 
@@ -781,13 +930,182 @@ impl JsonConvert<Project<VecLookup>> for ProjectJson {
         project.name.clone_from(&self.name);
         project.url.clone_from(&self.url);
         project.instance_path.clone_from(&self.instance_path);
+        project.default_branch.clone_from(&self.default_branch);
+        project.previous_default_branches = self
+            .previous_default_branches
+            .iter()
+            .map(PreviousDefaultBranchJson::create_from_json)
+            .collect::<Result<_, _>>()?;
+        project.cim_fidelity = enum_from_name(DataFidelity::from_name, &self.cim_fidelity)?;
         project.cim_fetched_at = self.cim_fetched_at;
         project.cim_refreshed_at = self.cim_refreshed_at;
+        project.cim_deleted_at = self.cim_deleted_at;
+        project.cim_removed_from_group_at = self.cim_removed_from_group_at;
 
         Ok(project)
     }
 }
 
+#[derive(Deserialize, Serialize)]
+pub(super) struct PushJson {
+    author: Option<usize>,
+    commit_count: u64,
+    commit_title: String,
+    project: usize,
+    refname: String,
+    tag: bool,
+    sha: String,
+    before: Option<String>,
+    forge_id: u64,
+    pushed_at: DateTime<Utc>,
+    cim_fetched_at: DateTime<Utc>,
+    cim_refreshed_at: DateTime<Utc>,
+    #[serde(default)]
+    cim_deleted_at: Option<DateTime<Utc>>,
+}
+
+impl JsonConvert<Push<VecLookup>> for PushJson {
+    fn convert_to_json(o: &Push<VecLookup>) -> Self {
+        Self {
+            author: o.author.map(|a| a.idx),
+            commit_count: o.commit_count,
+            commit_title: o.commit_title.clone(),
+            project: o.project.idx,
+            refname: o.refname.clone(),
+            tag: o.tag,
+            sha: o.sha.clone(),
+            before: o.before.clone(),
+            forge_id: o.forge_id,
+            pushed_at: o.pushed_at,
+            cim_fetched_at: o.cim_fetched_at,
+            cim_refreshed_at: o.cim_refreshed_at,
+            cim_deleted_at: o.cim_deleted_at,
+        }
+    }
+
+    fn create_from_json(&self) -> Result<Push<VecLookup>, VecStoreError> {
+        let mut push = Push::builder()
+            .project(VecIndex::new(self.project))
+            .refname(&self.refname)
+            .sha(&self.sha)
+            .forge_id(self.forge_id)
+            .pushed_at(self.pushed_at)
+            .build()
+            .unwrap();
+        push.author = self.author.map(VecIndex::new);
+        push.commit_count = self.commit_count;
+        push.commit_title.clone_from(&self.commit_title);
+        push.tag = self.tag;
+        push.before.clone_from(&self.before);
+        push.cim_fetched_at = self.cim_fetched_at;
+        push.cim_refreshed_at = self.cim_refreshed_at;
+        push.cim_deleted_at = self.cim_deleted_at;
+
+        Ok(push)
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct ReleaseAssetJson {
+    name: String,
+    url: String,
+    link_type: Option<String>,
+}
+
+impl JsonConvert<ReleaseAsset> for ReleaseAssetJson {
+    fn convert_to_json(o: &ReleaseAsset) -> Self {
+        Self {
+            name: o.name.clone(),
+            url: o.url.clone(),
+            link_type: o.link_type.clone(),
+        }
+    }
+
+    fn create_from_json(&self) -> Result<ReleaseAsset, VecStoreError> {
+        let mut asset = ReleaseAsset::builder()
+            .name(&self.name)
+            .url(&self.url)
+            .build()
+            .unwrap();
+        asset.link_type.clone_from(&self.link_type);
+
+        Ok(asset)
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub(super) struct ReleaseJson {
+    name: String,
+    description: String,
+    author: Option<usize>,
+    assets: Vec<ReleaseAssetJson>,
+    project: usize,
+    tag: String,
+    sha: String,
+    pipeline: Option<usize>,
+    unique_id: u64,
+    url: String,
+    created_at: DateTime<Utc>,
+    released_at: DateTime<Utc>,
+    cim_fetched_at: DateTime<Utc>,
+    cim_refreshed_at: DateTime<Utc>,
+    #[serde(default)]
+    cim_deleted_at: Option<DateTime<Utc>>,
+}
+
+impl JsonConvert<Release<VecLookup>> for ReleaseJson {
+    fn convert_to_json(o: &Release<VecLookup>) -> Self {
+        Self {
+            name: o.name.clone(),
+            description: o.description.clone(),
+            author: o.author.map(|a| a.idx),
+            assets: o
+                .assets
+                .iter()
+                .map(ReleaseAssetJson::convert_to_json)
+                .collect(),
+            project: o.project.idx,
+            tag: o.tag.clone(),
+            sha: o.sha.clone(),
+            pipeline: o.pipeline.map(|p| p.idx),
+            unique_id: o.unique_id,
+            url: o.url.clone(),
+            created_at: o.created_at,
+            released_at: o.released_at,
+            cim_fetched_at: o.cim_fetched_at,
+            cim_refreshed_at: o.cim_refreshed_at,
+            cim_deleted_at: o.cim_deleted_at,
+        }
+    }
+
+    fn create_from_json(&self) -> Result<Release<VecLookup>, VecStoreError> {
+        let mut release = Release::builder()
+            .project(VecIndex::new(self.project))
+            .tag(&self.tag)
+            .sha(&self.sha)
+            .unique_id(self.unique_id)
+            .created_at(self.created_at)
+            .released_at(self.released_at)
+            .build()
+            .unwrap();
+        release.name.clone_from(&self.name);
+        release.description.clone_from(&self.description);
+        release.author = self.author.map(VecIndex::new);
+        release.assets = self
+            .assets
+            .iter()
+            .map(ReleaseAssetJson::create_from_json)
+            .collect::<Result<_, _>>()?;
+        release.pipeline = self.pipeline.map(VecIndex::new);
+        release.url.clone_from(&self.url);
+        release.cim_fetched_at = self.cim_fetched_at;
+        release.cim_refreshed_at = self.cim_refreshed_at;
+        release.cim_deleted_at = self.cim_deleted_at;
+
+        Ok(release)
+    }
+}
+
 #[derive(Deserialize, Serialize)]
 pub(super) struct RunnerJson {
     description: String,
@@ -808,32 +1126,24 @@ pub(super) struct RunnerJson {
     online: bool,
     locked: bool,
     contacted_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    ip_address: Option<String>,
     maintenance_note: Option<String>,
     instance: usize,
     runner_host: Option<usize>,
+    #[serde(default)]
+    max_concurrent_jobs: Option<u64>,
     cim_fetched_at: DateTime<Utc>,
     cim_refreshed_at: DateTime<Utc>,
 }
 
-const RUNNER_TYPE_TABLE: &[(RunnerType, &str)] = &[
-    (RunnerType::Instance, "instance"),
-    (RunnerType::Group, "group"),
-    (RunnerType::Project, "project"),
-];
-
-const RUNNER_PROTECTION_LEVEL_TABLE: &[(RunnerProtectionLevel, &str)] = &[
-    (RunnerProtectionLevel::Protected, "protected"),
-    (RunnerProtectionLevel::Any, "any"),
-];
-
 impl JsonConvert<Runner<VecLookup>> for RunnerJson {
     fn convert_to_json(o: &Runner<VecLookup>) -> Self {
         Self {
             description: o.description.clone(),
-            runner_type: enum_to_string(RUNNER_TYPE_TABLE, o.runner_type).into(),
+            runner_type: o.runner_type.name().into(),
             maximum_timeout: o.maximum_timeout,
-            protection_level: enum_to_string(RUNNER_PROTECTION_LEVEL_TABLE, o.protection_level)
-                .into(),
+            protection_level: o.protection_level.name().into(),
             implementation: o.implementation.clone(),
             version: o.version.clone(),
             revision: o.revision.clone(),
@@ -848,9 +1158,11 @@ impl JsonConvert<Runner<VecLookup>> for RunnerJson {
             online: o.online,
             locked: o.locked,
             contacted_at: o.contacted_at,
+            ip_address: o.ip_address.clone(),
             maintenance_note: o.maintenance_note.clone(),
             instance: o.instance.idx,
             runner_host: o.runner_host.map(|r| r.idx),
+            max_concurrent_jobs: o.max_concurrent_jobs,
             cim_fetched_at: o.cim_fetched_at,
             cim_refreshed_at: o.cim_refreshed_at,
         }
@@ -860,9 +1172,9 @@ impl JsonConvert<Runner<VecLookup>> for RunnerJson {
         let mut runner = Runner::builder()
             .forge_id(self.forge_id)
             .instance(VecIndex::new(self.instance))
-            .runner_type(enum_from_string(RUNNER_TYPE_TABLE, &self.runner_type)?)
-            .protection_level(enum_from_string(
-                RUNNER_PROTECTION_LEVEL_TABLE,
+            .runner_type(enum_from_name(RunnerType::from_name, &self.runner_type)?)
+            .protection_level(enum_from_name(
+                RunnerProtectionLevel::from_name,
                 &self.protection_level,
             )?)
             .build()
@@ -882,8 +1194,10 @@ impl JsonConvert<Runner<VecLookup>> for RunnerJson {
         runner.online = self.online;
         runner.locked = self.locked;
         runner.contacted_at = self.contacted_at;
+        runner.ip_address.clone_from(&self.ip_address);
         runner.maintenance_note.clone_from(&self.maintenance_note);
         runner.runner_host = self.runner_host.map(VecIndex::new);
+        runner.max_concurrent_jobs = self.max_concurrent_jobs;
         runner.cim_fetched_at = self.cim_fetched_at;
         runner.cim_refreshed_at = self.cim_refreshed_at;
 
@@ -891,6 +1205,35 @@ impl JsonConvert<Runner<VecLookup>> for RunnerJson {
     }
 }
 
+#[derive(Deserialize, Serialize)]
+struct AutoscaledInstanceJson {
+    name: String,
+    executor: String,
+    created_at: DateTime<Utc>,
+    ready_at: Option<DateTime<Utc>>,
+    removed_at: Option<DateTime<Utc>>,
+}
+
+impl JsonConvert<AutoscaledInstance> for AutoscaledInstanceJson {
+    fn convert_to_json(o: &AutoscaledInstance) -> Self {
+        Self {
+            name: o.name.clone(),
+            executor: o.executor.clone(),
+            created_at: o.created_at,
+            ready_at: o.ready_at,
+            removed_at: o.removed_at,
+        }
+    }
+
+    fn create_from_json(&self) -> Result<AutoscaledInstance, VecStoreError> {
+        let mut instance = AutoscaledInstance::new(&self.name, &self.executor, self.created_at);
+        instance.ready_at = self.ready_at;
+        instance.removed_at = self.removed_at;
+
+        Ok(instance)
+    }
+}
+
 #[derive(Deserialize, Serialize)]
 pub(super) struct RunnerHostJson {
     os: String,
@@ -899,6 +1242,10 @@ pub(super) struct RunnerHostJson {
     management: String,
     location: String,
     estimated_cost_per_hour: Option<f64>,
+    #[serde(default)]
+    max_concurrent_jobs: Option<u64>,
+    #[serde(default)]
+    autoscaled_instances: Vec<AutoscaledInstanceJson>,
     unique_id: u64,
     cim_fetched_at: DateTime<Utc>,
     cim_refreshed_at: DateTime<Utc>,
@@ -913,6 +1260,12 @@ impl JsonConvert<RunnerHost> for RunnerHostJson {
             management: o.management.clone(),
             location: o.location.clone(),
             estimated_cost_per_hour: o.estimated_cost_per_hour,
+            max_concurrent_jobs: o.max_concurrent_jobs,
+            autoscaled_instances: o
+                .autoscaled_instances
+                .iter()
+                .map(AutoscaledInstanceJson::convert_to_json)
+                .collect(),
             unique_id: o.unique_id,
             cim_fetched_at: o.cim_fetched_at,
             cim_refreshed_at: o.cim_refreshed_at,
@@ -930,6 +1283,12 @@ impl JsonConvert<RunnerHost> for RunnerHostJson {
         runner_host.management.clone_from(&self.management);
         runner_host.location.clone_from(&self.location);
         runner_host.estimated_cost_per_hour = self.estimated_cost_per_hour;
+        runner_host.max_concurrent_jobs = self.max_concurrent_jobs;
+        runner_host.autoscaled_instances = self
+            .autoscaled_instances
+            .iter()
+            .map(AutoscaledInstanceJson::create_from_json)
+            .collect::<Result<_, _>>()?;
         runner_host.cim_fetched_at = self.cim_fetched_at;
         runner_host.cim_refreshed_at = self.cim_refreshed_at;
 
@@ -940,6 +1299,8 @@ impl JsonConvert<RunnerHost> for RunnerHostJson {
 #[derive(Deserialize, Serialize)]
 pub(super) struct UserJson {
     handle: String,
+    #[serde(default)]
+    previous_handles: Vec<String>,
     name: String,
     email: Option<String>,
     avatar: Option<BlobReferenceJson>,
@@ -953,6 +1314,7 @@ impl JsonConvert<User<VecLookup>> for UserJson {
     fn convert_to_json(o: &User<VecLookup>) -> Self {
         Self {
             handle: o.handle.clone(),
+            previous_handles: o.previous_handles.clone(),
             name: o.name.clone(),
             email: o.email.clone(),
             avatar: o.avatar.as_ref().map(BlobReferenceJson::convert_to_json),
@@ -970,6 +1332,7 @@ impl JsonConvert<User<VecLookup>> for UserJson {
             .build()
             .unwrap();
         user.handle.clone_from(&self.handle);
+        user.previous_handles.clone_from(&self.previous_handles);
         user.name.clone_from(&self.name);
         user.email.clone_from(&self.email);
         user.avatar = self
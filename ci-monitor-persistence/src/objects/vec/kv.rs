@@ -0,0 +1,784 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use ci_monitor_core::data::{
+    AuditEntry, CrawlSession, Deployment, Environment, Instance, Job, JobArtifact, MergeRequest,
+    Pipeline, PipelineSchedule, Project, Push, Release, Runner, RunnerHost, User,
+};
+use ci_monitor_core::Lookup;
+use redb::{
+    Database, ReadTransaction, ReadableTable, Table, TableDefinition, TableError, WriteTransaction,
+};
+use thiserror::Error;
+
+use super::data::JsonStorable;
+use super::lazy::LazyVec;
+use super::{HasId, InstanceLookup, VecIndex, VecLookup, VecStore, VecStoreError};
+use crate::{DiscoverableLookup, EntityType};
+
+/// Metadata about the store.
+const META: TableDefinition<&str, u64> = TableDefinition::new("meta");
+/// Indices of entities keyed by their type and ID.
+const IDS: TableDefinition<(&str, u64), u64> = TableDefinition::new("ids");
+
+const VERSION_KEY: &str = "version";
+const LATEST_VERSION: u64 = 0;
+
+/// The table holding entities of a type keyed by their index.
+fn entities(ty: EntityType) -> TableDefinition<'static, u64, &'static [u8]> {
+    TableDefinition::new(ty.directory())
+}
+
+/// Errors which can occur when storing or loading a `VecLookup` in a key-value store.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum KvStoreError {
+    /// An entity could not be converted.
+    #[error("entity error: {}", source)]
+    Entity {
+        /// The error.
+        #[from]
+        source: VecStoreError,
+    },
+    /// A stored entity type has a gap in its indices.
+    #[error("missing {} at index {}", ty.name(), index)]
+    MissingEntity {
+        /// The type of the missing entity.
+        ty: EntityType,
+        /// The index of the missing entity.
+        index: u64,
+    },
+    /// An unsupported version of the store was found.
+    #[error("unsupported key-value store version: {}", version)]
+    UnsupportedVersion {
+        /// The unsupported version.
+        version: u64,
+    },
+    /// An entity could not be read on demand.
+    #[error("failed to read an entity: {}", reason)]
+    Lookup {
+        /// The reason the entity could not be read.
+        reason: String,
+    },
+    /// Database error.
+    #[error("database error: {}", source)]
+    Database {
+        /// The error.
+        source: Box<redb::Error>,
+    },
+}
+
+macro_rules! impl_from_database_error {
+    ($($t:ty),* $(,)?) => {
+        $(
+            impl From<$t> for KvStoreError {
+                fn from(err: $t) -> Self {
+                    Self::Database {
+                        source: Box::new(err.into()),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_from_database_error!(
+    redb::Error,
+    redb::CommitError,
+    redb::DatabaseError,
+    redb::StorageError,
+    redb::TableError,
+    redb::TransactionError,
+);
+
+/// Persistence of a `VecLookup` into a single-file embedded key-value database.
+///
+/// Each entity type is kept in its own table keyed by its index. Another table maps the IDs of
+/// entities (forge IDs or unique IDs) to their indices so that entities may be found without
+/// loading the store. Stores are written in a single transaction, so readers see either the old
+/// or the new contents and an interrupted write leaves the previous contents intact. Entities
+/// whose serialized form has not changed are not rewritten.
+pub struct KvStore {
+    db: Database,
+}
+
+impl KvStore {
+    /// Open a store, creating it if it does not exist.
+    pub fn create(path: &Path) -> Result<Self, KvStoreError> {
+        let db = Database::create(path)?;
+        let txn = db.begin_write()?;
+        {
+            let mut meta = txn.open_table(META)?;
+            let version = meta.get(VERSION_KEY)?.map(|version| version.value());
+            if version.is_none() {
+                meta.insert(VERSION_KEY, LATEST_VERSION)?;
+            }
+        }
+        txn.commit()?;
+
+        Self::check_version(db)
+    }
+
+    /// Open an existing store.
+    pub fn open(path: &Path) -> Result<Self, KvStoreError> {
+        Self::check_version(Database::open(path)?)
+    }
+
+    fn check_version(db: Database) -> Result<Self, KvStoreError> {
+        let txn = db.begin_read()?;
+        let version = match txn.open_table(META) {
+            Ok(meta) => meta.get(VERSION_KEY)?.map(|version| version.value()),
+            Err(TableError::TableDoesNotExist(_)) => None,
+            Err(err) => return Err(err.into()),
+        };
+        // Stores without a version have never been written to.
+        let version = version.unwrap_or(LATEST_VERSION);
+        if version != LATEST_VERSION {
+            return Err(KvStoreError::UnsupportedVersion {
+                version,
+            });
+        }
+        drop(txn);
+
+        Ok(Self {
+            db,
+        })
+    }
+
+    #[allow(clippy::ptr_arg)] // Ensure we're dealing with the entire set of entities.
+    fn persist<T>(
+        txn: &WriteTransaction,
+        ids: &mut Table<(&'static str, u64), u64>,
+        ty: EntityType,
+        objects: &[T],
+    ) -> Result<(), KvStoreError>
+    where
+        T: JsonStorable + HasId,
+    {
+        let name = ty.directory();
+        let mut table = txn.open_table(entities(ty))?;
+        let mut stale_ids = ids
+            .range((name, 0)..=(name, u64::MAX))?
+            .map(|entry| entry.map(|(key, index)| (key.value().1, index.value())))
+            .collect::<Result<BTreeMap<_, _>, _>>()?;
+
+        for (i, o) in objects.iter().enumerate() {
+            let index = i as u64;
+            let mut data = Vec::new();
+            o.write_json(&mut data).map_err(VecStoreError::from)?;
+            let unchanged = table
+                .get(index)?
+                .is_some_and(|stored| stored.value() == data.as_slice());
+            if !unchanged {
+                table.insert(index, data.as_slice())?;
+            }
+            if stale_ids.remove(&o.id()) != Some(index) {
+                ids.insert((name, o.id()), index)?;
+            }
+        }
+
+        let stale = table
+            .range(objects.len() as u64..)?
+            .map(|entry| entry.map(|(index, _)| index.value()))
+            .collect::<Result<Vec<_>, _>>()?;
+        for index in stale {
+            table.remove(index)?;
+        }
+        for id in stale_ids.into_keys() {
+            ids.remove((name, id))?;
+        }
+
+        Ok(())
+    }
+
+    /// Store a `VecLookup`, replacing the previous contents.
+    pub fn store(&self, store: &VecLookup) -> Result<(), KvStoreError> {
+        let txn = self.db.begin_write()?;
+        {
+            let mut ids = txn.open_table(IDS)?;
+            let ids = &mut ids;
+            Self::persist(
+                &txn,
+                ids,
+                EntityType::AuditEntry,
+                store.audit_entries.force(store)?,
+            )?;
+            Self::persist(
+                &txn,
+                ids,
+                EntityType::CrawlSession,
+                store.crawl_sessions.force(store)?,
+            )?;
+            Self::persist(
+                &txn,
+                ids,
+                EntityType::Deployment,
+                store.deployments.force(store)?,
+            )?;
+            Self::persist(
+                &txn,
+                ids,
+                EntityType::Environment,
+                store.environments.force(store)?,
+            )?;
+            Self::persist(
+                &txn,
+                ids,
+                EntityType::Instance,
+                store.instances.force(store)?,
+            )?;
+            Self::persist(&txn, ids, EntityType::Job, store.jobs.force(store)?)?;
+            Self::persist(
+                &txn,
+                ids,
+                EntityType::JobArtifact,
+                store.job_artifacts.force(store)?,
+            )?;
+            Self::persist(
+                &txn,
+                ids,
+                EntityType::MergeRequest,
+                store.merge_requests.force(store)?,
+            )?;
+            Self::persist(
+                &txn,
+                ids,
+                EntityType::Pipeline,
+                store.pipelines.force(store)?,
+            )?;
+            Self::persist(
+                &txn,
+                ids,
+                EntityType::PipelineSchedule,
+                store.pipeline_schedules.force(store)?,
+            )?;
+            Self::persist(&txn, ids, EntityType::Project, store.projects.force(store)?)?;
+            Self::persist(&txn, ids, EntityType::Push, store.pushes.force(store)?)?;
+            Self::persist(&txn, ids, EntityType::Release, store.releases.force(store)?)?;
+            Self::persist(&txn, ids, EntityType::Runner, store.runners.force(store)?)?;
+            Self::persist(
+                &txn,
+                ids,
+                EntityType::RunnerHost,
+                store.runner_hosts.force(store)?,
+            )?;
+            Self::persist(&txn, ids, EntityType::User, store.users.force(store)?)?;
+        }
+        txn.commit()?;
+
+        Ok(())
+    }
+
+    fn restore<T>(txn: &ReadTransaction, ty: EntityType) -> Result<LazyVec<T>, KvStoreError>
+    where
+        T: JsonStorable,
+    {
+        let table = match txn.open_table(entities(ty)) {
+            Ok(table) => table,
+            Err(TableError::TableDoesNotExist(_)) => return Ok(LazyVec::default()),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut vec = Vec::new();
+        for (i, entry) in table.iter()?.enumerate() {
+            let (index, data) = entry?;
+            // Indices are stored in order, so any gap shows up as a mismatch.
+            if index.value() != i as u64 {
+                return Err(KvStoreError::MissingEntity {
+                    ty,
+                    index: i as u64,
+                });
+            }
+            vec.push(T::read_json(data.value())?);
+        }
+
+        Ok(LazyVec::loaded(vec))
+    }
+
+    /// Load the stored `VecLookup`.
+    pub fn load(&self) -> Result<VecLookup, KvStoreError> {
+        let txn = self.db.begin_read()?;
+
+        let store = VecLookup {
+            audit_entries: Self::restore(&txn, EntityType::AuditEntry)?,
+            crawl_sessions: Self::restore(&txn, EntityType::CrawlSession)?,
+            deployments: Self::restore(&txn, EntityType::Deployment)?,
+            environments: Self::restore(&txn, EntityType::Environment)?,
+            instances: Self::restore(&txn, EntityType::Instance)?,
+            jobs: Self::restore(&txn, EntityType::Job)?,
+            job_artifacts: Self::restore(&txn, EntityType::JobArtifact)?,
+            merge_requests: Self::restore(&txn, EntityType::MergeRequest)?,
+            pipelines: Self::restore(&txn, EntityType::Pipeline)?,
+            pipeline_schedules: Self::restore(&txn, EntityType::PipelineSchedule)?,
+            projects: Self::restore(&txn, EntityType::Project)?,
+            pushes: Self::restore(&txn, EntityType::Push)?,
+            releases: Self::restore(&txn, EntityType::Release)?,
+            runners: Self::restore(&txn, EntityType::Runner)?,
+            runner_hosts: Self::restore(&txn, EntityType::RunnerHost)?,
+            users: Self::restore(&txn, EntityType::User)?,
+        };
+
+        VecStore::verify(&store, &store.audit_entries)?;
+        VecStore::verify(&store, &store.crawl_sessions)?;
+        VecStore::verify(&store, &store.deployments)?;
+        VecStore::verify(&store, &store.environments)?;
+        VecStore::verify(&store, &store.instances)?;
+        VecStore::verify(&store, &store.jobs)?;
+        VecStore::verify(&store, &store.job_artifacts)?;
+        VecStore::verify(&store, &store.merge_requests)?;
+        VecStore::verify(&store, &store.pipelines)?;
+        VecStore::verify(&store, &store.pipeline_schedules)?;
+        VecStore::verify(&store, &store.projects)?;
+        VecStore::verify(&store, &store.pushes)?;
+        VecStore::verify(&store, &store.releases)?;
+        VecStore::verify(&store, &store.runners)?;
+        VecStore::verify(&store, &store.runner_hosts)?;
+        VecStore::verify(&store, &store.users)?;
+
+        Ok(store)
+    }
+
+    /// Find the index of an entity by its ID without loading the store.
+    ///
+    /// The ID is the forge ID of the entity or, for entities without one, its unique ID. The
+    /// index is the position of the entity among those of its type in the loaded `VecLookup`.
+    pub fn find(&self, ty: EntityType, id: u64) -> Result<Option<usize>, KvStoreError> {
+        let txn = self.db.begin_read()?;
+        let ids = match txn.open_table(IDS) {
+            Ok(ids) => ids,
+            Err(TableError::TableDoesNotExist(_)) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let index = ids.get((ty.directory(), id))?;
+
+        Ok(index.map(|index| index.value() as usize))
+    }
+
+    /// Count the entities of each type in the store without loading them.
+    ///
+    /// Soft-deleted entities are included.
+    pub fn counts(&self) -> Result<Vec<(EntityType, usize)>, KvStoreError> {
+        let txn = self.db.begin_read()?;
+        let mut counts = Vec::with_capacity(EntityType::ALL.len());
+        for &ty in EntityType::ALL {
+            let count = match txn.open_table(entities(ty)) {
+                // Indices are contiguous, so the last one gives the count.
+                Ok(table) => table.last()?.map_or(0, |(index, _)| index.value() + 1),
+                Err(TableError::TableDoesNotExist(_)) => 0,
+                Err(err) => return Err(err.into()),
+            };
+            counts.push((ty, count as usize));
+        }
+
+        Ok(counts)
+    }
+
+    /// Look up entities in the store without loading it.
+    pub fn into_lookup(self) -> Result<KvLookup, KvStoreError> {
+        let counts = self.counts()?;
+
+        Ok(KvLookup {
+            store: self,
+            failure: OnceLock::new(),
+            audit_entries: KvTable::new(EntityType::AuditEntry, &counts),
+            crawl_sessions: KvTable::new(EntityType::CrawlSession, &counts),
+            deployments: KvTable::new(EntityType::Deployment, &counts),
+            environments: KvTable::new(EntityType::Environment, &counts),
+            instances: KvTable::new(EntityType::Instance, &counts),
+            jobs: KvTable::new(EntityType::Job, &counts),
+            job_artifacts: KvTable::new(EntityType::JobArtifact, &counts),
+            merge_requests: KvTable::new(EntityType::MergeRequest, &counts),
+            pipelines: KvTable::new(EntityType::Pipeline, &counts),
+            pipeline_schedules: KvTable::new(EntityType::PipelineSchedule, &counts),
+            projects: KvTable::new(EntityType::Project, &counts),
+            pushes: KvTable::new(EntityType::Push, &counts),
+            releases: KvTable::new(EntityType::Release, &counts),
+            runners: KvTable::new(EntityType::Runner, &counts),
+            runner_hosts: KvTable::new(EntityType::RunnerHost, &counts),
+            users: KvTable::new(EntityType::User, &counts),
+        })
+    }
+}
+
+/// Entities of a type in a `KvLookup`.
+struct KvTable<T> {
+    ty: EntityType,
+    /// Entities which have been read or stored, by index.
+    entities: Vec<OnceLock<T>>,
+    /// The indices of entities stored since the last commit, by ID.
+    stored: BTreeMap<u64, usize>,
+}
+
+impl<T> KvTable<T> {
+    fn new(ty: EntityType, counts: &[(EntityType, usize)]) -> Self {
+        let count = counts
+            .iter()
+            .find(|&&(counted, _)| counted == ty)
+            .map_or(0, |&(_, count)| count);
+
+        Self {
+            ty,
+            entities: (0..count).map(|_| OnceLock::new()).collect(),
+            stored: BTreeMap::new(),
+        }
+    }
+
+    fn insert(&mut self, index: usize, id: u64, data: T) {
+        if index == self.entities.len() {
+            self.entities.push(OnceLock::from(data));
+        } else {
+            self.entities[index] = OnceLock::from(data);
+        }
+        self.stored.insert(id, index);
+    }
+}
+
+/// A lookup which reads entities from a `KvStore` on demand.
+///
+/// Entities are read when they are first looked up and kept in memory afterwards. Stored entities
+/// are kept in memory until `KvLookup::commit` writes them in a single transaction. Indices are
+/// the same as those of the `VecLookup` loaded from the store, so entities refer to each other
+/// using `VecIndex`. Entities are not verified against each other when read; use `KvStore::load`
+/// for that.
+///
+/// Entities which cannot be read appear to be missing. The failure is kept and reported by
+/// `KvLookup::check` and `KvLookup::commit`.
+///
+/// IDs are indexed per entity type rather than per instance, so `find_in` only finds an entity
+/// if the one indexed for its ID belongs to the instance.
+pub struct KvLookup {
+    store: KvStore,
+    failure: OnceLock<String>,
+    audit_entries: KvTable<AuditEntry>,
+    crawl_sessions: KvTable<CrawlSession<VecLookup>>,
+    deployments: KvTable<Deployment<VecLookup>>,
+    environments: KvTable<Environment<VecLookup>>,
+    instances: KvTable<Instance>,
+    jobs: KvTable<Job<VecLookup>>,
+    job_artifacts: KvTable<JobArtifact<VecLookup>>,
+    merge_requests: KvTable<MergeRequest<VecLookup>>,
+    pipelines: KvTable<Pipeline<VecLookup>>,
+    pipeline_schedules: KvTable<PipelineSchedule<VecLookup>>,
+    projects: KvTable<Project<VecLookup>>,
+    pushes: KvTable<Push<VecLookup>>,
+    releases: KvTable<Release<VecLookup>>,
+    runners: KvTable<Runner<VecLookup>>,
+    runner_hosts: KvTable<RunnerHost>,
+    users: KvTable<User<VecLookup>>,
+}
+
+impl KvLookup {
+    /// Report entities which could not be read.
+    pub fn check(&self) -> Result<(), KvStoreError> {
+        if let Some(reason) = self.failure.get() {
+            return Err(KvStoreError::Lookup {
+                reason: reason.clone(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Write the entities stored since the last commit.
+    ///
+    /// Nothing is written if any entity could not be read since the lookup may be incomplete.
+    pub fn commit(&mut self) -> Result<(), KvStoreError> {
+        self.check()?;
+
+        let txn = self.store.db.begin_write()?;
+        {
+            let mut ids = txn.open_table(IDS)?;
+            let ids = &mut ids;
+            Self::commit_table(&txn, ids, &self.audit_entries)?;
+            Self::commit_table(&txn, ids, &self.crawl_sessions)?;
+            Self::commit_table(&txn, ids, &self.deployments)?;
+            Self::commit_table(&txn, ids, &self.environments)?;
+            Self::commit_table(&txn, ids, &self.instances)?;
+            Self::commit_table(&txn, ids, &self.jobs)?;
+            Self::commit_table(&txn, ids, &self.job_artifacts)?;
+            Self::commit_table(&txn, ids, &self.merge_requests)?;
+            Self::commit_table(&txn, ids, &self.pipelines)?;
+            Self::commit_table(&txn, ids, &self.pipeline_schedules)?;
+            Self::commit_table(&txn, ids, &self.projects)?;
+            Self::commit_table(&txn, ids, &self.pushes)?;
+            Self::commit_table(&txn, ids, &self.releases)?;
+            Self::commit_table(&txn, ids, &self.runners)?;
+            Self::commit_table(&txn, ids, &self.runner_hosts)?;
+            Self::commit_table(&txn, ids, &self.users)?;
+        }
+        txn.commit()?;
+
+        self.audit_entries.stored.clear();
+        self.crawl_sessions.stored.clear();
+        self.deployments.stored.clear();
+        self.environments.stored.clear();
+        self.instances.stored.clear();
+        self.jobs.stored.clear();
+        self.job_artifacts.stored.clear();
+        self.merge_requests.stored.clear();
+        self.pipelines.stored.clear();
+        self.pipeline_schedules.stored.clear();
+        self.projects.stored.clear();
+        self.pushes.stored.clear();
+        self.releases.stored.clear();
+        self.runners.stored.clear();
+        self.runner_hosts.stored.clear();
+        self.users.stored.clear();
+
+        Ok(())
+    }
+
+    fn commit_table<T>(
+        txn: &WriteTransaction,
+        ids: &mut Table<(&'static str, u64), u64>,
+        table: &KvTable<T>,
+    ) -> Result<(), KvStoreError>
+    where
+        T: JsonStorable,
+    {
+        let name = table.ty.directory();
+        let mut entities_table = txn.open_table(entities(table.ty))?;
+        for (&id, &index) in &table.stored {
+            let o = table.entities[index]
+                .get()
+                .expect("stored entities are kept in memory");
+            let mut data = Vec::new();
+            o.write_json(&mut data).map_err(VecStoreError::from)?;
+            entities_table.insert(index as u64, data.as_slice())?;
+            ids.insert((name, id), index as u64)?;
+        }
+
+        Ok(())
+    }
+
+    fn read<T>(&self, ty: EntityType, index: usize) -> Result<T, KvStoreError>
+    where
+        T: JsonStorable,
+    {
+        let txn = self.store.db.begin_read()?;
+        let table = txn.open_table(entities(ty))?;
+        let data = table
+            .get(index as u64)?
+            .ok_or(KvStoreError::MissingEntity {
+                ty,
+                index: index as u64,
+            })?;
+
+        Ok(T::read_json(data.value())?)
+    }
+
+    fn get<'a, T>(&'a self, table: &'a KvTable<T>, index: usize) -> Option<&'a T>
+    where
+        T: JsonStorable,
+    {
+        let entity = table.entities.get(index)?;
+        if let Some(o) = entity.get() {
+            return Some(o);
+        }
+
+        match self.read(table.ty, index) {
+            Ok(o) => Some(entity.get_or_init(|| o)),
+            Err(err) => {
+                self.failure.get_or_init(|| err.to_string());
+                None
+            },
+        }
+    }
+
+    fn find_index<T>(&self, table: &KvTable<T>, id: u64) -> Option<usize> {
+        if let Some(&index) = table.stored.get(&id) {
+            return Some(index);
+        }
+
+        self.store.find(table.ty, id).unwrap_or_else(|err| {
+            self.failure.get_or_init(|| err.to_string());
+            None
+        })
+    }
+}
+
+impl InstanceLookup for KvLookup {}
+
+macro_rules! impl_kv_lookup {
+    ($t:ty, $field:ident) => {
+        impl Lookup<$t> for KvLookup {
+            type Index = VecIndex<$t>;
+
+            fn lookup<'a>(&'a self, idx: &'a Self::Index) -> Option<&'a $t> {
+                self.get(&self.$field, idx.idx)
+            }
+
+            fn store(&mut self, data: $t) -> Self::Index {
+                let id = data.id();
+                let index = self
+                    .find_index(&self.$field, id)
+                    .unwrap_or(self.$field.entities.len());
+                self.$field.insert(index, id, data);
+                Self::Index::new(index)
+            }
+        }
+
+        impl DiscoverableLookup<$t> for KvLookup {
+            fn all_indices(&self) -> Vec<Self::Index> {
+                (0..self.$field.entities.len())
+                    .map(Self::Index::new)
+                    .collect()
+            }
+
+            fn find(&self, id: u64) -> Option<Self::Index> {
+                self.find_index(&self.$field, id).map(Self::Index::new)
+            }
+
+            fn find_in(&self, instance: &VecIndex<Instance>, id: u64) -> Option<Self::Index> {
+                self.find_index(&self.$field, id)
+                    .filter(|&index| {
+                        self.get(&self.$field, index)
+                            .is_some_and(|ent| ent.has_id_in(self, instance, id))
+                    })
+                    .map(Self::Index::new)
+            }
+        }
+    };
+}
+
+impl_kv_lookup!(AuditEntry, audit_entries);
+impl_kv_lookup!(CrawlSession<VecLookup>, crawl_sessions);
+impl_kv_lookup!(Deployment<VecLookup>, deployments);
+impl_kv_lookup!(Environment<VecLookup>, environments);
+impl_kv_lookup!(Instance, instances);
+impl_kv_lookup!(Job<VecLookup>, jobs);
+impl_kv_lookup!(JobArtifact<VecLookup>, job_artifacts);
+impl_kv_lookup!(MergeRequest<VecLookup>, merge_requests);
+impl_kv_lookup!(Pipeline<VecLookup>, pipelines);
+impl_kv_lookup!(PipelineSchedule<VecLookup>, pipeline_schedules);
+impl_kv_lookup!(Project<VecLookup>, projects);
+impl_kv_lookup!(Push<VecLookup>, pushes);
+impl_kv_lookup!(Release<VecLookup>, releases);
+impl_kv_lookup!(Runner<VecLookup>, runners);
+impl_kv_lookup!(RunnerHost, runner_hosts);
+impl_kv_lookup!(User<VecLookup>, users);
+
+#[cfg(test)]
+mod tests {
+    use ci_monitor_core::data::{Instance, Project, User};
+    use ci_monitor_core::Lookup;
+
+    use crate::test::{populated, tempdir};
+    use crate::{DiscoverableLookup, EntityType, KvLookup, KvStore, KvStoreError, VecLookup};
+
+    #[test]
+    fn store_and_load() {
+        let workdir = tempdir();
+        let path = workdir.path().join("store.redb");
+        let store = KvStore::create(&path).unwrap();
+        store.store(&populated()).unwrap();
+        drop(store);
+
+        let store = KvStore::open(&path).unwrap();
+        let lookup = store.load().unwrap();
+        let project = <VecLookup as DiscoverableLookup<Project<VecLookup>>>::find(&lookup, 1);
+        let project = project.unwrap();
+        let project = <VecLookup as Lookup<Project<VecLookup>>>::lookup(&lookup, &project);
+        assert_eq!(project.unwrap().url, "project");
+
+        let counts = store.counts().unwrap();
+        assert_eq!(counts.len(), EntityType::ALL.len());
+        assert!(counts.contains(&(EntityType::Project, 1)));
+        assert!(counts.contains(&(EntityType::User, 1)));
+        assert!(counts.contains(&(EntityType::Job, 0)));
+
+        assert_eq!(store.find(EntityType::User, 2).unwrap(), Some(0));
+        assert_eq!(store.find(EntityType::User, 1).unwrap(), None);
+    }
+
+    #[test]
+    fn store_updates() {
+        let workdir = tempdir();
+        let store = KvStore::create(&workdir.path().join("store.redb")).unwrap();
+        let mut lookup = populated();
+        store.store(&lookup).unwrap();
+
+        let instance = <VecLookup as DiscoverableLookup<Instance>>::find(&lookup, 0).unwrap();
+        let project = Project::builder()
+            .forge_id(1)
+            .instance(instance)
+            .url("renamed")
+            .build()
+            .unwrap();
+        lookup.store(project);
+        let user = User::builder()
+            .forge_id(3)
+            .instance(instance)
+            .build()
+            .unwrap();
+        lookup.store(user);
+        store.store(&lookup).unwrap();
+
+        let lookup = store.load().unwrap();
+        let project = <VecLookup as DiscoverableLookup<Project<VecLookup>>>::find(&lookup, 1);
+        let project = project.unwrap();
+        let project = <VecLookup as Lookup<Project<VecLookup>>>::lookup(&lookup, &project);
+        assert_eq!(project.unwrap().url, "renamed");
+        assert_eq!(store.find(EntityType::User, 3).unwrap(), Some(1));
+
+        // Replacing the contents removes entities which are gone.
+        store.store(&VecLookup::default()).unwrap();
+        assert_eq!(store.find(EntityType::User, 2).unwrap(), None);
+        assert!(store.counts().unwrap().iter().all(|&(_, count)| count == 0));
+    }
+
+    #[test]
+    fn lookup() {
+        let workdir = tempdir();
+        let path = workdir.path().join("store.redb");
+        let store = KvStore::create(&path).unwrap();
+        store.store(&populated()).unwrap();
+
+        let mut lookup = store.into_lookup().unwrap();
+        let project = <KvLookup as DiscoverableLookup<Project<VecLookup>>>::find(&lookup, 1);
+        let project = project.unwrap();
+        let project = <KvLookup as Lookup<Project<VecLookup>>>::lookup(&lookup, &project).unwrap();
+        assert_eq!(project.url, "project");
+        let instance = <KvLookup as Lookup<Instance>>::lookup(&lookup, &project.instance).unwrap();
+        assert_eq!(instance.forge, "forge");
+        let instance = project.instance;
+
+        // Stored entities are visible before they are committed.
+        let user = User::builder()
+            .forge_id(3)
+            .instance(instance)
+            .build()
+            .unwrap();
+        let user = <KvLookup as Lookup<User<VecLookup>>>::store(&mut lookup, user);
+        let found = <KvLookup as DiscoverableLookup<User<VecLookup>>>::find(&lookup, 3);
+        assert_eq!(found, Some(user));
+        let users = <KvLookup as DiscoverableLookup<User<VecLookup>>>::all_indices(&lookup);
+        assert_eq!(users.len(), 2);
+        lookup.commit().unwrap();
+        drop(lookup);
+
+        let store = KvStore::open(&path).unwrap();
+        assert_eq!(store.find(EntityType::User, 3).unwrap(), Some(1));
+        let loaded = store.load().unwrap();
+        let user = <VecLookup as DiscoverableLookup<User<VecLookup>>>::find(&loaded, 3);
+        assert!(user.is_some());
+        let lookup = store.into_lookup().unwrap();
+        assert!(lookup.check().is_ok());
+    }
+
+    #[test]
+    fn open_missing() {
+        let workdir = tempdir();
+        let err = KvStore::open(&workdir.path().join("missing.redb"))
+            .err()
+            .unwrap();
+        assert!(matches!(err, KvStoreError::Database { .. }));
+    }
+}
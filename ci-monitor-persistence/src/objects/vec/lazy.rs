@@ -0,0 +1,157 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use super::data::JsonStorable;
+use super::{VecIndex, VecLookup, VecStore, VecStoreError};
+
+/// Where to load deferred entities from.
+#[derive(Debug, Clone)]
+struct DeferredLoad {
+    /// The directory of the store.
+    root: PathBuf,
+    /// The directory of the entities.
+    path: PathBuf,
+    count: usize,
+    /// The generation of the store the entities belong to.
+    generation: u64,
+}
+
+/// A `Vec` of entities which may be loaded from a store on first access.
+///
+/// Deferred entities are only loaded from the generation of the store they were deferred from.
+/// If they cannot be loaded, the entities are treated as empty and the failure is kept so that it
+/// may be reported by `force`.
+#[derive(Clone)]
+pub(super) struct LazyVec<T> {
+    entities: OnceLock<Vec<T>>,
+    deferred: Option<DeferredLoad>,
+    failure: OnceLock<String>,
+}
+
+impl<T> Default for LazyVec<T> {
+    fn default() -> Self {
+        Self::loaded(Vec::new())
+    }
+}
+
+impl<T> LazyVec<T> {
+    /// Entities which have already been loaded.
+    pub(super) fn loaded(entities: Vec<T>) -> Self {
+        Self {
+            entities: OnceLock::from(entities),
+            deferred: None,
+            failure: OnceLock::new(),
+        }
+    }
+
+    /// Entities which are loaded from a generation of a store when first accessed.
+    pub(super) fn deferred(root: PathBuf, path: PathBuf, count: usize, generation: u64) -> Self {
+        Self {
+            entities: OnceLock::new(),
+            deferred: Some(DeferredLoad {
+                root,
+                path,
+                count,
+                generation,
+            }),
+            failure: OnceLock::new(),
+        }
+    }
+
+    /// The number of entities.
+    ///
+    /// Does not load deferred entities.
+    pub(super) fn len(&self) -> usize {
+        if let Some(entities) = self.entities.get() {
+            entities.len()
+        } else {
+            self.deferred.as_ref().map_or(0, |deferred| deferred.count)
+        }
+    }
+
+    /// Whether the entities have been loaded.
+    pub(super) fn is_loaded(&self) -> bool {
+        self.entities.get().is_some()
+    }
+
+    /// Mutable access to the entities.
+    ///
+    /// Deferred entities must have been loaded using `force` beforehand; otherwise they are
+    /// treated as having failed to load.
+    pub(super) fn loaded_mut(&mut self) -> &mut Vec<T> {
+        if self.entities.get().is_none() {
+            self.failure
+                .get_or_init(|| "entities were modified before being loaded".into());
+        }
+        self.entities.get_or_init(Vec::new);
+        self.entities
+            .get_mut()
+            .expect("entities are initialized above")
+    }
+
+    /// Report a failure to load the entities, if any, without loading them.
+    pub(super) fn check(&self) -> Result<(), VecStoreError> {
+        if let Some(reason) = self.failure.get() {
+            return Err(VecStoreError::Deferred {
+                reason: reason.clone(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl<T> LazyVec<T>
+where
+    T: JsonStorable,
+{
+    /// Access the entities, loading and verifying them against `store` if they were deferred.
+    pub(super) fn force(&self, store: &VecLookup) -> Result<&Vec<T>, VecStoreError> {
+        self.check()?;
+        if let Some(entities) = self.entities.get() {
+            return Ok(entities);
+        }
+
+        let res = self.load().and_then(|entities| {
+            for (i, o) in entities.iter().enumerate() {
+                o.validate_indices(VecIndex::new(i), store)?;
+            }
+            Ok(entities)
+        });
+        match res {
+            // Another thread may have loaded the entities in the meantime; either copy is the same.
+            Ok(entities) => Ok(self.entities.get_or_init(|| entities)),
+            Err(err) => {
+                self.failure.get_or_init(|| err.to_string());
+                self.entities.get_or_init(Vec::new);
+                Err(err)
+            },
+        }
+    }
+
+    /// The entities, or none if they could not be loaded.
+    ///
+    /// The failure may be retrieved using `force`.
+    pub(super) fn entities(&self, store: &VecLookup) -> &[T] {
+        self.force(store).map_or(&[], Vec::as_slice)
+    }
+
+    fn load(&self) -> Result<Vec<T>, VecStoreError> {
+        if let Some(deferred) = self.deferred.as_ref() {
+            VecStore::restore_generation(
+                &deferred.root,
+                deferred.path.clone(),
+                deferred.count,
+                deferred.generation,
+            )
+        } else {
+            Ok(Vec::new())
+        }
+    }
+}
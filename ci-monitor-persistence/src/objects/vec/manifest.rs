@@ -0,0 +1,391 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use ci_monitor_core::data::{Blob, BlobReference, ContentHash};
+use serde::{Deserialize, Serialize};
+
+use super::persist::{self, Counts, Index};
+use super::{VecStore, VecStoreError};
+use crate::EntityType;
+
+pub(super) const MANIFEST_NAME: &str = "manifest.json";
+
+/// The checksum of an entity file.
+pub(super) fn checksum(blob: &Blob) -> String {
+    BlobReference::for_blob(blob, ContentHash::Sha256)
+        .hash()
+        .into()
+}
+
+/// The files making up the entities of a single type.
+///
+/// Written after the entity files so that it describes a complete directory.
+#[derive(Deserialize, Serialize)]
+pub(super) struct Manifest {
+    count: usize,
+    sha256: Vec<String>,
+}
+
+impl Manifest {
+    pub(super) fn new(sha256: Vec<String>) -> Self {
+        Self {
+            count: sha256.len(),
+            sha256,
+        }
+    }
+
+    pub(super) fn write(&self, dir: &Path) -> Result<(), VecStoreError> {
+        fs::write(dir.join(MANIFEST_NAME), serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+
+    fn read(dir: &Path) -> Result<Option<Self>, VecStoreError> {
+        match fs::read(dir.join(MANIFEST_NAME)) {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+/// A problem with the files of an entity type in a store.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ManifestProblem {
+    /// The entity type has no manifest.
+    MissingManifest {
+        /// The entity type.
+        entity_type: EntityType,
+    },
+    /// The store index and the manifest disagree about the number of entities.
+    CountMismatch {
+        /// The entity type.
+        entity_type: EntityType,
+        /// The number of entities according to the store index.
+        index: usize,
+        /// The number of entities according to the manifest.
+        manifest: usize,
+    },
+    /// An entity file is missing.
+    MissingFile {
+        /// The entity type.
+        entity_type: EntityType,
+        /// The index of the entity.
+        index: usize,
+    },
+    /// An entity file does not match its checksum.
+    ChecksumMismatch {
+        /// The entity type.
+        entity_type: EntityType,
+        /// The index of the entity.
+        index: usize,
+    },
+    /// A file which is not part of the manifest.
+    ExtraFile {
+        /// The entity type.
+        entity_type: EntityType,
+        /// The name of the file.
+        name: String,
+    },
+}
+
+impl ManifestProblem {
+    /// The entity type with the problem.
+    pub fn entity_type(&self) -> EntityType {
+        match self {
+            Self::MissingManifest {
+                entity_type,
+            }
+            | Self::CountMismatch {
+                entity_type, ..
+            }
+            | Self::MissingFile {
+                entity_type, ..
+            }
+            | Self::ChecksumMismatch {
+                entity_type, ..
+            }
+            | Self::ExtraFile {
+                entity_type, ..
+            } => *entity_type,
+        }
+    }
+}
+
+impl fmt::Display for ManifestProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::MissingManifest {
+                entity_type,
+            } => write!(f, "{}: no manifest", entity_type.name()),
+            Self::CountMismatch {
+                entity_type,
+                index,
+                manifest,
+            } => {
+                write!(
+                    f,
+                    "{}: index has {} entities, manifest has {}",
+                    entity_type.name(),
+                    index,
+                    manifest,
+                )
+            },
+            Self::MissingFile {
+                entity_type,
+                index,
+            } => write!(f, "{}@{}: missing", entity_type.name(), index),
+            Self::ChecksumMismatch {
+                entity_type,
+                index,
+            } => write!(f, "{}@{}: checksum mismatch", entity_type.name(), index),
+            Self::ExtraFile {
+                entity_type,
+                name,
+            } => write!(f, "{}: unexpected file '{}'", entity_type.name(), name),
+        }
+    }
+}
+
+/// The result of checking the directory of an entity type.
+struct DirectoryCheck {
+    problems: Vec<ManifestProblem>,
+    /// Checksums of the longest run of intact entity files.
+    intact: Vec<String>,
+}
+
+fn check_directory(
+    dir: &Path,
+    entity_type: EntityType,
+    index_count: usize,
+) -> Result<DirectoryCheck, VecStoreError> {
+    let mut problems = Vec::new();
+    let mut intact = Vec::new();
+    let mut is_intact = true;
+
+    let manifest = Manifest::read(dir)?;
+    let expected = if let Some(manifest) = manifest.as_ref() {
+        if manifest.count != index_count {
+            problems.push(ManifestProblem::CountMismatch {
+                entity_type,
+                index: index_count,
+                manifest: manifest.count,
+            });
+        }
+        manifest.count
+    } else {
+        problems.push(ManifestProblem::MissingManifest {
+            entity_type,
+        });
+        index_count
+    };
+
+    for index in 0..expected {
+        let data = match fs::read(dir.join(format!("{}.json", index))) {
+            Ok(data) => data,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                problems.push(ManifestProblem::MissingFile {
+                    entity_type,
+                    index,
+                });
+                is_intact = false;
+                continue;
+            },
+            Err(err) => return Err(err.into()),
+        };
+        let sum = checksum(&Blob::new(data));
+        let expected_sum = manifest
+            .as_ref()
+            .and_then(|manifest| manifest.sha256.get(index));
+        if expected_sum.is_some_and(|expected_sum| *expected_sum != sum) {
+            problems.push(ManifestProblem::ChecksumMismatch {
+                entity_type,
+                index,
+            });
+            is_intact = false;
+        } else if is_intact {
+            intact.push(sum);
+        }
+    }
+
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries.collect::<Result<Vec<_>, _>>()?,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(err) => return Err(err.into()),
+    };
+    let mut extra = entries
+        .into_iter()
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| {
+            let index = name
+                .strip_suffix(".json")
+                .and_then(|index| index.parse::<usize>().ok());
+            name != MANIFEST_NAME && index.is_none_or(|index| expected <= index)
+        })
+        .collect::<Vec<_>>();
+    extra.sort();
+    problems.extend(extra.into_iter().map(|name| {
+        ManifestProblem::ExtraFile {
+            entity_type,
+            name,
+        }
+    }));
+
+    Ok(DirectoryCheck {
+        problems,
+        intact,
+    })
+}
+
+impl VecStore {
+    /// Check the entity files of a store against their manifests.
+    ///
+    /// Detects entity files which are missing, corrupt, or unaccounted for as well as
+    /// disagreements between the store index and the manifests.
+    pub fn check(path: &Path) -> Result<Vec<ManifestProblem>, VecStoreError> {
+        let mut counts = Index::read(path)?.counts;
+
+        let mut problems = Vec::new();
+        for &ty in EntityType::ALL {
+            let check = check_directory(&path.join(ty.directory()), ty, *counts.get_mut(ty))?;
+            problems.extend(check.problems);
+        }
+
+        Ok(problems)
+    }
+
+    /// Repair the index of a store from its manifests.
+    ///
+    /// Each entity type keeps the longest run of intact entity files; entity types without a
+    /// manifest keep the entities the store index accounts for. Manifests and the store index are
+    /// rewritten to match. Returns the problems which were found.
+    ///
+    /// References to entities which were dropped are detected when the store is loaded. Entities
+    /// staged by a write of the store which was interrupted are discarded and the entities it
+    /// replaced are restored.
+    pub fn repair(path: &Path) -> Result<Vec<ManifestProblem>, VecStoreError> {
+        let lock = persist::lock_file(path)?;
+        lock.lock()?;
+
+        let mut index = match Index::read(path) {
+            Ok(index) => index,
+            Err(VecStoreError::Io {
+                source,
+            }) if source.kind() == io::ErrorKind::NotFound => Index::new(Counts::default()),
+            Err(err) => return Err(err),
+        };
+        Self::discard_staged(path, index.writing)?;
+
+        let mut problems = Vec::new();
+        for &ty in EntityType::ALL {
+            let dir = path.join(ty.directory());
+            let count = index.counts.get_mut(ty);
+            let check = check_directory(&dir, ty, *count)?;
+
+            *count = check.intact.len();
+            if !check.problems.is_empty() {
+                fs::create_dir_all(&dir)?;
+                Manifest::new(check.intact).write(&dir)?;
+            }
+            problems.extend(check.problems);
+        }
+
+        index.generation += 1;
+        index.writing = false;
+        index.write(path)?;
+
+        Ok(problems)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::test::{self, tempdir};
+    use crate::{EntityType, ManifestProblem, VecLookup, VecStore};
+
+    fn populated() -> VecLookup {
+        let mut lookup = VecLookup::default();
+        let instance = test::instance(&mut lookup);
+        for id in 0..3 {
+            test::project(&mut lookup, instance, id);
+        }
+        lookup
+    }
+
+    #[test]
+    fn check_intact() {
+        let workdir = tempdir();
+        VecStore::store(workdir.path(), &populated()).unwrap();
+
+        assert_eq!(VecStore::check(workdir.path()).unwrap(), []);
+    }
+
+    #[test]
+    fn check_problems() {
+        let workdir = tempdir();
+        VecStore::store(workdir.path(), &populated()).unwrap();
+        let projects = workdir.path().join("projects");
+        fs::write(projects.join("1.json"), "{}").unwrap();
+        fs::remove_file(projects.join("2.json")).unwrap();
+        fs::write(projects.join("3.json"), "{}").unwrap();
+        fs::remove_file(workdir.path().join("users").join("manifest.json")).unwrap();
+
+        let problems = VecStore::check(workdir.path()).unwrap();
+
+        assert_eq!(
+            problems,
+            [
+                ManifestProblem::ChecksumMismatch {
+                    entity_type: EntityType::Project,
+                    index: 1,
+                },
+                ManifestProblem::MissingFile {
+                    entity_type: EntityType::Project,
+                    index: 2,
+                },
+                ManifestProblem::ExtraFile {
+                    entity_type: EntityType::Project,
+                    name: "3.json".into(),
+                },
+                ManifestProblem::MissingManifest {
+                    entity_type: EntityType::User,
+                },
+            ],
+        );
+        assert_eq!(problems[0].to_string(), "project@1: checksum mismatch");
+    }
+
+    #[test]
+    fn repair() {
+        let workdir = tempdir();
+        VecStore::store(workdir.path(), &populated()).unwrap();
+        let projects = workdir.path().join("projects");
+        fs::remove_file(projects.join("2.json")).unwrap();
+
+        // The store cannot be loaded with a missing entity.
+        assert!(VecStore::load(workdir.path()).is_err());
+
+        let problems = VecStore::repair(workdir.path()).unwrap();
+        assert_eq!(
+            problems,
+            [ManifestProblem::MissingFile {
+                entity_type: EntityType::Project,
+                index: 2,
+            }],
+        );
+
+        let lookup = VecStore::load(workdir.path()).unwrap();
+        assert_eq!(lookup.projects.len(), 2);
+        assert_eq!(VecStore::check(workdir.path()).unwrap(), []);
+    }
+}
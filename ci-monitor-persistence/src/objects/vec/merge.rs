@@ -0,0 +1,282 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use ci_monitor_core::data::{Instance, User};
+
+use crate::{VecIndex, VecLookup};
+
+impl VecLookup {
+    /// Merge one user record into another.
+    ///
+    /// This is for distinct accounts which belong to the same person. Jobs, pipelines, merge
+    /// requests, pipeline schedules, pushes, and releases referring to `from` are updated to refer
+    /// to `into` and the handles of `from` are recorded as previous handles of `into`. The `from`
+    /// record is kept since the forge still knows about it; entities fetched again later may refer
+    /// to it and require another merge.
+    ///
+    /// Returns the number of references which were updated.
+    pub fn merge_users(
+        &mut self,
+        from: &VecIndex<User<Self>>,
+        into: &VecIndex<User<Self>>,
+    ) -> usize {
+        let (from, into) = (from.idx, into.idx);
+        if from == into {
+            return 0;
+        }
+        self.load_for_update();
+        let handles = if let Some(user) = self.users.entities(self).get(from) {
+            let mut handles = user.previous_handles.clone();
+            handles.push(user.handle.clone());
+            handles
+        } else {
+            return 0;
+        };
+        if let Some(user) = self.users.loaded_mut().get_mut(into) {
+            for handle in handles {
+                user.add_previous_handle(handle);
+            }
+        } else {
+            return 0;
+        }
+
+        let mut moved = 0;
+        let mut update = |user: &mut VecIndex<User<Self>>| {
+            if user.idx == from {
+                *user = VecIndex::new(into);
+                moved += 1;
+            }
+        };
+
+        for job in self.jobs.loaded_mut() {
+            update(&mut job.user);
+            if let Some(played_by) = job.played_by.as_mut() {
+                update(played_by);
+            }
+        }
+        for merge_request in self.merge_requests.loaded_mut() {
+            update(&mut merge_request.author);
+        }
+        for pipeline in self.pipelines.loaded_mut() {
+            if let Some(user) = pipeline.user.as_mut() {
+                update(user);
+            }
+        }
+        for schedule in self.pipeline_schedules.loaded_mut() {
+            update(&mut schedule.owner);
+        }
+        for push in self.pushes.loaded_mut() {
+            if let Some(author) = push.author.as_mut() {
+                update(author);
+            }
+        }
+        for release in self.releases.loaded_mut() {
+            if let Some(author) = release.author.as_mut() {
+                update(author);
+            }
+        }
+
+        moved
+    }
+
+    /// Merge one instance record into another.
+    ///
+    /// This is for instances which were stored separately because they were reached under
+    /// different URLs. Crawl sessions, projects, runners, and users referring to `from` are
+    /// updated to refer to `into`. The `from` record is kept so that indices remain stable.
+    ///
+    /// Returns the number of references which were updated.
+    pub fn merge_instances(
+        &mut self,
+        from: &VecIndex<Instance>,
+        into: &VecIndex<Instance>,
+    ) -> usize {
+        let (from, into) = (from.idx, into.idx);
+        let instances = self.instances.entities(self);
+        if from == into || instances.get(from).is_none() || instances.get(into).is_none() {
+            return 0;
+        }
+        self.load_for_update();
+
+        let mut moved = 0;
+        let mut update = |instance: &mut VecIndex<Instance>| {
+            if instance.idx == from {
+                *instance = VecIndex::new(into);
+                moved += 1;
+            }
+        };
+
+        for session in self.crawl_sessions.loaded_mut() {
+            let mut instances = Vec::with_capacity(session.instances.len());
+            for mut instance in session.instances.drain(..) {
+                update(&mut instance);
+                // Sessions which covered both instances only cover the merged one once.
+                if !instances.contains(&instance) {
+                    instances.push(instance);
+                }
+            }
+            session.instances = instances;
+            for usage in &mut session.api_usage {
+                update(&mut usage.instance);
+            }
+        }
+        for project in self.projects.loaded_mut() {
+            update(&mut project.instance);
+        }
+        for runner in self.runners.loaded_mut() {
+            update(&mut runner.instance);
+        }
+        for user in self.users.loaded_mut() {
+            update(&mut user.instance);
+        }
+
+        moved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use ci_monitor_core::data::{
+        CrawlSession, Instance, Job, JobState, Pipeline, PipelineSource, PipelineStatus, Project,
+        Push, User,
+    };
+    use ci_monitor_core::Lookup;
+
+    use crate::VecLookup;
+
+    #[test]
+    fn merge_users() {
+        let mut lookup = VecLookup::default();
+        let instance = Instance::builder()
+            .unique_id(0)
+            .forge("forge")
+            .url("url")
+            .build()
+            .unwrap();
+        let instance = lookup.store(instance);
+        let [old, new, other] = [("old", 0), ("new", 1), ("other", 2)].map(|(handle, id)| {
+            let user = User::builder()
+                .handle(handle)
+                .forge_id(id)
+                .instance(instance)
+                .build()
+                .unwrap();
+            lookup.store(user)
+        });
+        let project = Project::builder()
+            .forge_id(0)
+            .instance(instance)
+            .url("project")
+            .build()
+            .unwrap();
+        let project = lookup.store(project);
+        let now = Utc::now();
+        let pipeline = Pipeline::builder()
+            .project(project)
+            .user(Some(old))
+            .sha("0000000000000000000000000000000000000000")
+            .source(PipelineSource::Push)
+            .status(PipelineStatus::Success)
+            .forge_id(0)
+            .url("url")
+            .created_at(now)
+            .updated_at(now)
+            .build()
+            .unwrap();
+        let pipeline = lookup.store(pipeline);
+        let jobs = [old, other].map(|user| {
+            let job = Job::builder()
+                .user(user)
+                .state(JobState::Success)
+                .created_at(now)
+                .played_by(Some(old))
+                .forge_id(user.idx as u64)
+                .pipeline(pipeline)
+                .build()
+                .unwrap();
+            lookup.store(job)
+        });
+        let push = Push::builder()
+            .author(Some(old))
+            .project(project)
+            .refname("main")
+            .sha("0000000000000000000000000000000000000000")
+            .forge_id(0)
+            .pushed_at(now)
+            .build()
+            .unwrap();
+        let push = lookup.store(push);
+
+        assert_eq!(lookup.merge_users(&old, &old), 0);
+        // Pipeline user, two job players, one job user, and the push author.
+        assert_eq!(lookup.merge_users(&old, &new), 5);
+        assert_eq!(lookup.merge_users(&old, &new), 0);
+
+        let user = <VecLookup as Lookup<User<VecLookup>>>::lookup(&lookup, &new).unwrap();
+        assert_eq!(user.handle, "new");
+        assert_eq!(user.previous_handles, ["old"]);
+
+        let pipeline =
+            <VecLookup as Lookup<Pipeline<VecLookup>>>::lookup(&lookup, &pipeline).unwrap();
+        assert_eq!(pipeline.user, Some(new));
+        let job = <VecLookup as Lookup<Job<VecLookup>>>::lookup(&lookup, &jobs[0]).unwrap();
+        assert_eq!(job.user, new);
+        assert_eq!(job.played_by, Some(new));
+        let job = <VecLookup as Lookup<Job<VecLookup>>>::lookup(&lookup, &jobs[1]).unwrap();
+        assert_eq!(job.user, other);
+        let push = <VecLookup as Lookup<Push<VecLookup>>>::lookup(&lookup, &push).unwrap();
+        assert_eq!(push.author, Some(new));
+    }
+
+    #[test]
+    fn merge_instances() {
+        let mut lookup = VecLookup::default();
+        let [split, canonical] =
+            [(0, "git.example.com"), (1, "gitlab.example.com")].map(|(id, url)| {
+                let instance = Instance::builder()
+                    .unique_id(id)
+                    .forge("gitlab")
+                    .url(url)
+                    .build()
+                    .unwrap();
+                lookup.store(instance)
+            });
+        let project = Project::builder()
+            .forge_id(0)
+            .instance(split)
+            .url("project")
+            .build()
+            .unwrap();
+        let project = lookup.store(project);
+        let user = User::builder()
+            .forge_id(0)
+            .instance(canonical)
+            .build()
+            .unwrap();
+        let user = lookup.store(user);
+        let session = CrawlSession::builder()
+            .started_at(Utc::now())
+            .instances(vec![split, canonical])
+            .revision(0)
+            .build()
+            .unwrap();
+        let session = lookup.store(session);
+
+        assert_eq!(lookup.merge_instances(&split, &split), 0);
+        // The project and the session.
+        assert_eq!(lookup.merge_instances(&split, &canonical), 2);
+        assert_eq!(lookup.merge_instances(&split, &canonical), 0);
+
+        let project = <VecLookup as Lookup<Project<VecLookup>>>::lookup(&lookup, &project).unwrap();
+        assert_eq!(project.instance, canonical);
+        let user = <VecLookup as Lookup<User<VecLookup>>>::lookup(&lookup, &user).unwrap();
+        assert_eq!(user.instance, canonical);
+        let session =
+            <VecLookup as Lookup<CrawlSession<VecLookup>>>::lookup(&lookup, &session).unwrap();
+        assert_eq!(session.instances, [canonical]);
+    }
+}
@@ -4,16 +4,20 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::fs::{self, File};
+use std::fs::{self, File, OpenOptions};
 use std::io;
 use std::iter;
 use std::path::{Path, PathBuf};
 
+use ci_monitor_core::data::Blob;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use super::data::JsonStorable;
+use super::lazy::LazyVec;
+use super::manifest::{self, Manifest};
 use super::{VecIndex, VecLookup};
+use crate::EntityType;
 
 /// Persistence implementation for `VecLookup`.
 #[non_exhaustive]
@@ -61,6 +65,27 @@ pub enum VecStoreError {
         #[from]
         source: serde_json::Error,
     },
+    /// The store was left partially written by a writer which did not finish.
+    #[error("the store was left partially written; it must be repaired")]
+    Interrupted,
+    /// Deferred entities were requested after the store had been written again.
+    #[error(
+        "the store was written since it was loaded (generation {} is now {})",
+        generation,
+        current
+    )]
+    GenerationChanged {
+        /// The generation the store was loaded from.
+        generation: u64,
+        /// The current generation of the store.
+        current: u64,
+    },
+    /// Deferred entities failed to load earlier.
+    #[error("deferred entities failed to load: {}", reason)]
+    Deferred {
+        /// Why the entities failed to load.
+        reason: String,
+    },
     /// I/O error.
     #[error("i/o error: {}", source)]
     Io {
@@ -70,11 +95,47 @@ pub enum VecStoreError {
     },
 }
 
-const INDEX_NAME: &str = "vecindex.json";
+pub(super) const INDEX_NAME: &str = "vecindex.json";
+const INDEX_TEMP_NAME: &str = "vecindex.json.tmp";
+const LOCK_NAME: &str = "vecindex.lock";
 const LATEST_VERSION: usize = 0;
 
-#[derive(Deserialize, Serialize)]
-struct Counts {
+/// The suffix of directories holding entities which are being written.
+const STAGED_SUFFIX: &str = ".new";
+/// The suffix of directories holding entities which are being replaced.
+const RETIRED_SUFFIX: &str = ".old";
+
+/// Open the lock file of a store.
+///
+/// Writers hold an exclusive lock on it and loads hold a shared lock. The lock is released when
+/// its holder exits, so a store which is marked as being written without a lock holder was left
+/// behind by a writer which did not finish.
+pub(super) fn lock_file(path: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path.join(LOCK_NAME))
+}
+
+fn suffixed(path: &Path, ty: EntityType, suffix: &str) -> PathBuf {
+    path.join(format!("{}{}", ty.directory(), suffix))
+}
+
+fn remove_dir(path: &Path) -> io::Result<()> {
+    match fs::remove_dir_all(path) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        res => res,
+    }
+}
+
+#[derive(Default, Deserialize, Serialize)]
+pub(super) struct Counts {
+    #[serde(default)]
+    audit_entries: usize,
+    #[serde(default)]
+    crawl_sessions: usize,
     deployments: usize,
     environments: usize,
     instances: usize,
@@ -84,71 +145,269 @@ struct Counts {
     pipelines: usize,
     pipeline_schedules: usize,
     projects: usize,
+    #[serde(default)]
+    pushes: usize,
+    #[serde(default)]
+    releases: usize,
     runners: usize,
     runner_hosts: usize,
     users: usize,
 }
 
+impl Counts {
+    pub(super) fn get_mut(&mut self, ty: EntityType) -> &mut usize {
+        match ty {
+            EntityType::AuditEntry => &mut self.audit_entries,
+            EntityType::CrawlSession => &mut self.crawl_sessions,
+            EntityType::Deployment => &mut self.deployments,
+            EntityType::Environment => &mut self.environments,
+            EntityType::Instance => &mut self.instances,
+            EntityType::Job => &mut self.jobs,
+            EntityType::JobArtifact => &mut self.job_artifacts,
+            EntityType::MergeRequest => &mut self.merge_requests,
+            EntityType::Pipeline => &mut self.pipelines,
+            EntityType::PipelineSchedule => &mut self.pipeline_schedules,
+            EntityType::Project => &mut self.projects,
+            EntityType::Push => &mut self.pushes,
+            EntityType::Release => &mut self.releases,
+            EntityType::Runner => &mut self.runners,
+            EntityType::RunnerHost => &mut self.runner_hosts,
+            EntityType::User => &mut self.users,
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize)]
-struct Index {
+pub(super) struct Index {
     version: usize,
-    counts: Counts,
+    /// Incremented each time the store is written.
+    #[serde(default)]
+    pub(super) generation: u64,
+    /// Whether the entities of the store are being written.
+    #[serde(default)]
+    pub(super) writing: bool,
+    pub(super) counts: Counts,
+}
+
+impl Index {
+    pub(super) fn new(counts: Counts) -> Self {
+        Self {
+            version: LATEST_VERSION,
+            generation: 0,
+            writing: false,
+            counts,
+        }
+    }
+
+    pub(super) fn read(path: &Path) -> Result<Self, VecStoreError> {
+        let index = File::open(path.join(INDEX_NAME))?;
+        let index: Self = serde_json::from_reader(index)?;
+        if index.version != LATEST_VERSION {
+            return Err(VecStoreError::UnsupportedVersion {
+                version: index.version,
+            });
+        }
+
+        Ok(index)
+    }
+
+    /// Write the index.
+    ///
+    /// The index is replaced atomically so that readers never see a partially written index.
+    pub(super) fn write(&self, path: &Path) -> Result<(), VecStoreError> {
+        let temp_path = path.join(INDEX_TEMP_NAME);
+        let index = File::create(&temp_path)?;
+        serde_json::to_writer_pretty(index, self)?;
+        fs::rename(temp_path, path.join(INDEX_NAME))?;
+        Ok(())
+    }
 }
 
 impl VecStore {
+    /// Write entities into the staging directory of their type.
     #[allow(clippy::ptr_arg)] // Ensure we're dealing with the entire set of entities.
-    fn persist<T>(path: PathBuf, objects: &Vec<T>) -> Result<usize, VecStoreError>
+    fn persist<T>(path: &Path, ty: EntityType, objects: &[T]) -> Result<usize, VecStoreError>
     where
         T: JsonStorable,
     {
+        let path = suffixed(path, ty, STAGED_SUFFIX);
+        // Discard anything staged by a write which did not finish.
+        remove_dir(&path)?;
         fs::create_dir_all(&path)?;
 
+        let mut checksums = Vec::with_capacity(objects.len());
         for (i, o) in objects.iter().enumerate() {
-            let path = path.join(format!("{}.json", i));
-            let file = File::create(path)?;
-            let json = o.to_json()?;
+            let mut data = Vec::new();
+            o.write_json(&mut data)?;
+            let data = Blob::new(data);
 
-            serde_json::to_writer_pretty(file, &json)?;
+            fs::write(path.join(format!("{}.json", i)), &*data)?;
+            checksums.push(manifest::checksum(&data));
         }
 
+        // The manifest is written last so that it only describes complete directories.
+        Manifest::new(checksums).write(&path)?;
+
         Ok(objects.len())
     }
 
+    /// Replace the entities of one type with its staged entities.
+    ///
+    /// The replaced entities are kept until the index of the write has been stored so that the
+    /// write may be rolled back.
+    fn commit_staged_type(path: &Path, ty: EntityType) -> Result<(), VecStoreError> {
+        let dir = path.join(ty.directory());
+        let retired = suffixed(path, ty, RETIRED_SUFFIX);
+        match fs::rename(&dir, &retired) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+            res => res?,
+        }
+        fs::rename(suffixed(path, ty, STAGED_SUFFIX), dir)?;
+
+        Ok(())
+    }
+
+    /// Replace the entities of a store with the staged entities.
+    fn commit_staged(path: &Path) -> Result<(), VecStoreError> {
+        for &ty in EntityType::ALL {
+            Self::commit_staged_type(path, ty)?;
+        }
+
+        Ok(())
+    }
+
+    /// Discard the staged entities of a write which did not finish.
+    ///
+    /// If `rollback` is set, the store is marked as being written and entities which were
+    /// replaced by the write are restored. Otherwise, replaced entities are left over from a
+    /// complete write and are removed.
+    pub(super) fn discard_staged(path: &Path, rollback: bool) -> Result<(), VecStoreError> {
+        for &ty in EntityType::ALL {
+            let dir = path.join(ty.directory());
+            let retired = suffixed(path, ty, RETIRED_SUFFIX);
+            if rollback && retired.exists() {
+                remove_dir(&dir)?;
+                fs::rename(&retired, &dir)?;
+            }
+            remove_dir(&retired)?;
+            remove_dir(&suffixed(path, ty, STAGED_SUFFIX))?;
+        }
+
+        Ok(())
+    }
+
+    /// Roll back a write of the store which did not finish.
+    ///
+    /// Must be called with the store locked exclusively.
+    fn roll_back(path: &Path) -> Result<(), VecStoreError> {
+        let mut index = Index::read(path)?;
+        if index.writing {
+            Self::discard_staged(path, true)?;
+            index.writing = false;
+            index.write(path)?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether a write of the store stopped while replacing entities.
+    fn is_interrupted(path: &Path) -> bool {
+        EntityType::ALL
+            .iter()
+            .any(|&ty| suffixed(path, ty, RETIRED_SUFFIX).exists())
+    }
+
     /// Store a `VecLookup` to a directory.
+    ///
+    /// Entities are written next to the existing entities and swapped in once all of them have
+    /// been written. The store is locked while it is being written so that concurrent loads wait
+    /// for the write to complete rather than mixing old and new entities. A write which did not
+    /// finish is rolled back first.
     pub fn store(path: &Path, store: &VecLookup) -> Result<(), VecStoreError> {
+        // Deferred entities may come from the same store, so they must be read before it is
+        // locked for writing.
+        store.load_deferred()?;
+
+        fs::create_dir_all(path)?;
+        let lock = lock_file(path)?;
+        lock.lock()?;
+
+        // Keep the previous counts so that counting entities does not need to wait. Only a new
+        // store may be written without an index; an unreadable index may guard a write which
+        // needs to be rolled back.
+        let mut index = match Index::read(path) {
+            Ok(index) => index,
+            Err(VecStoreError::Io {
+                source,
+            }) if source.kind() == io::ErrorKind::NotFound => Index::new(Counts::default()),
+            Err(err) => return Err(err),
+        };
+        Self::discard_staged(path, index.writing)?;
+        index.generation += 1;
+        index.writing = true;
+        index.write(path)?;
+
         let counts = Counts {
-            deployments: Self::persist(path.join("deployments"), &store.deployments)?,
-            environments: Self::persist(path.join("environments"), &store.environments)?,
-            instances: Self::persist(path.join("instances"), &store.instances)?,
-            jobs: Self::persist(path.join("jobs"), &store.jobs)?,
-            job_artifacts: Self::persist(path.join("job_artifacts"), &store.job_artifacts)?,
-            merge_requests: Self::persist(path.join("merge_requests"), &store.merge_requests)?,
-            pipelines: Self::persist(path.join("pipelines"), &store.pipelines)?,
+            audit_entries: Self::persist(
+                path,
+                EntityType::AuditEntry,
+                store.audit_entries.force(store)?,
+            )?,
+            crawl_sessions: Self::persist(
+                path,
+                EntityType::CrawlSession,
+                store.crawl_sessions.force(store)?,
+            )?,
+            deployments: Self::persist(
+                path,
+                EntityType::Deployment,
+                store.deployments.force(store)?,
+            )?,
+            environments: Self::persist(
+                path,
+                EntityType::Environment,
+                store.environments.force(store)?,
+            )?,
+            instances: Self::persist(path, EntityType::Instance, store.instances.force(store)?)?,
+            jobs: Self::persist(path, EntityType::Job, store.jobs.force(store)?)?,
+            job_artifacts: Self::persist(
+                path,
+                EntityType::JobArtifact,
+                store.job_artifacts.force(store)?,
+            )?,
+            merge_requests: Self::persist(
+                path,
+                EntityType::MergeRequest,
+                store.merge_requests.force(store)?,
+            )?,
+            pipelines: Self::persist(path, EntityType::Pipeline, store.pipelines.force(store)?)?,
             pipeline_schedules: Self::persist(
-                path.join("pipeline_schedules"),
-                &store.pipeline_schedules,
+                path,
+                EntityType::PipelineSchedule,
+                store.pipeline_schedules.force(store)?,
+            )?,
+            projects: Self::persist(path, EntityType::Project, store.projects.force(store)?)?,
+            pushes: Self::persist(path, EntityType::Push, store.pushes.force(store)?)?,
+            releases: Self::persist(path, EntityType::Release, store.releases.force(store)?)?,
+            runners: Self::persist(path, EntityType::Runner, store.runners.force(store)?)?,
+            runner_hosts: Self::persist(
+                path,
+                EntityType::RunnerHost,
+                store.runner_hosts.force(store)?,
             )?,
-            projects: Self::persist(path.join("projects"), &store.projects)?,
-            runners: Self::persist(path.join("runners"), &store.runners)?,
-            runner_hosts: Self::persist(path.join("runner_hosts"), &store.runner_hosts)?,
-            users: Self::persist(path.join("users"), &store.users)?,
+            users: Self::persist(path, EntityType::User, store.users.force(store)?)?,
         };
+        Self::commit_staged(path)?;
 
-        // Finally, store the index file.
-        {
-            let inventory = Index {
-                version: LATEST_VERSION,
-                counts,
-            };
-
-            let index = File::create(path.join(INDEX_NAME))?;
-            serde_json::to_writer_pretty(index, &inventory)?;
-        }
+        // Finally, store the index file. This completes the write.
+        index.counts = counts;
+        index.writing = false;
+        index.write(path)?;
 
-        Ok(())
+        Self::discard_staged(path, false)
     }
 
-    fn restore<T>(path: PathBuf, count: usize) -> Result<Vec<T>, VecStoreError>
+    pub(super) fn restore<T>(path: PathBuf, count: usize) -> Result<Vec<T>, VecStoreError>
     where
         T: JsonStorable,
     {
@@ -156,56 +415,177 @@ impl VecStore {
 
         for (i, ()) in iter::repeat(()).enumerate().take(count) {
             let path = path.join(format!("{}.json", i));
-            let file = File::open(path)?;
-            let json = serde_json::from_reader(file)?;
+            // Reading the whole file up front is much faster than parsing from an unbuffered
+            // reader.
+            let data = fs::read(path)?;
 
-            vec.push(T::from_json(json)?);
+            vec.push(T::read_json(&data)?);
         }
 
         Ok(vec)
     }
 
-    #[allow(clippy::ptr_arg)] // Ensure we're dealing with the entire set of entities.
-    fn verify<T>(store: &VecLookup, objects: &Vec<T>) -> Result<(), VecStoreError>
+    /// Restore entities from a generation of a store.
+    ///
+    /// The store is locked while the entities are read so that a write cannot replace them
+    /// midway.
+    pub(super) fn restore_generation<T>(
+        root: &Path,
+        path: PathBuf,
+        count: usize,
+        generation: u64,
+    ) -> Result<Vec<T>, VecStoreError>
     where
         T: JsonStorable,
     {
-        for (i, o) in objects.iter().enumerate() {
+        // Stores which cannot be locked (e.g., read-only directories) are read as-is.
+        let lock = lock_file(root).ok();
+        if let Some(lock) = lock.as_ref() {
+            lock.lock_shared()?;
+        }
+
+        let check_generation = || {
+            let index = Index::read(root)?;
+            if index.writing || index.generation != generation {
+                return Err(VecStoreError::GenerationChanged {
+                    generation,
+                    current: index.generation,
+                });
+            }
+            Ok(())
+        };
+        check_generation()?;
+        let entities = Self::restore(path, count)?;
+        // Without a lock, a write may have started while reading.
+        if lock.is_none() {
+            check_generation()?;
+        }
+
+        Ok(entities)
+    }
+
+    fn restore_lazy<T>(
+        path: &Path,
+        index: &mut Index,
+        ty: EntityType,
+        types: &[EntityType],
+    ) -> Result<LazyVec<T>, VecStoreError>
+    where
+        T: JsonStorable,
+    {
+        let dir = path.join(ty.directory());
+        let count = *index.counts.get_mut(ty);
+        if types.contains(&ty) {
+            Ok(LazyVec::loaded(Self::restore(dir, count)?))
+        } else {
+            Ok(LazyVec::deferred(path.into(), dir, count, index.generation))
+        }
+    }
+
+    pub(super) fn verify<T>(store: &VecLookup, objects: &LazyVec<T>) -> Result<(), VecStoreError>
+    where
+        T: JsonStorable,
+    {
+        // Deferred entities are verified when they are loaded.
+        if !objects.is_loaded() {
+            return Ok(());
+        }
+
+        for (i, o) in objects.force(store)?.iter().enumerate() {
             o.validate_indices(VecIndex::new(i), store)?;
         }
 
         Ok(())
     }
 
+    /// Count the entities of each type in a store without loading them.
+    ///
+    /// Soft-deleted entities are included.
+    pub fn counts(path: &Path) -> Result<Vec<(EntityType, usize)>, VecStoreError> {
+        let mut counts = Index::read(path)?.counts;
+        Ok(EntityType::ALL
+            .iter()
+            .map(|&ty| (ty, *counts.get_mut(ty)))
+            .collect())
+    }
+
+    /// The generation of a store.
+    ///
+    /// The generation changes each time the store is written.
+    pub fn generation(path: &Path) -> Result<u64, VecStoreError> {
+        Ok(Index::read(path)?.generation)
+    }
+
     /// Load a `VecLookup` from a directory.
+    ///
+    /// The loaded entities are a consistent snapshot of the store: a write in progress is waited
+    /// for and a write which did not finish is rolled back.
     pub fn load(path: &Path) -> Result<VecLookup, VecStoreError> {
-        let index = File::open(path.join(INDEX_NAME))?;
-        let index: Index = serde_json::from_reader(index)?;
-        if index.version != LATEST_VERSION {
-            return Err(VecStoreError::UnsupportedVersion {
-                version: index.version,
-            });
+        Self::load_only(path, EntityType::ALL)
+    }
+
+    /// Load a `VecLookup` from a directory, only materializing some entity types.
+    ///
+    /// Other entity types are loaded and verified on first access. Deferred entities are part of
+    /// the snapshot: they are read from the same generation of the store and fail to load if the
+    /// store has been written since. Entities which fail to load appear empty; use
+    /// `VecLookup::load_deferred` to load them up front or to report such failures.
+    pub fn load_only(path: &Path, types: &[EntityType]) -> Result<VecLookup, VecStoreError> {
+        // Stores which cannot be locked (e.g., read-only directories) are loaded as-is.
+        let lock = lock_file(path).ok();
+        if let Some(lock) = lock.as_ref() {
+            lock.lock_shared()?;
         }
-        let counts = index.counts;
 
+        // The index may still be marked as being written if a writer did not finish. Writers hold
+        // the lock exclusively, so the writer has exited and its write is rolled back.
+        let mut index = Index::read(path)?;
+        if index.writing {
+            if let Some(lock) = lock.as_ref() {
+                lock.unlock()?;
+                lock.lock()?;
+                Self::roll_back(path)?;
+                lock.unlock()?;
+                lock.lock_shared()?;
+                index = Index::read(path)?;
+            } else if Self::is_interrupted(path) {
+                return Err(VecStoreError::Interrupted);
+            }
+        }
+        Self::load_index(path, index, types)
+    }
+
+    fn load_index(
+        path: &Path,
+        mut index: Index,
+        types: &[EntityType],
+    ) -> Result<VecLookup, VecStoreError> {
         let store = VecLookup {
-            deployments: Self::restore(path.join("deployments"), counts.deployments)?,
-            environments: Self::restore(path.join("environments"), counts.environments)?,
-            instances: Self::restore(path.join("instances"), counts.instances)?,
-            jobs: Self::restore(path.join("jobs"), counts.jobs)?,
-            job_artifacts: Self::restore(path.join("job_artifacts"), counts.job_artifacts)?,
-            merge_requests: Self::restore(path.join("merge_requests"), counts.merge_requests)?,
-            pipelines: Self::restore(path.join("pipelines"), counts.pipelines)?,
-            pipeline_schedules: Self::restore(
-                path.join("pipeline_schedules"),
-                counts.pipeline_schedules,
+            audit_entries: Self::restore_lazy(path, &mut index, EntityType::AuditEntry, types)?,
+            crawl_sessions: Self::restore_lazy(path, &mut index, EntityType::CrawlSession, types)?,
+            deployments: Self::restore_lazy(path, &mut index, EntityType::Deployment, types)?,
+            environments: Self::restore_lazy(path, &mut index, EntityType::Environment, types)?,
+            instances: Self::restore_lazy(path, &mut index, EntityType::Instance, types)?,
+            jobs: Self::restore_lazy(path, &mut index, EntityType::Job, types)?,
+            job_artifacts: Self::restore_lazy(path, &mut index, EntityType::JobArtifact, types)?,
+            merge_requests: Self::restore_lazy(path, &mut index, EntityType::MergeRequest, types)?,
+            pipelines: Self::restore_lazy(path, &mut index, EntityType::Pipeline, types)?,
+            pipeline_schedules: Self::restore_lazy(
+                path,
+                &mut index,
+                EntityType::PipelineSchedule,
+                types,
             )?,
-            projects: Self::restore(path.join("projects"), counts.projects)?,
-            runners: Self::restore(path.join("runners"), counts.runners)?,
-            runner_hosts: Self::restore(path.join("runner_hosts"), counts.runner_hosts)?,
-            users: Self::restore(path.join("users"), counts.users)?,
+            projects: Self::restore_lazy(path, &mut index, EntityType::Project, types)?,
+            pushes: Self::restore_lazy(path, &mut index, EntityType::Push, types)?,
+            releases: Self::restore_lazy(path, &mut index, EntityType::Release, types)?,
+            runners: Self::restore_lazy(path, &mut index, EntityType::Runner, types)?,
+            runner_hosts: Self::restore_lazy(path, &mut index, EntityType::RunnerHost, types)?,
+            users: Self::restore_lazy(path, &mut index, EntityType::User, types)?,
         };
 
+        Self::verify(&store, &store.audit_entries)?;
+        Self::verify(&store, &store.crawl_sessions)?;
         Self::verify(&store, &store.deployments)?;
         Self::verify(&store, &store.environments)?;
         Self::verify(&store, &store.instances)?;
@@ -215,6 +595,8 @@ impl VecStore {
         Self::verify(&store, &store.pipelines)?;
         Self::verify(&store, &store.pipeline_schedules)?;
         Self::verify(&store, &store.projects)?;
+        Self::verify(&store, &store.pushes)?;
+        Self::verify(&store, &store.releases)?;
         Self::verify(&store, &store.runners)?;
         Self::verify(&store, &store.runner_hosts)?;
         Self::verify(&store, &store.users)?;
@@ -222,3 +604,391 @@ impl VecStore {
         Ok(store)
     }
 }
+
+impl VecLookup {
+    /// Load and verify all entities deferred by `VecStore::load_only`.
+    ///
+    /// Also reports entities which failed to load when first accessed.
+    pub fn load_deferred(&self) -> Result<(), VecStoreError> {
+        self.audit_entries.force(self)?;
+        self.crawl_sessions.force(self)?;
+        self.deployments.force(self)?;
+        self.environments.force(self)?;
+        self.instances.force(self)?;
+        self.jobs.force(self)?;
+        self.job_artifacts.force(self)?;
+        self.merge_requests.force(self)?;
+        self.pipelines.force(self)?;
+        self.pipeline_schedules.force(self)?;
+        self.projects.force(self)?;
+        self.pushes.force(self)?;
+        self.releases.force(self)?;
+        self.runners.force(self)?;
+        self.runner_hosts.force(self)?;
+        self.users.force(self)?;
+
+        Ok(())
+    }
+
+    /// Report deferred entities which failed to load when first accessed.
+    ///
+    /// Entities which have not been accessed are not loaded.
+    pub fn check_deferred(&self) -> Result<(), VecStoreError> {
+        self.audit_entries.check()?;
+        self.crawl_sessions.check()?;
+        self.deployments.check()?;
+        self.environments.check()?;
+        self.instances.check()?;
+        self.jobs.check()?;
+        self.job_artifacts.check()?;
+        self.merge_requests.check()?;
+        self.pipelines.check()?;
+        self.pipeline_schedules.check()?;
+        self.projects.check()?;
+        self.pushes.check()?;
+        self.releases.check()?;
+        self.runners.check()?;
+        self.runner_hosts.check()?;
+        self.users.check()?;
+
+        Ok(())
+    }
+
+    /// Load deferred entities before modifying them.
+    ///
+    /// A failure to load is kept and reported when the store is written.
+    pub(super) fn load_for_update(&self) {
+        let _ = self.load_deferred();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::thread;
+    use std::time::Duration;
+
+    use chrono::Utc;
+    use ci_monitor_core::data::{
+        Elapsed, Instance, Job, JobState, Pipeline, PipelineSource, PipelineStatus, Project, User,
+    };
+    use ci_monitor_core::Lookup;
+
+    use super::Index;
+    use crate::test::{populated, tempdir};
+    use crate::{DiscoverableLookup, EntityType, VecLookup, VecStore, VecStoreError};
+
+    #[test]
+    fn counts() {
+        let workdir = tempdir();
+        VecStore::store(workdir.path(), &populated()).unwrap();
+
+        let counts = VecStore::counts(workdir.path()).unwrap();
+        assert_eq!(counts.len(), EntityType::ALL.len());
+        assert!(counts.contains(&(EntityType::Project, 1)));
+        assert!(counts.contains(&(EntityType::User, 1)));
+        assert!(counts.contains(&(EntityType::Job, 0)));
+    }
+
+    #[test]
+    fn load_only() {
+        let workdir = tempdir();
+        VecStore::store(workdir.path(), &populated()).unwrap();
+
+        let lookup = VecStore::load_only(workdir.path(), &[EntityType::Project]).unwrap();
+        assert!(lookup.projects.is_loaded());
+        assert!(!lookup.users.is_loaded());
+        assert!(!lookup.instances.is_loaded());
+
+        // Deferred entities are loaded on demand.
+        let user = <VecLookup as DiscoverableLookup<User<VecLookup>>>::find(&lookup, 2);
+        assert!(user.is_some());
+        assert!(lookup.users.is_loaded());
+        assert!(!lookup.instances.is_loaded());
+    }
+
+    #[test]
+    fn load_deferred() {
+        let workdir = tempdir();
+        VecStore::store(workdir.path(), &populated()).unwrap();
+
+        let lookup = VecStore::load_only(workdir.path(), &[]).unwrap();
+        // Remove deferred entities before they are loaded.
+        fs::remove_dir_all(workdir.path().join("users")).unwrap();
+
+        let err = lookup.load_deferred().unwrap_err();
+        assert!(err.to_string().starts_with("i/o error: "));
+        assert!(lookup.instances.is_loaded());
+        assert!(lookup.projects.is_loaded());
+
+        // Entities which failed to load appear empty and the failure is kept.
+        let user = <VecLookup as DiscoverableLookup<User<VecLookup>>>::find(&lookup, 2);
+        assert!(user.is_none());
+        let err = lookup.load_deferred().unwrap_err();
+        assert!(matches!(err, VecStoreError::Deferred { .. }));
+
+        // A store missing entities may not be written.
+        let target = tempdir();
+        let err = VecStore::store(target.path(), &lookup).unwrap_err();
+        assert!(matches!(err, VecStoreError::Deferred { .. }));
+    }
+
+    #[test]
+    fn load_deferred_after_write() {
+        let workdir = tempdir();
+        VecStore::store(workdir.path(), &populated()).unwrap();
+
+        let lookup = VecStore::load_only(workdir.path(), &[EntityType::Instance]).unwrap();
+        VecStore::store(workdir.path(), &VecLookup::default()).unwrap();
+
+        // Deferred entities come from the loaded generation or not at all.
+        assert!(lookup.check_deferred().is_ok());
+        let user = <VecLookup as DiscoverableLookup<User<VecLookup>>>::find(&lookup, 2);
+        assert!(user.is_none());
+        let err = lookup.check_deferred().unwrap_err();
+        assert!(err.to_string().contains("generation 1 is now 2"));
+        assert!(!lookup.projects.is_loaded());
+        let err = lookup.load_deferred().unwrap_err();
+        assert!(err.to_string().contains("generation 1 is now 2"));
+    }
+
+    #[test]
+    fn store_generation() {
+        let workdir = tempdir();
+        VecStore::store(workdir.path(), &populated()).unwrap();
+        assert_eq!(VecStore::generation(workdir.path()).unwrap(), 1);
+        VecStore::store(workdir.path(), &populated()).unwrap();
+        assert_eq!(VecStore::generation(workdir.path()).unwrap(), 2);
+        assert!(!workdir.path().join("vecindex.json.tmp").exists());
+    }
+
+    #[test]
+    fn load_after_crashed_writer() {
+        let workdir = tempdir();
+        VecStore::store(workdir.path(), &populated()).unwrap();
+
+        // Simulate a writer which exited partway through writing its entities.
+        let mut index = Index::read(workdir.path()).unwrap();
+        index.generation += 1;
+        index.writing = true;
+        index.write(workdir.path()).unwrap();
+        let staged = workdir.path().join("projects.new");
+        fs::create_dir_all(&staged).unwrap();
+        fs::write(staged.join("0.json"), "{").unwrap();
+
+        // The entities of the last complete write are loaded without waiting.
+        let lookup = VecStore::load(workdir.path()).unwrap();
+        let project = <VecLookup as DiscoverableLookup<Project<VecLookup>>>::find(&lookup, 1);
+        assert!(project.is_some());
+        let counts = VecStore::counts(workdir.path()).unwrap();
+        assert!(counts.contains(&(EntityType::Project, 1)));
+
+        // Repairing the store discards the staged entities.
+        VecStore::repair(workdir.path()).unwrap();
+        assert_eq!(VecStore::generation(workdir.path()).unwrap(), 3);
+        assert!(!staged.exists());
+        let lookup = VecStore::load(workdir.path()).unwrap();
+        let project = <VecLookup as DiscoverableLookup<Project<VecLookup>>>::find(&lookup, 1);
+        assert!(project.is_some());
+
+        // Later writes replace leftovers as well.
+        fs::create_dir_all(&staged).unwrap();
+        VecStore::store(workdir.path(), &populated()).unwrap();
+        assert!(!staged.exists());
+        VecStore::load(workdir.path()).unwrap();
+    }
+
+    #[test]
+    fn load_after_interrupted_commit() {
+        let workdir = tempdir();
+        VecStore::store(workdir.path(), &populated()).unwrap();
+
+        let mut lookup = populated();
+        let instance = Instance::builder()
+            .unique_id(0)
+            .forge("forge")
+            .url("url")
+            .build()
+            .unwrap();
+        let instance = lookup.store(instance);
+        let project = Project::builder()
+            .forge_id(3)
+            .instance(instance)
+            .url("other")
+            .build()
+            .unwrap();
+        lookup.store(project);
+
+        // Simulate a writer which exited after replacing only some of the entities.
+        let mut index = Index::read(workdir.path()).unwrap();
+        index.generation += 1;
+        index.writing = true;
+        index.write(workdir.path()).unwrap();
+        VecStore::persist(
+            workdir.path(),
+            EntityType::Instance,
+            lookup.instances.entities(&lookup),
+        )
+        .unwrap();
+        VecStore::persist(
+            workdir.path(),
+            EntityType::Project,
+            lookup.projects.entities(&lookup),
+        )
+        .unwrap();
+        VecStore::persist(
+            workdir.path(),
+            EntityType::User,
+            lookup.users.entities(&lookup),
+        )
+        .unwrap();
+        VecStore::commit_staged_type(workdir.path(), EntityType::Instance).unwrap();
+        VecStore::commit_staged_type(workdir.path(), EntityType::Project).unwrap();
+        assert!(workdir.path().join("projects.old").exists());
+
+        // The replaced entities are restored rather than mixed with the new ones.
+        let lookup = VecStore::load(workdir.path()).unwrap();
+        assert_eq!(lookup.projects.len(), 1);
+        let project = <VecLookup as DiscoverableLookup<Project<VecLookup>>>::find(&lookup, 3);
+        assert!(project.is_none());
+        assert!(!Index::read(workdir.path()).unwrap().writing);
+        for &ty in EntityType::ALL {
+            assert!(!super::suffixed(workdir.path(), ty, ".old").exists());
+            assert!(!super::suffixed(workdir.path(), ty, ".new").exists());
+        }
+    }
+
+    #[test]
+    fn store_after_interrupted_commit() {
+        let workdir = tempdir();
+        VecStore::store(workdir.path(), &populated()).unwrap();
+
+        // Simulate a writer which exited after replacing all of the entities, but before writing
+        // the index.
+        let mut index = Index::read(workdir.path()).unwrap();
+        index.writing = true;
+        index.write(workdir.path()).unwrap();
+        let empty = VecLookup::default();
+        VecStore::persist(
+            workdir.path(),
+            EntityType::Project,
+            empty.projects.entities(&empty),
+        )
+        .unwrap();
+        VecStore::commit_staged_type(workdir.path(), EntityType::Project).unwrap();
+
+        // The next write rolls back the interrupted one before replacing the entities.
+        VecStore::store(workdir.path(), &populated()).unwrap();
+        assert!(!workdir.path().join("projects.old").exists());
+        let lookup = VecStore::load(workdir.path()).unwrap();
+        let project = <VecLookup as DiscoverableLookup<Project<VecLookup>>>::find(&lookup, 1);
+        assert!(project.is_some());
+    }
+
+    #[test]
+    fn store_with_unreadable_index() {
+        let workdir = tempdir();
+        VecStore::store(workdir.path(), &populated()).unwrap();
+
+        // Simulate a writer which exited after replacing the projects and an index which was
+        // damaged afterwards.
+        let empty = VecLookup::default();
+        VecStore::persist(
+            workdir.path(),
+            EntityType::Project,
+            empty.projects.entities(&empty),
+        )
+        .unwrap();
+        VecStore::commit_staged_type(workdir.path(), EntityType::Project).unwrap();
+        fs::write(workdir.path().join(super::INDEX_NAME), "{").unwrap();
+
+        // The write is refused rather than discarding the only copy of the previous projects.
+        let err = VecStore::store(workdir.path(), &VecLookup::default()).unwrap_err();
+        assert!(matches!(err, VecStoreError::Json { .. }), "{:?}", err);
+        assert!(workdir.path().join("projects.old").exists());
+        assert_eq!(fs::read_to_string(workdir.path().join(super::INDEX_NAME)).unwrap(), "{");
+    }
+
+    #[test]
+    fn load_waits_for_writer() {
+        let workdir = tempdir();
+        VecStore::store(workdir.path(), &populated()).unwrap();
+
+        let lock = super::lock_file(workdir.path()).unwrap();
+        lock.lock().unwrap();
+        let path = workdir.path().to_path_buf();
+        let loader = thread::spawn(move || VecStore::load(&path).map(|_| ()));
+
+        thread::sleep(Duration::from_millis(50));
+        assert!(!loader.is_finished());
+        lock.unlock().unwrap();
+        loader.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn load_value_written() {
+        let workdir = tempdir();
+        VecStore::store(workdir.path(), &populated()).unwrap();
+
+        // Entities used to be written through `serde_json::Value` which sorts keys.
+        let path = workdir.path().join("projects").join("0.json");
+        let value: serde_json::Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        fs::write(&path, serde_json::to_vec_pretty(&value).unwrap()).unwrap();
+
+        let lookup = VecStore::load(workdir.path()).unwrap();
+        let project = <VecLookup as DiscoverableLookup<Project<VecLookup>>>::find(&lookup, 1);
+        assert!(project.is_some());
+    }
+
+    #[test]
+    fn load_invalid_duration() {
+        let mut lookup = populated();
+        let project = <VecLookup as DiscoverableLookup<Project<VecLookup>>>::find(&lookup, 1);
+        let user = <VecLookup as DiscoverableLookup<User<VecLookup>>>::find(&lookup, 2);
+        let pipeline = Pipeline::builder()
+            .project(project.unwrap())
+            .sha("0000000000000000000000000000000000000000")
+            .source(PipelineSource::Push)
+            .status(PipelineStatus::Success)
+            .forge_id(3)
+            .url("pipeline")
+            .created_at(Utc::now())
+            .updated_at(Utc::now())
+            .build()
+            .unwrap();
+        let pipeline = lookup.store(pipeline);
+        let job = Job::builder()
+            .user(user.unwrap())
+            .state(JobState::Success)
+            .created_at(Utc::now())
+            .queued_duration(Elapsed::from_seconds(1.5))
+            .forge_id(4)
+            .pipeline(pipeline)
+            .build()
+            .unwrap();
+        lookup.store(job);
+
+        let workdir = tempdir();
+        VecStore::store(workdir.path(), &lookup).unwrap();
+
+        // Durations are stored as seconds.
+        let path = workdir.path().join("jobs").join("0.json");
+        let mut value: serde_json::Value =
+            serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(value["queued_duration"], 1.5);
+
+        let lookup = VecStore::load(workdir.path()).unwrap();
+        let job = <VecLookup as DiscoverableLookup<Job<VecLookup>>>::find(&lookup, 4).unwrap();
+        let job = <VecLookup as Lookup<Job<VecLookup>>>::lookup(&lookup, &job).unwrap();
+        assert_eq!(job.queued_duration, Elapsed::from_seconds(1.5));
+
+        // Legacy stores may contain negative durations; these are dropped.
+        value["queued_duration"] = (-1.).into();
+        fs::write(&path, serde_json::to_vec(&value).unwrap()).unwrap();
+
+        let lookup = VecStore::load(workdir.path()).unwrap();
+        let job = <VecLookup as DiscoverableLookup<Job<VecLookup>>>::find(&lookup, 4).unwrap();
+        let job = <VecLookup as Lookup<Job<VecLookup>>>::lookup(&lookup, &job).unwrap();
+        assert_eq!(job.queued_duration, None);
+    }
+}
@@ -0,0 +1,187 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::{HashMap, HashSet};
+
+use ci_monitor_core::data::{BlobReference, ContentHash};
+
+use crate::blob::BATCH_SIZE;
+use crate::{BlobPersistence, BlobPersistenceVerifyError, VecLookup};
+
+impl VecLookup {
+    /// Rehash the blobs referenced by the store with another algorithm.
+    ///
+    /// Blobs referenced by job artifacts, pipeline CI configurations, and user avatars which use
+    /// a different algorithm are verified, stored again into `blobs`, and their references are
+    /// updated. `blobs` must hash new blobs using `algo`. The original blobs are left in place.
+    ///
+    /// Blobs are fetched and stored in batches.
+    ///
+    /// Returns the number of blobs which were rehashed.
+    pub fn rehash_blobs<B>(
+        &mut self,
+        blobs: &B,
+        algo: ContentHash,
+    ) -> Result<usize, BlobPersistenceVerifyError>
+    where
+        B: BlobPersistence,
+    {
+        let mut seen = HashSet::new();
+        let mut pending = Vec::new();
+        self.for_each_blob_ref(|blob_ref| {
+            if blob_ref.algo() != algo && seen.insert(blob_ref.clone()) {
+                pending.push(blob_ref.clone());
+            }
+        });
+
+        let mut rehashed: HashMap<BlobReference, BlobReference> = HashMap::new();
+        for batch in pending.chunks(BATCH_SIZE) {
+            let data = batch
+                .iter()
+                .zip(blobs.fetch_many(batch))
+                .map(|(blob_ref, blob)| {
+                    let blob = blob?;
+                    let actual = BlobReference::for_blob(&blob, blob_ref.algo());
+                    if actual != *blob_ref {
+                        return Err(BlobPersistenceVerifyError::invalid(actual));
+                    }
+                    Ok(blob)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let stored = blobs.store_many(&data);
+            for ((blob_ref, blob), new_ref) in batch.iter().zip(&data).zip(stored) {
+                let new_ref = new_ref?;
+                if new_ref != BlobReference::for_blob(blob, algo) {
+                    return Err(BlobPersistenceVerifyError::invalid(new_ref));
+                }
+                rehashed.insert(blob_ref.clone(), new_ref);
+            }
+        }
+
+        self.for_each_blob_ref(|blob_ref| {
+            if let Some(new_ref) = rehashed.get(blob_ref) {
+                *blob_ref = new_ref.clone();
+            }
+        });
+
+        Ok(rehashed.len())
+    }
+
+    fn for_each_blob_ref<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut BlobReference),
+    {
+        self.load_for_update();
+        for artifact in self.job_artifacts.loaded_mut() {
+            if let Some(blob_ref) = artifact.blob.as_mut() {
+                f(blob_ref);
+            }
+        }
+        for pipeline in self.pipelines.loaded_mut() {
+            if let Some(blob_ref) = pipeline.ci_config.as_mut() {
+                f(blob_ref);
+            }
+        }
+        for user in self.users.loaded_mut() {
+            if let Some(blob_ref) = user.avatar.as_mut() {
+                f(blob_ref);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "filesystem"))]
+mod tests {
+    use ci_monitor_core::data::{
+        ArtifactKind, Blob, ContentHash, Instance, Job, JobArtifact, JobState, Pipeline,
+        PipelineSource, PipelineStatus, Project, User,
+    };
+    use ci_monitor_core::Lookup;
+    use tempfile::TempDir;
+
+    use crate::{BlobPersistence, Filesystem, Sharding, VecLookup};
+
+    #[test]
+    fn rehash_blobs() {
+        let workdir = TempDir::new().unwrap();
+        let mut blobs =
+            Filesystem::create(workdir.path(), ContentHash::Sha256, Sharding::default()).unwrap();
+        let log = Blob::new(b"job log".to_vec());
+        let old_ref = blobs.store(&log).unwrap();
+
+        let mut lookup = VecLookup::default();
+        let instance = Instance::builder()
+            .unique_id(0)
+            .forge("forge")
+            .url("url")
+            .build()
+            .unwrap();
+        let instance = lookup.store(instance);
+        let user = User::builder()
+            .forge_id(0)
+            .instance(instance)
+            .build()
+            .unwrap();
+        let user = lookup.store(user);
+        let project = Project::builder()
+            .forge_id(0)
+            .instance(instance)
+            .url("project")
+            .build()
+            .unwrap();
+        let project = lookup.store(project);
+        let now = chrono::Utc::now();
+        let pipeline = Pipeline::builder()
+            .project(project)
+            .sha("0000000000000000000000000000000000000000")
+            .source(PipelineSource::Push)
+            .status(PipelineStatus::Success)
+            .forge_id(0)
+            .url("url")
+            .created_at(now)
+            .updated_at(now)
+            .build()
+            .unwrap();
+        let pipeline = lookup.store(pipeline);
+        let job = Job::builder()
+            .user(user)
+            .state(JobState::Success)
+            .created_at(now)
+            .forge_id(0)
+            .pipeline(pipeline)
+            .build()
+            .unwrap();
+        let job = lookup.store(job);
+        // Two artifacts sharing a blob.
+        let artifacts = [0, 1].map(|id| {
+            let mut artifact = JobArtifact::builder()
+                .kind(ArtifactKind::JobLog)
+                .name("job.log")
+                .size(7)
+                .unique_id(id)
+                .job(job)
+                .build()
+                .unwrap();
+            artifact.blob = Some(old_ref.clone());
+            lookup.store(artifact)
+        });
+
+        blobs.set_algorithm(ContentHash::Blake3).unwrap();
+        assert_eq!(lookup.rehash_blobs(&blobs, ContentHash::Blake3).unwrap(), 1);
+        // Already rehashed blobs are skipped.
+        assert_eq!(lookup.rehash_blobs(&blobs, ContentHash::Blake3).unwrap(), 0);
+
+        for artifact in &artifacts {
+            let artifact =
+                <VecLookup as Lookup<JobArtifact<VecLookup>>>::lookup(&lookup, artifact).unwrap();
+            let new_ref = artifact.blob.as_ref().unwrap();
+            assert_eq!(new_ref.algo(), ContentHash::Blake3);
+            assert_eq!(*blobs.fetch(new_ref).unwrap(), *log);
+        }
+        assert!(blobs.contains(&old_ref).unwrap());
+    }
+}
@@ -0,0 +1,578 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use ci_monitor_core::data::{Blob, JobState};
+use tantivy::collector::TopDocs;
+use tantivy::directory::error::OpenDirectoryError;
+use tantivy::directory::MmapDirectory;
+use tantivy::query::{BooleanQuery, Occur, Query, QueryParser, QueryParserError, TermQuery};
+use tantivy::schema::{Field, IndexRecordOption, Schema, Value, STORED, STRING, TEXT};
+use tantivy::{Index, IndexWriter, TantivyDocument, TantivyError, Term};
+use thiserror::Error;
+
+use super::manifest;
+use super::{VecIndex, VecLookup, VecStoreError};
+
+/// The memory used by the index writer.
+const WRITER_MEMORY: usize = 50_000_000;
+
+/// Errors which can occur when using a search index.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum SearchIndexError {
+    /// The index directory could not be created.
+    #[error("failed to create the search index directory: {}", source)]
+    Create {
+        /// The error.
+        #[source]
+        source: io::Error,
+    },
+    /// The index directory could not be opened.
+    #[error("failed to open the search index directory: {}", source)]
+    Open {
+        /// The error.
+        #[from]
+        source: OpenDirectoryError,
+    },
+    /// A search query could not be parsed.
+    #[error("invalid search query: {}", source)]
+    Query {
+        /// The error.
+        #[from]
+        source: QueryParserError,
+    },
+    /// Index error.
+    #[error("search index error: {}", source)]
+    Index {
+        /// The error.
+        #[from]
+        source: TantivyError,
+    },
+    /// The store could not be read.
+    #[error("failed to read the store: {}", source)]
+    Store {
+        /// The error.
+        #[from]
+        source: VecStoreError,
+    },
+}
+
+/// The kinds of entities in a search index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SearchKind {
+    /// Merge requests by title and description.
+    MergeRequest,
+    /// Pipelines by name and ref.
+    Pipeline,
+    /// Failed jobs by name and stage.
+    ///
+    /// Jobs which are allowed to fail are not indexed.
+    JobFailure,
+}
+
+impl SearchKind {
+    /// All kinds.
+    pub const ALL: &'static [Self] = &[Self::MergeRequest, Self::Pipeline, Self::JobFailure];
+
+    /// The name of the kind.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::MergeRequest => "merge_request",
+            Self::Pipeline => "pipeline",
+            Self::JobFailure => "job_failure",
+        }
+    }
+
+    /// Look up a kind by its name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|kind| kind.name() == name)
+    }
+}
+
+/// An entity found by a search.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct SearchHit {
+    /// The kind of entity.
+    pub kind: SearchKind,
+    /// The forge ID of the entity.
+    pub id: u64,
+    /// The path of the project of the entity.
+    pub project: String,
+    /// The title of the entity.
+    pub title: String,
+    /// The URL of the entity.
+    pub url: String,
+    /// How well the entity matches the query.
+    pub score: f32,
+}
+
+/// The changes made by updating a search index.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SearchIndexUpdate {
+    /// The number of entities added to the index.
+    pub added: usize,
+    /// The number of entities which were reindexed because they changed.
+    pub updated: usize,
+    /// The number of entities removed from the index.
+    pub removed: usize,
+    /// The number of entities which did not change.
+    pub unchanged: usize,
+}
+
+/// A document to index.
+struct SearchDocument {
+    kind: SearchKind,
+    id: u64,
+    project: String,
+    title: String,
+    body: String,
+    url: String,
+}
+
+impl SearchDocument {
+    /// The key of the document in the index.
+    fn key(&self) -> String {
+        format!("{}:{}", self.kind.name(), self.id)
+    }
+
+    /// A checksum of the indexed contents used to detect changes.
+    fn stamp(&self) -> String {
+        let contents = [&self.project, &self.title, &self.body, &self.url]
+            .iter()
+            .flat_map(|field| field.bytes().chain(Some(0)))
+            .collect::<Vec<_>>();
+        manifest::checksum(&Blob::new(contents))
+    }
+}
+
+/// The documents for the entities of a store.
+///
+/// Soft-deleted entities are not indexed.
+fn documents(store: &VecLookup) -> Vec<SearchDocument> {
+    let project_path = |idx: &VecIndex<_>| {
+        store
+            .projects
+            .entities(store)
+            .get(idx.idx)
+            .map(|project| project.instance_path.clone())
+            .unwrap_or_default()
+    };
+
+    let merge_requests = store
+        .merge_requests
+        .entities(store)
+        .iter()
+        .filter(|mr| mr.cim_deleted_at.is_none())
+        .map(|mr| {
+            SearchDocument {
+                kind: SearchKind::MergeRequest,
+                id: mr.forge_id,
+                project: project_path(&mr.target_project),
+                title: mr.title.clone(),
+                body: format!("{}\n{}", mr.source_branch, mr.description),
+                url: mr.url.clone(),
+            }
+        });
+    let pipelines = store
+        .pipelines
+        .entities(store)
+        .iter()
+        .filter(|pipeline| pipeline.cim_deleted_at.is_none())
+        .map(|pipeline| {
+            let refname = pipeline.refname.clone().unwrap_or_default();
+            SearchDocument {
+                kind: SearchKind::Pipeline,
+                id: pipeline.forge_id,
+                project: project_path(&pipeline.project),
+                title: pipeline.name.clone().unwrap_or_else(|| refname.clone()),
+                body: format!("{}\n{}", refname, pipeline.sha),
+                url: pipeline.url.clone(),
+            }
+        });
+    let job_failures = store
+        .jobs
+        .entities(store)
+        .iter()
+        .filter(|job| {
+            job.cim_deleted_at.is_none() && job.state == JobState::Failed && !job.allow_failure
+        })
+        .map(|job| {
+            let pipeline = store.pipelines.entities(store).get(job.pipeline.idx);
+            SearchDocument {
+                kind: SearchKind::JobFailure,
+                id: job.forge_id,
+                project: pipeline
+                    .map(|pipeline| project_path(&pipeline.project))
+                    .unwrap_or_default(),
+                title: job.name.clone(),
+                body: format!(
+                    "{}\n{}",
+                    job.stage,
+                    pipeline
+                        .and_then(|pipeline| pipeline.refname.as_deref())
+                        .unwrap_or_default(),
+                ),
+                url: job.url.clone(),
+            }
+        });
+
+    merge_requests
+        .chain(pipelines)
+        .chain(job_failures)
+        .collect()
+}
+
+#[derive(Clone, Copy)]
+struct Fields {
+    key: Field,
+    kind: Field,
+    id: Field,
+    project: Field,
+    title: Field,
+    body: Field,
+    url: Field,
+    stamp: Field,
+}
+
+impl Fields {
+    fn schema() -> (Schema, Self) {
+        let mut builder = Schema::builder();
+        let fields = Self {
+            key: builder.add_text_field("key", STRING | STORED),
+            kind: builder.add_text_field("kind", STRING | STORED),
+            id: builder.add_u64_field("id", STORED),
+            project: builder.add_text_field("project", STRING | STORED),
+            title: builder.add_text_field("title", TEXT | STORED),
+            body: builder.add_text_field("body", TEXT),
+            url: builder.add_text_field("url", STORED),
+            stamp: builder.add_text_field("stamp", STORED),
+        };
+
+        (builder.build(), fields)
+    }
+
+    fn document(&self, document: &SearchDocument, key: &str, stamp: &str) -> TantivyDocument {
+        let mut doc = TantivyDocument::default();
+        doc.add_text(self.key, key);
+        doc.add_text(self.kind, document.kind.name());
+        doc.add_u64(self.id, document.id);
+        doc.add_text(self.project, &document.project);
+        doc.add_text(self.title, &document.title);
+        doc.add_text(self.body, &document.body);
+        doc.add_text(self.url, &document.url);
+        doc.add_text(self.stamp, stamp);
+        doc
+    }
+
+    fn text(doc: &TantivyDocument, field: Field) -> String {
+        doc.get_first(field)
+            .and_then(|value| value.as_str())
+            .unwrap_or_default()
+            .into()
+    }
+}
+
+/// A full-text search index over the entities of a store.
+///
+/// Merge requests (by title, description, and source branch), pipelines (by name and ref), and
+/// failed jobs (by name and stage) are indexed. The index is kept in its own directory and is
+/// updated incrementally: only entities which changed since the last update are reindexed.
+pub struct SearchIndex {
+    index: Index,
+    fields: Fields,
+}
+
+impl SearchIndex {
+    /// Open a search index, creating it if it does not exist.
+    pub fn open(path: &Path) -> Result<Self, SearchIndexError> {
+        fs::create_dir_all(path).map_err(|err| {
+            SearchIndexError::Create {
+                source: err,
+            }
+        })?;
+        let (schema, fields) = Fields::schema();
+        let index = Index::open_or_create(MmapDirectory::open(path)?, schema)?;
+
+        Ok(Self {
+            index,
+            fields,
+        })
+    }
+
+    /// The stamps of the indexed documents by key.
+    fn stamps(&self) -> Result<BTreeMap<String, String>, SearchIndexError> {
+        let searcher = self.index.reader()?.searcher();
+        let mut stamps = BTreeMap::new();
+        for segment in searcher.segment_readers() {
+            let docs = segment.get_store_reader(1).map_err(TantivyError::from)?;
+            for doc_id in segment.doc_ids_alive() {
+                let doc: TantivyDocument = docs.get(doc_id)?;
+                stamps.insert(
+                    Fields::text(&doc, self.fields.key),
+                    Fields::text(&doc, self.fields.stamp),
+                );
+            }
+        }
+
+        Ok(stamps)
+    }
+
+    /// Update the index to match a store.
+    ///
+    /// Entities which are new or have changed are (re)indexed and entities which are no longer in
+    /// the store (or have been soft-deleted) are removed.
+    pub fn update(&self, store: &VecLookup) -> Result<SearchIndexUpdate, SearchIndexError> {
+        let mut stale = self.stamps()?;
+        let mut writer: IndexWriter = self.index.writer(WRITER_MEMORY)?;
+        let mut update = SearchIndexUpdate::default();
+
+        for document in documents(store) {
+            let key = document.key();
+            let stamp = document.stamp();
+            match stale.remove(&key) {
+                Some(indexed) if indexed == stamp => {
+                    update.unchanged += 1;
+                    continue;
+                },
+                Some(_) => {
+                    writer.delete_term(Term::from_field_text(self.fields.key, &key));
+                    update.updated += 1;
+                },
+                None => update.added += 1,
+            }
+            writer.add_document(self.fields.document(&document, &key, &stamp))?;
+        }
+        // Entities which failed to load must not be removed from the index.
+        store.check_deferred()?;
+
+        for key in stale.keys() {
+            writer.delete_term(Term::from_field_text(self.fields.key, key));
+        }
+        update.removed = stale.len();
+        writer.commit()?;
+
+        Ok(update)
+    }
+
+    /// Search the index.
+    ///
+    /// The query uses the query language of `tantivy` (e.g., `title:flaky AND timeout`) and
+    /// matches titles and bodies by default. Results are sorted by relevance (best first).
+    pub fn search(
+        &self,
+        query: &str,
+        kind: Option<SearchKind>,
+        limit: usize,
+    ) -> Result<Vec<SearchHit>, SearchIndexError> {
+        let parser = QueryParser::for_index(&self.index, vec![self.fields.title, self.fields.body]);
+        let mut query = parser.parse_query(query)?;
+        if let Some(kind) = kind {
+            let kind_query: Box<dyn Query> = Box::new(TermQuery::new(
+                Term::from_field_text(self.fields.kind, kind.name()),
+                IndexRecordOption::Basic,
+            ));
+            query = Box::new(BooleanQuery::new(vec![
+                (Occur::Must, query),
+                (Occur::Must, kind_query),
+            ]));
+        }
+
+        let searcher = self.index.reader()?.searcher();
+        let top_docs = searcher.search(&query, &TopDocs::with_limit(limit))?;
+        top_docs
+            .into_iter()
+            .filter_map(|(score, address)| {
+                let doc: TantivyDocument = match searcher.doc(address) {
+                    Ok(doc) => doc,
+                    Err(err) => return Some(Err(err.into())),
+                };
+                let kind = SearchKind::from_name(&Fields::text(&doc, self.fields.kind))?;
+                let id = doc
+                    .get_first(self.fields.id)
+                    .and_then(|value| value.as_u64())?;
+
+                Some(Ok(SearchHit {
+                    kind,
+                    id,
+                    project: Fields::text(&doc, self.fields.project),
+                    title: Fields::text(&doc, self.fields.title),
+                    url: Fields::text(&doc, self.fields.url),
+                    score,
+                }))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use ci_monitor_core::data::{
+        Job, JobState, MergeRequest, MergeRequestStatus, Pipeline, PipelineSource, PipelineStatus,
+        Project, User,
+    };
+    use ci_monitor_core::Lookup;
+
+    use crate::test::{self, tempdir};
+    use crate::{DiscoverableLookup, SearchIndex, SearchIndexUpdate, SearchKind, VecLookup};
+
+    #[test]
+    fn kind_names() {
+        for kind in SearchKind::ALL {
+            assert_eq!(SearchKind::from_name(kind.name()), Some(*kind));
+        }
+        assert_eq!(SearchKind::from_name("unknown"), None);
+    }
+
+    fn merge_request(lookup: &VecLookup, id: u64, title: &str) -> MergeRequest<VecLookup> {
+        let project = <VecLookup as DiscoverableLookup<Project<VecLookup>>>::find(lookup, 1);
+        let user = <VecLookup as DiscoverableLookup<User<VecLookup>>>::find(lookup, 2);
+        MergeRequest::builder()
+            .id(id)
+            .source_project(project.unwrap())
+            .source_branch("topic")
+            .sha("0000000000000000000000000000000000000000")
+            .target_project(project.unwrap())
+            .target_branch("main")
+            .forge_id(id)
+            .title(title)
+            .description("Fixes the cache eviction.")
+            .state(MergeRequestStatus::Open)
+            .author(user.unwrap())
+            .url(format!("mr/{}", id))
+            .build()
+            .unwrap()
+    }
+
+    fn populated() -> VecLookup {
+        let mut lookup = test::populated();
+        let project = <VecLookup as DiscoverableLookup<Project<VecLookup>>>::find(&lookup, 1);
+        let project = project.unwrap();
+        let user = <VecLookup as DiscoverableLookup<User<VecLookup>>>::find(&lookup, 2);
+        let user = user.unwrap();
+
+        lookup.store(merge_request(&lookup, 10, "Speed up the test suite"));
+        lookup.store(merge_request(&lookup, 11, "Update documentation"));
+        let pipeline = Pipeline::builder()
+            .project(project)
+            .name(Some("Nightly coverage".into()))
+            .refname(Some("main".into()))
+            .sha("0000000000000000000000000000000000000000")
+            .source(PipelineSource::Schedule)
+            .status(PipelineStatus::Failed)
+            .forge_id(20)
+            .url("pipeline")
+            .created_at(Utc::now())
+            .updated_at(Utc::now())
+            .build()
+            .unwrap();
+        let pipeline = lookup.store(pipeline);
+        let job = |id, name: &str, state, allow_failure| {
+            Job::builder()
+                .user(user)
+                .name(name)
+                .stage("test")
+                .state(state)
+                .allow_failure(allow_failure)
+                .created_at(Utc::now())
+                .forge_id(id)
+                .url(format!("job/{}", id))
+                .pipeline(pipeline)
+                .build()
+                .unwrap()
+        };
+        lookup.store(job(30, "coverage", JobState::Failed, false));
+        lookup.store(job(31, "lint", JobState::Failed, true));
+        lookup.store(job(32, "build", JobState::Success, false));
+
+        lookup
+    }
+
+    #[test]
+    fn search() {
+        let workdir = tempdir();
+        let index = SearchIndex::open(workdir.path()).unwrap();
+        let update = index.update(&populated()).unwrap();
+        assert_eq!(update.added, 4);
+
+        let hits = index.search("eviction", None, 10).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert!(hits.iter().all(|hit| hit.kind == SearchKind::MergeRequest));
+        let hits = index.search("suite", None, 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].id, 10);
+        assert_eq!(hits[0].project, "group/project");
+        assert_eq!(hits[0].title, "Speed up the test suite");
+        assert_eq!(hits[0].url, "mr/10");
+
+        // Pipelines and failed jobs are found by name.
+        let hits = index.search("coverage", None, 10).unwrap();
+        assert_eq!(hits.len(), 2);
+        let hits = index
+            .search("coverage", Some(SearchKind::JobFailure), 10)
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].id, 30);
+        // Jobs which may fail are not indexed.
+        assert!(index.search("lint", None, 10).unwrap().is_empty());
+
+        assert!(index.search("title:(", None, 10).is_err());
+    }
+
+    #[test]
+    fn incremental_update() {
+        let workdir = tempdir();
+        let mut lookup = populated();
+        SearchIndex::open(workdir.path())
+            .unwrap()
+            .update(&lookup)
+            .unwrap();
+
+        // Reopening keeps the index.
+        let index = SearchIndex::open(workdir.path()).unwrap();
+        let update = index.update(&lookup).unwrap();
+        assert_eq!(
+            update,
+            SearchIndexUpdate {
+                unchanged: 4,
+                ..Default::default()
+            },
+        );
+
+        // Changed entities are reindexed.
+        lookup.store(merge_request(&lookup, 11, "Rewrite the scheduler"));
+        let mr = <VecLookup as DiscoverableLookup<MergeRequest<VecLookup>>>::find(&lookup, 10);
+        let mut mr = <VecLookup as Lookup<MergeRequest<VecLookup>>>::lookup(&lookup, &mr.unwrap())
+            .unwrap()
+            .clone();
+        mr.cim_deleted_at = Some(Utc::now());
+        lookup.store(mr);
+
+        let update = index.update(&lookup).unwrap();
+        assert_eq!(
+            update,
+            SearchIndexUpdate {
+                updated: 1,
+                removed: 1,
+                unchanged: 2,
+                ..Default::default()
+            },
+        );
+        assert!(index.search("documentation", None, 10).unwrap().is_empty());
+        assert!(index.search("suite", None, 10).unwrap().is_empty());
+        let hits = index.search("scheduler", None, 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].id, 11);
+    }
+}
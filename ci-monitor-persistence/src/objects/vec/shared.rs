@@ -0,0 +1,70 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::sync::{Arc, RwLock};
+
+use super::VecLookup;
+
+/// A `VecLookup` shared between readers which may be replaced as a whole.
+///
+/// Readers take a snapshot which is not affected by later replacements, so each reader sees a
+/// consistent view of the store while a newer version is loaded in the background.
+#[derive(Default)]
+pub struct SharedVecLookup {
+    current: RwLock<Arc<VecLookup>>,
+}
+
+impl SharedVecLookup {
+    /// Share a `VecLookup`.
+    pub fn new(storage: VecLookup) -> Self {
+        Self {
+            current: RwLock::new(Arc::new(storage)),
+        }
+    }
+
+    /// The current contents.
+    pub fn snapshot(&self) -> Arc<VecLookup> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Replace the contents.
+    ///
+    /// Existing snapshots keep the previous contents.
+    pub fn replace(&self, storage: VecLookup) {
+        *self.current.write().unwrap() = Arc::new(storage);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ci_monitor_core::data::Instance;
+    use ci_monitor_core::Lookup;
+
+    use crate::{DiscoverableLookup, SharedVecLookup, VecLookup};
+
+    fn instances(lookup: &VecLookup) -> usize {
+        <VecLookup as DiscoverableLookup<Instance>>::all_indices(lookup).len()
+    }
+
+    #[test]
+    fn snapshot_survives_replace() {
+        let shared = SharedVecLookup::default();
+        let before = shared.snapshot();
+
+        let mut lookup = VecLookup::default();
+        let instance = Instance::builder()
+            .unique_id(0)
+            .forge("forge")
+            .url("url")
+            .build()
+            .unwrap();
+        lookup.store(instance);
+        shared.replace(lookup);
+
+        assert_eq!(instances(&before), 0);
+        assert_eq!(instances(&shared.snapshot()), 1);
+    }
+}
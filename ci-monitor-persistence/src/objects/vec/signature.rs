@@ -0,0 +1,348 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::manifest::MANIFEST_NAME;
+use super::persist::INDEX_NAME;
+use super::{VecStore, VecStoreError};
+use crate::EntityType;
+
+const SIGNATURES_NAME: &str = "signatures.json";
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex<const N: usize>(hex: &str) -> Option<[u8; N]> {
+    if hex.len() != 2 * N || !hex.is_ascii() {
+        return None;
+    }
+
+    let mut bytes = [0; N];
+    for (byte, chunk) in bytes.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        let chunk = std::str::from_utf8(chunk).ok()?;
+        *byte = u8::from_str_radix(chunk, 16).ok()?;
+    }
+
+    Some(bytes)
+}
+
+/// Errors which can occur when parsing a store signing key.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum StoreKeyError {
+    /// The key is not a hex-encoded 32-byte value.
+    #[error("expected 64 hexadecimal digits")]
+    InvalidHex {},
+    /// The key is not a valid ed25519 public key.
+    #[error("invalid ed25519 public key")]
+    InvalidKey {},
+}
+
+/// An ed25519 key used to sign the index and manifests of a store.
+#[derive(Clone)]
+pub struct StoreSigningKey {
+    key: SigningKey,
+}
+
+impl StoreSigningKey {
+    /// Parse a key from its hex-encoded 32-byte secret.
+    pub fn from_hex(hex: &str) -> Result<Self, StoreKeyError> {
+        let secret = from_hex(hex.trim()).ok_or(StoreKeyError::InvalidHex {})?;
+        Ok(Self {
+            key: SigningKey::from_bytes(&secret),
+        })
+    }
+
+    /// The key which verifies signatures made by this key.
+    pub fn verifying_key(&self) -> StoreVerifyingKey {
+        StoreVerifyingKey {
+            key: self.key.verifying_key(),
+        }
+    }
+}
+
+impl fmt::Debug for StoreSigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StoreSigningKey")
+            .field("verifying_key", &self.verifying_key())
+            .finish_non_exhaustive()
+    }
+}
+
+/// An ed25519 key used to verify the signatures of a store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StoreVerifyingKey {
+    key: VerifyingKey,
+}
+
+impl StoreVerifyingKey {
+    /// Parse a key from its hex encoding.
+    pub fn from_hex(hex: &str) -> Result<Self, StoreKeyError> {
+        let public = from_hex(hex.trim()).ok_or(StoreKeyError::InvalidHex {})?;
+        let key = VerifyingKey::from_bytes(&public).map_err(|_| StoreKeyError::InvalidKey {})?;
+        Ok(Self {
+            key,
+        })
+    }
+
+    /// The hex encoding of the key.
+    pub fn to_hex(&self) -> String {
+        to_hex(self.key.as_bytes())
+    }
+}
+
+/// Signatures over the index and manifests of a store.
+///
+/// Written after the files it covers. Since manifests hold the checksum of every entity file, the
+/// signatures cover all stored entities (including their blob references).
+#[derive(Deserialize, Serialize)]
+struct Signatures {
+    public_key: String,
+    files: BTreeMap<String, String>,
+}
+
+impl Signatures {
+    fn read(path: &Path) -> Result<Option<Self>, VecStoreError> {
+        match fs::read(path.join(SIGNATURES_NAME)) {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+/// The message signed for a file.
+///
+/// The name is included so that signed files cannot be swapped with each other.
+fn message(name: &str, contents: &[u8]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(name.len() + 1 + contents.len());
+    message.extend_from_slice(name.as_bytes());
+    message.push(0);
+    message.extend_from_slice(contents);
+    message
+}
+
+/// The signed files of a store which exist along with their contents.
+fn signed_files(path: &Path) -> Result<Vec<(String, Vec<u8>)>, VecStoreError> {
+    let names = Some(INDEX_NAME.to_string()).into_iter().chain(
+        EntityType::ALL
+            .iter()
+            .map(|ty| format!("{}/{}", ty.directory(), MANIFEST_NAME)),
+    );
+
+    let mut files = Vec::new();
+    for name in names {
+        match fs::read(path.join(&name)) {
+            Ok(data) => files.push((name, data)),
+            // Missing files are reported by `VecStore::check`.
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    Ok(files)
+}
+
+/// A problem with the signatures of a store.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SignatureProblem {
+    /// The store has not been signed.
+    Unsigned,
+    /// The store was signed by a different key.
+    KeyMismatch {
+        /// The hex encoding of the key which signed the store.
+        public_key: String,
+    },
+    /// A file has no signature.
+    MissingSignature {
+        /// The path of the file within the store.
+        file: String,
+    },
+    /// A file does not match its signature.
+    InvalidSignature {
+        /// The path of the file within the store.
+        file: String,
+    },
+}
+
+impl fmt::Display for SignatureProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Unsigned => write!(f, "the store is not signed"),
+            Self::KeyMismatch {
+                public_key,
+            } => write!(f, "the store is signed by another key ({})", public_key),
+            Self::MissingSignature {
+                file,
+            } => write!(f, "{}: no signature", file),
+            Self::InvalidSignature {
+                file,
+            } => write!(f, "{}: invalid signature", file),
+        }
+    }
+}
+
+impl VecStore {
+    /// Sign the index and manifests of a store.
+    ///
+    /// Any previous signatures are replaced.
+    pub fn sign(path: &Path, key: &StoreSigningKey) -> Result<(), VecStoreError> {
+        let files = signed_files(path)?
+            .into_iter()
+            .map(|(name, data)| {
+                let signature = key.key.sign(&message(&name, &data));
+                (name, to_hex(&signature.to_bytes()))
+            })
+            .collect();
+        let signatures = Signatures {
+            public_key: key.verifying_key().to_hex(),
+            files,
+        };
+
+        fs::write(
+            path.join(SIGNATURES_NAME),
+            serde_json::to_vec_pretty(&signatures)?,
+        )?;
+
+        Ok(())
+    }
+
+    /// Verify the signatures of a store's index and manifests.
+    ///
+    /// Use `VecStore::check` to verify entity files against the signed manifests.
+    pub fn verify_signatures(
+        path: &Path,
+        key: &StoreVerifyingKey,
+    ) -> Result<Vec<SignatureProblem>, VecStoreError> {
+        let signatures = if let Some(signatures) = Signatures::read(path)? {
+            signatures
+        } else {
+            return Ok(vec![SignatureProblem::Unsigned]);
+        };
+
+        if signatures.public_key != key.to_hex() {
+            return Ok(vec![SignatureProblem::KeyMismatch {
+                public_key: signatures.public_key,
+            }]);
+        }
+
+        let problems = signed_files(path)?
+            .into_iter()
+            .filter_map(|(file, data)| {
+                let signature = if let Some(signature) = signatures.files.get(&file) {
+                    signature
+                } else {
+                    return Some(SignatureProblem::MissingSignature {
+                        file,
+                    });
+                };
+                let is_valid = from_hex(signature).is_some_and(|signature| {
+                    key.key
+                        .verify(&message(&file, &data), &Signature::from_bytes(&signature))
+                        .is_ok()
+                });
+
+                if is_valid {
+                    None
+                } else {
+                    Some(SignatureProblem::InvalidSignature {
+                        file,
+                    })
+                }
+            })
+            .collect();
+
+        Ok(problems)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::test::{populated, tempdir};
+    use crate::{SignatureProblem, StoreSigningKey, StoreVerifyingKey, VecStore};
+
+    fn key(byte: u8) -> StoreSigningKey {
+        StoreSigningKey::from_hex(&format!("{:02x}", byte).repeat(32)).unwrap()
+    }
+
+    #[test]
+    fn key_parsing() {
+        assert!(StoreSigningKey::from_hex("00").is_err());
+        assert!(StoreSigningKey::from_hex(&"zz".repeat(32)).is_err());
+
+        let public = key(1).verifying_key();
+        assert_eq!(
+            StoreVerifyingKey::from_hex(&public.to_hex()).unwrap(),
+            public
+        );
+    }
+
+    #[test]
+    fn sign_and_verify() {
+        let workdir = tempdir();
+        let key = key(1);
+        VecStore::store(workdir.path(), &populated()).unwrap();
+
+        assert_eq!(
+            VecStore::verify_signatures(workdir.path(), &key.verifying_key()).unwrap(),
+            [SignatureProblem::Unsigned],
+        );
+
+        VecStore::sign(workdir.path(), &key).unwrap();
+        assert_eq!(
+            VecStore::verify_signatures(workdir.path(), &key.verifying_key()).unwrap(),
+            [],
+        );
+    }
+
+    #[test]
+    fn detect_tampering() {
+        let workdir = tempdir();
+        let key = key(1);
+        VecStore::store(workdir.path(), &populated()).unwrap();
+        VecStore::sign(workdir.path(), &key).unwrap();
+
+        // Swapping manifests is detected.
+        let manifest = fs::read(workdir.path().join("instances/manifest.json")).unwrap();
+        fs::write(workdir.path().join("users/manifest.json"), manifest).unwrap();
+
+        assert_eq!(
+            VecStore::verify_signatures(workdir.path(), &key.verifying_key()).unwrap(),
+            [SignatureProblem::InvalidSignature {
+                file: "users/manifest.json".into(),
+            }],
+        );
+    }
+
+    #[test]
+    fn other_key() {
+        let workdir = tempdir();
+        VecStore::store(workdir.path(), &populated()).unwrap();
+        VecStore::sign(workdir.path(), &key(1)).unwrap();
+
+        let problems =
+            VecStore::verify_signatures(workdir.path(), &key(2).verifying_key()).unwrap();
+        assert_eq!(
+            problems,
+            [SignatureProblem::KeyMismatch {
+                public_key: key(1).verifying_key().to_hex(),
+            }],
+        );
+    }
+}
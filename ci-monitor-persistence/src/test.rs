@@ -0,0 +1,64 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Fixtures shared by persistence tests.
+
+use std::env;
+
+use ci_monitor_core::data::{Instance, Project, User};
+use ci_monitor_core::Lookup;
+use tempfile::TempDir;
+
+use crate::{VecIndex, VecLookup};
+
+/// A temporary directory next to the test executable.
+pub fn tempdir() -> TempDir {
+    let mut working_dir = env::current_exe().unwrap();
+    working_dir.pop();
+
+    TempDir::new_in(working_dir).unwrap()
+}
+
+/// Store the forge instance of test data.
+pub fn instance(lookup: &mut VecLookup) -> VecIndex<Instance> {
+    let instance = Instance::builder()
+        .unique_id(0)
+        .forge("forge")
+        .url("url")
+        .build()
+        .unwrap();
+    lookup.store(instance)
+}
+
+/// Store a project.
+pub fn project(
+    lookup: &mut VecLookup,
+    instance: VecIndex<Instance>,
+    forge_id: u64,
+) -> VecIndex<Project<VecLookup>> {
+    let project = Project::builder()
+        .forge_id(forge_id)
+        .instance(instance)
+        .instance_path("group/project")
+        .url("project")
+        .build()
+        .unwrap();
+    lookup.store(project)
+}
+
+/// A store with an instance, a project with ID 1, and a user with ID 2.
+pub fn populated() -> VecLookup {
+    let mut lookup = VecLookup::default();
+    let instance = instance(&mut lookup);
+    project(&mut lookup, instance, 1);
+    let user = User::builder()
+        .forge_id(2)
+        .instance(instance)
+        .build()
+        .unwrap();
+    lookup.store(user);
+    lookup
+}
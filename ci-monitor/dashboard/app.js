@@ -0,0 +1,177 @@
+"use strict";
+
+async function fetchJson(path) {
+  const response = await fetch(path);
+  if (!response.ok) {
+    throw new Error(`${path}: ${response.status} ${await response.text()}`);
+  }
+  return response.json();
+}
+
+function cell(row, content, className) {
+  const td = row.insertCell();
+  if (content instanceof Node) {
+    td.appendChild(content);
+  } else {
+    td.textContent = content ?? "";
+  }
+  if (className) {
+    td.className = className;
+  }
+  return td;
+}
+
+function link(url, text) {
+  const a = document.createElement("a");
+  a.href = url;
+  a.textContent = text;
+  a.addEventListener("click", (event) => event.stopPropagation());
+  return a;
+}
+
+function time(value) {
+  return value ? new Date(value).toLocaleString() : "";
+}
+
+function percent(rate) {
+  const fraction = rate.finished ? rate.failed / rate.finished : 0;
+  return `${(fraction * 100).toFixed(1)}%`;
+}
+
+function duration(seconds) {
+  const hours = Math.floor(seconds / 3600);
+  return hours < 48 ? `${hours}h` : `${Math.floor(hours / 24)}d`;
+}
+
+function card(title, lines) {
+  const div = document.createElement("div");
+  div.className = "card";
+  const h3 = document.createElement("h3");
+  h3.textContent = title;
+  div.appendChild(h3);
+  for (const line of lines) {
+    const p = document.createElement("div");
+    p.textContent = line;
+    div.appendChild(p);
+  }
+  return div;
+}
+
+async function loadSummary() {
+  const summary = await fetchJson("/api/summary");
+  document.getElementById("site").textContent = summary.name;
+  document.title = `ci-monitor: ${summary.name}`;
+  if (summary.heartbeat) {
+    document.getElementById("heartbeat").textContent =
+      `Last heartbeat: ${time(summary.heartbeat.at)}`;
+  }
+
+  const cards = document.getElementById("summary");
+  cards.replaceChildren(
+    card("Runners", [
+      `${summary.runners.online} of ${summary.runners.total} online`,
+      `${summary.runners.paused} paused`,
+    ]),
+    card("Pipelines", [
+      `${summary.pipelines.failed} of ${summary.pipelines.finished} failed`,
+      `${percent(summary.pipelines)} failure rate`,
+    ]),
+    card("Jobs", [
+      `${summary.jobs.failed} of ${summary.jobs.finished} failed`,
+      `${percent(summary.jobs)} failure rate`,
+    ]),
+    card(
+      "Entities",
+      Object.entries(summary.entities).map(([kind, count]) => `${kind}: ${count}`),
+    ),
+  );
+}
+
+async function loadJobs(pipeline) {
+  const instance = encodeURIComponent(pipeline.instance);
+  const jobs = await fetchJson(`/api/pipelines/${pipeline.id}/jobs?instance=${instance}`);
+  document.getElementById("jobs-title").textContent =
+    `Jobs of pipeline #${pipeline.id} (${pipeline.project})`;
+
+  const body = document.querySelector("#jobs tbody");
+  body.replaceChildren();
+  for (const job of jobs) {
+    const row = body.insertRow();
+    cell(row, link(job.url, job.name));
+    cell(row, job.stage);
+    cell(row, job.allow_failure ? `${job.state} (allowed)` : job.state, job.state);
+    cell(row, job.runner);
+    cell(row, time(job.started_at));
+    cell(row, time(job.finished_at));
+  }
+
+  const section = document.getElementById("jobs-section");
+  section.hidden = false;
+  section.scrollIntoView();
+}
+
+async function loadPipelines() {
+  const project = document.getElementById("project").value.trim();
+  const query = project ? `?project=${encodeURIComponent(project)}` : "";
+  const pipelines = await fetchJson(`/api/pipelines${query}`);
+
+  const body = document.querySelector("#pipelines tbody");
+  body.replaceChildren();
+  for (const pipeline of pipelines) {
+    const row = body.insertRow();
+    row.className = "selectable";
+    row.addEventListener("click", () => loadJobs(pipeline).catch(showError));
+    cell(row, link(pipeline.url, `#${pipeline.id}`));
+    cell(row, pipeline.project);
+    cell(row, pipeline.refname);
+    cell(row, pipeline.status, pipeline.status);
+    cell(row, time(pipeline.created_at));
+    cell(row, time(pipeline.finished_at));
+  }
+}
+
+async function loadRunners() {
+  const runners = await fetchJson("/api/runners");
+
+  const body = document.querySelector("#runners tbody");
+  body.replaceChildren();
+  for (const runner of runners) {
+    const row = body.insertRow();
+    const state = runner.paused ? "paused" : runner.online ? "online" : "offline";
+    cell(row, runner.description || `#${runner.id}`);
+    cell(row, runner.host);
+    cell(row, [runner.platform, runner.architecture].filter(Boolean).join("/"));
+    cell(row, runner.version);
+    cell(row, runner.tags.join(", "));
+    cell(row, state, state);
+    cell(row, time(runner.contacted_at));
+  }
+}
+
+async function loadDrift() {
+  const environments = await fetchJson("/api/drift");
+
+  const body = document.querySelector("#drift tbody");
+  body.replaceChildren();
+  for (const environment of environments) {
+    const row = body.insertRow();
+    cell(row, environment.project);
+    cell(row, environment.environment);
+    cell(row, environment.tier);
+    cell(row, `${environment.deployed.sha.slice(0, 8)} (${time(environment.deployed.finished_at)})`);
+    cell(row, environment.commits_behind);
+    cell(row, duration(environment.behind));
+  }
+}
+
+function showError(err) {
+  const p = document.getElementById("heartbeat");
+  p.textContent = `Error: ${err.message}`;
+}
+
+document.getElementById("filter").addEventListener("submit", (event) => {
+  event.preventDefault();
+  loadPipelines().catch(showError);
+});
+
+Promise.all([loadSummary(), loadPipelines(), loadDrift(), loadRunners()]).catch(showError);
@@ -0,0 +1,136 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::HashMap;
+
+use ci_monitor_core::data::{BlobReference, Job, JobArtifact};
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::{BlobPersistence, BlobVerifyFailure, DiscoverableLookup, VecLookup};
+use schemars::JsonSchema;
+use serde::Serialize;
+
+/// An artifact referring to a blob.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct BlobOwner {
+    /// The ID of the job owning the artifact.
+    pub job: u64,
+    /// The URL of the job.
+    pub job_url: String,
+    /// The name of the artifact.
+    pub artifact: String,
+}
+
+/// A blob which failed verification along with the artifacts referring to it.
+#[derive(Debug)]
+pub struct BlobProblem {
+    pub failure: BlobVerifyFailure,
+    pub owners: Vec<BlobOwner>,
+}
+
+impl BlobProblem {
+    /// A short description of the problem.
+    pub fn kind(&self) -> &'static str {
+        if self.failure.is_missing() {
+            "missing"
+        } else if self.failure.is_corrupt() {
+            "corrupt"
+        } else {
+            "unreadable"
+        }
+    }
+}
+
+/// Collect the blobs referenced by job artifacts along with the artifacts referring to them.
+///
+/// Blobs are returned in the order they are first referenced.
+fn artifact_blobs(
+    storage: &VecLookup,
+) -> (Vec<BlobReference>, HashMap<BlobReference, Vec<BlobOwner>>) {
+    let mut order = Vec::new();
+    let mut owners: HashMap<BlobReference, Vec<BlobOwner>> = HashMap::new();
+
+    let artifacts = <VecLookup as DiscoverableLookup<JobArtifact<VecLookup>>>::all_indices(storage);
+    let artifacts = artifacts
+        .iter()
+        .filter_map(|idx| <VecLookup as Lookup<JobArtifact<VecLookup>>>::lookup(storage, idx));
+    for artifact in artifacts {
+        let blob = if let Some(blob) = artifact.blob.as_ref() {
+            blob
+        } else {
+            continue;
+        };
+        let job = <VecLookup as Lookup<Job<VecLookup>>>::lookup(storage, &artifact.job);
+
+        let owner = BlobOwner {
+            job: job.map_or(0, |job| job.forge_id),
+            job_url: job.map(|job| job.url.clone()).unwrap_or_default(),
+            artifact: artifact.name.clone(),
+        };
+        owners
+            .entry(blob.clone())
+            .or_insert_with(|| {
+                order.push(blob.clone());
+                Vec::new()
+            })
+            .push(owner);
+    }
+
+    (order, owners)
+}
+
+/// Verify all blobs referenced by job artifacts.
+///
+/// Returns the number of blobs checked and those which failed verification.
+pub fn verify_artifact_blobs<B>(
+    storage: &VecLookup,
+    blobs: &B,
+    quiet: bool,
+) -> (usize, Vec<BlobProblem>)
+where
+    B: BlobPersistence,
+{
+    let (refs, mut owners) = artifact_blobs(storage);
+    let total = refs.len();
+
+    let failures = ci_monitor_persistence::verify_blobs(blobs, &refs, |done| {
+        if !quiet {
+            eprintln!("verified {}/{} blobs", done, total);
+        }
+    });
+
+    let problems = failures
+        .into_iter()
+        .map(|failure| {
+            let owners = owners.remove(&failure.blob).unwrap_or_default();
+            BlobProblem {
+                failure,
+                owners,
+            }
+        })
+        .collect();
+
+    (total, problems)
+}
+
+/// Print blobs which failed verification.
+pub fn print_problems(problems: &[BlobProblem]) {
+    for problem in problems {
+        let blob = &problem.failure.blob;
+        println!(
+            "{}: {}:{}: {}",
+            problem.kind(),
+            blob.algo().name(),
+            blob.hash(),
+            problem.failure.error,
+        );
+        for owner in &problem.owners {
+            println!(
+                "    job #{} ({}): {}",
+                owner.job, owner.job_url, owner.artifact,
+            );
+        }
+    }
+}
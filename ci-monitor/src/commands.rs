@@ -0,0 +1,525 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+mod actions_usage;
+mod alerts;
+mod api_usage;
+mod artifact_graph;
+mod artifact_retention;
+mod audit;
+mod audit_log;
+mod autoscaler;
+mod backfill_logs;
+mod blobs;
+mod capture;
+mod check_policy;
+mod check_token;
+mod combined_report;
+mod compare_variables;
+mod crawl;
+mod dashboard;
+mod drift;
+mod experiment;
+mod export;
+mod failure_clusters;
+mod federate;
+mod incidents;
+mod ingest_autoscaler;
+mod ingest_email;
+mod merge_latency;
+mod notifications;
+mod os_eol;
+mod platform_matrix;
+mod push_coverage;
+mod quarantine;
+mod queue_time;
+mod reconcile_pipelines;
+mod releases;
+mod runner_saturation;
+mod search;
+mod search_index;
+mod section_timings;
+mod services;
+mod store;
+mod summary;
+mod tag_routing;
+mod time_to_green;
+mod timeline;
+
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use chrono::Utc;
+use ci_monitor_analysis::EnvironmentDrift;
+use ci_monitor_core::data::{AuditEntry, AuditEntryBuilder, AuditTrigger};
+use ci_monitor_forge::{EndpointLatency, Heartbeat, RunBudget, RunReport};
+use ci_monitor_gitlab::gitlab;
+use ci_monitor_persistence::{EntityType, StoreSigningKey, VecLookup, VecStore, VecStoreError};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::exit::RunError;
+use crate::kv;
+
+/// State shared by the subcommands.
+pub struct Context {
+    /// The parsed command line.
+    pub matches: ArgMatches,
+    /// The configuration.
+    pub config: Config,
+    /// The key to sign the store with.
+    pub signing_key: Option<StoreSigningKey>,
+    /// The directory to load and save the store.
+    pub store_path: Option<PathBuf>,
+    /// Where to write API metrics.
+    pub metrics_path: Option<PathBuf>,
+    /// Whether to suppress progress and summary output.
+    pub quiet: bool,
+    /// Whether to output JSON.
+    pub json: bool,
+}
+
+/// The command line interface.
+pub fn cli() -> Command {
+    Command::new("ci-monitor")
+        .version(clap::crate_version!())
+        .author("Ben Boeckel <ben.boeckel@kitware.com>")
+        .about("Monitor CI on a forge to store for further analysis")
+        .arg(
+            Arg::new("TOKEN")
+                .short('t')
+                .long("token")
+                .help("Token to use")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("CONFIG")
+                .short('c')
+                .long("config")
+                .help("Configuration file")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("STORE")
+                .short('s')
+                .long("store")
+                .help("Directory to load and save the store")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("REPORT")
+                .long("report")
+                .help("Path to write a JSON report of the run (default: alongside the store)")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("METRICS")
+                .long("metrics")
+                .help("Path to write API metrics in the Prometheus text format")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("QUIET")
+                .short('q')
+                .long("quiet")
+                .help("Suppress progress and summary output")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("OPERATOR")
+                .long("operator")
+                .help("Who is running the command, as recorded in the audit log (default: $USER)")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("MAX_DURATION")
+                .long("max-duration")
+                .help("Stop starting tasks after this many seconds and leave the rest for the next run")
+                .value_parser(value_parser!(u64))
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("MAX_API_CALLS")
+                .long("max-api-calls")
+                .help("Stop starting tasks after this many API calls and leave the rest for the next run")
+                .value_parser(value_parser!(u64))
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("LOG_DIFFS")
+                .long("log-diffs")
+                .help("Log the fields changed when updates overwrite stored entities")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("OUTPUT")
+                .short('o')
+                .long("output")
+                .help("Format of the output")
+                .value_parser(["text", "json"])
+                .default_value("text")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("SCHEMA")
+                .long("schema")
+                .help("Print the JSON schema of the command's output and exit")
+                .action(ArgAction::SetTrue),
+        )
+        .subcommand(check_token::command())
+        .subcommand(backfill_logs::command())
+        .subcommand(reconcile_pipelines::command())
+        .subcommand(capture::command())
+        .subcommand(blobs::command())
+        .subcommand(compare_variables::command())
+        .subcommand(runner_saturation::command())
+        .subcommand(store::command())
+        .subcommand(export::command())
+        .subcommand(search_index::command())
+        .subcommand(search::command())
+        .subcommand(audit_log::command())
+        .subcommand(audit::command())
+        .subcommand(failure_clusters::command())
+        .subcommand(artifact_graph::command())
+        .subcommand(check_policy::command())
+        .subcommand(releases::command())
+        .subcommand(combined_report::command())
+        .subcommand(merge_latency::command())
+        .subcommand(time_to_green::command())
+        .subcommand(push_coverage::command())
+        .subcommand(notifications::command())
+        .subcommand(alerts::command())
+        .subcommand(incidents::command())
+        .subcommand(drift::command())
+        .subcommand(experiment::command())
+        .subcommand(platform_matrix::command())
+        .subcommand(os_eol::command())
+        .subcommand(quarantine::command())
+        .subcommand(services::command())
+        .subcommand(tag_routing::command())
+        .subcommand(section_timings::command())
+        .subcommand(queue_time::command())
+        .subcommand(ingest_autoscaler::command())
+        .subcommand(ingest_email::command())
+        .subcommand(autoscaler::command())
+        .subcommand(artifact_retention::command())
+        .subcommand(timeline::command())
+        .subcommand(api_usage::command())
+        .subcommand(actions_usage::command())
+        .subcommand(summary::command())
+        .subcommand(dashboard::command())
+        .subcommand(federate::command())
+        .subcommand(crawl::command())
+}
+
+/// The subcommand requested on the command line along with its own subcommand (if any).
+///
+/// For example, `store check`. `None` refers to a crawl.
+pub fn subcommand_path(matches: &ArgMatches) -> Option<String> {
+    let (name, args) = matches.subcommand()?;
+    Some(if let Some(sub) = args.subcommand_name() {
+        format!("{} {}", name, sub)
+    } else {
+        name.into()
+    })
+}
+
+/// Run the subcommand requested on the command line.
+///
+/// Without a subcommand, the forge is crawled.
+pub async fn run(ctx: &Context) -> Result<ExitCode, RunError> {
+    // These only need the store.
+    match ctx.matches.subcommand() {
+        Some(("compare-variables", args)) => return compare_variables::run(ctx, args),
+        Some(("blobs", args)) => return blobs::run(ctx, args),
+        Some(("store", args)) => return store::run(ctx, args),
+        Some(("export", args)) => return export::run(ctx, args),
+        Some(("search-index", _)) => return search_index::run(ctx),
+        Some(("search", args)) => return search::run(ctx, args),
+        Some(("audit-log", args)) => return audit_log::run(ctx, args),
+        Some(("audit", args)) => return audit::run(ctx, args),
+        Some(("failure-clusters", args)) => return failure_clusters::run(ctx, args),
+        Some(("artifact-graph", args)) => return artifact_graph::run(ctx, args),
+        Some(("check-policy", _)) => return check_policy::run(ctx),
+        Some(("releases", _)) => return releases::run(ctx),
+        Some(("combined-report", args)) => return combined_report::run(ctx, args),
+        Some(("merge-latency", args)) => return merge_latency::run(ctx, args),
+        Some(("time-to-green", args)) => return time_to_green::run(ctx, args),
+        Some(("push-coverage", args)) => return push_coverage::run(ctx, args),
+        Some(("notifications", args)) => return notifications::run(ctx, args),
+        Some(("alerts", args)) => return alerts::run(ctx, args).await,
+        Some(("incidents", args)) => return incidents::run(ctx, args),
+        Some(("drift", args)) => return drift::run(ctx, args),
+        Some(("experiment", args)) => return experiment::run(ctx, args),
+        Some(("tag-routing", args)) => return tag_routing::run(ctx, args),
+        Some(("platform-matrix", args)) => return platform_matrix::run(ctx, args),
+        Some(("os-eol", args)) => return os_eol::run(ctx, args),
+        Some(("quarantine", _)) => return quarantine::run(ctx),
+        Some(("services", args)) => return services::run(ctx, args),
+        Some(("section-timings", args)) => return section_timings::run(ctx, args),
+        Some(("queue-time", args)) => return queue_time::run(ctx, args),
+        Some(("ingest-autoscaler", args)) => return ingest_autoscaler::run(ctx, args),
+        Some(("ingest-email", args)) => return ingest_email::run(ctx, args),
+        Some(("autoscaler", args)) => return autoscaler::run(ctx, args),
+        Some(("artifact-retention", args)) => return artifact_retention::run(ctx, args),
+        Some(("timeline", args)) => return timeline::run(ctx, args),
+        Some(("api-usage", args)) => return api_usage::run(ctx, args),
+        Some(("actions-usage", args)) => return actions_usage::run(ctx, args),
+        Some(("summary", args)) => return summary::run(ctx, args),
+        Some(("dashboard", args)) => return dashboard::run(ctx, args).await,
+        Some(("federate", _)) => return federate::run(ctx).await,
+        Some(("runner-saturation", args)) => return runner_saturation::run(ctx, args),
+        _ => (),
+    }
+
+    let token = ctx
+        .matches
+        .get_one::<String>("TOKEN")
+        .ok_or(RunError::NoToken)?;
+
+    if let Some(args) = ctx.matches.subcommand_matches("capture") {
+        return capture::run(ctx, args, token).await;
+    }
+
+    let gitlab = gitlab::GitlabBuilder::new(crawl::GITLAB_INSTANCE, token)
+        .build_async()
+        .await
+        .map_err(RunError::connect)?;
+
+    match ctx.matches.subcommand() {
+        Some(("check-token", _)) => check_token::run(ctx, &gitlab).await,
+        Some(("backfill-logs", args)) => backfill_logs::run(ctx, args, gitlab).await,
+        Some(("reconcile-pipelines", args)) => reconcile_pipelines::run(ctx, args, gitlab).await,
+        _ => crawl::run(ctx, gitlab).await,
+    }
+}
+
+const REPORT_NAME: &str = "run-report.json";
+const HEARTBEAT_NAME: &str = "heartbeat.json";
+
+/// Load a store from a directory (if it has been populated).
+fn load_store(path: &Path) -> Result<VecLookup, RunError> {
+    load_store_only(path, EntityType::ALL)
+}
+
+/// Load a store from a directory, deferring entity types which are not needed up front.
+///
+/// Commands should call `VecLookup::check_deferred` before reporting results so that deferred
+/// entities which failed to load are not silently treated as empty. Stores kept in a key-value
+/// database are loaded completely.
+fn load_store_only(path: &Path, types: &[EntityType]) -> Result<VecLookup, RunError> {
+    if kv::is_kv_store(path) {
+        return kv::load(path);
+    }
+
+    let is_populated = fs::read_dir(path).is_ok_and(|mut entries| entries.next().is_some());
+    if is_populated {
+        Ok(VecStore::load_only(path, types)?)
+    } else {
+        Ok(VecLookup::default())
+    }
+}
+
+/// Write a store to a directory, signing it if a key is given.
+fn save_store(
+    path: &Path,
+    storage: &VecLookup,
+    key: Option<&StoreSigningKey>,
+) -> Result<(), RunError> {
+    if kv::is_kv_store(path) {
+        if key.is_some() {
+            return Err(RunError::KvUnsupported {
+                operation: "signing",
+            });
+        }
+        kv::save(path, storage)?;
+    } else {
+        fs::create_dir_all(path).map_err(VecStoreError::from)?;
+        VecStore::store(path, storage)?;
+        if let Some(key) = key {
+            VecStore::sign(path, key)?;
+        }
+    }
+    // The store has been saved; the index catches up with it the next time it is updated.
+    if let Err(err) = crate::search::update_index(path, storage) {
+        eprintln!("warning: failed to update the search index: {}", err);
+    }
+
+    Ok(())
+}
+
+/// Print the JSON output of a command.
+pub fn print_json<T>(output: &T) -> Result<(), RunError>
+where
+    T: Serialize + ?Sized,
+{
+    println!("{}", serde_json::to_string_pretty(output)?);
+    Ok(())
+}
+
+/// Print a summary of a run.
+fn print_summary(report: &RunReport) {
+    println!(
+        "executed {} tasks ({} failed) in {:.1}s using {} API requests",
+        report.executed(),
+        report.failed(),
+        report.duration,
+        report.api_requests,
+    );
+    if report.lost > 0 {
+        println!("lost {} queued tasks", report.lost);
+    }
+    if report.warm_started > 0 {
+        println!(
+            "queued {} tasks for entities missed by the previous run",
+            report.warm_started,
+        );
+    }
+    print_slowest_endpoints(&report.api_latency);
+    if report.canceled > 0 {
+        println!("canceled with {} tasks remaining", report.canceled);
+    }
+    for (instance, shed) in &report.shed {
+        println!(
+            "{} tasks for {} left for the next run; too many were waiting",
+            shed, instance,
+        );
+    }
+    if let Some(budget) = report.budget_exhausted {
+        let budget = match budget {
+            RunBudget::Duration => "duration",
+            RunBudget::ApiRequests => "API call",
+            _ => "unknown",
+        };
+        println!(
+            "{} budget exhausted with {} tasks left for the next run ({:.1}% complete)",
+            budget,
+            report.remaining,
+            report.completed * 100.,
+        );
+    }
+    for failure in &report.failures {
+        println!("failed: {}: {}", failure.task, failure.reason);
+    }
+    for failure in &report.partial_failures {
+        println!(
+            "partially failed after {} items: {}: {}",
+            failure.processed, failure.task, failure.reason,
+        );
+    }
+}
+
+/// The number of endpoints listed in the summary of a run.
+const SLOWEST_ENDPOINTS: usize = 5;
+
+/// Print the API endpoints with the highest latency.
+fn print_slowest_endpoints(latency: &[EndpointLatency]) {
+    let mut latency = latency.iter().collect::<Vec<_>>();
+    latency.sort_by(|a, b| {
+        b.quantile(0.95)
+            .total_cmp(&a.quantile(0.95))
+            .then(b.mean().total_cmp(&a.mean()))
+    });
+
+    for endpoint in latency.into_iter().take(SLOWEST_ENDPOINTS) {
+        println!(
+            "api: {}: {} requests ({:.1}% errors), mean {:.2}s, p95 <= {}s",
+            endpoint.endpoint,
+            endpoint.requests,
+            100. * endpoint.error_rate(),
+            endpoint.mean(),
+            endpoint.quantile(0.95),
+        );
+    }
+}
+
+/// Write API metrics in the Prometheus text format.
+///
+/// The file is replaced atomically so that it may be read by a collector at any time.
+fn write_metrics(
+    path: &Path,
+    report: &RunReport,
+    heartbeat: Option<&Heartbeat>,
+    drift: &[EnvironmentDrift],
+) -> io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    let mut metrics = ci_monitor_forge::prometheus_metrics(&report.api_latency);
+    if let Some(heartbeat) = heartbeat {
+        metrics.push_str(&heartbeat.prometheus_metrics());
+    }
+    metrics.push_str(&drift_metrics(drift));
+    fs::write(&tmp, metrics)?;
+    fs::rename(&tmp, path)
+}
+
+/// A gauge exported for each environment: its name, help text, and value.
+type DriftGauge = (&'static str, &'static str, fn(&EnvironmentDrift) -> i64);
+
+/// Render how far environments are behind in the Prometheus text exposition format.
+fn drift_metrics(drift: &[EnvironmentDrift]) -> String {
+    let mut out = String::new();
+
+    let gauges: [DriftGauge; 2] = [
+        (
+            "ci_monitor_environment_commits_behind",
+            "The number of built commits which have not been deployed into the environment.",
+            |drift| drift.commits_behind as i64,
+        ),
+        (
+            "ci_monitor_environment_behind_seconds",
+            "How long the oldest commit which has not been deployed has been waiting.",
+            |drift| drift.behind.num_seconds(),
+        ),
+    ];
+    for (name, help, value) in gauges {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} gauge", name);
+        for drift in drift {
+            let _ = writeln!(
+                out,
+                "{}{{project=\"{}\",environment=\"{}\"}} {}",
+                name,
+                ci_monitor_forge::escape_label(&drift.project),
+                ci_monitor_forge::escape_label(&drift.environment),
+                value(drift),
+            );
+        }
+    }
+
+    out
+}
+
+/// Who is running the command.
+fn operator(matches: &ArgMatches) -> String {
+    matches
+        .get_one::<String>("OPERATOR")
+        .cloned()
+        .or_else(|| env::var("USER").ok())
+        .or_else(|| env::var("USERNAME").ok())
+        .unwrap_or_else(|| "unknown".into())
+}
+
+/// Start an audit log entry for an action requested on the command line.
+fn audit_entry(matches: &ArgMatches, action: &str, target: String) -> AuditEntryBuilder {
+    AuditEntry::builder()
+        .at(Utc::now())
+        .operator(operator(matches))
+        .trigger(AuditTrigger::Cli)
+        .action(action)
+        .target(target)
+}
+
+fn id_arg(help: &'static str) -> Arg {
+    Arg::new("ID")
+        .help(help)
+        .value_parser(value_parser!(u64))
+        .required(true)
+}
@@ -0,0 +1,94 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use ci_monitor_analysis::UsageReconciliation;
+use ci_monitor_persistence::{EntityType, VecLookup};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+
+use crate::commands::{load_store_only, print_json, Context};
+use crate::exit::RunError;
+
+/// The `actions-usage` subcommand.
+pub fn command() -> Command {
+    Command::new("actions-usage")
+        .about("Reconcile a GitHub Actions usage report against stored job durations")
+        .arg(
+            Arg::new("REPORT")
+                .help("Path to a usage report exported from GitHub billing (CSV)")
+                .required(true),
+        )
+        .arg(
+            Arg::new("TOLERANCE")
+                .long("tolerance")
+                .help("Percentage by which billed minutes may differ without being flagged")
+                .value_parser(value_parser!(f64))
+                .default_value("10")
+                .action(ArgAction::Set),
+        )
+}
+
+/// Run the `actions-usage` subcommand.
+pub fn run(ctx: &Context, actions_usage: &ArgMatches) -> Result<ExitCode, RunError> {
+    let report_path = PathBuf::from(
+        actions_usage
+            .get_one::<String>("REPORT")
+            .expect("the report is required"),
+    );
+    let report =
+        fs::read_to_string(&report_path).map_err(|err| RunError::read(report_path, err))?;
+    let usage = ci_monitor_analysis::parse_actions_usage(&report)?;
+    let storage = if let Some(path) = ctx.store_path.as_ref() {
+        load_store_only(
+            path,
+            &[EntityType::Project, EntityType::Pipeline, EntityType::Job],
+        )?
+    } else {
+        VecLookup::default()
+    };
+    let tolerance = *actions_usage
+        .get_one::<f64>("TOLERANCE")
+        .expect("--tolerance has a default")
+        / 100.;
+    let reconciliations = ci_monitor_analysis::reconcile_actions_usage(&storage, &usage, tolerance);
+
+    storage.check_deferred()?;
+
+    if ctx.json {
+        print_json(&reconciliations)?;
+    } else if !ctx.quiet {
+        print_usage_reconciliations(&reconciliations);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print billed GitHub Actions minutes against those computed from stored jobs.
+fn print_usage_reconciliations(reconciliations: &[UsageReconciliation]) {
+    for reconciliation in reconciliations {
+        let marker = if reconciliation.discrepancy { "!" } else { " " };
+        let unknown = if reconciliation.known {
+            ""
+        } else {
+            " (not in the store)"
+        };
+        println!(
+            "{} {} {} to {}: {:.0} billed, {:.0} computed from {} jobs ({:+.0}){}",
+            marker,
+            reconciliation.repository,
+            reconciliation.first_day,
+            reconciliation.last_day,
+            reconciliation.billed_minutes,
+            reconciliation.computed_minutes,
+            reconciliation.jobs,
+            reconciliation.difference(),
+            unknown,
+        );
+    }
+}
@@ -0,0 +1,118 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeSet;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use chrono::Utc;
+use ci_monitor_analysis::Alert;
+use ci_monitor_persistence::{EntityType, VecLookup};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+
+use crate::commands::{load_store_only, print_json, Context};
+use crate::exit::RunError;
+
+/// The `alerts` subcommand.
+pub fn command() -> Command {
+    Command::new("alerts")
+        .about("Evaluate the configured alert rules over the store")
+        .arg(
+            Arg::new("EVERY")
+                .long("every")
+                .help(
+                    "Re-evaluate every number of minutes, only reporting newly raised \
+                     alerts",
+                )
+                .value_parser(value_parser!(u32).range(1..))
+                .action(ArgAction::Set),
+        )
+}
+
+/// Run the `alerts` subcommand.
+pub async fn run(ctx: &Context, alerts: &ArgMatches) -> Result<ExitCode, RunError> {
+    let rules = ctx.config.alert_rules()?;
+    let router = ctx
+        .config
+        .notifications
+        .router()?
+        .quarantine(ctx.config.quarantine()?);
+    let every = alerts
+        .get_one::<u32>("EVERY")
+        .map(|minutes| Duration::from_secs(u64::from(*minutes) * 60));
+
+    // Alerts raised by the previous evaluation are only reported again once they clear.
+    let mut raised: BTreeSet<(String, String)> = BTreeSet::new();
+    loop {
+        let storage = if let Some(path) = ctx.store_path.as_ref() {
+            load_store_only(
+                path,
+                &[EntityType::Pipeline, EntityType::Job, EntityType::Project],
+            )?
+        } else {
+            VecLookup::default()
+        };
+        let alerts = ci_monitor_analysis::evaluate_alerts(&storage, &rules, &router, Utc::now());
+        let new_alerts = alerts
+            .iter()
+            .filter(|alert| !raised.contains(&(alert.rule.clone(), alert.project.clone())))
+            .collect::<Vec<_>>();
+
+        storage.check_deferred()?;
+
+        if ctx.json {
+            print_json(&new_alerts)?;
+        } else if !ctx.quiet {
+            print_alerts(&new_alerts);
+        }
+
+        let every = if let Some(every) = every {
+            every
+        } else {
+            break;
+        };
+        raised = alerts
+            .iter()
+            .map(|alert| (alert.rule.clone(), alert.project.clone()))
+            .collect();
+        tokio::select! {
+            _ = tokio::time::sleep(every) => (),
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print raised alerts and where they are delivered.
+fn print_alerts(alerts: &[&Alert]) {
+    for alert in alerts {
+        println!(
+            "{}: {}: {} of {} is {:.2} (threshold {:.2}, {} measured since {})",
+            alert.project,
+            alert.rule,
+            alert.condition.name(),
+            alert.entity.name(),
+            alert.value,
+            alert.threshold,
+            alert.samples,
+            alert.since,
+        );
+        for route in &alert.routes {
+            let owner = route
+                .owner
+                .as_ref()
+                .map_or_else(String::new, |owner| format!(" ({})", owner));
+            println!(
+                "    notify {}{} {} at {}",
+                route.channel,
+                owner,
+                route.kind.name(),
+                route.deliver_at,
+            );
+        }
+    }
+}
@@ -0,0 +1,72 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::process::ExitCode;
+
+use chrono::Utc;
+use ci_monitor_analysis::{ApiUsageBucket, UsagePeriod};
+use ci_monitor_persistence::{EntityType, VecLookup};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+
+use crate::commands::{load_store_only, print_json, Context};
+use crate::exit::RunError;
+
+/// The `api-usage` subcommand.
+pub fn command() -> Command {
+    Command::new("api-usage")
+        .about("Show the API requests made to each instance by past crawls")
+        .arg(
+            Arg::new("DAILY")
+                .long("daily")
+                .help("Group requests by day rather than by hour")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("SINCE")
+                .long("since")
+                .help("Number of hours to look back")
+                .value_parser(value_parser!(u32))
+                .action(ArgAction::Set),
+        )
+}
+
+/// Run the `api-usage` subcommand.
+pub fn run(ctx: &Context, api_usage: &ArgMatches) -> Result<ExitCode, RunError> {
+    let storage = if let Some(path) = ctx.store_path.as_ref() {
+        load_store_only(path, &[EntityType::CrawlSession, EntityType::Instance])?
+    } else {
+        VecLookup::default()
+    };
+    let period = if api_usage.get_flag("DAILY") {
+        UsagePeriod::Day
+    } else {
+        UsagePeriod::Hour
+    };
+    let since = api_usage
+        .get_one::<u32>("SINCE")
+        .map(|hours| Utc::now() - chrono::Duration::hours((*hours).into()));
+    let history = ci_monitor_analysis::api_usage_history(&storage, period, since);
+
+    storage.check_deferred()?;
+
+    if ctx.json {
+        print_json(&history)?;
+    } else if !ctx.quiet {
+        print_api_usage(&history);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print API usage history.
+fn print_api_usage(history: &[ApiUsageBucket]) {
+    for bucket in history {
+        println!(
+            "{} {}: {} requests",
+            bucket.instance, bucket.start, bucket.requests
+        );
+    }
+}
@@ -0,0 +1,90 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::process::ExitCode;
+
+use ci_monitor_analysis::{ArtifactGraph, EntityGraph, GraphFormat};
+use ci_monitor_persistence::{EntityType, VecLookup};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+
+use crate::commands::{load_store_only, print_json, Context};
+use crate::exit::RunError;
+
+/// The `artifact-graph` subcommand.
+pub fn command() -> Command {
+    Command::new("artifact-graph")
+        .about("Show which jobs of a stored pipeline consume the artifacts of other jobs")
+        .arg(
+            Arg::new("PIPELINE")
+                .help("The ID of the pipeline")
+                .value_parser(value_parser!(u64))
+                .required(true),
+        )
+        .arg(
+            Arg::new("FORMAT")
+                .long("format")
+                .help("Render the graph instead of listing consumers")
+                .value_parser(["graphviz", "mermaid"])
+                .action(ArgAction::Set),
+        )
+}
+
+/// Run the `artifact-graph` subcommand.
+pub fn run(ctx: &Context, artifacts: &ArgMatches) -> Result<ExitCode, RunError> {
+    let storage = if let Some(path) = ctx.store_path.as_ref() {
+        load_store_only(path, &[EntityType::Pipeline, EntityType::Job])?
+    } else {
+        VecLookup::default()
+    };
+    let pipeline = *artifacts
+        .get_one::<u64>("PIPELINE")
+        .expect("the pipeline is required");
+    let graph = ArtifactGraph::pipeline(&storage, pipeline).ok_or_else(|| {
+        RunError::MissingEntity {
+            kind: "pipeline with artifact dependencies".into(),
+            id: pipeline,
+        }
+    })?;
+    let format = match artifacts.get_one::<String>("FORMAT").map(String::as_str) {
+        Some("graphviz") => Some(GraphFormat::GraphViz),
+        Some("mermaid") => Some(GraphFormat::Mermaid),
+        _ => None,
+    };
+
+    storage.check_deferred()?;
+
+    if ctx.json {
+        print_json(&graph)?;
+    } else if let Some(format) = format {
+        print!("{}", EntityGraph::from(&graph).render(format));
+    } else if !ctx.quiet {
+        print_artifact_graph(&graph);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print the jobs consuming the artifacts of each job.
+fn print_artifact_graph(graph: &ArtifactGraph) {
+    let name = |id| {
+        graph
+            .jobs
+            .iter()
+            .find(|job| job.id == id)
+            .map_or("", |job| job.name.as_str())
+    };
+    for job in &graph.jobs {
+        let consumers = graph
+            .consumers(job.id)
+            .map(|consumer| format!("{} (#{})", name(consumer), consumer))
+            .collect::<Vec<_>>();
+        if consumers.is_empty() {
+            println!("{} (#{}): no consumers", job.name, job.id);
+        } else {
+            println!("{} (#{}): {}", job.name, job.id, consumers.join(", "));
+        }
+    }
+}
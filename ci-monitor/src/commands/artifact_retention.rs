@@ -0,0 +1,123 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::process::ExitCode;
+
+use chrono::Utc;
+use ci_monitor_analysis::{ArtifactRetention, ArtifactRetentionOptions};
+use ci_monitor_persistence::{EntityType, VecLookup};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+
+use crate::commands::{load_store_only, print_json, Context};
+use crate::exit::RunError;
+
+/// The `artifact-retention` subcommand.
+pub fn command() -> Command {
+    Command::new("artifact-retention")
+        .about("Estimate artifact storage costs and forecast their growth per project")
+        .arg(
+            Arg::new("PRICE")
+                .long("price")
+                .help("Price of storing a GiB for a month")
+                .value_parser(value_parser!(f64))
+                .default_value("0")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("MAX_AGE")
+                .long("max-age")
+                .help("Expiry policy (in days) to estimate savings for")
+                .value_parser(value_parser!(u32))
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("TOP")
+                .long("top")
+                .help("Number of projects to report")
+                .value_parser(value_parser!(usize))
+                .default_value("10")
+                .action(ArgAction::Set),
+        )
+}
+
+/// Run the `artifact-retention` subcommand.
+pub fn run(ctx: &Context, retention: &ArgMatches) -> Result<ExitCode, RunError> {
+    let storage = if let Some(path) = ctx.store_path.as_ref() {
+        load_store_only(
+            path,
+            &[
+                EntityType::Project,
+                EntityType::Pipeline,
+                EntityType::Job,
+                EntityType::JobArtifact,
+            ],
+        )?
+    } else {
+        VecLookup::default()
+    };
+    let mut options = ArtifactRetentionOptions::default().price_per_gib_month(
+        *retention
+            .get_one::<f64>("PRICE")
+            .expect("--price has a default"),
+    );
+    if let Some(&days) = retention.get_one::<u32>("MAX_AGE") {
+        options = options.max_age(chrono::Duration::days(days.into()));
+    }
+    let top = *retention
+        .get_one::<usize>("TOP")
+        .expect("--top has a default");
+    let mut report = ci_monitor_analysis::artifact_retention(&storage, Utc::now(), options);
+    report.projects.truncate(top);
+
+    storage.check_deferred()?;
+
+    if ctx.json {
+        print_json(&report)?;
+    } else if !ctx.quiet {
+        print_artifact_retention(&report);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print estimated artifact storage costs and the projects using the most storage.
+fn print_artifact_retention(retention: &ArtifactRetention) {
+    let total = &retention.total;
+    println!(
+        "{} artifacts: {:.2} GiB retained by the forge ({:.2} GiB never expire), {:.2} GiB stored \
+         locally; {:.2}/month now, {:.2}/month forecast",
+        total.artifacts,
+        gib(total.retained),
+        gib(total.never_expires),
+        gib(total.stored),
+        total.monthly_cost,
+        total.forecast_cost,
+    );
+    if total.reclaimable > 0 {
+        println!(
+            "  a tighter expiry policy would reclaim {:.2} GiB ({:.2}/month)",
+            gib(total.reclaimable),
+            total.reclaimable_cost,
+        );
+    }
+    for project in &retention.projects {
+        let storage = &project.storage;
+        println!(
+            "{}: {} artifacts, {:.2} GiB -> {:.2} GiB forecast, {:.2}/month, {:.2} GiB reclaimable",
+            project.path,
+            storage.artifacts,
+            gib(storage.total()),
+            gib(storage.forecast()),
+            storage.monthly_cost,
+            gib(storage.reclaimable),
+        );
+    }
+}
+
+/// Format a size in bytes as GiB.
+fn gib(bytes: u64) -> f64 {
+    bytes as f64 / (1024. * 1024. * 1024.)
+}
@@ -0,0 +1,113 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::num::NonZeroUsize;
+use std::process::ExitCode;
+use std::thread;
+
+use ci_monitor_persistence::VecStore;
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+
+use crate::blobs;
+use crate::commands::{load_store, print_json, Context};
+use crate::exit::{self, RunError};
+use crate::kv;
+use crate::output::{
+    AuditOutput, BlobProblemOutput, BlobVerificationOutput, SignatureProblemOutput,
+    StoreProblemOutput,
+};
+
+/// The `audit` subcommand.
+pub fn command() -> Command {
+    Command::new("audit")
+        .about("Detect tampering with the store and the blobs it references")
+        .arg(
+            Arg::new("THREADS")
+                .long("threads")
+                .help("Number of threads to verify blobs with (overrides `artifacts.parallelism`)")
+                .value_parser(value_parser!(NonZeroUsize))
+                .action(ArgAction::Set),
+        )
+}
+
+/// Run the `audit` subcommand.
+pub fn run(ctx: &Context, audit: &ArgMatches) -> Result<ExitCode, RunError> {
+    let path = ctx.store_path.as_ref().ok_or(RunError::NoStore)?;
+    let key = ctx
+        .config
+        .signing
+        .verifying_key()?
+        .ok_or(RunError::NoSigningKey)?;
+    if kv::is_kv_store(path) {
+        return Err(RunError::KvUnsupported {
+            operation: "auditing",
+        });
+    }
+    let signatures = VecStore::verify_signatures(path, &key)?;
+    let entities = VecStore::check(path)?;
+    // Blobs are only trustworthy if the entities referring to them are intact.
+    let blob_check = if entities.is_empty() {
+        let threads = audit
+            .get_one::<NonZeroUsize>("THREADS")
+            .copied()
+            .or(ctx.config.artifacts.parallelism)
+            .or_else(|| thread::available_parallelism().ok());
+        if let Some(blob_storage) = ctx.config.artifacts.blob_tiers_with_parallelism(threads)? {
+            let storage = load_store(path)?;
+            Some(blobs::verify_artifact_blobs(
+                &storage,
+                &blob_storage,
+                ctx.quiet || ctx.json,
+            ))
+        } else {
+            None
+        }
+    } else {
+        None
+    };
+    let is_intact = signatures.is_empty()
+        && entities.is_empty()
+        && blob_check
+            .as_ref()
+            .is_none_or(|(_, problems)| problems.is_empty());
+
+    if ctx.json {
+        let output = AuditOutput {
+            signatures: signatures
+                .iter()
+                .map(SignatureProblemOutput::from)
+                .collect(),
+            entities: entities.iter().map(StoreProblemOutput::from).collect(),
+            blobs: blob_check.as_ref().map(|(checked, problems)| {
+                BlobVerificationOutput {
+                    checked: *checked,
+                    problems: problems.iter().map(BlobProblemOutput::from).collect(),
+                }
+            }),
+        };
+        print_json(&output)?;
+    } else if !ctx.quiet {
+        for problem in &signatures {
+            println!("{}", problem);
+        }
+        for problem in &entities {
+            println!("{}", problem);
+        }
+        if let Some((checked, problems)) = blob_check.as_ref() {
+            blobs::print_problems(problems);
+            println!("{} blobs checked, {} problems", checked, problems.len());
+        }
+        if is_intact {
+            println!("no tampering detected");
+        }
+    }
+
+    Ok(if is_intact {
+        ExitCode::SUCCESS
+    } else {
+        exit::CORRUPT.into()
+    })
+}
@@ -0,0 +1,80 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::process::ExitCode;
+
+use chrono::Utc;
+use ci_monitor_core::data::AuditEntry;
+use ci_monitor_core::Lookup;
+use ci_monitor_persistence::{DiscoverableLookup, EntityType, VecLookup};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+
+use crate::commands::{load_store_only, print_json, Context};
+use crate::exit::RunError;
+use crate::output::AuditEntryOutput;
+
+/// The `audit-log` subcommand.
+pub fn command() -> Command {
+    Command::new("audit-log")
+        .about("Show manual refreshes and deletions recorded in the store")
+        .arg(
+            Arg::new("SINCE")
+                .long("since")
+                .help("Number of days to look back for actions")
+                .value_parser(value_parser!(u32))
+                .action(ArgAction::Set),
+        )
+}
+
+/// Run the `audit-log` subcommand.
+pub fn run(ctx: &Context, audit_log: &ArgMatches) -> Result<ExitCode, RunError> {
+    let path = ctx.store_path.as_ref().ok_or(RunError::NoStore)?;
+    let storage = load_store_only(path, &[EntityType::AuditEntry])?;
+    let since = audit_log
+        .get_one::<u32>("SINCE")
+        .map(|&days| Utc::now() - chrono::Duration::days(days.into()));
+    let indices = <VecLookup as DiscoverableLookup<AuditEntry>>::all_indices(&storage);
+    let mut entries = indices
+        .iter()
+        .filter_map(|idx| <VecLookup as Lookup<AuditEntry>>::lookup(&storage, idx))
+        .filter(|entry| since.is_none_or(|since| since <= entry.at))
+        .collect::<Vec<_>>();
+    entries.sort_by_key(|entry| entry.unique_id);
+
+    storage.check_deferred()?;
+
+    if ctx.json {
+        print_json(
+            &entries
+                .iter()
+                .copied()
+                .map(AuditEntryOutput::from)
+                .collect::<Vec<_>>(),
+        )?;
+    } else if !ctx.quiet {
+        print_audit_log(&entries);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print manual actions recorded in the store.
+fn print_audit_log(entries: &[&AuditEntry]) {
+    for entry in entries {
+        println!(
+            "{} {} ({}): {} {} ({} affected)",
+            entry.at,
+            entry.operator,
+            entry.trigger.name(),
+            entry.action,
+            entry.target,
+            entry.affected,
+        );
+        if !entry.tasks.is_empty() {
+            println!("    tasks: {}", entry.tasks.join(", "));
+        }
+    }
+}
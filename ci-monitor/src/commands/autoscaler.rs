@@ -0,0 +1,91 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::process::ExitCode;
+
+use chrono::Utc;
+use ci_monitor_analysis::AutoscalerActivity;
+use ci_monitor_persistence::{EntityType, VecLookup};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+
+use crate::commands::{load_store_only, print_json, Context};
+use crate::exit::RunError;
+
+/// The `autoscaler` subcommand.
+pub fn command() -> Command {
+    Command::new("autoscaler")
+        .about("Summarize autoscaler spin-ups, lifetimes, and costs per runner host")
+        .arg(
+            Arg::new("SINCE")
+                .long("since")
+                .help("Number of hours to look back for instances and jobs")
+                .value_parser(value_parser!(u32))
+                .default_value("168")
+                .action(ArgAction::Set),
+        )
+}
+
+/// Run the `autoscaler` subcommand.
+pub fn run(ctx: &Context, autoscaler: &ArgMatches) -> Result<ExitCode, RunError> {
+    let storage = if let Some(path) = ctx.store_path.as_ref() {
+        load_store_only(
+            path,
+            &[EntityType::RunnerHost, EntityType::Runner, EntityType::Job],
+        )?
+    } else {
+        VecLookup::default()
+    };
+    let hours = *autoscaler
+        .get_one::<u32>("SINCE")
+        .expect("--since has a default");
+    let now = Utc::now();
+    let since = now - chrono::Duration::hours(hours.into());
+    let activities = ci_monitor_analysis::autoscaler_activity(&storage, since, now);
+
+    storage.check_deferred()?;
+
+    if ctx.json {
+        print_json(&activities)?;
+    } else if !ctx.quiet {
+        print_autoscaler_activity(&activities);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print autoscaler behavior per runner host.
+fn print_autoscaler_activity(activities: &[AutoscalerActivity]) {
+    let seconds = |value: Option<f64>| {
+        value.map_or_else(|| "unknown".into(), |value| format!("{:.0}s", value))
+    };
+
+    for activity in activities {
+        println!(
+            "{}: {} spin-ups, {} removals, {} active, {:.1} instance hours",
+            activity.host,
+            activity.spin_ups,
+            activity.removals,
+            activity.active,
+            activity.instance_hours,
+        );
+        println!(
+            "  time to ready {}, lifetime {}",
+            seconds(activity.mean_time_to_ready),
+            seconds(activity.mean_lifetime),
+        );
+        if let Some(estimated_cost) = activity.estimated_cost {
+            println!("  estimated cost {:.2}", estimated_cost);
+        }
+        println!(
+            "  {} jobs queued for a median of {:.0}s; {} queued during a spin-up for a median of \
+             {:.0}s",
+            activity.jobs,
+            activity.median_queue_time,
+            activity.jobs_during_spin_up,
+            activity.median_queue_time_during_spin_up,
+        );
+    }
+}
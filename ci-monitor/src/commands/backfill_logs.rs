@@ -0,0 +1,142 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::num::NonZeroU32;
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use chrono::Utc;
+use ci_monitor_analysis::LogBackfill;
+use ci_monitor_forge::{ForgeTask, Heartbeat};
+use ci_monitor_gitlab::gitlab::AsyncGitlab;
+use ci_monitor_gitlab::GitlabForge;
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+
+use crate::commands::{
+    load_store, print_json, print_summary, save_store, write_metrics, Context, HEARTBEAT_NAME,
+};
+use crate::exit::{self, RunError};
+use crate::output::BackfillOutput;
+
+/// The `backfill-logs` subcommand.
+pub fn command() -> Command {
+    Command::new("backfill-logs")
+        .about("Fetch logs of stored jobs which are missing them")
+        .arg(
+            Arg::new("LIMIT")
+                .long("limit")
+                .help("Maximum number of logs to fetch in this run")
+                .value_parser(value_parser!(usize))
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("RATE")
+                .long("rate")
+                .help("Number of requests per second to make")
+                .value_parser(value_parser!(NonZeroU32))
+                .action(ArgAction::Set),
+        )
+}
+
+/// Run the `backfill-logs` subcommand.
+pub async fn run(
+    ctx: &Context,
+    backfill: &ArgMatches,
+    gitlab: AsyncGitlab,
+) -> Result<ExitCode, RunError> {
+    let path = ctx.store_path.as_ref().ok_or(RunError::NoStore)?;
+    let blob_storage = ctx
+        .config
+        .artifacts
+        .blob_storage()?
+        .ok_or(RunError::NoBlobStore)?;
+    let storage = load_store(path)?;
+
+    let progress = ci_monitor_analysis::log_backfill(&storage);
+    if !ctx.quiet && !ctx.json {
+        print_backfill_progress(&progress);
+    }
+    let limit = backfill
+        .get_one::<usize>("LIMIT")
+        .copied()
+        .or(ctx.config.backfill.batch)
+        .unwrap_or(usize::MAX);
+    let tasks = progress
+        .missing
+        .iter()
+        .take(limit)
+        .map(|missing| {
+            ForgeTask::FetchJobLog {
+                project: missing.project,
+                job: missing.job,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let forge = GitlabForge::with_aliases(
+        "gitlab.kitware.com",
+        &ctx.config.instances.aliases(),
+        gitlab,
+        storage,
+    )
+    .with_blob_storage(blob_storage);
+    let forge = Arc::new(forge);
+    let mut executor = ctx.config.backfill.executor(&ctx.config.tasks)?;
+    if let Some(&rate) = backfill.get_one::<NonZeroU32>("RATE") {
+        executor = executor.rate_limit(rate);
+    }
+    if let Some(interval) = ctx.config.tasks.heartbeat_interval() {
+        executor = executor.heartbeat(path.join(HEARTBEAT_NAME), interval);
+    }
+    let canceller = executor.canceller();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            canceller.cancel();
+        }
+    });
+    let report = executor.run(forge.clone(), tasks).await;
+
+    // Fetched logs are saved even if the run was interrupted so that it may be resumed.
+    let storage = Arc::into_inner(forge)
+        .expect("all tasks have completed")
+        .into_storage();
+    save_store(path, &storage, ctx.signing_key.as_ref())?;
+    let heartbeat = Heartbeat::read(&path.join(HEARTBEAT_NAME))?;
+    if let Some(path) = ctx.metrics_path.as_ref() {
+        let drift = ci_monitor_analysis::environment_drift(
+            &storage,
+            ctx.config.deployments.branch(),
+            Utc::now(),
+        );
+        write_metrics(path, &report, heartbeat.as_ref(), &drift)
+            .map_err(|err| RunError::metrics(path.clone(), err))?;
+    }
+
+    let progress = ci_monitor_analysis::log_backfill(&storage);
+    if ctx.json {
+        let output = BackfillOutput {
+            progress,
+            report,
+        };
+        print_json(&output)?;
+        return Ok(exit::for_report(&output.report));
+    } else if !ctx.quiet {
+        print_summary(&report);
+        print_backfill_progress(&progress);
+    }
+
+    Ok(exit::for_report(&report))
+}
+
+/// Print the progress of a log backfill.
+fn print_backfill_progress(backfill: &LogBackfill) {
+    println!(
+        "{}/{} job logs stored ({:.1}%)",
+        backfill.stored,
+        backfill.jobs,
+        backfill.percent_complete(),
+    );
+}
@@ -0,0 +1,183 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::num::NonZeroUsize;
+use std::process::ExitCode;
+use std::thread;
+
+use chrono::Utc;
+use ci_monitor_core::data::ContentHash;
+use ci_monitor_persistence::VecLookup;
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+
+use crate::blobs;
+use crate::commands::{load_store, print_json, save_store, Context};
+use crate::config::ConfigError;
+use crate::exit::{self, RunError};
+use crate::output::{
+    BlobProblemOutput, BlobRehashOutput, BlobVerificationOutput, LogArchiveOutput,
+};
+
+/// The `blobs` subcommand.
+pub fn command() -> Command {
+    Command::new("blobs")
+        .about("Manage the blob store")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("verify")
+                .about("Verify the contents of blobs referenced by job artifacts")
+                .arg(
+                    Arg::new("THREADS")
+                        .long("threads")
+                        .help("Number of threads to verify blobs with (overrides `artifacts.parallelism`)")
+                        .value_parser(value_parser!(NonZeroUsize))
+                        .action(ArgAction::Set),
+                ),
+        )
+        .subcommand(
+            Command::new("rehash")
+                .about(
+                    "Rehash blobs referenced by the store with another algorithm (keeping \
+                     the original blobs)",
+                )
+                .arg(
+                    Arg::new("ALGORITHM")
+                        .help("The algorithm to hash blobs with")
+                        .value_parser(["sha256", "sha512", "blake3"])
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("archive")
+                .about("Move the logs of old jobs into the cold blob store")
+                .arg(
+                    Arg::new("OLDER_THAN")
+                        .long("older-than")
+                        .help("Number of days after a job finishes to archive its log")
+                        .value_parser(value_parser!(u32))
+                        .default_value("90")
+                        .action(ArgAction::Set),
+                ),
+        )
+}
+
+/// Run the `blobs` subcommand.
+pub fn run(ctx: &Context, blobs: &ArgMatches) -> Result<ExitCode, RunError> {
+    match blobs.subcommand() {
+        Some(("verify", verify)) => verify_blobs(ctx, verify),
+        Some(("rehash", rehash)) => rehash_blobs(ctx, rehash),
+        Some(("archive", archive)) => archive_logs(ctx, archive),
+        _ => unreachable!("a blobs subcommand is required"),
+    }
+}
+
+/// Verify the contents of blobs referenced by job artifacts.
+fn verify_blobs(ctx: &Context, verify: &ArgMatches) -> Result<ExitCode, RunError> {
+    let storage = if let Some(path) = ctx.store_path.as_ref() {
+        load_store(path)?
+    } else {
+        VecLookup::default()
+    };
+    let threads = verify
+        .get_one::<NonZeroUsize>("THREADS")
+        .copied()
+        .or(ctx.config.artifacts.parallelism)
+        .or_else(|| thread::available_parallelism().ok());
+    let blob_storage = ctx
+        .config
+        .artifacts
+        .blob_tiers_with_parallelism(threads)?
+        .ok_or(RunError::NoBlobStore)?;
+    let (checked, problems) =
+        blobs::verify_artifact_blobs(&storage, &blob_storage, ctx.quiet || ctx.json);
+
+    if ctx.json {
+        let output = BlobVerificationOutput {
+            checked,
+            problems: problems.iter().map(BlobProblemOutput::from).collect(),
+        };
+        print_json(&output)?;
+    } else if !ctx.quiet {
+        blobs::print_problems(&problems);
+        println!("{} blobs checked, {} problems", checked, problems.len());
+    }
+
+    Ok(if problems.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        exit::CORRUPT.into()
+    })
+}
+
+/// Rehash the blobs referenced by the store.
+fn rehash_blobs(ctx: &Context, rehash: &ArgMatches) -> Result<ExitCode, RunError> {
+    let path = ctx.store_path.as_ref().ok_or(RunError::NoStore)?;
+    let mut storage = load_store(path)?;
+    let mut blob_storage = ctx
+        .config
+        .artifacts
+        .blob_tiers()?
+        .ok_or(RunError::NoBlobStore)?;
+    let algo = rehash
+        .get_one::<String>("ALGORITHM")
+        .and_then(|name| ContentHash::from_name(name))
+        .expect("the algorithm is required");
+
+    // Rehashed blobs are stored into the hot tier.
+    blob_storage
+        .hot_mut()
+        .set_algorithm(algo)
+        .map_err(ConfigError::from)?;
+    let rehashed = storage.rehash_blobs(&blob_storage, algo)?;
+    save_store(path, &storage, ctx.signing_key.as_ref())?;
+
+    if ctx.json {
+        let output = BlobRehashOutput {
+            algorithm: algo.name().into(),
+            rehashed,
+        };
+        print_json(&output)?;
+    } else if !ctx.quiet {
+        println!("rehashed {} blobs using {}", rehashed, algo.name());
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Move the logs of old jobs into the cold blob store.
+fn archive_logs(ctx: &Context, archive: &ArgMatches) -> Result<ExitCode, RunError> {
+    let path = ctx.store_path.as_ref().ok_or(RunError::NoStore)?;
+    let mut storage = load_store(path)?;
+    let blob_storage = ctx
+        .config
+        .artifacts
+        .blob_tiers()?
+        .ok_or(RunError::NoBlobStore)?;
+    if blob_storage.cold().is_none() {
+        return Err(RunError::NoColdBlobStore);
+    }
+    let days = *archive
+        .get_one::<u32>("OLDER_THAN")
+        .expect("--older-than has a default");
+    let before = Utc::now() - chrono::Duration::days(days.into());
+
+    let archived = storage
+        .archive_job_logs(&blob_storage, before)
+        .map_err(RunError::archive)?;
+    save_store(path, &storage, ctx.signing_key.as_ref())?;
+
+    if ctx.json {
+        let output = LogArchiveOutput {
+            before,
+            archived,
+        };
+        print_json(&output)?;
+    } else if !ctx.quiet {
+        println!("archived {} job logs", archived);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,144 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use ci_monitor_forge::RefreshTarget;
+use ci_monitor_gitlab::{gitlab, GitlabForge, PipelineUrl};
+use ci_monitor_persistence::VecLookup;
+use clap::{Arg, ArgMatches, Command};
+
+use crate::commands::{print_json, print_summary, save_store, Context, REPORT_NAME};
+use crate::exit::{self, RunError};
+use crate::output::CaptureOutput;
+
+/// Discovery tasks which would reach beyond a captured pipeline.
+const CAPTURE_SKIPPED_TASKS: &[&str] = &[
+    "discover_group_projects",
+    "discover_merge_requests",
+    "discover_merge_request_pipelines",
+    "discover_pipeline_schedules",
+    "discover_pipelines",
+    "discover_environments",
+    "discover_deployments",
+    "discover_releases",
+    "discover_pushes",
+];
+
+/// The `capture` subcommand.
+pub fn command() -> Command {
+    Command::new("capture")
+        .about("Capture CI context into a standalone store")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("pipeline")
+                .about("Capture a pipeline with its jobs, logs, and artifacts")
+                .arg(
+                    Arg::new("URL")
+                        .help("The web URL of the pipeline")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("DIRECTORY")
+                        .help("The directory to write to (defaults to `pipeline-<ID>`)"),
+                ),
+        )
+}
+
+/// Run the `capture` subcommand.
+pub async fn run(ctx: &Context, capture: &ArgMatches, token: &str) -> Result<ExitCode, RunError> {
+    let (_, capture) = capture.subcommand().expect("a capture target is required");
+    let url = capture.get_one::<String>("URL").unwrap();
+    let target = PipelineUrl::parse(url).ok_or_else(|| {
+        RunError::PipelineUrl {
+            url: url.clone(),
+        }
+    })?;
+    let path = capture.get_one::<String>("DIRECTORY").map_or_else(
+        || PathBuf::from(format!("pipeline-{}", target.pipeline)),
+        PathBuf::from,
+    );
+    // Captures are standalone; do not mix them into existing stores.
+    if fs::read_dir(&path).is_ok_and(|mut entries| entries.next().is_some()) {
+        return Err(RunError::CaptureExists {
+            path,
+        });
+    }
+
+    let gitlab = gitlab::GitlabBuilder::new(&target.host, token)
+        .build_async()
+        .await
+        .map_err(RunError::connect)?;
+    let project = target.project_id(&gitlab).await.map_err(|source| {
+        RunError::ResolveProject {
+            source,
+        }
+    })?;
+
+    let blobs = ctx.config.artifacts.blob_storage_at(&path.join("blobs"))?;
+    let forge = GitlabForge::with_aliases(
+        &target.host,
+        &ctx.config.instances.aliases(),
+        gitlab,
+        VecLookup::default(),
+    )
+    .with_artifact_extraction(ctx.config.artifacts.extraction_rules()?)
+    .with_blob_storage(blobs)
+    .with_job_logs();
+    let forge = Arc::new(forge);
+    let executor = CAPTURE_SKIPPED_TASKS
+        .iter()
+        .fold(ctx.config.tasks.executor()?, |executor, kind| {
+            executor.skip_task(*kind)
+        });
+    let canceller = executor.canceller();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            canceller.cancel();
+        }
+    });
+    let tasks = RefreshTarget::Pipeline {
+        project,
+        pipeline: target.pipeline,
+    }
+    .tasks();
+    let report = executor.run(forge.clone(), tasks).await;
+
+    let storage = Arc::into_inner(forge)
+        .expect("all tasks have completed")
+        .into_storage();
+    save_store(&path, &storage, ctx.signing_key.as_ref())?;
+    report.write(&path.join(REPORT_NAME))?;
+
+    let logs = ci_monitor_analysis::log_backfill(&storage);
+    if ctx.json {
+        let output = CaptureOutput {
+            instance: target.host,
+            project: target.project,
+            pipeline: target.pipeline,
+            path: path.display().to_string(),
+            logs,
+            report,
+        };
+        print_json(&output)?;
+        return Ok(exit::for_report(&output.report));
+    } else if !ctx.quiet {
+        print_summary(&report);
+        println!(
+            "captured {} pipeline #{} with {}/{} job logs into {}",
+            target.project,
+            target.pipeline,
+            logs.stored,
+            logs.jobs,
+            path.display(),
+        );
+    }
+
+    Ok(exit::for_report(&report))
+}
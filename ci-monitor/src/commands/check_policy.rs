@@ -0,0 +1,70 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::process::ExitCode;
+
+use ci_monitor_analysis::{PolicyViolationKind, ProjectPolicyViolations};
+use ci_monitor_persistence::{EntityType, VecLookup};
+use clap::Command;
+
+use crate::commands::{load_store_only, print_json, Context};
+use crate::exit::{self, RunError};
+
+/// The `check-policy` subcommand.
+pub fn command() -> Command {
+    Command::new("check-policy")
+        .about("Check stored pipelines against the configured required jobs")
+}
+
+/// Run the `check-policy` subcommand.
+pub fn run(ctx: &Context) -> Result<ExitCode, RunError> {
+    let storage = if let Some(path) = ctx.store_path.as_ref() {
+        load_store_only(
+            path,
+            &[EntityType::Pipeline, EntityType::Job, EntityType::Project],
+        )?
+    } else {
+        VecLookup::default()
+    };
+    let policy = ctx.config.policies.required_jobs()?;
+    let violations = ci_monitor_analysis::required_job_violations(&storage, &policy);
+
+    storage.check_deferred()?;
+
+    if ctx.json {
+        print_json(&violations)?;
+    } else if !ctx.quiet {
+        print_policy_violations(&violations);
+    }
+
+    Ok(if violations.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        exit::VIOLATIONS.into()
+    })
+}
+
+/// Print pipelines which violate policies.
+fn print_policy_violations(projects: &[ProjectPolicyViolations]) {
+    for project in projects {
+        println!("{} (#{}):", project.path, project.project);
+        for violation in &project.violations {
+            let problem = match violation.kind {
+                PolicyViolationKind::Missing => "is missing".into(),
+                PolicyViolationKind::NotPassed {
+                    state,
+                } => {
+                    format!("is {}", state.name())
+                },
+                _ => "violates the policy".into(),
+            };
+            println!(
+                "    pipeline #{} ({}): job '{}' {} ({})",
+                violation.pipeline, violation.refname, violation.job, problem, violation.url,
+            );
+        }
+    }
+}
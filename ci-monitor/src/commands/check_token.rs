@@ -0,0 +1,68 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeSet;
+use std::process::ExitCode;
+
+use ci_monitor_gitlab::gitlab::AsyncGitlab;
+use ci_monitor_gitlab::{TokenFeatures, TokenScopeReport};
+use clap::Command;
+
+use crate::commands::{print_json, Context};
+use crate::exit::{self, RunError};
+
+/// The `check-token` subcommand.
+pub fn command() -> Command {
+    Command::new("check-token").about("Compare the token's scopes against those required")
+}
+
+/// Run the `check-token` subcommand.
+///
+/// Compares the scopes of the token against those required.
+pub async fn run(ctx: &Context, gitlab: &AsyncGitlab) -> Result<ExitCode, RunError> {
+    let features = TokenFeatures::default()
+        .all_runners(ctx.config.admin)
+        .artifacts(ctx.config.artifacts.blobs.is_some());
+    let scopes = ci_monitor_gitlab::token_scopes(gitlab)
+        .await
+        .map_err(RunError::token)?;
+    let report = TokenScopeReport::new(scopes, features);
+
+    if ctx.json {
+        print_json(&report)?;
+    } else if !ctx.quiet {
+        print_token_report(&report);
+    }
+
+    Ok(if report.is_sufficient() {
+        ExitCode::SUCCESS
+    } else {
+        exit::AUTH.into()
+    })
+}
+
+/// Print a comparison of a token's scopes against those required.
+fn print_token_report(report: &TokenScopeReport) {
+    let join = |scopes: &BTreeSet<String>| scopes.iter().cloned().collect::<Vec<_>>().join(", ");
+
+    println!("granted scopes: {}", join(&report.granted));
+    println!("required scopes: {}", join(&report.required));
+    if !report.is_sufficient() {
+        println!("missing scopes: {}", join(&report.missing));
+    }
+    for scope in report.excess_write_scopes() {
+        println!(
+            "warning: the `{}` scope grants unneeded write access",
+            scope
+        );
+    }
+    if report.is_over_privileged() {
+        println!(
+            "recommended: create a token with only these scopes: {}",
+            join(&report.required),
+        );
+    }
+}
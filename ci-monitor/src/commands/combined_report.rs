@@ -0,0 +1,93 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::path::Path;
+use std::process::ExitCode;
+
+use ci_monitor_analysis::{CombinedReport, FailureRate, RunnerHealth};
+use ci_monitor_persistence::EntityType;
+use clap::{Arg, ArgMatches, Command};
+
+use crate::commands::{load_store_only, print_json, Context};
+use crate::exit::RunError;
+
+/// The `combined-report` subcommand.
+pub fn command() -> Command {
+    Command::new("combined-report")
+        .about("Report runner health and failure rates across several stores")
+        .arg(
+            Arg::new("STORES")
+                .help("Directories of the stores to combine")
+                .num_args(1..)
+                .required(true),
+        )
+}
+
+/// Run the `combined-report` subcommand.
+pub fn run(ctx: &Context, combined: &ArgMatches) -> Result<ExitCode, RunError> {
+    let paths = combined
+        .get_many::<String>("STORES")
+        .expect("stores are required")
+        .collect::<Vec<_>>();
+    let stores = paths
+        .iter()
+        .map(|path| {
+            load_store_only(
+                Path::new(path),
+                &[
+                    EntityType::Instance,
+                    EntityType::Project,
+                    EntityType::Pipeline,
+                    EntityType::Job,
+                    EntityType::Runner,
+                ],
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let report = ci_monitor_analysis::combined_report(
+        paths.iter().map(|path| path.as_str()).zip(stores.iter()),
+    );
+
+    for storage in &stores {
+        storage.check_deferred()?;
+    }
+
+    if ctx.json {
+        print_json(&report)?;
+    } else if !ctx.quiet {
+        print_combined_report(&report);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print a report combining several stores.
+fn print_combined_report(report: &CombinedReport) {
+    for store in &report.stores {
+        print_store_report(&store.name, &store.runners, &store.pipelines, &store.jobs);
+    }
+    print_store_report("total", &report.runners, &report.pipelines, &report.jobs);
+}
+
+/// Print runner health and failure rates.
+fn print_store_report(
+    name: &str,
+    runners: &RunnerHealth,
+    pipelines: &FailureRate,
+    jobs: &FailureRate,
+) {
+    println!(
+        "{}: {}/{} runners online ({} paused); {:.1}% of {} pipelines and {:.1}% of {} jobs failed",
+        name,
+        runners.online,
+        runners.total,
+        runners.paused,
+        100. * pipelines.rate(),
+        pipelines.finished,
+        100. * jobs.rate(),
+        jobs.finished,
+    );
+}
@@ -0,0 +1,80 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::process::ExitCode;
+
+use ci_monitor_analysis::{VariableChange, VariableComparisonError};
+use ci_monitor_core::data::Pipeline;
+use ci_monitor_persistence::{DiscoverableLookup, VecLookup};
+use clap::{value_parser, Arg, ArgMatches, Command};
+
+use crate::commands::{load_store, print_json, Context};
+use crate::exit::RunError;
+
+/// The `compare-variables` subcommand.
+pub fn command() -> Command {
+    Command::new("compare-variables")
+        .about("Compare the variables of two stored pipelines of the same project and ref")
+        .arg(
+            Arg::new("BEFORE")
+                .help("The ID of the first pipeline")
+                .value_parser(value_parser!(u64))
+                .required(true),
+        )
+        .arg(
+            Arg::new("AFTER")
+                .help("The ID of the second pipeline")
+                .value_parser(value_parser!(u64))
+                .required(true),
+        )
+}
+
+/// Run the `compare-variables` subcommand.
+pub fn run(ctx: &Context, compare: &ArgMatches) -> Result<ExitCode, RunError> {
+    let storage = if let Some(path) = ctx.store_path.as_ref() {
+        load_store(path)?
+    } else {
+        VecLookup::default()
+    };
+    let changes = compare_variables(&storage, compare)?;
+
+    if ctx.json {
+        print_json(&changes)?;
+    } else if !ctx.quiet {
+        print_variable_changes(&changes);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Compare the variables of two stored pipelines.
+fn compare_variables(
+    storage: &VecLookup,
+    matches: &ArgMatches,
+) -> Result<Vec<VariableChange>, VariableComparisonError> {
+    let find = |name| {
+        let id = *matches
+            .get_one::<u64>(name)
+            .expect("pipeline IDs are required");
+        <VecLookup as DiscoverableLookup<Pipeline<VecLookup>>>::find(storage, id).ok_or_else(|| {
+            VariableComparisonError::MissingPipeline {
+                pipeline: format!("#{}", id),
+            }
+        })
+    };
+
+    ci_monitor_analysis::compare_pipeline_variables(storage, &find("BEFORE")?, &find("AFTER")?)
+}
+
+/// Print the differences in variables between pipelines.
+fn print_variable_changes(changes: &[VariableChange]) {
+    if changes.is_empty() {
+        println!("no variable changes");
+    }
+    for change in changes {
+        println!("{}", change);
+    }
+}
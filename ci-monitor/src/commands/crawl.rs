@@ -0,0 +1,285 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use ci_monitor_core::data::Instance;
+use ci_monitor_core::Lookup;
+use ci_monitor_forge::{
+    FaultInjectingForge, Heartbeat, HeartbeatError, InstanceTask, RefreshTarget, RunReport,
+};
+use ci_monitor_gitlab::gitlab::AsyncGitlab;
+use ci_monitor_gitlab::GitlabForge;
+use ci_monitor_persistence::{VecIndex, VecLookup};
+use clap::{value_parser, Arg, ArgMatches, Command};
+
+use crate::commands::{
+    audit_entry, id_arg, load_store, print_json, print_summary, save_store, write_metrics, Context,
+    HEARTBEAT_NAME, REPORT_NAME,
+};
+use crate::entities;
+use crate::exit::{self, RunError};
+
+const PENDING_NAME: &str = "pending-tasks.jsonl";
+const MISSES_NAME: &str = "unknown-entities.jsonl";
+
+/// The name of the instance crawled by default.
+pub const GITLAB_INSTANCE: &str = "gitlab.kitware.com";
+
+/// The `refresh` subcommand.
+pub fn command() -> Command {
+    Command::new("refresh")
+        .about("Re-fetch a single entity and its children")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("project")
+                .about("Refresh a project")
+                .arg(project_arg()),
+        )
+        .subcommand(
+            Command::new("merge-request")
+                .about("Refresh a merge request and its pipelines")
+                .arg(project_arg())
+                .arg(id_arg("The ID of the merge request within the project")),
+        )
+        .subcommand(
+            Command::new("pipeline")
+                .about("Refresh a pipeline and its jobs")
+                .arg(project_arg())
+                .arg(id_arg("The ID of the pipeline")),
+        )
+        .subcommand(
+            Command::new("users")
+                .about("Refresh all stored users (e.g., to pick up renamed handles)"),
+        )
+}
+
+/// Crawl the forge (or refresh the entity requested on the command line).
+pub async fn run(ctx: &Context, gitlab: AsyncGitlab) -> Result<ExitCode, RunError> {
+    let runner_limits = ctx.config.runners.concurrency_limits()?;
+    let runner_hosts = ctx.config.runners.host_rules()?;
+    let runner_host_data = ctx.config.runners.host_data();
+
+    let storage = if let Some(path) = ctx.store_path.as_ref() {
+        load_store(path)?
+    } else {
+        VecLookup::default()
+    };
+    let mut forge = GitlabForge::with_aliases(
+        GITLAB_INSTANCE,
+        &ctx.config.instances.aliases(),
+        gitlab,
+        storage,
+    )
+    .with_artifact_extraction(ctx.config.artifacts.extraction_rules()?);
+    if let Some(blobs) = ctx.config.artifacts.blob_storage()? {
+        forge = forge.with_blob_storage(blobs);
+    }
+    if ctx.matches.get_flag("LOG_DIFFS") {
+        forge = forge.with_update_diffs();
+    }
+    if ctx.config.scan_pushes {
+        forge = forge.with_push_scanning();
+    }
+    let faults = ctx.config.faults.injection();
+    if faults.is_enabled() && !ctx.quiet {
+        eprintln!("warning: injecting faults into forge tasks");
+    }
+    let forge = Arc::new(FaultInjectingForge::new(forge, faults));
+
+    let refresh = ctx
+        .matches
+        .subcommand_matches("refresh")
+        .and_then(refresh_target);
+    let tasks = if let Some(target) = refresh {
+        target.tasks()
+    } else {
+        ctx.config.crawl_tasks()
+    };
+    let mut executor = ctx.config.tasks.executor()?.deduplicate(refresh.is_some());
+    if let (Some(path), Some(interval)) = (
+        ctx.store_path.as_ref(),
+        ctx.config.tasks.heartbeat_interval(),
+    ) {
+        executor = executor.heartbeat(path.join(HEARTBEAT_NAME), interval);
+    }
+    if let Some(&seconds) = ctx.matches.get_one::<u64>("MAX_DURATION") {
+        executor = executor.max_duration(Duration::from_secs(seconds));
+    }
+    if let Some(&requests) = ctx.matches.get_one::<u64>("MAX_API_CALLS") {
+        executor = executor.max_api_requests(requests);
+    }
+    // Refreshes are targeted, so tasks left behind by crawls are only picked up by crawls.
+    if let Some(path) = ctx.store_path.as_ref().filter(|_| refresh.is_none()) {
+        executor = executor
+            .pending_tasks(path.join(PENDING_NAME))
+            .warm_start(path.join(MISSES_NAME));
+    }
+    let canceller = executor.canceller();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            canceller.cancel();
+        }
+    });
+    // Scope the tasks to the instance so that its limits from `tasks.instances` apply.
+    let forges = [(GITLAB_INSTANCE.into(), forge.clone())]
+        .into_iter()
+        .collect();
+    let tasks = tasks
+        .into_iter()
+        .map(|task| InstanceTask::new(GITLAB_INSTANCE, task));
+    let mut report = executor.run_instances(forges, tasks).await;
+
+    let instance = forge.inner().instance_index();
+    let mut storage = Arc::into_inner(forge)
+        .expect("all tasks have completed")
+        .into_inner()
+        .into_storage();
+    entities::apply_runner_limits(&mut storage, &runner_limits);
+    entities::assign_runner_hosts(&mut storage, &runner_hosts);
+    entities::apply_runner_host_data(&mut storage, &runner_host_data);
+    let removed_projects = entities::projects_removed_from_groups(&storage, report.started_at);
+    entities::record_refreshed(&mut report, &storage);
+    let revision = entities::record_crawl_session(&mut storage, &instance, &report);
+    if let Some(target) = refresh {
+        let entry = audit_entry(&ctx.matches, "refresh", describe_refresh_target(target))
+            .tasks(
+                target
+                    .tasks()
+                    .iter()
+                    .map(|task| task.name().into())
+                    .collect(),
+            )
+            .affected(report.executed());
+        entities::record_audit_entry(&mut storage, entry);
+    }
+
+    let mut heartbeat = None;
+    if let Some(path) = ctx.store_path.as_ref() {
+        save_store(path, &storage, ctx.signing_key.as_ref())?;
+        if ctx.config.tasks.heartbeat.is_some() {
+            heartbeat = Some(update_heartbeat(
+                &path.join(HEARTBEAT_NAME),
+                &storage,
+                &instance,
+                &report,
+            )?);
+        }
+    }
+
+    let report_path = ctx
+        .matches
+        .get_one::<String>("REPORT")
+        .map(PathBuf::from)
+        .or_else(|| ctx.store_path.as_ref().map(|path| path.join(REPORT_NAME)));
+    if let Some(path) = report_path {
+        report.write(&path)?;
+    }
+    if let Some(path) = ctx.metrics_path.as_ref() {
+        let drift = ci_monitor_analysis::environment_drift(
+            &storage,
+            ctx.config.deployments.branch(),
+            Utc::now(),
+        );
+        write_metrics(path, &report, heartbeat.as_ref(), &drift)
+            .map_err(|err| RunError::metrics(path.clone(), err))?;
+    }
+
+    if ctx.json {
+        print_json(&report)?;
+    } else if !ctx.quiet {
+        print_summary(&report);
+        if removed_projects > 0 {
+            println!(
+                "{} projects are no longer in watched groups",
+                removed_projects
+            );
+        }
+        println!("store revision {}", revision);
+    }
+
+    Ok(exit::for_report(&report))
+}
+
+/// The entity to refresh from the command line.
+fn refresh_target(matches: &ArgMatches) -> Option<RefreshTarget> {
+    let (kind, matches) = matches.subcommand()?;
+    if kind == "users" {
+        return Some(RefreshTarget::Users);
+    }
+    let project = *matches.get_one::<u64>("PROJECT")?;
+    let target = match kind {
+        "project" => {
+            RefreshTarget::Project {
+                project,
+            }
+        },
+        "merge-request" => {
+            RefreshTarget::MergeRequest {
+                project,
+                merge_request: *matches.get_one::<u64>("ID")?,
+            }
+        },
+        "pipeline" => {
+            RefreshTarget::Pipeline {
+                project,
+                pipeline: *matches.get_one::<u64>("ID")?,
+            }
+        },
+        _ => return None,
+    };
+    Some(target)
+}
+
+/// A description of a refresh target for the audit log.
+fn describe_refresh_target(target: RefreshTarget) -> String {
+    match target {
+        RefreshTarget::Project {
+            project,
+        } => format!("project #{}", project),
+        RefreshTarget::MergeRequest {
+            project,
+            merge_request,
+        } => format!("merge request !{} of project #{}", merge_request, project),
+        RefreshTarget::Pipeline {
+            project,
+            pipeline,
+        } => format!("pipeline #{} of project #{}", pipeline, project),
+        RefreshTarget::Users => "users".into(),
+        _ => format!("{:?}", target),
+    }
+}
+
+fn project_arg() -> Arg {
+    Arg::new("PROJECT")
+        .help("The ID of the project")
+        .value_parser(value_parser!(u64))
+        .required(true)
+}
+
+/// Update the heartbeat in the store after a run.
+///
+/// Complete runs are recorded as the last complete crawl of the instance.
+fn update_heartbeat(
+    path: &Path,
+    storage: &VecLookup,
+    instance: &VecIndex<Instance>,
+    report: &RunReport,
+) -> Result<Heartbeat, HeartbeatError> {
+    let mut heartbeat =
+        Heartbeat::read(path)?.unwrap_or_else(|| Heartbeat::start(report.started_at));
+    if report.failed() == 0 && report.canceled == 0 && report.remaining == 0 {
+        if let Some(instance) = <VecLookup as Lookup<Instance>>::lookup(storage, instance) {
+            heartbeat.record_complete_crawl(instance.url.clone(), report.started_at);
+            heartbeat.write(path)?;
+        }
+    }
+    Ok(heartbeat)
+}
@@ -0,0 +1,69 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::net::SocketAddr;
+use std::process::ExitCode;
+
+use ci_monitor_persistence::VecStore;
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+
+use crate::commands::{load_store, Context, HEARTBEAT_NAME};
+use crate::dashboard;
+use crate::exit::RunError;
+
+/// The `dashboard` subcommand.
+pub fn command() -> Command {
+    Command::new("dashboard")
+        .about("Serve a dashboard for browsing the store")
+        .arg(
+            Arg::new("LISTEN")
+                .long("listen")
+                .help("Address to serve the dashboard on")
+                .value_parser(value_parser!(SocketAddr))
+                .default_value("127.0.0.1:8080")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("NAME")
+                .long("name")
+                .help("Name of the site (default: from the configuration)")
+                .action(ArgAction::Set),
+        )
+}
+
+/// Run the `dashboard` subcommand.
+pub async fn run(ctx: &Context, ui: &ArgMatches) -> Result<ExitCode, RunError> {
+    let path = ctx.store_path.as_ref().ok_or(RunError::NoStore)?;
+    // Read the generation first so that writes while loading are picked up by a reload.
+    let generation = VecStore::generation(path).ok();
+    let storage = load_store(path)?;
+    let name = ui
+        .get_one::<String>("NAME")
+        .or(ctx.config.federation.name.as_ref())
+        .cloned()
+        .unwrap_or_else(|| path.display().to_string());
+    let addr = *ui
+        .get_one::<SocketAddr>("LISTEN")
+        .expect("--listen has a default");
+
+    if !ctx.quiet {
+        eprintln!("serving the dashboard at http://{}", addr);
+    }
+    let branch = ctx.config.deployments.branch().map(Into::into);
+    dashboard::serve(
+        addr,
+        name,
+        path.clone(),
+        path.join(HEARTBEAT_NAME),
+        branch,
+        generation,
+        storage,
+    )
+    .await
+    .map_err(|err| RunError::dashboard(addr, err))?;
+
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,84 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::process::ExitCode;
+
+use chrono::Utc;
+use ci_monitor_analysis::EnvironmentDrift;
+use ci_monitor_persistence::{EntityType, VecLookup};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+
+use crate::commands::{load_store_only, print_json, Context};
+use crate::exit::RunError;
+
+/// The `drift` subcommand.
+pub fn command() -> Command {
+    Command::new("drift")
+        .about("Show how far each environment is behind a branch")
+        .arg(
+            Arg::new("BRANCH")
+                .long("branch")
+                .help(
+                    "Branch to compare environments against (default: from config or \
+                     the default branch of each project)",
+                )
+                .action(ArgAction::Set),
+        )
+}
+
+/// Run the `drift` subcommand.
+pub fn run(ctx: &Context, drift: &ArgMatches) -> Result<ExitCode, RunError> {
+    let storage = if let Some(path) = ctx.store_path.as_ref() {
+        load_store_only(
+            path,
+            &[
+                EntityType::Deployment,
+                EntityType::Environment,
+                EntityType::Pipeline,
+                EntityType::Project,
+            ],
+        )?
+    } else {
+        VecLookup::default()
+    };
+    let branch = drift
+        .get_one::<String>("BRANCH")
+        .map(String::as_str)
+        .or_else(|| ctx.config.deployments.branch());
+    let drift = ci_monitor_analysis::environment_drift(&storage, branch, Utc::now());
+
+    storage.check_deferred()?;
+
+    if ctx.json {
+        print_json(&drift)?;
+    } else if !ctx.quiet {
+        print_drift(&drift);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print how far environments are behind a branch.
+fn print_drift(drift: &[EnvironmentDrift]) {
+    for drift in drift {
+        println!(
+            "{} {} ({}): {} commit(s) behind {} for {}h",
+            drift.project,
+            drift.environment,
+            drift.tier.name(),
+            drift.commits_behind,
+            drift.branch,
+            drift.behind.num_hours(),
+        );
+        println!(
+            "    deployed: {} (deployment #{} at {})",
+            drift.deployed.sha, drift.deployed.deployment, drift.deployed.finished_at,
+        );
+        if let Some(head) = drift.head.as_ref() {
+            println!("    head: {}", head);
+        }
+    }
+}
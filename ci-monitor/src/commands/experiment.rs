@@ -0,0 +1,111 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::process::ExitCode;
+
+use chrono::Utc;
+use ci_monitor_analysis::{ExperimentOptions, ExperimentReport};
+use ci_monitor_persistence::{EntityType, VecLookup};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+
+use crate::commands::{load_store_only, print_json, Context};
+use crate::exit::RunError;
+
+/// The `experiment` subcommand.
+pub fn command() -> Command {
+    Command::new("experiment")
+        .about("Compare pipelines grouped by the value of a pipeline variable")
+        .arg(
+            Arg::new("VARIABLE")
+                .help("The variable which selects the experiment group")
+                .required(true),
+        )
+        .arg(
+            Arg::new("CONTROL")
+                .long("control")
+                .help("Value of the control group (default: pipelines without the variable)")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("PROJECT")
+                .long("project")
+                .help("Only consider pipelines of the project with this path")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("SINCE")
+                .long("since")
+                .help("Number of hours to look back for created pipelines")
+                .value_parser(value_parser!(u32))
+                .action(ArgAction::Set),
+        )
+}
+
+/// Run the `experiment` subcommand.
+pub fn run(ctx: &Context, experiment: &ArgMatches) -> Result<ExitCode, RunError> {
+    let storage = if let Some(path) = ctx.store_path.as_ref() {
+        load_store_only(path, &[EntityType::Pipeline, EntityType::Project])?
+    } else {
+        VecLookup::default()
+    };
+    let variable = experiment
+        .get_one::<String>("VARIABLE")
+        .expect("the variable is required");
+    let mut options = ExperimentOptions::default();
+    if let Some(control) = experiment.get_one::<String>("CONTROL") {
+        options = options.control(control);
+    }
+    if let Some(project) = experiment.get_one::<String>("PROJECT") {
+        options = options.project(project);
+    }
+    if let Some(hours) = experiment.get_one::<u32>("SINCE") {
+        options = options.since(Utc::now() - chrono::Duration::hours((*hours).into()));
+    }
+    let report = ci_monitor_analysis::pipeline_experiment(&storage, variable, &options);
+
+    storage.check_deferred()?;
+
+    if ctx.json {
+        print_json(&report)?;
+    } else if !ctx.quiet {
+        print_experiment(&report);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print a comparison of pipelines grouped by the value of a variable.
+fn print_experiment(report: &ExperimentReport) {
+    for group in &report.groups {
+        let value = match (group.value.as_ref(), group.redacted) {
+            (Some(value), true) => format!("<{}>", value),
+            (Some(value), false) => format!("{:?}", value),
+            (None, _) => "(unset)".into(),
+        };
+        println!(
+            "{}={}{}: {} pipelines, {:.1}% failed; median {}m, p90 {}m, max {}m",
+            report.variable,
+            value,
+            if group.control { " (control)" } else { "" },
+            group.pipelines,
+            group.failure_rate * 100.,
+            group.median.num_minutes(),
+            group.p90.num_minutes(),
+            group.max.num_minutes(),
+        );
+        if let (false, Some(median), Some(failure_rate)) = (
+            group.control,
+            group.median_change,
+            group.failure_rate_change,
+        ) {
+            println!(
+                "    vs. control: median {:+}m, failure rate {:+.1}%",
+                median.num_minutes(),
+                failure_rate * 100.,
+            );
+        }
+    }
+}
@@ -0,0 +1,70 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::path::Path;
+use std::process::ExitCode;
+
+use clap::{Arg, ArgMatches, Command};
+
+#[cfg(feature = "duckdb")]
+use crate::commands::load_store;
+use crate::commands::{print_json, Context};
+use crate::exit::RunError;
+use crate::output::ExportOutput;
+
+/// The `export` subcommand.
+pub fn command() -> Command {
+    Command::new("export")
+        .about("Export the store for use by other tools")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("duckdb")
+                .about(
+                    "Export the store into a DuckDB database for ad-hoc SQL queries \
+                     (requires the `duckdb` feature)",
+                )
+                .arg(
+                    Arg::new("FILE")
+                        .help("The database to write (replaced if it exists)")
+                        .required(true),
+                ),
+        )
+}
+
+/// Run the `export` subcommand.
+pub fn run(ctx: &Context, export: &ArgMatches) -> Result<ExitCode, RunError> {
+    let path = ctx.store_path.as_ref().ok_or(RunError::NoStore)?;
+    if let Some(duckdb) = export.subcommand_matches("duckdb") {
+        let file = duckdb
+            .get_one::<String>("FILE")
+            .expect("the file is required");
+        export_duckdb(path, Path::new(file))?;
+        if ctx.json {
+            let output = ExportOutput {
+                path: file.clone(),
+            };
+            print_json(&output)?;
+        } else if !ctx.quiet {
+            println!("exported the store to {}", file);
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Export a store into a DuckDB database.
+#[cfg(feature = "duckdb")]
+fn export_duckdb(path: &Path, file: &Path) -> Result<(), RunError> {
+    let storage = load_store(path)?;
+    ci_monitor_persistence::export_duckdb(&storage, file)?;
+    Ok(())
+}
+
+/// Export a store into a DuckDB database.
+#[cfg(not(feature = "duckdb"))]
+fn export_duckdb(_: &Path, _: &Path) -> Result<(), RunError> {
+    Err(RunError::NoDuckDb)
+}
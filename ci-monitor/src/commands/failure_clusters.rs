@@ -0,0 +1,87 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::process::ExitCode;
+
+use ci_monitor_analysis::{FailureClusters, LogClusterOptions};
+use ci_monitor_persistence::VecLookup;
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+
+use crate::commands::{load_store, print_json, Context};
+use crate::exit::RunError;
+
+/// The `failure-clusters` subcommand.
+pub fn command() -> Command {
+    Command::new("failure-clusters")
+        .about("Group the logs of failed jobs into distinct failure signatures")
+        .arg(
+            Arg::new("THRESHOLD")
+                .long("threshold")
+                .help("Similarity (between 0 and 1) required to group logs together")
+                .value_parser(value_parser!(f64))
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("TAIL")
+                .long("tail")
+                .help("Number of lines from the end of each log to compare")
+                .value_parser(value_parser!(usize))
+                .action(ArgAction::Set),
+        )
+}
+
+/// Run the `failure-clusters` subcommand.
+pub fn run(ctx: &Context, failures: &ArgMatches) -> Result<ExitCode, RunError> {
+    let storage = if let Some(path) = ctx.store_path.as_ref() {
+        load_store(path)?
+    } else {
+        VecLookup::default()
+    };
+    let blob_storage = ctx
+        .config
+        .artifacts
+        .blob_tiers()?
+        .ok_or(RunError::NoBlobStore)?;
+    let mut options = LogClusterOptions::default();
+    if let Some(&threshold) = failures.get_one::<f64>("THRESHOLD") {
+        options = options.threshold(threshold);
+    }
+    if let Some(&tail) = failures.get_one::<usize>("TAIL") {
+        options = options.tail_lines(tail);
+    }
+    let clusters = ci_monitor_analysis::failure_log_clusters(&storage, &blob_storage, options);
+
+    if ctx.json {
+        print_json(&clusters)?;
+    } else if !ctx.quiet {
+        print_failure_clusters(&clusters);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print clusters of failing job logs.
+fn print_failure_clusters(clusters: &FailureClusters) {
+    for cluster in &clusters.clusters {
+        println!(
+            "{} jobs like #{}: {}",
+            cluster.jobs.len(),
+            cluster.representative,
+            cluster
+                .jobs
+                .iter()
+                .map(|job| format!("#{}", job))
+                .collect::<Vec<_>>()
+                .join(" "),
+        );
+        for line in cluster.excerpt.lines() {
+            println!("    {}", line);
+        }
+    }
+    if !clusters.unreadable.is_empty() {
+        println!("{} logs could not be read", clusters.unreadable.len());
+    }
+}
@@ -0,0 +1,64 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::process::ExitCode;
+
+use clap::Command;
+
+use crate::commands::summary::print_site_summary;
+use crate::commands::{print_json, Context};
+use crate::exit::{self, RunError};
+use crate::federation;
+use crate::output::FederationReportOutput;
+
+/// The `federate` subcommand.
+pub fn command() -> Command {
+    Command::new("federate").about("Aggregate the summaries published by the configured sites")
+}
+
+/// Run the `federate` subcommand.
+pub async fn run(ctx: &Context) -> Result<ExitCode, RunError> {
+    let report = federation::federate(&ctx.config.federation).await?;
+
+    if ctx.json {
+        print_json(&report)?;
+    } else if !ctx.quiet {
+        print_federation_report(&report);
+    }
+
+    Ok(if report.unreachable() == 0 {
+        ExitCode::SUCCESS
+    } else {
+        exit::PARTIAL.into()
+    })
+}
+
+/// Print summaries aggregated across sites.
+fn print_federation_report(report: &FederationReportOutput) {
+    for site in &report.sites {
+        if let Some(summary) = site.summary.as_ref() {
+            print_site_summary(summary);
+            if site.stale {
+                println!("{}: stale since {}", site.name, summary.generated_at);
+            }
+        } else if let Some(error) = site.error.as_ref() {
+            println!("{}: unreachable: {}", site.name, error);
+        }
+    }
+    let entities = report.entities.values().sum::<usize>();
+    println!(
+        "total: {} entities; {}/{} runners online ({} paused); pipelines fail {:.1}% of {}; jobs \
+         fail {:.1}% of {}",
+        entities,
+        report.runners.online,
+        report.runners.total,
+        report.runners.paused,
+        100. * report.pipelines.rate(),
+        report.pipelines.finished,
+        100. * report.jobs.rate(),
+        report.jobs.finished,
+    );
+}
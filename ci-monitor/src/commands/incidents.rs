@@ -0,0 +1,120 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::process::ExitCode;
+
+use chrono::Utc;
+use ci_monitor_analysis::DeploymentIncident;
+use ci_monitor_persistence::{EntityType, VecLookup};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+
+use crate::commands::{load_store_only, print_json, Context};
+use crate::exit::RunError;
+
+/// The `incidents` subcommand.
+pub fn command() -> Command {
+    Command::new("incidents")
+        .about("Collect the context of recently failed deployments for on-call use")
+        .arg(
+            Arg::new("SINCE")
+                .long("since")
+                .help("Number of hours to look back for failed deployments")
+                .value_parser(value_parser!(u32))
+                .default_value("24")
+                .action(ArgAction::Set),
+        )
+}
+
+/// Run the `incidents` subcommand.
+pub fn run(ctx: &Context, incidents: &ArgMatches) -> Result<ExitCode, RunError> {
+    let storage = if let Some(path) = ctx.store_path.as_ref() {
+        load_store_only(
+            path,
+            &[
+                EntityType::Deployment,
+                EntityType::Environment,
+                EntityType::Job,
+                EntityType::MergeRequest,
+                EntityType::Pipeline,
+                EntityType::Project,
+                EntityType::User,
+            ],
+        )?
+    } else {
+        VecLookup::default()
+    };
+    let router = ctx.config.notifications.router()?;
+    let hours = *incidents
+        .get_one::<u32>("SINCE")
+        .expect("--since has a default");
+    let since = Utc::now() - chrono::Duration::hours(hours.into());
+    let incidents = ci_monitor_analysis::deployment_incidents(&storage, &router, since);
+
+    storage.check_deferred()?;
+
+    if ctx.json {
+        print_json(&incidents)?;
+    } else if !ctx.quiet {
+        print_incidents(&incidents);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print the context of failed deployments.
+fn print_incidents(incidents: &[DeploymentIncident]) {
+    for incident in incidents {
+        println!(
+            "{} deployment #{} into {} ({}) failed at {}",
+            incident.project,
+            incident.deployment,
+            incident.environment,
+            incident.tier.name(),
+            incident.failed_at,
+        );
+        if !incident.environment_url.is_empty() {
+            println!("    environment: {}", incident.environment_url);
+        }
+        if let Some(last_success) = incident.last_success.as_ref() {
+            println!(
+                "    last success: {} (deployment #{} at {})",
+                last_success.sha, last_success.deployment, last_success.finished_at,
+            );
+        }
+        println!(
+            "    pipeline: {} ({} on {})",
+            incident.pipeline_url,
+            incident.sha,
+            incident.refname.as_deref().unwrap_or("<unknown>"),
+        );
+        if let Some(mr) = incident.merge_request.as_ref() {
+            println!(
+                "    merge request: {} {}",
+                mr,
+                incident.merge_request_title.as_deref().unwrap_or_default(),
+            );
+        }
+        if let Some(author) = incident.author.as_ref() {
+            println!("    author: {}", author);
+        }
+        if !incident.failed_jobs.is_empty() {
+            println!("    failed jobs: {}", incident.failed_jobs.join(", "));
+        }
+        for route in &incident.routes {
+            let owner = route
+                .owner
+                .as_ref()
+                .map_or_else(String::new, |owner| format!(" ({})", owner));
+            println!(
+                "    notify {}{} {} at {}",
+                route.channel,
+                owner,
+                route.kind.name(),
+                route.deliver_at,
+            );
+        }
+    }
+}
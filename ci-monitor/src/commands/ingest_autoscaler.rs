@@ -0,0 +1,87 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use ci_monitor_forge::AutoscalerLogFormat;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+
+use crate::commands::{load_store, print_json, save_store, Context};
+use crate::entities;
+use crate::exit::RunError;
+use crate::output::AutoscalerIngestOutput;
+
+/// The `ingest-autoscaler` subcommand.
+pub fn command() -> Command {
+    Command::new("ingest-autoscaler")
+        .about("Record autoscaled instances (e.g., VMs or pods) of a runner host")
+        .arg(
+            Arg::new("HOST")
+                .long("host")
+                .help("Name of the runner host which spins up the instances")
+                .required(true)
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("FORMAT")
+                .long("format")
+                .help("Format of the events")
+                .value_parser(["events", "runner-log"])
+                .default_value("runner-log")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("FILE")
+                .help("Path to the events (JSON lines or a GitLab Runner JSON log)")
+                .required(true),
+        )
+}
+
+/// Run the `ingest-autoscaler` subcommand.
+pub fn run(ctx: &Context, ingest: &ArgMatches) -> Result<ExitCode, RunError> {
+    let path = ctx.store_path.as_ref().ok_or(RunError::NoStore)?;
+    let host = ingest
+        .get_one::<String>("HOST")
+        .expect("the host is required");
+    let format = ingest
+        .get_one::<String>("FORMAT")
+        .map(String::as_str)
+        .and_then(AutoscalerLogFormat::from_name)
+        .expect("--format has a default");
+    let file = PathBuf::from(
+        ingest
+            .get_one::<String>("FILE")
+            .expect("the file is required"),
+    );
+    let contents = fs::read_to_string(&file).map_err(|err| RunError::read(file, err))?;
+    let events = ci_monitor_forge::parse_autoscaler_events(&contents, format)?;
+
+    let mut storage = load_store(path)?;
+    let recorded = entities::record_autoscaler_events(&mut storage, host, &events);
+    if recorded > 0 {
+        save_store(path, &storage, ctx.signing_key.as_ref())?;
+    }
+
+    if ctx.json {
+        let output = AutoscalerIngestOutput {
+            host: host.clone(),
+            events: events.len(),
+            recorded,
+        };
+        print_json(&output)?;
+    } else if !ctx.quiet {
+        println!(
+            "recorded {} of {} autoscaler events for {}",
+            recorded,
+            events.len(),
+            host,
+        );
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,87 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::process::ExitCode;
+
+use ci_monitor_gitlab::{NotificationError, PipelineNotification};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+
+use crate::commands::crawl::GITLAB_INSTANCE;
+use crate::commands::{load_store, print_json, save_store, Context};
+use crate::exit::RunError;
+use crate::output::EmailIngestOutput;
+
+/// The `ingest-email` subcommand.
+pub fn command() -> Command {
+    Command::new("ingest-email")
+        .about("Record pipelines from GitLab pipeline notification emails")
+        .arg(
+            Arg::new("INSTANCE")
+                .long("instance")
+                .help("Host of the instance which sent the emails")
+                .default_value(GITLAB_INSTANCE)
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("PATH")
+                .help("An email file, a directory of `.eml` files, or a maildir")
+                .required(true),
+        )
+}
+
+/// Run the `ingest-email` subcommand.
+pub fn run(ctx: &Context, ingest: &ArgMatches) -> Result<ExitCode, RunError> {
+    let path = ctx.store_path.as_ref().ok_or(RunError::NoStore)?;
+    let instance = ingest
+        .get_one::<String>("INSTANCE")
+        .expect("--instance has a default");
+    let emails = ingest
+        .get_one::<String>("PATH")
+        .expect("the path is required");
+    let emails = ci_monitor_gitlab::find_notification_emails(emails)?;
+
+    let mut storage = load_store(path)?;
+    let aliases = ctx.config.instances.aliases();
+    let mut ingested = 0;
+    for email in &emails {
+        let notification = match PipelineNotification::read(email) {
+            Ok(notification) => notification,
+            // Other emails may be mixed in with the notifications.
+            Err(NotificationError::NotAPipelineNotification {
+                ..
+            }) => continue,
+            Err(err) => return Err(err.into()),
+        };
+        ci_monitor_gitlab::ingest_pipeline_notification(
+            &mut storage,
+            instance,
+            &aliases,
+            notification,
+        )?;
+        ingested += 1;
+    }
+    if ingested > 0 {
+        save_store(path, &storage, ctx.signing_key.as_ref())?;
+    }
+
+    if ctx.json {
+        let output = EmailIngestOutput {
+            instance: instance.clone(),
+            emails: emails.len(),
+            ingested,
+        };
+        print_json(&output)?;
+    } else if !ctx.quiet {
+        println!(
+            "ingested {} of {} emails as pipeline notifications from {}",
+            ingested,
+            emails.len(),
+            instance,
+        );
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,78 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::process::ExitCode;
+
+use chrono::Utc;
+use ci_monitor_analysis::MergeRequestLatency;
+use ci_monitor_persistence::{EntityType, VecLookup};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+
+use crate::commands::{load_store_only, print_json, Context};
+use crate::exit::RunError;
+
+/// The `merge-latency` subcommand.
+pub fn command() -> Command {
+    Command::new("merge-latency")
+        .about("Break down the time to merge of merge requests into CI and human time")
+        .arg(
+            Arg::new("SINCE")
+                .long("since")
+                .help("Number of hours to look back for merged merge requests")
+                .value_parser(value_parser!(u32))
+                .action(ArgAction::Set),
+        )
+}
+
+/// Run the `merge-latency` subcommand.
+pub fn run(ctx: &Context, latency: &ArgMatches) -> Result<ExitCode, RunError> {
+    let storage = if let Some(path) = ctx.store_path.as_ref() {
+        load_store_only(
+            path,
+            &[
+                EntityType::MergeRequest,
+                EntityType::Pipeline,
+                EntityType::Project,
+            ],
+        )?
+    } else {
+        VecLookup::default()
+    };
+    let since = latency
+        .get_one::<u32>("SINCE")
+        .map(|hours| Utc::now() - chrono::Duration::hours((*hours).into()));
+    let latencies = ci_monitor_analysis::merge_request_latencies(&storage, since);
+
+    storage.check_deferred()?;
+
+    if ctx.json {
+        print_json(&latencies)?;
+    } else if !ctx.quiet {
+        print_merge_latencies(&latencies);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print how long merged merge requests spent waiting on CI and on people.
+fn print_merge_latencies(latencies: &[MergeRequestLatency]) {
+    let minutes = |duration: Option<chrono::Duration>| {
+        duration.map_or_else(|| "-".into(), |d| format!("{}m", d.num_minutes()))
+    };
+
+    for latency in latencies {
+        println!(
+            "{} ({}): merged after {}m; first review {}, approval {}; {}m CI, {}m human",
+            latency.url,
+            latency.title,
+            latency.time_to_merge().num_minutes(),
+            minutes(latency.first_review),
+            minutes(latency.approval),
+            latency.ci_time.num_minutes(),
+            latency.human_time().num_minutes(),
+        );
+    }
+}
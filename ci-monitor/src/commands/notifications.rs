@@ -0,0 +1,88 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::process::ExitCode;
+
+use chrono::Utc;
+use ci_monitor_analysis::{FailureNotification, RouteKind};
+use ci_monitor_persistence::{EntityType, VecLookup};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+
+use crate::commands::{load_store_only, print_json, Context};
+use crate::exit::RunError;
+
+/// The `notifications` subcommand.
+pub fn command() -> Command {
+    Command::new("notifications")
+        .about("Route notifications for recently failed pipelines to their owners")
+        .arg(
+            Arg::new("SINCE")
+                .long("since")
+                .help("Number of hours to look back for failed pipelines")
+                .value_parser(value_parser!(u32))
+                .default_value("24")
+                .action(ArgAction::Set),
+        )
+}
+
+/// Run the `notifications` subcommand.
+pub fn run(ctx: &Context, notifications: &ArgMatches) -> Result<ExitCode, RunError> {
+    let storage = if let Some(path) = ctx.store_path.as_ref() {
+        load_store_only(
+            path,
+            &[EntityType::Pipeline, EntityType::Job, EntityType::Project],
+        )?
+    } else {
+        VecLookup::default()
+    };
+    let router = ctx
+        .config
+        .notifications
+        .router()?
+        .quarantine(ctx.config.quarantine()?);
+    let hours = *notifications
+        .get_one::<u32>("SINCE")
+        .expect("--since has a default");
+    let since = Utc::now() - chrono::Duration::hours(hours.into());
+    let notifications = ci_monitor_analysis::failure_notifications(&storage, &router, since);
+
+    storage.check_deferred()?;
+
+    if ctx.json {
+        print_json(&notifications)?;
+    } else if !ctx.quiet {
+        print_notifications(&notifications);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print where notifications about failed pipelines are delivered.
+fn print_notifications(notifications: &[FailureNotification]) {
+    for notification in notifications {
+        println!(
+            "{} pipeline #{} failed at {}: {}",
+            notification.project,
+            notification.pipeline,
+            notification.failed_at,
+            notification.jobs.join(", "),
+        );
+        for route in &notification.routes {
+            let owner = route
+                .owner
+                .as_ref()
+                .map_or_else(String::new, |owner| format!(" ({})", owner));
+            match route.kind {
+                RouteKind::Deferred => {
+                    println!("    {}{} at {}", route.channel, owner, route.deliver_at);
+                },
+                kind => {
+                    println!("    {}{} {}", route.channel, owner, kind.name())
+                },
+            }
+        }
+    }
+}
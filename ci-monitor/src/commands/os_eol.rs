@@ -0,0 +1,90 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::process::ExitCode;
+
+use chrono::Utc;
+use ci_monitor_analysis::EndOfLifeHost;
+use ci_monitor_persistence::{EntityType, VecLookup};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+
+use crate::commands::{load_store_only, print_json, Context};
+use crate::exit::RunError;
+
+/// The `os-eol` subcommand.
+pub fn command() -> Command {
+    Command::new("os-eol")
+        .about("Report runner hosts running operating systems at or near their end of life")
+        .arg(
+            Arg::new("SINCE")
+                .long("since")
+                .help("Number of hours to look back for jobs")
+                .value_parser(value_parser!(u32))
+                .default_value("168")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("WARNING")
+                .long("warning")
+                .help("Number of days before the end of life to start reporting hosts")
+                .value_parser(value_parser!(u32))
+                .default_value("90")
+                .action(ArgAction::Set),
+        )
+}
+
+/// Run the `os-eol` subcommand.
+pub fn run(ctx: &Context, eol: &ArgMatches) -> Result<ExitCode, RunError> {
+    let catalog = ctx.config.runners.os_catalog();
+    let storage = if let Some(path) = ctx.store_path.as_ref() {
+        load_store_only(
+            path,
+            &[EntityType::Runner, EntityType::RunnerHost, EntityType::Job],
+        )?
+    } else {
+        VecLookup::default()
+    };
+    let hours = *eol.get_one::<u32>("SINCE").expect("--since has a default");
+    let days = *eol
+        .get_one::<u32>("WARNING")
+        .expect("--warning has a default");
+    let now = Utc::now();
+    let since = now - chrono::Duration::hours(hours.into());
+    let warning = chrono::Duration::days(days.into());
+    let hosts = ci_monitor_analysis::end_of_life_hosts(&storage, &catalog, now, warning, since);
+
+    storage.check_deferred()?;
+
+    if ctx.json {
+        print_json(&hosts)?;
+    } else if !ctx.quiet {
+        print_end_of_life_hosts(&hosts);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print runner hosts running end-of-life operating systems.
+fn print_end_of_life_hosts(hosts: &[EndOfLifeHost]) {
+    for host in hosts {
+        let state = if host.expired {
+            "reached end of life"
+        } else {
+            "reaches end of life"
+        };
+        println!(
+            "{} ({} {}) {} at {}: {} runners; {} jobs, {}h machine time",
+            host.host,
+            host.os,
+            host.os_version,
+            state,
+            host.end_of_life,
+            host.runners,
+            host.jobs,
+            host.machine_time.num_hours(),
+        );
+    }
+}
@@ -0,0 +1,80 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::process::ExitCode;
+
+use chrono::Utc;
+use ci_monitor_analysis::PlatformCell;
+use ci_monitor_persistence::{EntityType, VecLookup};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+
+use crate::commands::{load_store_only, print_json, Context};
+use crate::exit::RunError;
+
+/// The `platform-matrix` subcommand.
+pub fn command() -> Command {
+    Command::new("platform-matrix")
+        .about("Show the runner fleet and its job volume by OS, architecture, and version")
+        .arg(
+            Arg::new("SINCE")
+                .long("since")
+                .help("Number of hours to look back for jobs")
+                .value_parser(value_parser!(u32))
+                .default_value("168")
+                .action(ArgAction::Set),
+        )
+}
+
+/// Run the `platform-matrix` subcommand.
+pub fn run(ctx: &Context, platforms: &ArgMatches) -> Result<ExitCode, RunError> {
+    let storage = if let Some(path) = ctx.store_path.as_ref() {
+        load_store_only(
+            path,
+            &[EntityType::Runner, EntityType::RunnerHost, EntityType::Job],
+        )?
+    } else {
+        VecLookup::default()
+    };
+    let hours = *platforms
+        .get_one::<u32>("SINCE")
+        .expect("--since has a default");
+    let since = Utc::now() - chrono::Duration::hours(hours.into());
+    let matrix = ci_monitor_analysis::platform_matrix(&storage, since);
+
+    storage.check_deferred()?;
+
+    if ctx.json {
+        print_json(&matrix)?;
+    } else if !ctx.quiet {
+        print_platform_matrix(&matrix);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print the runner fleet by platform.
+fn print_platform_matrix(matrix: &[PlatformCell]) {
+    for cell in matrix {
+        let version = if cell.platform.os_version.is_empty() {
+            "?"
+        } else {
+            &cell.platform.os_version
+        };
+        let idle = if cell.is_idle() { " (idle)" } else { "" };
+        println!(
+            "{} {} {}: {}/{} runners online; {} jobs, {}h machine time, peak {} at once{}",
+            cell.platform.os,
+            cell.platform.architecture,
+            version,
+            cell.online,
+            cell.runners,
+            cell.jobs,
+            cell.machine_time.num_hours(),
+            cell.peak_concurrency,
+            idle,
+        );
+    }
+}
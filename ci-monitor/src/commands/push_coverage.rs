@@ -0,0 +1,80 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::process::ExitCode;
+
+use chrono::Utc;
+use ci_monitor_analysis::PushCoverage;
+use ci_monitor_persistence::{EntityType, VecLookup};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+
+use crate::commands::{load_store_only, print_json, Context};
+use crate::exit::RunError;
+
+/// The `push-coverage` subcommand.
+pub fn command() -> Command {
+    Command::new("push-coverage")
+        .about("Report pushes which did not produce a pipeline")
+        .arg(
+            Arg::new("SINCE")
+                .long("since")
+                .help("Number of hours to look back for pushes")
+                .value_parser(value_parser!(u32))
+                .action(ArgAction::Set),
+        )
+}
+
+/// Run the `push-coverage` subcommand.
+pub fn run(ctx: &Context, coverage: &ArgMatches) -> Result<ExitCode, RunError> {
+    let storage = if let Some(path) = ctx.store_path.as_ref() {
+        load_store_only(
+            path,
+            &[
+                EntityType::Push,
+                EntityType::Pipeline,
+                EntityType::Project,
+                EntityType::User,
+            ],
+        )?
+    } else {
+        VecLookup::default()
+    };
+    let since = coverage
+        .get_one::<u32>("SINCE")
+        .map(|hours| Utc::now() - chrono::Duration::hours((*hours).into()));
+    let report = ci_monitor_analysis::push_coverage(&storage, since);
+
+    storage.check_deferred()?;
+
+    if ctx.json {
+        print_json(&report)?;
+    } else if !ctx.quiet {
+        print_push_coverage(&report);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print pushes which did not produce a pipeline.
+fn print_push_coverage(report: &PushCoverage) {
+    for project in &report.projects {
+        println!(
+            "{}: {}/{} pushes built",
+            project.project, project.built, project.pushes,
+        );
+    }
+    for push in &report.unbuilt {
+        println!(
+            "{} {} ({}) pushed at {} by {}: no pipeline ({})",
+            push.project,
+            push.refname,
+            push.title,
+            push.pushed_at,
+            push.author.as_deref().unwrap_or("unknown"),
+            push.reason.name(),
+        );
+    }
+}
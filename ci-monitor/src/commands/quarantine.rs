@@ -0,0 +1,68 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::process::ExitCode;
+
+use chrono::Utc;
+use ci_monitor_analysis::QuarantineStatus;
+use ci_monitor_persistence::{EntityType, VecLookup};
+use clap::Command;
+
+use crate::commands::{load_store_only, print_json, Context};
+use crate::exit::RunError;
+
+/// The `quarantine` subcommand.
+pub fn command() -> Command {
+    Command::new("quarantine")
+        .about("Report how long jobs have been quarantined and the failures suppressed")
+}
+
+/// Run the `quarantine` subcommand.
+pub fn run(ctx: &Context) -> Result<ExitCode, RunError> {
+    let quarantine = ctx.config.quarantine()?;
+    let storage = if let Some(path) = ctx.store_path.as_ref() {
+        load_store_only(
+            path,
+            &[EntityType::Project, EntityType::Pipeline, EntityType::Job],
+        )?
+    } else {
+        VecLookup::default()
+    };
+    let now = Utc::now();
+    let statuses = ci_monitor_analysis::quarantine_report(&storage, &quarantine, now);
+
+    storage.check_deferred()?;
+
+    if ctx.json {
+        print_json(&statuses)?;
+    } else if !ctx.quiet {
+        print_quarantine(&statuses);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print how long jobs have been quarantined.
+fn print_quarantine(statuses: &[QuarantineStatus]) {
+    for status in statuses {
+        let state = if status.expired {
+            format!("expired {}", status.end)
+        } else {
+            format!("until {}", status.end)
+        };
+        println!(
+            "{} {}: quarantined for {} days ({}); {} failures suppressed",
+            status.projects,
+            status.job,
+            status.quarantined_for.num_days(),
+            state,
+            status.suppressed_failures,
+        );
+        if let Some(reason) = status.reason.as_ref() {
+            println!("    {}", reason);
+        }
+    }
+}
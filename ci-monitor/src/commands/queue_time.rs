@@ -0,0 +1,74 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::process::ExitCode;
+
+use chrono::Utc;
+use ci_monitor_analysis::QueueTimeBreakdown;
+use ci_monitor_persistence::{EntityType, VecLookup};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+
+use crate::commands::{load_store_only, print_json, Context};
+use crate::exit::RunError;
+
+/// The `queue-time` subcommand.
+pub fn command() -> Command {
+    Command::new("queue-time")
+        .about("Split job queue time into waiting for capacity and runner startup per tag")
+        .arg(
+            Arg::new("SINCE")
+                .long("since")
+                .help("Number of hours to look back for jobs")
+                .value_parser(value_parser!(u32))
+                .default_value("168")
+                .action(ArgAction::Set),
+        )
+}
+
+/// Run the `queue-time` subcommand.
+pub fn run(ctx: &Context, queue_time: &ArgMatches) -> Result<ExitCode, RunError> {
+    let storage = if let Some(path) = ctx.store_path.as_ref() {
+        load_store_only(path, &[EntityType::Job, EntityType::Runner])?
+    } else {
+        VecLookup::default()
+    };
+    let hours = *queue_time
+        .get_one::<u32>("SINCE")
+        .expect("--since has a default");
+    let since = Utc::now() - chrono::Duration::hours(hours.into());
+    let breakdowns = ci_monitor_analysis::queue_time_breakdown(&storage, since);
+
+    storage.check_deferred()?;
+
+    if ctx.json {
+        print_json(&breakdowns)?;
+    } else if !ctx.quiet {
+        print_queue_time_breakdowns(&breakdowns);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print how the queue time of jobs splits between waiting for capacity and runner startup.
+fn print_queue_time_breakdowns(breakdowns: &[QueueTimeBreakdown]) {
+    for breakdown in breakdowns {
+        let tag = if breakdown.tag.is_empty() {
+            "(untagged)"
+        } else {
+            breakdown.tag.as_str()
+        };
+        println!(
+            "{}: {} jobs, waiting {:.0}s (median {:.0}s), starting {:.0}s (median {:.0}s), {:.0}% starting",
+            tag,
+            breakdown.jobs,
+            breakdown.waiting,
+            breakdown.median_waiting,
+            breakdown.starting,
+            breakdown.median_starting,
+            breakdown.starting_fraction() * 100.,
+        );
+    }
+}
@@ -0,0 +1,130 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+
+use ci_monitor_analysis::PipelineDivergence;
+use ci_monitor_forge::ForgeTask;
+use ci_monitor_gitlab::gitlab::AsyncGitlab;
+use ci_monitor_gitlab::GitlabForge;
+use clap::{value_parser, Arg, ArgMatches, Command};
+
+use crate::commands::{
+    audit_entry, load_store, print_json, print_summary, save_store, Context, HEARTBEAT_NAME,
+};
+use crate::entities;
+use crate::exit::{self, RunError};
+use crate::output::ReconciliationOutput;
+
+/// The `reconcile-pipelines` subcommand.
+pub fn command() -> Command {
+    Command::new("reconcile-pipelines")
+        .about("Check a sample of stored pipelines against the forge and mark removed ones")
+        .arg(
+            Arg::new("SAMPLE")
+                .long("sample")
+                .help("Number of pipelines to check in each project")
+                .value_parser(value_parser!(usize))
+                .default_value("10"),
+        )
+}
+
+/// Run the `reconcile-pipelines` subcommand.
+pub async fn run(
+    ctx: &Context,
+    reconcile: &ArgMatches,
+    gitlab: AsyncGitlab,
+) -> Result<ExitCode, RunError> {
+    let path = ctx.store_path.as_ref().ok_or(RunError::NoStore)?;
+    let storage = load_store(path)?;
+
+    let sample = reconcile
+        .get_one::<usize>("SAMPLE")
+        .copied()
+        .expect("the sample size has a default");
+    let samples = ci_monitor_analysis::pipeline_reconciliation_sample(&storage, sample);
+    let tasks = samples
+        .iter()
+        .map(|sample| {
+            ForgeTask::ReconcilePipeline {
+                project: sample.project,
+                pipeline: sample.pipeline,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let forge = GitlabForge::with_aliases(
+        "gitlab.kitware.com",
+        &ctx.config.instances.aliases(),
+        gitlab,
+        storage,
+    );
+    let forge = Arc::new(forge);
+    let mut executor = ctx.config.tasks.executor()?;
+    if let Some(interval) = ctx.config.tasks.heartbeat_interval() {
+        executor = executor.heartbeat(path.join(HEARTBEAT_NAME), interval);
+    }
+    if let Some(&seconds) = ctx.matches.get_one::<u64>("MAX_DURATION") {
+        executor = executor.max_duration(Duration::from_secs(seconds));
+    }
+    if let Some(&requests) = ctx.matches.get_one::<u64>("MAX_API_CALLS") {
+        executor = executor.max_api_requests(requests);
+    }
+    let canceller = executor.canceller();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            canceller.cancel();
+        }
+    });
+    let report = executor.run(forge.clone(), tasks).await;
+
+    // Checked pipelines are saved even if the run was interrupted; unchecked pipelines are
+    // preferred by the next sample.
+    let mut storage = Arc::into_inner(forge)
+        .expect("all tasks have completed")
+        .into_storage();
+    let removed = entities::cascade_pipeline_deletions(&mut storage, report.started_at);
+    if removed > 0 {
+        let target = format!("{} sampled pipelines", samples.len());
+        let entry =
+            audit_entry(&ctx.matches, "reconcile-pipelines", target).affected(removed as u64);
+        entities::record_audit_entry(&mut storage, entry);
+    }
+    save_store(path, &storage, ctx.signing_key.as_ref())?;
+
+    let divergences =
+        ci_monitor_analysis::pipeline_divergence(&storage, &samples, report.started_at);
+    if ctx.json {
+        let output = ReconciliationOutput {
+            projects: divergences,
+            report,
+        };
+        print_json(&output)?;
+        return Ok(exit::for_report(&output.report));
+    } else if !ctx.quiet {
+        print_summary(&report);
+        print_pipeline_divergence(&divergences);
+    }
+
+    Ok(exit::for_report(&report))
+}
+
+/// Print how far the stored pipelines of each project have diverged from the forge.
+fn print_pipeline_divergence(divergences: &[PipelineDivergence]) {
+    for divergence in divergences {
+        println!(
+            "{}: {}/{} checked pipelines diverged ({:.1}%): {} removed, {} stale",
+            divergence.project,
+            divergence.removed + divergence.stale,
+            divergence.checked,
+            100. * divergence.rate(),
+            divergence.removed,
+            divergence.stale,
+        );
+    }
+}
@@ -0,0 +1,68 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::process::ExitCode;
+
+use ci_monitor_analysis::ProjectReleases;
+use ci_monitor_persistence::VecLookup;
+use clap::Command;
+
+use crate::commands::{load_store, print_json, Context};
+use crate::exit::RunError;
+
+/// The `releases` subcommand.
+pub fn command() -> Command {
+    Command::new("releases")
+        .about("Show the release cadence of projects and their pipelines at release time")
+}
+
+/// Run the `releases` subcommand.
+pub fn run(ctx: &Context) -> Result<ExitCode, RunError> {
+    let storage = if let Some(path) = ctx.store_path.as_ref() {
+        load_store(path)?
+    } else {
+        VecLookup::default()
+    };
+    let releases = ci_monitor_analysis::release_history(&storage);
+
+    if ctx.json {
+        print_json(&releases)?;
+    } else if !ctx.quiet {
+        print_releases(&releases);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print the releases of each project.
+fn print_releases(projects: &[ProjectReleases]) {
+    for project in projects {
+        let cadence = project
+            .mean_interval()
+            .map_or_else(String::new, |interval| {
+                format!(", every {:.1} days", interval.num_hours() as f64 / 24.)
+            });
+        println!(
+            "{} (#{}): {} releases{}",
+            project.path,
+            project.project,
+            project.releases.len(),
+            cadence,
+        );
+        for release in &project.releases {
+            let pipeline = release
+                .pipeline
+                .map_or_else(String::new, |pipeline| format!("pipeline #{} ", pipeline));
+            println!(
+                "    {} ({}): {}{}",
+                release.tag,
+                release.released_at,
+                pipeline,
+                release.pipeline_state.name(),
+            );
+        }
+    }
+}
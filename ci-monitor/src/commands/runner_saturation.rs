@@ -0,0 +1,72 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::process::ExitCode;
+
+use ci_monitor_analysis::RunnerSaturation;
+use ci_monitor_persistence::VecLookup;
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+
+use crate::commands::{load_store, print_json, Context};
+use crate::entities;
+use crate::exit::RunError;
+
+/// The `runner-saturation` subcommand.
+pub fn command() -> Command {
+    Command::new("runner-saturation")
+        .about("Report how busy runners are relative to their concurrency limits")
+        .arg(
+            Arg::new("WINDOW")
+                .long("window")
+                .help("Length of each sampling window (in hours)")
+                .value_parser(value_parser!(u64).range(1..))
+                .default_value("24")
+                .action(ArgAction::Set),
+        )
+}
+
+/// Run the `runner-saturation` subcommand.
+pub fn run(ctx: &Context, saturation: &ArgMatches) -> Result<ExitCode, RunError> {
+    let runner_limits = ctx.config.runners.concurrency_limits()?;
+    let mut storage = if let Some(path) = ctx.store_path.as_ref() {
+        load_store(path)?
+    } else {
+        VecLookup::default()
+    };
+    entities::apply_runner_limits(&mut storage, &runner_limits);
+    let hours = *saturation
+        .get_one::<u64>("WINDOW")
+        .expect("the window has a default");
+    let window = chrono::Duration::hours(hours as i64);
+    let saturation = ci_monitor_analysis::runner_saturation(&storage, window);
+
+    if ctx.json {
+        print_json(&saturation)?;
+    } else if !ctx.quiet {
+        print_runner_saturation(&saturation);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print the saturation of runners.
+fn print_runner_saturation(saturation: &[RunnerSaturation]) {
+    if saturation.is_empty() {
+        println!("no runners with a known concurrency limit");
+    }
+    for runner in saturation {
+        println!(
+            "runner #{} ({}): {} jobs, peak {}/{} ({:.0}%), mean {:.0}%",
+            runner.runner,
+            runner.description,
+            runner.jobs,
+            runner.peak_concurrency,
+            runner.max_concurrent_jobs,
+            runner.peak_saturation * 100.,
+            runner.mean_saturation * 100.,
+        );
+    }
+}
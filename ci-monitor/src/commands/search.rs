@@ -0,0 +1,54 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::process::ExitCode;
+
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+
+use crate::commands::Context;
+use crate::exit::RunError;
+use crate::search;
+
+/// The `search` subcommand.
+pub fn command() -> Command {
+    Command::new("search")
+        .about("Search merge requests, pipelines, and failed jobs in the search index")
+        .arg(
+            Arg::new("QUERY")
+                .help("The query (e.g., `title:timeout AND flaky`)")
+                .required(true),
+        )
+        .arg(
+            Arg::new("KIND")
+                .long("kind")
+                .help("The kind of entity to search for")
+                .value_parser(["merge_request", "pipeline", "job_failure"])
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("LIMIT")
+                .long("limit")
+                .help("Maximum number of results")
+                .value_parser(value_parser!(usize))
+                .default_value("20")
+                .action(ArgAction::Set),
+        )
+}
+
+/// Run the `search` subcommand.
+pub fn run(ctx: &Context, search_args: &ArgMatches) -> Result<ExitCode, RunError> {
+    let path = ctx.store_path.as_ref().ok_or(RunError::NoStore)?;
+    let query = search_args
+        .get_one::<String>("QUERY")
+        .expect("the query is required");
+    let kind = search_args.get_one::<String>("KIND").map(String::as_str);
+    let limit = *search_args
+        .get_one::<usize>("LIMIT")
+        .expect("--limit has a default");
+    search::print_search(path, query, kind, limit, ctx.json)?;
+
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,39 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::process::ExitCode;
+
+use ci_monitor_persistence::EntityType;
+use clap::Command;
+
+use crate::commands::{load_store_only, Context};
+use crate::exit::RunError;
+use crate::search;
+
+/// The `search-index` subcommand.
+pub fn command() -> Command {
+    Command::new("search-index").about(
+        "Build or update the full-text search index of the store (requires the `search` \
+     feature)",
+    )
+}
+
+/// Run the `search-index` subcommand.
+pub fn run(ctx: &Context) -> Result<ExitCode, RunError> {
+    let path = ctx.store_path.as_ref().ok_or(RunError::NoStore)?;
+    let storage = load_store_only(
+        path,
+        &[
+            EntityType::MergeRequest,
+            EntityType::Pipeline,
+            EntityType::Job,
+            EntityType::Project,
+        ],
+    )?;
+    search::build_index(path, &storage, ctx.json, ctx.quiet)?;
+
+    Ok(ExitCode::SUCCESS)
+}
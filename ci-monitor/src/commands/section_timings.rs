@@ -0,0 +1,73 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::process::ExitCode;
+
+use chrono::Utc;
+use ci_monitor_analysis::SectionTiming;
+use ci_monitor_persistence::{EntityType, VecLookup};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+
+use crate::commands::{load_store_only, print_json, Context};
+use crate::exit::RunError;
+
+/// The `section-timings` subcommand.
+pub fn command() -> Command {
+    Command::new("section-timings")
+        .about("Compare recent job log section durations against earlier ones")
+        .arg(
+            Arg::new("SINCE")
+                .long("since")
+                .help("Number of hours considered recent")
+                .value_parser(value_parser!(u32))
+                .default_value("168")
+                .action(ArgAction::Set),
+        )
+}
+
+/// Run the `section-timings` subcommand.
+pub fn run(ctx: &Context, timings: &ArgMatches) -> Result<ExitCode, RunError> {
+    let storage = if let Some(path) = ctx.store_path.as_ref() {
+        load_store_only(
+            path,
+            &[EntityType::Pipeline, EntityType::Job, EntityType::Project],
+        )?
+    } else {
+        VecLookup::default()
+    };
+    let hours = *timings
+        .get_one::<u32>("SINCE")
+        .expect("--since has a default");
+    let split = Utc::now() - chrono::Duration::hours(hours.into());
+    let timings = ci_monitor_analysis::section_timings(&storage, split);
+
+    storage.check_deferred()?;
+
+    if ctx.json {
+        print_json(&timings)?;
+    } else if !ctx.quiet {
+        print_section_timings(&timings);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print how the durations of job log sections changed.
+fn print_section_timings(timings: &[SectionTiming]) {
+    for timing in timings {
+        println!(
+            "{} {} {}: {:.0}s -> {:.0}s ({:+.0}s, {} -> {} samples)",
+            timing.project,
+            timing.job,
+            timing.section,
+            timing.before,
+            timing.after,
+            timing.change(),
+            timing.before_samples,
+            timing.after_samples,
+        );
+    }
+}
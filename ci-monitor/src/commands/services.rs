@@ -0,0 +1,80 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::process::ExitCode;
+
+use chrono::Utc;
+use ci_monitor_analysis::ServiceReport;
+use ci_monitor_persistence::{EntityType, VecLookup};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+
+use crate::commands::{load_store_only, print_json, Context};
+use crate::exit::RunError;
+
+/// The `services` subcommand.
+pub fn command() -> Command {
+    Command::new("services")
+        .about("Report failure rates and machine time aggregated by service")
+        .arg(
+            Arg::new("SINCE")
+                .long("since")
+                .help("Number of hours to look back for pipelines")
+                .value_parser(value_parser!(u32))
+                .action(ArgAction::Set),
+        )
+}
+
+/// Run the `services` subcommand.
+pub fn run(ctx: &Context, services: &ArgMatches) -> Result<ExitCode, RunError> {
+    let service_map = ctx.config.service_map()?;
+    let quarantine = ctx.config.quarantine()?;
+    let storage = if let Some(path) = ctx.store_path.as_ref() {
+        load_store_only(
+            path,
+            &[
+                EntityType::Project,
+                EntityType::Pipeline,
+                EntityType::Job,
+                EntityType::Runner,
+                EntityType::RunnerHost,
+            ],
+        )?
+    } else {
+        VecLookup::default()
+    };
+    let since = services
+        .get_one::<u32>("SINCE")
+        .map(|hours| Utc::now() - chrono::Duration::hours((*hours).into()));
+    let reports = ci_monitor_analysis::service_reports(&storage, &service_map, &quarantine, since);
+
+    storage.check_deferred()?;
+
+    if ctx.json {
+        print_json(&reports)?;
+    } else if !ctx.quiet {
+        print_service_reports(&reports);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print reports aggregated by service.
+fn print_service_reports(reports: &[ServiceReport]) {
+    for report in reports {
+        println!(
+            "{} ({} projects): {:.1}% of {} pipelines and {:.1}% of {} jobs failed; {}h machine \
+             time costing {:.2}",
+            report.service,
+            report.projects.len(),
+            100. * report.pipelines.rate(),
+            report.pipelines.finished,
+            100. * report.jobs.rate(),
+            report.jobs.finished,
+            report.machine_time.num_hours(),
+            report.estimated_cost,
+        );
+    }
+}
@@ -0,0 +1,333 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fs;
+use std::process::ExitCode;
+
+use chrono::Utc;
+use ci_monitor_core::data::{Pipeline, Project, User};
+use ci_monitor_persistence::{
+    DiscoverableLookup, SoftDeleteCounts, VecLookup, VecStore, VecStoreError,
+};
+use clap::{value_parser, Arg, ArgMatches, Command};
+
+use crate::commands::{audit_entry, id_arg, load_store, print_json, save_store, Context};
+use crate::entities;
+use crate::exit::{self, RunError};
+use crate::kv;
+use crate::output::{
+    InstanceMergeOutput, SoftDeleteOutput, StoreConvertOutput, StoreProblemOutput, UserMergeOutput,
+};
+
+/// The `store` subcommand.
+pub fn command() -> Command {
+    Command::new("store")
+        .about("Manage entities in the store")
+        .subcommand_required(true)
+        .subcommand(entity_command(
+            "delete",
+            "Mark an entity and its children as deleted",
+        ))
+        .subcommand(entity_command(
+            "undelete",
+            "Restore an entity and the children deleted with it",
+        ))
+        .subcommand(
+            Command::new("check")
+                .about("Check the entity files of the store against their manifests"),
+        )
+        .subcommand(
+            Command::new("repair")
+                .about("Rebuild the store index from its manifests, dropping damaged entities"),
+        )
+        .subcommand(
+            Command::new("merge-users")
+                .about("Merge a user into another user who is the same person")
+                .arg(
+                    Arg::new("FROM")
+                        .help("The ID of the user to merge")
+                        .value_parser(value_parser!(u64))
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("INTO")
+                        .help("The ID of the user to merge into")
+                        .value_parser(value_parser!(u64))
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("merge-instances")
+                .about("Merge instances stored under different URLs of the same instance"),
+        )
+        .subcommand(
+            Command::new("convert")
+                .about(
+                    "Move the entities of the store into JSON files or into a key-value database \
+                     (requires the `kv` feature)",
+                )
+                .arg(
+                    Arg::new("BACKEND")
+                        .help("Where to keep the entities")
+                        .value_parser(["directory", "kv"])
+                        .required(true),
+                ),
+        )
+}
+
+/// Run the `store` subcommand.
+pub fn run(ctx: &Context, store: &ArgMatches) -> Result<ExitCode, RunError> {
+    match store.subcommand() {
+        Some(("check", _)) => check(ctx, false),
+        Some(("repair", _)) => check(ctx, true),
+        Some(("merge-users", merge)) => merge_users(ctx, merge),
+        Some(("merge-instances", _)) => merge_instances(ctx),
+        Some(("convert", convert)) => convert_store(ctx, convert),
+        Some((action, entity)) => soft_delete_entity(ctx, action, entity),
+        None => unreachable!("a store subcommand is required"),
+    }
+}
+
+/// Check (or repair) the entity files of the store.
+fn check(ctx: &Context, repair: bool) -> Result<ExitCode, RunError> {
+    let path = ctx.store_path.as_ref().ok_or(RunError::NoStore)?;
+    if kv::is_kv_store(path) {
+        return Err(RunError::KvUnsupported {
+            operation: "checking entity files",
+        });
+    }
+    let problems = if repair {
+        let problems = VecStore::repair(path)?;
+        // Repairs rewrite the index and manifests.
+        if let Some(key) = ctx.signing_key.as_ref() {
+            VecStore::sign(path, key)?;
+        }
+        problems
+    } else {
+        VecStore::check(path)?
+    };
+
+    if ctx.json {
+        print_json(
+            &problems
+                .iter()
+                .map(StoreProblemOutput::from)
+                .collect::<Vec<_>>(),
+        )?;
+    } else if !ctx.quiet {
+        for problem in &problems {
+            println!("{}", problem);
+        }
+        if repair && !problems.is_empty() {
+            println!("repaired {} problems", problems.len());
+        }
+    }
+
+    Ok(if problems.is_empty() || repair {
+        ExitCode::SUCCESS
+    } else {
+        exit::CORRUPT.into()
+    })
+}
+
+/// Move the entities of the store between JSON files and a key-value database.
+///
+/// Entity files are left in place when moving into a key-value database, but are no longer used.
+fn convert_store(ctx: &Context, convert: &ArgMatches) -> Result<ExitCode, RunError> {
+    let path = ctx.store_path.as_ref().ok_or(RunError::NoStore)?;
+    let backend = convert
+        .get_one::<String>("BACKEND")
+        .expect("the backend is required");
+    let into_kv = backend == "kv";
+    let converted = kv::is_kv_store(path) != into_kv;
+    if converted {
+        let storage = load_store(path)?;
+        if into_kv {
+            if ctx.signing_key.is_some() {
+                return Err(RunError::KvUnsupported {
+                    operation: "signing",
+                });
+            }
+            fs::create_dir_all(path).map_err(VecStoreError::from)?;
+            kv::save(path, &storage)?;
+        } else {
+            VecStore::store(path, &storage)?;
+            if let Some(key) = ctx.signing_key.as_ref() {
+                VecStore::sign(path, key)?;
+            }
+            // The entity files are complete; only now may the database go.
+            fs::remove_file(kv::kv_path(path)).map_err(VecStoreError::from)?;
+        }
+    }
+
+    if ctx.json {
+        let output = StoreConvertOutput {
+            backend: backend.clone(),
+            converted,
+        };
+        print_json(&output)?;
+    } else if !ctx.quiet {
+        let target = if into_kv {
+            "a key-value database"
+        } else {
+            "entity files"
+        };
+        if converted {
+            println!("moved the entities of the store into {}", target);
+        } else {
+            println!("the entities of the store are already kept in {}", target);
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Merge a user into another user.
+fn merge_users(ctx: &Context, merge: &ArgMatches) -> Result<ExitCode, RunError> {
+    let path = ctx.store_path.as_ref().ok_or(RunError::NoStore)?;
+    let mut storage = load_store(path)?;
+    let find_user = |id: u64| {
+        let idx = <VecLookup as DiscoverableLookup<User<VecLookup>>>::find(&storage, id);
+        idx.ok_or_else(|| {
+            RunError::MissingEntity {
+                kind: "user".into(),
+                id,
+            }
+        })
+    };
+    let from = *merge.get_one::<u64>("FROM").expect("the user is required");
+    let into = *merge.get_one::<u64>("INTO").expect("the user is required");
+    let from_idx = find_user(from)?;
+    let into_idx = find_user(into)?;
+    let moved = storage.merge_users(&from_idx, &into_idx);
+    let target = format!("user #{} into #{}", from, into);
+    let entry = audit_entry(&ctx.matches, "merge-users", target).affected(moved as u64);
+    entities::record_audit_entry(&mut storage, entry);
+    save_store(path, &storage, ctx.signing_key.as_ref())?;
+
+    if ctx.json {
+        let output = UserMergeOutput {
+            from,
+            into,
+            references: moved,
+        };
+        print_json(&output)?;
+    } else if !ctx.quiet {
+        println!(
+            "merged user #{} into #{} ({} references updated)",
+            from, into, moved,
+        );
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Merge instances stored under different URLs.
+fn merge_instances(ctx: &Context) -> Result<ExitCode, RunError> {
+    let path = ctx.store_path.as_ref().ok_or(RunError::NoStore)?;
+    let mut storage = load_store(path)?;
+    let (merged, moved) =
+        entities::merge_split_instances(&mut storage, &ctx.config.instances.aliases());
+    if merged > 0 {
+        let entry =
+            audit_entry(&ctx.matches, "merge-instances", String::new()).affected(moved as u64);
+        entities::record_audit_entry(&mut storage, entry);
+        save_store(path, &storage, ctx.signing_key.as_ref())?;
+    }
+
+    if ctx.json {
+        let output = InstanceMergeOutput {
+            merged,
+            references: moved,
+        };
+        print_json(&output)?;
+    } else if !ctx.quiet {
+        println!("merged {} instances ({} references updated)", merged, moved);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Soft-delete (or restore) an entity and its children.
+fn soft_delete_entity(
+    ctx: &Context,
+    action: &str,
+    entity: &ArgMatches,
+) -> Result<ExitCode, RunError> {
+    let path = ctx.store_path.as_ref().ok_or(RunError::NoStore)?;
+    let mut storage = load_store(path)?;
+    let (kind, entity) = entity.subcommand().expect("an entity kind is required");
+    let id = *entity.get_one::<u64>("ID").expect("the ID is required");
+    let counts = soft_delete(&mut storage, kind, id, action == "delete").ok_or_else(|| {
+        RunError::MissingEntity {
+            kind: kind.into(),
+            id,
+        }
+    })?;
+    let entry = audit_entry(&ctx.matches, action, format!("{} #{}", kind, id))
+        .affected(counts.total() as u64);
+    entities::record_audit_entry(&mut storage, entry);
+    save_store(path, &storage, ctx.signing_key.as_ref())?;
+
+    if ctx.json {
+        let output = SoftDeleteOutput::new(kind, id, &counts);
+        print_json(&output)?;
+    } else if !ctx.quiet {
+        println!("{}d {} entities", action, counts.total());
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Soft-delete (or restore) an entity in the store.
+///
+/// Returns `None` if the entity is not in the store.
+fn soft_delete(
+    storage: &mut VecLookup,
+    kind: &str,
+    id: u64,
+    delete: bool,
+) -> Option<SoftDeleteCounts> {
+    let now = Utc::now();
+
+    let counts = match kind {
+        "project" => {
+            let idx = <VecLookup as DiscoverableLookup<Project<VecLookup>>>::find(storage, id)?;
+            if delete {
+                storage.soft_delete_project(&idx, now)
+            } else {
+                storage.undelete_project(&idx)
+            }
+        },
+        "pipeline" => {
+            let idx = <VecLookup as DiscoverableLookup<Pipeline<VecLookup>>>::find(storage, id)?;
+            if delete {
+                storage.soft_delete_pipeline(&idx, now)
+            } else {
+                storage.undelete_pipeline(&idx)
+            }
+        },
+        _ => unreachable!("unknown entity kind {}", kind),
+    };
+
+    Some(counts)
+}
+
+fn entity_command(name: &'static str, about: &'static str) -> Command {
+    Command::new(name)
+        .about(about)
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("project")
+                .about("A project and its merge requests, pipelines, and environments")
+                .arg(id_arg("The ID of the project")),
+        )
+        .subcommand(
+            Command::new("pipeline")
+                .about("A pipeline and its jobs and deployments")
+                .arg(id_arg("The ID of the pipeline")),
+        )
+}
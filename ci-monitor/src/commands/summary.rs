@@ -0,0 +1,89 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use ci_monitor_forge::Heartbeat;
+use ci_monitor_persistence::EntityType;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+
+use crate::commands::{load_store_only, print_json, Context, HEARTBEAT_NAME};
+use crate::exit::RunError;
+use crate::federation;
+use crate::output::SiteSummaryOutput;
+
+/// The `summary` subcommand.
+pub fn command() -> Command {
+    Command::new("summary")
+        .about("Summarize the store for a federating monitor")
+        .arg(
+            Arg::new("NAME")
+                .long("name")
+                .help("Name of the site (default: from the configuration)")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("FILE")
+                .long("file")
+                .help("Path to atomically write the summary to for serving")
+                .action(ArgAction::Set),
+        )
+}
+
+/// Run the `summary` subcommand.
+pub fn run(ctx: &Context, summary: &ArgMatches) -> Result<ExitCode, RunError> {
+    let path = ctx.store_path.as_ref().ok_or(RunError::NoStore)?;
+    let storage = load_store_only(
+        path,
+        &[
+            EntityType::Instance,
+            EntityType::Project,
+            EntityType::Pipeline,
+            EntityType::Job,
+            EntityType::Runner,
+        ],
+    )?;
+    let name = summary
+        .get_one::<String>("NAME")
+        .or(ctx.config.federation.name.as_ref())
+        .cloned()
+        .unwrap_or_else(|| path.display().to_string());
+    let heartbeat = Heartbeat::read(&path.join(HEARTBEAT_NAME))?;
+    let summary_output = federation::summarize(&name, path, &storage, heartbeat)?;
+
+    if let Some(file) = summary.get_one::<String>("FILE").map(PathBuf::from) {
+        federation::write_summary(&file, &summary_output)
+            .map_err(|err| RunError::summary(file.clone(), err))?;
+    }
+    storage.check_deferred()?;
+
+    if ctx.json {
+        print_json(&summary_output)?;
+    } else if !ctx.quiet {
+        print_site_summary(&summary_output);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print the summary of a site.
+pub fn print_site_summary(summary: &SiteSummaryOutput) {
+    let entities = summary.entities.values().sum::<usize>();
+    println!(
+        "{}: {} entities; {}/{} runners online ({} paused); pipelines fail {:.1}% of {}; jobs fail \
+         {:.1}% of {}",
+        summary.name,
+        entities,
+        summary.runners.online,
+        summary.runners.total,
+        summary.runners.paused,
+        100. * summary.pipelines.rate(),
+        summary.pipelines.finished,
+        100. * summary.jobs.rate(),
+        summary.jobs.finished,
+    );
+}
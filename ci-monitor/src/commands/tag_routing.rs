@@ -0,0 +1,106 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::process::ExitCode;
+
+use chrono::Utc;
+use ci_monitor_analysis::TagRoutingReport;
+use ci_monitor_persistence::{EntityType, VecLookup};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+
+use crate::commands::{load_store_only, print_json, Context};
+use crate::exit::RunError;
+
+/// The `tag-routing` subcommand.
+pub fn command() -> Command {
+    Command::new("tag-routing")
+        .about("Report over-constrained job tags and under-utilized runner tag pools")
+        .arg(
+            Arg::new("SINCE")
+                .long("since")
+                .help("Number of hours to look back for jobs")
+                .value_parser(value_parser!(u32))
+                .default_value("168")
+                .action(ArgAction::Set),
+        )
+}
+
+/// Run the `tag-routing` subcommand.
+pub fn run(ctx: &Context, routing: &ArgMatches) -> Result<ExitCode, RunError> {
+    let storage = if let Some(path) = ctx.store_path.as_ref() {
+        load_store_only(
+            path,
+            &[
+                EntityType::Instance,
+                EntityType::Project,
+                EntityType::Pipeline,
+                EntityType::Job,
+                EntityType::Runner,
+            ],
+        )?
+    } else {
+        VecLookup::default()
+    };
+    let hours = *routing
+        .get_one::<u32>("SINCE")
+        .expect("--since has a default");
+    let since = Utc::now() - chrono::Duration::hours(hours.into());
+    let report = ci_monitor_analysis::tag_routing_report(&storage, since);
+
+    storage.check_deferred()?;
+
+    if ctx.json {
+        print_json(&report)?;
+    } else if !ctx.quiet {
+        print_tag_routing(&report);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print over-constrained jobs and under-utilized tag pools.
+fn print_tag_routing(report: &TagRoutingReport) {
+    for routing in &report.jobs {
+        let tags = routing.tags.iter().cloned().collect::<Vec<_>>().join(", ");
+        if routing.is_unroutable() {
+            println!(
+                "{} {} [{}]: no active runner accepts the tags ({} jobs)",
+                routing.project, routing.job, tags, routing.jobs,
+            );
+        } else if routing.is_over_constrained() {
+            println!(
+                "{} {} [{}]: only runner #{} is eligible ({} jobs)",
+                routing.project,
+                routing.job,
+                tags,
+                routing
+                    .eligible_runners
+                    .first()
+                    .copied()
+                    .unwrap_or_default(),
+                routing.jobs,
+            );
+        }
+        if routing.mismatched > 0 {
+            println!(
+                "{} {} [{}]: {} jobs ran on runners which no longer accept the tags",
+                routing.project, routing.job, tags, routing.mismatched,
+            );
+        }
+    }
+    for pool in &report.pools {
+        let state = if pool.is_unused() { " (unused)" } else { "" };
+        println!(
+            "{} tag '{}': {} runners, {} jobs, {}m machine time per runner{}",
+            pool.instance,
+            pool.tag,
+            pool.runners,
+            pool.jobs,
+            pool.machine_time_per_runner().num_minutes(),
+            state,
+        );
+    }
+}
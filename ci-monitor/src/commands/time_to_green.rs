@@ -0,0 +1,89 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::process::ExitCode;
+
+use chrono::Utc;
+use ci_monitor_analysis::TimeToGreen;
+use ci_monitor_persistence::{EntityType, VecLookup};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+
+use crate::commands::{load_store_only, print_json, Context};
+use crate::exit::RunError;
+
+/// The `time-to-green` subcommand.
+pub fn command() -> Command {
+    Command::new("time-to-green")
+        .about("Measure how long merge requests wait for their first fully green pipeline")
+        .arg(
+            Arg::new("SINCE")
+                .long("since")
+                .help("Number of hours to look back for created merge requests")
+                .value_parser(value_parser!(u32))
+                .action(ArgAction::Set),
+        )
+}
+
+/// Run the `time-to-green` subcommand.
+pub fn run(ctx: &Context, green: &ArgMatches) -> Result<ExitCode, RunError> {
+    let storage = if let Some(path) = ctx.store_path.as_ref() {
+        load_store_only(
+            path,
+            &[
+                EntityType::MergeRequest,
+                EntityType::Pipeline,
+                EntityType::Job,
+                EntityType::Project,
+            ],
+        )?
+    } else {
+        VecLookup::default()
+    };
+    let since = green
+        .get_one::<u32>("SINCE")
+        .map(|hours| Utc::now() - chrono::Duration::hours((*hours).into()));
+    let report = ci_monitor_analysis::time_to_green(&storage, since);
+
+    storage.check_deferred()?;
+
+    if ctx.json {
+        print_json(&report)?;
+    } else if !ctx.quiet {
+        print_time_to_green(&report);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print how long merge requests waited for their first fully green pipeline.
+fn print_time_to_green(report: &TimeToGreen) {
+    for project in &report.projects {
+        println!(
+            "{}: {}/{} merge requests green; median {}m, p90 {}m, max {}m",
+            project.project,
+            project.green,
+            project.merge_requests,
+            project.median.num_minutes(),
+            project.p90.num_minutes(),
+            project.max.num_minutes(),
+        );
+    }
+    for mr in &report.merge_requests {
+        if let Some(time_to_green) = mr.time_to_green() {
+            println!(
+                "{} ({}): green after {}m",
+                mr.url,
+                mr.title,
+                time_to_green.num_minutes(),
+            );
+        } else {
+            println!(
+                "{} ({}): not green since {}",
+                mr.url, mr.title, mr.started_at
+            );
+        }
+    }
+}
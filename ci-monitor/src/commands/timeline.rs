@@ -0,0 +1,97 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::process::ExitCode;
+
+use chrono::{DateTime, Utc};
+use ci_monitor_analysis::Timeline;
+use ci_monitor_persistence::{EntityType, VecLookup};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+
+use crate::commands::{load_store_only, print_json, Context};
+use crate::exit::RunError;
+
+/// The `timeline` subcommand.
+pub fn command() -> Command {
+    Command::new("timeline")
+        .about("Export a chronological timeline of CI activity for incident reviews")
+        .arg(
+            Arg::new("START")
+                .long("start")
+                .help("Start of the window (RFC 3339; default: a day before the end)")
+                .value_parser(parse_timestamp)
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("END")
+                .long("end")
+                .help("End of the window (RFC 3339; default: now)")
+                .value_parser(parse_timestamp)
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("FORMAT")
+                .long("format")
+                .help("Render the timeline instead of listing events")
+                .value_parser(["ics"])
+                .action(ArgAction::Set),
+        )
+}
+
+/// Run the `timeline` subcommand.
+pub fn run(ctx: &Context, window: &ArgMatches) -> Result<ExitCode, RunError> {
+    let storage = if let Some(path) = ctx.store_path.as_ref() {
+        load_store_only(
+            path,
+            &[
+                EntityType::Project,
+                EntityType::Pipeline,
+                EntityType::Environment,
+                EntityType::Deployment,
+                EntityType::Runner,
+            ],
+        )?
+    } else {
+        VecLookup::default()
+    };
+    let end = window
+        .get_one::<DateTime<Utc>>("END")
+        .copied()
+        .unwrap_or_else(Utc::now);
+    let start = window
+        .get_one::<DateTime<Utc>>("START")
+        .copied()
+        .unwrap_or(end - chrono::Duration::days(1));
+    let timeline = ci_monitor_analysis::event_timeline(&storage, start, end);
+
+    storage.check_deferred()?;
+
+    if ctx.json {
+        print_json(&timeline)?;
+    } else if window.get_one::<String>("FORMAT").is_some() {
+        print!("{}", timeline.render_ics());
+    } else if !ctx.quiet {
+        print_timeline(&timeline);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print the events of a timeline.
+fn print_timeline(timeline: &Timeline) {
+    for event in &timeline.events {
+        if let Some(project) = event.project.as_ref() {
+            println!("{} {}: {}", event.at, project, event.summary);
+        } else {
+            println!("{} {}", event.at, event.summary);
+        }
+    }
+}
+
+/// Parse an RFC 3339 timestamp.
+fn parse_timestamp(value: &str) -> Result<DateTime<Utc>, chrono::ParseError> {
+    DateTime::parse_from_rfc3339(value).map(|at| at.with_timezone(&Utc))
+}
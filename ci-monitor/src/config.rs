@@ -0,0 +1,1049 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use ci_monitor_analysis::{
+    AlertCondition, AlertEntity, AlertError, AlertRule, NotificationRouter, OsCatalog, Owner,
+    OwnershipRule, PolicyError, Quarantine, QuarantineError, QuarantinedJob, QuietHours,
+    RequiredJob, RoutingError, ServiceError, ServiceMap,
+};
+use ci_monitor_core::data::ContentHash;
+use ci_monitor_forge::{
+    ArtifactExtractionRules, ExtractionError, Fault, FaultInjection, ForgeTask, RateClass,
+    RunnerHostData, RunnerHostRuleError, RunnerHostRules, TaskExecutor,
+};
+use ci_monitor_gitlab::InstanceAliases;
+use ci_monitor_persistence::{
+    Filesystem, FilesystemError, Sharding, StoreKeyError, StoreSigningKey, StoreVerifyingKey,
+    Tiered,
+};
+use serde::Deserialize;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum ConfigError {
+    #[error("failed to read configuration '{}': {}", path.display(), source)]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("failed to parse configuration '{}': {}", path.display(), source)]
+    Parse {
+        path: PathBuf,
+        #[source]
+        source: Box<toml::de::Error>,
+    },
+    #[error("failed to create blob storage directory '{}': {}", path.display(), source)]
+    CreateBlobs {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("failed to open blob storage: {}", source)]
+    Blobs {
+        #[from]
+        source: FilesystemError,
+    },
+    #[error("unknown content hash algorithm '{}'", name)]
+    ContentHash { name: String },
+    #[error("invalid artifact extraction rule: {}", source)]
+    Extraction {
+        #[from]
+        source: ExtractionError,
+    },
+    #[error("invalid runner ID '{}'", id)]
+    RunnerId { id: String },
+    #[error("invalid runner host rule: {}", source)]
+    RunnerHostRule {
+        #[from]
+        source: RunnerHostRuleError,
+    },
+    #[error("unknown rate class '{}'", name)]
+    RateClass { name: String },
+    #[error("invalid policy: {}", source)]
+    Policy {
+        #[from]
+        source: PolicyError,
+    },
+    #[error("notification routing requires a fallback channel")]
+    NoFallback {},
+    #[error("invalid notification routing: {}", source)]
+    Routing {
+        #[from]
+        source: RoutingError,
+    },
+    #[error("unknown alert entity '{}'", name)]
+    AlertEntity { name: String },
+    #[error("unknown alert condition '{}'", name)]
+    AlertCondition { name: String },
+    #[error("invalid alert: {}", source)]
+    Alert {
+        #[from]
+        source: AlertError,
+    },
+    #[error("invalid service: {}", source)]
+    Service {
+        #[from]
+        source: ServiceError,
+    },
+    #[error("invalid quarantine: {}", source)]
+    Quarantine {
+        #[from]
+        source: QuarantineError,
+    },
+    #[error("failed to read signing key '{}': {}", path.display(), source)]
+    ReadSigningKey {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("invalid signing key '{}': {}", path.display(), source)]
+    SigningKey {
+        path: PathBuf,
+        #[source]
+        source: StoreKeyError,
+    },
+    #[error("invalid public key: {}", source)]
+    PublicKey {
+        #[from]
+        source: StoreKeyError,
+    },
+}
+
+impl ConfigError {
+    fn read(path: PathBuf, source: io::Error) -> Self {
+        Self::Read {
+            path,
+            source,
+        }
+    }
+
+    fn parse(path: PathBuf, source: toml::de::Error) -> Self {
+        Self::Parse {
+            path,
+            source: Box::new(source),
+        }
+    }
+
+    fn create_blobs(path: PathBuf, source: io::Error) -> Self {
+        Self::CreateBlobs {
+            path,
+            source,
+        }
+    }
+
+    fn read_signing_key(path: PathBuf, source: io::Error) -> Self {
+        Self::ReadSigningKey {
+            path,
+            source,
+        }
+    }
+
+    fn signing_key(path: PathBuf, source: StoreKeyError) -> Self {
+        Self::SigningKey {
+            path,
+            source,
+        }
+    }
+}
+
+/// Configuration for artifact handling.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ArtifactsConfig {
+    /// Path to a blob store for artifact contents.
+    pub blobs: Option<PathBuf>,
+    /// Path to a blob pool shared with other stores.
+    ///
+    /// Identical blobs in stores sharing a pool are only stored once.
+    pub shared_pool: Option<PathBuf>,
+    /// Path to a blob store for archived job logs.
+    ///
+    /// This is intended for slower, cheaper storage (e.g., an archive volume). Logs are moved
+    /// into it by `blobs archive` and are read from it when they are not in the main store.
+    pub cold_blobs: Option<PathBuf>,
+    /// The algorithm to hash blobs with when creating a blob store.
+    ///
+    /// One of `sha256` (the default), `sha512`, or `blake3`.
+    pub algorithm: Option<String>,
+    /// Glob patterns for files to extract from archive artifacts.
+    pub extract: Vec<String>,
+    /// The maximum size (in bytes) of a file extracted from an archive artifact.
+    pub extract_max_file_size: Option<u64>,
+    /// The maximum size (in bytes) of all files extracted from an archive artifact.
+    pub extract_max_total_size: Option<u64>,
+    /// The number of threads to use for batches of blob operations.
+    pub parallelism: Option<NonZeroUsize>,
+}
+
+impl ArtifactsConfig {
+    /// Open the blob store (creating it if necessary).
+    pub fn blob_storage(&self) -> Result<Option<Filesystem>, ConfigError> {
+        self.blob_storage_with_parallelism(self.parallelism)
+    }
+
+    fn blob_storage_with_parallelism(
+        &self,
+        parallelism: Option<NonZeroUsize>,
+    ) -> Result<Option<Filesystem>, ConfigError> {
+        let path = if let Some(path) = self.blobs.as_ref() {
+            path
+        } else {
+            return Ok(None);
+        };
+
+        let algo = self.content_hash()?;
+        let mut store = Self::open_blobs(path, algo)?;
+        if let Some(pool) = self.shared_pool.as_ref() {
+            store = store.with_shared_pool(Self::open_blobs(pool, algo)?);
+        }
+        if let Some(parallelism) = parallelism {
+            store = store.with_parallelism(parallelism);
+        }
+
+        Ok(Some(store))
+    }
+
+    /// Open the blob store along with the cold store for archived logs (if configured).
+    pub fn blob_tiers(&self) -> Result<Option<Tiered<Filesystem, Filesystem>>, ConfigError> {
+        self.blob_tiers_with_parallelism(self.parallelism)
+    }
+
+    /// Open the blob tiers using a different number of threads for batches of blob operations.
+    pub fn blob_tiers_with_parallelism(
+        &self,
+        parallelism: Option<NonZeroUsize>,
+    ) -> Result<Option<Tiered<Filesystem, Filesystem>>, ConfigError> {
+        let hot = if let Some(hot) = self.blob_storage_with_parallelism(parallelism)? {
+            hot
+        } else {
+            return Ok(None);
+        };
+
+        let mut tiers = Tiered::new(hot);
+        if let Some(path) = self.cold_blobs.as_ref() {
+            let mut cold = Self::open_blobs(path, self.content_hash()?)?;
+            if let Some(parallelism) = parallelism {
+                cold = cold.with_parallelism(parallelism);
+            }
+            tiers = tiers.with_cold_tier(cold);
+        }
+
+        Ok(Some(tiers))
+    }
+
+    /// Open a standalone blob store at the given path (creating it if necessary).
+    ///
+    /// The shared pool is not used so that the store is self-contained.
+    pub fn blob_storage_at(&self, path: &Path) -> Result<Filesystem, ConfigError> {
+        let mut store = Self::open_blobs(path, self.content_hash()?)?;
+        if let Some(parallelism) = self.parallelism {
+            store = store.with_parallelism(parallelism);
+        }
+
+        Ok(store)
+    }
+
+    /// The algorithm to hash blobs with when creating a blob store.
+    pub fn content_hash(&self) -> Result<ContentHash, ConfigError> {
+        if let Some(name) = self.algorithm.as_ref() {
+            ContentHash::from_name(name).ok_or_else(|| {
+                ConfigError::ContentHash {
+                    name: name.clone(),
+                }
+            })
+        } else {
+            Ok(ContentHash::Sha256)
+        }
+    }
+
+    fn open_blobs(path: &Path, algo: ContentHash) -> Result<Filesystem, ConfigError> {
+        fs::create_dir_all(path).map_err(|err| ConfigError::create_blobs(path.into(), err))?;
+        let is_empty = fs::read_dir(path)
+            .map_err(|err| ConfigError::create_blobs(path.into(), err))?
+            .next()
+            .is_none();
+
+        let store = if is_empty {
+            Filesystem::create(path, algo, Sharding::default())?
+        } else {
+            Filesystem::open(path)?
+        };
+
+        Ok(store)
+    }
+
+    /// The rules for files to extract from archive artifacts.
+    pub fn extraction_rules(&self) -> Result<ArtifactExtractionRules, ConfigError> {
+        let mut rules = ArtifactExtractionRules::default();
+        for pattern in &self.extract {
+            rules.add_pattern(pattern)?;
+        }
+        if let Some(size) = self.extract_max_file_size {
+            rules.max_file_size(size);
+        }
+        if let Some(size) = self.extract_max_total_size {
+            rules.max_total_size(size);
+        }
+        Ok(rules)
+    }
+}
+
+/// Configuration for task execution.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct TasksConfig {
+    /// The maximum time (in seconds) any task may run.
+    pub timeout: Option<u64>,
+    /// The maximum time (in seconds) each kind of task may run.
+    pub timeouts: BTreeMap<String, u64>,
+    /// The maximum number of queued tasks to hold in memory.
+    pub queue_capacity: Option<usize>,
+    /// Directory to spill queued tasks into beyond the queue capacity.
+    pub spill_dir: Option<PathBuf>,
+    /// Defer discovery tasks while at least this many tasks are queued.
+    ///
+    /// This counts tasks rather than measuring memory use; see `queue_capacity` and `spill_dir`
+    /// to bound the memory held by queued tasks.
+    pub throttle_discovery_backlog: Option<usize>,
+    /// Report the running tasks if none complete for this long (in seconds).
+    pub watchdog: Option<u64>,
+    /// Abort and retry tasks which are running when no progress is made.
+    pub restart_stalled: bool,
+    /// The number of rate limit units which may be used per second (default: 50).
+    pub rate_limit: Option<NonZeroU32>,
+    /// Budgets for each rate class keyed by its name.
+    pub rate_classes: BTreeMap<String, RateClassConfig>,
+    /// Limits for the tasks of each instance keyed by its name (e.g., `gitlab.example.com`).
+    pub instances: BTreeMap<String, InstanceTasksConfig>,
+    /// Write a heartbeat into the store this often (in seconds) while running.
+    pub heartbeat: Option<u64>,
+}
+
+/// Configuration for a rate class.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct RateClassConfig {
+    /// The number of rate limit units used by each task of the class.
+    pub cost: Option<u32>,
+    /// The number of tasks of the class which may be started per second.
+    pub rate_limit: Option<NonZeroU32>,
+}
+
+/// Configuration for the tasks of an instance.
+///
+/// Tasks for an instance at its concurrency limit wait for a free slot while tasks for other
+/// instances keep running.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct InstanceTasksConfig {
+    /// The maximum number of tasks for the instance which may run at once.
+    pub concurrency: Option<NonZeroUsize>,
+    /// The number of rate limit units which tasks for the instance may use per second.
+    ///
+    /// Replaces the overall rate limit for the instance.
+    pub rate_limit: Option<NonZeroU32>,
+    /// The maximum number of tasks which may wait for a free slot of the instance.
+    ///
+    /// Tasks beyond this are left for a later run when the crawl has a store to leave them in.
+    /// Otherwise, they are held for the instance (spilling to `spill_dir`, if set) while tasks for
+    /// other instances continue. Defaults to the queue capacity.
+    pub queue_depth: Option<usize>,
+}
+
+impl TasksConfig {
+    /// Create an executor using the configuration.
+    pub fn executor(&self) -> Result<TaskExecutor, ConfigError> {
+        let mut executor = TaskExecutor::default();
+        if let Some(rate_limit) = self.rate_limit {
+            executor = executor.rate_limit(rate_limit);
+        }
+        for (name, config) in &self.rate_classes {
+            let class = RateClass::from_name(name).ok_or_else(|| {
+                ConfigError::RateClass {
+                    name: name.clone(),
+                }
+            })?;
+            if let Some(cost) = config.cost {
+                executor = executor.class_cost(class, cost);
+            }
+            if let Some(rate_limit) = config.rate_limit {
+                executor = executor.class_rate_limit(class, rate_limit);
+            }
+        }
+        for (instance, config) in &self.instances {
+            if let Some(max) = config.concurrency {
+                executor = executor.instance_concurrency(instance, max);
+            }
+            if let Some(rate_limit) = config.rate_limit {
+                executor = executor.instance_rate_limit(instance, rate_limit);
+            }
+            if let Some(depth) = config.queue_depth {
+                executor = executor.instance_queue_depth(instance, depth);
+            }
+        }
+        if let Some(timeout) = self.timeout {
+            executor = executor.timeout(Duration::from_secs(timeout));
+        }
+        for (kind, timeout) in &self.timeouts {
+            executor = executor.task_timeout(kind, Duration::from_secs(*timeout));
+        }
+        if let Some(capacity) = self.queue_capacity {
+            executor = executor.queue_capacity(capacity);
+        }
+        if let Some(dir) = self.spill_dir.as_ref() {
+            executor = executor.spill_directory(dir);
+        }
+        if let Some(tasks) = self.throttle_discovery_backlog {
+            executor = executor.throttle_discovery_backlog(tasks);
+        }
+        if let Some(watchdog) = self.watchdog {
+            executor = executor
+                .watchdog(Duration::from_secs(watchdog))
+                .restart_stalled(self.restart_stalled);
+        }
+        Ok(executor)
+    }
+
+    /// The interval at which heartbeats should be written.
+    pub fn heartbeat_interval(&self) -> Option<Duration> {
+        self.heartbeat.map(Duration::from_secs)
+    }
+}
+
+/// Configuration for injecting faults into forge tasks.
+///
+/// Intended for staging deployments to exercise failure handling; all faults are disabled by
+/// default.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct FaultsConfig {
+    /// The fraction of tasks which fail to connect to the forge.
+    pub error_rate: f64,
+    /// The fraction of tasks which receive a malformed payload.
+    pub malformed_rate: f64,
+    /// The fraction of tasks which fail after doing part of their work.
+    pub partial_rate: f64,
+    /// The latency (in milliseconds) added to each task.
+    pub latency: u64,
+    /// The maximum random latency (in milliseconds) added to each task.
+    pub latency_jitter: u64,
+    /// The seed for choosing which tasks suffer faults.
+    pub seed: u64,
+}
+
+impl FaultsConfig {
+    /// The faults to inject.
+    pub fn injection(&self) -> FaultInjection {
+        FaultInjection::default()
+            .rate(Fault::Error, self.error_rate)
+            .rate(Fault::Malformed, self.malformed_rate)
+            .rate(Fault::Partial, self.partial_rate)
+            .latency(
+                Duration::from_millis(self.latency),
+                Duration::from_millis(self.latency_jitter),
+            )
+            .seed(self.seed)
+    }
+}
+
+/// Configuration for backfilling job logs.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct BackfillConfig {
+    /// The number of requests per second to make (default: 1).
+    pub rate: Option<NonZeroU32>,
+    /// The maximum number of logs to fetch per run.
+    pub batch: Option<usize>,
+}
+
+impl BackfillConfig {
+    /// Create an executor for backfilling.
+    ///
+    /// The backfill uses its own rate limit so that it does not compete with crawls.
+    pub fn executor(&self, tasks: &TasksConfig) -> Result<TaskExecutor, ConfigError> {
+        Ok(tasks
+            .executor()?
+            .rate_limit(self.rate.unwrap_or(NonZeroU32::MIN)))
+    }
+}
+
+/// Configuration for runners.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct RunnersConfig {
+    /// The maximum number of concurrent jobs for runners keyed by their ID.
+    pub max_concurrent_jobs: BTreeMap<String, u64>,
+    /// Regular expressions which find host names in runner descriptions.
+    ///
+    /// The `host` capture group names the host if present, otherwise the whole match does.
+    /// Runners which match are assigned to the host (which is created if needed).
+    pub host_patterns: Vec<String>,
+    /// Regular expressions which find host names in the IP addresses runners last contacted the
+    /// forge from.
+    ///
+    /// Only used for runners whose description does not match.
+    pub host_address_patterns: Vec<String>,
+    /// Metadata for runner hosts keyed by their name.
+    ///
+    /// Hosts which do not exist are created.
+    pub hosts: BTreeMap<String, RunnerHostConfig>,
+    /// When operating system versions reach their end of life.
+    ///
+    /// Keyed by operating system and then by version. Versions also cover hosts with more
+    /// specific versions (e.g., `20.04` covers `20.04.6`).
+    pub os_end_of_life: BTreeMap<String, BTreeMap<String, DateTime<Utc>>>,
+}
+
+impl RunnersConfig {
+    /// The maximum number of concurrent jobs for each configured runner ID.
+    pub fn concurrency_limits(&self) -> Result<BTreeMap<u64, u64>, ConfigError> {
+        self.max_concurrent_jobs
+            .iter()
+            .map(|(id, limit)| {
+                id.parse().map(|id| (id, *limit)).map_err(|_| {
+                    ConfigError::RunnerId {
+                        id: id.clone(),
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// The rules which assign runners to hosts.
+    pub fn host_rules(&self) -> Result<RunnerHostRules, ConfigError> {
+        let mut rules = RunnerHostRules::default();
+        for pattern in &self.host_patterns {
+            rules.add_description_pattern(pattern)?;
+        }
+        for pattern in &self.host_address_patterns {
+            rules.add_address_pattern(pattern)?;
+        }
+        Ok(rules)
+    }
+
+    /// The metadata for each configured runner host.
+    pub fn host_data(&self) -> BTreeMap<String, RunnerHostData> {
+        self.hosts
+            .iter()
+            .map(|(name, host)| (name.clone(), host.data()))
+            .collect()
+    }
+
+    /// The end of life dates of operating system versions.
+    pub fn os_catalog(&self) -> OsCatalog {
+        let mut catalog = OsCatalog::default();
+        for (os, versions) in &self.os_end_of_life {
+            for (version, end_of_life) in versions {
+                catalog = catalog.release(os, version.clone(), *end_of_life);
+            }
+        }
+        catalog
+    }
+}
+
+/// Metadata for a runner host.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct RunnerHostConfig {
+    /// The operating system.
+    pub os: Option<String>,
+    /// The version of the operating system.
+    pub os_version: Option<String>,
+    /// How the host is managed.
+    pub management: Option<String>,
+    /// Where the host resides.
+    pub location: Option<String>,
+    /// An estimate of how much it costs to run tasks per hour.
+    pub estimated_cost_per_hour: Option<f64>,
+    /// The maximum number of jobs the host may run at once.
+    pub max_concurrent_jobs: Option<u64>,
+}
+
+impl RunnerHostConfig {
+    fn data(&self) -> RunnerHostData {
+        let mut data = RunnerHostData::default();
+        data.os.clone_from(&self.os);
+        data.os_version.clone_from(&self.os_version);
+        data.management.clone_from(&self.management);
+        data.location.clone_from(&self.location);
+        data.estimated_cost_per_hour = self.estimated_cost_per_hour.map(Some);
+        data.max_concurrent_jobs = self.max_concurrent_jobs.map(Some);
+        data
+    }
+}
+
+/// A job which must be present in pipelines.
+#[derive(Debug, Deserialize)]
+pub struct RequiredJobConfig {
+    /// The name of the job.
+    pub job: String,
+    /// A glob pattern for the refs of pipelines which must contain the job.
+    pub refs: String,
+    /// A glob pattern for the paths of projects the requirement applies to.
+    pub projects: Option<String>,
+    /// Whether the job must also pass (default: true).
+    #[serde(default = "RequiredJobConfig::default_must_pass")]
+    pub must_pass: bool,
+}
+
+impl RequiredJobConfig {
+    fn default_must_pass() -> bool {
+        true
+    }
+}
+
+/// Configuration for pipeline policies.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct PoliciesConfig {
+    /// Jobs which must be present in pipelines.
+    pub required_jobs: Vec<RequiredJobConfig>,
+}
+
+impl PoliciesConfig {
+    /// The required jobs.
+    pub fn required_jobs(&self) -> Result<Vec<RequiredJob>, ConfigError> {
+        self.required_jobs
+            .iter()
+            .map(|required| {
+                let mut rule = RequiredJob::new(&required.job, &required.refs)?;
+                if let Some(projects) = required.projects.as_ref() {
+                    rule = rule.projects(projects)?;
+                }
+                Ok(rule.must_pass(required.must_pass))
+            })
+            .collect()
+    }
+}
+
+/// A period during which an owner is out of office.
+#[derive(Debug, Deserialize)]
+pub struct AwayConfig {
+    /// When the owner leaves.
+    pub start: DateTime<Utc>,
+    /// When the owner returns.
+    pub end: DateTime<Utc>,
+}
+
+/// An owner of projects or jobs.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct OwnerConfig {
+    /// The channel to notify the owner through (default: the owner's name).
+    pub channel: Option<String>,
+    /// The offset of the owner's local time from UTC (e.g., `+02:00`).
+    pub utc_offset: Option<String>,
+    /// Local times during which the owner should not be notified (e.g., `22:00-07:00`).
+    pub quiet_hours: Option<String>,
+    /// Periods during which the owner is out of office.
+    pub away: Vec<AwayConfig>,
+}
+
+/// A rule assigning owners to projects and jobs.
+#[derive(Debug, Deserialize)]
+pub struct OwnershipRuleConfig {
+    /// A glob pattern for the paths of projects.
+    pub projects: String,
+    /// A glob pattern for the names of jobs.
+    pub jobs: Option<String>,
+    /// The owners.
+    pub owners: Vec<String>,
+}
+
+/// Configuration for routing failure notifications.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct NotificationsConfig {
+    /// The channel to escalate to when no owner is available.
+    pub fallback: Option<String>,
+    /// Path to a `CODEOWNERS`-like file of ownership rules.
+    ///
+    /// Rules in the file are considered before `rules`.
+    pub owners_file: Option<PathBuf>,
+    /// Ownership rules; the last matching rule determines the owners.
+    pub rules: Vec<OwnershipRuleConfig>,
+    /// Owner descriptions.
+    pub owners: BTreeMap<String, OwnerConfig>,
+}
+
+impl NotificationsConfig {
+    /// The notification router.
+    pub fn router(&self) -> Result<NotificationRouter, ConfigError> {
+        let fallback = self.fallback.as_ref().ok_or(ConfigError::NoFallback {})?;
+        let mut router = NotificationRouter::new(fallback);
+
+        if let Some(path) = self.owners_file.as_ref() {
+            let contents =
+                fs::read_to_string(path).map_err(|err| ConfigError::read(path.clone(), err))?;
+            router = router.rules(ci_monitor_analysis::parse_owners(&contents)?);
+        }
+        let rules = self
+            .rules
+            .iter()
+            .map(|rule| {
+                let mut ownership = OwnershipRule::new(&rule.projects, &rule.owners)?;
+                if let Some(jobs) = rule.jobs.as_ref() {
+                    ownership = ownership.jobs(jobs)?;
+                }
+                Ok(ownership)
+            })
+            .collect::<Result<Vec<_>, RoutingError>>()?;
+        router = router.rules(rules);
+
+        for (name, conf) in &self.owners {
+            let mut owner = Owner::new(name, conf.channel.as_ref().unwrap_or(name));
+            if let Some(offset) = conf.utc_offset.as_ref() {
+                owner = owner.utc_offset(ci_monitor_analysis::parse_utc_offset(offset)?);
+            }
+            if let Some(quiet_hours) = conf.quiet_hours.as_ref() {
+                owner = owner.quiet_hours(QuietHours::parse(quiet_hours)?);
+            }
+            for away in &conf.away {
+                owner = owner.away(away.start, away.end);
+            }
+            router = router.owner(owner);
+        }
+
+        Ok(router)
+    }
+}
+
+/// A rule raising alerts when a condition exceeds a threshold.
+#[derive(Debug, Deserialize)]
+pub struct AlertRuleConfig {
+    /// The name of the rule.
+    pub name: String,
+    /// The entities to measure (`pipelines` or `jobs`).
+    pub entity: String,
+    /// The condition to measure.
+    ///
+    /// One of `failure_rate`, `failures`, `queue_time` (jobs only; in seconds), or `duration`
+    /// (in seconds).
+    pub condition: String,
+    /// Alerts are raised when the measured value exceeds this.
+    pub threshold: f64,
+    /// The number of hours before the evaluation to measure (default: 24).
+    pub window: Option<u32>,
+    /// A glob pattern for the paths of projects.
+    pub projects: Option<String>,
+    /// A glob pattern for the names of jobs.
+    pub jobs: Option<String>,
+    /// A glob pattern for the refs of pipelines.
+    pub refs: Option<String>,
+    /// The channel to deliver alerts to (default: the owners of the project).
+    pub channel: Option<String>,
+}
+
+impl AlertRuleConfig {
+    fn rule(&self) -> Result<AlertRule, ConfigError> {
+        let entity = AlertEntity::from_name(&self.entity).ok_or_else(|| {
+            ConfigError::AlertEntity {
+                name: self.entity.clone(),
+            }
+        })?;
+        let condition = AlertCondition::from_name(&self.condition).ok_or_else(|| {
+            ConfigError::AlertCondition {
+                name: self.condition.clone(),
+            }
+        })?;
+
+        let mut rule = AlertRule::new(&self.name, entity, condition, self.threshold)?;
+        if let Some(window) = self.window {
+            rule = rule.window(chrono::Duration::hours(window.into()));
+        }
+        if let Some(projects) = self.projects.as_ref() {
+            rule = rule.projects(projects)?;
+        }
+        if let Some(jobs) = self.jobs.as_ref() {
+            rule = rule.jobs(jobs)?;
+        }
+        if let Some(refs) = self.refs.as_ref() {
+            rule = rule.refs(refs)?;
+        }
+        if let Some(channel) = self.channel.as_ref() {
+            rule = rule.channel(channel);
+        }
+        Ok(rule)
+    }
+}
+
+/// Configuration for a service.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ServiceConfig {
+    /// Glob patterns for the paths of the projects making up the service.
+    pub projects: Vec<String>,
+}
+
+/// A known-broken job whose failures are suppressed.
+#[derive(Debug, Deserialize)]
+pub struct QuarantineConfig {
+    /// A glob pattern for the paths of projects.
+    pub projects: String,
+    /// A glob pattern for the names of jobs.
+    pub job: String,
+    /// When the quarantine starts.
+    pub start: DateTime<Utc>,
+    /// When the quarantine ends.
+    pub end: DateTime<Utc>,
+    /// Why the job is quarantined.
+    pub reason: Option<String>,
+}
+
+/// Configuration for federating the summaries of several monitors.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct FederationConfig {
+    /// The name of this site in its published summary.
+    pub name: Option<String>,
+    /// URLs (or paths) of summaries published by other monitors keyed by their site name.
+    pub sites: BTreeMap<String, String>,
+    /// The maximum time (in seconds) to wait for each site (default: 30).
+    pub timeout: Option<u64>,
+    /// Summaries or crawls older than this (in hours) are reported as stale (default: 24).
+    pub stale_after: Option<u32>,
+}
+
+impl FederationConfig {
+    /// The maximum time to wait for each site.
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout.unwrap_or(30))
+    }
+
+    /// The age at which a site is considered stale.
+    pub fn stale_after(&self) -> chrono::Duration {
+        chrono::Duration::hours(self.stale_after.unwrap_or(24).into())
+    }
+}
+
+/// Configuration for deployment tracking.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct DeploymentsConfig {
+    /// The branch environments are compared against.
+    ///
+    /// Defaults to the default branch of each project.
+    pub branch: Option<String>,
+}
+
+impl DeploymentsConfig {
+    /// The branch environments are compared against.
+    ///
+    /// `None` compares against the default branch of each project.
+    pub fn branch(&self) -> Option<&str> {
+        self.branch.as_deref()
+    }
+}
+
+/// Configuration for the instances being monitored.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct InstancesConfig {
+    /// Canonical URLs of instances keyed by alternate URLs they are reachable under.
+    ///
+    /// Data fetched through an alias is stored under the canonical instance.
+    pub aliases: BTreeMap<String, String>,
+}
+
+impl InstancesConfig {
+    /// The aliases of instances.
+    pub fn aliases(&self) -> InstanceAliases {
+        self.aliases
+            .iter()
+            .fold(InstanceAliases::default(), |aliases, (alias, canonical)| {
+                aliases.alias(alias, canonical)
+            })
+    }
+}
+
+/// Configuration for signing the store.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct SigningConfig {
+    /// Path to a file holding the hex-encoded ed25519 secret key to sign the store with.
+    ///
+    /// When set, the index and manifests of the store are signed whenever it is saved.
+    pub key: Option<PathBuf>,
+    /// The hex-encoded ed25519 public key to verify the store with.
+    ///
+    /// Defaults to the public key of `key`. Allows auditing a store without access to the secret
+    /// key.
+    pub public_key: Option<String>,
+}
+
+impl SigningConfig {
+    /// The key to sign the store with.
+    pub fn signing_key(&self) -> Result<Option<StoreSigningKey>, ConfigError> {
+        let path = if let Some(path) = self.key.as_ref() {
+            path
+        } else {
+            return Ok(None);
+        };
+
+        let contents = fs::read_to_string(path)
+            .map_err(|err| ConfigError::read_signing_key(path.into(), err))?;
+        StoreSigningKey::from_hex(&contents)
+            .map(Some)
+            .map_err(|err| ConfigError::signing_key(path.into(), err))
+    }
+
+    /// The key to verify the store with.
+    pub fn verifying_key(&self) -> Result<Option<StoreVerifyingKey>, ConfigError> {
+        if let Some(public_key) = self.public_key.as_ref() {
+            Ok(Some(StoreVerifyingKey::from_hex(public_key)?))
+        } else {
+            Ok(self.signing_key()?.map(|key| key.verifying_key()))
+        }
+    }
+}
+
+/// Configuration for the monitor.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Artifact handling.
+    pub artifacts: ArtifactsConfig,
+    /// Task execution.
+    pub tasks: TasksConfig,
+    /// Runner metadata.
+    pub runners: RunnersConfig,
+    /// Job log backfilling.
+    pub backfill: BackfillConfig,
+    /// Pipeline policies.
+    pub policies: PoliciesConfig,
+    /// Failure notification routing.
+    pub notifications: NotificationsConfig,
+    /// Rules raising alerts over the store.
+    ///
+    /// Alerts are delivered through notification routing.
+    pub alerts: Vec<AlertRuleConfig>,
+    /// Deployment tracking.
+    pub deployments: DeploymentsConfig,
+    /// Monitored instances.
+    pub instances: InstancesConfig,
+    /// Paths of groups whose projects are monitored.
+    ///
+    /// Each crawl discovers projects added to the groups and marks projects which have left them
+    /// as deleted.
+    pub groups: Vec<String>,
+    /// Whether the token has administrator access to the instance.
+    ///
+    /// Crawls then discover all users and runners on the instance rather than only those
+    /// encountered through monitored projects.
+    pub admin: bool,
+    /// Whether pushes to monitored projects are scanned.
+    ///
+    /// Pushes which did not produce a pipeline can then be reported.
+    pub scan_pushes: bool,
+    /// Services keyed by their name.
+    ///
+    /// Projects matching several services belong to the first by name.
+    pub services: BTreeMap<String, ServiceConfig>,
+    /// Known-broken jobs whose failures are excluded from failure rates and notifications.
+    pub quarantine: Vec<QuarantineConfig>,
+    /// Summaries of other monitors to aggregate.
+    pub federation: FederationConfig,
+    /// Signing of the store for tamper evidence.
+    pub signing: SigningConfig,
+    /// Faults to inject into forge tasks.
+    pub faults: FaultsConfig,
+}
+
+impl Config {
+    /// Load configuration from a file.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let contents =
+            fs::read_to_string(path).map_err(|err| ConfigError::read(path.into(), err))?;
+        toml::from_str(&contents).map_err(|err| ConfigError::parse(path.into(), err))
+    }
+
+    /// The mapping of projects to services.
+    pub fn service_map(&self) -> Result<ServiceMap, ConfigError> {
+        self.services
+            .iter()
+            .try_fold(ServiceMap::default(), |services, (name, conf)| {
+                services.service(name, &conf.projects)
+            })
+            .map_err(Into::into)
+    }
+
+    /// The alert rules.
+    pub fn alert_rules(&self) -> Result<Vec<AlertRule>, ConfigError> {
+        self.alerts.iter().map(AlertRuleConfig::rule).collect()
+    }
+
+    /// The quarantined jobs.
+    pub fn quarantine(&self) -> Result<Quarantine, ConfigError> {
+        self.quarantine
+            .iter()
+            .try_fold(Quarantine::default(), |quarantine, conf| {
+                let mut job = QuarantinedJob::new(&conf.projects, &conf.job, conf.start, conf.end)?;
+                if let Some(reason) = conf.reason.as_ref() {
+                    job = job.reason(reason);
+                }
+                Ok(quarantine.job(job))
+            })
+    }
+
+    /// The tasks which start a crawl.
+    ///
+    /// Instance-wide enumeration of runners and users is only done with administrator access
+    /// since the endpoints require it.
+    pub fn crawl_tasks(&self) -> Vec<ForgeTask> {
+        let mut tasks = vec![ForgeTask::UpdateProject {
+            project: 13,
+        }];
+        if self.admin {
+            tasks.extend([ForgeTask::DiscoverRunners, ForgeTask::DiscoverUsers]);
+        }
+        tasks.extend(self.groups.iter().map(|group| {
+            ForgeTask::DiscoverGroupProjects {
+                group: group.clone(),
+            }
+        }));
+        tasks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ci_monitor_forge::ForgeTask;
+
+    use crate::config::Config;
+
+    fn is_admin_only(task: &ForgeTask) -> bool {
+        matches!(task, ForgeTask::DiscoverRunners | ForgeTask::DiscoverUsers)
+    }
+
+    #[test]
+    fn crawl_tasks_without_admin() {
+        let config: Config = toml::from_str("groups = [\"group\"]").unwrap();
+        let tasks = config.crawl_tasks();
+
+        assert!(!tasks.iter().any(is_admin_only));
+        assert!(tasks.iter().any(|task| {
+            matches!(task, ForgeTask::DiscoverGroupProjects { group } if group == "group")
+        }));
+    }
+
+    #[test]
+    fn crawl_tasks_with_admin() {
+        let config: Config = toml::from_str("admin = true").unwrap();
+        let tasks = config.crawl_tasks();
+
+        assert_eq!(tasks.iter().filter(|task| is_admin_only(task)).count(), 2);
+    }
+}
@@ -0,0 +1,398 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A self-contained dashboard.
+//!
+//! A single-page UI is embedded into the binary and served alongside a read-only JSON API over the
+//! store. The store is reloaded in the background whenever it is written (e.g., by a crawl); each
+//! request is answered from a single snapshot of the store.
+
+use std::cmp::Reverse;
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::{Path, Query, State};
+use axum::http::{header, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::Utc;
+use ci_monitor_analysis::EnvironmentDrift;
+use ci_monitor_core::data::{Instance, Job, Pipeline, Project, Runner, RunnerHost};
+use ci_monitor_core::Lookup;
+use ci_monitor_forge::Heartbeat;
+use ci_monitor_persistence::{DiscoverableLookup, SharedVecLookup, VecLookup, VecStore};
+use rust_embed::RustEmbed;
+use serde::Deserialize;
+
+#[cfg(feature = "search")]
+use ci_monitor_persistence::SearchIndexError;
+
+#[cfg(feature = "search")]
+use crate::exit::RunError;
+use crate::federation;
+#[cfg(feature = "search")]
+use crate::output::SearchHitOutput;
+use crate::output::{
+    DashboardJobOutput, DashboardPipelineOutput, DashboardRunnerOutput, SiteSummaryOutput,
+};
+
+/// The assets of the dashboard UI.
+#[derive(RustEmbed)]
+#[folder = "dashboard/"]
+struct Assets;
+
+/// The number of pipelines listed when no limit is given.
+const DEFAULT_PIPELINE_LIMIT: usize = 50;
+/// The number of search results listed when no limit is given.
+#[cfg(feature = "search")]
+const DEFAULT_SEARCH_LIMIT: usize = 20;
+/// How often to check whether the store has been written.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(30);
+
+struct Dashboard {
+    name: String,
+    path: PathBuf,
+    heartbeat: PathBuf,
+    branch: Option<String>,
+    storage: SharedVecLookup,
+}
+
+type DashboardState = State<Arc<Dashboard>>;
+
+/// The URL of an instance.
+///
+/// Forge IDs are only unique within an instance, so entities are identified by both.
+fn instance_url(
+    storage: &VecLookup,
+    instance: &<VecLookup as Lookup<Instance>>::Index,
+) -> Option<String> {
+    <VecLookup as Lookup<Instance>>::lookup(storage, instance).map(|instance| instance.url.clone())
+}
+
+/// An error response.
+fn error<E>(status: StatusCode, err: E) -> Response
+where
+    E: ToString,
+{
+    (status, err.to_string()).into_response()
+}
+
+async fn summary(State(dashboard): DashboardState) -> Result<Json<SiteSummaryOutput>, Response> {
+    let heartbeat = Heartbeat::read(&dashboard.heartbeat)
+        .map_err(|err| error(StatusCode::INTERNAL_SERVER_ERROR, err))?;
+    federation::summarize(
+        &dashboard.name,
+        &dashboard.path,
+        &dashboard.storage.snapshot(),
+        heartbeat,
+    )
+    .map(Json)
+    .map_err(|err| error(StatusCode::INTERNAL_SERVER_ERROR, err))
+}
+
+#[derive(Debug, Deserialize)]
+struct PipelinesQuery {
+    /// The path of the project to list pipelines of.
+    project: Option<String>,
+    /// The maximum number of pipelines to list.
+    limit: Option<usize>,
+}
+
+/// List the most recent pipelines.
+async fn pipelines(
+    State(dashboard): DashboardState,
+    Query(query): Query<PipelinesQuery>,
+) -> Json<Vec<DashboardPipelineOutput>> {
+    let snapshot = dashboard.storage.snapshot();
+    let storage = snapshot.as_ref();
+    let mut pipelines =
+        <VecLookup as DiscoverableLookup<Pipeline<VecLookup>>>::all_indices(storage)
+            .iter()
+            .filter_map(|idx| <VecLookup as Lookup<Pipeline<VecLookup>>>::lookup(storage, idx))
+            .filter(|pipeline| pipeline.cim_deleted_at.is_none())
+            .filter_map(|pipeline| {
+                let project =
+                    <VecLookup as Lookup<Project<VecLookup>>>::lookup(storage, &pipeline.project)?;
+                if query
+                    .project
+                    .as_ref()
+                    .is_some_and(|path| *path != project.instance_path)
+                {
+                    return None;
+                }
+
+                Some(DashboardPipelineOutput {
+                    id: pipeline.forge_id,
+                    instance: instance_url(storage, &project.instance)?,
+                    project: project.instance_path.clone(),
+                    refname: pipeline.refname.clone(),
+                    sha: pipeline.sha.clone(),
+                    status: pipeline.status.name(),
+                    url: pipeline.url.clone(),
+                    created_at: pipeline.created_at,
+                    finished_at: pipeline.finished_at,
+                })
+            })
+            .collect::<Vec<_>>();
+    pipelines.sort_by_key(|pipeline| Reverse(pipeline.created_at));
+    pipelines.truncate(query.limit.unwrap_or(DEFAULT_PIPELINE_LIMIT));
+
+    Json(pipelines)
+}
+
+#[derive(Debug, Deserialize)]
+struct PipelineJobsQuery {
+    /// The URL of the instance of the pipeline.
+    instance: String,
+}
+
+/// List the jobs of a pipeline.
+async fn pipeline_jobs(
+    State(dashboard): DashboardState,
+    Path(id): Path<u64>,
+    Query(query): Query<PipelineJobsQuery>,
+) -> Result<Json<Vec<DashboardJobOutput>>, Response> {
+    let snapshot = dashboard.storage.snapshot();
+    let storage = snapshot.as_ref();
+    let pipeline = <VecLookup as DiscoverableLookup<Pipeline<VecLookup>>>::all_indices(storage)
+        .into_iter()
+        .find(|idx| {
+            <VecLookup as Lookup<Pipeline<VecLookup>>>::lookup(storage, idx)
+                .filter(|pipeline| pipeline.forge_id == id)
+                .and_then(|pipeline| {
+                    <VecLookup as Lookup<Project<VecLookup>>>::lookup(storage, &pipeline.project)
+                })
+                .and_then(|project| instance_url(storage, &project.instance))
+                .is_some_and(|instance| instance == query.instance)
+        })
+        .ok_or_else(|| {
+            error(
+                StatusCode::NOT_FOUND,
+                format!("no such pipeline on {}: #{}", query.instance, id),
+            )
+        })?;
+
+    let mut jobs = <VecLookup as DiscoverableLookup<Job<VecLookup>>>::all_indices(storage)
+        .iter()
+        .filter_map(|idx| <VecLookup as Lookup<Job<VecLookup>>>::lookup(storage, idx))
+        .filter(|job| job.cim_deleted_at.is_none() && job.pipeline == pipeline)
+        .map(|job| {
+            let runner = job
+                .runner
+                .as_ref()
+                .and_then(|runner| {
+                    <VecLookup as Lookup<Runner<VecLookup>>>::lookup(storage, runner)
+                })
+                .map(|runner| runner.description.clone());
+
+            DashboardJobOutput {
+                id: job.forge_id,
+                name: job.name.clone(),
+                stage: job.stage.clone(),
+                state: job.state.name(),
+                allow_failure: job.allow_failure,
+                runner,
+                url: job.url.clone(),
+                started_at: job.started_at,
+                finished_at: job.finished_at,
+            }
+        })
+        .collect::<Vec<_>>();
+    jobs.sort_by_key(|job| job.id);
+
+    Ok(Json(jobs))
+}
+
+/// List the runners.
+async fn runners(State(dashboard): DashboardState) -> Json<Vec<DashboardRunnerOutput>> {
+    let snapshot = dashboard.storage.snapshot();
+    let storage = snapshot.as_ref();
+    let mut runners = <VecLookup as DiscoverableLookup<Runner<VecLookup>>>::all_indices(storage)
+        .iter()
+        .filter_map(|idx| <VecLookup as Lookup<Runner<VecLookup>>>::lookup(storage, idx))
+        .filter_map(|runner| {
+            let host = runner
+                .runner_host
+                .as_ref()
+                .and_then(|host| <VecLookup as Lookup<RunnerHost>>::lookup(storage, host))
+                .map(|host| host.name.clone());
+
+            Some(DashboardRunnerOutput {
+                id: runner.forge_id,
+                instance: instance_url(storage, &runner.instance)?,
+                description: runner.description.clone(),
+                host,
+                platform: runner.platform.clone(),
+                architecture: runner.architecture.clone(),
+                version: runner.version.clone(),
+                tags: runner.tags.clone(),
+                online: runner.online,
+                paused: runner.paused,
+                contacted_at: runner.contacted_at,
+            })
+        })
+        .collect::<Vec<_>>();
+    runners.sort_by(|a, b| a.instance.cmp(&b.instance).then(a.id.cmp(&b.id)));
+
+    Json(runners)
+}
+
+#[derive(Debug, Deserialize)]
+struct DriftQuery {
+    /// The branch to compare environments against.
+    branch: Option<String>,
+}
+
+/// List how far environments are behind a branch.
+async fn drift(
+    State(dashboard): DashboardState,
+    Query(query): Query<DriftQuery>,
+) -> Json<Vec<EnvironmentDrift>> {
+    let branch = query.branch.as_deref().or(dashboard.branch.as_deref());
+    let snapshot = dashboard.storage.snapshot();
+    let drift = ci_monitor_analysis::environment_drift(snapshot.as_ref(), branch, Utc::now());
+
+    Json(drift)
+}
+
+#[cfg(feature = "search")]
+#[derive(Debug, Deserialize)]
+struct SearchQuery {
+    /// The query.
+    q: String,
+    /// The kind of entity to search for.
+    kind: Option<String>,
+    /// The maximum number of results.
+    limit: Option<usize>,
+}
+
+/// Search the search index of the store.
+#[cfg(feature = "search")]
+async fn search(
+    State(dashboard): DashboardState,
+    Query(query): Query<SearchQuery>,
+) -> Result<Json<Vec<SearchHitOutput>>, Response> {
+    let limit = query.limit.unwrap_or(DEFAULT_SEARCH_LIMIT);
+    crate::search::search(&dashboard.path, &query.q, query.kind.as_deref(), limit)
+        .map(Json)
+        .map_err(|err| {
+            let status = match err {
+                RunError::NoSearchIndex => StatusCode::NOT_FOUND,
+                RunError::Search {
+                    source:
+                        SearchIndexError::Query {
+                            ..
+                        },
+                } => StatusCode::BAD_REQUEST,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            error(status, err)
+        })
+}
+
+/// The content type of an asset.
+fn content_type(path: &str) -> &'static str {
+    match path.rsplit_once('.').map(|(_, ext)| ext) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("ico") => "image/x-icon",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Serve an embedded asset.
+///
+/// Paths which are not assets serve the UI so that it can handle its own routes.
+async fn asset(uri: Uri) -> Response {
+    let path = uri.path().trim_start_matches('/');
+    if path.starts_with("api/") {
+        return error(StatusCode::NOT_FOUND, "no such endpoint");
+    }
+
+    let (path, file) = match Assets::get(path) {
+        Some(file) if !path.is_empty() => (path, file),
+        _ => {
+            if let Some(file) = Assets::get("index.html") {
+                ("index.html", file)
+            } else {
+                return error(StatusCode::NOT_FOUND, "the dashboard is missing its UI");
+            }
+        },
+    };
+
+    ([(header::CONTENT_TYPE, content_type(path))], file.data).into_response()
+}
+
+/// Reload the store whenever it is written.
+///
+/// Requests which are in progress keep using their snapshot of the store.
+async fn reload(dashboard: Arc<Dashboard>, mut generation: Option<u64>) {
+    let mut interval = tokio::time::interval(RELOAD_INTERVAL);
+    loop {
+        interval.tick().await;
+        let current = VecStore::generation(&dashboard.path).ok();
+        if current.is_none() || current == generation {
+            continue;
+        }
+
+        let path = dashboard.path.clone();
+        let loaded = tokio::task::spawn_blocking(move || VecStore::load(&path))
+            .await
+            .expect("loading the store does not panic");
+        match loaded {
+            Ok(storage) => {
+                dashboard.storage.replace(storage);
+                generation = current;
+            },
+            Err(err) => eprintln!("warning: failed to reload the store: {}", err),
+        }
+    }
+}
+
+/// Serve the dashboard until interrupted.
+///
+/// The `generation` is that of the store when `storage` was loaded from it.
+pub async fn serve(
+    addr: SocketAddr,
+    name: String,
+    path: PathBuf,
+    heartbeat: PathBuf,
+    branch: Option<String>,
+    generation: Option<u64>,
+    storage: VecLookup,
+) -> io::Result<()> {
+    let dashboard = Arc::new(Dashboard {
+        name,
+        path,
+        heartbeat,
+        branch,
+        storage: SharedVecLookup::new(storage),
+    });
+    tokio::spawn(reload(dashboard.clone(), generation));
+    let app = Router::new()
+        .route("/api/summary", get(summary))
+        .route("/api/pipelines", get(pipelines))
+        .route("/api/pipelines/:id/jobs", get(pipeline_jobs))
+        .route("/api/runners", get(runners))
+        .route("/api/drift", get(drift));
+    #[cfg(feature = "search")]
+    let app = app.route("/api/search", get(search));
+    let app = app.fallback(asset).with_state(dashboard);
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await
+}
@@ -0,0 +1,360 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use ci_monitor_core::data::{
+    ApiUsage, AuditEntry, AuditEntryBuilder, CrawlSession, Deployment, Environment, Instance, Job,
+    MergeRequest, Pipeline, PipelineSchedule, Project, Push, Release, Runner, RunnerHost, User,
+};
+use ci_monitor_core::Lookup;
+use ci_monitor_forge::{AutoscalerEvent, RunReport, RunnerHostData, RunnerHostRules};
+use ci_monitor_gitlab::InstanceAliases;
+use ci_monitor_persistence::{DiscoverableLookup, VecLookup};
+
+fn count_refreshed<T, F>(storage: &VecLookup, refreshed_at: F, since: DateTime<Utc>) -> u64
+where
+    VecLookup: DiscoverableLookup<T>,
+    F: Fn(&T) -> DateTime<Utc>,
+{
+    <VecLookup as DiscoverableLookup<T>>::all_indices(storage)
+        .iter()
+        .filter_map(|idx| <VecLookup as Lookup<T>>::lookup(storage, idx))
+        .filter(|entity| refreshed_at(entity) >= since)
+        .count() as u64
+}
+
+/// Record the entities which were refreshed during a run.
+pub fn record_refreshed(report: &mut RunReport, storage: &VecLookup) {
+    let since = report.started_at;
+
+    macro_rules! record {
+        ($name:expr, $type:ty) => {
+            report.record_entities(
+                $name,
+                count_refreshed(storage, |entity: &$type| entity.cim_refreshed_at, since),
+            );
+        };
+    }
+
+    record!("deployment", Deployment<VecLookup>);
+    record!("environment", Environment<VecLookup>);
+    record!("job", Job<VecLookup>);
+    record!("merge_request", MergeRequest<VecLookup>);
+    record!("pipeline", Pipeline<VecLookup>);
+    record!("pipeline_schedule", PipelineSchedule<VecLookup>);
+    record!("project", Project<VecLookup>);
+    record!("push", Push<VecLookup>);
+    record!("release", Release<VecLookup>);
+    record!("runner", Runner<VecLookup>);
+    record!("runner_host", RunnerHost);
+    record!("user", User<VecLookup>);
+}
+
+/// Record the crawl session which produced the store.
+///
+/// Returns the new revision of the store.
+pub fn record_crawl_session(
+    storage: &mut VecLookup,
+    instance: &<VecLookup as Lookup<Instance>>::Index,
+    report: &RunReport,
+) -> u64 {
+    let sessions = <VecLookup as DiscoverableLookup<CrawlSession<VecLookup>>>::all_indices(storage);
+    let revision = sessions
+        .iter()
+        .filter_map(|idx| <VecLookup as Lookup<CrawlSession<VecLookup>>>::lookup(storage, idx))
+        .map(|session| session.revision)
+        .max()
+        .map_or(1, |revision| revision + 1);
+    let instances = <VecLookup as DiscoverableLookup<Instance>>::all_indices(storage);
+    let api_usage = report
+        .api_requests_by_hour
+        .iter()
+        .map(|(&hour, &requests)| ApiUsage::new(*instance, hour, requests))
+        .collect();
+
+    let session = CrawlSession::builder()
+        .started_at(report.started_at)
+        .finished_at(Some(report.finished_at))
+        .tasks_executed(report.executed())
+        .tasks_failed(report.failed())
+        .tasks_canceled(report.canceled)
+        .instances(instances)
+        .api_usage(api_usage)
+        .revision(revision)
+        .build()
+        .unwrap();
+    storage.store(session);
+
+    revision
+}
+
+/// Record a manual action in the audit log of the store.
+///
+/// The entry is numbered after the existing entries.
+pub fn record_audit_entry(storage: &mut VecLookup, entry: AuditEntryBuilder) {
+    let entries = <VecLookup as DiscoverableLookup<AuditEntry>>::all_indices(storage);
+    let unique_id = entries
+        .iter()
+        .filter_map(|idx| <VecLookup as Lookup<AuditEntry>>::lookup(storage, idx))
+        .map(|entry| entry.unique_id)
+        .max()
+        .map_or(0, |unique_id| unique_id + 1);
+
+    let entry = entry.unique_id(unique_id).build().unwrap();
+    storage.store(entry);
+}
+
+/// Apply configured concurrency limits to stored runners.
+pub fn apply_runner_limits(storage: &mut VecLookup, limits: &BTreeMap<u64, u64>) {
+    let runners = <VecLookup as DiscoverableLookup<Runner<VecLookup>>>::all_indices(storage);
+    let updated = runners
+        .iter()
+        .filter_map(|idx| <VecLookup as Lookup<Runner<VecLookup>>>::lookup(storage, idx))
+        .filter_map(|runner| {
+            let limit = limits.get(&runner.forge_id).copied();
+            if limit.is_some() && runner.max_concurrent_jobs != limit {
+                let mut runner = runner.clone();
+                runner.max_concurrent_jobs = limit;
+                Some(runner)
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    for runner in updated {
+        storage.store(runner);
+    }
+}
+
+/// Assign runners to hosts according to heuristic rules.
+///
+/// Hosts which are not yet known are created. Runners which do not match any rule keep their
+/// current host. Returns the number of runners which were assigned to a new host.
+pub fn assign_runner_hosts(storage: &mut VecLookup, rules: &RunnerHostRules) -> usize {
+    if rules.is_empty() {
+        return 0;
+    }
+
+    let host_indices = <VecLookup as DiscoverableLookup<RunnerHost>>::all_indices(storage);
+    let mut next_unique_id = host_indices.len() as u64;
+    let mut hosts = host_indices
+        .into_iter()
+        .filter_map(|idx| {
+            <VecLookup as Lookup<RunnerHost>>::lookup(storage, &idx)
+                .map(|host| (host.name.clone(), idx))
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    let runners = <VecLookup as DiscoverableLookup<Runner<VecLookup>>>::all_indices(storage);
+    let matched = runners
+        .iter()
+        .filter_map(|idx| <VecLookup as Lookup<Runner<VecLookup>>>::lookup(storage, idx))
+        .filter_map(|runner| {
+            rules
+                .host_name(&runner.description, runner.ip_address.as_deref())
+                .map(|name| (runner.clone(), name))
+        })
+        .collect::<Vec<_>>();
+
+    let mut assigned = 0;
+    for (mut runner, name) in matched {
+        let host = if let Some(idx) = hosts.get(&name) {
+            *idx
+        } else {
+            let host = RunnerHost::builder()
+                .name(name.clone())
+                .unique_id(next_unique_id)
+                .build()
+                .unwrap();
+            next_unique_id += 1;
+            let idx = storage.store(host);
+            hosts.insert(name, idx);
+            idx
+        };
+
+        if runner.runner_host != Some(host) {
+            runner.runner_host = Some(host);
+            storage.store(runner);
+            assigned += 1;
+        }
+    }
+
+    assigned
+}
+
+/// Merge GitLab instances which were stored under different URLs of the same instance.
+///
+/// Instances are grouped by their canonical URL. Each group is merged into the instance stored
+/// under the canonical URL (or the first instance of the group), which is then renamed to the
+/// canonical URL. Returns the number of instances merged away and the number of references which
+/// were updated.
+pub fn merge_split_instances(storage: &mut VecLookup, aliases: &InstanceAliases) -> (usize, usize) {
+    let mut groups: BTreeMap<String, Vec<_>> = BTreeMap::new();
+    for idx in <VecLookup as DiscoverableLookup<Instance>>::all_indices(storage) {
+        if let Some(instance) = <VecLookup as Lookup<Instance>>::lookup(storage, &idx) {
+            if instance.forge == "gitlab" {
+                let canonical = aliases.canonical(&instance.url);
+                let exact = instance.url == canonical;
+                groups.entry(canonical).or_default().push((exact, idx));
+            }
+        }
+    }
+
+    let mut merged = 0;
+    let mut moved = 0;
+    for (url, mut group) in groups {
+        // Prefer the instance stored under the canonical URL; the sort is stable.
+        group.sort_by_key(|(exact, _)| !exact);
+        let (exact, into) = group[0];
+        if !exact {
+            if let Some(instance) = <VecLookup as Lookup<Instance>>::lookup(storage, &into) {
+                let mut instance = instance.clone();
+                instance.url = url;
+                storage.store(instance);
+            }
+        }
+        for (_, from) in &group[1..] {
+            moved += storage.merge_instances(from, &into);
+            merged += 1;
+        }
+    }
+
+    (merged, moved)
+}
+
+/// Apply configured metadata to runner hosts.
+///
+/// Hosts which are not yet known are created. Returns the number of hosts which were created.
+pub fn apply_runner_host_data(
+    storage: &mut VecLookup,
+    data: &BTreeMap<String, RunnerHostData>,
+) -> usize {
+    if data.is_empty() {
+        return 0;
+    }
+
+    let host_indices = <VecLookup as DiscoverableLookup<RunnerHost>>::all_indices(storage);
+    let mut next_unique_id = host_indices.len() as u64;
+    let mut hosts = host_indices
+        .iter()
+        .filter_map(|idx| <VecLookup as Lookup<RunnerHost>>::lookup(storage, idx))
+        .map(|host| (host.name.clone(), host.clone()))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut created = 0;
+    for (name, data) in data {
+        let mut host = if let Some(host) = hosts.remove(name) {
+            host
+        } else {
+            let host = RunnerHost::builder()
+                .name(name.clone())
+                .unique_id(next_unique_id)
+                .build()
+                .unwrap();
+            next_unique_id += 1;
+            created += 1;
+            host
+        };
+
+        if let Some(os) = &data.os {
+            host.os.clone_from(os);
+        }
+        if let Some(os_version) = &data.os_version {
+            host.os_version.clone_from(os_version);
+        }
+        if let Some(management) = &data.management {
+            host.management.clone_from(management);
+        }
+        if let Some(location) = &data.location {
+            host.location.clone_from(location);
+        }
+        if let Some(estimated_cost_per_hour) = data.estimated_cost_per_hour {
+            host.estimated_cost_per_hour = estimated_cost_per_hour;
+        }
+        if let Some(max_concurrent_jobs) = data.max_concurrent_jobs {
+            host.max_concurrent_jobs = max_concurrent_jobs;
+        }
+
+        storage.store(host);
+    }
+
+    created
+}
+
+/// Record autoscaler events against a runner host.
+///
+/// The host is created if it is not yet known. Returns the number of events which changed the
+/// host.
+pub fn record_autoscaler_events(
+    storage: &mut VecLookup,
+    name: &str,
+    events: &[AutoscalerEvent],
+) -> usize {
+    let host_indices = <VecLookup as DiscoverableLookup<RunnerHost>>::all_indices(storage);
+    let next_unique_id = host_indices.len() as u64;
+    let host = host_indices
+        .iter()
+        .filter_map(|idx| <VecLookup as Lookup<RunnerHost>>::lookup(storage, idx))
+        .find(|host| host.name == name)
+        .cloned();
+    let mut host = host.unwrap_or_else(|| {
+        RunnerHost::builder()
+            .name(name)
+            .unique_id(next_unique_id)
+            .build()
+            .unwrap()
+    });
+
+    let recorded = events
+        .iter()
+        .filter(|event| {
+            host.record_autoscaler_event(&event.instance, &event.executor, event.kind, event.at)
+        })
+        .count();
+    if recorded > 0 {
+        host.cim_refreshed_at = Utc::now();
+        storage.store(host);
+    }
+
+    recorded
+}
+
+/// Count the projects which were noticed to be missing from watched groups since a time.
+pub fn projects_removed_from_groups(storage: &VecLookup, since: DateTime<Utc>) -> usize {
+    let projects = <VecLookup as DiscoverableLookup<Project<VecLookup>>>::all_indices(storage);
+    projects
+        .iter()
+        .filter_map(|idx| <VecLookup as Lookup<Project<VecLookup>>>::lookup(storage, idx))
+        .filter_map(|project| project.cim_removed_from_group_at)
+        .filter(|&when| since <= when)
+        .count()
+}
+
+/// Cascade deletion markers on pipelines to their jobs and deployments.
+///
+/// Reconciliation only marks the pipeline itself when it is found to be missing from the forge.
+/// Returns the number of pipelines marked as deleted since the given time.
+pub fn cascade_pipeline_deletions(storage: &mut VecLookup, since: DateTime<Utc>) -> usize {
+    let pipelines = <VecLookup as DiscoverableLookup<Pipeline<VecLookup>>>::all_indices(storage);
+    let deleted = pipelines
+        .into_iter()
+        .filter_map(|idx| {
+            <VecLookup as Lookup<Pipeline<VecLookup>>>::lookup(storage, &idx)
+                .and_then(|pipeline| pipeline.cim_deleted_at)
+                .filter(|&when| since <= when)
+                .map(|when| (idx, when))
+        })
+        .collect::<Vec<_>>();
+
+    let newly_deleted = deleted.len();
+    for (idx, when) in deleted {
+        storage.soft_delete_pipeline(&idx, when);
+    }
+    newly_deleted
+}
@@ -0,0 +1,313 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use ci_monitor_analysis::{ActionsUsageError, VariableComparisonError};
+use ci_monitor_forge::{
+    AutoscalerLogError, ForgeError, ForgeErrorKind, HeartbeatError, RunReport, RunReportError,
+};
+use ci_monitor_gitlab::NotificationError;
+#[cfg(feature = "duckdb")]
+use ci_monitor_persistence::DuckDbExportError;
+#[cfg(feature = "kv")]
+use ci_monitor_persistence::KvStoreError;
+#[cfg(feature = "search")]
+use ci_monitor_persistence::SearchIndexError;
+use ci_monitor_persistence::{BlobPersistenceVerifyError, VecStoreError};
+use thiserror::Error;
+
+use crate::config::ConfigError;
+
+/// An unclassified error.
+pub const FAILURE: u8 = 1;
+/// Authentication with the forge failed.
+///
+/// Note that `2` is used by `clap` for usage errors.
+pub const AUTH: u8 = 3;
+/// Some tasks failed or were canceled during the crawl or federated sites were unreachable.
+pub const PARTIAL: u8 = 4;
+/// The store could not be loaded or saved.
+pub const STORE: u8 = 5;
+/// Entity files of the store or blobs referenced by it are missing, corrupt, or do not match the
+/// store's signatures.
+pub const CORRUPT: u8 = 6;
+/// Stored pipelines violate a configured policy.
+pub const VIOLATIONS: u8 = 7;
+/// The forge could not be reached.
+pub const CONNECT: u8 = 8;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum RunError {
+    #[error("configuration error: {}", source)]
+    Config {
+        #[from]
+        source: ConfigError,
+    },
+    #[error("no token was given to access the forge")]
+    NoToken,
+    #[error("failed to connect to the forge: {}", details)]
+    Connect { details: String },
+    #[error("failed to query the token: {}", source)]
+    Token {
+        #[source]
+        source: ForgeError,
+    },
+    #[error("store error: {}", source)]
+    Store {
+        #[from]
+        source: VecStoreError,
+    },
+    #[error("no blob store is configured")]
+    NoBlobStore,
+    #[error("no cold blob store is configured")]
+    NoColdBlobStore,
+    #[error("no signing key is configured")]
+    NoSigningKey,
+    #[error("failed to rehash blobs: {}", source)]
+    Rehash {
+        #[from]
+        source: BlobPersistenceVerifyError,
+    },
+    #[error("failed to archive job logs: {}", source)]
+    Archive {
+        #[source]
+        source: BlobPersistenceVerifyError,
+    },
+    #[error("no store was given")]
+    NoStore,
+    #[cfg(not(feature = "duckdb"))]
+    #[error("DuckDB support is not enabled in this build")]
+    NoDuckDb,
+    #[cfg(feature = "duckdb")]
+    #[error("failed to export the store: {}", source)]
+    Export {
+        #[from]
+        source: DuckDbExportError,
+    },
+    #[cfg(not(feature = "kv"))]
+    #[error("key-value stores are not enabled in this build")]
+    NoKv,
+    #[cfg(feature = "kv")]
+    #[error("key-value store error: {}", source)]
+    Kv {
+        #[from]
+        source: KvStoreError,
+    },
+    #[error(
+        "{} is not supported for stores kept in a key-value database",
+        operation
+    )]
+    KvUnsupported { operation: &'static str },
+    #[cfg(not(feature = "search"))]
+    #[error("full-text search is not enabled in this build")]
+    NoSearch,
+    #[cfg(feature = "search")]
+    #[error("the store has no search index; build it with `search-index`")]
+    NoSearchIndex,
+    #[cfg(feature = "search")]
+    #[error("search failed: {}", source)]
+    Search {
+        #[from]
+        source: SearchIndexError,
+    },
+    #[error("not a pipeline URL: {}", url)]
+    PipelineUrl { url: String },
+    #[error("failed to resolve the project: {}", source)]
+    ResolveProject {
+        #[source]
+        source: ForgeError,
+    },
+    #[error("the capture directory '{}' is not empty", path.display())]
+    CaptureExists { path: PathBuf },
+    #[error("no such {} in the store: #{}", kind, id)]
+    MissingEntity { kind: String, id: u64 },
+    #[error("failed to compare pipelines: {}", source)]
+    Compare {
+        #[from]
+        source: VariableComparisonError,
+    },
+    #[error("failed to write the run report: {}", source)]
+    Report {
+        #[from]
+        source: RunReportError,
+    },
+    #[error("failed to update the heartbeat: {}", source)]
+    Heartbeat {
+        #[from]
+        source: HeartbeatError,
+    },
+    #[error("failed to write metrics to '{}': {}", path.display(), source)]
+    Metrics {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("failed to read '{}': {}", path.display(), source)]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("invalid GitHub usage report: {}", source)]
+    ActionsUsage {
+        #[from]
+        source: ActionsUsageError,
+    },
+    #[error("failed to ingest notification emails: {}", source)]
+    Notification {
+        #[from]
+        source: NotificationError,
+    },
+    #[error("invalid autoscaler events: {}", source)]
+    AutoscalerLog {
+        #[from]
+        source: AutoscalerLogError,
+    },
+    #[error("failed to set up the federation client: {}", source)]
+    Federation {
+        #[from]
+        source: reqwest::Error,
+    },
+    #[error("failed to write the summary to '{}': {}", path.display(), source)]
+    Summary {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("failed to serve the dashboard on {}: {}", addr, source)]
+    Dashboard {
+        addr: SocketAddr,
+        #[source]
+        source: io::Error,
+    },
+    #[error("failed to serialize output: {}", source)]
+    Output {
+        #[from]
+        source: serde_json::Error,
+    },
+}
+
+impl RunError {
+    pub fn connect<E>(err: E) -> Self
+    where
+        E: ToString,
+    {
+        Self::Connect {
+            details: err.to_string(),
+        }
+    }
+
+    pub fn token(source: ForgeError) -> Self {
+        Self::Token {
+            source,
+        }
+    }
+
+    pub fn archive(source: BlobPersistenceVerifyError) -> Self {
+        Self::Archive {
+            source,
+        }
+    }
+
+    pub fn read(path: PathBuf, source: io::Error) -> Self {
+        Self::Read {
+            path,
+            source,
+        }
+    }
+
+    pub fn metrics(path: PathBuf, source: io::Error) -> Self {
+        Self::Metrics {
+            path,
+            source,
+        }
+    }
+
+    pub fn summary(path: PathBuf, source: io::Error) -> Self {
+        Self::Summary {
+            path,
+            source,
+        }
+    }
+
+    pub fn dashboard(addr: SocketAddr, source: io::Error) -> Self {
+        Self::Dashboard {
+            addr,
+            source,
+        }
+    }
+
+    /// The exit code for the error.
+    pub fn exit_code(&self) -> ExitCode {
+        let code = match self {
+            Self::Config {
+                source:
+                    ConfigError::CreateBlobs {
+                        ..
+                    }
+                    | ConfigError::Blobs {
+                        ..
+                    },
+            } => STORE,
+            Self::Connect {
+                ..
+            }
+            | Self::Token {
+                source: ForgeError::Connection {
+                    ..
+                },
+            }
+            | Self::ResolveProject {
+                source: ForgeError::Connection {
+                    ..
+                },
+            } => CONNECT,
+            Self::NoToken
+            | Self::Token {
+                source: ForgeError::Auth {
+                    ..
+                },
+            }
+            | Self::ResolveProject {
+                source: ForgeError::Auth {
+                    ..
+                },
+            } => AUTH,
+            Self::Store {
+                ..
+            }
+            | Self::NoStore => STORE,
+            #[cfg(feature = "kv")]
+            Self::Kv {
+                ..
+            } => STORE,
+            _ => FAILURE,
+        };
+
+        code.into()
+    }
+}
+
+/// The exit code for a completed run.
+pub fn for_report(report: &RunReport) -> ExitCode {
+    if report
+        .failures
+        .iter()
+        .any(|failure| failure.error == ForgeErrorKind::Auth)
+    {
+        AUTH.into()
+    } else if report.failed() > 0 || report.partial() > 0 || report.lost > 0 || report.canceled > 0
+    {
+        PARTIAL.into()
+    } else {
+        ExitCode::SUCCESS
+    }
+}
@@ -0,0 +1,162 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Federation of several monitors.
+//!
+//! Each site publishes a small summary of its store (e.g., by serving the output of the `summary`
+//! command over HTTP) and a central monitor aggregates them without copying the stores.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use chrono::{DateTime, Duration, Utc};
+use ci_monitor_analysis::{FailureRate, RunnerHealth};
+use ci_monitor_forge::Heartbeat;
+use ci_monitor_persistence::{VecLookup, VecStore, VecStoreError};
+
+use crate::config::FederationConfig;
+use crate::exit::RunError;
+use crate::output::{FederationReportOutput, SiteStatusOutput, SiteSummaryOutput};
+
+/// Summarize a store for publication.
+pub fn summarize(
+    name: &str,
+    path: &Path,
+    storage: &VecLookup,
+    heartbeat: Option<Heartbeat>,
+) -> Result<SiteSummaryOutput, VecStoreError> {
+    let entities = match VecStore::counts(path) {
+        Ok(counts) => {
+            counts
+                .into_iter()
+                .map(|(ty, count)| (ty.name().into(), count))
+                .collect()
+        },
+        // An unpopulated store has no entities.
+        Err(VecStoreError::Io {
+            source,
+        }) if source.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+        Err(err) => return Err(err),
+    };
+    let report = ci_monitor_analysis::combined_report([(name, storage)]);
+
+    Ok(SiteSummaryOutput {
+        name: name.into(),
+        generated_at: Utc::now(),
+        entities,
+        runners: report.runners,
+        pipelines: report.pipelines,
+        jobs: report.jobs,
+        heartbeat,
+    })
+}
+
+/// Write a summary to a file.
+///
+/// The file is replaced atomically so that it may be served at any time.
+pub fn write_summary(path: &Path, summary: &SiteSummaryOutput) -> io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, serde_json::to_vec_pretty(summary)?)?;
+    fs::rename(&tmp, path)
+}
+
+/// Fetch the summary of a site.
+///
+/// Locations which are not HTTP URLs are read as paths.
+async fn fetch_summary(client: reqwest::Client, url: String) -> Result<SiteSummaryOutput, String> {
+    if url.starts_with("http://") || url.starts_with("https://") {
+        async {
+            client
+                .get(&url)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await
+        }
+        .await
+        .map_err(|err: reqwest::Error| err.to_string())
+    } else {
+        let data = fs::read(&url).map_err(|err| err.to_string())?;
+        serde_json::from_slice(&data).map_err(|err| err.to_string())
+    }
+}
+
+/// Whether a summary is older than the staleness threshold.
+///
+/// A summary is also stale if the site's monitor has not written a heartbeat recently.
+fn is_stale(summary: &SiteSummaryOutput, now: DateTime<Utc>, stale_after: Duration) -> bool {
+    let heartbeat_at = summary.heartbeat.as_ref().map(|heartbeat| heartbeat.at);
+    summary.generated_at + stale_after < now
+        || heartbeat_at.is_some_and(|at| at + stale_after < now)
+}
+
+/// Fetch and aggregate the summaries of the configured sites.
+///
+/// Sites are queried concurrently. Totals only include sites whose summary could be fetched.
+pub async fn federate(config: &FederationConfig) -> Result<FederationReportOutput, RunError> {
+    let client = reqwest::Client::builder()
+        .timeout(config.timeout())
+        .build()?;
+
+    let fetches = config
+        .sites
+        .iter()
+        .map(|(name, url)| {
+            let fetch = tokio::spawn(fetch_summary(client.clone(), url.clone()));
+            (name, url, fetch)
+        })
+        .collect::<Vec<_>>();
+
+    let now = Utc::now();
+    let stale_after = config.stale_after();
+    let mut entities = BTreeMap::new();
+    let mut runners = RunnerHealth::default();
+    let mut pipelines = FailureRate::default();
+    let mut jobs = FailureRate::default();
+    let mut sites = Vec::with_capacity(fetches.len());
+    for (name, url, fetch) in fetches {
+        let summary = fetch.await.unwrap_or_else(|err| Err(err.to_string()));
+        let status = match summary {
+            Ok(summary) => {
+                for (ty, count) in &summary.entities {
+                    *entities.entry(ty.clone()).or_default() += count;
+                }
+                runners.merge(&summary.runners);
+                pipelines.merge(&summary.pipelines);
+                jobs.merge(&summary.jobs);
+
+                SiteStatusOutput {
+                    name: name.clone(),
+                    url: url.clone(),
+                    error: None,
+                    stale: is_stale(&summary, now, stale_after),
+                    summary: Some(summary),
+                }
+            },
+            Err(err) => {
+                SiteStatusOutput {
+                    name: name.clone(),
+                    url: url.clone(),
+                    error: Some(err),
+                    stale: true,
+                    summary: None,
+                }
+            },
+        };
+        sites.push(status);
+    }
+
+    Ok(FederationReportOutput {
+        sites,
+        entities,
+        runners,
+        pipelines,
+        jobs,
+    })
+}
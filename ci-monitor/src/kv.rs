@@ -0,0 +1,58 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Storage of entities in an embedded key-value database.
+//!
+//! A store keeps its entities in the `entities.redb` database of the store directory instead of as
+//! JSON files if the database exists. `store convert` moves the entities of a store between the
+//! two. Requires the `kv` feature.
+
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "kv")]
+use ci_monitor_persistence::KvStore;
+use ci_monitor_persistence::VecLookup;
+
+use crate::exit::RunError;
+
+/// The key-value database within a store.
+const KV_STORE_NAME: &str = "entities.redb";
+
+/// The path to the key-value database of a store.
+pub fn kv_path(path: &Path) -> PathBuf {
+    path.join(KV_STORE_NAME)
+}
+
+/// Whether a store keeps its entities in a key-value database.
+pub fn is_kv_store(path: &Path) -> bool {
+    kv_path(path).is_file()
+}
+
+/// Load the entities of a store from its key-value database.
+#[cfg(feature = "kv")]
+pub fn load(path: &Path) -> Result<VecLookup, RunError> {
+    Ok(KvStore::open(&kv_path(path))?.load()?)
+}
+
+/// Load the entities of a store from its key-value database.
+#[cfg(not(feature = "kv"))]
+pub fn load(_: &Path) -> Result<VecLookup, RunError> {
+    Err(RunError::NoKv)
+}
+
+/// Save the entities of a store into its key-value database, creating it if needed.
+#[cfg(feature = "kv")]
+pub fn save(path: &Path, storage: &VecLookup) -> Result<(), RunError> {
+    KvStore::create(&kv_path(path))?.store(storage)?;
+
+    Ok(())
+}
+
+/// Save the entities of a store into its key-value database, creating it if needed.
+#[cfg(not(feature = "kv"))]
+pub fn save(_: &Path, _: &VecLookup) -> Result<(), RunError> {
+    Err(RunError::NoKv)
+}
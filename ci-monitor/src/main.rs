@@ -4,128 +4,87 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::error::Error;
-use std::mem;
-use std::num::NonZeroU32;
-use std::sync::Arc;
-use std::time::Duration;
-
-use ci_monitor_forge::{Forge, ForgeTask};
-use ci_monitor_gitlab::gitlab;
-use ci_monitor_gitlab::GitlabForge;
-use ci_monitor_persistence::VecLookup;
-use clap::{Arg, ArgAction, Command};
-use governor::{Jitter, Quota, RateLimiter};
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
-
-async fn handle_tasks(
-    forge: Arc<GitlabForge<VecLookup>>,
-    send: UnboundedSender<ForgeTask>,
-    mut recv: UnboundedReceiver<ForgeTask>,
-) {
-    let mut count = 0;
-    let governor = RateLimiter::direct(Quota::per_second(NonZeroU32::new(50).unwrap()));
-    let jitter = Jitter::up_to(Duration::from_secs(2));
-
-    loop {
-        let mut tokio_tasks = Vec::new();
-
-        while let Some(task) = recv.recv().await {
-            governor.until_ready_with_jitter(jitter).await;
-
-            println!(
-                "performing task {} ({} remaining): {:?}",
-                count,
-                recv.len(),
-                task,
-            );
-            count += 1;
-
-            let inner_forge = forge.clone();
-            let inner_send = send.clone();
-            let async_task = tokio::spawn(async move {
-                let res = inner_forge.run_task_async(task).await;
-                match res {
-                    Ok(outcome) => {
-                        for task in outcome.additional_tasks {
-                            inner_send.send(task).unwrap();
-                        }
-                    },
-                    Err(err) => {
-                        println!("failed: {:?}", err);
-                    },
-                }
-            });
-
-            tokio_tasks.push(async_task);
-
-            let complete: Vec<_> = {
-                let task_inspection = mem::take(&mut tokio_tasks);
-                let (mut incomplete, complete) = task_inspection
-                    .into_iter()
-                    .partition(|task| task.is_finished());
-                mem::swap(&mut tokio_tasks, &mut incomplete);
-                complete
-            };
-
-            for tokio_task in complete {
-                tokio_task.await.unwrap();
-            }
-
-            if recv.is_empty() {
-                break;
-            }
-        }
-
-        for tokio_task in tokio_tasks {
-            tokio_task.await.unwrap();
-        }
-
-        if recv.is_empty() {
-            break;
-        }
-    }
-}
+mod blobs;
+mod commands;
+mod config;
+mod dashboard;
+mod entities;
+mod exit;
+mod federation;
+mod kv;
+mod output;
+mod search;
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+use crate::commands::Context;
+use crate::config::Config;
+use crate::exit::RunError;
 
 /// A `main` function which supports `try!`.
-async fn try_main() -> Result<(), Box<dyn Error>> {
-    let matches = Command::new("ci-monitor")
-        .version(clap::crate_version!())
-        .author("Ben Boeckel <ben.boeckel@kitware.com>")
-        .about("Monitor CI on a forge to store for further analysis")
-        .arg(
-            Arg::new("TOKEN")
-                .short('t')
-                .long("token")
-                .help("Token to use")
-                .action(ArgAction::Set),
-        )
-        .get_matches();
-
-    let token = matches.get_one::<String>("TOKEN").unwrap();
-    let gitlab = gitlab::GitlabBuilder::new("gitlab.kitware.com", token)
-        .build_async()
-        .await
-        .unwrap();
-    let storage = VecLookup::default();
-    let forge = GitlabForge::new("gitlab.kitware.com", gitlab, storage);
-    let forge = Arc::new(forge);
-
-    let (send, recv) = tokio::sync::mpsc::unbounded_channel();
-    send.send(ForgeTask::DiscoverRunners {}).unwrap();
-    send.send(ForgeTask::UpdateProject {
-        project: 13,
-    })
-    .unwrap();
-
-    handle_tasks(forge, send, recv).await;
+async fn try_main() -> Result<ExitCode, RunError> {
+    // The output of a command does not depend on its arguments, so required arguments may be
+    // omitted when asking for its schema.
+    let schema_matches = commands::cli().ignore_errors(true).get_matches();
+    if schema_matches.get_flag("SCHEMA") {
+        let command = commands::subcommand_path(&schema_matches);
+        commands::print_json(&output::schema_for(command.as_deref()))?;
+        return Ok(ExitCode::SUCCESS);
+    }
 
-    Ok(())
+    let matches = commands::cli().get_matches();
+
+    let quiet = matches.get_flag("QUIET");
+    let json = matches.get_one::<String>("OUTPUT").map(String::as_str) == Some("json");
+
+    // Task progress is logged unless output is suppressed; problems are always logged.
+    let progress = if quiet || json {
+        LevelFilter::WARN
+    } else {
+        LevelFilter::INFO
+    };
+    let mut targets = Targets::new()
+        .with_target("ci_monitor_forge", progress)
+        .with_target("ci_monitor_persistence", LevelFilter::WARN);
+    if matches.get_flag("LOG_DIFFS") {
+        targets = targets.with_target("ci_monitor_gitlab", LevelFilter::DEBUG);
+    }
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_writer(io::stderr))
+        .with(targets)
+        .init();
+
+    let config = if let Some(path) = matches.get_one::<String>("CONFIG") {
+        Config::load(Path::new(path))?
+    } else {
+        Config::default()
+    };
+
+    let ctx = Context {
+        signing_key: config.signing.signing_key()?,
+        store_path: matches.get_one::<String>("STORE").map(PathBuf::from),
+        metrics_path: matches.get_one::<String>("METRICS").map(PathBuf::from),
+        quiet,
+        json,
+        config,
+        matches,
+    };
+    commands::run(&ctx).await
 }
 
 #[tokio::main]
-async fn main() {
-    if let Err(err) = try_main().await {
-        panic!("{:?}", err);
+async fn main() -> ExitCode {
+    match try_main().await {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            err.exit_code()
+        },
     }
 }
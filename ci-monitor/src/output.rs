@@ -0,0 +1,658 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Machine-readable output of analysis commands.
+//!
+//! Analysis results are output as they are; the types here describe output which combines
+//! results or summarizes problems. Both are used to generate the schema of the output.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use ci_monitor_analysis::{
+    Alert, ApiUsageBucket, ArtifactGraph, ArtifactRetention, AutoscalerActivity, CombinedReport,
+    DeploymentIncident, EndOfLifeHost, EnvironmentDrift, ExperimentReport, FailureClusters,
+    FailureNotification, FailureRate, LogBackfill, MergeRequestLatency, PipelineDivergence,
+    PlatformCell, ProjectPolicyViolations, ProjectReleases, PushCoverage, QuarantineStatus,
+    QueueTimeBreakdown, RunnerHealth, RunnerSaturation, SectionTiming, ServiceReport,
+    TagRoutingReport, TimeToGreen, Timeline, UsageReconciliation, VariableChange,
+};
+use ci_monitor_core::data::AuditEntry;
+use ci_monitor_forge::{Heartbeat, RunReport};
+use ci_monitor_gitlab::TokenScopeReport;
+use ci_monitor_persistence::{ManifestProblem, SignatureProblem, SoftDeleteCounts};
+#[cfg(feature = "search")]
+use ci_monitor_persistence::{SearchHit, SearchIndexUpdate};
+use schemars::gen::SchemaSettings;
+use schemars::schema::{InstanceType, Metadata, ObjectValidation, RootSchema, SchemaObject};
+use schemars::{JsonSchema, Map};
+use serde::{Deserialize, Serialize};
+
+use crate::blobs::{BlobOwner, BlobProblem};
+
+/// A blob which failed verification.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct BlobProblemOutput {
+    /// The blob reference (`algorithm:hash`).
+    pub blob: String,
+    /// The kind of problem: `missing`, `corrupt`, or `unreadable`.
+    pub problem: &'static str,
+    /// Details of the problem.
+    pub details: String,
+    /// The artifacts referring to the blob.
+    pub owners: Vec<BlobOwner>,
+}
+
+impl From<&BlobProblem> for BlobProblemOutput {
+    fn from(problem: &BlobProblem) -> Self {
+        let blob = &problem.failure.blob;
+        Self {
+            blob: format!("{}:{}", blob.algo().name(), blob.hash()),
+            problem: problem.kind(),
+            details: problem.failure.error.to_string(),
+            owners: problem.owners.clone(),
+        }
+    }
+}
+
+/// A problem with the entity files of the store.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct StoreProblemOutput {
+    /// The entity type with the problem.
+    pub entity_type: &'static str,
+    /// The kind of problem: `missing-manifest`, `count-mismatch`, `missing`, `corrupt`, or
+    /// `extra`.
+    pub problem: &'static str,
+    /// Details of the problem.
+    pub details: String,
+}
+
+impl From<&ManifestProblem> for StoreProblemOutput {
+    fn from(problem: &ManifestProblem) -> Self {
+        let kind = match problem {
+            ManifestProblem::MissingManifest {
+                ..
+            } => "missing-manifest",
+            ManifestProblem::CountMismatch {
+                ..
+            } => "count-mismatch",
+            ManifestProblem::MissingFile {
+                ..
+            } => "missing",
+            ManifestProblem::ChecksumMismatch {
+                ..
+            } => "corrupt",
+            ManifestProblem::ExtraFile {
+                ..
+            } => "extra",
+            _ => "unknown",
+        };
+
+        Self {
+            entity_type: problem.entity_type().name(),
+            problem: kind,
+            details: problem.to_string(),
+        }
+    }
+}
+
+/// The result of verifying blobs.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct BlobVerificationOutput {
+    /// The number of blobs checked.
+    pub checked: usize,
+    /// Blobs which failed verification.
+    pub problems: Vec<BlobProblemOutput>,
+}
+
+/// A problem with the signatures of the store.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SignatureProblemOutput {
+    /// The signed file with the problem (if any).
+    pub file: Option<String>,
+    /// The kind of problem: `unsigned`, `key-mismatch`, `missing`, or `invalid`.
+    pub problem: &'static str,
+    /// Details of the problem.
+    pub details: String,
+}
+
+impl From<&SignatureProblem> for SignatureProblemOutput {
+    fn from(problem: &SignatureProblem) -> Self {
+        let (kind, file) = match problem {
+            SignatureProblem::Unsigned => ("unsigned", None),
+            SignatureProblem::KeyMismatch {
+                ..
+            } => ("key-mismatch", None),
+            SignatureProblem::MissingSignature {
+                file,
+            } => ("missing", Some(file.clone())),
+            SignatureProblem::InvalidSignature {
+                file,
+            } => ("invalid", Some(file.clone())),
+            _ => ("unknown", None),
+        };
+
+        Self {
+            file,
+            problem: kind,
+            details: problem.to_string(),
+        }
+    }
+}
+
+/// The result of auditing the store for tampering.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct AuditOutput {
+    /// Problems with the signatures of the store index and manifests.
+    pub signatures: Vec<SignatureProblemOutput>,
+    /// Entity files which do not match their manifests.
+    pub entities: Vec<StoreProblemOutput>,
+    /// The verification of blobs referenced by the store.
+    ///
+    /// Missing if no blob store is configured or entity files are damaged.
+    pub blobs: Option<BlobVerificationOutput>,
+}
+
+/// A manual action recorded in the store.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct AuditEntryOutput {
+    /// The ID of the entry.
+    pub id: u64,
+    /// When the action was taken.
+    pub at: DateTime<Utc>,
+    /// Who requested the action.
+    pub operator: String,
+    /// How the action was requested.
+    ///
+    /// One of `cli`, `webhook`, or `api`.
+    pub trigger: String,
+    /// The action which was taken.
+    pub action: String,
+    /// The entity the action was taken on.
+    pub target: String,
+    /// The names of the tasks injected for the action.
+    pub tasks: Vec<String>,
+    /// The number of entities changed (or tasks executed) by the action.
+    pub affected: u64,
+}
+
+impl From<&AuditEntry> for AuditEntryOutput {
+    fn from(entry: &AuditEntry) -> Self {
+        Self {
+            id: entry.unique_id,
+            at: entry.at,
+            operator: entry.operator.clone(),
+            trigger: entry.trigger.name().into(),
+            action: entry.action.clone(),
+            target: entry.target.clone(),
+            tasks: entry.tasks.clone(),
+            affected: entry.affected,
+        }
+    }
+}
+
+/// The result of soft-deleting (or restoring) an entity.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SoftDeleteOutput {
+    /// The kind of entity (`project` or `pipeline`).
+    pub kind: String,
+    /// The ID of the entity.
+    pub id: u64,
+    /// The number of entities affected in total.
+    pub total: usize,
+    /// The number of projects affected.
+    pub projects: usize,
+    /// The number of merge requests affected.
+    pub merge_requests: usize,
+    /// The number of pipeline schedules affected.
+    pub pipeline_schedules: usize,
+    /// The number of pipelines affected.
+    pub pipelines: usize,
+    /// The number of jobs affected.
+    pub jobs: usize,
+    /// The number of environments affected.
+    pub environments: usize,
+    /// The number of deployments affected.
+    pub deployments: usize,
+    /// The number of releases affected.
+    pub releases: usize,
+    /// The number of pushes affected.
+    pub pushes: usize,
+}
+
+impl SoftDeleteOutput {
+    /// The output for the counts of a soft deletion of an entity.
+    pub fn new(kind: &str, id: u64, counts: &SoftDeleteCounts) -> Self {
+        Self {
+            kind: kind.into(),
+            id,
+            total: counts.total(),
+            projects: counts.projects,
+            merge_requests: counts.merge_requests,
+            pipeline_schedules: counts.pipeline_schedules,
+            pipelines: counts.pipelines,
+            jobs: counts.jobs,
+            environments: counts.environments,
+            deployments: counts.deployments,
+            releases: counts.releases,
+            pushes: counts.pushes,
+        }
+    }
+}
+
+/// The result of merging a user into another user.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct UserMergeOutput {
+    /// The ID of the user which was merged.
+    pub from: u64,
+    /// The ID of the user it was merged into.
+    pub into: u64,
+    /// The number of references which were updated.
+    pub references: usize,
+}
+
+/// The result of merging instances stored under different URLs.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct InstanceMergeOutput {
+    /// The number of instances which were merged into another instance.
+    pub merged: usize,
+    /// The number of references which were updated.
+    pub references: usize,
+}
+
+/// The result of moving the entities of the store into another backend.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct StoreConvertOutput {
+    /// Where the entities are kept (`directory` or `kv`).
+    pub backend: String,
+    /// Whether the entities were moved (they are not if they were already kept there).
+    pub converted: bool,
+}
+
+/// The result of rehashing the blobs referenced by the store.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct BlobRehashOutput {
+    /// The algorithm the blobs were hashed with.
+    pub algorithm: String,
+    /// The number of blobs which were rehashed.
+    pub rehashed: usize,
+}
+
+/// The result of archiving job logs into the cold blob store.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct LogArchiveOutput {
+    /// Logs of jobs which finished before this time were archived.
+    pub before: DateTime<Utc>,
+    /// The number of logs which were archived.
+    pub archived: usize,
+}
+
+/// The result of exporting the store.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ExportOutput {
+    /// The file the store was exported to.
+    pub path: String,
+}
+
+/// The result of ingesting autoscaler events.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct AutoscalerIngestOutput {
+    /// The runner host the events belong to.
+    pub host: String,
+    /// The number of events read.
+    pub events: usize,
+    /// The number of events which were not already recorded.
+    pub recorded: usize,
+}
+
+/// The result of ingesting pipeline notification emails.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct EmailIngestOutput {
+    /// The host of the instance which sent the emails.
+    pub instance: String,
+    /// The number of emails read.
+    pub emails: usize,
+    /// The number of emails which were pipeline notifications.
+    pub ingested: usize,
+}
+
+/// The result of a backfill run.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct BackfillOutput {
+    /// Progress after the run.
+    pub progress: LogBackfill,
+    /// The report of the run.
+    pub report: RunReport,
+}
+
+/// The result of a pipeline reconciliation run.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ReconciliationOutput {
+    /// Divergence of each sampled project.
+    pub projects: Vec<PipelineDivergence>,
+    /// The report of the run.
+    pub report: RunReport,
+}
+
+/// The result of capturing a pipeline.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct CaptureOutput {
+    /// The host of the instance.
+    pub instance: String,
+    /// The path of the project.
+    pub project: String,
+    /// The ID of the pipeline.
+    pub pipeline: u64,
+    /// The directory the capture was written to.
+    pub path: String,
+    /// How many logs of the pipeline's jobs were captured.
+    pub logs: LogBackfill,
+    /// The report of the run.
+    pub report: RunReport,
+}
+
+/// A summary of a store published for a federating monitor.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct SiteSummaryOutput {
+    /// The name of the site.
+    pub name: String,
+    /// When the summary was generated.
+    pub generated_at: DateTime<Utc>,
+    /// The number of entities of each type (including soft-deleted entities).
+    pub entities: BTreeMap<String, usize>,
+    /// The health of the site's runners.
+    pub runners: RunnerHealth,
+    /// The failure rate of the site's pipelines.
+    pub pipelines: FailureRate,
+    /// The failure rate of the site's jobs.
+    pub jobs: FailureRate,
+    /// The last heartbeat of the site's monitor.
+    pub heartbeat: Option<Heartbeat>,
+}
+
+/// The state of a federated site.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SiteStatusOutput {
+    /// The name of the site.
+    pub name: String,
+    /// Where the site's summary is published.
+    pub url: String,
+    /// Why the summary could not be fetched.
+    pub error: Option<String>,
+    /// Whether the summary or the site's last heartbeat is older than the staleness threshold.
+    pub stale: bool,
+    /// The summary of the site.
+    pub summary: Option<SiteSummaryOutput>,
+}
+
+/// Summaries aggregated across federated sites.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct FederationReportOutput {
+    /// The state of each site.
+    pub sites: Vec<SiteStatusOutput>,
+    /// The number of entities of each type across reachable sites.
+    ///
+    /// Entities are not deduplicated; runners shared between sites are counted once per site.
+    pub entities: BTreeMap<String, usize>,
+    /// The health of runners across reachable sites.
+    pub runners: RunnerHealth,
+    /// The failure rate of pipelines across reachable sites.
+    pub pipelines: FailureRate,
+    /// The failure rate of jobs across reachable sites.
+    pub jobs: FailureRate,
+}
+
+impl FederationReportOutput {
+    /// The number of sites which could not be reached.
+    pub fn unreachable(&self) -> usize {
+        self.sites
+            .iter()
+            .filter(|site| site.error.is_some())
+            .count()
+    }
+}
+
+/// A pipeline as listed by the dashboard.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct DashboardPipelineOutput {
+    /// The ID of the pipeline.
+    pub id: u64,
+    /// The URL of the instance of the pipeline.
+    pub instance: String,
+    /// The path of the project.
+    pub project: String,
+    /// The ref which was built.
+    pub refname: Option<String>,
+    /// The commit which was built.
+    pub sha: String,
+    /// The status of the pipeline.
+    pub status: &'static str,
+    /// The URL of the pipeline.
+    pub url: String,
+    /// When the pipeline was created.
+    pub created_at: DateTime<Utc>,
+    /// When the pipeline finished.
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// A job as listed by the dashboard.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct DashboardJobOutput {
+    /// The ID of the job.
+    pub id: u64,
+    /// The name of the job.
+    pub name: String,
+    /// The stage of the job.
+    pub stage: String,
+    /// The state of the job.
+    pub state: &'static str,
+    /// Whether the job is allowed to fail.
+    pub allow_failure: bool,
+    /// The description of the runner which ran the job.
+    pub runner: Option<String>,
+    /// The URL of the job.
+    pub url: String,
+    /// When the job started.
+    pub started_at: Option<DateTime<Utc>>,
+    /// When the job finished.
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// A runner as listed by the dashboard.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct DashboardRunnerOutput {
+    /// The ID of the runner.
+    pub id: u64,
+    /// The URL of the instance of the runner.
+    pub instance: String,
+    /// The description of the runner.
+    pub description: String,
+    /// The host the runner runs on.
+    pub host: Option<String>,
+    /// The platform of the runner.
+    pub platform: String,
+    /// The architecture of the runner.
+    pub architecture: String,
+    /// The version of the runner.
+    pub version: String,
+    /// The tags of the runner.
+    pub tags: Vec<String>,
+    /// Whether the runner is online.
+    pub online: bool,
+    /// Whether the runner is paused.
+    pub paused: bool,
+    /// When the runner last contacted the instance.
+    pub contacted_at: Option<DateTime<Utc>>,
+}
+
+/// An entity found by a full-text search.
+#[cfg(feature = "search")]
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SearchHitOutput {
+    /// The kind of entity (`merge_request`, `pipeline`, or `job_failure`).
+    pub kind: &'static str,
+    /// The forge ID of the entity.
+    pub id: u64,
+    /// The path of the project of the entity.
+    pub project: String,
+    /// The title of the entity.
+    pub title: String,
+    /// The URL of the entity.
+    pub url: String,
+    /// How well the entity matches the query.
+    pub score: f32,
+}
+
+#[cfg(feature = "search")]
+impl From<&SearchHit> for SearchHitOutput {
+    fn from(hit: &SearchHit) -> Self {
+        Self {
+            kind: hit.kind.name(),
+            id: hit.id,
+            project: hit.project.clone(),
+            title: hit.title.clone(),
+            url: hit.url.clone(),
+            score: hit.score,
+        }
+    }
+}
+
+/// The changes made by updating the search index.
+#[cfg(feature = "search")]
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SearchIndexUpdateOutput {
+    /// The number of entities added to the index.
+    pub added: usize,
+    /// The number of entities which were reindexed because they changed.
+    pub updated: usize,
+    /// The number of entities removed from the index.
+    pub removed: usize,
+    /// The number of entities which did not change.
+    pub unchanged: usize,
+}
+
+#[cfg(feature = "search")]
+impl From<SearchIndexUpdate> for SearchIndexUpdateOutput {
+    fn from(update: SearchIndexUpdate) -> Self {
+        Self {
+            added: update.added,
+            updated: update.updated,
+            removed: update.removed,
+            unchanged: update.unchanged,
+        }
+    }
+}
+
+/// The JSON schema of the output of a command.
+///
+/// Commands with subcommands are given along with the subcommand (e.g., `store check`). `None`
+/// refers to a crawl.
+pub fn schema_for(command: Option<&str>) -> RootSchema {
+    match command {
+        Some("compare-variables") => schemars::schema_for!(Vec<VariableChange>),
+        Some("runner-saturation") => schemars::schema_for!(Vec<RunnerSaturation>),
+        Some("check-token") => schemars::schema_for!(TokenScopeReport),
+        Some("blobs verify") => schemars::schema_for!(BlobVerificationOutput),
+        Some("blobs rehash") => schemars::schema_for!(BlobRehashOutput),
+        Some("blobs archive") => schemars::schema_for!(LogArchiveOutput),
+        Some("store check" | "store repair") => schemars::schema_for!(Vec<StoreProblemOutput>),
+        Some("store delete" | "store undelete") => schemars::schema_for!(SoftDeleteOutput),
+        Some("store merge-users") => schemars::schema_for!(UserMergeOutput),
+        Some("store merge-instances") => schemars::schema_for!(InstanceMergeOutput),
+        Some("store convert") => schemars::schema_for!(StoreConvertOutput),
+        Some("export duckdb") => schemars::schema_for!(ExportOutput),
+        Some("ingest-autoscaler") => schemars::schema_for!(AutoscalerIngestOutput),
+        Some("ingest-email") => schemars::schema_for!(EmailIngestOutput),
+        Some("dashboard") => dashboard_schema(),
+        Some("audit") => schemars::schema_for!(AuditOutput),
+        Some("audit-log") => schemars::schema_for!(Vec<AuditEntryOutput>),
+        Some("backfill-logs") => schemars::schema_for!(BackfillOutput),
+        Some("reconcile-pipelines") => schemars::schema_for!(ReconciliationOutput),
+        Some("capture pipeline") => schemars::schema_for!(CaptureOutput),
+        Some("failure-clusters") => schemars::schema_for!(FailureClusters),
+        Some("artifact-graph") => schemars::schema_for!(ArtifactGraph),
+        Some("check-policy") => schemars::schema_for!(Vec<ProjectPolicyViolations>),
+        Some("releases") => schemars::schema_for!(Vec<ProjectReleases>),
+        Some("combined-report") => schemars::schema_for!(CombinedReport),
+        Some("notifications") => schemars::schema_for!(Vec<FailureNotification>),
+        Some("alerts") => schemars::schema_for!(Vec<Alert>),
+        Some("incidents") => schemars::schema_for!(Vec<DeploymentIncident>),
+        Some("drift") => schemars::schema_for!(Vec<EnvironmentDrift>),
+        Some("experiment") => schemars::schema_for!(ExperimentReport),
+        Some("merge-latency") => schemars::schema_for!(Vec<MergeRequestLatency>),
+        Some("time-to-green") => schemars::schema_for!(TimeToGreen),
+        Some("push-coverage") => schemars::schema_for!(PushCoverage),
+        Some("platform-matrix") => schemars::schema_for!(Vec<PlatformCell>),
+        Some("os-eol") => schemars::schema_for!(Vec<EndOfLifeHost>),
+        Some("quarantine") => schemars::schema_for!(Vec<QuarantineStatus>),
+        Some("services") => schemars::schema_for!(Vec<ServiceReport>),
+        Some("section-timings") => schemars::schema_for!(Vec<SectionTiming>),
+        Some("queue-time") => schemars::schema_for!(Vec<QueueTimeBreakdown>),
+        Some("autoscaler") => schemars::schema_for!(Vec<AutoscalerActivity>),
+        Some("artifact-retention") => schemars::schema_for!(ArtifactRetention),
+        Some("timeline") => schemars::schema_for!(Timeline),
+        Some("api-usage") => schemars::schema_for!(Vec<ApiUsageBucket>),
+        Some("actions-usage") => schemars::schema_for!(Vec<UsageReconciliation>),
+        Some("tag-routing") => schemars::schema_for!(TagRoutingReport),
+        Some("summary") => schemars::schema_for!(SiteSummaryOutput),
+        Some("federate") => schemars::schema_for!(FederationReportOutput),
+        #[cfg(feature = "search")]
+        Some("search-index") => schemars::schema_for!(SearchIndexUpdateOutput),
+        #[cfg(feature = "search")]
+        Some("search") => schemars::schema_for!(Vec<SearchHitOutput>),
+        _ => schemars::schema_for!(RunReport),
+    }
+}
+
+/// The JSON schema of the responses of the dashboard's API.
+///
+/// The dashboard serves its data rather than printing it, so the schema describes an object
+/// with the response of each endpoint under its path.
+fn dashboard_schema() -> RootSchema {
+    let mut gen = SchemaSettings::draft07().into_generator();
+    let mut endpoints = Map::new();
+    endpoints.insert(
+        "/api/summary".into(),
+        gen.subschema_for::<SiteSummaryOutput>(),
+    );
+    endpoints.insert(
+        "/api/pipelines".into(),
+        gen.subschema_for::<Vec<DashboardPipelineOutput>>(),
+    );
+    endpoints.insert(
+        "/api/pipelines/{id}/jobs".into(),
+        gen.subschema_for::<Vec<DashboardJobOutput>>(),
+    );
+    endpoints.insert(
+        "/api/runners".into(),
+        gen.subschema_for::<Vec<DashboardRunnerOutput>>(),
+    );
+    endpoints.insert(
+        "/api/drift".into(),
+        gen.subschema_for::<Vec<EnvironmentDrift>>(),
+    );
+    #[cfg(feature = "search")]
+    endpoints.insert(
+        "/api/search".into(),
+        gen.subschema_for::<Vec<SearchHitOutput>>(),
+    );
+
+    let schema = SchemaObject {
+        metadata: Some(Box::new(Metadata {
+            title: Some("DashboardApi".into()),
+            ..Default::default()
+        })),
+        instance_type: Some(InstanceType::Object.into()),
+        object: Some(Box::new(ObjectValidation {
+            properties: endpoints,
+            ..Default::default()
+        })),
+        ..Default::default()
+    };
+    let mut root = gen.into_root_schema_for::<()>();
+    root.schema = schema;
+    root
+}
@@ -0,0 +1,130 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Full-text search over the store.
+//!
+//! The search index is kept in the `search` directory of the store. It is only built on request;
+//! once it exists, it is updated whenever the store is saved. Requires the `search` feature.
+
+use std::path::Path;
+#[cfg(feature = "search")]
+use std::path::PathBuf;
+
+use ci_monitor_persistence::VecLookup;
+#[cfg(feature = "search")]
+use ci_monitor_persistence::{SearchIndex, SearchKind};
+
+#[cfg(feature = "search")]
+use crate::commands::print_json;
+use crate::exit::RunError;
+#[cfg(feature = "search")]
+use crate::output::{SearchHitOutput, SearchIndexUpdateOutput};
+
+/// The directory of the search index within a store.
+#[cfg(feature = "search")]
+const SEARCH_INDEX_NAME: &str = "search";
+
+#[cfg(feature = "search")]
+fn index_path(path: &Path) -> PathBuf {
+    path.join(SEARCH_INDEX_NAME)
+}
+
+/// Build or update the search index of a store.
+#[cfg(feature = "search")]
+pub fn build_index(
+    path: &Path,
+    storage: &VecLookup,
+    json: bool,
+    quiet: bool,
+) -> Result<(), RunError> {
+    let update = SearchIndex::open(&index_path(path))?.update(storage)?;
+
+    if json {
+        print_json(&SearchIndexUpdateOutput::from(update))?;
+    } else if !quiet {
+        println!(
+            "indexed {} new and {} changed entities; removed {} ({} unchanged)",
+            update.added, update.updated, update.removed, update.unchanged,
+        );
+    }
+
+    Ok(())
+}
+
+/// Build or update the search index of a store.
+#[cfg(not(feature = "search"))]
+pub fn build_index(_: &Path, _: &VecLookup, _: bool, _: bool) -> Result<(), RunError> {
+    Err(RunError::NoSearch)
+}
+
+/// Update the search index of a store if it has one.
+#[cfg(feature = "search")]
+pub fn update_index(path: &Path, storage: &VecLookup) -> Result<(), RunError> {
+    let index_path = index_path(path);
+    if index_path.is_dir() {
+        SearchIndex::open(&index_path)?.update(storage)?;
+    }
+
+    Ok(())
+}
+
+/// Update the search index of a store if it has one.
+///
+/// The index cannot be updated without the `search` feature; it catches up with the store the
+/// next time it is updated.
+#[cfg(not(feature = "search"))]
+pub fn update_index(_: &Path, _: &VecLookup) -> Result<(), RunError> {
+    Ok(())
+}
+
+/// Search the index of a store.
+///
+/// The `kind` is the name of a kind of entity to restrict the search to.
+#[cfg(feature = "search")]
+pub fn search(
+    path: &Path,
+    query: &str,
+    kind: Option<&str>,
+    limit: usize,
+) -> Result<Vec<SearchHitOutput>, RunError> {
+    let index_path = index_path(path);
+    if !index_path.is_dir() {
+        return Err(RunError::NoSearchIndex);
+    }
+    let kind = kind.and_then(SearchKind::from_name);
+    let hits = SearchIndex::open(&index_path)?.search(query, kind, limit)?;
+
+    Ok(hits.iter().map(Into::into).collect())
+}
+
+/// Search the index of a store and print the results.
+#[cfg(feature = "search")]
+pub fn print_search(
+    path: &Path,
+    query: &str,
+    kind: Option<&str>,
+    limit: usize,
+    json: bool,
+) -> Result<(), RunError> {
+    let hits = search(path, query, kind, limit)?;
+
+    if json {
+        print_json(&hits)?;
+    } else {
+        for hit in hits {
+            println!("{} #{} in {}: {}", hit.kind, hit.id, hit.project, hit.title);
+            println!("  {}", hit.url);
+        }
+    }
+
+    Ok(())
+}
+
+/// Search the index of a store and print the results.
+#[cfg(not(feature = "search"))]
+pub fn print_search(_: &Path, _: &str, _: Option<&str>, _: usize, _: bool) -> Result<(), RunError> {
+    Err(RunError::NoSearch)
+}